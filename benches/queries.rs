@@ -0,0 +1,163 @@
+//! Times the trade queries on a large database, with the trade indexes and
+//! again after dropping them.
+//!
+//! ```text
+//! cargo bench --bench queries            # 100,000 trades
+//! cargo bench --bench queries -- 20000   # or any other count
+//! ```
+
+use options_tracker::cost_basis::CostBasisMethod;
+use options_tracker::date::DateRange;
+use options_tracker::db::{
+    Account, Action, Database, Trade, TradeFilter, TradeType, DEFAULT_CURRENCY,
+};
+use rusqlite::Connection;
+use rust_decimal::Decimal;
+use std::time::{Duration, Instant};
+
+const SYMBOLS: usize = 500;
+const ACCOUNTS: usize = 3;
+
+fn main() {
+    let count: usize = std::env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse().ok())
+        .unwrap_or(100_000);
+    let path =
+        std::env::temp_dir().join(format!("options_tracker_bench_{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let name = path.to_string_lossy().into_owned();
+
+    let mut db = Database::new(&name).expect("open the database");
+    let started = Instant::now();
+    fill(&db, count);
+    println!("Wrote {} trades in {:.1?}\n", count, started.elapsed());
+
+    let conn = Connection::open(&path).expect("open a second connection");
+    println!("Plan for one account's trades:");
+    for line in plan(&conn, "account_id = 1") {
+        println!("  {}", line);
+    }
+    println!();
+
+    let indexed = run(&mut db);
+    conn.execute_batch(
+        "DROP INDEX trades_symbol; DROP INDEX trades_date; DROP INDEX trades_account_date;",
+    )
+    .expect("drop the indexes");
+    // Reopen so no statement planned with the indexes is reused.
+    drop(db);
+    let mut db = Database::new(&name).expect("reopen the database");
+    let unindexed = run(&mut db);
+
+    println!("{:<36} {:>12} {:>12}", "query", "indexed", "no indexes");
+    for ((query, with), (_, without)) in indexed.iter().zip(&unindexed) {
+        println!("{:<36} {:>12.1?} {:>12.1?}", query, with, without);
+    }
+    drop(db);
+    let _ = std::fs::remove_file(&path);
+}
+
+// Adds `count` stock trades spread over the symbols, accounts, and five years.
+fn fill(db: &Database, count: usize) {
+    let accounts: Vec<i64> = (0..ACCOUNTS)
+        .map(|n| {
+            db.add_account(&Account {
+                id: None,
+                name: format!("Account {}", n + 1),
+                currency: DEFAULT_CURRENCY.to_string(),
+            })
+            .expect("add an account")
+        })
+        .collect();
+    let trades: Vec<Trade> = (0..count)
+        .map(|n| Trade {
+            symbol: format!("SYM{:03}", n % SYMBOLS),
+            trade_type: TradeType::Stock,
+            action: if n % 3 == 2 {
+                Action::SellToClose
+            } else {
+                Action::BuyToOpen
+            },
+            price: Decimal::new(1_000 + (n % 9_000) as i64, 2),
+            quantity: Decimal::from(10),
+            date: format!(
+                "{}-{:02}-{:02}",
+                2020 + n % 5,
+                1 + n / 7 % 12,
+                1 + n / 3 % 28
+            ),
+            account_id: Some(accounts[n % ACCOUNTS]),
+            ..Default::default()
+        })
+        .collect();
+    db.add_trades_with_strategies(&trades, &[])
+        .expect("add the trades");
+}
+
+// The query plan SQLite picks for reading trades matching `filter`.
+fn plan(conn: &Connection, filter: &str) -> Vec<String> {
+    let sql = format!(
+        "EXPLAIN QUERY PLAN SELECT * FROM trades WHERE deleted_at IS NULL AND {} \
+         ORDER BY date DESC, id DESC",
+        filter
+    );
+    let mut stmt = conn.prepare(&sql).expect("plan the query");
+    let lines = stmt
+        .query_map([], |row| row.get::<_, String>(3))
+        .expect("read the plan");
+    lines.map(|line| line.expect("read a plan row")).collect()
+}
+
+// Times each query, best of three.
+fn run(db: &mut Database) -> Vec<(&'static str, Duration)> {
+    let range = DateRange {
+        start: Some("2024-03-01".to_string()),
+        end: Some("2024-03-31".to_string()),
+    };
+    let mut timings = Vec::new();
+    let mut time = |query: &'static str, db: &mut Database, f: &dyn Fn(&Database)| {
+        let best = (0..3)
+            .map(|_| {
+                let started = Instant::now();
+                f(db);
+                started.elapsed()
+            })
+            .min()
+            .unwrap_or_default();
+        timings.push((query, best));
+    };
+
+    time("all trades", db, &|db| {
+        db.get_all_trades().expect("all trades");
+    });
+    time("first page of trades", db, &|db| {
+        db.query_trades(&TradeFilter::default(), Some(100), 0)
+            .expect("page");
+    });
+    time("one symbol's trades", db, &|db| {
+        db.get_trades_by_symbol("SYM042").expect("symbol");
+    });
+    time("trades in one month", db, &|db| {
+        db.get_trades_in(&range).expect("month");
+    });
+    time("break-even of 20 symbols", db, &|db| {
+        for n in 0..20 {
+            db.get_break_even(&format!("SYM{:03}", n))
+                .expect("break-even");
+        }
+    });
+    time("report by symbol", db, &|db| {
+        db.get_report_by_symbol(CostBasisMethod::default(), &DateRange::default())
+            .expect("report");
+    });
+    db.set_account(Some(1));
+    time("one account's trades", db, &|db| {
+        db.get_all_trades().expect("account");
+    });
+    time("one account's month", db, &|db| {
+        db.get_trades_in(&range).expect("account month");
+    });
+    db.set_account(None);
+    timings
+}
@@ -1,3 +1,5 @@
+use options_tracker::cost_basis::CostBasisMethod;
+use options_tracker::date::DateRange;
 use options_tracker::db::{Action, Database, OptionStatus, OptionType, Trade, TradeType};
 use rust_decimal_macros::dec;
 
@@ -77,7 +79,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n✓ Updated trade successfully");
 
     // Generate report
-    let reports = db.get_report_by_symbol()?;
+    let reports = db.get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())?;
     println!("\n✓ Generated reports for {} symbols:", reports.len());
     for report in reports {
         println!(
@@ -0,0 +1,176 @@
+//! Price and P/L alerts.
+//!
+//! An [`Alert`] watches one instrument's mark (`price_below`/`price_above`) or
+//! the unrealized P/L of the open position in it, as a percent of its cost
+//! basis (`pl_below`/`pl_above`). Alerts are checked whenever marks change;
+//! one that is met is stamped as triggered and stays so until re-armed, so it
+//! fires once rather than on every refresh.
+
+use crate::date::format_timestamp;
+use crate::db::{Alert, AlertKind, Database, Quote};
+use crate::positions::{open_positions, Position};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// The value an alert compares against its threshold: the instrument's mark,
+/// or the P/L percent of its open position. `None` without a mark, or for a
+/// P/L alert on an instrument that is not held.
+pub fn current_value(
+    alert: &Alert,
+    positions: &[Position],
+    quotes: &HashMap<String, Quote>,
+) -> Option<Decimal> {
+    let mark = quotes.get(&alert.instrument)?.price;
+    if !alert.kind.is_percent() {
+        return Some(mark);
+    }
+    let held: Vec<&Position> = positions
+        .iter()
+        .filter(|p| p.key.instrument() == alert.instrument)
+        .collect();
+    let cost: Decimal = held.iter().map(|p| p.cost_basis()).sum();
+    if cost == Decimal::ZERO {
+        return None;
+    }
+    let unrealized: Decimal = held.iter().map(|p| p.unrealized(mark)).sum();
+    Some((unrealized / cost * Decimal::ONE_HUNDRED).round_dp(2))
+}
+
+/// Whether `value` meets the alert's threshold.
+pub fn is_met(kind: AlertKind, threshold: Decimal, value: Decimal) -> bool {
+    match kind {
+        AlertKind::PriceBelow | AlertKind::PlBelow => value < threshold,
+        AlertKind::PriceAbove | AlertKind::PlAbove => value > threshold,
+    }
+}
+
+/// Checks every armed alert against the current marks and positions, stamps
+/// the ones that are met as triggered at Unix time `now`, and returns them.
+pub fn check_alerts(db: &Database, today: &str, now: i64) -> rusqlite::Result<Vec<Alert>> {
+    let alerts = db.get_alerts()?;
+    if alerts.iter().all(|a| a.triggered_at.is_some()) {
+        return Ok(Vec::new());
+    }
+    let positions = open_positions(&db.get_all_trades()?, today);
+    let quotes = db.get_quotes()?;
+    let triggered_at = format_timestamp(now);
+
+    let mut triggered = Vec::new();
+    for alert in alerts {
+        if alert.triggered_at.is_some() {
+            continue;
+        }
+        let value = match current_value(&alert, &positions, &quotes) {
+            Some(value) if is_met(alert.kind, alert.threshold, value) => value,
+            _ => continue,
+        };
+        if let Some(id) = alert.id {
+            db.set_alert_triggered(id, Some((&triggered_at, value)))?;
+        }
+        triggered.push(Alert {
+            triggered_at: Some(triggered_at.clone()),
+            triggered_value: Some(value),
+            ..alert
+        });
+    }
+    Ok(triggered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::parse_timestamp;
+    use crate::db::{Action, Trade};
+    use rust_decimal_macros::dec;
+
+    fn alert(instrument: &str, kind: AlertKind, threshold: Decimal) -> Alert {
+        Alert {
+            id: None,
+            instrument: instrument.to_string(),
+            kind,
+            threshold,
+            triggered_at: None,
+            triggered_value: None,
+        }
+    }
+
+    fn set_mark(db: &Database, instrument: &str, price: Decimal) {
+        db.set_quote(&Quote {
+            instrument: instrument.to_string(),
+            price,
+            fetched_at: "2024-06-03 15:00:00".to_string(),
+            source: "manual".to_string(),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn price_and_pl_alerts_trigger_once() {
+        let db = Database::new(":memory:").unwrap();
+        db.add_trade(&Trade {
+            symbol: "AAPL".to_string(),
+            action: Action::BuyToOpen,
+            price: dec!(200),
+            quantity: dec!(10),
+            date: "2024-05-01".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        db.add_alert(&alert("AAPL", AlertKind::PriceBelow, dec!(170)))
+            .unwrap();
+        db.add_alert(&alert("AAPL", AlertKind::PlBelow, dec!(-10)))
+            .unwrap();
+        db.add_alert(&alert("MSFT", AlertKind::PriceAbove, dec!(400)))
+            .unwrap();
+        let now = parse_timestamp("2024-06-03 15:00:00").unwrap();
+
+        // Down 7.5%: neither AAPL alert is met yet, and MSFT has no mark.
+        set_mark(&db, "AAPL", dec!(185));
+        assert!(check_alerts(&db, "2024-06-03", now).unwrap().is_empty());
+
+        // Down 12.5%: the P/L alert fires, the price alert still waits.
+        set_mark(&db, "AAPL", dec!(175));
+        let fired = check_alerts(&db, "2024-06-03", now).unwrap();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].kind, AlertKind::PlBelow);
+        assert_eq!(fired[0].triggered_value, Some(dec!(-12.5)));
+
+        // Already triggered alerts do not fire again.
+        set_mark(&db, "AAPL", dec!(160));
+        let fired = check_alerts(&db, "2024-06-03", now).unwrap();
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].kind, AlertKind::PriceBelow);
+        let stored = db.get_alerts().unwrap();
+        assert_eq!(
+            stored[0].triggered_at.as_deref(),
+            Some("2024-06-03 15:00:00")
+        );
+
+        // Re-arming lets it fire again.
+        db.set_alert_triggered(stored[0].id.unwrap(), None).unwrap();
+        assert_eq!(check_alerts(&db, "2024-06-03", now).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn short_position_pl_uses_proceeds() {
+        let db = Database::new(":memory:").unwrap();
+        db.add_trade(&Trade {
+            symbol: "TSLA".to_string(),
+            action: Action::SellToOpen,
+            price: dec!(100),
+            quantity: dec!(10),
+            date: "2024-05-01".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        set_mark(&db, "TSLA", dec!(130));
+        let positions = open_positions(&db.get_all_trades().unwrap(), "2024-06-03");
+        let quotes = db.get_quotes().unwrap();
+        let value = current_value(
+            &alert("TSLA", AlertKind::PlBelow, dec!(-20)),
+            &positions,
+            &quotes,
+        );
+        assert_eq!(value, Some(dec!(-30)));
+    }
+}
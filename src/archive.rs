@@ -0,0 +1,263 @@
+//! Archiving the trades of closed years.
+//!
+//! After years of trading, most trades belong to positions long since closed,
+//! yet every list and report still reads them. Archiving moves the trades of
+//! every year up to a closed one out of the `trades` table into
+//! `archived_trades`, leaving per year, account, and symbol an
+//! [`ArchiveSummary`] of their count, cash flow, fees, and realized P/L.
+//!
+//! A year is closed when every position is flat at its end and no later
+//! trade is tied to an earlier one — an assignment's stock, a roll, a
+//! strategy's legs, or a loss sale and the buy that washes it — so matching
+//! the lots of the trades left finds nothing missing and no wash-sale
+//! adjustment is lost. Only years before the current one are archived, and always every
+//! year up to the one chosen, so the archive holds the oldest trades.
+
+use crate::cost_basis::{match_lots, CostBasisMethod, LotMatch, LotOverride};
+use crate::date::DateRange;
+use crate::db::{ArchiveSummary, PositionKey, Trade};
+use crate::wash_sale::wash_sales;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The year of a `YYYY-MM-DD` date.
+pub fn year_of(date: &str) -> Option<i32> {
+    date.get(..4)?.parse().ok()
+}
+
+/// The years before `this_year` that `trades` can be archived through,
+/// oldest first, with `overrides` the manual lot matches.
+pub fn closed_years(trades: &[Trade], overrides: &[LotOverride], this_year: i32) -> Vec<i32> {
+    let year = |trade: &Trade| year_of(&trade.date).unwrap_or(this_year);
+
+    // Years from the first to the last trade tied together by a strategy, a
+    // roll chain, or an assignment; no year in between is closed.
+    let mut spans: BTreeMap<(&str, i64), (i32, i32)> = BTreeMap::new();
+    let mut widen = |group: (&'static str, i64), year: i32| {
+        let span = spans.entry(group).or_insert((year, year));
+        *span = (span.0.min(year), span.1.max(year));
+    };
+    let by_id: HashMap<i64, &Trade> = trades.iter().filter_map(|t| Some((t.id?, t))).collect();
+    for trade in trades {
+        if let Some(id) = trade.strategy_id {
+            widen(("strategy", id), year(trade));
+        }
+        if let Some(id) = trade.roll_group_id {
+            widen(("roll", id), year(trade));
+        }
+        if let Some(option) = trade.assigned_from.and_then(|id| by_id.get(&id)) {
+            widen(("assignment", trade.id.unwrap_or_default()), year(trade));
+            widen(("assignment", trade.id.unwrap_or_default()), year(option));
+        }
+    }
+
+    // A loss sale washed by a buy in another year: archiving the sale's year
+    // would lose the adjustment to the replacement's basis. Which sales are
+    // losses depends on how lots match, so every method is checked.
+    let mut washes: Vec<(i32, i32)> = Vec::new();
+    for method in CostBasisMethod::variants() {
+        let matches = match_lots(trades, *method, overrides);
+        for wash in wash_sales(trades, &matches) {
+            let sold = year_of(&wash.close_date).unwrap_or(this_year);
+            let bought = year_of(&wash.replacement_date).unwrap_or(this_year);
+            washes.push((sold.min(bought), sold.max(bought)));
+        }
+    }
+
+    let mut by_year: BTreeMap<i32, Vec<&Trade>> = BTreeMap::new();
+    for trade in trades {
+        by_year.entry(year(trade)).or_default().push(trade);
+    }
+    let mut open: HashMap<PositionKey, (Decimal, Decimal)> = HashMap::new();
+    let mut closed = Vec::new();
+    for (year, trades) in by_year.range(..this_year) {
+        for trade in trades {
+            let (long, short) = open.entry(trade.position_key()).or_default();
+            *long += trade.open_quantity_change(false);
+            *short += trade.open_quantity_change(true);
+        }
+        let flat = open
+            .values()
+            .all(|(long, short)| *long <= Decimal::ZERO && *short <= Decimal::ZERO);
+        let tied = spans
+            .values()
+            .chain(&washes)
+            .any(|(first, last)| first <= year && year < last);
+        if flat && !tied {
+            closed.push(*year);
+        }
+    }
+    closed
+}
+
+/// Per year, account, and symbol: the count, cash flow, and fees of
+/// `trades`, and the P/L of the lots `matches` closed that year.
+pub fn summarize(trades: &[Trade], matches: &[LotMatch]) -> Vec<ArchiveSummary> {
+    let mut summaries: BTreeMap<(i32, Option<i64>, String), ArchiveSummary> = BTreeMap::new();
+    for trade in trades {
+        if let Some(year) = year_of(&trade.date) {
+            let entry = summary(&mut summaries, year, trade.account_id, &trade.symbol);
+            entry.trades += 1;
+            entry.cash_flow += trade.cash_flow();
+            entry.fees += trade.fees;
+        }
+    }
+    for lot in matches {
+        if let Some(year) = year_of(&lot.close_date) {
+            summary(&mut summaries, year, lot.key.account_id, &lot.key.symbol).realized +=
+                lot.realized;
+        }
+    }
+    summaries.into_values().collect()
+}
+
+// The summary of one year, account, and symbol, from zero the first time.
+fn summary<'a>(
+    summaries: &'a mut BTreeMap<(i32, Option<i64>, String), ArchiveSummary>,
+    year: i32,
+    account_id: Option<i64>,
+    symbol: &str,
+) -> &'a mut ArchiveSummary {
+    summaries
+        .entry((year, account_id, symbol.to_string()))
+        .or_insert_with(|| ArchiveSummary {
+            year,
+            account_id,
+            symbol: symbol.to_string(),
+            trades: 0,
+            cash_flow: Decimal::ZERO,
+            fees: Decimal::ZERO,
+            realized: Decimal::ZERO,
+        })
+}
+
+/// A note for a report over `range` that it leaves out the years archived,
+/// through `archived_through`; `None` when the range starts after them.
+pub fn archive_note(archived_through: Option<i32>, range: &DateRange) -> Option<String> {
+    let year = archived_through?;
+    let last_day = format!("{}-12-31", year);
+    if range
+        .start
+        .as_deref()
+        .is_some_and(|start| start > last_day.as_str())
+    {
+        return None;
+    }
+    Some(format!("excludes archived years through {}", year))
+}
+
+/// The distinct years of `summaries`, oldest first.
+pub fn archived_years(summaries: &[ArchiveSummary]) -> Vec<i32> {
+    let years: BTreeSet<i32> = summaries.iter().map(|s| s.year).collect();
+    years.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionStatus, OptionType, TradeType};
+    use rust_decimal_macros::dec;
+
+    fn stock(id: i64, action: Action, price: Decimal, date: &str) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            action,
+            price,
+            quantity: dec!(10),
+            date: date.to_string(),
+            fees: dec!(1),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_year_is_closed_once_every_position_is_flat() {
+        let trades = vec![
+            stock(1, Action::BuyToOpen, dec!(100), "2021-03-01"),
+            stock(2, Action::SellToClose, dec!(110), "2021-06-01"),
+            // Held over the end of 2022.
+            stock(3, Action::BuyToOpen, dec!(120), "2022-11-01"),
+            stock(4, Action::SellToClose, dec!(115), "2023-01-10"),
+            stock(5, Action::BuyToOpen, dec!(130), "2024-02-01"),
+        ];
+        assert_eq!(closed_years(&trades, &[], 2025), [2021, 2023]);
+        // The current year is never closed.
+        assert_eq!(closed_years(&trades[..4], &[], 2023), [2021]);
+    }
+
+    #[test]
+    fn linked_trades_keep_their_years_open() {
+        let put = Trade {
+            id: Some(1),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            price: dec!(2),
+            quantity: dec!(1),
+            date: "2022-12-01".to_string(),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(150)),
+            expiration: Some("2022-12-30".to_string()),
+            status: Some(OptionStatus::Assigned),
+            ..Default::default()
+        };
+        // Assigned shares dated after the year the put was sold, then sold.
+        let shares = Trade {
+            assigned_from: Some(1),
+            quantity: dec!(100),
+            ..stock(2, Action::BuyToOpen, dec!(150), "2023-01-03")
+        };
+        let sale = Trade {
+            quantity: dec!(100),
+            ..stock(3, Action::SellToClose, dec!(155), "2023-02-01")
+        };
+        assert_eq!(closed_years(&[put, shares, sale], &[], 2024), [2023]);
+    }
+
+    #[test]
+    fn a_loss_washed_the_next_year_keeps_its_year_open() {
+        let trades = vec![
+            stock(1, Action::BuyToOpen, dec!(100), "2022-06-01"),
+            // Sold at a loss and bought back within 30 days, over New Year.
+            stock(2, Action::SellToClose, dec!(90), "2022-12-20"),
+            stock(3, Action::BuyToOpen, dec!(92), "2023-01-05"),
+            stock(4, Action::SellToClose, dec!(95), "2023-02-01"),
+        ];
+        assert_eq!(closed_years(&trades, &[], 2024), [2023]);
+        // Bought back after the window, the loss stands and 2022 is closed.
+        let mut late = trades.clone();
+        late[2].date = "2023-01-25".to_string();
+        assert_eq!(closed_years(&late, &[], 2024), [2022, 2023]);
+    }
+
+    #[test]
+    fn summarizes_per_year_and_symbol() {
+        let trades = vec![
+            stock(1, Action::BuyToOpen, dec!(100), "2021-12-01"),
+            stock(2, Action::SellToClose, dec!(110), "2022-01-05"),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let summaries = summarize(&trades, &matches);
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[0].year, 2021);
+        assert_eq!(summaries[0].cash_flow, dec!(-1001));
+        assert_eq!(summaries[0].realized, dec!(0));
+        // $100 less a dollar of fees each way.
+        assert_eq!(summaries[1].realized, dec!(98));
+        assert_eq!(summaries[1].fees, dec!(1));
+        assert_eq!(archived_years(&summaries), [2021, 2022]);
+
+        let from = |start: &str| DateRange {
+            start: Some(start.to_string()),
+            end: None,
+        };
+        assert_eq!(
+            archive_note(Some(2022), &DateRange::default()).as_deref(),
+            Some("excludes archived years through 2022")
+        );
+        assert!(archive_note(Some(2022), &from("2022-12-31")).is_some());
+        assert_eq!(archive_note(Some(2022), &from("2023-01-01")), None);
+        assert_eq!(archive_note(None, &DateRange::default()), None);
+    }
+}
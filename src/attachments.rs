@@ -0,0 +1,321 @@
+//! Files attached to trades.
+//!
+//! A chart screenshot or broker confirmation is attached either by linking
+//! its path, which keeps the database small but breaks if the file moves, or
+//! by copying it into the database, so it travels with snapshots and backups.
+//! Opening one hands the file to the system's opener (`xdg-open`, `open`, or
+//! `explorer`); a stored file is written out first to a temporary directory
+//! of the process's own, which `remove_temporary` deletes on the way out.
+
+use crate::db::Attachment;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The directory stored attachments are written out to, once one has been.
+static TEMPORARY: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+// Counts the files written out, so opening one twice never reuses a file the
+// first opener may still have open.
+static WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+/// An attachment of the file at `path` to a trade, linking the file or, with
+/// `store`, holding a copy of it.
+pub fn attach_file(trade_id: i64, path: &Path, store: bool) -> Result<Attachment, String> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| format!("{} is not a file", path.display()))?;
+    let (path, data) = if store {
+        let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        (None, Some(data))
+    } else {
+        // Absolute, so it still opens from another working directory.
+        let path = fs::canonicalize(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        if !path.is_file() {
+            return Err(format!("{} is not a file", path.display()));
+        }
+        (Some(path.to_string_lossy().into_owned()), None)
+    };
+    Ok(Attachment {
+        id: None,
+        trade_id,
+        name,
+        path,
+        data,
+        added_at: String::new(),
+    })
+}
+
+/// Where the attachment is: the linked path, or the size of the stored copy.
+pub fn location(attachment: &Attachment) -> String {
+    match (&attachment.path, &attachment.data) {
+        (Some(path), _) => path.clone(),
+        (None, Some(data)) if data.len() >= 1024 * 1024 => {
+            format!("stored, {:.1} MB", data.len() as f64 / (1024.0 * 1024.0))
+        }
+        (None, Some(data)) if data.len() >= 1024 => {
+            format!("stored, {:.1} KB", data.len() as f64 / 1024.0)
+        }
+        (None, Some(data)) => format!("stored, {} bytes", data.len()),
+        (None, None) => String::new(),
+    }
+}
+
+/// Opens the attachment with the system's default application for it.
+pub fn open_attachment(attachment: &Attachment) -> Result<(), String> {
+    let path = match (&attachment.path, &attachment.data) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(data)) => write_temporary(attachment, data)?,
+        (None, None) => return Err(format!("{} has no contents", attachment.name)),
+    };
+    if !path.exists() {
+        return Err(format!("{} no longer exists", path.display()));
+    }
+    let mut child = opener(&path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("couldn't open {}: {}", path.display(), e))?;
+    // Reap the opener when it exits without holding up the caller.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Deletes the files stored attachments were written out to, and their
+/// directory.
+pub fn remove_temporary() {
+    let mut temporary = TEMPORARY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(dir) = temporary.take() {
+        let _ = fs::remove_dir_all(dir);
+    }
+}
+
+// Writes a stored attachment's `data` out under the process's temporary
+// directory, named after it so the opener can tell its type. The file must
+// not exist yet, so nothing planted there, a link least of all, is written
+// through.
+fn write_temporary(attachment: &Attachment, data: &[u8]) -> Result<PathBuf, String> {
+    let dir = temporary_dir()?;
+    // The name is only ever a file name, but backups can say otherwise.
+    let name = Path::new(&attachment.name)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let path = dir.join(format!(
+        "{}-{}-{}",
+        WRITTEN.fetch_add(1, Ordering::Relaxed),
+        attachment.id.unwrap_or(0),
+        name
+    ));
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(data))
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+// The process's temporary directory for stored attachments, created on first
+// use under a name no other process has, readable by the user alone.
+fn temporary_dir() -> Result<PathBuf, String> {
+    let mut temporary = TEMPORARY.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(dir) = temporary.as_ref() {
+        return Ok(dir.clone());
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.subsec_nanos())
+        .unwrap_or(0);
+    for attempt in 0..100u32 {
+        let dir = std::env::temp_dir().join(format!(
+            "options_tracker_attachments_{}_{}",
+            std::process::id(),
+            nanos.wrapping_add(attempt)
+        ));
+        match private_dir(&dir) {
+            Ok(()) => {
+                *temporary = Some(dir.clone());
+                return Ok(dir);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("{}: {}", dir.display(), e)),
+        }
+    }
+    Err("couldn't create a temporary directory".to_string())
+}
+
+// Creates `dir`, failing if anything is there already.
+#[cfg(unix)]
+fn private_dir(dir: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new().mode(0o700).create(dir)
+}
+
+#[cfg(not(unix))]
+fn private_dir(dir: &Path) -> std::io::Result<()> {
+    fs::create_dir(dir)
+}
+
+// Explorer, not `cmd /C start`, which would read `&` or `^` in the file name
+// as its own syntax.
+#[cfg(target_os = "windows")]
+fn opener(path: &Path) -> Command {
+    let mut command = Command::new("explorer");
+    command.arg(path);
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn opener(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg(path);
+    command
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn opener(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(path);
+    command
+}
+
+/// Serde for stored attachment contents as a hex string.
+pub(crate) mod hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        data: &Option<Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match data {
+            Some(data) => {
+                let text: String = data.iter().map(|byte| format!("{:02x}", byte)).collect();
+                serializer.serialize_some(&text)
+            }
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        if text.len() % 2 != 0 {
+            return Err(serde::de::Error::custom("odd number of hex digits"));
+        }
+        (0..text.len())
+            .step_by(2)
+            .map(|at| {
+                text.get(at..at + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(|| serde::de::Error::custom("invalid hex digits"))
+            })
+            .collect::<Result<_, _>>()
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::Backup;
+    use crate::db::{Action, Database, Trade};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn links_or_stores_files_and_backs_them_up() {
+        let dir =
+            std::env::temp_dir().join(format!("options_tracker_attach_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let chart = dir.join("chart.png");
+        fs::write(&chart, [0x89, b'P', b'N', b'G', 0]).unwrap();
+
+        let db = Database::new(":memory:").unwrap();
+        let trade = db
+            .add_trade(&Trade {
+                symbol: "AAPL".to_string(),
+                action: Action::BuyToOpen,
+                price: dec!(150),
+                quantity: dec!(10),
+                date: "2024-01-05".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let linked = attach_file(trade, &chart, false).unwrap();
+        assert_eq!(linked.name, "chart.png");
+        assert!(linked.path.as_deref().unwrap().ends_with("chart.png"));
+        assert_eq!(linked.data, None);
+        let stored = attach_file(trade, &chart, true).unwrap();
+        assert_eq!(
+            stored.data.as_deref(),
+            Some(&[0x89, b'P', b'N', b'G', 0][..])
+        );
+        assert_eq!(location(&stored), "stored, 5 bytes");
+        assert!(attach_file(trade, &dir.join("missing.pdf"), false).is_err());
+        assert!(attach_file(trade, &dir, false).is_err());
+
+        db.add_attachment(&linked).unwrap();
+        let id = db.add_attachment(&stored).unwrap();
+        let saved = db.get_attachments(trade).unwrap();
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[1].data, stored.data);
+        assert_eq!(saved[1].added_at.len(), 19);
+
+        let json = db.get_backup().unwrap().to_json();
+        assert!(json.contains("\"data\": \"89504e4700\""));
+        let restored = Database::new(":memory:").unwrap();
+        restored
+            .restore_backup(&Backup::from_json(&json).unwrap())
+            .unwrap();
+        assert_eq!(restored.get_attachments(trade).unwrap(), saved);
+
+        db.delete_attachment(id).unwrap();
+        assert_eq!(db.get_attachments(trade).unwrap().len(), 1);
+        db.delete_trade(trade).unwrap();
+        db.purge_trash().unwrap();
+        assert!(db.get_attachments(trade).unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn writes_stored_files_to_a_private_directory_and_removes_them() {
+        let attachment = Attachment {
+            id: Some(7),
+            trade_id: 1,
+            name: "../confirm.pdf".to_string(),
+            path: None,
+            data: Some(b"%PDF".to_vec()),
+            added_at: String::new(),
+        };
+        let first = write_temporary(&attachment, b"%PDF").unwrap();
+        let second = write_temporary(&attachment, b"%PDF").unwrap();
+        assert_ne!(first, second);
+        assert!(first.to_string_lossy().ends_with("-7-confirm.pdf"));
+        assert_eq!(fs::read(&first).unwrap(), b"%PDF");
+        let dir = first.parent().unwrap().to_path_buf();
+        assert_eq!(second.parent(), Some(dir.as_path()));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&dir).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        remove_temporary();
+        assert!(!dir.exists());
+        let third = write_temporary(&attachment, b"%PDF").unwrap();
+        assert!(third.exists());
+        remove_temporary();
+    }
+}
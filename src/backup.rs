@@ -0,0 +1,149 @@
+//! Whole-database backup as JSON.
+//!
+//! A [`Backup`] holds every row of every table as the entities the rest of the
+//! crate uses, ids included, so trades keep their links to strategies, rolls,
+//! assignments, covered lots, and manual lot matches when restored. Amounts
+//! are written as strings to keep their exact digits. The `version` field is
+//! bumped whenever the format changes in a way older readers can't follow;
+//! sections missing from a file read as empty, so other tools may write just
+//! the trades.
+
+use crate::cost_basis::LotOverride;
+use crate::db::{
+    Account, Alert, ArchiveSummary, ArchivedTrade, Attachment, CashTransaction, CorporateAction,
+    Dividend, FxRate, HistoryEntry, PriceBar, Quote, SavedReport, Strategy, Trade,
+    ValuationSnapshot,
+};
+use serde::{Deserialize, Serialize};
+
+/// Version of the format written by [`Backup::to_json`].
+pub const BACKUP_VERSION: u32 = 1;
+
+/// A sold call and the stock lot covering it (the `covered_calls` table).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoveredCall {
+    pub call_id: i64,
+    pub stock_id: i64,
+}
+
+/// Everything stored in the database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u32,
+    #[serde(default)]
+    pub accounts: Vec<Account>,
+    #[serde(default)]
+    pub trades: Vec<Trade>,
+    #[serde(default)]
+    pub strategies: Vec<Strategy>,
+    #[serde(default)]
+    pub covered_calls: Vec<CoveredCall>,
+    #[serde(default)]
+    pub lot_matches: Vec<LotOverride>,
+    #[serde(default)]
+    pub corporate_actions: Vec<CorporateAction>,
+    #[serde(default)]
+    pub dividends: Vec<Dividend>,
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+    #[serde(default)]
+    pub saved_reports: Vec<SavedReport>,
+    #[serde(default)]
+    pub quotes: Vec<Quote>,
+    #[serde(default)]
+    pub prices: Vec<PriceBar>,
+    #[serde(default)]
+    pub valuation_snapshots: Vec<ValuationSnapshot>,
+    #[serde(default)]
+    pub history: Vec<HistoryEntry>,
+    /// Files stored in the database are written out in hex.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub cash_transactions: Vec<CashTransaction>,
+    #[serde(default)]
+    pub fx_rates: Vec<FxRate>,
+    #[serde(default)]
+    pub archived_trades: Vec<ArchivedTrade>,
+    #[serde(default)]
+    pub archive_summaries: Vec<ArchiveSummary>,
+}
+
+impl Backup {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("backup entities always serialize")
+    }
+
+    /// Reads a backup, refusing one written by a newer version.
+    pub fn from_json(text: &str) -> Result<Backup, String> {
+        let backup: Backup = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if backup.version > BACKUP_VERSION {
+            return Err(format!(
+                "backup format {} is newer than this version reads ({})",
+                backup.version, BACKUP_VERSION
+            ));
+        }
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, Database, OptionStatus, OptionType, StrategyKind, TradeType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn restores_what_it_backed_up() {
+        let db = Database::new(":memory:").unwrap();
+        let strategy_id = db
+            .add_strategy(&Strategy {
+                id: None,
+                name: "KO puts".to_string(),
+                kind: StrategyKind::Custom,
+            })
+            .unwrap();
+        let put = db
+            .add_trade(&Trade {
+                symbol: "KO".to_string(),
+                trade_type: TradeType::Option,
+                action: Action::SellToOpen,
+                option_type: Some(OptionType::Put),
+                strike: Some(dec!(55)),
+                expiration: Some("2024-02-16".to_string()),
+                status: Some(OptionStatus::Open),
+                price: dec!(1.20),
+                quantity: dec!(2),
+                date: "2024-01-10".to_string(),
+                fees: dec!(1.30),
+                strategy_id: Some(strategy_id),
+                ..Default::default()
+            })
+            .unwrap();
+        db.add_dividend(&Dividend {
+            id: None,
+            symbol: "KO".to_string(),
+            date: "2024-04-01".to_string(),
+            amount: dec!(48.50),
+            qualified: true,
+            account_id: None,
+        })
+        .unwrap();
+
+        let json = db.get_backup().unwrap().to_json();
+        assert!(json.contains("\"strike\": \"55\""));
+        let restored = Database::new(":memory:").unwrap();
+        restored
+            .restore_backup(&Backup::from_json(&json).unwrap())
+            .unwrap();
+        assert_eq!(restored.get_backup().unwrap().to_json(), json);
+        assert_eq!(
+            restored.get_trade(put).unwrap().unwrap().strategy_id,
+            Some(strategy_id)
+        );
+
+        assert!(Backup::from_json("{\"version\": 2}").is_err());
+        let trades_only = Backup::from_json("{\"version\": 1, \"trades\": []}").unwrap();
+        assert!(trades_only.dividends.is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+//! Portfolio returns against a buy-and-hold benchmark.
+//!
+//! The portfolio's return for a month is its realized P/L that month over the
+//! account capital, so the capital is taken as fixed and monthly returns add
+//! up. The benchmark's return is the change in its last close of the month
+//! over the last close of the month before, as if bought at that close and
+//! held. Months without any stored close for the benchmark are left out.
+
+use crate::db::PriceBar;
+use crate::periods::Period;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// One month's returns, in percent, with the totals since the first month.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonthReturn {
+    /// `YYYY-MM`.
+    pub month: String,
+    pub portfolio: Decimal,
+    pub benchmark: Decimal,
+    pub portfolio_total: Decimal,
+    pub benchmark_total: Decimal,
+}
+
+/// Monthly returns of the realized P/L in `daily` (by close date) on
+/// `capital`, next to buy-and-hold returns of `bars`, from month `start`
+/// through month `end` (or the last month with a close).
+pub fn compare(
+    daily: &BTreeMap<String, Decimal>,
+    bars: &[PriceBar],
+    capital: Decimal,
+    start: &str,
+    end: Option<&str>,
+) -> Vec<MonthReturn> {
+    let mut pl_by_month: BTreeMap<String, Decimal> = BTreeMap::new();
+    for (date, pl) in daily {
+        *pl_by_month.entry(Period::Month.label(date)).or_default() += pl;
+    }
+    // Bars are oldest first, so each month keeps its last close.
+    let closes: BTreeMap<String, Decimal> = bars
+        .iter()
+        .map(|bar| (Period::Month.label(&bar.date), bar.close))
+        .collect();
+
+    let hundred = Decimal::from(100);
+    let mut months = Vec::new();
+    let mut base: Option<Decimal> = None;
+    let mut previous: Option<Decimal> = None;
+    let mut portfolio_total = Decimal::ZERO;
+    for (month, close) in &closes {
+        if month.as_str() >= start && end.is_none_or(|end| month.as_str() <= end) {
+            if let (Some(base), Some(previous)) = (base, previous) {
+                let portfolio =
+                    pl_by_month.get(month).copied().unwrap_or_default() / capital * hundred;
+                portfolio_total += portfolio;
+                months.push(MonthReturn {
+                    month: month.clone(),
+                    portfolio,
+                    benchmark: (close / previous - Decimal::ONE) * hundred,
+                    portfolio_total,
+                    benchmark_total: (close / base - Decimal::ONE) * hundred,
+                });
+            }
+        }
+        // Held from the last close before the first month compared.
+        if month.as_str() < start || base.is_none() {
+            base = Some(*close);
+        }
+        previous = Some(*close);
+    }
+    months
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn bar(date: &str, close: Decimal) -> PriceBar {
+        PriceBar {
+            symbol: "SPY".to_string(),
+            date: date.to_string(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+        }
+    }
+
+    #[test]
+    fn compares_monthly_returns() {
+        let daily = BTreeMap::from([
+            ("2024-02-09".to_string(), dec!(300)),
+            ("2024-02-16".to_string(), dec!(200)),
+            ("2024-04-19".to_string(), dec!(-250)),
+        ]);
+        let bars = vec![
+            bar("2024-01-02", dec!(470)),
+            bar("2024-01-31", dec!(480)),
+            bar("2024-02-29", dec!(504)),
+            bar("2024-03-28", dec!(504)),
+            bar("2024-04-30", dec!(478.80)),
+        ];
+        let months = compare(&daily, &bars, dec!(10000), "2024-02", None);
+        assert_eq!(months.len(), 3);
+        assert_eq!(months[0].month, "2024-02");
+        assert_eq!(months[0].portfolio, dec!(5));
+        assert_eq!(months[0].benchmark, dec!(5));
+        assert_eq!(months[1].portfolio, Decimal::ZERO);
+        assert_eq!(months[1].benchmark, Decimal::ZERO);
+        assert_eq!(months[2].portfolio_total, dec!(2.5));
+        assert_eq!(months[2].benchmark, dec!(-5));
+        assert_eq!(months[2].benchmark_total, dec!(-0.25));
+
+        // Without a close before the first month there's nothing to hold from.
+        let months = compare(&daily, &bars[1..], dec!(10000), "2024-01", Some("2024-03"));
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].month, "2024-02");
+        assert_eq!(months[1].benchmark_total, dec!(5));
+    }
+}
@@ -0,0 +1,1145 @@
+//! Trade import from broker exports.
+//!
+//! Unlike a spreadsheet, a broker's export has a fixed layout, so it can be
+//! read without asking how its columns map: each supported layout is a
+//! [`BrokerFormat`], recognized by its header rows, that turns the CSV rows
+//! into trades. [`detect`] picks the format of a file among [`FORMATS`]; a
+//! file none of them recognizes is mapped by hand instead (see
+//! [`crate::import`]). Brokers describe options in text rather than OCC
+//! symbols, so [`option_description`] reads the descriptions they use.
+
+use crate::date::{format_ymd, MONTHS};
+use crate::db::{
+    Action, OptionStatus, OptionType, Strategy, StrategyKind, Trade, TradeType, OPTION_MULTIPLIER,
+};
+use crate::import::{parse_action, parse_date, parse_number, ReadResult};
+use crate::occ::OccSymbol;
+use crate::templates::TEMPLATES;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// A broker's export layout.
+pub trait BrokerFormat {
+    /// Name shown when the format is detected, e.g. `Schwab`.
+    fn name(&self) -> &'static str;
+
+    /// Whether `records`, the file's CSV rows, are in this layout.
+    fn detects(&self, records: &[Vec<String>]) -> bool;
+
+    /// The trades in `records`. Rows that aren't trades, such as totals or
+    /// cash movements, are left out without being reported.
+    fn read(&self, records: &[Vec<String>]) -> ReadResult;
+
+    /// [`read`](BrokerFormat::read), with each trade marked as placed with
+    /// this broker.
+    fn import(&self, records: &[Vec<String>]) -> ReadResult {
+        let mut result = self.read(records);
+        for trade in &mut result.trades {
+            trade.broker = Some(self.name().to_string());
+        }
+        result
+    }
+}
+
+/// Every supported broker layout.
+pub const FORMATS: &[&dyn BrokerFormat] =
+    &[&Schwab, &Thinkorswim, &Fidelity, &Tastytrade, &Robinhood];
+
+/// The broker layout `records` are in, if any.
+pub fn detect(records: &[Vec<String>]) -> Option<&'static dyn BrokerFormat> {
+    FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.detects(records))
+}
+
+/// The broker layout called `name`, ignoring case.
+pub fn format_named(name: &str) -> Option<&'static dyn BrokerFormat> {
+    FORMATS
+        .iter()
+        .copied()
+        .find(|format| format.name().eq_ignore_ascii_case(name.trim()))
+}
+
+/// Schwab's transaction history export: `Date`, `Action`, `Symbol`,
+/// `Description`, `Quantity`, `Price`, `Fees & Comm`, `Amount`, sometimes
+/// under a title line. Options are named in the symbol column as
+/// `AAPL 02/16/2024 180.00 P`.
+pub struct Schwab;
+
+const SCHWAB_HEADERS: [&str; 7] = [
+    "date",
+    "action",
+    "symbol",
+    "description",
+    "quantity",
+    "price",
+    "feescomm",
+];
+
+impl BrokerFormat for Schwab {
+    fn name(&self) -> &'static str {
+        "Schwab"
+    }
+
+    fn detects(&self, records: &[Vec<String>]) -> bool {
+        header_row(records, &SCHWAB_HEADERS).is_some()
+    }
+
+    fn read(&self, records: &[Vec<String>]) -> ReadResult {
+        let mut result = ReadResult::default();
+        let Some(header) = header_row(records, &SCHWAB_HEADERS) else {
+            return result;
+        };
+        let column = |name: &str| column(&records[header], name);
+        let (date, action, symbol, description) = (
+            column("date"),
+            column("action"),
+            column("symbol"),
+            column("description"),
+        );
+        let (quantity, price, fees) = (column("quantity"), column("price"), column("feescomm"));
+        for (index, row) in records.iter().enumerate().skip(header + 1) {
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map(|f| f.trim())
+                    .unwrap_or_default()
+            };
+            // Skip cash movements, dividends, expirations, and the total line.
+            let Ok(action) = parse_action(field(action)) else {
+                continue;
+            };
+            let trade = (|| {
+                let option = option_description(field(symbol))
+                    .or_else(|| option_description(field(description)));
+                Ok(leg(
+                    field(symbol).split_whitespace().next().unwrap_or_default(),
+                    option,
+                    action,
+                    parse_number(field(quantity), "quantity")?,
+                    parse_number(field(price), "price")?,
+                    parse_date(field(date))?,
+                    optional_number(field(fees), "fees")?,
+                ))
+            })();
+            push(&mut result, index + 1, trade);
+        }
+        result
+    }
+}
+
+/// The Account Trade History section of a thinkorswim account statement:
+/// `Exec Time`, `Spread`, `Side`, `Qty`, `Pos Effect`, `Symbol`, `Exp`,
+/// `Strike`, `Type`, `Price`, ... Every leg of a multi-leg order is a row, with
+/// the time only on the first, and each such order becomes a strategy. Fees
+/// are listed separately in the statement, so trades are read without them.
+pub struct Thinkorswim;
+
+const TOS_SECTION: &str = "Account Trade History";
+const TOS_HEADERS: [&str; 5] = ["", "exectime", "spread", "side", "qty"];
+
+impl Thinkorswim {
+    // Index of the section's header row.
+    fn header(records: &[Vec<String>]) -> Option<usize> {
+        let title = records
+            .iter()
+            .position(|row| row.first().map(|f| f.trim()) == Some(TOS_SECTION))?;
+        let header = title + 1;
+        let matches = records
+            .get(header)
+            .is_some_and(|row| starts_with(row, &TOS_HEADERS));
+        matches.then_some(header)
+    }
+}
+
+impl BrokerFormat for Thinkorswim {
+    fn name(&self) -> &'static str {
+        "thinkorswim"
+    }
+
+    fn detects(&self, records: &[Vec<String>]) -> bool {
+        Thinkorswim::header(records).is_some()
+    }
+
+    fn read(&self, records: &[Vec<String>]) -> ReadResult {
+        let mut result = ReadResult::default();
+        let Some(header) = Thinkorswim::header(records) else {
+            return result;
+        };
+        let column = |name: &str| column(&records[header], name);
+        let (time, side, qty, effect) = (
+            column("exectime"),
+            column("side"),
+            column("qty"),
+            column("poseffect"),
+        );
+        let (symbol, exp, strike, kind, price) = (
+            column("symbol"),
+            column("exp"),
+            column("strike"),
+            column("type"),
+            column("price"),
+        );
+        let mut exec_time = String::new();
+        let mut orders = Vec::new();
+        for (index, row) in records.iter().enumerate().skip(header + 1) {
+            // The section ends at the next section's title.
+            if !row.first().is_some_and(|f| f.trim().is_empty()) {
+                break;
+            }
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map(|f| f.trim())
+                    .unwrap_or_default()
+            };
+            if !field(time).is_empty() {
+                exec_time = field(time).to_string();
+            }
+            let trade = (|| {
+                let action = parse_action(&format!("{} {}", field(side), field(effect)))?;
+                let option = match field(kind).to_uppercase().as_str() {
+                    "PUT" | "CALL" => Some(
+                        option_description(&format!(
+                            "{} {} {} {}",
+                            field(symbol),
+                            field(exp),
+                            field(strike),
+                            field(kind)
+                        ))
+                        .ok_or_else(|| format!("unreadable option \"{}\"", field(exp)))?,
+                    ),
+                    _ => None,
+                };
+                Ok(leg(
+                    field(symbol),
+                    option,
+                    action,
+                    parse_number(field(qty), "quantity")?,
+                    parse_number(field(price), "price")?,
+                    parse_date(&exec_time)?,
+                    Decimal::ZERO,
+                ))
+            })();
+            if let Some(index) = push(&mut result, index + 1, trade) {
+                orders.push((exec_time.clone(), index));
+            }
+        }
+        result.strategies = group_orders(&result.trades, &orders);
+        result
+    }
+}
+
+/// Fidelity's account history (`Accounts_History.csv`): `Run Date`,
+/// `Action`, `Symbol`, `Security Description`, `Security Type`, `Quantity`,
+/// `Price ($)`, `Commission ($)`, `Fees ($)`, ... The action column is a
+/// sentence such as `YOU SOLD OPENING TRANSACTION PUT (AAPL) ...`, and
+/// options are named `-AAPL240216P180`.
+pub struct Fidelity;
+
+const FIDELITY_HEADERS: [&str; 6] = [
+    "rundate",
+    "action",
+    "symbol",
+    "securitydescription",
+    "securitytype",
+    "quantity",
+];
+
+impl Fidelity {
+    // The trade an action sentence describes; `None` for dividends,
+    // transfers, expirations, and the like.
+    fn action(text: &str) -> Option<Action> {
+        let text = text.to_uppercase();
+        let opening = text.contains("OPENING TRANSACTION");
+        let closing = text.contains("CLOSING TRANSACTION");
+        if text.starts_with("YOU BOUGHT") {
+            Some(if closing {
+                Action::BuyToClose
+            } else {
+                Action::BuyToOpen
+            })
+        } else if text.starts_with("YOU SOLD") {
+            Some(if opening || text.contains("SHORT SALE") {
+                Action::SellToOpen
+            } else {
+                Action::SellToClose
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl BrokerFormat for Fidelity {
+    fn name(&self) -> &'static str {
+        "Fidelity"
+    }
+
+    fn detects(&self, records: &[Vec<String>]) -> bool {
+        header_row(records, &FIDELITY_HEADERS).is_some()
+    }
+
+    fn read(&self, records: &[Vec<String>]) -> ReadResult {
+        let mut result = ReadResult::default();
+        let Some(header) = header_row(records, &FIDELITY_HEADERS) else {
+            return result;
+        };
+        let column = |name: &str| column(&records[header], name);
+        let (date, action, symbol, description) = (
+            column("rundate"),
+            column("action"),
+            column("symbol"),
+            column("securitydescription"),
+        );
+        let (quantity, price, commission, fees) = (
+            column("quantity"),
+            column("price"),
+            column("commission"),
+            column("fees"),
+        );
+        for (index, row) in records.iter().enumerate().skip(header + 1) {
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map(|f| f.trim())
+                    .unwrap_or_default()
+            };
+            let Some(action) = Fidelity::action(field(action)) else {
+                continue;
+            };
+            let trade = (|| {
+                // The symbol is the surer source; descriptions name the
+                // company, not always the ticker.
+                let option = option_description(field(symbol))
+                    .or_else(|| option_description(field(description)));
+                Ok(leg(
+                    field(symbol),
+                    option,
+                    action,
+                    parse_number(field(quantity), "quantity")?,
+                    parse_number(field(price), "price")?,
+                    parse_date(field(date))?,
+                    optional_number(field(commission), "commission")?
+                        + optional_number(field(fees), "fees")?,
+                ))
+            })();
+            push(&mut result, index + 1, trade);
+        }
+        result
+    }
+}
+
+/// Tastytrade's transaction history: `Date`, `Type`, `Action`, `Symbol`,
+/// `Instrument Type`, `Description`, `Value`, `Quantity`, `Average Price`,
+/// `Commissions`, `Fees`, `Multiplier`, ..., `Order #`. Actions carry the
+/// opening or closing code (`SELL_TO_OPEN`), options are OCC symbols, and the
+/// average price is per contract. The legs of a multi-leg order share its
+/// order number, so each such order becomes a strategy.
+pub struct Tastytrade;
+
+const TASTY_HEADERS: [&str; 6] = [
+    "date",
+    "type",
+    "action",
+    "symbol",
+    "instrumenttype",
+    "description",
+];
+
+impl BrokerFormat for Tastytrade {
+    fn name(&self) -> &'static str {
+        "Tastytrade"
+    }
+
+    fn detects(&self, records: &[Vec<String>]) -> bool {
+        header_row(records, &TASTY_HEADERS).is_some()
+    }
+
+    fn read(&self, records: &[Vec<String>]) -> ReadResult {
+        let mut result = ReadResult::default();
+        let Some(header) = header_row(records, &TASTY_HEADERS) else {
+            return result;
+        };
+        let column = |name: &str| column(&records[header], name);
+        let (date, kind, action, symbol) = (
+            column("date"),
+            column("type"),
+            column("action"),
+            column("symbol"),
+        );
+        let (quantity, price, multiplier) = (
+            column("quantity"),
+            column("averageprice"),
+            column("multiplier"),
+        );
+        let (commissions, fees, order) = (column("commissions"), column("fees"), column("order"));
+        let mut orders = Vec::new();
+        for (index, row) in records.iter().enumerate().skip(header + 1) {
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map(|f| f.trim())
+                    .unwrap_or_default()
+            };
+            // Expirations and assignments are `Receive Deliver` rows.
+            if !field(kind).eq_ignore_ascii_case("trade") {
+                continue;
+            }
+            let trade = (|| {
+                let option = option_description(field(symbol));
+                let multiplier = match optional_number(field(multiplier), "multiplier")? {
+                    m if !m.is_zero() => m,
+                    _ if option.is_some() => OPTION_MULTIPLIER,
+                    _ => Decimal::ONE,
+                };
+                let mut trade = leg(
+                    field(symbol),
+                    option,
+                    parse_action(field(action))?,
+                    parse_number(field(quantity), "quantity")?,
+                    parse_number(field(price), "price")? / multiplier,
+                    parse_date(field(date))?,
+                    optional_number(field(commissions), "commissions")?.abs()
+                        + optional_number(field(fees), "fees")?.abs(),
+                );
+                if trade.trade_type == TradeType::Option && multiplier != OPTION_MULTIPLIER {
+                    trade.contract_multiplier = Some(multiplier);
+                }
+                Ok(trade)
+            })();
+            if let Some(index) = push(&mut result, index + 1, trade) {
+                orders.push((field(order).to_string(), index));
+            }
+        }
+        result.strategies = group_orders(&result.trades, &orders);
+        result
+    }
+}
+
+/// Robinhood's account activity report: `Activity Date`, `Process Date`,
+/// `Settle Date`, `Instrument`, `Description`, `Trans Code`, `Quantity`,
+/// `Price`, `Amount`, newest first. Stock quantities may be fractional, and
+/// options are described as `AAPL 2/16/2024 Put $180.00`. Robinhood charges
+/// no commission, so the fees are the regulatory fees the amount leaves out.
+///
+/// Expirations, assignments, and exercises are event rows of their own
+/// (`OEXP`, `OASGN`, `OEXCS`). Each sets the status of the option it ends,
+/// which must have been opened in the same file; the shares Robinhood lists
+/// for an assignment are left out, as the ledger adds them itself.
+pub struct Robinhood;
+
+const ROBINHOOD_HEADERS: [&str; 6] = [
+    "activitydate",
+    "processdate",
+    "settledate",
+    "instrument",
+    "description",
+    "transcode",
+];
+
+impl Robinhood {
+    // The status an event row's code leaves its option in.
+    fn event(code: &str) -> Option<OptionStatus> {
+        match code {
+            "OEXP" => Some(OptionStatus::Expired),
+            "OASGN" => Some(OptionStatus::Assigned),
+            "OEXCS" => Some(OptionStatus::Exercised),
+            _ => None,
+        }
+    }
+}
+
+impl BrokerFormat for Robinhood {
+    fn name(&self) -> &'static str {
+        "Robinhood"
+    }
+
+    fn detects(&self, records: &[Vec<String>]) -> bool {
+        header_row(records, &ROBINHOOD_HEADERS).is_some()
+    }
+
+    fn read(&self, records: &[Vec<String>]) -> ReadResult {
+        let mut result = ReadResult::default();
+        let Some(header) = header_row(records, &ROBINHOOD_HEADERS) else {
+            return result;
+        };
+        let column = |name: &str| column(&records[header], name);
+        let (date, instrument, description, code) = (
+            column("activitydate"),
+            column("instrument"),
+            column("description"),
+            column("transcode"),
+        );
+        let (quantity, price, amount) = (column("quantity"), column("price"), column("amount"));
+        let mut events = Vec::new();
+        for (index, row) in records.iter().enumerate().skip(header + 1) {
+            let field = |column: Option<usize>| {
+                column
+                    .and_then(|c| row.get(c))
+                    .map(|f| f.trim())
+                    .unwrap_or_default()
+            };
+            // Event quantities are marked `S` or `L` for the short or long side.
+            let contracts =
+                || parse_number(field(quantity).trim_end_matches(['S', 'L']), "quantity");
+            if let Some(status) = Robinhood::event(field(code)) {
+                let event = (|| {
+                    let option = option_description(field(description))
+                        .ok_or_else(|| format!("unreadable option \"{}\"", field(description)))?;
+                    Ok(OptionEvent {
+                        row: index + 1,
+                        option,
+                        date: parse_date(field(date))?,
+                        status,
+                        quantity: contracts()?,
+                    })
+                })();
+                match event {
+                    Ok(event) => events.push(event),
+                    Err(e) => result.errors.push((index + 1, e)),
+                }
+                continue;
+            }
+            // Skip deposits, dividends, interest, and the closing disclaimer.
+            if !matches!(field(code), "Buy" | "Sell" | "BTO" | "STO" | "BTC" | "STC") {
+                continue;
+            }
+            let trade = (|| {
+                let option = option_description(field(description));
+                let action = parse_action(field(code))?;
+                let quantity = contracts()?;
+                let price = parse_number(field(price), "price")?;
+                let multiplier = if option.is_some() {
+                    OPTION_MULTIPLIER
+                } else {
+                    Decimal::ONE
+                };
+                // The amount is the gross less fees for a sale, and the gross
+                // plus fees for a purchase, both to the cent.
+                let gross = quantity * price * multiplier;
+                let net = optional_number(field(amount), "amount")?.abs();
+                let fees = if action.is_buy() {
+                    net - gross
+                } else {
+                    gross - net
+                };
+                Ok(leg(
+                    field(instrument),
+                    option,
+                    action,
+                    quantity,
+                    price,
+                    parse_date(field(date))?,
+                    fees.round_dp(2).max(Decimal::ZERO),
+                ))
+            })();
+            push(&mut result, index + 1, trade);
+        }
+        end_options(&mut result, events);
+        result
+    }
+}
+
+/// An option expiring, assigned, or exercised, listed by a broker apart from
+/// its trades.
+#[derive(Debug, Clone)]
+pub struct OptionEvent {
+    /// The row (or record) number listing it.
+    pub row: usize,
+    pub option: OccSymbol,
+    pub date: String,
+    pub status: OptionStatus,
+    pub quantity: Decimal,
+}
+
+/// Sets the status of the options `events` end among `result`'s trades,
+/// oldest event first. An event whose option wasn't opened among them is
+/// reported as an error for its row. For an assignment or exercise, the
+/// stock trade the broker lists for it at the strike is dropped, since the
+/// ledger adds its own linked one.
+pub fn end_options(result: &mut ReadResult, mut events: Vec<OptionEvent>) {
+    events.sort_by(|a, b| a.date.cmp(&b.date));
+    for event in events {
+        let OptionEvent {
+            row,
+            option,
+            date,
+            status,
+            quantity,
+        } = event;
+        let unmatched = end_option(&mut result.trades, &option, &date, &status, quantity);
+        if !unmatched.is_zero() {
+            result.errors.push((
+                row,
+                format!(
+                    "no {} {} {} {} opened in this file to mark {}",
+                    option.underlying, option.expiration, option.strike, option.option_type, status
+                ),
+            ));
+        } else if status.triggers_stock_event() {
+            let shares = quantity * OPTION_MULTIPLIER;
+            let listed = result.trades.iter().position(|t| {
+                t.trade_type == TradeType::Stock
+                    && t.symbol == option.underlying
+                    && t.date == date
+                    && t.quantity == shares
+                    && t.price == option.strike
+            });
+            if let Some(listed) = listed {
+                result.trades.remove(listed);
+            }
+        }
+    }
+}
+
+// Ends `quantity` contracts of `option` opened in `trades` on or before
+// `date`, oldest first, splitting an opening trade that has more. Returns
+// the contracts no opening trade was found for.
+fn end_option(
+    trades: &mut Vec<Trade>,
+    option: &OccSymbol,
+    date: &str,
+    status: &OptionStatus,
+    mut quantity: Decimal,
+) -> Decimal {
+    let mut openers: Vec<usize> = (0..trades.len())
+        .filter(|&i| {
+            let trade = &trades[i];
+            trade.action.is_open()
+                && trade.status == Some(OptionStatus::Open)
+                && trade.occ_symbol().as_ref() == Some(option)
+                && trade.date.as_str() <= date
+        })
+        .collect();
+    openers.sort_by(|&a, &b| trades[a].date.cmp(&trades[b].date));
+    for i in openers {
+        if quantity.is_zero() {
+            break;
+        }
+        if trades[i].quantity > quantity {
+            let mut rest = trades[i].clone();
+            rest.quantity -= quantity;
+            rest.fees = Decimal::ZERO;
+            trades[i].quantity = quantity;
+            trades.push(rest);
+        }
+        trades[i].status = Some(status.clone());
+        quantity -= trades[i].quantity;
+    }
+    quantity
+}
+
+/// Strategies for the multi-leg orders among `trades`, given the order each
+/// trade (by index) was part of. An order opening legs that match a template
+/// is named and typed after it, e.g. `SPY Bull Put Spread`; other orders are
+/// custom strategies. An order closing legs that an earlier order opened
+/// joins that order's strategy.
+pub fn group_orders(trades: &[Trade], orders: &[(String, usize)]) -> Vec<(Strategy, Vec<usize>)> {
+    let mut by_order: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (order, index) in orders {
+        if !order.is_empty() {
+            by_order.entry(order).or_default().push(*index);
+        }
+    }
+    let mut groups: Vec<Vec<usize>> = by_order
+        .into_values()
+        .filter(|legs| legs.len() > 1)
+        .collect();
+    groups.sort_by_key(|legs| legs.iter().map(|&i| trades[i].date.clone()).min());
+
+    let mut strategies: Vec<(Strategy, Vec<usize>)> = Vec::new();
+    for legs in groups {
+        if legs.iter().all(|&i| !trades[i].action.is_open()) {
+            let opened = strategies.iter_mut().find(|(_, opened)| {
+                legs.iter().all(|&i| {
+                    let key = trades[i].position_key();
+                    opened.iter().any(|&o| trades[o].position_key() == key)
+                })
+            });
+            if let Some((_, opened)) = opened {
+                opened.extend(legs);
+                continue;
+            }
+        }
+        // Closing legs are matched against the template legs they close.
+        let shape: Vec<(Action, Option<OptionType>)> = legs
+            .iter()
+            .map(|&i| {
+                let action = match trades[i].action {
+                    Action::BuyToClose => Action::SellToOpen,
+                    Action::SellToClose => Action::BuyToOpen,
+                    action => action,
+                };
+                (action, trades[i].option_type)
+            })
+            .collect();
+        let template = TEMPLATES.iter().find(|template| {
+            let mut unmatched = shape.clone();
+            template.legs.len() == shape.len()
+                && template.legs.iter().all(|leg| {
+                    let wanted = (leg.action, Some(leg.option_type));
+                    match unmatched.iter().position(|l| *l == wanted) {
+                        Some(at) => {
+                            unmatched.remove(at);
+                            true
+                        }
+                        None => false,
+                    }
+                })
+        });
+        let symbol = &trades[legs[0]].symbol;
+        let strategy = match template {
+            Some(template) => Strategy {
+                id: None,
+                name: format!("{} {}", symbol, template.name),
+                kind: template.kind,
+            },
+            None => Strategy {
+                id: None,
+                name: format!("{} {}-leg order", symbol, legs.len()),
+                kind: StrategyKind::Custom,
+            },
+        };
+        strategies.push((strategy, legs));
+    }
+    strategies
+}
+
+/// The contract named by a broker's option description, in any of the forms
+///
+/// - `AAPL 02/16/2024 180.00 P` (Schwab),
+/// - `SOLD -2 AAPL 100 (Weeklys) 16 FEB 24 180 PUT @1.25` (thinkorswim),
+/// - `PUT (AAPL) APPLE INC FEB 16 24 $180 (100 SHS)` and `-AAPL240216P180`
+///   (Fidelity),
+/// - `AAPL 2/16/2024 Put $180.00` (Robinhood),
+/// - `AAPL240216P00180000` (OCC).
+pub fn option_description(text: &str) -> Option<OccSymbol> {
+    if let Ok(occ) = text.parse::<OccSymbol>() {
+        return Some(occ);
+    }
+    if let Some(option) = dashed_symbol(text) {
+        return Some(option);
+    }
+    let words: Vec<&str> = text
+        .split_whitespace()
+        .filter(|w| !w.starts_with('@'))
+        .collect();
+    // The expiration takes one word (`02/16/2024`) or three (`16 FEB 24`,
+    // `FEB 16 24`), and is followed by the strike and usually the type, or
+    // by the type and the strike.
+    for start in 1..words.len() {
+        let Some((expiration, length)) = slashed_date(words[start])
+            .map(|date| (date, 1))
+            .or_else(|| spelled_date(&words[start..]).map(|date| (date, 3)))
+        else {
+            continue;
+        };
+        let after = start + length;
+        let leading = words.get(after).and_then(|w| option_kind(w));
+        let strike_at = if leading.is_some() { after + 1 } else { after };
+        let Some(Ok(strike)) = words
+            .get(strike_at)
+            .map(|w| w.trim_start_matches('$').parse::<Decimal>())
+        else {
+            continue;
+        };
+        let Some(option_type) = leading
+            .or_else(|| words.get(strike_at + 1).and_then(|w| option_kind(w)))
+            .or_else(|| words[..start].iter().find_map(|w| option_kind(w)))
+        else {
+            continue;
+        };
+        // The underlying is a ticker in parentheses, or else the last
+        // ticker-like word before, skipping the quantity, the deliverable
+        // (`100`), and notes such as `(Weeklys)`.
+        let ticker = |w: &&str| {
+            !w.is_empty()
+                && w.chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '.')
+        };
+        let underlying = words[..start]
+            .iter()
+            .filter_map(|w| w.strip_prefix('(')?.strip_suffix(')'))
+            .find(ticker)
+            .or_else(|| {
+                words[..start].iter().rev().copied().find(|w| {
+                    w.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                        && option_kind(w).is_none()
+                })
+            })?;
+        return Some(OccSymbol {
+            underlying: underlying.to_uppercase(),
+            expiration,
+            option_type,
+            strike: strike.normalize(),
+        });
+    }
+    None
+}
+
+// `P`, `PUT`, `C`, or `CALL`.
+fn option_kind(word: &str) -> Option<OptionType> {
+    match word.to_uppercase().as_str() {
+        "P" | "PUT" => Some(OptionType::Put),
+        "C" | "CALL" => Some(OptionType::Call),
+        _ => None,
+    }
+}
+
+// Fidelity's `-AAPL240216P180`: root, `YYMMDD`, `C` or `P`, and the strike
+// without padding.
+fn dashed_symbol(text: &str) -> Option<OccSymbol> {
+    let symbol = text.trim().strip_prefix('-')?;
+    if !symbol.is_ascii() {
+        return None;
+    }
+    let digits = symbol.find(|c: char| c.is_ascii_digit())?;
+    let (root, rest) = symbol.split_at(digits);
+    if root.is_empty() || rest.len() < 8 {
+        return None;
+    }
+    let (yymmdd, rest) = rest.split_at(6);
+    let (kind, strike) = rest.split_at(1);
+    if !yymmdd.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let expiration = format!("20{}-{}-{}", &yymmdd[0..2], &yymmdd[2..4], &yymmdd[4..6]);
+    Some(OccSymbol {
+        underlying: root.to_uppercase(),
+        expiration: parse_date(&expiration).ok()?,
+        option_type: option_kind(kind)?,
+        strike: strike.parse::<Decimal>().ok()?.normalize(),
+    })
+}
+
+// `MM/DD/YYYY` or `MM/DD/YY`.
+fn slashed_date(word: &str) -> Option<String> {
+    word.contains('/').then(|| parse_date(word).ok()).flatten()
+}
+
+// `16 FEB 24` or `FEB 16 24`, with the year in two digits or four.
+fn spelled_date(words: &[&str]) -> Option<String> {
+    let [first, second, year, ..] = words else {
+        return None;
+    };
+    let month_of = |word: &str| MONTHS.iter().position(|m| m.eq_ignore_ascii_case(word));
+    let (day, month) = match (month_of(first), month_of(second)) {
+        (None, Some(month)) => (first, month),
+        (Some(month), None) => (second, month),
+        _ => return None,
+    };
+    let day: u32 = day.parse().ok()?;
+    let year: i64 = year.parse().ok()?;
+    let year = if year < 100 { 2000 + year } else { year };
+    parse_date(&format_ymd(year, month as u32 + 1, day)).ok()
+}
+
+// A trade of `quantity` of `symbol`, or of the `option` contract.
+pub(crate) fn leg(
+    symbol: &str,
+    option: Option<OccSymbol>,
+    action: Action,
+    quantity: Decimal,
+    price: Decimal,
+    date: String,
+    fees: Decimal,
+) -> Trade {
+    let mut trade = Trade {
+        symbol: symbol.to_uppercase(),
+        action,
+        quantity: quantity.abs(),
+        price: price.abs(),
+        date,
+        fees: fees.abs(),
+        ..Default::default()
+    };
+    if let Some(option) = option {
+        trade.symbol = option.underlying;
+        trade.trade_type = TradeType::Option;
+        trade.option_type = Some(option.option_type);
+        trade.strike = Some(option.strike);
+        trade.expiration = Some(option.expiration);
+        trade.status = Some(OptionStatus::Open);
+    }
+    trade
+}
+
+// Adds a trade read from `row`, or the reason it couldn't be, returning the
+// trade's index.
+pub(crate) fn push(
+    result: &mut ReadResult,
+    row: usize,
+    trade: Result<Trade, String>,
+) -> Option<usize> {
+    match trade {
+        Ok(trade) if trade.quantity.is_zero() => {
+            result.errors.push((row, "quantity is zero".into()));
+            None
+        }
+        Ok(trade) => {
+            result.trades.push(trade);
+            Some(result.trades.len() - 1)
+        }
+        Err(e) => {
+            result.errors.push((row, e));
+            None
+        }
+    }
+}
+
+pub(crate) fn optional_number(raw: &str, what: &str) -> Result<Decimal, String> {
+    if raw.is_empty() {
+        Ok(Decimal::ZERO)
+    } else {
+        parse_number(raw, what)
+    }
+}
+
+// Header names lowercased with everything but letters and digits dropped.
+fn squashed(row: &[String]) -> Vec<String> {
+    row.iter()
+        .map(|f| {
+            f.chars()
+                .filter(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_lowercase()
+        })
+        .collect()
+}
+
+// Whether `row` begins with the squashed `headers`.
+fn starts_with(row: &[String], headers: &[&str]) -> bool {
+    let row = squashed(row);
+    row.len() >= headers.len() && row.iter().zip(headers).all(|(a, b)| a == b)
+}
+
+// Index of the header row among the first few of `records`.
+fn header_row(records: &[Vec<String>], headers: &[&str]) -> Option<usize> {
+    records
+        .iter()
+        .take(5)
+        .position(|row| starts_with(row, headers))
+}
+
+fn column(header: &[String], name: &str) -> Option<usize> {
+    squashed(header).iter().position(|h| h == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::parse_csv;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn reads_option_descriptions() {
+        let put = OccSymbol {
+            underlying: "AAPL".to_string(),
+            expiration: "2024-02-16".to_string(),
+            option_type: OptionType::Put,
+            strike: dec!(180),
+        };
+        assert_eq!(
+            option_description("AAPL 02/16/2024 180.00 P"),
+            Some(put.clone())
+        );
+        assert_eq!(
+            option_description("SOLD -2 AAPL 100 (Weeklys) 16 FEB 24 180 PUT @1.25"),
+            Some(put.clone())
+        );
+        assert_eq!(
+            option_description("PUT (AAPL) APPLE INC FEB 16 24 $180 (100 SHS)"),
+            Some(put.clone())
+        );
+        assert_eq!(option_description(" -AAPL240216P180"), Some(put.clone()));
+        assert_eq!(
+            option_description("-SPY240216C472.5").map(|o| o.strike),
+            Some(dec!(472.5))
+        );
+        assert_eq!(
+            option_description("AAPL 2/16/2024 Put $180.00"),
+            Some(put.clone())
+        );
+        assert_eq!(option_description("AAPL240216P00180000"), Some(put));
+        assert_eq!(option_description("APPLE INC"), None);
+        assert_eq!(option_description("-A12345é9P180"), None);
+        assert_eq!(
+            option_description("PUT (AAPL) APPLÉ INC FÉB 16 24 $180"),
+            None
+        );
+        assert_eq!(option_description("PUT APPLE INC $180 EXP 02/16/24"), None);
+    }
+
+    #[test]
+    fn reads_schwab_transactions() {
+        let records = parse_csv(
+            "\"Transactions  for account XXXX-1234 as of 02/20/2024\"\n\
+             \"Date\",\"Action\",\"Symbol\",\"Description\",\"Quantity\",\"Price\",\"Fees & Comm\",\"Amount\"\n\
+             \"01/05/2024\",\"Sell to Open\",\"AAPL 02/16/2024 180.00 P\",\"PUT APPLE INC $180 EXP 02/16/24\",\"2\",\"$1.25\",\"$1.32\",\"$248.68\"\n\
+             \"01/08/2024 as of 01/05/2024\",\"Buy\",\"MSFT\",\"MICROSOFT CORP\",\"10\",\"$1,050.00\",\"\",\"-$10,500.00\"\n\
+             \"01/10/2024\",\"Qualified Dividend\",\"KO\",\"COCA COLA CO\",\"\",\"\",\"\",\"$46.00\"\n\
+             \"01/12/2024\",\"Sell\",\"MSFT\",\"MICROSOFT CORP\",\"10\",\"n/a\",\"\",\"\"\n\
+             \"Transactions Total\",\"\",\"\",\"\",\"\",\"\",\"\",\"-$10,205.32\"\n",
+        );
+        let format = detect(&records).unwrap();
+        assert_eq!(format.name(), "Schwab");
+        assert!(format_named(" schwab").is_some_and(|named| named.name() == "Schwab"));
+        assert!(format_named("etrade").is_none());
+        let ReadResult { trades, errors, .. } = format.import(&records);
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].broker.as_deref(), Some("Schwab"));
+        assert_eq!(trades[0].symbol, "AAPL");
+        assert_eq!(trades[0].action, Action::SellToOpen);
+        assert_eq!(trades[0].strike, Some(dec!(180)));
+        assert_eq!(trades[0].fees, dec!(1.32));
+        assert_eq!(trades[1].trade_type, TradeType::Stock);
+        assert_eq!(trades[1].price, dec!(1050.00));
+        assert_eq!(trades[1].date, "2024-01-08");
+        assert_eq!(
+            errors,
+            vec![(6, "price \"n/a\" is not a number".to_string())]
+        );
+    }
+
+    #[test]
+    fn reads_thinkorswim_trade_history() {
+        let records = parse_csv(
+            "Account Statement for 123456789 since 1/1/24 through 2/20/24\n\
+             \n\
+             Account Trade History\n\
+             ,Exec Time,Spread,Side,Qty,Pos Effect,Symbol,Exp,Strike,Type,Price,Net Price,Order Type\n\
+             ,1/5/24 10:31:02,VERTICAL,SELL,-2,TO OPEN,SPY,16 FEB 24,470,PUT,2.10,0.85,LMT\n\
+             ,,,BUY,+2,TO OPEN,SPY,16 FEB 24,465,PUT,1.25,CREDIT,\n\
+             ,1/8/24 09:45:10,STOCK,BUY,+100,TO OPEN,KO,,,STOCK,59.80,59.80,LMT\n\
+             Equities\n\
+             Symbol,Description,Qty\n\
+             KO,COCA COLA CO,100\n",
+        );
+        let format = detect(&records).unwrap();
+        assert_eq!(format.name(), "thinkorswim");
+        let ReadResult {
+            trades,
+            errors,
+            strategies,
+        } = format.read(&records);
+        assert!(errors.is_empty());
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[0].action, Action::SellToOpen);
+        assert_eq!(trades[0].quantity, dec!(2));
+        assert_eq!(trades[1].action, Action::BuyToOpen);
+        assert_eq!(trades[1].strike, Some(dec!(465)));
+        assert_eq!(trades[1].date, "2024-01-05");
+        assert_eq!(trades[2].trade_type, TradeType::Stock);
+        assert_eq!(trades[2].date, "2024-01-08");
+        assert_eq!(strategies.len(), 1);
+        assert_eq!(strategies[0].0.name, "SPY Bull Put Spread");
+        assert_eq!(strategies[0].1, vec![0, 1]);
+    }
+
+    #[test]
+    fn reads_fidelity_account_history() {
+        let records = parse_csv(
+            "\n\nRun Date,Action,Symbol,Security Description,Security Type,Quantity,Price ($),Commission ($),Fees ($),Accrued Interest ($),Amount ($),Settlement Date\n\
+             01/05/2024, YOU SOLD OPENING TRANSACTION PUT (AAPL) APPLE INC FEB 16 24 $180 (100 SHS) (Cash), -AAPL240216P180,PUT (AAPL) APPLE INC FEB 16 24 $180 (100 SHS),Cash,-2,1.25,1.30,0.04,,248.66,01/08/2024\n\
+             01/08/2024, YOU BOUGHT APPLE INC (AAPL) (Cash), AAPL,APPLE INC,Cash,10,185.50,0,0,,-1855.00,01/10/2024\n\
+             01/10/2024, DIVIDEND RECEIVED COCA-COLA CO (KO) (Cash), KO,COCA-COLA CO,Cash,,,,,,46.00,\n\
+             02/16/2024, YOU BOUGHT CLOSING TRANSACTION PUT (AAPL) APPLE INC FEB 16 24 $180 (100 SHS) (Cash), -AAPL240216P180,PUT (AAPL) APPLE INC FEB 16 24 $180 (100 SHS),Cash,2,0.05,1.30,0.04,,-11.34,02/20/2024\n\
+             \n\
+             \"The data and information in this spreadsheet is provided to you solely for your use.\"\n",
+        );
+        let format = detect(&records).unwrap();
+        assert_eq!(format.name(), "Fidelity");
+        let ReadResult { trades, errors, .. } = format.read(&records);
+        assert!(errors.is_empty());
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[0].action, Action::SellToOpen);
+        assert_eq!(trades[0].symbol, "AAPL");
+        assert_eq!(trades[0].expiration.as_deref(), Some("2024-02-16"));
+        assert_eq!(trades[0].quantity, dec!(2));
+        assert_eq!(trades[0].fees, dec!(1.34));
+        assert_eq!(trades[1].action, Action::BuyToOpen);
+        assert_eq!(trades[1].trade_type, TradeType::Stock);
+        assert_eq!(trades[2].action, Action::BuyToClose);
+    }
+
+    #[test]
+    fn reads_tastytrade_orders_as_strategies() {
+        let records = parse_csv(
+            "Date,Type,Action,Symbol,Instrument Type,Description,Value,Quantity,Average Price,Commissions,Fees,Multiplier,Root Symbol,Underlying Symbol,Expiration Date,Strike Price,Call or Put,Order #,Currency\n\
+             2024-02-09T15:02:11-0500,Trade,BUY_TO_CLOSE,SPY   240216P00470000,Equity Option,Bought 1 SPY 02/16/24 Put 470.00 @ 0.40,-40.00,1,-40.00,0.00,-0.13,100,SPY,SPY,2/16/24,470,PUT,302,USD\n\
+             2024-02-09T15:02:11-0500,Trade,SELL_TO_CLOSE,SPY   240216P00465000,Equity Option,Sold 1 SPY 02/16/24 Put 465.00 @ 0.15,15.00,1,15.00,0.00,-0.13,100,SPY,SPY,2/16/24,465,PUT,302,USD\n\
+             2024-01-05T10:31:02-0500,Trade,SELL_TO_OPEN,SPY   240216P00470000,Equity Option,Sold 1 SPY 02/16/24 Put 470.00 @ 2.10,210.00,1,210.00,-1.00,-0.14,100,SPY,SPY,2/16/24,470,PUT,301,USD\n\
+             2024-01-05T10:31:02-0500,Trade,BUY_TO_OPEN,SPY   240216P00465000,Equity Option,Bought 1 SPY 02/16/24 Put 465.00 @ 1.25,-125.00,1,-125.00,-1.00,-0.14,100,SPY,SPY,2/16/24,465,PUT,301,USD\n\
+             2024-01-04T09:45:10-0500,Trade,BUY_TO_OPEN,KO,Equity,Bought 100 KO @ 59.80,-5980.00,100,-59.80,0.00,-0.08,1,,KO,,,,300,USD\n\
+             2024-01-03T00:00:00-0500,Receive Deliver,,AAPL  240119C00190000,Equity Option,Removal of option due to expiration,0.00,1,0.00,,0.00,100,AAPL,AAPL,1/19/24,190,CALL,,USD\n",
+        );
+        let format = detect(&records).unwrap();
+        assert_eq!(format.name(), "Tastytrade");
+        let read = format.read(&records);
+        assert!(read.errors.is_empty());
+        assert_eq!(read.trades.len(), 5);
+        assert_eq!(read.trades[2].action, Action::SellToOpen);
+        assert_eq!(read.trades[2].price, dec!(2.10));
+        assert_eq!(read.trades[2].fees, dec!(1.14));
+        assert_eq!(read.trades[2].date, "2024-01-05");
+        assert_eq!(read.trades[4].price, dec!(59.80));
+
+        assert_eq!(read.strategies.len(), 1);
+        let (strategy, legs) = &read.strategies[0];
+        assert_eq!(strategy.name, "SPY Bull Put Spread");
+        assert_eq!(strategy.kind, StrategyKind::VerticalSpread);
+        assert_eq!(legs, &vec![2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn reads_robinhood_activity_with_option_events() {
+        let records = parse_csv(
+            "\"Activity Date\",\"Process Date\",\"Settle Date\",\"Instrument\",\"Description\",\"Trans Code\",\"Quantity\",\"Price\",\"Amount\"\n\
+             \"2/16/2024\",\"2/16/2024\",\"2/20/2024\",\"KO\",\"Coca-Cola\nCUSIP: 191216100\",\"Buy\",\"100\",\"$57.50\",\"($5,750.00)\"\n\
+             \"2/16/2024\",\"2/16/2024\",\"2/20/2024\",\"KO\",\"Option Assigned for KO 2/16/2024 Put $57.50\",\"OASGN\",\"1S\",\"\",\"\"\n\
+             \"2/16/2024\",\"2/16/2024\",\"2/20/2024\",\"AAPL\",\"Option Expiration for AAPL 2/16/2024 Put $180.00\",\"OEXP\",\"1S\",\"\",\"\"\n\
+             \"2/16/2024\",\"2/16/2024\",\"2/20/2024\",\"MSFT\",\"Option Expiration for MSFT 2/16/2024 Call $420.00\",\"OEXP\",\"1S\",\"\",\"\"\n\
+             \"1/12/2024\",\"1/12/2024\",\"1/16/2024\",\"VOO\",\"Vanguard S&P 500 ETF\",\"Sell\",\"0.25\",\"$440.00\",\"$109.99\"\n\
+             \"1/8/2024\",\"1/8/2024\",\"1/9/2024\",\"\",\"ACH Deposit\",\"ACH\",\"\",\"\",\"$1,000.00\"\n\
+             \"1/5/2024\",\"1/5/2024\",\"1/8/2024\",\"KO\",\"KO 2/16/2024 Put $57.50\",\"STO\",\"1\",\"$0.80\",\"$79.96\"\n\
+             \"1/5/2024\",\"1/5/2024\",\"1/8/2024\",\"AAPL\",\"AAPL 2/16/2024 Put $180.00\",\"STO\",\"2\",\"$1.25\",\"$249.92\"\n\
+             \"1/3/2024\",\"1/3/2024\",\"1/5/2024\",\"VOO\",\"Vanguard S&P 500 ETF\",\"Buy\",\"0.5\",\"$435.12\",\"($217.56)\"\n\
+             \"\",\"\",\"\",\"\",\"\",\"\",\"\",\"\",\"The data provided is for informational purposes only.\"\n",
+        );
+        let format = detect(&records).unwrap();
+        assert_eq!(format.name(), "Robinhood");
+        let ReadResult { trades, errors, .. } = format.read(&records);
+        assert_eq!(
+            errors,
+            vec![(
+                5,
+                "no MSFT 2024-02-16 420 call opened in this file to mark expired".to_string()
+            )]
+        );
+        // Robinhood's assignment shares are dropped; one AAPL put of two
+        // expired, leaving the other open.
+        assert_eq!(trades.len(), 5);
+        assert_eq!(trades[0].symbol, "VOO");
+        assert_eq!(trades[0].quantity, dec!(0.25));
+        assert_eq!(trades[0].fees, dec!(0.01));
+        let ko = &trades[1];
+        assert_eq!(ko.status, Some(OptionStatus::Assigned));
+        assert_eq!(ko.fees, dec!(0.04));
+        let aapl = &trades[2];
+        assert_eq!(
+            (aapl.quantity, aapl.status.clone()),
+            (dec!(1), Some(OptionStatus::Expired))
+        );
+        assert_eq!(aapl.fees, dec!(0.08));
+        assert_eq!(trades[3].quantity, dec!(0.5));
+        assert_eq!(trades[3].fees, Decimal::ZERO);
+        let rest = &trades[4];
+        assert_eq!(
+            (rest.quantity, rest.status.clone()),
+            (dec!(1), Some(OptionStatus::Open))
+        );
+        assert_eq!(rest.occ_symbol(), aapl.occ_symbol());
+    }
+}
@@ -0,0 +1,113 @@
+//! One change made to many trades at once.
+//!
+//! A [`BulkEdit`] sets any of a trade's symbol, fees, and account and adds
+//! tags to the ones it has. [`crate::db::Database::bulk_edit`] makes it to
+//! every trade picked, in one transaction; generated stock rows are left to
+//! follow their options.
+
+use crate::db::Trade;
+use rust_decimal::Decimal;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BulkEdit {
+    pub symbol: Option<String>,
+    pub fees: Option<Decimal>,
+    /// `Some(None)` takes the trades out of every account.
+    pub account: Option<Option<i64>>,
+    /// Added to each trade's tags; a tag it has in another case is kept.
+    pub tags: Vec<String>,
+}
+
+impl BulkEdit {
+    pub fn is_empty(&self) -> bool {
+        *self == BulkEdit::default()
+    }
+
+    /// `trade` with the change made.
+    pub fn apply(&self, trade: &Trade) -> Trade {
+        let mut trade = trade.clone();
+        if let Some(symbol) = &self.symbol {
+            trade.symbol = symbol.clone();
+        }
+        if let Some(fees) = self.fees {
+            trade.fees = fees;
+        }
+        if let Some(account) = self.account {
+            trade.account_id = account;
+        }
+        for tag in &self.tags {
+            if !trade.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                trade.tags.push(tag.clone());
+            }
+        }
+        trade
+    }
+
+    /// What the change does to `trade`, one entry per field it alters, such
+    /// as `symbol APPL → AAPL`; empty when it leaves the trade as it is.
+    /// Accounts are shown by `account_name`.
+    pub fn changes(
+        &self,
+        trade: &Trade,
+        account_name: impl Fn(Option<i64>) -> String,
+    ) -> Vec<String> {
+        let mut changes = Vec::new();
+        if let Some(symbol) = self.symbol.as_ref().filter(|s| **s != trade.symbol) {
+            changes.push(format!("symbol {} → {}", trade.symbol, symbol));
+        }
+        if let Some(fees) = self.fees.filter(|fees| *fees != trade.fees) {
+            changes.push(format!("fees {} → {}", trade.fees, fees));
+        }
+        if let Some(account) = self.account.filter(|a| *a != trade.account_id) {
+            changes.push(format!(
+                "account {} → {}",
+                account_name(trade.account_id),
+                account_name(account)
+            ));
+        }
+        let added: Vec<&str> = self
+            .tags
+            .iter()
+            .filter(|tag| !trade.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+            .map(String::as_str)
+            .collect();
+        if !added.is_empty() {
+            changes.push(format!("tags + {}", added.join(", ")));
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn changes_only_what_differs() {
+        let trade = Trade {
+            id: Some(1),
+            symbol: "APPL".to_string(),
+            fees: dec!(1),
+            tags: vec!["Hedge".to_string()],
+            ..Default::default()
+        };
+        let edit = BulkEdit {
+            symbol: Some("AAPL".to_string()),
+            fees: Some(dec!(1)),
+            account: Some(Some(2)),
+            tags: vec!["hedge".to_string(), "fix".to_string()],
+        };
+        let name = |id: Option<i64>| id.map_or("none".to_string(), |id| format!("#{}", id));
+        assert_eq!(
+            edit.changes(&trade, name),
+            ["symbol APPL → AAPL", "account none → #2", "tags + fix"]
+        );
+        let edited = edit.apply(&trade);
+        assert_eq!(edited.symbol, "AAPL");
+        assert_eq!(edited.account_id, Some(2));
+        assert_eq!(edited.tags, ["Hedge", "fix"]);
+        assert!(edit.changes(&edited, name).is_empty());
+        assert!(BulkEdit::default().is_empty());
+    }
+}
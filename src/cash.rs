@@ -0,0 +1,202 @@
+//! The account's cash: what was deposited and withdrawn, what it earned and
+//! was charged outside of trades, and what trading and dividends added.
+//!
+//! Trade P/L alone starts every account at zero; with the deposits and
+//! withdrawals in the cash ledger the balance is the account's real cash, and
+//! the account value what the broker would show. Cash available is the
+//! balance less what open short puts set aside to buy their shares if
+//! assigned (strike times shares), the same collateral
+//! [`crate::concentration`] counts.
+//!
+//! The time-weighted return chains the returns between valuation snapshots,
+//! each taken with the deposits and withdrawals since the one before counted
+//! as arriving at its end, so moving money in or out doesn't count as gain or
+//! loss. Interest, fees, and dividends do.
+
+use crate::db::{CashKind, CashTransaction, Dividend, OptionType, Trade, ValuationSnapshot};
+use crate::positions::Position;
+use rust_decimal::Decimal;
+
+/// Where an account's cash came from and went.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CashSummary {
+    pub deposits: Decimal,
+    pub withdrawals: Decimal,
+    pub interest: Decimal,
+    pub fees: Decimal,
+    pub dividends: Decimal,
+    /// Net cash flow of every trade.
+    pub trading: Decimal,
+    /// Set aside for open short puts.
+    pub collateral: Decimal,
+}
+
+impl CashSummary {
+    pub fn balance(&self) -> Decimal {
+        self.ledger() + self.trading
+    }
+
+    /// The balance less collateral for open short puts.
+    pub fn available(&self) -> Decimal {
+        self.balance() - self.collateral
+    }
+
+    /// The cash that didn't come from trades.
+    pub fn ledger(&self) -> Decimal {
+        self.deposits - self.withdrawals + self.interest - self.fees + self.dividends
+    }
+}
+
+/// The cash `trades`, `transactions`, and `dividends` leave, with open
+/// `positions` (see [`crate::positions::open_positions`]) for collateral.
+pub fn cash_summary(
+    trades: &[Trade],
+    transactions: &[CashTransaction],
+    dividends: &[Dividend],
+    positions: &[Position],
+) -> CashSummary {
+    let mut summary = CashSummary {
+        trading: trades.iter().map(Trade::cash_flow).sum(),
+        dividends: dividends.iter().map(|d| d.amount).sum(),
+        collateral: positions
+            .iter()
+            .filter(|p| p.key.option_type == Some(OptionType::Put) && p.is_short())
+            .map(|p| p.key.strike.unwrap_or_default() * p.quantity.abs() * p.multiplier)
+            .sum(),
+        ..Default::default()
+    };
+    for transaction in transactions {
+        let total = match transaction.kind {
+            CashKind::Deposit => &mut summary.deposits,
+            CashKind::Withdrawal => &mut summary.withdrawals,
+            CashKind::Interest => &mut summary.interest,
+            CashKind::Fee => &mut summary.fees,
+        };
+        *total += transaction.amount;
+    }
+    summary
+}
+
+/// Time-weighted return over `snapshots`, in percent, with the deposits and
+/// withdrawals among `transactions` taken out. `None` without two snapshots
+/// to measure between; a period starting at a value of zero or less is
+/// skipped, since there was nothing for it to return on.
+pub fn time_weighted_return(
+    snapshots: &[ValuationSnapshot],
+    transactions: &[CashTransaction],
+) -> Option<Decimal> {
+    let mut snapshots: Vec<&ValuationSnapshot> = snapshots.iter().collect();
+    snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+    let mut growth = Decimal::ONE;
+    let mut measured = false;
+    for pair in snapshots.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        if start.account_value <= Decimal::ZERO {
+            continue;
+        }
+        let flows: Decimal = transactions
+            .iter()
+            .filter(|t| t.kind.is_external())
+            .filter(|t| t.date > start.date && t.date <= end.date)
+            .map(CashTransaction::signed_amount)
+            .sum();
+        growth *= (end.account_value - flows) / start.account_value;
+        measured = true;
+    }
+    measured.then(|| (growth - Decimal::ONE) * Decimal::ONE_HUNDRED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionStatus, TradeType};
+    use crate::positions::open_positions;
+    use rust_decimal_macros::dec;
+
+    fn cash(date: &str, kind: CashKind, amount: Decimal) -> CashTransaction {
+        CashTransaction {
+            id: None,
+            date: date.to_string(),
+            kind,
+            amount,
+            comment: String::new(),
+            account_id: None,
+        }
+    }
+
+    fn snapshot(date: &str, account_value: Decimal) -> ValuationSnapshot {
+        ValuationSnapshot {
+            date: date.to_string(),
+            market_value: Decimal::ZERO,
+            cash: account_value,
+            account_value,
+        }
+    }
+
+    #[test]
+    fn sums_the_ledger_trades_and_collateral() {
+        let trades = vec![Trade {
+            id: Some(1),
+            symbol: "KO".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(55)),
+            expiration: Some("2024-02-16".to_string()),
+            status: Some(OptionStatus::Open),
+            price: dec!(1.20),
+            quantity: dec!(2),
+            date: "2024-01-10".to_string(),
+            fees: dec!(1.30),
+            ..Default::default()
+        }];
+        let transactions = vec![
+            cash("2024-01-02", CashKind::Deposit, dec!(20000)),
+            cash("2024-01-31", CashKind::Interest, dec!(12.40)),
+            cash("2024-02-01", CashKind::Withdrawal, dec!(5000)),
+            cash("2024-02-01", CashKind::Fee, dec!(25)),
+        ];
+        let dividends = vec![Dividend {
+            id: None,
+            symbol: "KO".to_string(),
+            date: "2024-01-15".to_string(),
+            amount: dec!(48.50),
+            qualified: true,
+            account_id: None,
+        }];
+        let summary = cash_summary(
+            &trades,
+            &transactions,
+            &dividends,
+            &open_positions(&trades, "2024-01-20"),
+        );
+        assert_eq!(summary.trading, dec!(238.70));
+        assert_eq!(summary.ledger(), dec!(15035.90));
+        assert_eq!(summary.balance(), dec!(15274.60));
+        assert_eq!(summary.collateral, dec!(11000));
+        assert_eq!(summary.available(), dec!(4274.60));
+    }
+
+    #[test]
+    fn leaves_deposits_and_withdrawals_out_of_the_return() {
+        let transactions = vec![
+            cash("2024-01-02", CashKind::Deposit, dec!(10000)),
+            cash("2024-02-15", CashKind::Deposit, dec!(10000)),
+            cash("2024-03-15", CashKind::Withdrawal, dec!(2200)),
+            cash("2024-03-20", CashKind::Interest, dec!(100)),
+        ];
+        let snapshots = vec![
+            snapshot("2024-03-31", dec!(18800)),
+            snapshot("2024-01-02", dec!(10000)),
+            snapshot("2024-01-31", dec!(11000)),
+        ];
+        // +10% in January, then flat: 11000 with 7800 more put in is 18800.
+        assert_eq!(
+            time_weighted_return(&snapshots, &transactions),
+            Some(dec!(10))
+        );
+        assert_eq!(time_weighted_return(&snapshots[..1], &transactions), None);
+        let from_zero = vec![snapshot("2024-01-01", Decimal::ZERO), snapshots[1].clone()];
+        assert_eq!(time_weighted_return(&from_zero, &transactions), None);
+    }
+}
@@ -0,0 +1,553 @@
+//! Text price charts.
+//!
+//! [`render_price_chart`] plots daily closes as a line of dots scaled to the
+//! chart's height, one column per day — or per group of days when there are
+//! more days than columns, each column showing its group's last close. Trades
+//! are marked on the line on the day they happened: `B` for a buy, `S` for a
+//! sell, `X` for both in the same column. [`render_line_chart`] draws any
+//! dated series the same way, unmarked, and [`render_comparison_chart`] draws
+//! two of them on one scale.
+//!
+//! [`render_candlestick_chart`] draws the same bars as candles, and a
+//! [`ChartWindow`] picks the stretch of bars either chart shows, for zooming
+//! and panning through a long history.
+//!
+//! [`render_bar_chart`] draws signed amounts as horizontal bars either side of
+//! a zero axis: gains to the right in `█`, losses to the left in `▒`.
+//!
+//! [`render_sparkline`] squeezes a series into one line of block heights, to
+//! sit beside a figure in a table.
+
+use crate::db::{PriceBar, Trade, TradeType};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::ops::Range;
+
+const LINE: char = '•';
+const SECOND_LINE: char = '○';
+const OVERLAP: char = '◉';
+const WICK: char = '│';
+const UP_BODY: char = '█';
+const DOWN_BODY: char = '░';
+// Width of the price labels left of the axis.
+const LABEL_WIDTH: usize = 10;
+// Fewest bars a window zooms in to.
+const MIN_SPAN: usize = 10;
+
+/// The stretch of a symbol's bars a chart shows: the `span` bars before
+/// index `end`. Zooming keeps the right edge where it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChartWindow {
+    pub end: usize,
+    pub span: usize,
+}
+
+impl ChartWindow {
+    /// The last `span` of `len` bars.
+    pub fn latest(len: usize, span: usize) -> ChartWindow {
+        ChartWindow {
+            end: len,
+            span: span.min(len),
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.end - self.span..self.end
+    }
+
+    /// Half as many bars, down to ten.
+    pub fn zoom_in(&self) -> ChartWindow {
+        ChartWindow {
+            span: (self.span / 2).max(MIN_SPAN.min(self.span)),
+            ..*self
+        }
+    }
+
+    /// Twice as many bars, up to all `len`; at the start of the history the
+    /// right edge moves to make room.
+    pub fn zoom_out(&self, len: usize) -> ChartWindow {
+        let span = (self.span * 2).min(len);
+        ChartWindow {
+            end: self.end.max(span),
+            span,
+        }
+    }
+
+    /// `bars` later, or earlier when negative, stopping at either end of the
+    /// `len` bars.
+    pub fn pan(&self, bars: isize, len: usize) -> ChartWindow {
+        ChartWindow {
+            end: self.end.saturating_add_signed(bars).clamp(self.span, len),
+            ..*self
+        }
+    }
+}
+
+/// Draws `bars` (oldest first) at most `width` columns wide and `height` rows
+/// tall, with `trades` marked on the day they happened. Trades outside the
+/// charted dates are left out. Empty when there are no bars.
+pub fn render_price_chart(
+    bars: &[PriceBar],
+    trades: &[Trade],
+    width: usize,
+    height: usize,
+) -> String {
+    let Some(last) = bars.last() else {
+        return String::new();
+    };
+    let marks: Vec<(usize, char)> = trades
+        .iter()
+        .filter(|trade| trade.date >= bars[0].date && trade.date <= last.date)
+        .map(|trade| {
+            // The bar of the trade's day, or the last one before it.
+            let k = bars.partition_point(|bar| bar.date <= trade.date) - 1;
+            (k, if trade.action.is_buy() { 'B' } else { 'S' })
+        })
+        .collect();
+    let dates: Vec<&str> = bars.iter().map(|bar| bar.date.as_str()).collect();
+    let closes: Vec<Decimal> = bars.iter().map(|bar| bar.close).collect();
+    render_line(&dates, &[(&closes, LINE)], &marks, width, height)
+}
+
+/// Draws `bars` (oldest first) as candles at most `width` columns wide and
+/// `height` rows tall: a `│` wick from high to low and a body from open to
+/// close, `█` when the day closed up and `░` when down. With more bars than
+/// columns, each column merges its group of days into one candle. Stock
+/// trades are marked at their price and option trades at the day's close,
+/// with the letters of [`render_price_chart`]. Empty when there are no bars.
+pub fn render_candlestick_chart(
+    bars: &[PriceBar],
+    trades: &[Trade],
+    width: usize,
+    height: usize,
+) -> String {
+    let (Some(first), Some(last)) = (bars.first(), bars.last()) else {
+        return String::new();
+    };
+    if width == 0 || height == 0 {
+        return String::new();
+    }
+    let n = bars.len();
+    let columns = width.min(n);
+    // Column `c` covers bars `c * n / columns` up to the next column's first.
+    let candles: Vec<PriceBar> = (0..columns)
+        .map(|c| {
+            let group = &bars[c * n / columns..(c + 1) * n / columns];
+            let mut candle = group[group.len() - 1].clone();
+            candle.open = group[0].open;
+            candle.high = group.iter().map(|bar| bar.high).max().unwrap_or_default();
+            candle.low = group.iter().map(|bar| bar.low).min().unwrap_or_default();
+            candle
+        })
+        .collect();
+    let high = candles.iter().map(|bar| bar.high).max().unwrap_or_default();
+    let low = candles.iter().map(|bar| bar.low).min().unwrap_or_default();
+    let row_of = scale(high, low, height);
+
+    let mut grid = vec![vec![' '; columns]; height];
+    for (c, candle) in candles.iter().enumerate() {
+        for row in grid
+            .iter_mut()
+            .take(row_of(candle.low) + 1)
+            .skip(row_of(candle.high))
+        {
+            row[c] = WICK;
+        }
+        let (top, bottom) = (
+            row_of(candle.open.max(candle.close)),
+            row_of(candle.open.min(candle.close)),
+        );
+        let body = if candle.close >= candle.open {
+            UP_BODY
+        } else {
+            DOWN_BODY
+        };
+        for row in grid.iter_mut().take(bottom + 1).skip(top) {
+            row[c] = body;
+        }
+    }
+    for trade in trades {
+        if trade.date < first.date || trade.date > last.date {
+            continue;
+        }
+        let k = bars.partition_point(|bar| bar.date <= trade.date) - 1;
+        let c = ((k + 1) * columns - 1) / n;
+        let price = match trade.trade_type {
+            TradeType::Stock => trade.price,
+            TradeType::Option => bars[k].close,
+        };
+        let mark = if trade.action.is_buy() { 'B' } else { 'S' };
+        let cell = &mut grid[row_of(price)][c];
+        *cell = match *cell {
+            'B' | 'S' | 'X' if *cell != mark => 'X',
+            _ => mark,
+        };
+    }
+    frame(&grid, high, low, &first.date, &last.date)
+}
+
+/// Draws `points` — dates (oldest first) with their values — as a line at
+/// most `width` columns wide and `height` rows tall, the same way as
+/// [`render_price_chart`] but without trade markers. Empty when there are no
+/// points.
+pub fn render_line_chart(points: &[(String, Decimal)], width: usize, height: usize) -> String {
+    let dates: Vec<&str> = points.iter().map(|(date, _)| date.as_str()).collect();
+    let values: Vec<Decimal> = points.iter().map(|(_, value)| *value).collect();
+    render_line(&dates, &[(&values, LINE)], &[], width, height)
+}
+
+/// Draws two series over the same dates on one scale, the first in `•` and
+/// the second in `○`, with `◉` where they meet. Otherwise like
+/// [`render_line_chart`].
+pub fn render_comparison_chart(
+    points: &[(String, Decimal, Decimal)],
+    width: usize,
+    height: usize,
+) -> String {
+    let dates: Vec<&str> = points.iter().map(|(date, _, _)| date.as_str()).collect();
+    let first: Vec<Decimal> = points.iter().map(|(_, value, _)| *value).collect();
+    let second: Vec<Decimal> = points.iter().map(|(_, _, value)| *value).collect();
+    render_line(
+        &dates,
+        &[(&first, LINE), (&second, SECOND_LINE)],
+        &[],
+        width,
+        height,
+    )
+}
+
+// Plots each series of values, drawn in its character, against `dates`,
+// putting each mark — an index into the first series and its character — on
+// that line in the value's column. Two different marks in one column show as
+// `X`.
+fn render_line(
+    dates: &[&str],
+    series: &[(&[Decimal], char)],
+    marks: &[(usize, char)],
+    width: usize,
+    height: usize,
+) -> String {
+    if dates.is_empty() || width == 0 || height == 0 {
+        return String::new();
+    }
+    let n = dates.len();
+    let columns = width.min(n);
+    // Column `c` covers values `c * n / columns` up to the next column's first.
+    let shown: Vec<(Vec<Decimal>, char)> = series
+        .iter()
+        .map(|(values, glyph)| {
+            let shown = (0..columns)
+                .map(|c| values[(c + 1) * n / columns - 1])
+                .collect();
+            (shown, *glyph)
+        })
+        .collect();
+    let all = || shown.iter().flat_map(|(values, _)| values.iter().copied());
+    let high = all().max().unwrap_or_default();
+    let low = all().min().unwrap_or_default();
+    let row_of = scale(high, low, height);
+
+    let mut grid = vec![vec![' '; columns]; height];
+    for (values, glyph) in &shown {
+        for (c, value) in values.iter().enumerate() {
+            let cell = &mut grid[row_of(*value)][c];
+            *cell = if *cell == ' ' || cell == glyph {
+                *glyph
+            } else {
+                OVERLAP
+            };
+        }
+    }
+    for &(k, mark) in marks {
+        let c = ((k + 1) * columns - 1) / n;
+        let cell = &mut grid[row_of(shown[0].0[c])][c];
+        *cell = match *cell {
+            LINE => mark,
+            existing if existing == mark => mark,
+            _ => 'X',
+        };
+    }
+
+    frame(&grid, high, low, dates[0], dates[n - 1])
+}
+
+// The row of a chart `height` rows tall, from `high` at the top to `low` at
+// the bottom, that a value falls on; values past either end are drawn on it.
+fn scale(high: Decimal, low: Decimal, height: usize) -> impl Fn(Decimal) -> usize {
+    move |value| {
+        if high == low {
+            return height / 2;
+        }
+        let scaled = (high - value) / (high - low) * Decimal::from(height - 1);
+        scaled.round().to_usize().unwrap_or(0).min(height - 1)
+    }
+}
+
+// Puts the plotted `grid` between the price labels and the date axis.
+fn frame(grid: &[Vec<char>], high: Decimal, low: Decimal, first: &str, last: &str) -> String {
+    let (height, columns) = (grid.len(), grid[0].len());
+    let mut lines = Vec::with_capacity(height + 2);
+    for (r, row) in grid.iter().enumerate() {
+        let label = if r == 0 {
+            format!("{:.2}", high)
+        } else if r == height - 1 {
+            format!("{:.2}", low)
+        } else {
+            String::new()
+        };
+        let line: String = row.iter().collect();
+        lines.push(format!(
+            "{:>w$} │{}",
+            label,
+            line.trim_end(),
+            w = LABEL_WIDTH
+        ));
+    }
+    lines.push(format!(
+        "{:>w$} └{}",
+        "",
+        "─".repeat(columns),
+        w = LABEL_WIDTH
+    ));
+    let gap = (columns + 1)
+        .saturating_sub(first.len() + last.len())
+        .max(1);
+    lines.push(format!(
+        "{:>w$}{}{}{}",
+        "",
+        first,
+        " ".repeat(gap),
+        last,
+        w = LABEL_WIDTH + 1
+    ));
+    lines.join("\n")
+}
+
+/// Draws one labelled bar per row, `width` columns across both sides of the
+/// axis, scaled so the largest amount fills its side. Each row ends with its
+/// amount. Empty when there are no rows.
+pub fn render_bar_chart(rows: &[(String, Decimal)], width: usize) -> String {
+    let label_width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+    let half = width / 2;
+    let largest = rows
+        .iter()
+        .map(|(_, amount)| amount.abs())
+        .max()
+        .unwrap_or_default();
+    let length = |amount: Decimal| -> usize {
+        if largest == Decimal::ZERO {
+            return 0;
+        }
+        (amount.abs() / largest * Decimal::from(half))
+            .round()
+            .to_usize()
+            .unwrap_or(0)
+    };
+    rows.iter()
+        .map(|(label, amount)| {
+            let bar = length(*amount);
+            let (left, right) = if *amount < Decimal::ZERO {
+                (
+                    format!("{}{}", " ".repeat(half - bar), "▒".repeat(bar)),
+                    " ".repeat(half),
+                )
+            } else {
+                (
+                    " ".repeat(half),
+                    format!("{}{}", "█".repeat(bar), " ".repeat(half - bar)),
+                )
+            };
+            format!(
+                "{:<w$} {}│{} ${:.2}",
+                label,
+                left,
+                right,
+                amount,
+                w = label_width
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draws `values` (oldest first) as one block per value, from `▁` for the
+/// lowest to `█` for the highest; a flat series sits on the bottom. Only the
+/// last `width` values are drawn.
+pub fn render_sparkline(values: &[Decimal], width: usize) -> String {
+    let values = &values[values.len().saturating_sub(width)..];
+    let low = values.iter().min().copied().unwrap_or_default();
+    let high = values.iter().max().copied().unwrap_or_default();
+    let top = Decimal::from(SPARKS.len() - 1);
+    values
+        .iter()
+        .map(|value| {
+            if high == low {
+                return SPARKS[0];
+            }
+            let level = ((*value - low) / (high - low) * top).round();
+            SPARKS[level.to_usize().unwrap_or(0).min(SPARKS.len() - 1)]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Action;
+    use rust_decimal_macros::dec;
+
+    fn bar(date: &str, close: Decimal) -> PriceBar {
+        PriceBar {
+            symbol: "AAPL".to_string(),
+            date: date.to_string(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 0,
+        }
+    }
+
+    fn trade(date: &str, action: Action) -> Trade {
+        Trade {
+            symbol: "AAPL".to_string(),
+            action,
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    // The plotted rows, without labels or the date axis.
+    fn plot(chart: &str) -> Vec<String> {
+        chart
+            .lines()
+            .filter_map(|line| line.split_once('│'))
+            .map(|(_, row)| row.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn plots_closes_with_trade_markers() {
+        let bars = vec![
+            bar("2024-06-03", dec!(100)),
+            bar("2024-06-04", dec!(110)),
+            bar("2024-06-05", dec!(120)),
+        ];
+        let trades = vec![
+            trade("2024-06-03", Action::BuyToOpen),
+            trade("2024-06-04", Action::SellToClose),
+            // Before the first bar, so left out.
+            trade("2024-06-02", Action::BuyToOpen),
+        ];
+        let chart = render_price_chart(&bars, &trades, 80, 3);
+        assert_eq!(plot(&chart), vec!["  •", " S", "B"]);
+        assert!(chart.starts_with("    120.00 │"));
+        assert!(chart.contains("    100.00 │B"));
+        assert!(chart.ends_with("2024-06-03 2024-06-05"));
+    }
+
+    #[test]
+    fn groups_days_when_narrower_than_the_history() {
+        let bars: Vec<PriceBar> = (1..=6)
+            .map(|day| bar(&format!("2024-06-{:02}", day), Decimal::from(day)))
+            .collect();
+        let trades = vec![
+            trade("2024-06-03", Action::BuyToOpen),
+            trade("2024-06-04", Action::SellToOpen),
+        ];
+        // Columns show days 2, 4, and 6; both trades fall in the middle one.
+        let chart = render_price_chart(&bars, &trades, 3, 3);
+        assert_eq!(plot(&chart), vec!["  •", " X", "•"]);
+        assert!(render_price_chart(&[], &trades, 3, 3).is_empty());
+    }
+
+    #[test]
+    fn plots_a_dated_series() {
+        let points = vec![
+            ("2024-06-03".to_string(), dec!(-50)),
+            ("2024-06-10".to_string(), dec!(25)),
+            ("2024-06-17".to_string(), dec!(100)),
+        ];
+        let chart = render_line_chart(&points, 80, 3);
+        assert_eq!(plot(&chart), vec!["  •", " •", "•"]);
+        assert!(chart.contains("    -50.00 │•"));
+        assert!(render_line_chart(&[], 80, 3).is_empty());
+    }
+
+    #[test]
+    fn plots_two_series_on_one_scale() {
+        let points = vec![
+            ("2024-04".to_string(), dec!(0), dec!(0)),
+            ("2024-05".to_string(), dec!(2), dec!(4)),
+            ("2024-06".to_string(), dec!(4), dec!(2)),
+        ];
+        let chart = render_comparison_chart(&points, 80, 3);
+        assert_eq!(plot(&chart), vec![" ○•", " •○", "◉"]);
+        assert!(chart.starts_with("      4.00 │"));
+    }
+
+    #[test]
+    fn bars_extend_either_side_of_the_axis() {
+        let rows = vec![
+            ("2024-05".to_string(), dec!(200)),
+            ("2024-06".to_string(), dec!(-100)),
+            ("2024-07".to_string(), Decimal::ZERO),
+        ];
+        assert_eq!(
+            render_bar_chart(&rows, 8),
+            "2024-05     │████ $200.00\n\
+             2024-06   ▒▒│     $-100.00\n\
+             2024-07     │     $0.00"
+        );
+        assert!(render_bar_chart(&[], 8).is_empty());
+    }
+
+    #[test]
+    fn sparks_span_the_lowest_to_the_highest() {
+        let values = [dec!(-100), dec!(0), dec!(50), dec!(250)];
+        assert_eq!(render_sparkline(&values, 10), "▁▃▄█");
+        assert_eq!(render_sparkline(&values, 2), "▁█");
+        assert_eq!(render_sparkline(&[dec!(5), dec!(5)], 10), "▁▁");
+        assert_eq!(render_sparkline(&[], 10), "");
+    }
+
+    #[test]
+    fn candles_show_wicks_bodies_and_fills() {
+        let candle = |date: &str, open, high, low, close| PriceBar {
+            open,
+            high,
+            low,
+            ..bar(date, close)
+        };
+        let bars = vec![
+            candle("2024-06-03", dec!(101), dec!(104), dec!(100), dec!(103)),
+            candle("2024-06-04", dec!(103), dec!(103), dec!(100), dec!(101)),
+        ];
+        let mut bought = trade("2024-06-03", Action::BuyToOpen);
+        bought.price = dec!(102);
+        let chart = render_candlestick_chart(&bars, &[bought], 80, 5);
+        assert_eq!(plot(&chart), vec!["│", "█░", "B░", "█░", "││"]);
+        assert!(chart.starts_with("    104.00 │"));
+
+        // Two days to a column merge into one candle.
+        let merged = render_candlestick_chart(&bars, &[], 1, 5);
+        assert_eq!(plot(&merged), vec!["│", "│", "│", "█", "│"]);
+    }
+
+    #[test]
+    fn windows_zoom_and_pan_within_the_history() {
+        let window = ChartWindow::latest(100, 40);
+        assert_eq!(window.range(), 60..100);
+        let zoomed = window.zoom_in();
+        assert_eq!(zoomed.range(), 80..100);
+        assert_eq!(zoomed.zoom_in().zoom_in().span, 10);
+        assert_eq!(zoomed.pan(-30, 100).range(), 50..70);
+        assert_eq!(zoomed.pan(isize::MIN, 100).range(), 0..20);
+        assert_eq!(zoomed.pan(5, 100).range(), 80..100);
+        assert_eq!(zoomed.pan(isize::MIN, 100).zoom_out(100).range(), 0..40);
+        assert_eq!(window.zoom_out(100).zoom_out(100).range(), 0..100);
+        assert_eq!(ChartWindow::latest(5, 40).range(), 0..5);
+    }
+}
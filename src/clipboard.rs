@@ -0,0 +1,28 @@
+//! Copying text to the system clipboard.
+//!
+//! The text goes through `arboard`, which talks to the clipboard directly —
+//! the Win32 clipboard as Unicode, the macOS pasteboard, and on Linux and the
+//! BSDs Wayland's data-control protocol or X11 — so nothing needs to be
+//! installed alongside the app.
+
+use arboard::Clipboard;
+use std::cell::RefCell;
+
+thread_local! {
+    // The clipboard, opened on first use and kept open: on X11 the copied
+    // text is served by its owner, so it would vanish with a clipboard closed
+    // after each copy.
+    static CLIPBOARD: RefCell<Option<Clipboard>> = const { RefCell::new(None) };
+}
+
+/// Puts `text` on the clipboard.
+pub fn copy(text: &str) -> Result<(), String> {
+    CLIPBOARD.with(|clipboard| {
+        let mut clipboard = clipboard.borrow_mut();
+        let clipboard = match clipboard.as_mut() {
+            Some(clipboard) => clipboard,
+            None => clipboard.insert(Clipboard::new().map_err(|e| e.to_string())?),
+        };
+        clipboard.set_text(text).map_err(|e| e.to_string())
+    })
+}
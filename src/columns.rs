@@ -0,0 +1,273 @@
+//! Columns of the View/Edit Trades list.
+//!
+//! Which columns the list shows, in what order, and how wide each is are read
+//! from `columns` in the `[trade_list]` table of the settings file (see
+//! [`crate::config`]), so a narrow terminal can give its room to the comment
+//! and P/L rather than the fees and id. A column is named as
+//! [`TradeColumn`] spells it, with an optional width after a colon:
+//! `comment:30`. Text longer than its column is cut short with `…`.
+
+use crate::date::{days_to_expiration, format_dte};
+use crate::db::{OptionStatus, Trade, TradeType};
+use rust_decimal::Decimal;
+
+string_enum! {
+    /// A column the trade list can show.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TradeColumn {
+        Id => "id",
+        Date => "date",
+        Symbol => "symbol",
+        Type => "type",
+        Action => "action",
+        Price => "price",
+        Quantity => "quantity",
+        Fees => "fees",
+        /// An option's type, strike, expiration, multiplier, status, and days
+        /// left; where a stock row came from.
+        Option => "option",
+        /// The comment's first line.
+        Comment => "comment",
+        Tags => "tags",
+        /// Realized P/L of the lots the trade closes, as the list sorts by.
+        Pl => "p/l",
+        Broker => "broker",
+    }
+    error = "column",
+}
+
+impl TradeColumn {
+    pub fn title(&self) -> &'static str {
+        match self {
+            TradeColumn::Id => "ID",
+            TradeColumn::Date => "Date",
+            TradeColumn::Symbol => "Symbol",
+            TradeColumn::Type => "Type",
+            TradeColumn::Action => "Action",
+            TradeColumn::Price => "Price",
+            TradeColumn::Quantity => "Qty",
+            TradeColumn::Fees => "Fees",
+            TradeColumn::Option => "Option",
+            TradeColumn::Comment => "Comment",
+            TradeColumn::Tags => "Tags",
+            TradeColumn::Pl => "P/L",
+            TradeColumn::Broker => "Broker",
+        }
+    }
+
+    /// The width the column has unless the settings give another.
+    pub fn default_width(&self) -> usize {
+        match self {
+            TradeColumn::Id => 5,
+            TradeColumn::Date => 10,
+            TradeColumn::Symbol => 6,
+            TradeColumn::Type => 6,
+            TradeColumn::Action => 13,
+            TradeColumn::Price => 9,
+            TradeColumn::Quantity => 6,
+            TradeColumn::Fees => 7,
+            TradeColumn::Option => 38,
+            TradeColumn::Comment => 24,
+            TradeColumn::Tags => 16,
+            TradeColumn::Pl => 10,
+            TradeColumn::Broker => 12,
+        }
+    }
+
+    // Amounts line up on the right.
+    fn numeric(&self) -> bool {
+        matches!(
+            self,
+            TradeColumn::Price | TradeColumn::Quantity | TradeColumn::Fees | TradeColumn::Pl
+        )
+    }
+
+    // The column's text for `trade`; `pl` is its realized P/L, if any.
+    fn cell(&self, trade: &Trade, today: &str, pl: Option<Decimal>) -> String {
+        match self {
+            TradeColumn::Id => format!("#{}", trade.id.unwrap_or(0)),
+            TradeColumn::Date => trade.date.clone(),
+            TradeColumn::Symbol => trade.symbol.clone(),
+            TradeColumn::Type => trade.trade_type.to_string(),
+            TradeColumn::Action => trade.action.to_string(),
+            TradeColumn::Price => format!("${:.2}", trade.price),
+            TradeColumn::Quantity => trade.quantity.normalize().to_string(),
+            TradeColumn::Fees => format!("${:.2}", trade.fees),
+            TradeColumn::Option => option_cell(trade, today),
+            TradeColumn::Comment => trade.comment.lines().next().unwrap_or("").to_string(),
+            TradeColumn::Tags => trade.tags.join(", "),
+            TradeColumn::Pl => pl.map(|pl| format!("${:.2}", pl)).unwrap_or_default(),
+            TradeColumn::Broker => trade.broker.clone().unwrap_or_default(),
+        }
+    }
+}
+
+/// A column and its width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Column {
+    pub column: TradeColumn,
+    pub width: usize,
+}
+
+impl From<TradeColumn> for Column {
+    fn from(column: TradeColumn) -> Self {
+        Column {
+            column,
+            width: column.default_width(),
+        }
+    }
+}
+
+/// The columns shown when the settings name none.
+pub fn default_columns() -> Vec<Column> {
+    use TradeColumn::*;
+    [
+        Id, Symbol, Type, Action, Price, Quantity, Date, Fees, Option, Tags,
+    ]
+    .into_iter()
+    .map(Column::from)
+    .collect()
+}
+
+/// Reads `name` or `name:width`.
+pub fn parse_column(text: &str) -> Result<Column, String> {
+    let (name, width) = match text.split_once(':') {
+        Some((name, width)) => (name, Some(width)),
+        None => (text, None),
+    };
+    let column: TradeColumn = name.trim().parse()?;
+    let width = match width {
+        None => column.default_width(),
+        Some(width) => width
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|width| *width > 0)
+            .ok_or_else(|| format!("Invalid width for {}: {}", column, width))?,
+    };
+    Ok(Column { column, width })
+}
+
+/// The column titles, spaced as [`format_row`] spaces the cells.
+pub fn format_header(columns: &[Column]) -> String {
+    layout(columns, |column| column.title().to_string())
+}
+
+/// `trade`'s row, with `pl` for its P/L column.
+pub fn format_row(columns: &[Column], trade: &Trade, today: &str, pl: Option<Decimal>) -> String {
+    layout(columns, |column| column.cell(trade, today, pl))
+}
+
+fn layout(columns: &[Column], text: impl Fn(&TradeColumn) -> String) -> String {
+    let cells: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            let text = fit(&text(&column.column), column.width);
+            if column.column.numeric() {
+                format!("{:>w$}", text, w = column.width)
+            } else {
+                format!("{:<w$}", text, w = column.width)
+            }
+        })
+        .collect();
+    cells.join(" ").trim_end().to_string()
+}
+
+/// `text` cut to `width` characters, ending in `…` when cut.
+pub(crate) fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+// The option's contract and state as the default row shows it; for stock,
+// the option it was assigned from.
+fn option_cell(trade: &Trade, today: &str) -> String {
+    if trade.trade_type != TradeType::Option {
+        return trade
+            .assigned_from
+            .map(|id| format!("auto from option #{}", id))
+            .unwrap_or_default();
+    }
+    let status = trade.status.clone().unwrap_or(OptionStatus::Open);
+    let mut cell = format!(
+        "{} {} x{} exp {} {}",
+        trade
+            .option_type
+            .map(|t| t.as_str().to_uppercase())
+            .unwrap_or_default(),
+        trade
+            .strike
+            .map(|s| format!("${:.2}", s))
+            .unwrap_or_else(|| "?".to_string()),
+        trade.multiplier().normalize(),
+        trade.expiration.as_deref().unwrap_or(""),
+        status
+    );
+    let expiration = trade.expiration.as_deref();
+    if status == OptionStatus::Open {
+        if let Some(dte) = expiration.and_then(|exp| days_to_expiration(today, exp)) {
+            cell.push_str(&format!(", {}", format_dte(dte)));
+        }
+    }
+    if let Some(group) = trade.roll_group_id {
+        cell.push_str(&format!(" roll #{}", group));
+    }
+    if let Some(strategy) = trade.strategy_id {
+        cell.push_str(&format!(" strategy #{}", strategy));
+    }
+    if status == OptionStatus::Open && expiration.is_some_and(|exp| exp < today) {
+        cell.push_str(" UNRESOLVED");
+    }
+    cell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn lays_out_the_chosen_columns() {
+        let columns: Vec<Column> = ["date", "symbol:4", "option", "comment:8", "p/l"]
+            .into_iter()
+            .map(|text| parse_column(text).unwrap())
+            .collect();
+        let put = Trade {
+            id: Some(7),
+            symbol: "GOOGL".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::BuyToClose,
+            date: "2024-06-03".to_string(),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(150)),
+            expiration: Some("2024-06-21".to_string()),
+            status: Some(OptionStatus::Closed),
+            comment: "rolled down and out\nsecond line".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_header(&columns),
+            format!("Date       Sym… {:<38} Comment         P/L", "Option")
+        );
+        assert_eq!(
+            format_row(&columns, &put, "2024-06-03", Some(dec!(-42.5))),
+            format!(
+                "2024-06-03 GOO… {:<38} rolled …    $-42.50",
+                "PUT $150.00 x100 exp 2024-06-21 closed"
+            )
+        );
+        assert_eq!(
+            parse_column("fees:0"),
+            Err("Invalid width for fees: 0".to_string())
+        );
+        assert_eq!(
+            parse_column("delta"),
+            Err("Invalid column: delta".to_string())
+        );
+    }
+}
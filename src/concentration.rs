@@ -0,0 +1,135 @@
+//! How much capital each underlying ties up.
+//!
+//! Capital is counted two ways: at cost (what was paid for longs, or received
+//! for shorts) and at market value (at the marks, or at cost for a position
+//! without one). Both are per underlying, so a stock and its options add up.
+//! A short put ties up the cash to buy the shares if assigned — strike times
+//! shares — which counts the same both ways. A short call adds nothing: the
+//! shares covering it are counted already, and margin for a naked call isn't
+//! modelled.
+
+use crate::db::OptionType;
+use crate::valuation::PositionValue;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// Capital tied up in one underlying, and its share of the portfolio's.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolExposure {
+    pub symbol: String,
+    pub cost: Decimal,
+    pub market: Decimal,
+    /// The part of `cost` and `market` set aside for short puts.
+    pub collateral: Decimal,
+    pub cost_percent: Decimal,
+    pub market_percent: Decimal,
+}
+
+impl SymbolExposure {
+    /// Whether either share of capital is over `threshold` percent.
+    pub fn exceeds(&self, threshold: Decimal) -> bool {
+        self.cost_percent > threshold || self.market_percent > threshold
+    }
+}
+
+/// Capital tied up per underlying across `positions`, largest market value
+/// first.
+pub fn concentration(positions: &[PositionValue]) -> Vec<SymbolExposure> {
+    let mut by_symbol: BTreeMap<&str, SymbolExposure> = BTreeMap::new();
+    for value in positions {
+        let position = &value.position;
+        let exposure = by_symbol
+            .entry(&position.key.symbol)
+            .or_insert_with(|| SymbolExposure {
+                symbol: position.key.symbol.clone(),
+                ..Default::default()
+            });
+        match (position.key.option_type, position.is_short()) {
+            (Some(OptionType::Put), true) => {
+                let strike = position.key.strike.unwrap_or_default();
+                let collateral = strike * position.quantity.abs() * position.multiplier;
+                exposure.cost += collateral;
+                exposure.market += collateral;
+                exposure.collateral += collateral;
+            }
+            (Some(OptionType::Call), true) => {}
+            _ => {
+                exposure.cost += position.cost_basis();
+                exposure.market += value.market_value.abs();
+            }
+        }
+    }
+
+    let total_cost: Decimal = by_symbol.values().map(|e| e.cost).sum();
+    let total_market: Decimal = by_symbol.values().map(|e| e.market).sum();
+    let percent = |part: Decimal, total: Decimal| {
+        if total.is_zero() {
+            Decimal::ZERO
+        } else {
+            part / total * Decimal::ONE_HUNDRED
+        }
+    };
+    let mut exposures: Vec<SymbolExposure> = by_symbol
+        .into_values()
+        .map(|mut e| {
+            e.cost_percent = percent(e.cost, total_cost);
+            e.market_percent = percent(e.market, total_market);
+            e
+        })
+        .collect();
+    exposures.sort_by(|a, b| b.market.cmp(&a.market).then(a.symbol.cmp(&b.symbol)));
+    exposures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionType, Trade, TradeType};
+    use crate::valuation::value_portfolio;
+    use rust_decimal_macros::dec;
+    use std::collections::HashMap;
+
+    fn option(id: i64, symbol: &str, option_type: OptionType, strike: Decimal) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: symbol.to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            option_type: Some(option_type),
+            strike: Some(strike),
+            expiration: Some("2099-01-15".to_string()),
+            price: dec!(2),
+            quantity: dec!(1),
+            date: "2024-06-03".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn counts_stock_options_and_put_collateral_per_underlying() {
+        let trades = vec![
+            Trade {
+                id: Some(1),
+                symbol: "AAPL".to_string(),
+                action: Action::BuyToOpen,
+                price: dec!(150),
+                quantity: dec!(100),
+                date: "2024-06-03".to_string(),
+                ..Default::default()
+            },
+            option(2, "AAPL", OptionType::Call, dec!(170)),
+            option(3, "KO", OptionType::Put, dec!(50)),
+        ];
+        let valuation = value_portfolio(&trades, &HashMap::new(), "2024-06-10");
+        let exposures = concentration(&valuation.positions);
+        assert_eq!(exposures.len(), 2);
+        assert_eq!(exposures[0].symbol, "AAPL");
+        assert_eq!(exposures[0].cost, dec!(15000));
+        assert_eq!(exposures[0].collateral, Decimal::ZERO);
+        assert_eq!(exposures[0].market_percent, dec!(75));
+        assert_eq!(exposures[1].collateral, dec!(5000));
+        assert_eq!(exposures[1].cost_percent, dec!(25));
+        assert!(exposures[0].exceeds(dec!(50)));
+        assert!(!exposures[1].exceeds(dec!(25)));
+    }
+}
@@ -0,0 +1,572 @@
+//! User settings read from a TOML file.
+//!
+//! The file is optional: a missing file, and any setting left out of it, fall
+//! back to the defaults. Settings are grouped into tables:
+//!
+//! ```toml
+//! [quotes]
+//! # Minutes a fetched quote is reused before the provider is asked again.
+//! ttl_minutes = 15
+//!
+//! [market_data]
+//! # Providers asked in order until one answers: yahoo, polygon, finnhub, csv.
+//! providers = ["yahoo", "polygon"]
+//! # Never touch the network; only the csv provider is asked.
+//! offline = false
+//!
+//! [market_data.polygon]
+//! api_key = "..."
+//!
+//! [market_data.finnhub]
+//! api_key = "..."
+//!
+//! [market_data.csv]
+//! # Lines of `instrument,price`.
+//! path = "quotes.csv"
+//!
+//! [export]
+//! # Directory report CSV files are written to.
+//! dir = "exports"
+//!
+//! [benchmark]
+//! # Index the portfolio's returns are compared against.
+//! symbol = "SPY"
+//! # Account capital that monthly P/L is taken as a return on.
+//! capital = 25000
+//!
+//! [concentration]
+//! # Percent of capital in one underlying that gets flagged.
+//! threshold = 25
+//!
+//! [backup]
+//! # Directory database snapshots are written to.
+//! dir = "backups"
+//! # Automatic snapshots kept; older ones are deleted.
+//! keep = 10
+//!
+//! [currency]
+//! # Currency reports convert trades into.
+//! base = "EUR"
+//!
+//! [theme]
+//! # Colors start from a built-in scheme: dark, light, or high-contrast.
+//! name = "light"
+//! # Any of text, background, title, selection, selection_text, profit,
+//! # loss, and warning may be overridden: "red", "light red", "#1e90ff", or
+//! # "default" for the terminal's own color.
+//! loss = "#c00000"
+//!
+//! [trade_list]
+//! # Columns of View/Edit Trades, in order, each with an optional width:
+//! # id, date, symbol, type, action, price, quantity, fees, option,
+//! # comment, tags, p/l, and broker.
+//! columns = ["date", "symbol", "option", "comment:30", "p/l"]
+//!
+//! [keys]
+//! # Keys for an action, replacing its defaults: a character, a sequence such
+//! # as "gg", a named key such as "pagedown", or "ctrl-" and a letter.
+//! page_down = ["ctrl-d", "pagedown"]
+//! undo = "ctrl-z"
+//!
+//! # One table per broker: what it charges per order, per option contract,
+//! # and per share, and the least it charges for an order.
+//! [commissions.tastytrade]
+//! per_contract = 1.00
+//! minimum = 0
+//! ```
+
+use crate::columns::{default_columns, parse_column, Column};
+use crate::db::DEFAULT_CURRENCY;
+use crate::fees::CommissionSchedule;
+use crate::fx::parse_currency;
+use crate::keymap::{parse_keys, KeyAction, Keymap};
+use crate::market_data::ProviderKind;
+use crate::theme::{parse_color, Theme};
+use rust_decimal::Decimal;
+use std::fs;
+use std::io::ErrorKind;
+use toml_edit::{DocumentMut, Item};
+
+/// Settings file looked up next to the database.
+pub const CONFIG_FILE: &str = "options_tracker.toml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Minutes a quote stays fresh; older ones are fetched again on refresh
+    /// and flagged as stale.
+    pub quote_ttl_minutes: i64,
+    /// Market data providers in priority order.
+    pub providers: Vec<ProviderKind>,
+    pub polygon_api_key: Option<String>,
+    pub finnhub_api_key: Option<String>,
+    /// Prices file read by the `csv` provider.
+    pub csv_path: Option<String>,
+    /// Start in offline mode: no network calls, marks come from the `csv`
+    /// provider or are entered by hand. Can be toggled while running.
+    pub offline: bool,
+    /// Directory report exports are written to.
+    pub export_dir: String,
+    /// Symbol whose buy-and-hold returns the portfolio is compared against.
+    pub benchmark_symbol: String,
+    /// Account capital, for turning realized P/L into a return.
+    pub capital: Option<Decimal>,
+    /// Percent of capital in one underlying above which it is flagged.
+    pub concentration_threshold: Decimal,
+    /// Directory database snapshots are written to and restored from.
+    pub backup_dir: String,
+    /// Automatic snapshots kept, newest first.
+    pub backup_keep: usize,
+    /// Currency totals across trades are converted into.
+    pub base_currency: String,
+    /// Commission schedules by broker, in the order they are listed.
+    pub commissions: Vec<CommissionSchedule>,
+    /// Colors the interface is drawn in.
+    pub theme: Theme,
+    /// Keys bound to movement, undo, and redo on every screen.
+    pub keys: Keymap,
+    /// Columns the trade list shows, in order.
+    pub trade_columns: Vec<Column>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            quote_ttl_minutes: 15,
+            providers: vec![ProviderKind::Yahoo],
+            polygon_api_key: None,
+            finnhub_api_key: None,
+            csv_path: None,
+            offline: false,
+            export_dir: ".".to_string(),
+            benchmark_symbol: "SPY".to_string(),
+            capital: None,
+            concentration_threshold: Decimal::from(25),
+            backup_dir: "backups".to_string(),
+            backup_keep: 10,
+            base_currency: DEFAULT_CURRENCY.to_string(),
+            commissions: Vec::new(),
+            theme: Theme::default(),
+            keys: Keymap::default(),
+            trade_columns: default_columns(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the settings at `path`, or the defaults when there is no file.
+    pub fn load(path: &str) -> Result<Config, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("{}: {}", path, e)),
+        }
+    }
+
+    /// Parses settings from TOML text.
+    pub fn parse(text: &str) -> Result<Config, String> {
+        let doc: DocumentMut = text.parse().map_err(|e| format!("{}", e))?;
+        let mut config = Config::default();
+        if let Some(ttl) = integer(doc.get("quotes"), "ttl_minutes")? {
+            if ttl < 0 {
+                return Err("quotes.ttl_minutes must not be negative".to_string());
+            }
+            config.quote_ttl_minutes = ttl;
+        }
+
+        let market_data = doc.get("market_data");
+        if let Some(item) = market_data.and_then(|table| table.get("providers")) {
+            let names = item
+                .as_array()
+                .ok_or_else(|| "market_data.providers must be a list".to_string())?;
+            config.providers = names
+                .iter()
+                .map(|name| {
+                    name.as_str()
+                        .ok_or_else(|| "market_data.providers must list names".to_string())?
+                        .parse()
+                })
+                .collect::<Result<_, String>>()?;
+            if config.providers.is_empty() {
+                return Err("market_data.providers must not be empty".to_string());
+            }
+        }
+        if let Some(item) = market_data.and_then(|table| table.get("offline")) {
+            config.offline = item
+                .as_bool()
+                .ok_or_else(|| "market_data.offline must be true or false".to_string())?;
+        }
+        let provider = |name: &str| market_data.and_then(|table| table.get(name));
+        config.polygon_api_key = string(provider("polygon"), "api_key")?;
+        config.finnhub_api_key = string(provider("finnhub"), "api_key")?;
+        config.csv_path = string(provider("csv"), "path")?;
+        if let Some(dir) = string(doc.get("export"), "dir")? {
+            config.export_dir = dir;
+        }
+        let benchmark = doc.get("benchmark");
+        if let Some(symbol) = string(benchmark, "symbol")? {
+            config.benchmark_symbol = symbol.to_uppercase();
+        }
+        config.capital = decimal(benchmark, "capital")?;
+        if config
+            .capital
+            .is_some_and(|capital| capital <= Decimal::ZERO)
+        {
+            return Err("benchmark.capital must be positive".to_string());
+        }
+        if let Some(threshold) = decimal(doc.get("concentration"), "threshold")? {
+            if threshold <= Decimal::ZERO || threshold > Decimal::ONE_HUNDRED {
+                return Err("concentration.threshold must be between 0 and 100".to_string());
+            }
+            config.concentration_threshold = threshold;
+        }
+        let backup = doc.get("backup");
+        if let Some(dir) = string(backup, "dir")? {
+            config.backup_dir = dir;
+        }
+        if let Some(keep) = integer(backup, "keep")? {
+            if keep < 1 {
+                return Err("backup.keep must be at least 1".to_string());
+            }
+            config.backup_keep = keep as usize;
+        }
+        if let Some(base) = string(doc.get("currency"), "base")? {
+            config.base_currency =
+                parse_currency(&base).map_err(|e| format!("currency.base: {}", e))?;
+        }
+        if let Some(item) = doc.get("commissions") {
+            let brokers = item
+                .as_table_like()
+                .ok_or_else(|| "commissions must be a table of brokers".to_string())?;
+            for (broker, schedule) in brokers.iter() {
+                let amount = |key: &str| -> Result<Decimal, String> {
+                    let amount = decimal(Some(schedule), key)
+                        .map_err(|e| format!("commissions.{}: {}", broker, e))?
+                        .unwrap_or_default();
+                    if amount < Decimal::ZERO {
+                        return Err(format!(
+                            "commissions.{}.{} must not be negative",
+                            broker, key
+                        ));
+                    }
+                    Ok(amount)
+                };
+                config.commissions.push(CommissionSchedule {
+                    broker: broker.to_string(),
+                    per_order: amount("per_order")?,
+                    per_contract: amount("per_contract")?,
+                    per_share: amount("per_share")?,
+                    minimum: amount("minimum")?,
+                });
+            }
+        }
+        let theme = doc.get("theme");
+        if let Some(name) = string(theme, "name")? {
+            config.theme = Theme::named(name.parse()?);
+        }
+        for (key, color) in config.theme.colors_mut() {
+            if let Some(name) = string(theme, key)? {
+                *color = parse_color(&name)
+                    .ok_or_else(|| format!("theme.{}: Invalid color: {}", key, name))?;
+            }
+        }
+        if let Some(item) = doc.get("trade_list").and_then(|table| table.get("columns")) {
+            config.trade_columns = item
+                .as_array()
+                .and_then(|columns| columns.iter().map(|column| column.as_str()).collect())
+                .ok_or_else(|| "trade_list.columns must be a list of columns".to_string())
+                .and_then(|columns: Vec<&str>| {
+                    columns
+                        .into_iter()
+                        .map(parse_column)
+                        .collect::<Result<Vec<_>, String>>()
+                        .map_err(|e| format!("trade_list.columns: {}", e))
+                })?;
+            if config.trade_columns.is_empty() {
+                return Err("trade_list.columns must not be empty".to_string());
+            }
+        }
+        if let Some(item) = doc.get("keys") {
+            let actions = item
+                .as_table_like()
+                .ok_or_else(|| "keys must be a table of actions".to_string())?;
+            for (action, keys) in actions.iter() {
+                let action: KeyAction = action.parse()?;
+                let keys: Vec<&str> = match keys.as_str() {
+                    Some(key) => vec![key],
+                    None => keys
+                        .as_array()
+                        .and_then(|keys| keys.iter().map(|key| key.as_str()).collect())
+                        .ok_or_else(|| {
+                            format!("keys.{} must be a key or a list of keys", action)
+                        })?,
+                };
+                let keys = keys
+                    .into_iter()
+                    .map(parse_keys)
+                    .collect::<Result<_, String>>()
+                    .map_err(|e| format!("keys.{}: {}", action, e))?;
+                config.keys.bind(action, keys);
+            }
+        }
+        for kind in &config.providers {
+            let missing = match kind {
+                ProviderKind::Yahoo => None,
+                ProviderKind::Polygon => config
+                    .polygon_api_key
+                    .is_none()
+                    .then_some("market_data.polygon.api_key"),
+                ProviderKind::Finnhub => config
+                    .finnhub_api_key
+                    .is_none()
+                    .then_some("market_data.finnhub.api_key"),
+                ProviderKind::Csv => config.csv_path.is_none().then_some("market_data.csv.path"),
+            };
+            if let Some(key) = missing {
+                return Err(format!("the {} provider needs {}", kind, key));
+            }
+        }
+        Ok(config)
+    }
+
+    /// Quote freshness in seconds.
+    pub fn quote_ttl_secs(&self) -> i64 {
+        self.quote_ttl_minutes * 60
+    }
+}
+
+// Reads `table.key` as an integer; `None` when either is absent.
+fn integer(table: Option<&Item>, key: &str) -> Result<Option<i64>, String> {
+    match table.and_then(|table| table.get(key)) {
+        None => Ok(None),
+        Some(item) => item
+            .as_integer()
+            .map(Some)
+            .ok_or_else(|| format!("{} must be a whole number", key)),
+    }
+}
+
+// Reads `table.key` as a whole or decimal number; `None` when either is
+// absent.
+fn decimal(table: Option<&Item>, key: &str) -> Result<Option<Decimal>, String> {
+    match table.and_then(|table| table.get(key)) {
+        None => Ok(None),
+        Some(item) => item
+            .as_integer()
+            .map(Decimal::from)
+            .or_else(|| item.as_float().and_then(|f| Decimal::try_from(f).ok()))
+            .map(Some)
+            .ok_or_else(|| format!("{} must be a number", key)),
+    }
+}
+
+// Reads `table.key` as a string; `None` when either is absent.
+fn string(table: Option<&Item>, key: &str) -> Result<Option<String>, String> {
+    match table.and_then(|table| table.get(key)) {
+        None => Ok(None),
+        Some(item) => item
+            .as_str()
+            .map(|s| Some(s.to_string()))
+            .ok_or_else(|| format!("{} must be a string", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_settings_use_defaults() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(
+            Config::load("does/not/exist.toml").unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn reads_quote_ttl() {
+        let config = Config::parse("[quotes]\nttl_minutes = 60\n").unwrap();
+        assert_eq!(config.quote_ttl_minutes, 60);
+        assert_eq!(config.quote_ttl_secs(), 3600);
+        assert!(Config::parse("[quotes]\nttl_minutes = \"soon\"\n").is_err());
+        assert!(Config::parse("[quotes]\nttl_minutes = -1\n").is_err());
+        assert!(Config::parse("[quotes\n").is_err());
+    }
+
+    #[test]
+    fn reads_providers_and_their_settings() {
+        let config = Config::parse(
+            "[market_data]\nproviders = [\"finnhub\", \"Yahoo\"]\n\
+             [market_data.finnhub]\napi_key = \"secret\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.providers,
+            vec![ProviderKind::Finnhub, ProviderKind::Yahoo]
+        );
+        assert_eq!(config.finnhub_api_key.as_deref(), Some("secret"));
+
+        assert_eq!(
+            Config::parse("[market_data]\nproviders = [\"bloomberg\"]\n"),
+            Err("Invalid provider: bloomberg".to_string())
+        );
+        assert_eq!(
+            Config::parse("[market_data]\nproviders = [\"polygon\"]\n"),
+            Err("the polygon provider needs market_data.polygon.api_key".to_string())
+        );
+        assert!(Config::parse("[market_data]\nproviders = []\n").is_err());
+
+        assert!(
+            Config::parse("[market_data]\noffline = true\n")
+                .unwrap()
+                .offline
+        );
+        assert!(Config::parse("[market_data]\noffline = 1\n").is_err());
+    }
+
+    #[test]
+    fn reads_export_dir() {
+        assert_eq!(Config::default().export_dir, ".");
+        let config = Config::parse("[export]\ndir = \"exports\"\n").unwrap();
+        assert_eq!(config.export_dir, "exports");
+    }
+
+    #[test]
+    fn reads_benchmark() {
+        let config = Config::parse("[benchmark]\nsymbol = \"qqq\"\ncapital = 25000.5\n").unwrap();
+        assert_eq!(config.benchmark_symbol, "QQQ");
+        assert_eq!(config.capital, Some(Decimal::new(250005, 1)));
+        assert_eq!(Config::default().benchmark_symbol, "SPY");
+        assert!(Config::parse("[benchmark]\ncapital = \"lots\"\n").is_err());
+        assert!(Config::parse("[benchmark]\ncapital = 0\n").is_err());
+    }
+
+    #[test]
+    fn reads_concentration_threshold() {
+        assert_eq!(Config::default().concentration_threshold, Decimal::from(25));
+        let config = Config::parse("[concentration]\nthreshold = 12.5\n").unwrap();
+        assert_eq!(config.concentration_threshold, Decimal::new(125, 1));
+        assert!(Config::parse("[concentration]\nthreshold = 150\n").is_err());
+    }
+
+    #[test]
+    fn reads_backup_settings() {
+        let config = Config::parse("[backup]\ndir = \"/mnt/backups\"\nkeep = 3\n").unwrap();
+        assert_eq!(config.backup_dir, "/mnt/backups");
+        assert_eq!(config.backup_keep, 3);
+        assert_eq!(Config::default().backup_keep, 10);
+        assert!(Config::parse("[backup]\nkeep = 0\n").is_err());
+    }
+
+    #[test]
+    fn reads_base_currency() {
+        assert_eq!(Config::default().base_currency, "USD");
+        let config = Config::parse("[currency]\nbase = \"eur\"\n").unwrap();
+        assert_eq!(config.base_currency, "EUR");
+        assert!(Config::parse("[currency]\nbase = \"euro\"\n").is_err());
+    }
+
+    #[test]
+    fn reads_commission_schedules() {
+        let config = Config::parse(
+            "[commissions.Schwab]\nper_contract = 0.65\n\n\
+             [commissions.IBKR]\nper_share = 0.005\nminimum = 1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.commissions,
+            [
+                CommissionSchedule {
+                    broker: "Schwab".to_string(),
+                    per_contract: Decimal::new(65, 2),
+                    ..Default::default()
+                },
+                CommissionSchedule {
+                    broker: "IBKR".to_string(),
+                    per_share: Decimal::new(5, 3),
+                    minimum: Decimal::ONE,
+                    ..Default::default()
+                },
+            ]
+        );
+        assert!(Config::parse("[commissions.Schwab]\nper_contract = -1\n").is_err());
+        assert!(Config::parse("[commissions.Schwab]\nminimum = \"one\"\n").is_err());
+    }
+
+    #[test]
+    fn reads_theme_and_color_overrides() {
+        use crate::theme::ThemeName;
+        use cursive::theme::{BaseColor, Color};
+
+        let config =
+            Config::parse("[theme]\nname = \"high-contrast\"\nloss = \"Light Magenta\"\n").unwrap();
+        assert_eq!(config.theme.name, ThemeName::HighContrast);
+        assert_eq!(config.theme.loss, Color::Light(BaseColor::Magenta));
+        assert_eq!(config.theme.title, Color::Light(BaseColor::Yellow));
+        assert_eq!(
+            Config::parse("[theme]\nprofit = \"#00FF00\"\n")
+                .unwrap()
+                .theme
+                .profit,
+            Color::Rgb(0, 255, 0)
+        );
+        assert!(Config::parse("[theme]\nname = \"solarized\"\n").is_err());
+        assert_eq!(
+            Config::parse("[theme]\nwarning = \"orange\"\n"),
+            Err("theme.warning: Invalid color: orange".to_string())
+        );
+    }
+
+    #[test]
+    fn reads_key_bindings() {
+        use cursive::event::{Event, Key};
+
+        let config =
+            Config::parse("[keys]\npage_down = [\"ctrl-d\", \"pagedown\"]\nundo = \"U\"\n")
+                .unwrap();
+        let mut pending = Vec::new();
+        let keys = &config.keys;
+        assert_eq!(
+            keys.feed(&mut pending, Event::Key(Key::PageDown)),
+            Some(KeyAction::PageDown)
+        );
+        assert_eq!(
+            keys.feed(&mut pending, Event::Char('U')),
+            Some(KeyAction::Undo)
+        );
+        assert_eq!(keys.feed(&mut pending, Event::Char('u')), None);
+        assert_eq!(
+            Config::parse("[keys]\nfly = \"f\"\n"),
+            Err("Invalid key action: fly".to_string())
+        );
+        assert!(Config::parse("[keys]\ndown = 1\n").is_err());
+        assert!(Config::parse("[keys]\ndown = \"ctrl-1\"\n").is_err());
+    }
+
+    #[test]
+    fn reads_trade_list_columns() {
+        use crate::columns::TradeColumn;
+
+        let config =
+            Config::parse("[trade_list]\ncolumns = [\"date\", \"comment:30\", \"P/L\"]\n").unwrap();
+        let columns: Vec<(TradeColumn, usize)> = config
+            .trade_columns
+            .iter()
+            .map(|column| (column.column, column.width))
+            .collect();
+        assert_eq!(
+            columns,
+            [
+                (TradeColumn::Date, 10),
+                (TradeColumn::Comment, 30),
+                (TradeColumn::Pl, 10)
+            ]
+        );
+        assert_eq!(
+            Config::parse("[trade_list]\ncolumns = [\"delta\"]\n"),
+            Err("trade_list.columns: Invalid column: delta".to_string())
+        );
+        assert!(Config::parse("[trade_list]\ncolumns = []\n").is_err());
+        assert!(Config::parse("[trade_list]\ncolumns = \"date\"\n").is_err());
+    }
+}
@@ -0,0 +1,614 @@
+//! Cost-basis lot matching.
+//!
+//! Every opening trade creates a lot on its position's long or short side, and
+//! every closing trade consumes open lots on that side in the order chosen by a
+//! [`CostBasisMethod`]. Stock is netted as one signed share balance instead, so
+//! a sale of shares you don't hold opens a short and the later buy covers it.
+//! Each pairing becomes a [`LotMatch`] carrying its realized P/L. An option
+//! that was assigned, exercised, or expired is closed without a closing trade,
+//! so whatever is left of its lot is realized at a price of zero: a sold
+//! option keeps its premium, a bought one loses what it cost.
+//!
+//! Prices are per share with fees folded in, so realized P/L already accounts
+//! for the fees on both sides of a match.
+//!
+//! A match held for more than a year, from its lot's opening date to its close,
+//! is a long-term gain or loss; anything shorter is short-term.
+//!
+//! A closing trade can also name the lots it closes (specific identification)
+//! through [`LotOverride`]s. Those lots are consumed first; any quantity the
+//! overrides leave uncovered falls back to the method's order.
+
+use crate::db::{OptionStatus, PositionKey, Trade, TradeType};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+string_enum! {
+    /// Which open lots a closing trade consumes first.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum CostBasisMethod {
+        #[default]
+        Fifo => "fifo",
+        Lifo => "lifo",
+        Average => "average",
+    }
+    error = "cost basis method",
+}
+
+string_enum! {
+    /// Whether a realized gain or loss is short- or long-term for taxes.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum HoldingPeriod {
+        ShortTerm => "short-term",
+        LongTerm => "long-term",
+    }
+    error = "holding period",
+}
+
+/// Part of an opening lot paired with the trade (or resolution) that closed it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LotMatch {
+    pub key: PositionKey,
+    pub short: bool,
+    pub open_id: Option<i64>,
+    /// The closing trade, or `None` when the option was assigned, exercised,
+    /// or expired.
+    pub close_id: Option<i64>,
+    /// Date of the opening trade.
+    pub open_date: String,
+    pub close_date: String,
+    /// Shares or contracts matched.
+    pub quantity: Decimal,
+    /// Per-share cost (long) or proceeds (short) of the opening side.
+    pub open_price: Decimal,
+    /// Per-share proceeds (long) or cost (short) of the closing side.
+    pub close_price: Decimal,
+    /// Shares per unit of `quantity`.
+    pub multiplier: Decimal,
+    pub realized: Decimal,
+}
+
+impl LotMatch {
+    /// Long-term when closed more than a year after the lot opened: after the
+    /// open date's anniversary.
+    pub fn holding_period(&self) -> HoldingPeriod {
+        let anniversary = self
+            .open_date
+            .get(..4)
+            .and_then(|year| year.parse::<i64>().ok())
+            .map(|year| format!("{}{}", year + 1, &self.open_date[4..]));
+        match anniversary {
+            Some(anniversary) if self.close_date > anniversary => HoldingPeriod::LongTerm,
+            _ => HoldingPeriod::ShortTerm,
+        }
+    }
+
+    /// What the sale brought in: the close of a long lot, the open of a
+    /// short one.
+    pub fn proceeds(&self) -> Decimal {
+        let price = if self.short {
+            self.open_price
+        } else {
+            self.close_price
+        };
+        price * self.quantity * self.multiplier
+    }
+
+    /// What the purchase cost: the open of a long lot, the close of a short
+    /// one.
+    pub fn cost(&self) -> Decimal {
+        let price = if self.short {
+            self.close_price
+        } else {
+            self.open_price
+        };
+        price * self.quantity * self.multiplier
+    }
+}
+
+/// The still-open remainder of an opening trade.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenLot {
+    pub key: PositionKey,
+    pub short: bool,
+    pub open_id: Option<i64>,
+    /// Date of the opening trade.
+    pub opened: String,
+    /// Shares or contracts still open.
+    pub quantity: Decimal,
+    /// Per-share cost (long) or proceeds (short), fees included.
+    pub price: Decimal,
+    pub multiplier: Decimal,
+}
+
+impl OpenLot {
+    /// P/L of the lot if it were closed at `mark` per share.
+    pub fn unrealized(&self, mark: Decimal) -> Decimal {
+        let per_share = if self.short {
+            self.price - mark
+        } else {
+            mark - self.price
+        };
+        per_share * self.quantity * self.multiplier
+    }
+}
+
+/// A user-chosen pairing: `quantity` of the closing trade `close_id` closes the
+/// opening trade `open_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LotOverride {
+    pub close_id: i64,
+    pub open_id: i64,
+    pub quantity: Decimal,
+}
+
+// The unmatched remainder of an opening trade.
+struct Lot {
+    id: Option<i64>,
+    date: String,
+    quantity: Decimal,
+    price: Decimal,
+    multiplier: Decimal,
+    resolved: bool,
+}
+
+/// Pairs every closing trade in `trades` with the open lots it closes, in
+/// chronological order per position key and side.
+pub fn match_lots(
+    trades: &[Trade],
+    method: CostBasisMethod,
+    overrides: &[LotOverride],
+) -> Vec<LotMatch> {
+    run(trades, method, overrides).0
+}
+
+/// Lots in `trades` still open after every close has been matched under
+/// `method`. Assigned, exercised, and expired options are never open.
+pub fn open_lots(
+    trades: &[Trade],
+    method: CostBasisMethod,
+    overrides: &[LotOverride],
+) -> Vec<OpenLot> {
+    run(trades, method, overrides).1
+}
+
+fn run(
+    trades: &[Trade],
+    method: CostBasisMethod,
+    overrides: &[LotOverride],
+) -> (Vec<LotMatch>, Vec<OpenLot>) {
+    let mut by_key: BTreeMap<PositionKey, Vec<&Trade>> = BTreeMap::new();
+    for trade in trades {
+        by_key.entry(trade.position_key()).or_default().push(trade);
+    }
+
+    let mut matches = Vec::new();
+    let mut open = Vec::new();
+    for (key, mut key_trades) in by_key {
+        key_trades.sort_by(|a, b| (&a.date, a.id).cmp(&(&b.date, b.id)));
+        let [long, short] = match_key(&key, &key_trades, method, overrides, &mut matches);
+        for (is_short, lots) in [(false, long), (true, short)] {
+            open.extend(lots.into_iter().map(|lot| OpenLot {
+                key: key.clone(),
+                short: is_short,
+                open_id: lot.id,
+                opened: lot.date,
+                quantity: lot.quantity,
+                price: lot.price,
+                multiplier: lot.multiplier,
+            }));
+        }
+    }
+    (matches, open)
+}
+
+/// Total realized P/L of `trades` under `method`.
+pub fn realized_pl(
+    trades: &[Trade],
+    method: CostBasisMethod,
+    overrides: &[LotOverride],
+) -> Decimal {
+    match_lots(trades, method, overrides)
+        .iter()
+        .map(|m| m.realized)
+        .sum()
+}
+
+// Per-share price of a trade with its fees folded in: fees raise what a buyer
+// pays and lower what a seller receives.
+pub(crate) fn unit_price(trade: &Trade) -> Decimal {
+    let shares = trade.quantity * trade.multiplier();
+    if shares == Decimal::ZERO {
+        return trade.price;
+    }
+    let fees_per_share = trade.fees / shares;
+    if trade.action.is_buy() {
+        trade.price + fees_per_share
+    } else {
+        trade.price - fees_per_share
+    }
+}
+
+// Matches one key's chronologically sorted trades, returning the lots left
+// open on the long and short sides.
+//
+// Option trades follow their action: opens add a lot to their side and closes
+// consume lots from it. Stock is a single signed share balance: a buy first
+// covers any short lots and a sell first closes any long lots, and whatever is
+// left over opens the other side — so selling shares you don't hold goes
+// short, and the buy that follows closes the short.
+fn match_key(
+    key: &PositionKey,
+    trades: &[&Trade],
+    method: CostBasisMethod,
+    overrides: &[LotOverride],
+    matches: &mut Vec<LotMatch>,
+) -> [Vec<Lot>; 2] {
+    let mut sides: [Vec<Lot>; 2] = [Vec::new(), Vec::new()];
+
+    for trade in trades {
+        let buy = trade.action.is_buy();
+        // A buy closes the short side (1), a sell the long side (0).
+        let close_side = usize::from(buy);
+        let to_open = if key.trade_type == TradeType::Stock {
+            close_lots(
+                key,
+                &mut sides[close_side],
+                close_side == 1,
+                trade,
+                method,
+                overrides,
+                matches,
+            )
+        } else if trade.action.is_open() {
+            trade.quantity
+        } else {
+            close_lots(
+                key,
+                &mut sides[close_side],
+                close_side == 1,
+                trade,
+                method,
+                overrides,
+                matches,
+            );
+            Decimal::ZERO
+        };
+
+        if to_open > Decimal::ZERO {
+            let lots = &mut sides[usize::from(!buy)];
+            lots.push(Lot {
+                id: trade.id,
+                date: trade.date.clone(),
+                quantity: to_open,
+                price: unit_price(trade),
+                multiplier: trade.multiplier(),
+                resolved: matches!(
+                    trade.status,
+                    Some(OptionStatus::Assigned | OptionStatus::Exercised | OptionStatus::Expired)
+                ),
+            });
+            if method == CostBasisMethod::Average {
+                average_lots(lots);
+            }
+        }
+    }
+
+    for (index, lots) in sides.iter_mut().enumerate() {
+        for lot in lots.iter().filter(|l| l.resolved) {
+            matches.push(lot_match(
+                key,
+                index == 1,
+                lot,
+                None,
+                key.expiration.as_deref().unwrap_or(&lot.date),
+                lot.quantity,
+                Decimal::ZERO,
+            ));
+        }
+        lots.retain(|l| !l.resolved);
+    }
+    sides
+}
+
+// Consumes `lots` with a closing trade: manually matched lots first, then in
+// the method's order. Returns the quantity left over once `lots` run out.
+fn close_lots(
+    key: &PositionKey,
+    lots: &mut Vec<Lot>,
+    short: bool,
+    trade: &Trade,
+    method: CostBasisMethod,
+    overrides: &[LotOverride],
+    matches: &mut Vec<LotMatch>,
+) -> Decimal {
+    let mut remaining = trade.quantity;
+    let close_price = unit_price(trade);
+    let chosen = overrides
+        .iter()
+        .filter(|o| trade.id == Some(o.close_id))
+        .filter_map(|o| {
+            lots.iter()
+                .position(|l| l.id == Some(o.open_id))
+                .map(|i| (i, o.quantity))
+        });
+    for (index, wanted) in chosen.collect::<Vec<_>>() {
+        let lot = &mut lots[index];
+        let quantity = remaining.min(lot.quantity).min(wanted);
+        if quantity <= Decimal::ZERO {
+            continue;
+        }
+        lot.quantity -= quantity;
+        remaining -= quantity;
+        matches.push(lot_match(
+            key,
+            short,
+            lot,
+            trade.id,
+            &trade.date,
+            quantity,
+            close_price,
+        ));
+    }
+    while remaining > Decimal::ZERO {
+        let index = match method {
+            CostBasisMethod::Lifo => lots.iter().rposition(|l| l.quantity > Decimal::ZERO),
+            _ => lots.iter().position(|l| l.quantity > Decimal::ZERO),
+        };
+        let lot = match index {
+            Some(i) => &mut lots[i],
+            None => break,
+        };
+        let quantity = remaining.min(lot.quantity);
+        lot.quantity -= quantity;
+        remaining -= quantity;
+        matches.push(lot_match(
+            key,
+            short,
+            lot,
+            trade.id,
+            &trade.date,
+            quantity,
+            close_price,
+        ));
+    }
+    lots.retain(|l| l.quantity > Decimal::ZERO);
+    remaining
+}
+
+// Re-prices every open lot at the side's weighted average cost.
+fn average_lots(lots: &mut [Lot]) {
+    let quantity: Decimal = lots.iter().map(|l| l.quantity).sum();
+    if quantity == Decimal::ZERO {
+        return;
+    }
+    let average = lots.iter().map(|l| l.price * l.quantity).sum::<Decimal>() / quantity;
+    for lot in lots {
+        lot.price = average;
+    }
+}
+
+fn lot_match(
+    key: &PositionKey,
+    short: bool,
+    lot: &Lot,
+    close_id: Option<i64>,
+    close_date: &str,
+    quantity: Decimal,
+    close_price: Decimal,
+) -> LotMatch {
+    let per_share = if short {
+        lot.price - close_price
+    } else {
+        close_price - lot.price
+    };
+    LotMatch {
+        key: key.clone(),
+        short,
+        open_id: lot.id,
+        close_id,
+        open_date: lot.date.clone(),
+        close_date: close_date.to_string(),
+        quantity,
+        open_price: lot.price,
+        close_price,
+        multiplier: lot.multiplier,
+        realized: per_share * quantity * lot.multiplier,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionType};
+    use rust_decimal_macros::dec;
+
+    fn stock(id: i64, date: &str, action: Action, price: Decimal, quantity: Decimal) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Stock,
+            action,
+            price,
+            quantity,
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn ladder() -> Vec<Trade> {
+        vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-01-03", Action::BuyToOpen, dec!(120), dec!(10)),
+            stock(3, "2024-01-04", Action::SellToClose, dec!(130), dec!(10)),
+        ]
+    }
+
+    #[test]
+    fn splits_holding_periods_at_one_year() {
+        let trades = vec![
+            stock(1, "2023-03-01", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-03-01", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(3, "2024-03-01", Action::SellToClose, dec!(110), dec!(10)),
+            stock(4, "2025-03-02", Action::SellToClose, dec!(110), dec!(10)),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        // Exactly a year is still short-term; a day more is long-term.
+        assert_eq!(matches[0].open_date, "2023-03-01");
+        assert_eq!(matches[0].holding_period(), HoldingPeriod::ShortTerm);
+        assert_eq!(matches[1].holding_period(), HoldingPeriod::LongTerm);
+    }
+
+    #[test]
+    fn methods_pick_different_lots() {
+        let trades = ladder();
+        assert_eq!(realized_pl(&trades, CostBasisMethod::Fifo, &[]), dec!(300));
+        assert_eq!(realized_pl(&trades, CostBasisMethod::Lifo, &[]), dec!(100));
+        assert_eq!(
+            realized_pl(&trades, CostBasisMethod::Average, &[]),
+            dec!(200)
+        );
+
+        let fifo = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        assert_eq!(fifo.len(), 1);
+        assert_eq!(fifo[0].open_id, Some(1));
+        assert_eq!(fifo[0].close_id, Some(3));
+
+        let open = open_lots(&trades, CostBasisMethod::Lifo, &[]);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].open_id, Some(1));
+        assert_eq!(open[0].unrealized(dec!(130)), dec!(300));
+    }
+
+    #[test]
+    fn overrides_pick_lots_before_the_method() {
+        let mut trades = ladder();
+        trades[2].quantity = dec!(15);
+        let overrides = [LotOverride {
+            close_id: 3,
+            open_id: 2,
+            quantity: dec!(10),
+        }];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &overrides);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].open_id, Some(2));
+        assert_eq!(matches[0].quantity, dec!(10));
+        // The 5 shares the override leaves uncovered fall back to FIFO.
+        assert_eq!(matches[1].open_id, Some(1));
+        assert_eq!(matches[1].quantity, dec!(5));
+        assert_eq!(
+            realized_pl(&trades, CostBasisMethod::Fifo, &overrides),
+            dec!(250)
+        );
+    }
+
+    #[test]
+    fn close_spanning_lots_and_fees() {
+        let mut sell = stock(3, "2024-01-04", Action::SellToClose, dec!(130), dec!(15));
+        sell.fees = dec!(15);
+        let trades = vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-01-03", Action::BuyToOpen, dec!(120), dec!(10)),
+            sell,
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].quantity, dec!(10));
+        assert_eq!(matches[1].quantity, dec!(5));
+        // $1/share fee on the sale: (129 - 100) * 10 + (129 - 120) * 5.
+        assert_eq!(realized_pl(&trades, CostBasisMethod::Fifo, &[]), dec!(335));
+    }
+
+    #[test]
+    fn average_cost_holds_through_sales() {
+        let mut trades = ladder();
+        trades.push(stock(
+            4,
+            "2024-01-05",
+            Action::BuyToOpen,
+            dec!(140),
+            dec!(5),
+        ));
+        trades.push(stock(
+            5,
+            "2024-01-06",
+            Action::SellToClose,
+            dec!(150),
+            dec!(15),
+        ));
+        // Average $110 on the first sale; the remaining 10 @ $110 blend with
+        // 5 @ $140 to $120 for the second.
+        assert_eq!(
+            realized_pl(&trades, CostBasisMethod::Average, &[]),
+            dec!(650)
+        );
+    }
+
+    #[test]
+    fn selling_unheld_stock_goes_short_until_bought_back() {
+        let trades = vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            // Sells 10 more than held, whatever the action says.
+            stock(2, "2024-01-03", Action::SellToClose, dec!(110), dec!(20)),
+            stock(3, "2024-01-04", Action::BuyToOpen, dec!(95), dec!(4)),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        assert_eq!(matches.len(), 2);
+        assert!(!matches[0].short);
+        assert_eq!(matches[0].realized, dec!(100));
+        // Sold short at $110 and covered at $95.
+        assert!(matches[1].short);
+        assert_eq!(matches[1].open_id, Some(2));
+        assert_eq!(matches[1].realized, dec!(60));
+
+        let open = open_lots(&trades, CostBasisMethod::Fifo, &[]);
+        assert_eq!(open.len(), 1);
+        assert!(open[0].short);
+        assert_eq!(open[0].quantity, dec!(6));
+        assert_eq!(open[0].opened, "2024-01-03");
+    }
+
+    #[test]
+    fn short_options_and_resolved_lots() {
+        let sold = Trade {
+            id: Some(1),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            price: dec!(3),
+            quantity: dec!(2),
+            date: "2024-01-02".to_string(),
+            option_type: Some(OptionType::Call),
+            strike: Some(dec!(110)),
+            expiration: Some("2024-02-16".to_string()),
+            status: Some(OptionStatus::Closed),
+            ..Default::default()
+        };
+        let mut bought_back = sold.clone();
+        bought_back.id = Some(2);
+        bought_back.action = Action::BuyToClose;
+        bought_back.price = dec!(1);
+        bought_back.quantity = dec!(1);
+        let mut expired = sold.clone();
+        expired.id = Some(3);
+        expired.strike = Some(dec!(120));
+        expired.status = Some(OptionStatus::Expired);
+
+        let trades = vec![sold, bought_back, expired];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        // One contract bought back for a $200 gain; the other stays open. The
+        // expired pair realizes its full $600 premium.
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.short));
+        assert_eq!(matches[0].realized, dec!(200));
+        assert_eq!(matches[1].close_id, None);
+        assert_eq!(matches[1].realized, dec!(600));
+
+        // The contract still open is short; it gains as the mark falls.
+        let open = open_lots(&trades, CostBasisMethod::Fifo, &[]);
+        assert_eq!(open.len(), 1);
+        assert!(open[0].short);
+        assert_eq!(open[0].unrealized(dec!(0.5)), dec!(250));
+    }
+}
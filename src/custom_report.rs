@@ -0,0 +1,525 @@
+//! User-defined reports over the trade ledger.
+//!
+//! A custom report is a filter and a grouping, saved by name in the
+//! `saved_reports` table. The filter is a small SQL-like `WHERE` clause:
+//! conditions of the form `field op value` joined by `and`, e.g.
+//!
+//! ```text
+//! action = sell_to_open and option_type = put and date >= 2024-01-01
+//! ```
+//!
+//! Operators are `=`, `!=`, `<`, `<=`, `>`, `>=`, and `~` (contains). Text is
+//! compared without regard to case; numeric fields compare as numbers. A field
+//! the trade doesn't have — the strike of a stock trade, say — fails every
+//! condition on it. `tag` tests a trade's tags: `tag = hedge` holds when one of
+//! them is `hedge`, `tag != mistake` when none is, and the other operators
+//! when any tag passes. Ungrouped, the report lists the matching trades; grouped,
+//! it totals them per group.
+
+use crate::date::DateRange;
+use crate::db::{Action, Trade, TradeFilter, TradeType};
+use crate::export::{or_blank, Table};
+use crate::periods::Period;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+string_enum! {
+    /// A trade field a filter can test.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Field {
+        Symbol => "symbol",
+        TradeType => "trade_type",
+        Action => "action",
+        OptionType => "option_type",
+        Status => "status",
+        Date => "date",
+        Expiration => "expiration",
+        Strike => "strike",
+        Price => "price",
+        Quantity => "quantity",
+        Fees => "fees",
+        Comment => "comment",
+        Tag => "tag",
+    }
+    error = "field",
+}
+
+enum Value {
+    Text(String),
+    Number(Decimal),
+}
+
+impl Field {
+    fn value(&self, trade: &Trade) -> Option<Value> {
+        let text = |s: &str| Some(Value::Text(s.to_string()));
+        match self {
+            Field::Symbol => text(&trade.symbol),
+            Field::TradeType => text(trade.trade_type.as_str()),
+            Field::Action => text(trade.action.as_str()),
+            Field::OptionType => trade.option_type.and_then(|o| text(o.as_str())),
+            Field::Status => trade.status.as_ref().and_then(|s| text(s.as_str())),
+            Field::Date => text(&trade.date),
+            Field::Expiration => trade.expiration.as_deref().and_then(text),
+            Field::Strike => trade.strike.map(Value::Number),
+            Field::Price => Some(Value::Number(trade.price)),
+            Field::Quantity => Some(Value::Number(trade.quantity)),
+            Field::Fees => Some(Value::Number(trade.fees)),
+            Field::Comment => text(&trade.comment),
+            // Tested tag by tag in `Condition::matches`.
+            Field::Tag => None,
+        }
+    }
+}
+
+// Longer operators first, so `<=` isn't read as `<`.
+const OPERATORS: [&str; 7] = ["!=", "<=", ">=", "=", "<", ">", "~"];
+
+/// One `field op value` test.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub field: Field,
+    pub op: &'static str,
+    pub value: String,
+}
+
+impl Condition {
+    fn matches(&self, trade: &Trade) -> bool {
+        if self.field == Field::Tag {
+            // `!=` holds when no tag equals the value; other operators need
+            // one tag to pass.
+            let any = |condition: &Condition| {
+                trade
+                    .tags
+                    .iter()
+                    .any(|tag| condition.compare(Value::Text(tag.clone())))
+            };
+            return if self.op == "!=" {
+                !any(&Condition {
+                    op: "=",
+                    ..self.clone()
+                })
+            } else {
+                any(self)
+            };
+        }
+        match self.field.value(trade) {
+            Some(value) => self.compare(value),
+            None => false,
+        }
+    }
+
+    fn compare(&self, value: Value) -> bool {
+        let ordering = match value {
+            Value::Number(number) => match self.value.parse::<Decimal>() {
+                Ok(value) => number.cmp(&value),
+                Err(_) => return false,
+            },
+            Value::Text(text) => {
+                let (text, value) = (text.to_lowercase(), self.value.to_lowercase());
+                if self.op == "~" {
+                    return text.contains(&value);
+                }
+                text.cmp(&value)
+            }
+        };
+        match self.op {
+            "=" => ordering.is_eq(),
+            "!=" => ordering.is_ne(),
+            "<" => ordering.is_lt(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            ">=" => ordering.is_ge(),
+            _ => false,
+        }
+    }
+}
+
+/// Conditions that must all hold; empty matches every trade.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter(pub Vec<Condition>);
+
+impl Filter {
+    pub fn matches(&self, trade: &Trade) -> bool {
+        self.0.iter().all(|condition| condition.matches(trade))
+    }
+
+    /// Splits the filter into the part the database can apply when reading
+    /// trades (see [`crate::db::Database::query_trades`]) and the conditions
+    /// left to test on the trades it reads. Together they match the same
+    /// trades as the whole filter.
+    pub fn split(&self) -> (TradeFilter, Filter) {
+        let mut query = TradeFilter::default();
+        let mut rest = Vec::new();
+        for condition in &self.0 {
+            let value = &condition.value;
+            let taken = match (condition.field, condition.op) {
+                // Symbols are stored upper case.
+                (Field::Symbol, "=") => fill(&mut query.symbol, Some(value.to_uppercase())),
+                (Field::Date, ">=") => fill(&mut query.range.start, Some(value.clone())),
+                (Field::Date, "<=") => fill(&mut query.range.end, Some(value.clone())),
+                (Field::TradeType, "=") => fill(&mut query.trade_type, value.parse().ok()),
+                (Field::Action, "=") => fill(&mut query.action, value.parse().ok()),
+                (Field::Tag, "=") => fill(&mut query.tag, Some(value.clone())),
+                _ => false,
+            };
+            if !taken {
+                rest.push(condition.clone());
+            }
+        }
+        (query, Filter(rest))
+    }
+
+    /// Splits off the conditions a [`QuickFilter`] holds — the first
+    /// `symbol ~`, `trade_type =`, `action =`, `date >=`, and `date <=` —
+    /// leaving the rest. [`QuickFilter::and`] puts them back.
+    pub fn quick(&self) -> (QuickFilter, Filter) {
+        let mut quick = QuickFilter::default();
+        let mut rest = Vec::new();
+        for condition in &self.0 {
+            let value = &condition.value;
+            let taken = match (condition.field, condition.op) {
+                (Field::Symbol, "~") => fill(&mut quick.symbol, Some(value.clone())),
+                (Field::TradeType, "=") => fill(&mut quick.trade_type, value.parse().ok()),
+                (Field::Action, "=") => fill(&mut quick.action, value.parse().ok()),
+                (Field::Date, ">=") => fill(&mut quick.range.start, Some(value.clone())),
+                (Field::Date, "<=") => fill(&mut quick.range.end, Some(value.clone())),
+                _ => false,
+            };
+            if !taken {
+                rest.push(condition.clone());
+            }
+        }
+        (quick, Filter(rest))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, condition) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " and ")?;
+            }
+            write!(
+                f,
+                "{} {} {}",
+                condition.field, condition.op, condition.value
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The common conditions the View/Edit Trades filter form has fields for:
+/// part of the symbol, the trade type, the action, and a date range.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QuickFilter {
+    pub symbol: Option<String>,
+    pub trade_type: Option<TradeType>,
+    pub action: Option<Action>,
+    pub range: DateRange,
+}
+
+impl QuickFilter {
+    /// This filter's conditions followed by those of `rest`.
+    pub fn and(&self, rest: Filter) -> Filter {
+        let condition = |field, op, value: String| Condition { field, op, value };
+        let mut conditions = Vec::new();
+        if let Some(symbol) = &self.symbol {
+            conditions.push(condition(Field::Symbol, "~", symbol.clone()));
+        }
+        if let Some(trade_type) = self.trade_type {
+            conditions.push(condition(Field::TradeType, "=", trade_type.to_string()));
+        }
+        if let Some(action) = self.action {
+            conditions.push(condition(Field::Action, "=", action.to_string()));
+        }
+        if let Some(start) = &self.range.start {
+            conditions.push(condition(Field::Date, ">=", start.clone()));
+        }
+        if let Some(end) = &self.range.end {
+            conditions.push(condition(Field::Date, "<=", end.clone()));
+        }
+        conditions.extend(rest.0);
+        Filter(conditions)
+    }
+}
+
+// Sets `slot` to `value` if it is still empty and `value` isn't; whether it
+// did.
+fn fill<T>(slot: &mut Option<T>, value: Option<T>) -> bool {
+    if slot.is_some() || value.is_none() {
+        return false;
+    }
+    *slot = value;
+    true
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Filter, String> {
+        if text.trim().is_empty() {
+            return Ok(Filter::default());
+        }
+        let clauses = text.split(|c: char| c.is_whitespace()).fold(
+            vec![String::new()],
+            |mut clauses, word| {
+                if word.eq_ignore_ascii_case("and") {
+                    clauses.push(String::new());
+                } else if let Some(clause) = clauses.last_mut() {
+                    if !word.is_empty() {
+                        clause.push_str(word);
+                        clause.push(' ');
+                    }
+                }
+                clauses
+            },
+        );
+        clauses
+            .iter()
+            .map(|clause| {
+                let (at, op) = OPERATORS
+                    .iter()
+                    .filter_map(|op| clause.find(op).map(|at| (at, *op)))
+                    .min_by_key(|(at, op)| (*at, usize::MAX - op.len()))
+                    .ok_or_else(|| format!("No operator in \"{}\"", clause.trim()))?;
+                let field = clause[..at].trim().parse()?;
+                let value = clause[at + op.len()..].trim().trim_matches(['"', '\'']);
+                if value.is_empty() {
+                    return Err(format!("No value in \"{}\"", clause.trim()));
+                }
+                Ok(Condition {
+                    field,
+                    op,
+                    value: value.to_string(),
+                })
+            })
+            .collect::<Result<_, String>>()
+            .map(Filter)
+    }
+}
+
+string_enum! {
+    /// What a custom report totals its trades by.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum GroupBy {
+        #[default]
+        None => "none",
+        Symbol => "symbol",
+        Month => "month",
+        Year => "year",
+        Action => "action",
+        TradeType => "trade_type",
+        OptionType => "option_type",
+        Status => "status",
+    }
+    error = "group by",
+}
+
+impl GroupBy {
+    fn key(&self, trade: &Trade) -> String {
+        match self {
+            GroupBy::None => String::new(),
+            GroupBy::Symbol => trade.symbol.clone(),
+            GroupBy::Month => Period::Month.label(&trade.date),
+            GroupBy::Year => Period::Year.label(&trade.date),
+            GroupBy::Action => trade.action.as_str().to_string(),
+            GroupBy::TradeType => trade.trade_type.as_str().to_string(),
+            GroupBy::OptionType => or_blank(trade.option_type),
+            GroupBy::Status => or_blank(trade.status.as_ref()),
+        }
+    }
+}
+
+/// The trades matching `filter`, listed one per row or totaled per group.
+pub fn run_report(trades: &[Trade], filter: &Filter, group_by: GroupBy) -> Table {
+    let matching = trades.iter().filter(|t| filter.matches(t));
+    if group_by == GroupBy::None {
+        let mut table = Table::new(&[
+            "ID",
+            "Date",
+            "Symbol",
+            "Type",
+            "Action",
+            "Option",
+            "Strike",
+            "Expiration",
+            "Quantity",
+            "Price",
+            "Fees",
+            "Cash Flow",
+        ]);
+        for trade in matching {
+            table.row(&[
+                &or_blank(trade.id),
+                &trade.date,
+                &trade.symbol,
+                &trade.trade_type,
+                &trade.action,
+                &or_blank(trade.option_type),
+                &or_blank(trade.strike),
+                &or_blank(trade.expiration.as_ref()),
+                &trade.quantity,
+                &trade.price,
+                &trade.fees,
+                &trade.cash_flow(),
+            ]);
+        }
+        return table;
+    }
+
+    // (trades, quantity, fees, cash flow) per group.
+    let mut groups: BTreeMap<String, (usize, Decimal, Decimal, Decimal)> = BTreeMap::new();
+    for trade in matching {
+        let group = groups.entry(group_by.key(trade)).or_default();
+        group.0 += 1;
+        group.1 += trade.quantity;
+        group.2 += trade.fees;
+        group.3 += trade.cash_flow();
+    }
+    let mut table = Table::new(&[group_by.as_str(), "Trades", "Quantity", "Fees", "Cash Flow"]);
+    for (name, (count, quantity, fees, cash_flow)) in &groups {
+        table.row(&[name, count, quantity, fees, cash_flow]);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::OptionType;
+    use rust_decimal_macros::dec;
+
+    fn trades() -> Vec<Trade> {
+        let put = Trade {
+            id: Some(1),
+            symbol: "KO".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(55)),
+            expiration: Some("2024-02-16".to_string()),
+            price: dec!(1.20),
+            quantity: dec!(2),
+            date: "2024-01-10".to_string(),
+            fees: dec!(1.30),
+            ..Default::default()
+        };
+        let stock = Trade {
+            id: Some(2),
+            symbol: "AAPL".to_string(),
+            action: Action::BuyToOpen,
+            price: dec!(180),
+            quantity: dec!(10),
+            date: "2024-02-01".to_string(),
+            comment: "Earnings dip".to_string(),
+            tags: vec!["earnings play".to_string(), "Hedge".to_string()],
+            ..Default::default()
+        };
+        vec![
+            put.clone(),
+            stock,
+            Trade {
+                id: Some(3),
+                date: "2024-02-20".to_string(),
+                ..put
+            },
+        ]
+    }
+
+    #[test]
+    fn parses_and_applies_filters() {
+        let filter: Filter = "option_type = PUT AND strike >= 50 and date < '2024-02-01'"
+            .parse()
+            .unwrap();
+        assert_eq!(filter.0.len(), 3);
+        assert_eq!(filter.0[1].op, ">=");
+        let trades = trades();
+        let matching: Vec<_> = trades.iter().filter(|t| filter.matches(t)).collect();
+        assert_eq!(matching.len(), 1);
+        assert_eq!(matching[0].id, Some(1));
+
+        let comment: Filter = "comment ~ earnings".parse().unwrap();
+        assert_eq!(trades.iter().filter(|t| comment.matches(t)).count(), 1);
+        let tagged: Filter = "tag = hedge".parse().unwrap();
+        assert_eq!(trades.iter().filter(|t| tagged.matches(t)).count(), 1);
+        let untagged: Filter = "tag != hedge".parse().unwrap();
+        assert_eq!(trades.iter().filter(|t| untagged.matches(t)).count(), 2);
+        let partly: Filter = "tag ~ earn".parse().unwrap();
+        assert_eq!(trades.iter().filter(|t| partly.matches(t)).count(), 1);
+        assert!("".parse::<Filter>().unwrap().matches(&trades[0]));
+        assert!("strike 50".parse::<Filter>().is_err());
+        assert!("colour = red".parse::<Filter>().is_err());
+        assert!("symbol =".parse::<Filter>().is_err());
+    }
+
+    #[test]
+    fn splits_off_what_the_database_can_filter() {
+        let filter: Filter =
+            "symbol = aapl and date >= 2024-01-01 and date >= 2024-02-01 and strike > 50 \
+             and action = SELL_TO_OPEN and trade_type = bond and tag = hedge"
+                .parse()
+                .unwrap();
+        let (query, rest) = filter.split();
+        assert_eq!(query.symbol.as_deref(), Some("AAPL"));
+        assert_eq!(query.range.start.as_deref(), Some("2024-01-01"));
+        assert_eq!(query.range.end, None);
+        assert_eq!(query.action, Some(Action::SellToOpen));
+        assert_eq!(query.trade_type, None);
+        assert_eq!(query.tag.as_deref(), Some("hedge"));
+        let left: Vec<Field> = rest.0.iter().map(|c| c.field).collect();
+        assert_eq!(left, vec![Field::Date, Field::Strike, Field::TradeType]);
+        assert!("".parse::<Filter>().unwrap().split().1.is_empty());
+    }
+
+    #[test]
+    fn takes_the_quick_conditions_apart_and_back() {
+        let filter: Filter = "symbol ~ aa and strike > 50 and action = sell_to_open \
+                              and date >= 2024-01-01 and symbol ~ pl"
+            .parse()
+            .unwrap();
+        let (quick, rest) = filter.quick();
+        assert_eq!(quick.symbol.as_deref(), Some("aa"));
+        assert_eq!(quick.action, Some(Action::SellToOpen));
+        assert_eq!(quick.trade_type, None);
+        assert_eq!(quick.range.start.as_deref(), Some("2024-01-01"));
+        assert_eq!(rest.to_string(), "strike > 50 and symbol ~ pl");
+
+        let quick = QuickFilter {
+            trade_type: Some(TradeType::Option),
+            ..quick
+        };
+        let joined = quick.and(rest).to_string();
+        assert_eq!(
+            joined,
+            "symbol ~ aa and trade_type = option and action = sell_to_open \
+             and date >= 2024-01-01 and strike > 50 and symbol ~ pl"
+        );
+        assert_eq!(joined.parse::<Filter>().unwrap().quick().0, quick);
+    }
+
+    #[test]
+    fn lists_or_groups_matching_trades() {
+        let trades = trades();
+        let filter: Filter = "symbol != SPY".parse().unwrap();
+        let listed = run_report(&trades, &filter, GroupBy::None);
+        assert_eq!(listed.rows.len(), 3);
+        assert_eq!(listed.rows[0][11], "238.70");
+
+        let grouped = run_report(&trades, &filter, GroupBy::Month);
+        assert_eq!(grouped.headers[0], "month");
+        assert_eq!(
+            grouped.rows,
+            vec![
+                vec!["2024-01", "1", "2", "1.30", "238.70"],
+                vec!["2024-02", "2", "12", "1.30", "-1561.30"],
+            ]
+        );
+    }
+}
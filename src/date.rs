@@ -14,21 +14,63 @@ use std::time::{SystemTime, UNIX_EPOCH};
 
 const SECONDS_PER_DAY: i64 = 86_400;
 
+/// Abbreviated month names, January first.
+pub const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
 /// Returns the current UTC civil date formatted as zero-padded `YYYY-MM-DD`.
 ///
 /// The result compares directly (lexicographically) against stored `expiration`
 /// strings. If the system clock is set before the Unix epoch we clamp to day 0
 /// (1970-01-01) rather than panicking.
 pub fn today() -> String {
-    let secs = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_secs() as i64)
-        .unwrap_or(0);
-    let days = secs.div_euclid(SECONDS_PER_DAY);
+    let days = unix_now().div_euclid(SECONDS_PER_DAY);
     let (y, m, d) = civil_from_days(days);
     format_ymd(y, m, d)
 }
 
+/// Seconds since the Unix epoch, clamped to 0 for a clock set before it.
+pub fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Formats Unix seconds as a UTC `YYYY-MM-DD HH:MM:SS` timestamp (SQLite's
+/// `datetime` format, which also sorts chronologically).
+pub fn format_timestamp(secs: i64) -> String {
+    let (y, m, d) = civil_from_days(secs.div_euclid(SECONDS_PER_DAY));
+    let time = secs.rem_euclid(SECONDS_PER_DAY);
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        format_ymd(y, m, d),
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Parses a `YYYY-MM-DD HH:MM:SS` timestamp into Unix seconds. A bare
+/// `YYYY-MM-DD` is taken as midnight.
+pub fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let (date, time) = match timestamp.split_once(' ') {
+        Some((date, time)) => (date, time),
+        None => (timestamp, "00:00:00"),
+    };
+    let parts: Vec<i64> = time
+        .split(':')
+        .map(|part| part.parse::<i64>().ok())
+        .collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [h, m, s] if (0..24).contains(h) && (0..60).contains(m) && (0..60).contains(s) => {
+            Some(parse_unix_day(date)? * SECONDS_PER_DAY + h * 3600 + m * 60 + s)
+        }
+        _ => None,
+    }
+}
+
 /// Formats a `(year, month, day)` triple as zero-padded `YYYY-MM-DD`.
 pub fn format_ymd(year: i64, month: u32, day: u32) -> String {
     format!("{:04}-{:02}-{:02}", year, month, day)
@@ -109,6 +151,130 @@ pub fn days_to_expiration(today: &str, expiration: &str) -> Option<i64> {
     Some(parse_unix_day(expiration)? - parse_unix_day(today)?)
 }
 
+/// Last day (Sunday) of the Monday-to-Sunday week containing `date`, as ISO
+/// `YYYY-MM-DD`. Returns `None` if `date` cannot be parsed.
+pub fn end_of_week(date: &str) -> Option<String> {
+    add_days(date, 6 - weekday(date)?)
+}
+
+/// Day of the week of `date`, from 0 for Monday to 6 for Sunday. Returns
+/// `None` if `date` cannot be parsed.
+pub fn weekday(date: &str) -> Option<i64> {
+    // 1970-01-01 was a Thursday, so Monday is weekday 0 at day count -3.
+    Some((parse_unix_day(date)? + 3).rem_euclid(7))
+}
+
+/// The ISO date `days` after `date` (before it when negative). Returns `None`
+/// if `date` cannot be parsed.
+pub fn add_days(date: &str, days: i64) -> Option<String> {
+    let (y, m, d) = civil_from_days(parse_unix_day(date)? + days);
+    Some(format_ymd(y, m, d))
+}
+
+/// The ISO date `months` after `date` (before it when negative), on the same
+/// day of the month or the month's last day if it is shorter. Returns `None`
+/// if `date` cannot be parsed.
+pub fn add_months(date: &str, months: i64) -> Option<String> {
+    parse_unix_day(date)?;
+    let year: i64 = date[..4].parse().ok()?;
+    let month: i64 = date[5..7].parse().ok()?;
+    let day: u32 = date[8..].parse().ok()?;
+    let index = year * 12 + month - 1 + months;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+    Some(format_ymd(year, month, day.min(days_in_month(year, month))))
+}
+
+/// Reads a date typed into a form: `YYYY-MM-DD`, `t` (today), `y`
+/// (yesterday), or a count of days, weeks, or months from today such as
+/// `-3d`, `+2w`, or `-1m` (a bare number counts days). Returns the ISO date,
+/// or `None` for anything else.
+pub fn parse_date_input(text: &str, today: &str) -> Option<String> {
+    let text = text.trim().to_lowercase();
+    match text.as_str() {
+        "t" | "today" => return Some(today.to_string()),
+        "y" | "yesterday" => return add_days(today, -1),
+        _ => {}
+    }
+    if parse_unix_day(&text).is_some() {
+        return Some(text);
+    }
+    if !text.starts_with(['+', '-']) {
+        return None;
+    }
+    let (count, unit) = match text.char_indices().last()? {
+        (at, unit @ ('d' | 'w' | 'm')) => (&text[..at], unit),
+        _ => (text.as_str(), 'd'),
+    };
+    let count: i64 = count.parse().ok()?;
+    match unit {
+        'w' => add_days(today, count * 7),
+        'm' => add_months(today, count),
+        _ => add_days(today, count),
+    }
+}
+
+/// An inclusive span of ISO dates; a missing end leaves that side unbounded.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+impl DateRange {
+    pub fn contains(&self, date: &str) -> bool {
+        self.start.as_deref().is_none_or(|start| date >= start)
+            && self.end.as_deref().is_none_or(|end| date <= end)
+    }
+
+    pub fn is_all(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+
+    /// `all dates`, `2024-01-01 to 2024-06-30`, `from 2024-01-01`, or
+    /// `through 2024-06-30`.
+    pub fn label(&self) -> String {
+        match (&self.start, &self.end) {
+            (None, None) => "all dates".to_string(),
+            (Some(start), Some(end)) => format!("{} to {}", start, end),
+            (Some(start), None) => format!("from {}", start),
+            (None, Some(end)) => format!("through {}", end),
+        }
+    }
+}
+
+string_enum! {
+    /// Common [`DateRange`]s, relative to today.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RangePreset {
+        All => "all",
+        YearToDate => "ytd",
+        Last30Days => "last 30 days",
+        PreviousYear => "previous year",
+    }
+    error = "date range preset",
+}
+
+impl RangePreset {
+    pub fn range(&self, today: &str) -> DateRange {
+        let year = today.get(..4).and_then(|y| y.parse::<i64>().ok());
+        match (self, year) {
+            (RangePreset::YearToDate, Some(year)) => DateRange {
+                start: Some(format_ymd(year, 1, 1)),
+                end: Some(today.to_string()),
+            },
+            (RangePreset::Last30Days, _) => DateRange {
+                start: add_days(today, -30),
+                end: Some(today.to_string()),
+            },
+            (RangePreset::PreviousYear, Some(year)) => DateRange {
+                start: Some(format_ymd(year - 1, 1, 1)),
+                end: Some(format_ymd(year - 1, 12, 31)),
+            },
+            _ => DateRange::default(),
+        }
+    }
+}
+
 /// Renders days-to-expiration as a short human label: `EXPIRED`, `expires today`,
 /// or `N days`.
 pub fn format_dte(dte: i64) -> String {
@@ -124,6 +290,18 @@ pub fn format_dte(dte: i64) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn timestamps_round_trip() {
+        let secs = parse_timestamp("2024-03-01 13:05:09").unwrap();
+        assert_eq!(format_timestamp(secs), "2024-03-01 13:05:09");
+        assert_eq!(
+            parse_timestamp("2024-03-01"),
+            parse_unix_day("2024-03-01").map(|d| d * SECONDS_PER_DAY)
+        );
+        assert_eq!(parse_timestamp("2024-03-01 24:00:00"), None);
+        assert_eq!(parse_timestamp("2024-03-01 noon"), None);
+    }
+
     #[test]
     fn civil_from_days_known_dates() {
         assert_eq!(civil_from_days(0), (1970, 1, 1));
@@ -192,6 +370,64 @@ mod tests {
         assert_eq!(format_dte(5), "5 days");
     }
 
+    #[test]
+    fn add_days_crosses_months_and_years() {
+        assert_eq!(add_days("2024-02-28", 1).as_deref(), Some("2024-02-29"));
+        assert_eq!(add_days("2024-01-10", -30).as_deref(), Some("2023-12-11"));
+        assert_eq!(add_days("garbage", 1), None);
+    }
+
+    #[test]
+    fn add_months_keeps_the_day_where_it_can() {
+        assert_eq!(add_months("2024-01-31", 1).as_deref(), Some("2024-02-29"));
+        assert_eq!(add_months("2024-03-15", -3).as_deref(), Some("2023-12-15"));
+        assert_eq!(add_months("2023-12-31", 14).as_deref(), Some("2025-02-28"));
+        assert_eq!(add_months("2024-13-01", 1), None);
+    }
+
+    #[test]
+    fn reads_typed_dates_and_shortcuts() {
+        let today = "2024-03-01";
+        let read = |text: &str| parse_date_input(text, today);
+        assert_eq!(read(" T ").as_deref(), Some("2024-03-01"));
+        assert_eq!(read("y").as_deref(), Some("2024-02-29"));
+        assert_eq!(read("-3d").as_deref(), Some("2024-02-27"));
+        assert_eq!(read("+2").as_deref(), Some("2024-03-03"));
+        assert_eq!(read("+1w").as_deref(), Some("2024-03-08"));
+        assert_eq!(read("-1m").as_deref(), Some("2024-02-01"));
+        assert_eq!(read("2024-02-10").as_deref(), Some("2024-02-10"));
+        for bad in ["", "3d", "-d", "-3x", "2024-02-30", "next week"] {
+            assert_eq!(read(bad), None, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn presets_bound_date_ranges() {
+        let ytd = RangePreset::YearToDate.range("2024-06-15");
+        assert_eq!(ytd.label(), "2024-01-01 to 2024-06-15");
+        assert!(ytd.contains("2024-01-01") && ytd.contains("2024-06-15"));
+        assert!(!ytd.contains("2023-12-31") && !ytd.contains("2024-06-16"));
+        assert_eq!(
+            RangePreset::PreviousYear.range("2024-06-15").label(),
+            "2023-01-01 to 2023-12-31"
+        );
+        assert_eq!(
+            RangePreset::Last30Days.range("2024-06-15").start.as_deref(),
+            Some("2024-05-16")
+        );
+        let all = RangePreset::All.range("2024-06-15");
+        assert!(all.is_all() && all.contains("1999-01-01"));
+    }
+
+    #[test]
+    fn end_of_week_is_the_following_sunday() {
+        assert_eq!(end_of_week("2024-06-17").as_deref(), Some("2024-06-23")); // Monday
+        assert_eq!(end_of_week("2024-06-21").as_deref(), Some("2024-06-23")); // Friday
+        assert_eq!(end_of_week("2024-06-23").as_deref(), Some("2024-06-23")); // Sunday
+        assert_eq!(end_of_week("2024-12-30").as_deref(), Some("2025-01-05"));
+        assert_eq!(end_of_week("not a date"), None);
+    }
+
     #[test]
     fn today_is_well_formed() {
         let t = today();
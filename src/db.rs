@@ -1,9 +1,73 @@
+use crate::archive::{closed_years, summarize, year_of};
+use crate::backup::{Backup, CoveredCall, BACKUP_VERSION};
+use crate::bulk_edit::BulkEdit;
+use crate::cost_basis::{
+    match_lots, open_lots, CostBasisMethod, HoldingPeriod, LotMatch, LotOverride,
+};
+use crate::custom_report::GroupBy;
+use crate::date::{parse_timestamp, DateRange};
+use crate::encryption::unlock;
+use crate::equity::equity_curve;
+use crate::fees::{fee_report, FeeReport};
+use crate::heatmap::daily_pl;
+use crate::migrations::{default_history_currency, migrate};
+use crate::occ::OccSymbol;
+use crate::outcomes::{option_outcomes, OutcomeReport};
+use crate::periods::{period_report, Period, PeriodReport};
+use crate::snapshot::{check_snapshot, failure, write_snapshot};
+use crate::statistics::{closed_positions, ClosedPosition};
+use crate::strategy_report::{strategy_report, StrategyTypeReport};
+use crate::wash_sale::{wash_sales, WashSale};
+use crate::wheel::{wheel_cycles, WheelCycle};
+use rusqlite::backup::Progress;
 use rusqlite::types::Type;
-use rusqlite::{params, Connection, OptionalExtension, Result};
+use rusqlite::{params, Connection, DatabaseName, OptionalExtension, Result, ToSql};
 use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
 use std::str::FromStr;
 
+/// The condition limiting trades to `account`, whose id is bound to parameter
+/// `param`. It is only `account_id = ?n` when there is an account, rather than
+/// `(?n IS NULL OR account_id = ?n)`, so SQLite can use the account index.
+fn account_filter(account: Option<i64>, param: usize) -> String {
+    optional_filter(account.is_some(), format!("account_id = ?{}", param), param)
+}
+
+/// `condition` on parameter `param` when that parameter is `present`, else a
+/// test of the (null) parameter that always holds, leaving the condition out
+/// of the query plan.
+fn optional_filter(present: bool, condition: String, param: usize) -> String {
+    if present {
+        condition
+    } else {
+        format!("?{} IS NULL", param)
+    }
+}
+
+/// The full-text query finding trades with a word starting with each word of
+/// `text`; `None` when it has no words.
+fn search_expression(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Break-even price of the net share position `trades` leave:
+/// `-(sum of all cash flows) / net_shares`, or `None` when flat.
+fn break_even<'a>(trades: impl Iterator<Item = &'a Trade> + Clone) -> Option<Decimal> {
+    let net_shares: Decimal = trades.clone().map(Trade::signed_shares).sum();
+    if net_shares == Decimal::ZERO {
+        return None;
+    }
+    let total_cash_flow: Decimal = trades.map(Trade::cash_flow).sum();
+    Some(-total_cash_flow / net_shares)
+}
+
 /// Reads a required `Decimal` stored as TEXT from the given column.
 fn decimal_from_row(row: &rusqlite::Row<'_>, idx: usize) -> Result<Decimal> {
     let raw: String = row.get(idx)?;
@@ -21,8 +85,20 @@ fn opt_decimal_from_row(row: &rusqlite::Row<'_>, idx: usize) -> Result<Option<De
     }
 }
 
+/// Splits a trade's tags, joined by the unit separator in SQL, and sorts them.
+fn split_tags(joined: Option<String>) -> Vec<String> {
+    let mut tags: Vec<String> = joined
+        .unwrap_or_default()
+        .split('\u{1f}')
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect();
+    tags.sort_by_key(|tag| tag.to_lowercase());
+    tags
+}
+
 string_enum! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub enum TradeType {
         Stock => "stock",
         Option => "option",
@@ -50,10 +126,26 @@ impl Action {
     pub fn is_buy(&self) -> bool {
         matches!(self, Action::BuyToOpen | Action::BuyToClose)
     }
+
+    /// True for the opening side (`BuyToOpen`/`SellToOpen`).
+    pub fn is_open(&self) -> bool {
+        matches!(self, Action::BuyToOpen | Action::SellToOpen)
+    }
+
+    /// The action that closes a position opened by this one (`BuyToOpen` →
+    /// `SellToClose`, `SellToOpen` → `BuyToClose`). Closing actions map to
+    /// themselves.
+    pub fn closing(&self) -> Action {
+        match self {
+            Action::BuyToOpen => Action::SellToClose,
+            Action::SellToOpen => Action::BuyToClose,
+            other => *other,
+        }
+    }
 }
 
 string_enum! {
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
     pub enum OptionType {
         Call => "call",
         Put => "put",
@@ -83,10 +175,61 @@ impl OptionStatus {
     }
 }
 
+string_enum! {
+    /// Kind of a named multi-leg strategy that trades can be grouped into.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    pub enum StrategyKind {
+        VerticalSpread => "vertical_spread",
+        IronCondor => "iron_condor",
+        Strangle => "strangle",
+        Straddle => "straddle",
+        Custom => "custom",
+    }
+    error = "strategy_kind",
+}
+
+string_enum! {
+    /// Kind of corporate action that rewrites earlier trades.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CorporateActionKind {
+        Split => "split",
+        SymbolChange => "symbol_change",
+    }
+    error = "corporate action kind",
+}
+
+string_enum! {
+    /// What an alert watches: an instrument's mark, or a position's
+    /// unrealized P/L as a percent of its cost basis.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum AlertKind {
+        PriceBelow => "price_below",
+        PriceAbove => "price_above",
+        PlBelow => "pl_below",
+        PlAbove => "pl_above",
+    }
+    error = "alert kind",
+}
+
+impl AlertKind {
+    /// Whether the threshold is a percent of cost basis rather than a price.
+    pub fn is_percent(&self) -> bool {
+        matches!(self, AlertKind::PlBelow | AlertKind::PlAbove)
+    }
+}
+
 /// Number of shares represented by a single option contract.
 pub const OPTION_MULTIPLIER: Decimal = dec!(100);
 
-#[derive(Debug, Clone)]
+/// Currency of trades and accounts that don't name one, and of everything
+/// saved before currencies were tracked.
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Trade {
     pub id: Option<i64>,
     pub symbol: String,
@@ -102,9 +245,98 @@ pub struct Trade {
     pub strike: Option<Decimal>,
     pub expiration: Option<String>,
     pub status: Option<OptionStatus>,
+    /// Shares per contract. `None` means the standard [`OPTION_MULTIPLIER`];
+    /// set for adjusted or mini contracts.
+    pub contract_multiplier: Option<Decimal>,
     /// Links an auto-generated stock row back to the option that produced it via
     /// assignment/exercise. `None` for user-entered rows.
     pub assigned_from: Option<i64>,
+    /// Shared by every leg of a roll chain (the id of the chain's first option),
+    /// so the closing and re-opening legs can be reported together.
+    pub roll_group_id: Option<i64>,
+    /// The named strategy (spread, condor, ...) this trade is a leg of.
+    pub strategy_id: Option<i64>,
+    /// The brokerage account the trade was made in; `None` when unassigned.
+    pub account_id: Option<i64>,
+    /// Free-form labels such as `earnings play` or `hedge`, sorted.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// When the trade was moved to the Trash (`YYYY-MM-DD HH:MM:SS`, UTC);
+    /// `None` for a live trade.
+    pub deleted_at: Option<String>,
+    /// Option greeks and implied volatility as of the trade (or last update).
+    pub greeks: Greeks,
+    /// ISO code of the currency `price` and `fees` are in, e.g. `USD`.
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Broker the trade was placed with, whose commission schedule fills in
+    /// its fees (see [`crate::fees::CommissionSchedule`]).
+    #[serde(default)]
+    pub broker: Option<String>,
+}
+
+/// Per-share greeks and implied volatility of an option contract, as quoted by
+/// a broker. Every field is optional; stock trades leave them all `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Greeks {
+    pub delta: Option<Decimal>,
+    pub gamma: Option<Decimal>,
+    /// Change in price per day, usually negative for a long option.
+    pub theta: Option<Decimal>,
+    /// Change in price per one-point move in implied volatility.
+    pub vega: Option<Decimal>,
+    /// Implied volatility in percent (e.g. `32.5`).
+    pub iv: Option<Decimal>,
+}
+
+impl Greeks {
+    /// True when no greek or IV has been entered.
+    pub fn is_empty(&self) -> bool {
+        *self == Greeks::default()
+    }
+}
+
+/// Identifies the instrument a trade opens or closes: the symbol for stock, the
+/// full contract (type, strike, expiration) for options, in the trade's
+/// account. Opens and closes pair up only within the same key.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PositionKey {
+    pub symbol: String,
+    pub trade_type: TradeType,
+    pub option_type: Option<OptionType>,
+    pub strike: Option<Decimal>,
+    pub expiration: Option<String>,
+    /// Positions in different accounts are kept apart.
+    pub account_id: Option<i64>,
+}
+
+impl PositionKey {
+    /// The name marks are quoted under: the ticker for stock, the OCC symbol
+    /// for an option contract.
+    pub fn instrument(&self) -> String {
+        match (self.option_type, self.strike, self.expiration.as_ref()) {
+            (Some(option_type), Some(strike), Some(expiration)) => OccSymbol {
+                underlying: self.symbol.clone(),
+                expiration: expiration.clone(),
+                option_type,
+                strike,
+            }
+            .to_string(),
+            _ => self.symbol.clone(),
+        }
+    }
+
+    /// True when both keys are the same stock or contract, in whichever
+    /// accounts.
+    pub fn same_instrument(&self, other: &PositionKey) -> bool {
+        PositionKey {
+            account_id: None,
+            ..self.clone()
+        } == PositionKey {
+            account_id: None,
+            ..other.clone()
+        }
+    }
 }
 
 impl Default for Trade {
@@ -123,17 +355,27 @@ impl Default for Trade {
             strike: None,
             expiration: None,
             status: None,
+            contract_multiplier: None,
             assigned_from: None,
+            roll_group_id: None,
+            strategy_id: None,
+            account_id: None,
+            tags: Vec::new(),
+            deleted_at: None,
+            greeks: Greeks::default(),
+            currency: default_currency(),
+            broker: None,
         }
     }
 }
 
 impl Trade {
-    /// Shares per unit for this trade: [`OPTION_MULTIPLIER`] for options, 1 for
-    /// stock. Used for cash-flow and share-ledger math.
+    /// Shares per unit for this trade: the contract multiplier for options
+    /// (defaulting to [`OPTION_MULTIPLIER`]), 1 for stock. Used for cash-flow and
+    /// share-ledger math.
     pub fn multiplier(&self) -> Decimal {
         match self.trade_type {
-            TradeType::Option => OPTION_MULTIPLIER,
+            TradeType::Option => self.contract_multiplier.unwrap_or(OPTION_MULTIPLIER),
             TradeType::Stock => Decimal::ONE,
         }
     }
@@ -149,6 +391,56 @@ impl Trade {
         }
     }
 
+    /// The OCC symbol for an option trade, or `None` for stock and for options
+    /// missing their type, strike, or expiration.
+    pub fn occ_symbol(&self) -> Option<OccSymbol> {
+        if self.trade_type != TradeType::Option {
+            return None;
+        }
+        Some(OccSymbol {
+            underlying: self.symbol.clone(),
+            expiration: self.expiration.clone()?,
+            option_type: self.option_type?,
+            strike: self.strike?,
+        })
+    }
+
+    pub fn position_key(&self) -> PositionKey {
+        let is_option = self.trade_type == TradeType::Option;
+        PositionKey {
+            symbol: self.symbol.clone(),
+            trade_type: self.trade_type,
+            option_type: self.option_type.filter(|_| is_option),
+            strike: self.strike.filter(|_| is_option).map(|s| s.normalize()),
+            expiration: self.expiration.clone().filter(|_| is_option),
+            account_id: self.account_id,
+        }
+    }
+
+    /// Change this trade makes to the open quantity of its position on the long
+    /// (`short == false`) or short side: opens on that side add, closes subtract,
+    /// and trades on the other side leave it unchanged. An option that was
+    /// assigned, exercised, or expired no longer counts as open, since those
+    /// events close it without a closing trade.
+    pub fn open_quantity_change(&self, short: bool) -> Decimal {
+        let opener = if short {
+            Action::SellToOpen
+        } else {
+            Action::BuyToOpen
+        };
+        let resolved = matches!(
+            self.status,
+            Some(OptionStatus::Assigned | OptionStatus::Exercised | OptionStatus::Expired)
+        );
+        if self.action == opener && !resolved {
+            self.quantity
+        } else if self.action == opener.closing() {
+            -self.quantity
+        } else {
+            Decimal::ZERO
+        }
+    }
+
     /// Signed share count contributed to a symbol's ledger by a stock trade:
     /// positive for buys, negative for sells. Options hold no shares (0).
     pub fn signed_shares(&self) -> Decimal {
@@ -167,7 +459,15 @@ impl Trade {
 #[derive(Debug, Clone, PartialEq)]
 pub struct SymbolReport {
     pub symbol: String,
+    /// Realized P/L from lots matched under the report's cost-basis method.
     pub profit_loss: Decimal,
+    /// The part of `profit_loss` held a year or less.
+    pub short_term: Decimal,
+    /// The part of `profit_loss` held more than a year.
+    pub long_term: Decimal,
+    /// P/L of the lots still open, valued at their marks. `None` when some open
+    /// lot has no mark; zero when nothing is open.
+    pub unrealized_pl: Option<Decimal>,
     pub trade_count: i32,
     /// Net share position: positive = long, negative = short, 0 = flat.
     pub net_shares: Decimal,
@@ -175,48 +475,464 @@ pub struct SymbolReport {
     pub break_even: Option<Decimal>,
 }
 
+/// A brokerage account (an IRA, a taxable account, ...) trades are kept in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    pub id: Option<i64>,
+    pub name: String,
+    /// ISO code of the currency the account is kept in; new trades in it
+    /// default to this.
+    #[serde(default = "default_currency")]
+    pub currency: String,
+}
+
+/// A named group of trades forming one multi-leg position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Strategy {
+    pub id: Option<i64>,
+    pub name: String,
+    pub kind: StrategyKind,
+}
+
+/// A strategy together with its legs' combined figures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategySummary {
+    pub strategy: Strategy,
+    pub leg_count: i32,
+    /// Sum of every leg's cash flow.
+    pub profit_loss: Decimal,
+}
+
+/// A trade with what it is tied to, for the trade detail view.
+#[derive(Debug, Clone)]
+pub struct TradeDetail {
+    pub trade: Trade,
+    /// Trades tied to it, each with how: the other legs of its strategy and
+    /// its roll chain, the option it was assigned from or the stock its
+    /// assignment made, and the lot covering its call or the calls its lot
+    /// covers. Ordered by date.
+    pub linked: Vec<(&'static str, Trade)>,
+    /// The lot matches it opens or closes.
+    pub matches: Vec<LotMatch>,
+}
+
+impl TradeDetail {
+    /// The realized P/L the trade accounts for: that of the lots it closes,
+    /// and of its lots closed by expiry or assignment rather than a trade.
+    pub fn realized(&self) -> Decimal {
+        let id = self.trade.id;
+        self.matches
+            .iter()
+            .filter(|m| m.close_id == id || (m.close_id.is_none() && m.open_id == id))
+            .map(|m| m.realized)
+            .sum()
+    }
+}
+
+/// A sold call linked to the stock lot that covers it, with the combined
+/// return of the stock and the premium.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoveredPosition {
+    pub call_id: i64,
+    pub stock_id: i64,
+    pub symbol: String,
+    /// Shares the call covers (`contracts * multiplier`).
+    pub shares: Decimal,
+    /// Cost of the covered shares, including their share of the lot's fees.
+    pub stock_cost: Decimal,
+    /// The call's cash flow, net of fees.
+    pub premium: Decimal,
+    /// Whether the call was assigned, selling the shares at the strike.
+    pub called_away: bool,
+    /// Gain on the covered shares when called away (`(strike - cost) *
+    /// shares`); zero while the shares are still held.
+    pub stock_gain: Decimal,
+    pub total_return: Decimal,
+    /// `total_return / stock_cost` as a percentage.
+    pub return_pct: Decimal,
+}
+
+/// A chain of option legs linked by rolls.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollChain {
+    pub roll_group_id: i64,
+    pub symbol: String,
+    pub leg_count: i32,
+    /// Combined cash flow of every leg: positive = net credit, negative = debit.
+    pub net_credit: Decimal,
+}
+
+/// A mark price for one instrument (see [`PositionKey::instrument`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    pub instrument: String,
+    pub price: Decimal,
+    /// When the price was entered or fetched, as a UTC `YYYY-MM-DD HH:MM:SS`
+    /// timestamp.
+    pub fetched_at: String,
+    /// Where the price came from, e.g. `manual`.
+    pub source: String,
+}
+
+impl Quote {
+    /// Whether the quote is at most `ttl_secs` old at Unix time `now`. A quote
+    /// with an unreadable timestamp is never fresh.
+    pub fn is_fresh(&self, now: i64, ttl_secs: i64) -> bool {
+        parse_timestamp(&self.fetched_at)
+            .map(|fetched| now - fetched <= ttl_secs)
+            .unwrap_or(false)
+    }
+}
+
+/// A stock split or ticker change, effective on `date`. Trades in `symbol`
+/// dated before `date` are rewritten when it is applied.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CorporateAction {
+    pub id: Option<i64>,
+    pub symbol: String,
+    pub kind: CorporateActionKind,
+    /// Effective (ex-)date, `YYYY-MM-DD`.
+    pub date: String,
+    /// New shares per old share for a split (4 for 4:1, 1.5 for 3:2, 0.1 for
+    /// a 1:10 reverse split).
+    pub ratio: Option<Decimal>,
+    /// The new ticker for a symbol change.
+    pub new_symbol: Option<String>,
+}
+
+/// A threshold on an instrument (see [`PositionKey::instrument`]). Once met it
+/// stays triggered until re-armed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    pub id: Option<i64>,
+    pub instrument: String,
+    pub kind: AlertKind,
+    /// A price per share, or a P/L percent such as `-20`.
+    pub threshold: Decimal,
+    /// When the threshold was first met (`YYYY-MM-DD HH:MM:SS`).
+    pub triggered_at: Option<String>,
+    /// The mark or P/L percent that met it.
+    pub triggered_value: Option<Decimal>,
+}
+
+impl Alert {
+    /// Short description such as `AAPL below $170.00`.
+    pub fn describe(&self) -> String {
+        match self.kind {
+            AlertKind::PriceBelow => format!("{} below ${:.2}", self.instrument, self.threshold),
+            AlertKind::PriceAbove => format!("{} above ${:.2}", self.instrument, self.threshold),
+            AlertKind::PlBelow => format!(
+                "{} P/L below {}%",
+                self.instrument,
+                self.threshold.normalize()
+            ),
+            AlertKind::PlAbove => format!(
+                "{} P/L above {}%",
+                self.instrument,
+                self.threshold.normalize()
+            ),
+        }
+    }
+}
+
+/// Portfolio value recorded at the end of a day; the series forms the equity
+/// curve.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValuationSnapshot {
+    pub date: String,
+    pub market_value: Decimal,
+    pub cash: Decimal,
+    pub account_value: Decimal,
+}
+
+/// One day's trading in a symbol (see the `prices` table). Prices are per
+/// share.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceBar {
+    pub symbol: String,
+    pub date: String,
+    pub open: Decimal,
+    pub high: Decimal,
+    pub low: Decimal,
+    pub close: Decimal,
+    pub volume: i64,
+}
+
+/// A cash dividend received on a symbol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dividend {
+    pub id: Option<i64>,
+    pub symbol: String,
+    /// Pay date, `YYYY-MM-DD`.
+    pub date: String,
+    pub amount: Decimal,
+    /// Taxed at the long-term capital gains rate rather than as income.
+    pub qualified: bool,
+    #[serde(default)]
+    pub account_id: Option<i64>,
+}
+
+string_enum! {
+    /// What a [`CashTransaction`] moves in or out of an account.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CashKind {
+        Deposit => "deposit",
+        Withdrawal => "withdrawal",
+        Interest => "interest",
+        Fee => "fee",
+    }
+    error = "cash transaction kind",
+}
+
+impl CashKind {
+    /// True for money coming into the account.
+    pub fn is_inflow(&self) -> bool {
+        matches!(self, CashKind::Deposit | CashKind::Interest)
+    }
+
+    /// True for money the owner moves in or out, as opposed to what the
+    /// account earns or is charged.
+    pub fn is_external(&self) -> bool {
+        matches!(self, CashKind::Deposit | CashKind::Withdrawal)
+    }
+}
+
+/// Cash moved in or out of an account other than by a trade or dividend.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CashTransaction {
+    pub id: Option<i64>,
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub kind: CashKind,
+    /// Always positive; `kind` gives the direction.
+    pub amount: Decimal,
+    pub comment: String,
+    #[serde(default)]
+    pub account_id: Option<i64>,
+}
+
+impl CashTransaction {
+    /// Positive for money in, negative for money out.
+    pub fn signed_amount(&self) -> Decimal {
+        if self.kind.is_inflow() {
+            self.amount
+        } else {
+            -self.amount
+        }
+    }
+}
+
+/// A trade in the archive with the rows tied to it, which leave the live
+/// tables with it and come back with it (a row of `archived_trades`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedTrade {
+    pub trade: Trade,
+    #[serde(default)]
+    pub covered_calls: Vec<CoveredCall>,
+    #[serde(default)]
+    pub lot_matches: Vec<LotOverride>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// What the archived trades in one symbol and account came to in a year (the
+/// `archive_summaries` table); see [`crate::archive`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveSummary {
+    pub year: i32,
+    #[serde(default)]
+    pub account_id: Option<i64>,
+    pub symbol: String,
+    pub trades: usize,
+    /// Net cash flow of the trades (see [`Trade::cash_flow`]).
+    pub cash_flow: Decimal,
+    pub fees: Decimal,
+    /// P/L of the lots closed in the year, under the cost-basis method used
+    /// when archiving.
+    pub realized: Decimal,
+}
+
+/// What one unit of `from` was worth in `to` on `date` (the `fx_rates`
+/// table), e.g. EUR to USD at 1.09.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FxRate {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    pub from: String,
+    pub to: String,
+    pub rate: Decimal,
+    /// The provider it was fetched from, or `manual`.
+    pub source: String,
+}
+
+string_enum! {
+    /// What a [`HistoryEntry`] records.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum HistoryChange {
+        Insert => "insert",
+        Update => "update",
+        Delete => "delete",
+        TagAdded => "tag_added",
+        TagRemoved => "tag_removed",
+    }
+    error = "history change",
+}
+
+/// One change to a trade, from the `trade_history` table. `before` and
+/// `after` are the trade's columns as a JSON object (`{"tag": ...}` for a tag
+/// change), absent on the side that didn't exist.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: Option<i64>,
+    pub trade_id: i64,
+    /// `YYYY-MM-DD HH:MM:SS`, UTC.
+    pub changed_at: String,
+    pub change: HistoryChange,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// A file attached to a trade (see [`crate::attachments`]): linked by `path`,
+/// or stored in the database as `data`, whichever is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: Option<i64>,
+    pub trade_id: i64,
+    /// The file's name, e.g. `confirmation.pdf`.
+    pub name: String,
+    pub path: Option<String>,
+    #[serde(default, with = "crate::attachments::hex")]
+    pub data: Option<Vec<u8>>,
+    /// `YYYY-MM-DD HH:MM:SS`, UTC.
+    pub added_at: String,
+}
+
+/// A custom report saved by name (see [`crate::custom_report`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedReport {
+    pub id: Option<i64>,
+    pub name: String,
+    /// Filter text, e.g. `symbol = AAPL and action = sell_to_open`.
+    pub filter: String,
+    pub group_by: GroupBy,
+}
+
+string_enum! {
+    /// A column [`Database::query_trades`] can order trades by.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum TradeSort {
+        #[default]
+        Date => "date",
+        Symbol => "symbol",
+        Price => "price",
+        Quantity => "quantity",
+        /// Realized P/L of the lots the trade closes; nothing for opening
+        /// trades.
+        Pl => "p/l",
+    }
+    error = "sort",
+}
+
+/// The order [`Database::query_trades`] reads trades in; the default is
+/// newest first. Ties go newest first.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TradeOrder {
+    pub by: TradeSort,
+    pub ascending: bool,
+    /// Lot matching for [`TradeSort::Pl`].
+    pub method: CostBasisMethod,
+}
+
+impl TradeOrder {
+    // The `ORDER BY` terms, or `None` for P/L, which takes matching lots.
+    fn sql(&self) -> Option<String> {
+        let direction = if self.ascending { "ASC" } else { "DESC" };
+        let column = match self.by {
+            TradeSort::Date => return Some(format!("date {0}, id {0}", direction)),
+            TradeSort::Symbol => "symbol",
+            // Stored as text.
+            TradeSort::Price => "CAST(price AS REAL)",
+            TradeSort::Quantity => "CAST(quantity AS REAL)",
+            TradeSort::Pl => return None,
+        };
+        Some(format!("{} {}, date DESC, id DESC", column, direction))
+    }
+}
+
+/// Which trades [`Database::query_trades`] reads, and in what order. Each
+/// field left empty matches every trade; the default matches them all.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TradeFilter {
+    pub symbol: Option<String>,
+    pub range: DateRange,
+    pub trade_type: Option<TradeType>,
+    pub action: Option<Action>,
+    /// A tag the trade has, in any case.
+    pub tag: Option<String>,
+    /// An account, within the one the database is limited to (see
+    /// [`Database::set_account`]).
+    pub account: Option<i64>,
+    /// Words the symbol or comment has words starting with, as in
+    /// [`Database::search_trades`].
+    pub search: Option<String>,
+    pub order: TradeOrder,
+}
+
 pub struct Database {
     conn: Connection,
+    // The account trades are read from, or every account for `None`.
+    account: Option<i64>,
+    // The passphrase of an encrypted database.
+    passphrase: Option<String>,
 }
 
 impl Database {
     pub fn new(db_path: &str) -> Result<Self> {
+        Self::open(db_path, None)
+    }
+
+    /// Opens the database at `db_path`, decrypting it with `passphrase` when
+    /// it is encrypted (see [`crate::encryption`]).
+    pub fn open(db_path: &str, passphrase: Option<&str>) -> Result<Self> {
         let conn = Connection::open(db_path)?;
-        let db = Database { conn };
+        if let Some(passphrase) = passphrase {
+            unlock(&conn, passphrase)?;
+        }
+        let db = Database {
+            conn,
+            account: None,
+            passphrase: passphrase.map(str::to_string),
+        };
         db.init_schema()?;
         Ok(db)
     }
 
+    /// Whether the database is encrypted.
+    pub fn is_encrypted(&self) -> bool {
+        self.passphrase.is_some()
+    }
+
+    // Brings the schema up to date, or refuses a database from a newer build.
     fn init_schema(&self) -> Result<()> {
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS trades (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                symbol TEXT NOT NULL,
-                trade_type TEXT NOT NULL,
-                action TEXT NOT NULL,
-                price TEXT NOT NULL,
-                quantity TEXT NOT NULL,
-                date TEXT NOT NULL,
-                fees TEXT NOT NULL,
-                comment TEXT,
-                option_type TEXT,
-                strike TEXT,
-                expiration TEXT,
-                status TEXT,
-                assigned_from INTEGER
-            )",
-            [],
-        )?;
-        Ok(())
+        migrate(&self.conn)
     }
 
     pub fn add_trade(&self, trade: &Trade) -> Result<i64> {
+        self.insert_trade(None, trade)
+    }
+
+    // Inserts `trade` under `id`, or under the next id for `None`.
+    fn insert_trade(&self, id: Option<i64>, trade: &Trade) -> Result<i64> {
         self.conn.execute(
             "INSERT INTO trades
-                (symbol, trade_type, action, price, quantity, date, fees, comment,
-                 option_type, strike, expiration, status, assigned_from)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                (id, symbol, trade_type, action, price, quantity, date, fees, comment,
+                 option_type, strike, expiration, status, contract_multiplier,
+                 assigned_from, roll_group_id, strategy_id, delta, gamma, theta, vega, iv,
+                 account_id, deleted_at, currency, broker)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                     ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26)",
             params![
+                id,
                 trade.symbol,
                 trade.trade_type,
                 trade.action,
@@ -229,10 +945,63 @@ impl Database {
                 trade.strike.map(|d| d.to_string()),
                 trade.expiration,
                 trade.status,
+                trade.contract_multiplier.map(|d| d.to_string()),
                 trade.assigned_from,
+                trade.roll_group_id,
+                trade.strategy_id,
+                trade.greeks.delta.map(|d| d.to_string()),
+                trade.greeks.gamma.map(|d| d.to_string()),
+                trade.greeks.theta.map(|d| d.to_string()),
+                trade.greeks.vega.map(|d| d.to_string()),
+                trade.greeks.iv.map(|d| d.to_string()),
+                trade.account_id,
+                trade.deleted_at,
+                trade.currency,
+                trade.broker,
             ],
         )?;
-        Ok(self.conn.last_insert_rowid())
+        let id = self.conn.last_insert_rowid();
+        self.write_tags(id, &trade.tags)?;
+        Ok(id)
+    }
+
+    /// Inserts several trades in one transaction: either all of them are saved
+    /// or none is. Returns their ids in order.
+    pub fn add_trades(&self, trades: &[Trade]) -> Result<Vec<i64>> {
+        self.add_trades_with_strategies(trades, &[])
+    }
+
+    /// [`add_trades`](Database::add_trades), first creating each of
+    /// `strategies` and making the trades at its indexes its legs. Options
+    /// already assigned or exercised get their linked stock rows.
+    pub fn add_trades_with_strategies(
+        &self,
+        trades: &[Trade],
+        strategies: &[(Strategy, Vec<usize>)],
+    ) -> Result<Vec<i64>> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut trades = trades.to_vec();
+        for (strategy, legs) in strategies {
+            let strategy_id = self.add_strategy(strategy)?;
+            for &leg in legs {
+                if let Some(trade) = trades.get_mut(leg) {
+                    trade.strategy_id = Some(strategy_id);
+                }
+            }
+        }
+        let mut ids = Vec::with_capacity(trades.len());
+        for trade in &mut trades {
+            let id = self.add_trade(trade)?;
+            trade.id = Some(id);
+            ids.push(id);
+            if let (TradeType::Option, Some(status)) = (trade.trade_type, &trade.status) {
+                if status.triggers_stock_event() {
+                    self.insert_linked_stock_row(trade, status)?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(ids)
     }
 
     fn row_to_trade(row: &rusqlite::Row<'_>) -> Result<Trade> {
@@ -250,40 +1019,353 @@ impl Database {
             strike: opt_decimal_from_row(row, 10)?,
             expiration: row.get(11)?,
             status: row.get(12)?,
-            assigned_from: row.get(13)?,
+            contract_multiplier: opt_decimal_from_row(row, 13)?,
+            assigned_from: row.get(14)?,
+            roll_group_id: row.get(15)?,
+            strategy_id: row.get(16)?,
+            account_id: row.get(22)?,
+            tags: split_tags(row.get(23)?),
+            deleted_at: row.get(24)?,
+            greeks: Greeks {
+                delta: opt_decimal_from_row(row, 17)?,
+                gamma: opt_decimal_from_row(row, 18)?,
+                theta: opt_decimal_from_row(row, 19)?,
+                vega: opt_decimal_from_row(row, 20)?,
+                iv: opt_decimal_from_row(row, 21)?,
+            },
+            currency: row.get(25)?,
+            broker: row.get(26)?,
         })
     }
 
     const SELECT_COLUMNS: &'static str = "id, symbol, trade_type, action, price, quantity, date, \
-         fees, comment, option_type, strike, expiration, status, assigned_from";
+         fees, comment, option_type, strike, expiration, status, contract_multiplier, \
+         assigned_from, roll_group_id, strategy_id, delta, gamma, theta, vega, iv, account_id, \
+         (SELECT group_concat(tags.name, char(31)) FROM trade_tags \
+          JOIN tags ON tags.id = trade_tags.tag_id WHERE trade_tags.trade_id = trades.id), \
+         deleted_at, currency, broker";
+
+    // The trade columns history entries hold, as the trade_history triggers
+    // write them.
+    const HISTORY_COLUMNS: &'static [&'static str] = &[
+        "symbol",
+        "trade_type",
+        "action",
+        "price",
+        "quantity",
+        "date",
+        "fees",
+        "comment",
+        "option_type",
+        "strike",
+        "expiration",
+        "status",
+        "contract_multiplier",
+        "assigned_from",
+        "roll_group_id",
+        "strategy_id",
+        "delta",
+        "gamma",
+        "theta",
+        "vega",
+        "iv",
+        "account_id",
+        "deleted_at",
+        "currency",
+        "broker",
+    ];
+
+    /// Limits the trades read, and so every report, to one account; `None`
+    /// reads all of them together.
+    pub fn set_account(&mut self, account: Option<i64>) {
+        self.account = account;
+    }
+
+    /// The account trades are read from, `None` for all of them.
+    pub fn account(&self) -> Option<i64> {
+        self.account
+    }
 
+    /// Trades in the current account (see [`Database::set_account`]), newest
+    /// first.
     pub fn get_all_trades(&self) -> Result<Vec<Trade>> {
+        self.query_trades(&TradeFilter::default(), None, 0)
+    }
+
+    /// Trades of `symbol`, newest first like [`Database::get_all_trades`].
+    pub fn get_trades_by_symbol(&self, symbol: &str) -> Result<Vec<Trade>> {
+        let filter = TradeFilter {
+            symbol: Some(symbol.to_string()),
+            ..Default::default()
+        };
+        self.query_trades(&filter, None, 0)
+    }
+
+    /// Trades dated within `range`, newest first.
+    pub fn get_trades_in(&self, range: &DateRange) -> Result<Vec<Trade>> {
+        let filter = TradeFilter {
+            range: range.clone(),
+            ..Default::default()
+        };
+        self.query_trades(&filter, None, 0)
+    }
+
+    /// Trades in the current account matching `filter`, in its order,
+    /// skipping the first `offset` and reading at most `limit` of them (all
+    /// for `None`).
+    pub fn query_trades(
+        &self,
+        filter: &TradeFilter,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<Vec<Trade>> {
+        self.select_trades(self.account, filter, limit, offset)
+    }
+
+    /// How many trades [`Database::query_trades`] would read for `filter`
+    /// without a limit.
+    pub fn count_trades(&self, filter: &TradeFilter) -> Result<usize> {
         let sql = format!(
-            "SELECT {} FROM trades ORDER BY date DESC, id DESC",
-            Self::SELECT_COLUMNS
+            "SELECT COUNT(*) FROM trades WHERE {}",
+            Self::trade_conditions(self.account, filter)
+        );
+        let search = filter.search.as_deref().and_then(search_expression);
+        self.conn.query_row(
+            &sql,
+            &*Self::filter_params(&self.account, filter, &search),
+            |row| row.get(0),
+        )
+    }
+
+    // Trades in `account`, or in every account for `None`.
+    fn trades_in_account(&self, account: Option<i64>) -> Result<Vec<Trade>> {
+        self.select_trades(account, &TradeFilter::default(), None, 0)
+    }
+
+    fn select_trades(
+        &self,
+        account: Option<i64>,
+        filter: &TradeFilter,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<Vec<Trade>> {
+        let Some(order) = filter.order.sql() else {
+            return self.select_trades_by_pl(account, filter, limit, offset);
+        };
+        let sql = format!(
+            "SELECT {} FROM trades
+             WHERE {}
+             ORDER BY {}
+             LIMIT ?10 OFFSET ?11",
+            Self::SELECT_COLUMNS,
+            Self::trade_conditions(account, filter),
+            order
         );
+        let search = filter.search.as_deref().and_then(search_expression);
+        // SQLite reads a negative limit as none.
+        let (limit, offset) = (limit.map_or(-1, |n| n as i64), offset as i64);
+        let mut params = Self::filter_params(&account, filter, &search);
+        params.extend([&limit as &dyn ToSql, &offset]);
         let mut stmt = self.conn.prepare(&sql)?;
-        let trades = stmt.query_map([], Self::row_to_trade)?;
+        let trades = stmt.query_map(&*params, Self::row_to_trade)?;
         trades.collect()
     }
 
-    pub fn get_trade(&self, id: i64) -> Result<Option<Trade>> {
-        let sql = format!("SELECT {} FROM trades WHERE id = ?1", Self::SELECT_COLUMNS);
-        self.conn
-            .query_row(&sql, params![id], Self::row_to_trade)
-            .optional()
+    // `select_trades` ordered by the P/L each trade realized, from lots
+    // matched over every trade in `account`, sorted after reading them all.
+    fn select_trades_by_pl(
+        &self,
+        account: Option<i64>,
+        filter: &TradeFilter,
+        limit: Option<usize>,
+        offset: usize,
+    ) -> Result<Vec<Trade>> {
+        let order = filter.order;
+        let by_date = TradeFilter {
+            order: TradeOrder::default(),
+            ..filter.clone()
+        };
+        let mut trades = self.select_trades(account, &by_date, None, 0)?;
+        let realized = self.realized_by_trade(account, order.method)?;
+        let pl = |trade: &Trade| {
+            trade
+                .id
+                .and_then(|id| realized.get(&id).copied())
+                .unwrap_or_default()
+        };
+        // Stable, so ties stay newest first.
+        if order.ascending {
+            trades.sort_by_key(pl);
+        } else {
+            trades.sort_by_key(|trade| std::cmp::Reverse(pl(trade)));
+        }
+        Ok(trades
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect())
     }
 
-    pub fn update_trade(&self, trade: &Trade) -> Result<()> {
-        if let Some(id) = trade.id {
-            let tx = self.conn.unchecked_transaction()?;
-            self.conn.execute(
-                "UPDATE trades
-                 SET symbol = ?1, trade_type = ?2, action = ?3, price = ?4,
-                     quantity = ?5, date = ?6, fees = ?7, comment = ?8,
+    /// Realized P/L of the lots each trade in the current account closes
+    /// under `method`, by trade id; trades that close nothing are left out.
+    pub fn get_realized_by_trade(&self, method: CostBasisMethod) -> Result<HashMap<i64, Decimal>> {
+        self.realized_by_trade(self.account, method)
+    }
+
+    fn realized_by_trade(
+        &self,
+        account: Option<i64>,
+        method: CostBasisMethod,
+    ) -> Result<HashMap<i64, Decimal>> {
+        let mut realized: HashMap<i64, Decimal> = HashMap::new();
+        let all = self.trades_in_account(account)?;
+        for lot in match_lots(&all, method, &self.get_lot_overrides()?) {
+            if let Some(id) = lot.close_id {
+                *realized.entry(id).or_default() += lot.realized;
+            }
+        }
+        Ok(realized)
+    }
+
+    // The conditions on a live trade in `account` matching `filter`, on the
+    // parameters `filter_params` binds. Only the fields set are tested, so
+    // SQLite can pick the symbol, date, or account index for them.
+    fn trade_conditions(account: Option<i64>, filter: &TradeFilter) -> String {
+        let range = &filter.range;
+        let search = filter.search.as_deref().and_then(search_expression);
+        [
+            "deleted_at IS NULL".to_string(),
+            account_filter(account, 1),
+            optional_filter(filter.symbol.is_some(), "symbol = ?2".to_string(), 2),
+            optional_filter(range.start.is_some(), "date >= ?3".to_string(), 3),
+            optional_filter(range.end.is_some(), "date <= ?4".to_string(), 4),
+            optional_filter(
+                filter.trade_type.is_some(),
+                "trade_type = ?5".to_string(),
+                5,
+            ),
+            optional_filter(filter.action.is_some(), "action = ?6".to_string(), 6),
+            optional_filter(
+                filter.tag.is_some(),
+                "EXISTS (SELECT 1 FROM trade_tags JOIN tags ON tags.id = trade_tags.tag_id
+                         WHERE trade_tags.trade_id = trades.id
+                           AND tags.name = ?7 COLLATE NOCASE)"
+                    .to_string(),
+                7,
+            ),
+            account_filter(filter.account, 8),
+            optional_filter(
+                search.is_some(),
+                "id IN (SELECT rowid FROM trades_fts WHERE trades_fts MATCH ?9)".to_string(),
+                9,
+            ),
+        ]
+        .join(" AND ")
+    }
+
+    fn filter_params<'a>(
+        account: &'a Option<i64>,
+        filter: &'a TradeFilter,
+        search: &'a Option<String>,
+    ) -> Vec<&'a dyn ToSql> {
+        vec![
+            account,
+            &filter.symbol,
+            &filter.range.start,
+            &filter.range.end,
+            &filter.trade_type,
+            &filter.action,
+            &filter.tag,
+            &filter.account,
+            search,
+        ]
+    }
+
+    /// Opening option trades still open (not assigned, exercised, expired, or
+    /// rolled) that expire between `from` and `until` (inclusive, ISO
+    /// `YYYY-MM-DD`; `None` leaves that end unbounded), soonest first. Closing
+    /// trades entered against them are not netted here; see
+    /// [`Database::open_quantity`].
+    pub fn get_open_options_by_expiration(
+        &self,
+        from: Option<&str>,
+        until: Option<&str>,
+    ) -> Result<Vec<Trade>> {
+        let sql = format!(
+            "SELECT {} FROM trades
+             WHERE trade_type = ?1 AND status = ?2 AND action IN (?3, ?4)
+               AND (?5 IS NULL OR expiration >= ?5)
+               AND (?6 IS NULL OR expiration <= ?6)
+               AND deleted_at IS NULL AND {}
+             ORDER BY expiration, symbol, id",
+            Self::SELECT_COLUMNS,
+            account_filter(self.account, 7)
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let trades = stmt.query_map(
+            params![
+                TradeType::Option,
+                OptionStatus::Open,
+                Action::BuyToOpen,
+                Action::SellToOpen,
+                from,
+                until,
+                self.account
+            ],
+            Self::row_to_trade,
+        )?;
+        trades.collect()
+    }
+
+    pub fn get_trade(&self, id: i64) -> Result<Option<Trade>> {
+        let sql = format!(
+            "SELECT {} FROM trades WHERE id = ?1 AND deleted_at IS NULL",
+            Self::SELECT_COLUMNS
+        );
+        self.conn
+            .query_row(&sql, params![id], Self::row_to_trade)
+            .optional()
+    }
+
+    pub fn update_trade(&self, trade: &Trade) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        self.write_trade(trade)?;
+        tx.commit()
+    }
+
+    /// Makes `edit` to every trade in `ids` in one transaction and returns
+    /// how many it changed. Generated stock rows are skipped; they are
+    /// rebuilt from their options.
+    pub fn bulk_edit(&self, ids: &[i64], edit: &BulkEdit) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut changed = 0;
+        for &id in ids {
+            let Some(trade) = self.get_trade(id)? else {
+                continue;
+            };
+            if trade.assigned_from.is_some() || edit.changes(&trade, |_| String::new()).is_empty() {
+                continue;
+            }
+            self.write_trade(&edit.apply(&trade))?;
+            changed += 1;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    // Saves an edited trade, within the caller's transaction.
+    fn write_trade(&self, trade: &Trade) -> Result<()> {
+        if let Some(id) = trade.id {
+            self.conn.execute(
+                "UPDATE trades
+                 SET symbol = ?1, trade_type = ?2, action = ?3, price = ?4,
+                     quantity = ?5, date = ?6, fees = ?7, comment = ?8,
                      option_type = ?9, strike = ?10, expiration = ?11,
-                     status = ?12, assigned_from = ?13
-                 WHERE id = ?14",
+                     status = ?12, contract_multiplier = ?13, assigned_from = ?14,
+                     roll_group_id = ?15, strategy_id = ?16, delta = ?17, gamma = ?18,
+                     theta = ?19, vega = ?20, iv = ?21, account_id = ?22, currency = ?23,
+                     broker = ?24
+                 WHERE id = ?25",
                 params![
                     trade.symbol,
                     trade.trade_type,
@@ -297,10 +1379,22 @@ impl Database {
                     trade.strike.map(|d| d.to_string()),
                     trade.expiration,
                     trade.status,
+                    trade.contract_multiplier.map(|d| d.to_string()),
                     trade.assigned_from,
+                    trade.roll_group_id,
+                    trade.strategy_id,
+                    trade.greeks.delta.map(|d| d.to_string()),
+                    trade.greeks.gamma.map(|d| d.to_string()),
+                    trade.greeks.theta.map(|d| d.to_string()),
+                    trade.greeks.vega.map(|d| d.to_string()),
+                    trade.greeks.iv.map(|d| d.to_string()),
+                    trade.account_id,
+                    trade.currency,
+                    trade.broker,
                     id,
                 ],
             )?;
+            self.write_tags(id, &trade.tags)?;
             // Reconcile auto-generated linked stock rows: clear any existing rows
             // for this option, then regenerate them if the edited option is still
             // in a stock-generating status (Assigned/Exercised). This keeps the
@@ -315,24 +1409,363 @@ impl Database {
                     }
                 }
             }
-            tx.commit()?;
         }
         Ok(())
     }
 
-    /// Deletes a trade. When the trade is an option, its auto-generated linked
-    /// stock rows are deleted too so the ledger never keeps orphaned assignment
-    /// rows.
+    /// Moves a trade, and the stock rows an assigned or exercised option
+    /// generated, to the Trash. Trashed trades are left out of every read and
+    /// report but keep their links, so [`Database::restore_trade`] brings
+    /// them back as they were.
     pub fn delete_trade(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE trades SET deleted_at = datetime('now')
+             WHERE (id = ?1 OR assigned_from = ?1) AND deleted_at IS NULL",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    /// Moves every trade in `ids` to the Trash at once, as [`Self::delete_trade`]
+    /// does one.
+    pub fn delete_trades(&self, ids: &[i64]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for &id in ids {
+            self.delete_trade(id)?;
+        }
+        tx.commit()
+    }
+
+    /// Adds `tags` to every trade in `ids`, keeping the tags they have; a tag
+    /// a trade already has in another case is left as it is.
+    pub fn tag_trades(&self, ids: &[i64], tags: &[String]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for &id in ids {
+            let Some(trade) = self.get_trade(id)? else {
+                continue;
+            };
+            let mut merged = trade.tags;
+            for tag in tags {
+                if !merged.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    merged.push(tag.clone());
+                }
+            }
+            self.write_tags(id, &merged)?;
+        }
+        tx.commit()
+    }
+
+    /// Takes a trade and its generated stock rows back out of the Trash.
+    pub fn restore_trade(&self, id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE trades SET deleted_at = NULL WHERE id = ?1 OR assigned_from = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    // Every trashed trade, generated stock rows included.
+    fn trashed_trades(&self) -> Result<Vec<Trade>> {
+        let sql = format!(
+            "SELECT {} FROM trades WHERE deleted_at IS NOT NULL",
+            Self::SELECT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let trades = stmt.query_map([], Self::row_to_trade)?;
+        trades.collect()
+    }
+
+    /// Trades in the Trash, most recently deleted first. Generated stock rows
+    /// are left out; they go and come back with their options.
+    pub fn get_deleted_trades(&self) -> Result<Vec<Trade>> {
+        let sql = format!(
+            "SELECT {} FROM trades
+             WHERE deleted_at IS NOT NULL
+               AND (assigned_from IS NULL
+                    OR assigned_from NOT IN (SELECT id FROM trades WHERE deleted_at IS NOT NULL))
+             ORDER BY deleted_at DESC, id DESC",
+            Self::SELECT_COLUMNS
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let trades = stmt.query_map([], Self::row_to_trade)?;
+        trades.collect()
+    }
+
+    /// Deletes every trade in the Trash for good, with its generated stock
+    /// rows, tags, covered-call links, and lot matches. Returns how many
+    /// trades were removed.
+    pub fn purge_trash(&self) -> Result<usize> {
         let tx = self.conn.unchecked_transaction()?;
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id FROM trades WHERE deleted_at IS NOT NULL")?;
+        let ids: Vec<i64> = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+        for &id in &ids {
+            self.purge_trade(id)?;
+        }
+        tx.commit()?;
+        Ok(ids.len())
+    }
+
+    // Deletes a trade outright, with everything linked to it.
+    fn purge_trade(&self, id: i64) -> Result<()> {
         self.delete_linked_stock_rows(id)?;
+        self.conn.execute(
+            "DELETE FROM covered_calls WHERE call_id = ?1 OR stock_id = ?1",
+            params![id],
+        )?;
+        self.conn.execute(
+            "DELETE FROM lot_matches WHERE close_id = ?1 OR open_id = ?1",
+            params![id],
+        )?;
+        self.write_tags(id, &[])?;
+        self.conn
+            .execute("DELETE FROM attachments WHERE trade_id = ?1", params![id])?;
         self.conn
             .execute("DELETE FROM trades WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    // Replaces the trade's tags, creating new ones and dropping any no trade
+    // has any more. Tags it keeps are left alone, so the history only shows
+    // real changes.
+    fn write_tags(&self, trade_id: i64, tags: &[String]) -> Result<()> {
+        let names = serde_json::to_string(tags).expect("tag names always serialize");
+        self.conn.execute(
+            "DELETE FROM trade_tags
+             WHERE trade_id = ?1 AND tag_id NOT IN
+                (SELECT id FROM tags WHERE name IN (SELECT value FROM json_each(?2)))",
+            params![trade_id, names],
+        )?;
+        for tag in tags {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
+                params![tag],
+            )?;
+            self.conn.execute(
+                "INSERT OR IGNORE INTO trade_tags (trade_id, tag_id)
+                 SELECT ?1, id FROM tags WHERE name = ?2",
+                params![trade_id, tag],
+            )?;
+        }
+        self.conn.execute(
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM trade_tags)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Every recorded change to a trade, oldest first.
+    pub fn get_trade_history(&self, trade_id: i64) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, trade_id, changed_at, change, before, after FROM trade_history
+             WHERE trade_id = ?1 ORDER BY id",
+        )?;
+        let entries = stmt.query_map(params![trade_id], Self::row_to_history)?;
+        entries.collect()
+    }
+
+    /// The id of the newest history entry, or 0 when nothing has been logged.
+    /// Taken before and after a change, it brackets the entries that change
+    /// logged for [`Database::undo_changes`] and [`Database::redo_changes`].
+    pub fn last_history_id(&self) -> Result<i64> {
+        self.conn.query_row(
+            "SELECT COALESCE(MAX(id), 0) FROM trade_history",
+            [],
+            |row| row.get(0),
+        )
+    }
+
+    /// Reverses the changes logged after entry `after` up to and including
+    /// entry `upto`, newest first, and returns the ids of the trades they
+    /// touched. The reversal is logged like any other change.
+    pub fn undo_changes(&self, after: i64, upto: i64) -> Result<Vec<i64>> {
+        self.replay_history(after, upto, false)
+    }
+
+    /// Makes the changes logged after entry `after` up to and including
+    /// entry `upto` again, oldest first, after they were undone, and returns
+    /// the ids of the trades they touched.
+    pub fn redo_changes(&self, after: i64, upto: i64) -> Result<Vec<i64>> {
+        self.replay_history(after, upto, true)
+    }
+
+    fn replay_history(&self, after: i64, upto: i64, forward: bool) -> Result<Vec<i64>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, trade_id, changed_at, change, before, after FROM trade_history
+             WHERE id > ?1 AND id <= ?2 ORDER BY id",
+        )?;
+        let mut entries = stmt
+            .query_map(params![after, upto], Self::row_to_history)?
+            .collect::<Result<Vec<_>>>()?;
+        if !forward {
+            entries.reverse();
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        for entry in &entries {
+            self.apply_history(entry, forward)?;
+        }
         tx.commit()?;
+        let mut touched: Vec<i64> = entries.iter().map(|entry| entry.trade_id).collect();
+        touched.sort_unstable();
+        touched.dedup();
+        Ok(touched)
+    }
+
+    // Puts a trade back the way `entry` left it (`forward`) or found it.
+    fn apply_history(&self, entry: &HistoryEntry, forward: bool) -> Result<()> {
+        let (from, to) = if forward {
+            (&entry.before, &entry.after)
+        } else {
+            (&entry.after, &entry.before)
+        };
+        let id = entry.trade_id;
+        match entry.change {
+            HistoryChange::Insert | HistoryChange::Update | HistoryChange::Delete => {
+                let column = |c: &&str| format!("json_extract(?1, '$.{}')", c);
+                match (from, to) {
+                    (_, None) => {
+                        self.conn
+                            .execute("DELETE FROM trades WHERE id = ?1", params![id])?;
+                    }
+                    (None, Some(row)) => {
+                        let values: Vec<String> =
+                            Self::HISTORY_COLUMNS.iter().map(column).collect();
+                        self.conn.execute(
+                            &format!(
+                                "INSERT INTO trades (id, {}) SELECT ?2, {}",
+                                Self::HISTORY_COLUMNS.join(", "),
+                                values.join(", ")
+                            ),
+                            params![row, id],
+                        )?;
+                    }
+                    (Some(_), Some(row)) => {
+                        let sets: Vec<String> = Self::HISTORY_COLUMNS
+                            .iter()
+                            .map(|c| format!("{} = {}", c, column(c)))
+                            .collect();
+                        self.conn.execute(
+                            &format!("UPDATE trades SET {} WHERE id = ?2", sets.join(", ")),
+                            params![row, id],
+                        )?;
+                    }
+                }
+            }
+            HistoryChange::TagAdded | HistoryChange::TagRemoved => {
+                let added = (entry.change == HistoryChange::TagAdded) == forward;
+                let tag = entry.after.as_ref().or(entry.before.as_ref());
+                if added {
+                    self.conn.execute(
+                        "INSERT OR IGNORE INTO tags (name) VALUES (json_extract(?1, '$.tag'))",
+                        params![tag],
+                    )?;
+                    self.conn.execute(
+                        "INSERT OR IGNORE INTO trade_tags (trade_id, tag_id)
+                         SELECT ?1, id FROM tags WHERE name = json_extract(?2, '$.tag')",
+                        params![id, tag],
+                    )?;
+                } else {
+                    self.conn.execute(
+                        "DELETE FROM trade_tags WHERE trade_id = ?1 AND tag_id IN
+                            (SELECT id FROM tags WHERE name = json_extract(?2, '$.tag'))",
+                        params![id, tag],
+                    )?;
+                    self.conn.execute(
+                        "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM trade_tags)",
+                        [],
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn row_to_history(row: &rusqlite::Row<'_>) -> Result<HistoryEntry> {
+        Ok(HistoryEntry {
+            id: Some(row.get(0)?),
+            trade_id: row.get(1)?,
+            changed_at: row.get(2)?,
+            change: row.get(3)?,
+            before: row.get(4)?,
+            after: row.get(5)?,
+        })
+    }
+
+    /// Ids of the trades whose symbol or comment has a word starting with
+    /// each word of `text`, in any order and case: `earn gap` finds "Earnings
+    /// gap down". Trashed trades and other accounts aren't filtered out here.
+    pub fn search_trades(&self, text: &str) -> Result<HashSet<i64>> {
+        let Some(expression) = search_expression(text) else {
+            return Ok(HashSet::new());
+        };
+        let mut stmt = self
+            .conn
+            .prepare("SELECT rowid FROM trades_fts WHERE trades_fts MATCH ?1")?;
+        let ids = stmt.query_map(params![expression], |row| row.get(0))?;
+        ids.collect()
+    }
+
+    /// Every tag in use, alphabetically.
+    pub fn get_tags(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name FROM tags ORDER BY name COLLATE NOCASE")?;
+        let tags = stmt.query_map([], |row| row.get(0))?;
+        tags.collect()
+    }
+
+    /// Attaches a file to a trade, stamped with the current time.
+    pub fn add_attachment(&self, attachment: &Attachment) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO attachments (trade_id, name, path, data, added_at)
+             VALUES (?1, ?2, ?3, ?4, datetime('now'))",
+            params![
+                attachment.trade_id,
+                attachment.name,
+                attachment.path,
+                attachment.data
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// The files attached to a trade, oldest first.
+    pub fn get_attachments(&self, trade_id: i64) -> Result<Vec<Attachment>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, trade_id, name, path, data, added_at FROM attachments
+             WHERE trade_id = ?1 ORDER BY id",
+        )?;
+        let attachments = stmt.query_map(params![trade_id], Self::row_to_attachment)?;
+        attachments.collect()
+    }
+
+    pub fn delete_attachment(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM attachments WHERE id = ?1", [id])?;
         Ok(())
     }
 
+    fn row_to_attachment(row: &rusqlite::Row) -> Result<Attachment> {
+        Ok(Attachment {
+            id: Some(row.get(0)?),
+            trade_id: row.get(1)?,
+            name: row.get(2)?,
+            path: row.get(3)?,
+            data: row.get(4)?,
+            added_at: row.get(5)?,
+        })
+    }
+
     fn delete_linked_stock_rows(&self, option_id: i64) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM lot_matches
+             WHERE close_id IN (SELECT id FROM trades WHERE assigned_from = ?1)
+                OR open_id IN (SELECT id FROM trades WHERE assigned_from = ?1)",
+            params![option_id],
+        )?;
         self.conn.execute(
             "DELETE FROM trades WHERE assigned_from = ?1",
             params![option_id],
@@ -343,8 +1776,8 @@ impl Database {
     /// Marks an open option as assigned or exercised and inserts the linked stock
     /// trade at the option's strike. Direction depends on the option's type and
     /// long/short side (short put assigned → buy, short call assigned → sell,
-    /// long put exercised → sell, long call exercised → buy), for `qty * 100`
-    /// shares. Late reconciliation is allowed — a past expiration does not block
+    /// long put exercised → sell, long call exercised → buy), for `qty *
+    /// multiplier` shares. Late reconciliation is allowed — a past expiration does not block
     /// this. No additional option cash flow is recorded; the premium was already
     /// booked when the option was opened.
     pub fn assign_option(&self, option_id: i64, status: OptionStatus) -> Result<i64> {
@@ -370,7 +1803,7 @@ impl Database {
     }
 
     /// Inserts the linked stock trade produced by assigning/exercising `option`
-    /// at its strike for `qty * 100` shares, tagged with `assigned_from =
+    /// at its strike for `qty * multiplier` shares, tagged with `assigned_from =
     /// option.id`. The buy/sell direction depends on the option type and its
     /// long/short side (see the match below). Returns the new row id. Callers are
     /// responsible for clearing any prior linked rows and for running inside a
@@ -405,7 +1838,7 @@ impl Database {
             trade_type: TradeType::Stock,
             action: stock_action,
             price: strike,
-            quantity: option.quantity * OPTION_MULTIPLIER,
+            quantity: option.quantity * option.multiplier(),
             date: option.expiration.clone().unwrap_or_else(crate::date::today),
             fees: Decimal::ZERO,
             comment: format!("Auto: {} {} of option #{}", option_type, status, option_id),
@@ -413,7 +1846,16 @@ impl Database {
             strike: None,
             expiration: None,
             status: None,
+            contract_multiplier: None,
             assigned_from: Some(option_id),
+            roll_group_id: None,
+            strategy_id: None,
+            account_id: option.account_id,
+            tags: Vec::new(),
+            deleted_at: None,
+            greeks: Greeks::default(),
+            currency: option.currency.clone(),
+            broker: option.broker.clone(),
         };
         self.add_trade(&stock)
     }
@@ -422,112 +1864,1717 @@ impl Database {
     /// (the premium was already booked when the option was opened) and removes
     /// any linked stock rows from a prior assignment.
     pub fn expire_option(&self, option_id: i64) -> Result<()> {
+        self.expire_options(&[option_id])
+    }
+
+    /// Marks every option in `option_ids` as expired, as
+    /// [`Database::expire_option`] does, all or none of them.
+    pub fn expire_options(&self, option_ids: &[i64]) -> Result<()> {
         let tx = self.conn.unchecked_transaction()?;
-        self.delete_linked_stock_rows(option_id)?;
-        self.conn.execute(
-            "UPDATE trades SET status = ?1 WHERE id = ?2",
-            params![OptionStatus::Expired, option_id],
-        )?;
+        for &option_id in option_ids {
+            self.delete_linked_stock_rows(option_id)?;
+            self.conn.execute(
+                "UPDATE trades SET status = ?1 WHERE id = ?2",
+                params![OptionStatus::Expired, option_id],
+            )?;
+        }
         tx.commit()?;
         Ok(())
     }
 
-    /// Net signed share position for a symbol (long > 0, short < 0), summed over
-    /// stock trades (including assignment-generated rows).
-    pub fn net_shares(&self, symbol: &str) -> Result<Decimal> {
+    /// Quantity still open on the long or short side of `key`, pairing every
+    /// open with the closes against it. `exclude_id` leaves out one trade — the
+    /// pre-edit version of a trade being saved.
+    pub fn open_quantity(
+        &self,
+        key: &PositionKey,
+        short: bool,
+        exclude_id: Option<i64>,
+    ) -> Result<Decimal> {
         Ok(self
             .get_all_trades()?
             .iter()
-            .filter(|t| t.symbol == symbol)
-            .map(Trade::signed_shares)
+            .filter(|t| (exclude_id.is_none() || t.id != exclude_id) && t.position_key() == *key)
+            .map(|t| t.open_quantity_change(short))
             .sum())
     }
 
-    /// Break-even price for a symbol's current net share position, derived from
-    /// the full ledger: `-(sum of all cash flows) / net_shares`. This folds in
-    /// collected option premium and all fees, so it works for both long and
-    /// short positions. Returns `None` when the net position is flat.
-    pub fn get_break_even(&self, symbol: &str) -> Result<Option<Decimal>> {
-        self.get_break_even_excluding(symbol, None)
-    }
-
-    /// Like [`get_break_even`], but ignores the trade whose id equals
-    /// `exclude_id` (if any). Used by the covered-call warning when *editing* an
-    /// option: the pre-edit version of the option being saved is still in the
-    /// ledger, and since option premium folds into break-even it would otherwise
-    /// skew the warning threshold. Pass `None` to include every trade.
-    pub fn get_break_even_excluding(
+    /// Rolls an open option: closes it with an offsetting trade at
+    /// `close_price`/`close_fees` and opens `new_leg` in its place, all in one
+    /// transaction. The closing and new legs (and the rolled option) share a
+    /// `roll_group_id` — the original chain's group when the option was itself
+    /// the product of a roll, otherwise the rolled option's id — so chains of
+    /// successive rolls report together. The closing trade is dated like the
+    /// new leg. Returns the new leg's id.
+    pub fn roll_option(
         &self,
-        symbol: &str,
-        exclude_id: Option<i64>,
-    ) -> Result<Option<Decimal>> {
-        let trades: Vec<Trade> = self
-            .get_all_trades()?
-            .into_iter()
-            .filter(|t| t.symbol == symbol && (exclude_id.is_none() || t.id != exclude_id))
-            .collect();
-        let net_shares: Decimal = trades.iter().map(Trade::signed_shares).sum();
-        if net_shares == Decimal::ZERO {
-            return Ok(None);
+        option_id: i64,
+        close_price: Decimal,
+        close_fees: Decimal,
+        new_leg: &Trade,
+    ) -> Result<i64> {
+        let option = self
+            .get_trade(option_id)?
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        if option.trade_type != TradeType::Option || option.status != Some(OptionStatus::Open) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "only an open option can be rolled".to_string(),
+            ));
         }
-        let total_cash_flow: Decimal = trades.iter().map(Trade::cash_flow).sum();
-        Ok(Some(-total_cash_flow / net_shares))
-    }
+        let group = option.roll_group_id.unwrap_or(option_id);
 
-    pub fn get_report_by_symbol(&self) -> Result<Vec<SymbolReport>> {
-        let trades = self.get_all_trades()?;
-        let mut symbols: Vec<String> = trades.iter().map(|t| t.symbol.clone()).collect();
-        symbols.sort();
-        symbols.dedup();
+        let close = Trade {
+            id: None,
+            action: option.action.closing(),
+            price: close_price,
+            date: new_leg.date.clone(),
+            fees: close_fees,
+            comment: format!("Roll: close of option #{}", option_id),
+            status: Some(OptionStatus::Closed),
+            assigned_from: None,
+            roll_group_id: Some(group),
+            ..option.clone()
+        };
+        let opened = Trade {
+            id: None,
+            trade_type: TradeType::Option,
+            status: Some(OptionStatus::Open),
+            assigned_from: None,
+            roll_group_id: Some(group),
+            ..new_leg.clone()
+        };
 
-        let mut reports = Vec::with_capacity(symbols.len());
-        for symbol in symbols {
-            let symbol_trades: Vec<&Trade> = trades.iter().filter(|t| t.symbol == symbol).collect();
-            let profit_loss: Decimal = symbol_trades.iter().map(|t| t.cash_flow()).sum();
-            let net_shares: Decimal = symbol_trades.iter().map(|t| t.signed_shares()).sum();
-            let trade_count = symbol_trades.len() as i32;
-            let break_even = self.get_break_even(&symbol)?;
-            reports.push(SymbolReport {
-                symbol,
-                profit_loss,
-                trade_count,
-                net_shares,
-                break_even,
-            });
-        }
-        Ok(reports)
+        let tx = self.conn.unchecked_transaction()?;
+        self.conn.execute(
+            "UPDATE trades SET status = ?1, roll_group_id = ?2 WHERE id = ?3",
+            params![OptionStatus::Closed, group, option_id],
+        )?;
+        self.add_trade(&close)?;
+        let new_id = self.add_trade(&opened)?;
+        tx.commit()?;
+        Ok(new_id)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Every roll chain with its combined cash flow, ordered by group id. A
+    /// positive `net_credit` means the chain collected more premium than it paid.
+    pub fn get_roll_chains(&self) -> Result<Vec<RollChain>> {
+        let trades = self.get_all_trades()?;
+        let mut groups: Vec<i64> = trades.iter().filter_map(|t| t.roll_group_id).collect();
+        groups.sort();
+        groups.dedup();
 
-    fn new_test_db() -> Database {
-        Database::new(":memory:").expect("failed to create in-memory database")
+        Ok(groups
+            .into_iter()
+            .map(|group| {
+                let legs: Vec<&Trade> = trades
+                    .iter()
+                    .filter(|t| t.roll_group_id == Some(group))
+                    .collect();
+                RollChain {
+                    roll_group_id: group,
+                    symbol: legs[0].symbol.clone(),
+                    leg_count: legs.len() as i32,
+                    net_credit: legs.iter().map(|t| t.cash_flow()).sum(),
+                }
+            })
+            .collect())
     }
 
-    fn stock(
-        symbol: &str,
-        action: Action,
-        price: Decimal,
-        quantity: Decimal,
-        fees: Decimal,
-    ) -> Trade {
-        Trade {
-            symbol: symbol.to_string(),
-            trade_type: TradeType::Stock,
-            action,
-            price,
-            quantity,
-            date: "2024-01-15".to_string(),
-            fees,
-            ..Default::default()
+    /// Links a sold call to the bought stock lot that covers it, replacing any
+    /// previous link for the call. The lot must be the same symbol and hold at
+    /// least the shares the call covers.
+    pub fn link_covered_call(&self, call_id: i64, stock_id: i64) -> Result<()> {
+        let call = self
+            .get_trade(call_id)?
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let lot = self
+            .get_trade(stock_id)?
+            .ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        if call.option_type != Some(OptionType::Call) || call.action.is_buy() {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "only a sold call can be covered".to_string(),
+            ));
         }
+        if !Self::lot_covers(&lot, &call) {
+            return Err(rusqlite::Error::InvalidParameterName(
+                "the lot must be a stock buy of the same symbol with enough shares".to_string(),
+            ));
+        }
+        self.conn.execute(
+            "INSERT OR REPLACE INTO covered_calls (call_id, stock_id) VALUES (?1, ?2)",
+            params![call_id, stock_id],
+        )?;
+        Ok(())
     }
 
-    fn option(
-        symbol: &str,
+    pub fn unlink_covered_call(&self, call_id: i64) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM covered_calls WHERE call_id = ?1",
+            params![call_id],
+        )?;
+        Ok(())
+    }
+
+    /// Whether `lot` is a bought stock lot of the call's symbol large enough to
+    /// cover it.
+    pub fn lot_covers(lot: &Trade, call: &Trade) -> bool {
+        lot.trade_type == TradeType::Stock
+            && lot.action.is_buy()
+            && lot.symbol == call.symbol
+            && lot.quantity >= call.quantity * call.multiplier()
+    }
+
+    /// The stock lot currently covering `call_id`, if any.
+    pub fn get_covering_lot(&self, call_id: i64) -> Result<Option<i64>> {
+        self.conn
+            .query_row(
+                "SELECT stock_id FROM covered_calls WHERE call_id = ?1",
+                params![call_id],
+                |row| row.get(0),
+            )
+            .optional()
+    }
+
+    /// Trade `id` with the trades tied to it and its lot matches under
+    /// `method`; `None` if there is no such trade.
+    pub fn get_trade_detail(
+        &self,
+        id: i64,
+        method: CostBasisMethod,
+    ) -> Result<Option<TradeDetail>> {
+        let Some(trade) = self.get_trade(id)? else {
+            return Ok(None);
+        };
+        let all = self.get_all_trades()?;
+        let mut covers = self
+            .conn
+            .prepare(
+                "SELECT call_id, stock_id FROM covered_calls WHERE call_id = ?1 OR stock_id = ?1",
+            )?
+            .query_map(params![id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        covers.retain(|(call, stock)| *call != *stock);
+        let relation = |other: &Trade| -> Option<&'static str> {
+            let shared = |a: Option<i64>, b: Option<i64>| a.is_some() && a == b;
+            if shared(other.strategy_id, trade.strategy_id) {
+                Some("strategy leg")
+            } else if shared(other.roll_group_id, trade.roll_group_id) {
+                Some("roll")
+            } else if trade.assigned_from == other.id {
+                Some("assigned from")
+            } else if other.assigned_from == Some(id) {
+                Some("assignment")
+            } else if covers
+                .iter()
+                .any(|&(call, stock)| call == id && other.id == Some(stock))
+            {
+                Some("covering lot")
+            } else if covers
+                .iter()
+                .any(|&(call, stock)| stock == id && other.id == Some(call))
+            {
+                Some("covered call")
+            } else {
+                None
+            }
+        };
+        let mut linked: Vec<(&'static str, Trade)> = all
+            .iter()
+            .filter(|other| other.id != Some(id))
+            .filter_map(|other| Some((relation(other)?, other.clone())))
+            .collect();
+        linked.sort_by(|a, b| (&a.1.date, a.1.id).cmp(&(&b.1.date, b.1.id)));
+        let matches = match_lots(&all, method, &self.get_lot_overrides()?)
+            .into_iter()
+            .filter(|m| m.open_id == Some(id) || m.close_id == Some(id))
+            .collect();
+        Ok(Some(TradeDetail {
+            trade,
+            linked,
+            matches,
+        }))
+    }
+
+    /// Every covered call with its lot's combined return, ordered by call id.
+    pub fn get_covered_positions(&self) -> Result<Vec<CoveredPosition>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT call_id, stock_id FROM covered_calls ORDER BY call_id")?;
+        let links: Vec<(i64, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_>>()?;
+
+        let mut positions = Vec::with_capacity(links.len());
+        for (call_id, stock_id) in links {
+            let (call, lot) = match (self.get_trade(call_id)?, self.get_trade(stock_id)?) {
+                (Some(call), Some(lot)) => (call, lot),
+                _ => continue,
+            };
+            let shares = call.quantity * call.multiplier();
+            let lot_fees = if lot.quantity > Decimal::ZERO {
+                lot.fees * shares / lot.quantity
+            } else {
+                Decimal::ZERO
+            };
+            let stock_cost = lot.price * shares + lot_fees;
+            let premium = call.cash_flow();
+            let called_away = call
+                .status
+                .as_ref()
+                .map(OptionStatus::triggers_stock_event)
+                .unwrap_or(false);
+            let stock_gain = match (called_away, call.strike) {
+                (true, Some(strike)) => strike * shares - stock_cost,
+                _ => Decimal::ZERO,
+            };
+            let total_return = premium + stock_gain;
+            let return_pct = if stock_cost > Decimal::ZERO {
+                total_return / stock_cost * dec!(100)
+            } else {
+                Decimal::ZERO
+            };
+            positions.push(CoveredPosition {
+                call_id,
+                stock_id,
+                symbol: call.symbol,
+                shares,
+                stock_cost,
+                premium,
+                called_away,
+                stock_gain,
+                total_return,
+                return_pct,
+            });
+        }
+        Ok(positions)
+    }
+
+    pub fn add_strategy(&self, strategy: &Strategy) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO strategies (name, kind) VALUES (?1, ?2)",
+            params![strategy.name, strategy.kind],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Creates `strategy` and inserts `legs` as its legs in one transaction.
+    /// Returns the new strategy's id.
+    pub fn add_strategy_with_legs(&self, strategy: &Strategy, legs: &[Trade]) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+        let strategy_id = self.add_strategy(strategy)?;
+        for leg in legs {
+            self.add_trade(&Trade {
+                strategy_id: Some(strategy_id),
+                ..leg.clone()
+            })?;
+        }
+        tx.commit()?;
+        Ok(strategy_id)
+    }
+
+    pub fn get_strategies(&self) -> Result<Vec<Strategy>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, kind FROM strategies ORDER BY id")?;
+        let strategies = stmt.query_map([], |row| {
+            Ok(Strategy {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                kind: row.get(2)?,
+            })
+        })?;
+        strategies.collect()
+    }
+
+    /// Makes a trade a leg of `strategy_id`, or removes it from its strategy
+    /// when `None`.
+    pub fn set_trade_strategy(&self, trade_id: i64, strategy_id: Option<i64>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE trades SET strategy_id = ?1 WHERE id = ?2",
+            params![strategy_id, trade_id],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a strategy. Its legs are kept and simply ungrouped.
+    pub fn delete_strategy(&self, strategy_id: i64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        self.conn.execute(
+            "UPDATE trades SET strategy_id = NULL WHERE strategy_id = ?1",
+            params![strategy_id],
+        )?;
+        self.conn
+            .execute("DELETE FROM strategies WHERE id = ?1", params![strategy_id])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Adds an account and returns its id. Names are unique.
+    pub fn add_account(&self, account: &Account) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO accounts (name, currency) VALUES (?1, ?2)",
+            params![account.name, account.currency],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Every account, by name.
+    pub fn get_accounts(&self) -> Result<Vec<Account>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, currency FROM accounts ORDER BY name")?;
+        let accounts = stmt.query_map([], |row| {
+            Ok(Account {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                currency: row.get(2)?,
+            })
+        })?;
+        accounts.collect()
+    }
+
+    /// Deletes an account. Its trades are kept without an account, and reads
+    /// limited to it go back to every account.
+    pub fn delete_account(&mut self, account_id: i64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for table in ["trades", "dividends", "cash_transactions"] {
+            tx.execute(
+                &format!(
+                    "UPDATE {} SET account_id = NULL WHERE account_id = ?1",
+                    table
+                ),
+                params![account_id],
+            )?;
+        }
+        tx.execute("DELETE FROM accounts WHERE id = ?1", params![account_id])?;
+        tx.commit()?;
+        if self.account == Some(account_id) {
+            self.account = None;
+        }
+        Ok(())
+    }
+
+    /// Every strategy with its leg count and combined P/L, ordered by id.
+    /// Strategies without legs are included with zero figures.
+    pub fn get_strategy_summaries(&self) -> Result<Vec<StrategySummary>> {
+        let trades = self.get_all_trades()?;
+        Ok(self
+            .get_strategies()?
+            .into_iter()
+            .map(|strategy| {
+                let legs: Vec<&Trade> = trades
+                    .iter()
+                    .filter(|t| t.strategy_id.is_some() && t.strategy_id == strategy.id)
+                    .collect();
+                StrategySummary {
+                    leg_count: legs.len() as i32,
+                    profit_loss: legs.iter().map(|t| t.cash_flow()).sum(),
+                    strategy,
+                }
+            })
+            .collect())
+    }
+
+    /// Records a corporate action and rewrites the trades it affects, in one
+    /// transaction. A split of ratio `r` multiplies stock quantities by `r` and
+    /// divides prices by `r`. Options have their strike and premium divided by
+    /// `r` too; a whole-number ratio multiplies the contract count, any other
+    /// ratio the contract multiplier (a 3:2 split leaves one contract
+    /// delivering 150 shares). Cash flows and fees are unchanged. A symbol
+    /// change renames the trades. Marks for the old symbol are dropped, since
+    /// they no longer apply. Returns the action's id.
+    pub fn apply_corporate_action(&self, action: &CorporateAction) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+        self.conn.execute(
+            "INSERT INTO corporate_actions (symbol, kind, date, ratio, new_symbol)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                action.symbol,
+                action.kind,
+                action.date,
+                action.ratio.map(|r| r.to_string()),
+                action.new_symbol,
+            ],
+        )?;
+        let action_id = self.conn.last_insert_rowid();
+
+        let affected = self
+            .trades_in_account(None)?
+            .into_iter()
+            .filter(|t| t.symbol == action.symbol && t.date < action.date);
+        for mut trade in affected {
+            match action.kind {
+                CorporateActionKind::Split => {
+                    let ratio = match action.ratio {
+                        Some(ratio) if ratio > Decimal::ZERO => ratio,
+                        _ => continue,
+                    };
+                    Self::split_trade(&mut trade, ratio);
+                }
+                CorporateActionKind::SymbolChange => match &action.new_symbol {
+                    Some(new_symbol) => trade.symbol = new_symbol.clone(),
+                    None => continue,
+                },
+            }
+            self.conn.execute(
+                "UPDATE trades
+                 SET symbol = ?1, price = ?2, quantity = ?3, strike = ?4,
+                     contract_multiplier = ?5
+                 WHERE id = ?6",
+                params![
+                    trade.symbol,
+                    trade.price.to_string(),
+                    trade.quantity.to_string(),
+                    trade.strike.map(|d| d.to_string()),
+                    trade.contract_multiplier.map(|d| d.to_string()),
+                    trade.id,
+                ],
+            )?;
+        }
+
+        // An option quote is keyed by its OCC symbol: the underlying followed
+        // by a fixed 15-character suffix.
+        self.conn.execute(
+            "DELETE FROM quotes
+             WHERE instrument = ?1
+                OR (length(instrument) = length(?1) + 15
+                    AND substr(instrument, 1, length(?1)) = ?1)",
+            params![action.symbol],
+        )?;
+        tx.commit()?;
+        Ok(action_id)
+    }
+
+    fn split_trade(trade: &mut Trade, ratio: Decimal) {
+        trade.price = (trade.price / ratio).normalize();
+        match trade.trade_type {
+            TradeType::Stock => trade.quantity = (trade.quantity * ratio).normalize(),
+            TradeType::Option => {
+                trade.strike = trade.strike.map(|strike| (strike / ratio).normalize());
+                if ratio.fract() == Decimal::ZERO {
+                    trade.quantity = (trade.quantity * ratio).normalize();
+                } else {
+                    trade.contract_multiplier = Some((trade.multiplier() * ratio).normalize());
+                }
+            }
+        }
+    }
+
+    pub fn get_corporate_actions(&self) -> Result<Vec<CorporateAction>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, symbol, kind, date, ratio, new_symbol
+             FROM corporate_actions ORDER BY date DESC, id DESC",
+        )?;
+        let actions = stmt.query_map([], |row| {
+            Ok(CorporateAction {
+                id: Some(row.get(0)?),
+                symbol: row.get(1)?,
+                kind: row.get(2)?,
+                date: row.get(3)?,
+                ratio: opt_decimal_from_row(row, 4)?,
+                new_symbol: row.get(5)?,
+            })
+        })?;
+        actions.collect()
+    }
+
+    pub fn add_alert(&self, alert: &Alert) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO alerts (instrument, kind, threshold, triggered_at, triggered_value)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                alert.instrument,
+                alert.kind,
+                alert.threshold.to_string(),
+                alert.triggered_at,
+                alert.triggered_value.map(|d| d.to_string()),
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Every alert, in the order they were added.
+    pub fn get_alerts(&self) -> Result<Vec<Alert>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, instrument, kind, threshold, triggered_at, triggered_value
+             FROM alerts ORDER BY id",
+        )?;
+        let alerts = stmt.query_map([], |row| {
+            Ok(Alert {
+                id: Some(row.get(0)?),
+                instrument: row.get(1)?,
+                kind: row.get(2)?,
+                threshold: decimal_from_row(row, 3)?,
+                triggered_at: row.get(4)?,
+                triggered_value: opt_decimal_from_row(row, 5)?,
+            })
+        })?;
+        alerts.collect()
+    }
+
+    /// Marks an alert triggered at `triggered_at` by `value`, or re-arms it
+    /// when `triggered` is `None`.
+    pub fn set_alert_triggered(&self, id: i64, triggered: Option<(&str, Decimal)>) -> Result<()> {
+        self.conn.execute(
+            "UPDATE alerts SET triggered_at = ?1, triggered_value = ?2 WHERE id = ?3",
+            params![
+                triggered.map(|(at, _)| at),
+                triggered.map(|(_, value)| value.to_string()),
+                id,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_alert(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM alerts WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Records the valuation for its date, replacing an earlier one that day.
+    pub fn save_snapshot(&self, snapshot: &ValuationSnapshot) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO valuation_snapshots (date, market_value, cash, account_value)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                snapshot.date,
+                snapshot.market_value.to_string(),
+                snapshot.cash.to_string(),
+                snapshot.account_value.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Every recorded valuation, oldest first.
+    pub fn get_snapshots(&self) -> Result<Vec<ValuationSnapshot>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date, market_value, cash, account_value
+             FROM valuation_snapshots ORDER BY date",
+        )?;
+        let snapshots = stmt.query_map([], |row| {
+            Ok(ValuationSnapshot {
+                date: row.get(0)?,
+                market_value: decimal_from_row(row, 1)?,
+                cash: decimal_from_row(row, 2)?,
+                account_value: decimal_from_row(row, 3)?,
+            })
+        })?;
+        snapshots.collect()
+    }
+
+    /// Stores daily bars, replacing any already stored for the same day.
+    pub fn save_price_bars(&self, bars: &[PriceBar]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for bar in bars {
+            tx.execute(
+                "INSERT OR REPLACE INTO prices (symbol, date, open, high, low, close, volume)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    bar.symbol,
+                    bar.date,
+                    bar.open.to_string(),
+                    bar.high.to_string(),
+                    bar.low.to_string(),
+                    bar.close.to_string(),
+                    bar.volume,
+                ],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Stored daily bars of `symbol`, oldest first.
+    pub fn get_price_bars(&self, symbol: &str) -> Result<Vec<PriceBar>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT symbol, date, open, high, low, close, volume
+             FROM prices WHERE symbol = ?1 ORDER BY date",
+        )?;
+        let bars = stmt.query_map([symbol], |row| {
+            Ok(PriceBar {
+                symbol: row.get(0)?,
+                date: row.get(1)?,
+                open: decimal_from_row(row, 2)?,
+                high: decimal_from_row(row, 3)?,
+                low: decimal_from_row(row, 4)?,
+                close: decimal_from_row(row, 5)?,
+                volume: row.get(6)?,
+            })
+        })?;
+        bars.collect()
+    }
+
+    pub fn add_dividend(&self, dividend: &Dividend) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO dividends (symbol, date, amount, qualified, account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                dividend.symbol,
+                dividend.date,
+                dividend.amount.to_string(),
+                dividend.qualified,
+                dividend.account_id,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Dividends in the current account paid within `range`, newest first.
+    pub fn get_dividends(&self, range: &DateRange) -> Result<Vec<Dividend>> {
+        self.dividends_in_account(self.account, range)
+    }
+
+    fn dividends_in_account(
+        &self,
+        account: Option<i64>,
+        range: &DateRange,
+    ) -> Result<Vec<Dividend>> {
+        let sql = format!(
+            "SELECT id, symbol, date, amount, qualified, account_id FROM dividends
+             WHERE (?1 IS NULL OR date >= ?1) AND (?2 IS NULL OR date <= ?2) AND {}
+             ORDER BY date DESC, id DESC",
+            account_filter(account, 3)
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let dividends = stmt.query_map(params![range.start, range.end, account], |row| {
+            Ok(Dividend {
+                id: Some(row.get(0)?),
+                symbol: row.get(1)?,
+                date: row.get(2)?,
+                amount: decimal_from_row(row, 3)?,
+                qualified: row.get(4)?,
+                account_id: row.get(5)?,
+            })
+        })?;
+        dividends.collect()
+    }
+
+    pub fn delete_dividend(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM dividends WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    pub fn add_cash_transaction(&self, transaction: &CashTransaction) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO cash_transactions (date, kind, amount, comment, account_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                transaction.date,
+                transaction.kind,
+                transaction.amount.to_string(),
+                transaction.comment,
+                transaction.account_id,
+            ],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Cash transactions in the current account dated within `range`, newest
+    /// first.
+    pub fn get_cash_transactions(&self, range: &DateRange) -> Result<Vec<CashTransaction>> {
+        self.cash_transactions_in_account(self.account, range)
+    }
+
+    fn cash_transactions_in_account(
+        &self,
+        account: Option<i64>,
+        range: &DateRange,
+    ) -> Result<Vec<CashTransaction>> {
+        let sql = format!(
+            "SELECT id, date, kind, amount, comment, account_id FROM cash_transactions
+             WHERE (?1 IS NULL OR date >= ?1) AND (?2 IS NULL OR date <= ?2) AND {}
+             ORDER BY date DESC, id DESC",
+            account_filter(account, 3)
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let transactions = stmt.query_map(params![range.start, range.end, account], |row| {
+            Ok(CashTransaction {
+                id: Some(row.get(0)?),
+                date: row.get(1)?,
+                kind: row.get(2)?,
+                amount: decimal_from_row(row, 3)?,
+                comment: row.get(4)?,
+                account_id: row.get(5)?,
+            })
+        })?;
+        transactions.collect()
+    }
+
+    pub fn delete_cash_transaction(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM cash_transactions WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Saves an exchange rate, replacing any for the same pair and date.
+    pub fn set_fx_rate(&self, rate: &FxRate) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO fx_rates (date, from_currency, to_currency, rate, source)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                rate.date,
+                rate.from,
+                rate.to,
+                rate.rate.to_string(),
+                rate.source
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Every exchange rate, newest first.
+    pub fn get_fx_rates(&self) -> Result<Vec<FxRate>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT date, from_currency, to_currency, rate, source FROM fx_rates
+             ORDER BY date DESC, from_currency, to_currency",
+        )?;
+        let rates = stmt.query_map([], |row| {
+            Ok(FxRate {
+                date: row.get(0)?,
+                from: row.get(1)?,
+                to: row.get(2)?,
+                rate: decimal_from_row(row, 3)?,
+                source: row.get(4)?,
+            })
+        })?;
+        rates.collect()
+    }
+
+    pub fn delete_fx_rate(&self, rate: &FxRate) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM fx_rates WHERE date = ?1 AND from_currency = ?2 AND to_currency = ?3",
+            params![rate.date, rate.from, rate.to],
+        )?;
+        Ok(())
+    }
+
+    /// The years before the one `today` is in that trades can be archived
+    /// through, oldest first (see [`crate::archive`]). Every account counts.
+    pub fn get_archivable_years(&self, today: &str) -> Result<Vec<i32>> {
+        let this_year = year_of(today).unwrap_or(i32::MAX);
+        Ok(closed_years(
+            &self.trades_in_account(None)?,
+            &self.get_lot_overrides()?,
+            this_year,
+        ))
+    }
+
+    /// Moves every trade dated up to the end of `year`, in every account, to
+    /// the archive, leaving summaries with their realized P/L under `method`
+    /// (including manual lot matches). Fails unless `year` is one of
+    /// [`Database::get_archivable_years`]. Returns how many trades were
+    /// archived.
+    pub fn archive_through(
+        &self,
+        year: i32,
+        method: CostBasisMethod,
+        today: &str,
+    ) -> Result<usize> {
+        if !self.get_archivable_years(today)?.contains(&year) {
+            return Err(rusqlite::Error::InvalidParameterName(format!(
+                "{} still has open positions, trades tied to later ones, or a loss washed by a later buy",
+                year
+            )));
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let mut trades = self.trades_in_account(None)?;
+        trades.retain(|t| year_of(&t.date).is_some_and(|y| y <= year));
+        let matches = match_lots(&trades, method, &self.get_lot_overrides()?);
+        for summary in summarize(&trades, &matches) {
+            self.add_archive_summary(&summary)?;
+        }
+        for trade in &trades {
+            let Some(id) = trade.id else { continue };
+            let mut stmt = self.conn.prepare(
+                "SELECT call_id, stock_id FROM covered_calls WHERE call_id = ?1 OR stock_id = ?1",
+            )?;
+            let covered_calls = stmt
+                .query_map([id], |row| {
+                    Ok(CoveredCall {
+                        call_id: row.get(0)?,
+                        stock_id: row.get(1)?,
+                    })
+                })?
+                .collect::<Result<_>>()?;
+            let archived = ArchivedTrade {
+                trade: trade.clone(),
+                covered_calls,
+                lot_matches: self
+                    .get_lot_overrides()?
+                    .into_iter()
+                    .filter(|o| o.close_id == id || o.open_id == id)
+                    .collect(),
+                attachments: self.get_attachments(id)?,
+            };
+            // A link between two archived trades goes with the first of them.
+            self.conn.execute(
+                "DELETE FROM covered_calls WHERE call_id = ?1 OR stock_id = ?1",
+                [id],
+            )?;
+            self.conn.execute(
+                "DELETE FROM lot_matches WHERE close_id = ?1 OR open_id = ?1",
+                [id],
+            )?;
+            self.conn
+                .execute("DELETE FROM attachments WHERE trade_id = ?1", [id])?;
+            self.write_tags(id, &[])?;
+            self.conn
+                .execute("DELETE FROM trades WHERE id = ?1", [id])?;
+            self.add_archived_trade(&archived)?;
+        }
+        tx.commit()?;
+        Ok(trades.len())
+    }
+
+    /// Moves the archived trades of `year` and every later year back among the
+    /// live trades and drops their summaries. Returns how many came back.
+    pub fn restore_archive_from(&self, year: i32) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let archived = self.archived_trades(Some(year))?;
+        for entry in &archived {
+            // A trade already back, through the change history, stays as it is.
+            let exists = self
+                .conn
+                .query_row(
+                    "SELECT 1 FROM trades WHERE id = ?1",
+                    [entry.trade.id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if !exists {
+                self.insert_trade(entry.trade.id, &entry.trade)?;
+            }
+        }
+        // Links go back once both their trades are, unless one of them is
+        // still archived or gone.
+        for entry in &archived {
+            for link in &entry.covered_calls {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO covered_calls (call_id, stock_id)
+                     SELECT ?1, ?2 WHERE (SELECT COUNT(*) FROM trades WHERE id IN (?1, ?2)) = 2",
+                    params![link.call_id, link.stock_id],
+                )?;
+            }
+            for lot in &entry.lot_matches {
+                self.conn.execute(
+                    "INSERT INTO lot_matches (close_id, open_id, quantity)
+                     SELECT ?1, ?2, ?3 WHERE (SELECT COUNT(*) FROM trades WHERE id IN (?1, ?2)) = 2",
+                    params![lot.close_id, lot.open_id, lot.quantity.to_string()],
+                )?;
+            }
+            for attachment in &entry.attachments {
+                self.conn.execute(
+                    "INSERT OR IGNORE INTO attachments (id, trade_id, name, path, data, added_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                    params![
+                        attachment.id,
+                        attachment.trade_id,
+                        attachment.name,
+                        attachment.path,
+                        attachment.data,
+                        attachment.added_at,
+                    ],
+                )?;
+            }
+        }
+        self.conn
+            .execute("DELETE FROM archived_trades WHERE year >= ?1", [year])?;
+        self.conn
+            .execute("DELETE FROM archive_summaries WHERE year >= ?1", [year])?;
+        tx.commit()?;
+        Ok(archived.len())
+    }
+
+    // Archived trades from `from_year` on (all of them for `None`), oldest
+    // first.
+    fn archived_trades(&self, from_year: Option<i32>) -> Result<Vec<ArchivedTrade>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT trade FROM archived_trades WHERE ?1 IS NULL OR year >= ?1 ORDER BY id",
+        )?;
+        let trades = stmt.query_map([from_year], |row| {
+            let json: String = row.get(0)?;
+            serde_json::from_str(&json)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+        })?;
+        trades.collect()
+    }
+
+    fn add_archived_trade(&self, archived: &ArchivedTrade) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO archived_trades (id, year, trade) VALUES (?1, ?2, ?3)",
+            params![
+                archived.trade.id,
+                year_of(&archived.trade.date),
+                serde_json::to_string(archived).expect("archived trades always serialize")
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn add_archive_summary(&self, summary: &ArchiveSummary) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO archive_summaries
+                (year, account_id, symbol, trades, cash_flow, fees, realized)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                summary.year,
+                summary.account_id,
+                summary.symbol,
+                summary.trades as i64,
+                summary.cash_flow.to_string(),
+                summary.fees.to_string(),
+                summary.realized.to_string(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Summaries of the archived trades in the current account, by year and
+    /// symbol.
+    pub fn get_archive_summaries(&self) -> Result<Vec<ArchiveSummary>> {
+        self.archive_summaries_in_account(self.account)
+    }
+
+    /// The last archived year, in any account, if any; reports leave out
+    /// every year through it.
+    pub fn archived_through(&self) -> Result<Option<i32>> {
+        self.conn
+            .query_row("SELECT MAX(year) FROM archive_summaries", [], |row| {
+                row.get(0)
+            })
+    }
+
+    fn archive_summaries_in_account(&self, account: Option<i64>) -> Result<Vec<ArchiveSummary>> {
+        let sql = format!(
+            "SELECT year, account_id, symbol, trades, cash_flow, fees, realized
+             FROM archive_summaries WHERE {}
+             ORDER BY year, symbol, account_id",
+            account_filter(account, 1)
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let summaries = stmt.query_map([account], |row| {
+            Ok(ArchiveSummary {
+                year: row.get(0)?,
+                account_id: row.get(1)?,
+                symbol: row.get(2)?,
+                trades: row.get::<_, i64>(3)? as usize,
+                cash_flow: decimal_from_row(row, 4)?,
+                fees: decimal_from_row(row, 5)?,
+                realized: decimal_from_row(row, 6)?,
+            })
+        })?;
+        summaries.collect()
+    }
+
+    /// Saves a new custom report; fails if its name is taken.
+    pub fn add_saved_report(&self, report: &SavedReport) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO saved_reports (name, filter, group_by) VALUES (?1, ?2, ?3)",
+            params![report.name, report.filter, report.group_by],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn update_saved_report(&self, report: &SavedReport) -> Result<()> {
+        self.conn.execute(
+            "UPDATE saved_reports SET name = ?1, filter = ?2, group_by = ?3 WHERE id = ?4",
+            params![report.name, report.filter, report.group_by, report.id],
+        )?;
+        Ok(())
+    }
+
+    /// Saved custom reports by name.
+    pub fn get_saved_reports(&self) -> Result<Vec<SavedReport>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, filter, group_by FROM saved_reports ORDER BY name")?;
+        let reports = stmt.query_map([], |row| {
+            Ok(SavedReport {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                filter: row.get(2)?,
+                group_by: row.get(3)?,
+            })
+        })?;
+        reports.collect()
+    }
+
+    pub fn delete_saved_report(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM saved_reports WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// Every manual lot match, in the order they were entered.
+    pub fn get_lot_overrides(&self) -> Result<Vec<LotOverride>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT close_id, open_id, quantity FROM lot_matches ORDER BY id")?;
+        let overrides = stmt.query_map([], |row| {
+            Ok(LotOverride {
+                close_id: row.get(0)?,
+                open_id: row.get(1)?,
+                quantity: decimal_from_row(row, 2)?,
+            })
+        })?;
+        overrides.collect()
+    }
+
+    /// Replaces the manual lot matches of a closing trade with `matches` of
+    /// `(open_id, quantity)`. An empty slice returns the trade to automatic
+    /// matching.
+    pub fn set_lot_overrides(&self, close_id: i64, matches: &[(i64, Decimal)]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        self.conn.execute(
+            "DELETE FROM lot_matches WHERE close_id = ?1",
+            params![close_id],
+        )?;
+        for (open_id, quantity) in matches {
+            self.conn.execute(
+                "INSERT INTO lot_matches (close_id, open_id, quantity) VALUES (?1, ?2, ?3)",
+                params![close_id, open_id, quantity.to_string()],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Opening trades a closing trade could close: the same stock or contract,
+    /// on the side the close reduces, dated on or before the close.
+    pub fn get_lot_candidates(&self, close: &Trade) -> Result<Vec<Trade>> {
+        let key = close.position_key();
+        let opener = if close.action.is_buy() {
+            Action::SellToOpen
+        } else {
+            Action::BuyToOpen
+        };
+        Ok(self
+            .get_all_trades()?
+            .into_iter()
+            .filter(|t| t.action == opener && t.position_key() == key && t.date <= close.date)
+            .collect())
+    }
+
+    /// Records (or replaces) the mark for an instrument.
+    pub fn set_quote(&self, quote: &Quote) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO quotes (instrument, price, fetched_at, source)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                quote.instrument,
+                quote.price.to_string(),
+                quote.fetched_at,
+                quote.source
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_quote(&self, instrument: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM quotes WHERE instrument = ?1", [instrument])?;
+        Ok(())
+    }
+
+    pub fn get_quotes(&self) -> Result<HashMap<String, Quote>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT instrument, price, fetched_at, source FROM quotes")?;
+        let quotes = stmt.query_map([], |row| {
+            Ok(Quote {
+                instrument: row.get(0)?,
+                price: decimal_from_row(row, 1)?,
+                fetched_at: row.get(2)?,
+                source: row.get(3)?,
+            })
+        })?;
+        quotes
+            .map(|quote| quote.map(|q| (q.instrument.clone(), q)))
+            .collect()
+    }
+
+    /// Copies the whole database to a new file at `path` (see
+    /// [`crate::snapshot`]).
+    pub fn write_snapshot(&self, path: &Path) -> Result<()> {
+        write_snapshot(&self.conn, path, self.passphrase.as_deref())
+    }
+
+    /// Replaces the whole database with the snapshot at `path`, upgrading it
+    /// if it is older. The file is checked first, so a damaged or foreign one
+    /// leaves the database as it was. Reads go back to every account.
+    pub fn restore_snapshot(&mut self, path: &Path) -> Result<()> {
+        let passphrase = self.passphrase.clone();
+        check_snapshot(path, passphrase.as_deref())?;
+        match (&passphrase, self.conn.path().map(str::to_string)) {
+            // SQLite's backup API can't write encrypted pages, so the file is
+            // swapped while the database is closed.
+            (Some(passphrase), Some(db_path)) => {
+                let io_error = |e: std::io::Error| {
+                    failure(rusqlite::ffi::SQLITE_IOERR, format!("{}: {}", db_path, e))
+                };
+                let copy = format!("{}.restore.tmp", db_path);
+                std::fs::copy(path, &copy).map_err(io_error)?;
+                let conn = std::mem::replace(&mut self.conn, Connection::open_in_memory()?);
+                conn.close().map_err(|(_, e)| e)?;
+                let renamed = std::fs::rename(&copy, &db_path);
+                self.conn = Connection::open(&db_path)?;
+                unlock(&self.conn, passphrase)?;
+                renamed.map_err(io_error)?;
+            }
+            _ => self
+                .conn
+                .restore(DatabaseName::Main, path, None::<fn(Progress)>)?,
+        }
+        self.account = None;
+        self.init_schema()
+    }
+
+    /// Everything stored, for a JSON backup.
+    pub fn get_backup(&self) -> Result<Backup> {
+        let mut trades = self.trades_in_account(None)?;
+        trades.extend(self.trashed_trades()?);
+        trades.sort_by_key(|t| t.id);
+        let mut stmt = self.conn.prepare(
+            "SELECT id, trade_id, changed_at, change, before, after FROM trade_history ORDER BY id",
+        )?;
+        let history = stmt
+            .query_map([], Self::row_to_history)?
+            .collect::<Result<_>>()?;
+        let mut quotes: Vec<Quote> = self.get_quotes()?.into_values().collect();
+        quotes.sort_by(|a, b| a.instrument.cmp(&b.instrument));
+        let mut stmt = self
+            .conn
+            .prepare("SELECT call_id, stock_id FROM covered_calls ORDER BY call_id")?;
+        let covered_calls = stmt
+            .query_map([], |row| {
+                Ok(CoveredCall {
+                    call_id: row.get(0)?,
+                    stock_id: row.get(1)?,
+                })
+            })?
+            .collect::<Result<_>>()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT symbol, date, open, high, low, close, volume
+             FROM prices ORDER BY symbol, date",
+        )?;
+        let prices = stmt
+            .query_map([], |row| {
+                Ok(PriceBar {
+                    symbol: row.get(0)?,
+                    date: row.get(1)?,
+                    open: decimal_from_row(row, 2)?,
+                    high: decimal_from_row(row, 3)?,
+                    low: decimal_from_row(row, 4)?,
+                    close: decimal_from_row(row, 5)?,
+                    volume: row.get(6)?,
+                })
+            })?
+            .collect::<Result<_>>()?;
+        let mut stmt = self.conn.prepare(
+            "SELECT id, trade_id, name, path, data, added_at FROM attachments ORDER BY id",
+        )?;
+        let attachments = stmt
+            .query_map([], Self::row_to_attachment)?
+            .collect::<Result<_>>()?;
+        let mut corporate_actions = self.get_corporate_actions()?;
+        corporate_actions.sort_by_key(|a| a.id);
+        let mut dividends = self.dividends_in_account(None, &DateRange::default())?;
+        dividends.sort_by_key(|d| d.id);
+        let mut cash_transactions =
+            self.cash_transactions_in_account(None, &DateRange::default())?;
+        cash_transactions.sort_by_key(|t| t.id);
+        Ok(Backup {
+            version: BACKUP_VERSION,
+            accounts: self.get_accounts()?,
+            trades,
+            strategies: self.get_strategies()?,
+            covered_calls,
+            lot_matches: self.get_lot_overrides()?,
+            corporate_actions,
+            dividends,
+            alerts: self.get_alerts()?,
+            saved_reports: self.get_saved_reports()?,
+            quotes,
+            prices,
+            valuation_snapshots: self.get_snapshots()?,
+            history,
+            attachments,
+            cash_transactions,
+            fx_rates: self.get_fx_rates()?,
+            archived_trades: self.archived_trades(None)?,
+            archive_summaries: self.archive_summaries_in_account(None)?,
+        })
+    }
+
+    /// Replaces everything stored with `backup`, keeping its ids so links
+    /// between rows still hold. Either all of it is restored or nothing
+    /// changes.
+    pub fn restore_backup(&self, backup: &Backup) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for table in [
+            "covered_calls",
+            "lot_matches",
+            "attachments",
+            "trade_tags",
+            "tags",
+            "trades",
+            "accounts",
+            "strategies",
+            "corporate_actions",
+            "dividends",
+            "cash_transactions",
+            "alerts",
+            "saved_reports",
+            "quotes",
+            "prices",
+            "valuation_snapshots",
+            "fx_rates",
+            "archived_trades",
+            "archive_summaries",
+        ] {
+            tx.execute(&format!("DELETE FROM {}", table), [])?;
+        }
+        for strategy in &backup.strategies {
+            tx.execute(
+                "INSERT INTO strategies (id, name, kind) VALUES (?1, ?2, ?3)",
+                params![strategy.id, strategy.name, strategy.kind],
+            )?;
+        }
+        for account in &backup.accounts {
+            tx.execute(
+                "INSERT INTO accounts (id, name, currency) VALUES (?1, ?2, ?3)",
+                params![account.id, account.name, account.currency],
+            )?;
+        }
+        for trade in &backup.trades {
+            self.insert_trade(trade.id, trade)?;
+        }
+        for link in &backup.covered_calls {
+            tx.execute(
+                "INSERT INTO covered_calls (call_id, stock_id) VALUES (?1, ?2)",
+                params![link.call_id, link.stock_id],
+            )?;
+        }
+        for lot in &backup.lot_matches {
+            tx.execute(
+                "INSERT INTO lot_matches (close_id, open_id, quantity) VALUES (?1, ?2, ?3)",
+                params![lot.close_id, lot.open_id, lot.quantity.to_string()],
+            )?;
+        }
+        for action in &backup.corporate_actions {
+            tx.execute(
+                "INSERT INTO corporate_actions (id, symbol, kind, date, ratio, new_symbol)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    action.id,
+                    action.symbol,
+                    action.kind,
+                    action.date,
+                    action.ratio.map(|d| d.to_string()),
+                    action.new_symbol,
+                ],
+            )?;
+        }
+        for dividend in &backup.dividends {
+            tx.execute(
+                "INSERT INTO dividends (id, symbol, date, amount, qualified, account_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    dividend.id,
+                    dividend.symbol,
+                    dividend.date,
+                    dividend.amount.to_string(),
+                    dividend.qualified,
+                    dividend.account_id,
+                ],
+            )?;
+        }
+        for transaction in &backup.cash_transactions {
+            tx.execute(
+                "INSERT INTO cash_transactions (id, date, kind, amount, comment, account_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    transaction.id,
+                    transaction.date,
+                    transaction.kind,
+                    transaction.amount.to_string(),
+                    transaction.comment,
+                    transaction.account_id,
+                ],
+            )?;
+        }
+        for alert in &backup.alerts {
+            tx.execute(
+                "INSERT INTO alerts (id, instrument, kind, threshold, triggered_at, triggered_value)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    alert.id,
+                    alert.instrument,
+                    alert.kind,
+                    alert.threshold.to_string(),
+                    alert.triggered_at,
+                    alert.triggered_value.map(|d| d.to_string()),
+                ],
+            )?;
+        }
+        for report in &backup.saved_reports {
+            tx.execute(
+                "INSERT INTO saved_reports (id, name, filter, group_by) VALUES (?1, ?2, ?3, ?4)",
+                params![report.id, report.name, report.filter, report.group_by],
+            )?;
+        }
+        for quote in &backup.quotes {
+            self.set_quote(quote)?;
+        }
+        for bar in &backup.prices {
+            tx.execute(
+                "INSERT INTO prices (symbol, date, open, high, low, close, volume)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    bar.symbol,
+                    bar.date,
+                    bar.open.to_string(),
+                    bar.high.to_string(),
+                    bar.low.to_string(),
+                    bar.close.to_string(),
+                    bar.volume,
+                ],
+            )?;
+        }
+        for snapshot in &backup.valuation_snapshots {
+            self.save_snapshot(snapshot)?;
+        }
+        for rate in &backup.fx_rates {
+            self.set_fx_rate(rate)?;
+        }
+        for archived in &backup.archived_trades {
+            self.add_archived_trade(archived)?;
+        }
+        for summary in &backup.archive_summaries {
+            self.add_archive_summary(summary)?;
+        }
+        for attachment in &backup.attachments {
+            tx.execute(
+                "INSERT INTO attachments (id, trade_id, name, path, data, added_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    attachment.id,
+                    attachment.trade_id,
+                    attachment.name,
+                    attachment.path,
+                    attachment.data,
+                    attachment.added_at,
+                ],
+            )?;
+        }
+        // The inserts above were logged as new; the backup's own history
+        // replaces that.
+        tx.execute("DELETE FROM trade_history", [])?;
+        for entry in &backup.history {
+            tx.execute(
+                "INSERT INTO trade_history (id, trade_id, changed_at, change, before, after)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    entry.id,
+                    entry.trade_id,
+                    entry.changed_at,
+                    entry.change,
+                    entry.before,
+                    entry.after,
+                ],
+            )?;
+        }
+        default_history_currency(&tx)?;
+        tx.commit()
+    }
+
+    /// Net signed share position for a symbol (long > 0, short < 0), summed over
+    /// stock trades (including assignment-generated rows).
+    pub fn net_shares(&self, symbol: &str) -> Result<Decimal> {
+        Ok(self
+            .get_trades_by_symbol(symbol)?
+            .iter()
+            .map(Trade::signed_shares)
+            .sum())
+    }
+
+    /// Break-even price for a symbol's current net share position, derived from
+    /// the full ledger: `-(sum of all cash flows) / net_shares`. This folds in
+    /// collected option premium and all fees, so it works for both long and
+    /// short positions. Returns `None` when the net position is flat.
+    pub fn get_break_even(&self, symbol: &str) -> Result<Option<Decimal>> {
+        self.get_break_even_excluding(symbol, None)
+    }
+
+    /// Like [`get_break_even`], but ignores the trade whose id equals
+    /// `exclude_id` (if any). Used by the covered-call warning when *editing* an
+    /// option: the pre-edit version of the option being saved is still in the
+    /// ledger, and since option premium folds into break-even it would otherwise
+    /// skew the warning threshold. Pass `None` to include every trade.
+    pub fn get_break_even_excluding(
+        &self,
+        symbol: &str,
+        exclude_id: Option<i64>,
+    ) -> Result<Option<Decimal>> {
+        let trades = self.get_trades_by_symbol(symbol)?;
+        Ok(break_even(
+            trades
+                .iter()
+                .filter(|t| exclude_id.is_none() || t.id != exclude_id),
+        ))
+    }
+
+    /// Loss sales washed by a repurchase, with lots matched under `method`
+    /// (including manual lot matches).
+    pub fn get_wash_sales(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<Vec<WashSale>> {
+        let trades = self.get_all_trades()?;
+        let matches = match_lots(&trades, method, &self.get_lot_overrides()?);
+        let mut sales = wash_sales(&trades, &matches);
+        sales.retain(|sale| range.contains(&sale.close_date));
+        Ok(sales)
+    }
+
+    /// Realized P/L, fees, and trade counts per `period`, with lots matched
+    /// under `method` (including manual lot matches).
+    pub fn get_period_report(
+        &self,
+        method: CostBasisMethod,
+        period: Period,
+        range: &DateRange,
+    ) -> Result<Vec<PeriodReport>> {
+        let (trades, matches) = self.trades_and_matches_in(method, range)?;
+        Ok(period_report(&trades, &matches, period))
+    }
+
+    /// Cumulative realized P/L per close date of lots matched under `method`
+    /// (including manual lot matches), oldest first. Only lots closed in
+    /// `range` count, so the curve starts from zero at its start.
+    pub fn get_equity_curve(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<Vec<(String, Decimal)>> {
+        let (_, matches) = self.trades_and_matches_in(method, range)?;
+        Ok(equity_curve(&matches))
+    }
+
+    /// How the options sold to open ended — expired, assigned, or bought
+    /// back — for lots matched under `method` (including manual lot matches)
+    /// and closed in `range`.
+    pub fn get_option_outcomes(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<OutcomeReport> {
+        let trades = self.get_all_trades()?;
+        let mut matches = match_lots(&trades, method, &self.get_lot_overrides()?);
+        matches.retain(|m| range.contains(&m.close_date));
+        Ok(option_outcomes(&trades, &matches))
+    }
+
+    /// Realized P/L by close date of lots matched under `method` (including
+    /// manual lot matches) and closed in `range`.
+    pub fn get_daily_pl(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<BTreeMap<String, Decimal>> {
+        let (_, matches) = self.trades_and_matches_in(method, range)?;
+        Ok(daily_pl(&matches))
+    }
+
+    /// Fees paid by month and by symbol next to the realized P/L of lots
+    /// matched under `method` (including manual lot matches).
+    pub fn get_fee_report(&self, method: CostBasisMethod, range: &DateRange) -> Result<FeeReport> {
+        let (trades, matches) = self.trades_and_matches_in(method, range)?;
+        Ok(fee_report(&trades, &matches))
+    }
+
+    /// Realized and unrealized P/L, win rate, and holding period per strategy
+    /// type, with lots matched under `method` (including manual lot matches).
+    /// Only plays opened in `range` count, each with its whole outcome.
+    pub fn get_strategy_report(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<Vec<StrategyTypeReport>> {
+        let trades = self.get_all_trades()?;
+        let overrides = self.get_lot_overrides()?;
+        let covered_calls: HashSet<i64> = self
+            .conn
+            .prepare("SELECT call_id FROM covered_calls")?
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_>>()?;
+        let opened: Vec<Trade> = trades
+            .iter()
+            .filter(|t| range.contains(&t.date))
+            .cloned()
+            .collect();
+        Ok(strategy_report(
+            &opened,
+            &self.get_strategies()?,
+            &covered_calls,
+            &match_lots(&trades, method, &overrides),
+            &open_lots(&trades, method, &overrides),
+            &self.get_quotes()?,
+        ))
+    }
+
+    /// Opening trades whose lots have all been closed, with lots matched under
+    /// `method` (including manual lot matches). Only positions whose last close
+    /// falls in `range` count.
+    pub fn get_closed_positions(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<Vec<ClosedPosition>> {
+        let trades = self.get_all_trades()?;
+        let overrides = self.get_lot_overrides()?;
+        let mut closed = closed_positions(
+            &trades,
+            &match_lots(&trades, method, &overrides),
+            &open_lots(&trades, method, &overrides),
+        );
+        closed.retain(|position| range.contains(&position.closed));
+        Ok(closed)
+    }
+
+    /// Wheel cycles that started in `range`, each with its whole history.
+    pub fn get_wheel_cycles(&self, range: &DateRange) -> Result<Vec<WheelCycle>> {
+        let mut cycles = wheel_cycles(&self.get_all_trades()?);
+        cycles.retain(|cycle| range.contains(&cycle.start_date));
+        Ok(cycles)
+    }
+
+    // Trades made in `range`, and the lots closed in it. Lots are matched over
+    // every trade first, so a close in range still finds a lot opened before.
+    fn trades_and_matches_in(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<(Vec<Trade>, Vec<LotMatch>)> {
+        let mut trades = self.get_all_trades()?;
+        let mut matches = match_lots(&trades, method, &self.get_lot_overrides()?);
+        trades.retain(|t| range.contains(&t.date));
+        matches.retain(|m| range.contains(&m.close_date));
+        Ok((trades, matches))
+    }
+
+    /// Lots closed in `range`, matched under `method` (including manual lot
+    /// matches).
+    pub fn get_realized_lots(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<Vec<LotMatch>> {
+        Ok(self.trades_and_matches_in(method, range)?.1)
+    }
+
+    /// Realized P/L and trade counts from `range`, next to each symbol's
+    /// current open lots, net position, and break-even.
+    pub fn get_report_by_symbol(
+        &self,
+        method: CostBasisMethod,
+        range: &DateRange,
+    ) -> Result<Vec<SymbolReport>> {
+        // Every figure comes from one read of the ledger, grouped by symbol
+        // once, so the report costs the same however many symbols there are.
+        let all = self.get_all_trades()?;
+        let overrides = self.get_lot_overrides()?;
+        let open = open_lots(&all, method, &overrides);
+        let mut matches = match_lots(&all, method, &overrides);
+        matches.retain(|m| range.contains(&m.close_date));
+        let quotes = self.get_quotes()?;
+
+        let mut ledgers: HashMap<&str, Vec<&Trade>> = HashMap::new();
+        for trade in &all {
+            ledgers.entry(&trade.symbol).or_default().push(trade);
+        }
+        let mut reports: BTreeMap<&str, SymbolReport> = BTreeMap::new();
+        let in_range = all.iter().filter(|t| range.contains(&t.date));
+        let symbols = in_range
+            .clone()
+            .map(|t| t.symbol.as_str())
+            .chain(matches.iter().map(|m| m.key.symbol.as_str()));
+        for symbol in symbols {
+            reports.entry(symbol).or_insert_with(|| {
+                let ledger = ledgers.get(symbol).map(Vec::as_slice).unwrap_or_default();
+                SymbolReport {
+                    symbol: symbol.to_string(),
+                    profit_loss: Decimal::ZERO,
+                    short_term: Decimal::ZERO,
+                    long_term: Decimal::ZERO,
+                    unrealized_pl: Some(Decimal::ZERO),
+                    trade_count: 0,
+                    net_shares: ledger.iter().map(|t| t.signed_shares()).sum(),
+                    break_even: break_even(ledger.iter().copied()),
+                }
+            });
+        }
+        for trade in in_range {
+            if let Some(report) = reports.get_mut(trade.symbol.as_str()) {
+                report.trade_count += 1;
+            }
+        }
+        for m in &matches {
+            if let Some(report) = reports.get_mut(m.key.symbol.as_str()) {
+                match m.holding_period() {
+                    HoldingPeriod::ShortTerm => report.short_term += m.realized,
+                    HoldingPeriod::LongTerm => report.long_term += m.realized,
+                }
+                report.profit_loss += m.realized;
+            }
+        }
+        for lot in &open {
+            if let Some(report) = reports.get_mut(lot.key.symbol.as_str()) {
+                let mark = quotes.get(&lot.key.instrument());
+                report.unrealized_pl = report
+                    .unrealized_pl
+                    .zip(mark)
+                    .map(|(sum, quote)| sum + lot.unrealized(quote.price));
+            }
+        }
+        Ok(reports.into_values().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_test_db() -> Database {
+        Database::new(":memory:").expect("failed to create in-memory database")
+    }
+
+    fn stock(
+        symbol: &str,
+        action: Action,
+        price: Decimal,
+        quantity: Decimal,
+        fees: Decimal,
+    ) -> Trade {
+        Trade {
+            symbol: symbol.to_string(),
+            trade_type: TradeType::Stock,
+            action,
+            price,
+            quantity,
+            date: "2024-01-15".to_string(),
+            fees,
+            ..Default::default()
+        }
+    }
+
+    fn option(
+        symbol: &str,
         action: Action,
         option_type: OptionType,
         price: Decimal,
@@ -548,86 +3595,996 @@ mod tests {
             expiration: Some(expiration.to_string()),
             status: Some(OptionStatus::Open),
             ..Default::default()
-        }
+        }
+    }
+
+    #[test]
+    fn enum_as_str_and_parse() {
+        assert_eq!(TradeType::Option.as_str(), "option");
+        assert_eq!(Action::BuyToOpen.as_str(), "buy_to_open");
+        assert_eq!(Action::SellToClose.as_str(), "sell_to_close");
+        assert_eq!(OptionType::Call.as_str(), "call");
+        assert_eq!(OptionStatus::Assigned.as_str(), "assigned");
+
+        assert!(matches!("buy_to_open".parse(), Ok(Action::BuyToOpen)));
+        assert!(matches!("SELL_TO_OPEN".parse(), Ok(Action::SellToOpen)));
+        assert!(matches!("Put".parse(), Ok(OptionType::Put)));
+        assert!(matches!("EXPIRED".parse(), Ok(OptionStatus::Expired)));
+
+        assert!("buy".parse::<Action>().is_err());
+        assert!("straddle".parse::<OptionType>().is_err());
+        assert!("pending".parse::<OptionStatus>().is_err());
+    }
+
+    #[test]
+    fn action_is_buy() {
+        assert!(Action::BuyToOpen.is_buy());
+        assert!(Action::BuyToClose.is_buy());
+        assert!(!Action::SellToOpen.is_buy());
+        assert!(!Action::SellToClose.is_buy());
+    }
+
+    #[test]
+    fn option_cash_flow_uses_100x_multiplier() {
+        // Sell-to-open a put for $2.00, 1 contract, no fees → +$200 collected.
+        let sold_put = option(
+            "AAPL",
+            Action::SellToOpen,
+            OptionType::Put,
+            dec!(2.0),
+            dec!(1.0),
+            dec!(100.0),
+            "2024-06-21",
+        );
+        assert_eq!(sold_put.cash_flow(), dec!(200));
+        // Stock keeps a 1x multiplier.
+        let bought = stock(
+            "AAPL",
+            Action::BuyToOpen,
+            dec!(100.0),
+            dec!(10.0),
+            dec!(0.0),
+        );
+        assert_eq!(bought.cash_flow(), dec!(-1000));
+    }
+
+    #[test]
+    fn custom_contract_multiplier_scales_cash_flow_and_assignment() {
+        let db = new_test_db();
+        // A mini contract (10 shares) sold for $2.00 → +$20 collected.
+        let mut mini_put = option(
+            "AAPL",
+            Action::SellToOpen,
+            OptionType::Put,
+            dec!(2.0),
+            dec!(1.0),
+            dec!(100.0),
+            "2024-06-21",
+        );
+        mini_put.contract_multiplier = Some(dec!(10));
+        assert_eq!(mini_put.cash_flow(), dec!(20));
+
+        let put_id = db.add_trade(&mini_put).unwrap();
+        assert_eq!(
+            db.get_trade(put_id).unwrap().unwrap().contract_multiplier,
+            Some(dec!(10))
+        );
+        db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(10));
+    }
+
+    #[test]
+    fn greeks_round_trip_and_update() {
+        let db = new_test_db();
+        let mut call = option(
+            "AAPL",
+            Action::BuyToOpen,
+            OptionType::Call,
+            dec!(3.0),
+            dec!(1.0),
+            dec!(190.0),
+            "2024-06-21",
+        );
+        call.greeks = Greeks {
+            delta: Some(dec!(0.45)),
+            theta: Some(dec!(-0.05)),
+            iv: Some(dec!(28.5)),
+            ..Default::default()
+        };
+        let id = db.add_trade(&call).unwrap();
+        let mut stored = db.get_trade(id).unwrap().unwrap();
+        assert_eq!(stored.greeks, call.greeks);
+
+        stored.greeks.delta = Some(dec!(0.6));
+        stored.greeks.theta = None;
+        db.update_trade(&stored).unwrap();
+        let updated = db.get_trade(id).unwrap().unwrap().greeks;
+        assert_eq!(updated.delta, Some(dec!(0.6)));
+        assert_eq!(updated.theta, None);
+        assert_eq!(updated.gamma, None);
+    }
+
+    #[test]
+    fn occ_symbol_only_for_complete_options() {
+        let call = option(
+            "AAPL",
+            Action::BuyToOpen,
+            OptionType::Call,
+            dec!(1.0),
+            dec!(1.0),
+            dec!(190.0),
+            "2024-01-19",
+        );
+        assert_eq!(
+            call.occ_symbol().map(|o| o.to_string()),
+            Some("AAPL240119C00190000".to_string())
+        );
+        let stock_row = stock("AAPL", Action::BuyToOpen, dec!(1.0), dec!(1.0), dec!(0.0));
+        assert_eq!(stock_row.occ_symbol(), None);
+    }
+
+    #[test]
+    fn schema_roundtrips_all_option_fields() {
+        let db = new_test_db();
+        let opt = option(
+            "AAPL",
+            Action::SellToOpen,
+            OptionType::Put,
+            dec!(2.0),
+            dec!(1.0),
+            dec!(100.0),
+            "2024-06-21",
+        );
+        let id = db.add_trade(&opt).unwrap();
+        let stored = db.get_trade(id).unwrap().unwrap();
+        assert_eq!(stored.symbol, "AAPL");
+        assert!(matches!(stored.action, Action::SellToOpen));
+        assert_eq!(stored.option_type, Some(OptionType::Put));
+        assert_eq!(stored.strike, Some(dec!(100.0)));
+        assert_eq!(stored.expiration, Some("2024-06-21".to_string()));
+        assert_eq!(stored.status, Some(OptionStatus::Open));
+        assert_eq!(stored.contract_multiplier, None);
+        assert_eq!(stored.multiplier(), OPTION_MULTIPLIER);
+        assert_eq!(stored.assigned_from, None);
+    }
+
+    #[test]
+    fn break_even_long_after_put_assignment() {
+        let db = new_test_db();
+        // Sell a put for $2 premium, then it gets assigned → buy 100 @ 100.
+        let put_id = db
+            .add_trade(&option(
+                "AAPL",
+                Action::SellToOpen,
+                OptionType::Put,
+                dec!(2.0),
+                dec!(1.0),
+                dec!(100.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+
+        // Long 100 shares, break-even = 100 - 2 = 98.
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(100));
+        let be = db.get_break_even("AAPL").unwrap().unwrap();
+        assert_eq!(be, dec!(98));
+    }
+
+    #[test]
+    fn put_assignment_creates_long_linked_stock_row() {
+        let db = new_test_db();
+        let put_id = db
+            .add_trade(&option(
+                "AAPL",
+                Action::SellToOpen,
+                OptionType::Put,
+                dec!(2.0),
+                dec!(2.0),
+                dec!(100.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+
+        let trades = db.get_all_trades().unwrap();
+        let option_row = trades.iter().find(|t| t.id == Some(put_id)).unwrap();
+        assert_eq!(option_row.status, Some(OptionStatus::Assigned));
+
+        let linked: Vec<&Trade> = trades
+            .iter()
+            .filter(|t| t.assigned_from == Some(put_id))
+            .collect();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].trade_type, TradeType::Stock);
+        assert!(linked[0].action.is_buy());
+        assert_eq!(linked[0].quantity, dec!(200)); // 2 contracts * 100
+        assert_eq!(linked[0].price, dec!(100));
+    }
+
+    #[test]
+    fn call_assignment_creates_short_linked_stock_row() {
+        let db = new_test_db();
+        let call_id = db
+            .add_trade(&option(
+                "AAPL",
+                Action::SellToOpen,
+                OptionType::Call,
+                dec!(1.0),
+                dec!(1.0),
+                dec!(110.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.assign_option(call_id, OptionStatus::Assigned).unwrap();
+
+        // From flat, an assigned call yields a short position.
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(-100));
+        let linked = db
+            .get_all_trades()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.assigned_from == Some(call_id))
+            .unwrap();
+        assert!(!linked.action.is_buy());
+    }
+
+    #[test]
+    fn long_call_exercise_creates_long_linked_stock_row() {
+        let db = new_test_db();
+        // Buy a call (long), then exercise it → buy shares at strike.
+        let call_id = db
+            .add_trade(&option(
+                "AAPL",
+                Action::BuyToOpen,
+                OptionType::Call,
+                dec!(1.0),
+                dec!(1.0),
+                dec!(110.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.assign_option(call_id, OptionStatus::Exercised).unwrap();
+
+        // Exercising a long call buys shares (from flat: long).
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(100));
+        let linked = db
+            .get_all_trades()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.assigned_from == Some(call_id))
+            .unwrap();
+        assert!(linked.action.is_buy());
+        assert_eq!(linked.price, dec!(110));
+    }
+
+    #[test]
+    fn long_put_exercise_creates_short_linked_stock_row() {
+        let db = new_test_db();
+        // Buy a put (long), then exercise it → sell shares at strike.
+        let put_id = db
+            .add_trade(&option(
+                "AAPL",
+                Action::BuyToOpen,
+                OptionType::Put,
+                dec!(1.0),
+                dec!(1.0),
+                dec!(90.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.assign_option(put_id, OptionStatus::Exercised).unwrap();
+
+        // Exercising a long put sells shares (from flat: short).
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(-100));
+        let linked = db
+            .get_all_trades()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.assigned_from == Some(put_id))
+            .unwrap();
+        assert!(!linked.action.is_buy());
+        assert_eq!(linked.price, dec!(90));
+    }
+
+    #[test]
+    fn reads_one_account_or_all_of_them() {
+        let mut db = new_test_db();
+        let ira = db
+            .add_account(&Account {
+                id: None,
+                name: "IRA".to_string(),
+                currency: DEFAULT_CURRENCY.to_string(),
+            })
+            .unwrap();
+        let taxable = db
+            .add_account(&Account {
+                id: None,
+                name: "Taxable".to_string(),
+                currency: DEFAULT_CURRENCY.to_string(),
+            })
+            .unwrap();
+        let buy = stock("AAPL", Action::BuyToOpen, dec!(90), dec!(10), dec!(0));
+        db.add_trade(&Trade {
+            account_id: Some(ira),
+            ..buy.clone()
+        })
+        .unwrap();
+        db.add_trade(&Trade {
+            account_id: Some(taxable),
+            ..buy
+        })
+        .unwrap();
+        // Selling in one account leaves the other account's lot open.
+        db.add_trade(&Trade {
+            account_id: Some(taxable),
+            ..stock("AAPL", Action::SellToClose, dec!(95), dec!(10), dec!(0))
+        })
+        .unwrap();
+
+        assert_eq!(db.get_all_trades().unwrap().len(), 3);
+        let positions =
+            crate::positions::open_positions(&db.get_all_trades().unwrap(), "2024-06-01");
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].key.account_id, Some(ira));
+
+        db.set_account(Some(taxable));
+        assert_eq!(db.get_all_trades().unwrap().len(), 2);
+        assert_eq!(db.get_trades_by_symbol("AAPL").unwrap().len(), 2);
+
+        db.delete_account(taxable).unwrap();
+        assert_eq!(db.account(), None);
+        assert_eq!(db.get_accounts().unwrap().len(), 1);
+        assert_eq!(db.get_backup().unwrap().trades.len(), 3);
+    }
+
+    #[test]
+    fn keeps_cash_transactions_per_account() {
+        let mut db = new_test_db();
+        let ira = db
+            .add_account(&Account {
+                id: None,
+                name: "IRA".to_string(),
+                currency: DEFAULT_CURRENCY.to_string(),
+            })
+            .unwrap();
+        let deposit = CashTransaction {
+            id: None,
+            date: "2024-01-02".to_string(),
+            kind: CashKind::Deposit,
+            amount: dec!(5000),
+            comment: "opening transfer".to_string(),
+            account_id: Some(ira),
+        };
+        let id = db.add_cash_transaction(&deposit).unwrap();
+        db.add_cash_transaction(&CashTransaction {
+            date: "2024-02-01".to_string(),
+            kind: CashKind::Fee,
+            amount: dec!(25),
+            comment: String::new(),
+            account_id: None,
+            ..deposit.clone()
+        })
+        .unwrap();
+
+        let all = db.get_cash_transactions(&DateRange::default()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(
+            all[1],
+            CashTransaction {
+                id: Some(id),
+                ..deposit
+            }
+        );
+        assert_eq!(all[0].signed_amount(), dec!(-25));
+
+        db.set_account(Some(ira));
+        assert_eq!(
+            db.get_cash_transactions(&DateRange::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        db.delete_account(ira).unwrap();
+        let all = db.get_cash_transactions(&DateRange::default()).unwrap();
+        assert!(all.iter().all(|t| t.account_id.is_none()));
+
+        db.delete_cash_transaction(id).unwrap();
+        assert_eq!(db.get_backup().unwrap().cash_transactions.len(), 1);
+    }
+
+    #[test]
+    fn saves_tags_and_drops_unused_ones() {
+        let db = new_test_db();
+        let buy = stock("AAPL", Action::BuyToOpen, dec!(90), dec!(10), dec!(0));
+        let id = db
+            .add_trade(&Trade {
+                tags: vec!["hedge".to_string(), "Earnings Play".to_string()],
+                ..buy.clone()
+            })
+            .unwrap();
+        db.add_trade(&Trade {
+            tags: vec!["Hedge".to_string()],
+            ..buy
+        })
+        .unwrap();
+
+        let mut trade = db.get_trade(id).unwrap().unwrap();
+        assert_eq!(trade.tags, vec!["Earnings Play", "hedge"]);
+        assert_eq!(db.get_tags().unwrap(), vec!["Earnings Play", "hedge"]);
+
+        trade.tags = vec!["mistake".to_string()];
+        db.update_trade(&trade).unwrap();
+        assert_eq!(db.get_tags().unwrap(), vec!["hedge", "mistake"]);
+        db.delete_trade(id).unwrap();
+        db.purge_trash().unwrap();
+        assert_eq!(db.get_tags().unwrap(), vec!["hedge"]);
+    }
+
+    #[test]
+    fn deletes_and_tags_trades_in_bulk() {
+        let db = new_test_db();
+        let buy = stock("AAPL", Action::BuyToOpen, dec!(90), dec!(10), dec!(0));
+        let ids: Vec<i64> = (0..3)
+            .map(|_| {
+                db.add_trade(&Trade {
+                    tags: vec!["Hedge".to_string()],
+                    ..buy.clone()
+                })
+                .unwrap()
+            })
+            .collect();
+
+        db.tag_trades(&ids[..2], &["hedge".to_string(), "q1".to_string()])
+            .unwrap();
+        assert_eq!(db.get_trade(ids[0]).unwrap().unwrap().tags, ["Hedge", "q1"]);
+        assert_eq!(db.get_trade(ids[2]).unwrap().unwrap().tags, ["Hedge"]);
+
+        db.delete_trades(&ids[1..]).unwrap();
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
+        assert_eq!(db.get_deleted_trades().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn bulk_edit_renames_options_and_rebuilds_their_stock() {
+        let db = new_test_db();
+        let put_id = db
+            .add_trade(&option(
+                "APPL",
+                Action::SellToOpen,
+                OptionType::Put,
+                dec!(2.0),
+                dec!(1.0),
+                dec!(100.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        let stock_id = db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+        let edit = BulkEdit {
+            symbol: Some("AAPL".to_string()),
+            fees: Some(dec!(0.65)),
+            ..Default::default()
+        };
+
+        // The generated row is skipped, then rebuilt under the new symbol.
+        assert_eq!(db.bulk_edit(&[put_id, stock_id], &edit).unwrap(), 1);
+        let trades = db.get_all_trades().unwrap();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|t| t.symbol == "AAPL"));
+        let stock = trades.iter().find(|t| t.assigned_from.is_some()).unwrap();
+        assert_eq!(stock.fees, dec!(0));
+        assert_eq!(db.bulk_edit(&[put_id], &edit).unwrap(), 0);
     }
 
     #[test]
-    fn enum_as_str_and_parse() {
-        assert_eq!(TradeType::Option.as_str(), "option");
-        assert_eq!(Action::BuyToOpen.as_str(), "buy_to_open");
-        assert_eq!(Action::SellToClose.as_str(), "sell_to_close");
-        assert_eq!(OptionType::Call.as_str(), "call");
-        assert_eq!(OptionStatus::Assigned.as_str(), "assigned");
+    fn searches_symbols_and_comments_by_word_prefix() {
+        let db = new_test_db();
+        let buy = stock("AAPL", Action::BuyToOpen, dec!(90), dec!(10), dec!(0));
+        let gap = db
+            .add_trade(&Trade {
+                comment: "Bought the earnings gap down".to_string(),
+                ..buy.clone()
+            })
+            .unwrap();
+        let msft = db
+            .add_trade(&Trade {
+                symbol: "MSFT".to_string(),
+                ..buy
+            })
+            .unwrap();
+        let ids = |text: &str| {
+            let mut ids: Vec<i64> = db.search_trades(text).unwrap().into_iter().collect();
+            ids.sort();
+            ids
+        };
 
-        assert!(matches!("buy_to_open".parse(), Ok(Action::BuyToOpen)));
-        assert!(matches!("SELL_TO_OPEN".parse(), Ok(Action::SellToOpen)));
-        assert!(matches!("Put".parse(), Ok(OptionType::Put)));
-        assert!(matches!("EXPIRED".parse(), Ok(OptionStatus::Expired)));
+        assert_eq!(ids("EARN gap"), vec![gap]);
+        assert_eq!(ids("gap earnings \"quoted"), Vec::<i64>::new());
+        assert_eq!(ids("msf"), vec![msft]);
+        assert!(ids("  ").is_empty());
 
-        assert!("buy".parse::<Action>().is_err());
-        assert!("straddle".parse::<OptionType>().is_err());
-        assert!("pending".parse::<OptionStatus>().is_err());
+        let mut trade = db.get_trade(msft).unwrap().unwrap();
+        trade.comment = "rolled before earnings".to_string();
+        db.update_trade(&trade).unwrap();
+        assert_eq!(ids("earnings"), vec![gap, msft]);
+        db.delete_trade(gap).unwrap();
+        db.purge_trash().unwrap();
+        assert_eq!(ids("earnings"), vec![msft]);
     }
 
     #[test]
-    fn action_is_buy() {
-        assert!(Action::BuyToOpen.is_buy());
-        assert!(Action::BuyToClose.is_buy());
-        assert!(!Action::SellToOpen.is_buy());
-        assert!(!Action::SellToClose.is_buy());
+    fn queries_filtered_pages_of_trades() {
+        let mut db = new_test_db();
+        let account = db
+            .add_account(&Account {
+                id: None,
+                name: "IRA".to_string(),
+                currency: DEFAULT_CURRENCY.to_string(),
+            })
+            .unwrap();
+        let buy = stock("AAPL", Action::BuyToOpen, dec!(90), dec!(10), dec!(0));
+        let mut ids = Vec::new();
+        for day in 1..=5 {
+            ids.push(
+                db.add_trade(&Trade {
+                    date: format!("2024-03-0{}", day),
+                    account_id: (day > 3).then_some(account),
+                    tags: if day == 2 {
+                        vec!["Hedge".to_string()]
+                    } else {
+                        Vec::new()
+                    },
+                    comment: if day == 5 {
+                        "earnings".to_string()
+                    } else {
+                        String::new()
+                    },
+                    ..buy.clone()
+                })
+                .unwrap(),
+            );
+        }
+        ids.push(
+            db.add_trade(&stock(
+                "MSFT",
+                Action::SellToClose,
+                dec!(1),
+                dec!(1),
+                dec!(0),
+            ))
+            .unwrap(),
+        );
+        db.delete_trade(ids[0]).unwrap();
+        let found = |db: &Database, filter: &TradeFilter, limit, offset| -> Vec<i64> {
+            db.query_trades(filter, limit, offset)
+                .unwrap()
+                .iter()
+                .filter_map(|t| t.id)
+                .collect()
+        };
+
+        let all = TradeFilter::default();
+        assert_eq!(
+            found(&db, &all, None, 0),
+            vec![ids[4], ids[3], ids[2], ids[1], ids[5]]
+        );
+        assert_eq!(found(&db, &all, Some(2), 1), vec![ids[3], ids[2]]);
+        assert_eq!(found(&db, &all, Some(2), 4), vec![ids[5]]);
+        assert_eq!(db.count_trades(&all).unwrap(), 5);
+
+        let aapl_march = TradeFilter {
+            symbol: Some("AAPL".to_string()),
+            range: DateRange {
+                start: Some("2024-03-02".to_string()),
+                end: Some("2024-03-04".to_string()),
+            },
+            action: Some(Action::BuyToOpen),
+            trade_type: Some(TradeType::Stock),
+            ..Default::default()
+        };
+        assert_eq!(
+            found(&db, &aapl_march, None, 0),
+            vec![ids[3], ids[2], ids[1]]
+        );
+        let tagged = TradeFilter {
+            tag: Some("hedge".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(found(&db, &tagged, None, 0), vec![ids[1]]);
+        let searched = TradeFilter {
+            search: Some("earn".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(found(&db, &searched, None, 0), vec![ids[4]]);
+        let in_account = TradeFilter {
+            account: Some(account),
+            ..Default::default()
+        };
+        assert_eq!(found(&db, &in_account, None, 0), vec![ids[4], ids[3]]);
+        assert_eq!(db.count_trades(&in_account).unwrap(), 2);
+
+        // Within the account the database is limited to.
+        db.set_account(Some(account));
+        assert_eq!(found(&db, &all, Some(1), 0), vec![ids[4]]);
+        assert_eq!(db.count_trades(&aapl_march).unwrap(), 1);
+        let other = TradeFilter {
+            account: Some(account + 1),
+            ..Default::default()
+        };
+        assert_eq!(db.count_trades(&other).unwrap(), 0);
     }
 
     #[test]
-    fn option_cash_flow_uses_100x_multiplier() {
-        // Sell-to-open a put for $2.00, 1 contract, no fees → +$200 collected.
-        let sold_put = option(
+    fn orders_trades_by_a_column_either_way() {
+        let db = new_test_db();
+        let add = |symbol: &str, action: Action, price: Decimal, quantity: Decimal, date: &str| {
+            db.add_trade(&Trade {
+                date: date.to_string(),
+                ..stock(symbol, action, price, quantity, dec!(0))
+            })
+            .unwrap()
+        };
+        let buy = add("MSFT", Action::BuyToOpen, dec!(9), dec!(20), "2024-01-02");
+        let loss = add("MSFT", Action::SellToClose, dec!(8), dec!(10), "2024-01-03");
+        let gain = add(
             "AAPL",
-            Action::SellToOpen,
-            OptionType::Put,
-            dec!(2.0),
-            dec!(1.0),
-            dec!(100.0),
-            "2024-06-21",
+            Action::SellToClose,
+            dec!(12),
+            dec!(10),
+            "2024-01-04",
         );
-        assert_eq!(sold_put.cash_flow(), dec!(200));
-        // Stock keeps a 1x multiplier.
-        let bought = stock(
-            "AAPL",
+        let ids = |by: TradeSort, ascending: bool| -> Vec<i64> {
+            let filter = TradeFilter {
+                order: TradeOrder {
+                    by,
+                    ascending,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            db.query_trades(&filter, None, 0)
+                .unwrap()
+                .iter()
+                .filter_map(|t| t.id)
+                .collect()
+        };
+
+        assert_eq!(ids(TradeSort::Date, false), [gain, loss, buy]);
+        assert_eq!(ids(TradeSort::Date, true), [buy, loss, gain]);
+        assert_eq!(ids(TradeSort::Symbol, true), [gain, loss, buy]);
+        // Numerically, not as text: 9 is under 12.
+        assert_eq!(ids(TradeSort::Price, true), [loss, buy, gain]);
+        assert_eq!(ids(TradeSort::Quantity, false), [buy, gain, loss]);
+        // AAPL closes nothing, so realizes nothing; ties stay newest first.
+        assert_eq!(ids(TradeSort::Pl, false), [gain, buy, loss]);
+        let filter = TradeFilter {
+            order: TradeOrder {
+                by: TradeSort::Pl,
+                ascending: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let page: Vec<i64> = db
+            .query_trades(&filter, Some(1), 1)
+            .unwrap()
+            .iter()
+            .filter_map(|t| t.id)
+            .collect();
+        assert_eq!(page, [gain]);
+    }
+
+    #[test]
+    fn undoes_and_redoes_logged_changes() {
+        let db = new_test_db();
+        let id = db
+            .add_trade(&Trade {
+                tags: vec!["hedge".to_string()],
+                ..stock("AAPL", Action::BuyToOpen, dec!(90), dec!(10), dec!(0))
+            })
+            .unwrap();
+        let original = db.get_trade(id).unwrap().unwrap();
+        let saved = |db: &Database| db.get_trade(id).unwrap().map(|t| (t.price, t.tags));
+
+        let before_edit = db.last_history_id().unwrap();
+        let mut edited = original;
+        edited.price = dec!(91.5);
+        edited.tags = vec!["mistake".to_string()];
+        db.update_trade(&edited).unwrap();
+        let after_edit = db.last_history_id().unwrap();
+        db.delete_trade(id).unwrap();
+        let after_delete = db.last_history_id().unwrap();
+
+        assert_eq!(db.undo_changes(after_edit, after_delete).unwrap(), [id]);
+        assert_eq!(saved(&db), Some((dec!(91.5), vec!["mistake".to_string()])));
+        db.undo_changes(before_edit, after_edit).unwrap();
+        assert_eq!(saved(&db), Some((dec!(90), vec!["hedge".to_string()])));
+        assert_eq!(db.get_tags().unwrap(), vec!["hedge"]);
+
+        db.redo_changes(before_edit, after_edit).unwrap();
+        assert_eq!(saved(&db), Some((dec!(91.5), vec!["mistake".to_string()])));
+        db.redo_changes(after_edit, after_delete).unwrap();
+        assert_eq!(saved(&db), None);
+        // Undoing back past the add removes the trade outright.
+        db.undo_changes(after_edit, after_delete).unwrap();
+        db.undo_changes(before_edit, after_edit).unwrap();
+        db.undo_changes(0, before_edit).unwrap();
+        assert_eq!(saved(&db), None);
+        assert!(db.get_deleted_trades().unwrap().is_empty());
+        assert!(db.get_tags().unwrap().is_empty());
+    }
+
+    #[test]
+    fn archives_closed_years_and_restores_them() {
+        let db = new_test_db();
+        let dated = |action: Action, price: Decimal, date: &str| Trade {
+            date: date.to_string(),
+            tags: vec!["core".to_string()],
+            ..stock("AAPL", action, price, dec!(10), dec!(1))
+        };
+        db.add_trade(&dated(Action::BuyToOpen, dec!(100), "2022-03-01"))
+            .unwrap();
+        db.add_trade(&dated(Action::SellToClose, dec!(110), "2022-09-01"))
+            .unwrap();
+        db.add_trade(&dated(Action::BuyToOpen, dec!(120), "2023-11-01"))
+            .unwrap();
+        let cash_flow = |db: &Database| -> Decimal {
+            let trades: Decimal = db
+                .get_all_trades()
+                .unwrap()
+                .iter()
+                .map(|t| t.cash_flow())
+                .sum();
+            let archived: Decimal = db
+                .get_archive_summaries()
+                .unwrap()
+                .iter()
+                .map(|s| s.cash_flow)
+                .sum();
+            trades + archived
+        };
+        let before = cash_flow(&db);
+
+        assert_eq!(db.get_archivable_years("2024-06-01").unwrap(), [2022]);
+        assert!(db
+            .archive_through(2023, CostBasisMethod::Fifo, "2024-06-01")
+            .is_err());
+        let archived = db
+            .archive_through(2022, CostBasisMethod::Fifo, "2024-06-01")
+            .unwrap();
+        assert_eq!(archived, 2);
+        assert_eq!(db.archived_through().unwrap(), Some(2022));
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
+        let summaries = db.get_archive_summaries().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!((summaries[0].trades, summaries[0].realized), (2, dec!(98)));
+        assert_eq!(cash_flow(&db), before);
+
+        let restored = new_test_db();
+        restored.restore_backup(&db.get_backup().unwrap()).unwrap();
+        assert_eq!(restored.get_archive_summaries().unwrap(), summaries);
+
+        assert_eq!(db.restore_archive_from(2022).unwrap(), 2);
+        let trades = db.get_all_trades().unwrap();
+        assert_eq!(trades.len(), 3);
+        assert_eq!(trades[2].tags, ["core"]);
+        assert!(db.get_archive_summaries().unwrap().is_empty());
+        assert_eq!(db.archived_through().unwrap(), None);
+    }
+
+    #[test]
+    fn keeps_currencies_through_history_and_backups() {
+        let db = new_test_db();
+        let account = Account {
+            id: None,
+            name: "Depot".to_string(),
+            currency: "EUR".to_string(),
+        };
+        db.add_account(&account).unwrap();
+        let id = db
+            .add_trade(&stock(
+                "SAP",
+                Action::BuyToOpen,
+                dec!(180),
+                dec!(5),
+                dec!(0),
+            ))
+            .unwrap();
+        let currency = |db: &Database| db.get_trade(id).unwrap().map(|t| t.currency);
+        assert_eq!(currency(&db).as_deref(), Some(DEFAULT_CURRENCY));
+
+        let before_edit = db.last_history_id().unwrap();
+        let mut edited = db.get_trade(id).unwrap().unwrap();
+        edited.currency = "EUR".to_string();
+        db.update_trade(&edited).unwrap();
+        let after_edit = db.last_history_id().unwrap();
+        db.undo_changes(before_edit, after_edit).unwrap();
+        assert_eq!(currency(&db).as_deref(), Some("USD"));
+
+        // History written before currencies were tracked can still be undone.
+        db.delete_trade(id).unwrap();
+        db.purge_trash().unwrap();
+        let after_purge = db.last_history_id().unwrap();
+        db.conn
+            .execute(
+                "UPDATE trade_history SET before = json_remove(before, '$.currency')",
+                [],
+            )
+            .unwrap();
+        default_history_currency(&db.conn).unwrap();
+        db.undo_changes(after_edit, after_purge).unwrap();
+        assert_eq!(currency(&db).as_deref(), Some("USD"));
+
+        let rate = FxRate {
+            date: "2024-01-02".to_string(),
+            from: "EUR".to_string(),
+            to: "USD".to_string(),
+            rate: dec!(1.10),
+            source: "manual".to_string(),
+        };
+        db.set_fx_rate(&rate).unwrap();
+        db.set_fx_rate(&FxRate {
+            rate: dec!(1.09),
+            ..rate.clone()
+        })
+        .unwrap();
+        assert_eq!(db.get_fx_rates().unwrap().len(), 1);
+        assert_eq!(db.get_fx_rates().unwrap()[0].rate, dec!(1.09));
+
+        let restored = new_test_db();
+        restored.restore_backup(&db.get_backup().unwrap()).unwrap();
+        assert_eq!(restored.get_accounts().unwrap()[0].currency, "EUR");
+        assert_eq!(restored.get_fx_rates().unwrap(), db.get_fx_rates().unwrap());
+        db.delete_fx_rate(&rate).unwrap();
+        assert!(db.get_fx_rates().unwrap().is_empty());
+    }
+
+    #[test]
+    fn trades_by_symbol_leave_other_symbols_out() {
+        let db = new_test_db();
+        let buy = stock("AAPL", Action::BuyToOpen, dec!(90), dec!(10), dec!(0));
+        db.add_trade(&buy).unwrap();
+        db.add_trade(&stock(
+            "MSFT",
             Action::BuyToOpen,
-            dec!(100.0),
-            dec!(10.0),
-            dec!(0.0),
+            dec!(400),
+            dec!(5),
+            dec!(0),
+        ))
+        .unwrap();
+        let sell = db
+            .add_trade(&stock(
+                "AAPL",
+                Action::SellToClose,
+                dec!(95),
+                dec!(10),
+                dec!(0),
+            ))
+            .unwrap();
+
+        let trades = db.get_trades_by_symbol("AAPL").unwrap();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|t| t.symbol == "AAPL"));
+        assert_eq!(trades[0].id, Some(sell));
+    }
+
+    #[test]
+    fn date_range_limits_report_queries() {
+        let db = new_test_db();
+        for (action, price, date) in [
+            (Action::BuyToOpen, dec!(100), "2023-11-01"),
+            (Action::SellToClose, dec!(110), "2024-02-01"),
+        ] {
+            db.add_trade(&Trade {
+                date: date.to_string(),
+                ..stock("AAPL", action, price, dec!(10), dec!(0))
+            })
+            .unwrap();
+        }
+        let range = DateRange {
+            start: Some("2024-01-01".to_string()),
+            end: None,
+        };
+
+        // The sale is in range and still matches the lot bought before it.
+        assert_eq!(db.get_trades_in(&range).unwrap().len(), 1);
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &range)
+            .unwrap();
+        assert_eq!(report[0].profit_loss, dec!(100));
+        assert_eq!(report[0].trade_count, 1);
+
+        let range = DateRange {
+            start: None,
+            end: Some("2023-12-31".to_string()),
+        };
+        let periods = db
+            .get_period_report(CostBasisMethod::Fifo, Period::Year, &range)
+            .unwrap();
+        assert_eq!(periods.len(), 1);
+        assert_eq!(periods[0].realized, Decimal::ZERO);
+    }
+
+    #[test]
+    fn dividends_round_trip_within_a_range() {
+        let db = new_test_db();
+        let dividend = |date: &str, qualified: bool| Dividend {
+            id: None,
+            symbol: "KO".to_string(),
+            date: date.to_string(),
+            amount: dec!(48.50),
+            qualified,
+            account_id: None,
+        };
+        db.add_dividend(&dividend("2023-12-15", false)).unwrap();
+        let id = db.add_dividend(&dividend("2024-04-01", true)).unwrap();
+
+        let range = DateRange {
+            start: Some("2024-01-01".to_string()),
+            end: None,
+        };
+        let stored = db.get_dividends(&range).unwrap();
+        assert_eq!(
+            stored,
+            vec![Dividend {
+                id: Some(id),
+                ..dividend("2024-04-01", true)
+            }]
         );
-        assert_eq!(bought.cash_flow(), dec!(-1000));
+        db.delete_dividend(id).unwrap();
+        assert_eq!(db.get_dividends(&DateRange::default()).unwrap().len(), 1);
     }
 
     #[test]
-    fn schema_roundtrips_all_option_fields() {
+    fn saved_reports_round_trip() {
         let db = new_test_db();
-        let opt = option(
+        let mut report = SavedReport {
+            id: None,
+            name: "Puts".to_string(),
+            filter: "option_type = put".to_string(),
+            group_by: GroupBy::Symbol,
+        };
+        report.id = Some(db.add_saved_report(&report).unwrap());
+        assert!(db.add_saved_report(&report).is_err());
+        report.group_by = GroupBy::Month;
+        db.update_saved_report(&report).unwrap();
+        assert_eq!(db.get_saved_reports().unwrap(), vec![report.clone()]);
+        db.delete_saved_report(report.id.unwrap()).unwrap();
+        assert!(db.get_saved_reports().unwrap().is_empty());
+    }
+
+    #[test]
+    fn assignment_shrinks_existing_long_position() {
+        let db = new_test_db();
+        // Own 100 shares long, then a covered call gets assigned → sell 100.
+        db.add_trade(&stock(
             "AAPL",
-            Action::SellToOpen,
-            OptionType::Put,
-            dec!(2.0),
-            dec!(1.0),
+            Action::BuyToOpen,
+            dec!(90.0),
             dec!(100.0),
-            "2024-06-21",
-        );
-        let id = db.add_trade(&opt).unwrap();
-        let stored = db.get_trade(id).unwrap().unwrap();
-        assert_eq!(stored.symbol, "AAPL");
-        assert!(matches!(stored.action, Action::SellToOpen));
-        assert_eq!(stored.option_type, Some(OptionType::Put));
-        assert_eq!(stored.strike, Some(dec!(100.0)));
-        assert_eq!(stored.expiration, Some("2024-06-21".to_string()));
-        assert_eq!(stored.status, Some(OptionStatus::Open));
-        assert_eq!(stored.assigned_from, None);
+            dec!(0.0),
+        ))
+        .unwrap();
+        let call_id = db
+            .add_trade(&option(
+                "AAPL",
+                Action::SellToOpen,
+                OptionType::Call,
+                dec!(1.0),
+                dec!(1.0),
+                dec!(110.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.assign_option(call_id, OptionStatus::Assigned).unwrap();
+        // 100 long - 100 sold = flat.
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(0));
+        assert_eq!(db.get_break_even("AAPL").unwrap(), None);
     }
 
     #[test]
-    fn break_even_long_after_put_assignment() {
+    fn deleting_option_cleans_up_linked_stock_rows() {
         let db = new_test_db();
-        // Sell a put for $2 premium, then it gets assigned → buy 100 @ 100.
         let put_id = db
             .add_trade(&option(
                 "AAPL",
@@ -640,15 +4597,20 @@ mod tests {
             ))
             .unwrap();
         db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+        assert_eq!(db.get_all_trades().unwrap().len(), 2);
 
-        // Long 100 shares, break-even = 100 - 2 = 98.
-        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(100));
-        let be = db.get_break_even("AAPL").unwrap().unwrap();
-        assert_eq!(be, dec!(98));
+        db.delete_trade(put_id).unwrap();
+        assert!(db.get_all_trades().unwrap().is_empty());
+        // The generated row goes to the Trash with its option and comes back
+        // with it.
+        assert_eq!(db.get_deleted_trades().unwrap().len(), 1);
+        db.restore_trade(put_id).unwrap();
+        assert_eq!(db.get_all_trades().unwrap().len(), 2);
+        assert!(db.get_deleted_trades().unwrap().is_empty());
     }
 
     #[test]
-    fn put_assignment_creates_long_linked_stock_row() {
+    fn reverting_assignment_via_expire_removes_linked_row() {
         let db = new_test_db();
         let put_id = db
             .add_trade(&option(
@@ -656,144 +4618,147 @@ mod tests {
                 Action::SellToOpen,
                 OptionType::Put,
                 dec!(2.0),
-                dec!(2.0),
+                dec!(1.0),
                 dec!(100.0),
                 "2024-06-21",
             ))
             .unwrap();
         db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+        db.expire_option(put_id).unwrap();
 
         let trades = db.get_all_trades().unwrap();
-        let option_row = trades.iter().find(|t| t.id == Some(put_id)).unwrap();
-        assert_eq!(option_row.status, Some(OptionStatus::Assigned));
-
-        let linked: Vec<&Trade> = trades
-            .iter()
-            .filter(|t| t.assigned_from == Some(put_id))
-            .collect();
-        assert_eq!(linked.len(), 1);
-        assert_eq!(linked[0].trade_type, TradeType::Stock);
-        assert!(linked[0].action.is_buy());
-        assert_eq!(linked[0].quantity, dec!(200)); // 2 contracts * 100
-        assert_eq!(linked[0].price, dec!(100));
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].status, Some(OptionStatus::Expired));
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(0));
     }
 
     #[test]
-    fn call_assignment_creates_short_linked_stock_row() {
+    fn expired_sold_call_keeps_premium_and_no_stock_row() {
         let db = new_test_db();
+        // Sell-to-open a call for $3 premium (1 contract) then it expires worthless.
         let call_id = db
             .add_trade(&option(
                 "AAPL",
                 Action::SellToOpen,
                 OptionType::Call,
-                dec!(1.0),
+                dec!(3.0),
                 dec!(1.0),
                 dec!(110.0),
                 "2024-06-21",
             ))
             .unwrap();
-        db.assign_option(call_id, OptionStatus::Assigned).unwrap();
+        db.expire_option(call_id).unwrap();
 
-        // From flat, an assigned call yields a short position.
-        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(-100));
-        let linked = db
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        assert_eq!(report.len(), 1);
+        // Premium kept as profit; no linked stock row created.
+        assert_eq!(report[0].profit_loss, dec!(300));
+        assert_eq!(report[0].net_shares, dec!(0));
+        assert!(db
             .get_all_trades()
             .unwrap()
-            .into_iter()
-            .find(|t| t.assigned_from == Some(call_id))
-            .unwrap();
-        assert!(!linked.action.is_buy());
+            .iter()
+            .all(|t| t.assigned_from.is_none()));
     }
 
     #[test]
-    fn long_call_exercise_creates_long_linked_stock_row() {
+    fn expired_bought_put_realizes_premium_as_loss() {
         let db = new_test_db();
-        // Buy a call (long), then exercise it → buy shares at strike.
-        let call_id = db
+        // Buy-to-open a put for $1.50 (1 contract) that expires worthless.
+        let put_id = db
             .add_trade(&option(
                 "AAPL",
                 Action::BuyToOpen,
-                OptionType::Call,
-                dec!(1.0),
+                OptionType::Put,
+                dec!(1.5),
                 dec!(1.0),
-                dec!(110.0),
+                dec!(90.0),
                 "2024-06-21",
             ))
             .unwrap();
-        db.assign_option(call_id, OptionStatus::Exercised).unwrap();
+        db.expire_option(put_id).unwrap();
 
-        // Exercising a long call buys shares (from flat: long).
-        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(100));
-        let linked = db
-            .get_all_trades()
-            .unwrap()
-            .into_iter()
-            .find(|t| t.assigned_from == Some(call_id))
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
             .unwrap();
-        assert!(linked.action.is_buy());
-        assert_eq!(linked.price, dec!(110));
+        // The premium paid at open is the whole loss; expiry adds no cash flow.
+        assert_eq!(report[0].profit_loss, dec!(-150));
+        assert_eq!(report[0].net_shares, dec!(0));
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
     }
 
     #[test]
-    fn long_put_exercise_creates_short_linked_stock_row() {
+    fn expires_past_options_together() {
         let db = new_test_db();
-        // Buy a put (long), then exercise it → sell shares at strike.
-        let put_id = db
-            .add_trade(&option(
+        let leg = |option_type, strike| {
+            db.add_trade(&option(
                 "AAPL",
-                Action::BuyToOpen,
-                OptionType::Put,
+                Action::SellToOpen,
+                option_type,
                 dec!(1.0),
                 dec!(1.0),
-                dec!(90.0),
+                strike,
                 "2024-06-21",
             ))
-            .unwrap();
-        db.assign_option(put_id, OptionStatus::Exercised).unwrap();
+            .unwrap()
+        };
+        let (put, call) = (
+            leg(OptionType::Put, dec!(90)),
+            leg(OptionType::Call, dec!(110)),
+        );
+        db.assign_option(put, OptionStatus::Assigned).unwrap();
+        db.expire_options(&[put, call]).unwrap();
 
-        // Exercising a long put sells shares (from flat: short).
-        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(-100));
-        let linked = db
-            .get_all_trades()
+        let trades = db.get_all_trades().unwrap();
+        // The assignment's stock row goes with it.
+        assert_eq!(trades.len(), 2);
+        assert!(trades
+            .iter()
+            .all(|t| t.status == Some(OptionStatus::Expired)));
+        assert!(db
+            .get_open_options_by_expiration(None, None)
             .unwrap()
-            .into_iter()
-            .find(|t| t.assigned_from == Some(put_id))
-            .unwrap();
-        assert!(!linked.action.is_buy());
-        assert_eq!(linked.price, dec!(90));
+            .is_empty());
     }
 
     #[test]
-    fn assignment_shrinks_existing_long_position() {
+    fn editing_assigned_option_regenerates_linked_stock_row() {
         let db = new_test_db();
-        // Own 100 shares long, then a covered call gets assigned → sell 100.
-        db.add_trade(&stock(
-            "AAPL",
-            Action::BuyToOpen,
-            dec!(90.0),
-            dec!(100.0),
-            dec!(0.0),
-        ))
-        .unwrap();
-        let call_id = db
+        let put_id = db
             .add_trade(&option(
                 "AAPL",
                 Action::SellToOpen,
-                OptionType::Call,
-                dec!(1.0),
+                OptionType::Put,
+                dec!(2.0),
                 dec!(1.0),
-                dec!(110.0),
+                dec!(100.0),
                 "2024-06-21",
             ))
             .unwrap();
-        db.assign_option(call_id, OptionStatus::Assigned).unwrap();
-        // 100 long - 100 sold = flat.
-        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(0));
-        assert_eq!(db.get_break_even("AAPL").unwrap(), None);
+        db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+
+        // Edit the assigned option's strike and quantity while keeping it assigned.
+        let mut edited = db.get_trade(put_id).unwrap().unwrap();
+        edited.strike = Some(dec!(90.0));
+        edited.quantity = dec!(2.0);
+        db.update_trade(&edited).unwrap();
+
+        let linked: Vec<Trade> = db
+            .get_all_trades()
+            .unwrap()
+            .into_iter()
+            .filter(|t| t.assigned_from == Some(put_id))
+            .collect();
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].price, dec!(90));
+        assert_eq!(linked[0].quantity, dec!(200)); // 2 contracts * 100
+        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(200));
     }
 
     #[test]
-    fn deleting_option_cleans_up_linked_stock_rows() {
+    fn editing_assigned_option_to_stock_removes_linked_rows() {
         let db = new_test_db();
         let put_id = db
             .add_trade(&option(
@@ -809,128 +4774,283 @@ mod tests {
         db.assign_option(put_id, OptionStatus::Assigned).unwrap();
         assert_eq!(db.get_all_trades().unwrap().len(), 2);
 
-        db.delete_trade(put_id).unwrap();
-        assert!(db.get_all_trades().unwrap().is_empty());
+        // Change the option row to a plain stock trade (status/option fields cleared).
+        let mut edited = db.get_trade(put_id).unwrap().unwrap();
+        edited.trade_type = TradeType::Stock;
+        edited.option_type = None;
+        edited.strike = None;
+        edited.expiration = None;
+        edited.status = None;
+        db.update_trade(&edited).unwrap();
+
+        // No orphaned linked stock row should remain.
+        assert!(db
+            .get_all_trades()
+            .unwrap()
+            .iter()
+            .all(|t| t.assigned_from.is_none()));
     }
 
     #[test]
-    fn reverting_assignment_via_expire_removes_linked_row() {
+    fn closing_action_pairs_opens_with_closes() {
+        assert_eq!(Action::BuyToOpen.closing(), Action::SellToClose);
+        assert_eq!(Action::SellToOpen.closing(), Action::BuyToClose);
+        assert_eq!(Action::BuyToClose.closing(), Action::BuyToClose);
+        assert!(Action::SellToOpen.is_open());
+        assert!(!Action::SellToClose.is_open());
+    }
+
+    #[test]
+    fn open_quantity_pairs_closes_with_opens_per_contract() {
         let db = new_test_db();
-        let put_id = db
+        let sold = option(
+            "AAPL",
+            Action::SellToOpen,
+            OptionType::Call,
+            dec!(2.0),
+            dec!(3.0),
+            dec!(105.0),
+            "2024-06-21",
+        );
+        db.add_trade(&sold).unwrap();
+        // A different strike is a different position.
+        let mut other = sold.clone();
+        other.strike = Some(dec!(110));
+        db.add_trade(&other).unwrap();
+        let mut bought_back = sold.clone();
+        bought_back.action = Action::BuyToClose;
+        bought_back.quantity = dec!(1.0);
+        let close_id = db.add_trade(&bought_back).unwrap();
+
+        let key = sold.position_key();
+        assert_eq!(db.open_quantity(&key, true, None).unwrap(), dec!(2));
+        assert_eq!(db.open_quantity(&key, false, None).unwrap(), dec!(0));
+        assert_eq!(
+            db.open_quantity(&key, true, Some(close_id)).unwrap(),
+            dec!(3)
+        );
+
+        // An expired option is no longer open.
+        let expiring = db.add_trade(&other).unwrap();
+        db.expire_option(expiring).unwrap();
+        assert_eq!(
+            db.open_quantity(&other.position_key(), true, None).unwrap(),
+            dec!(3)
+        );
+    }
+
+    #[test]
+    fn rolling_links_legs_and_reports_net_credit() {
+        let db = new_test_db();
+        // Sell a $105 call for $2, buy it back for $1, re-sell $110 for $1.50.
+        let call_id = db
             .add_trade(&option(
                 "AAPL",
                 Action::SellToOpen,
-                OptionType::Put,
+                OptionType::Call,
                 dec!(2.0),
                 dec!(1.0),
-                dec!(100.0),
+                dec!(105.0),
                 "2024-06-21",
             ))
             .unwrap();
-        db.assign_option(put_id, OptionStatus::Assigned).unwrap();
-        db.expire_option(put_id).unwrap();
+        let next = option(
+            "AAPL",
+            Action::SellToOpen,
+            OptionType::Call,
+            dec!(1.5),
+            dec!(1.0),
+            dec!(110.0),
+            "2024-07-19",
+        );
+        let new_id = db
+            .roll_option(call_id, dec!(1.0), dec!(0.5), &next)
+            .unwrap();
 
-        let trades = db.get_all_trades().unwrap();
-        assert_eq!(trades.len(), 1);
-        assert_eq!(trades[0].status, Some(OptionStatus::Expired));
-        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(0));
+        let rolled = db.get_trade(call_id).unwrap().unwrap();
+        assert_eq!(rolled.status, Some(OptionStatus::Closed));
+        assert_eq!(rolled.roll_group_id, Some(call_id));
+        let opened = db.get_trade(new_id).unwrap().unwrap();
+        assert_eq!(opened.status, Some(OptionStatus::Open));
+        assert_eq!(opened.roll_group_id, Some(call_id));
+
+        let close = db
+            .get_all_trades()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.action == Action::BuyToClose)
+            .unwrap();
+        assert_eq!(close.strike, Some(dec!(105.0)));
+        assert_eq!(close.roll_group_id, Some(call_id));
+
+        // +200 - 100 - 0.50 fees + 150 = 249.50 over three legs.
+        let chains = db.get_roll_chains().unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].leg_count, 3);
+        assert_eq!(chains[0].net_credit, dec!(249.5));
+
+        // Rolling the new leg again extends the same chain.
+        db.roll_option(new_id, dec!(0.5), dec!(0.0), &next).unwrap();
+        let chains = db.get_roll_chains().unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains[0].leg_count, 5);
     }
 
     #[test]
-    fn expired_sold_call_keeps_premium_and_no_stock_row() {
+    fn only_open_options_can_be_rolled() {
         let db = new_test_db();
-        // Sell-to-open a call for $3 premium (1 contract) then it expires worthless.
-        let call_id = db
+        let stock_id = db
+            .add_trade(&stock(
+                "AAPL",
+                Action::BuyToOpen,
+                dec!(100.0),
+                dec!(100.0),
+                dec!(0.0),
+            ))
+            .unwrap();
+        let leg = Trade::default();
+        assert!(db
+            .roll_option(stock_id, dec!(1.0), dec!(0.0), &leg)
+            .is_err());
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn strategies_group_legs_and_sum_profit_loss() {
+        let db = new_test_db();
+        let strategy_id = db
+            .add_strategy(&Strategy {
+                id: None,
+                name: "AAPL 100/105 put spread".to_string(),
+                kind: StrategyKind::VerticalSpread,
+            })
+            .unwrap();
+        // Sell the 105 put for $3, buy the 100 put for $1 → $200 credit.
+        let short_leg = db
             .add_trade(&option(
                 "AAPL",
                 Action::SellToOpen,
-                OptionType::Call,
+                OptionType::Put,
                 dec!(3.0),
                 dec!(1.0),
-                dec!(110.0),
+                dec!(105.0),
                 "2024-06-21",
             ))
             .unwrap();
-        db.expire_option(call_id).unwrap();
+        let long_leg = db
+            .add_trade(&option(
+                "AAPL",
+                Action::BuyToOpen,
+                OptionType::Put,
+                dec!(1.0),
+                dec!(1.0),
+                dec!(100.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.set_trade_strategy(short_leg, Some(strategy_id)).unwrap();
+        db.set_trade_strategy(long_leg, Some(strategy_id)).unwrap();
 
-        let report = db.get_report_by_symbol().unwrap();
-        assert_eq!(report.len(), 1);
-        // Premium kept as profit; no linked stock row created.
-        assert_eq!(report[0].profit_loss, dec!(300));
-        assert_eq!(report[0].net_shares, dec!(0));
-        assert!(db
-            .get_all_trades()
-            .unwrap()
-            .iter()
-            .all(|t| t.assigned_from.is_none()));
+        let summaries = db.get_strategy_summaries().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].strategy.kind, StrategyKind::VerticalSpread);
+        assert_eq!(summaries[0].leg_count, 2);
+        assert_eq!(summaries[0].profit_loss, dec!(200));
+
+        // Deleting the strategy keeps the legs but ungroups them.
+        db.delete_strategy(strategy_id).unwrap();
+        assert!(db.get_strategies().unwrap().is_empty());
+        let trades = db.get_all_trades().unwrap();
+        assert_eq!(trades.len(), 2);
+        assert!(trades.iter().all(|t| t.strategy_id.is_none()));
     }
 
     #[test]
-    fn editing_assigned_option_regenerates_linked_stock_row() {
+    fn covered_call_link_reports_combined_return() {
         let db = new_test_db();
-        let put_id = db
+        // Buy 100 @ $100 with $1 fees, sell a $105 call for $2.
+        let lot_id = db
+            .add_trade(&stock(
+                "AAPL",
+                Action::BuyToOpen,
+                dec!(100.0),
+                dec!(100.0),
+                dec!(1.0),
+            ))
+            .unwrap();
+        let call_id = db
             .add_trade(&option(
                 "AAPL",
                 Action::SellToOpen,
-                OptionType::Put,
+                OptionType::Call,
                 dec!(2.0),
                 dec!(1.0),
-                dec!(100.0),
+                dec!(105.0),
                 "2024-06-21",
             ))
             .unwrap();
-        db.assign_option(put_id, OptionStatus::Assigned).unwrap();
+        db.link_covered_call(call_id, lot_id).unwrap();
+        assert_eq!(db.get_covering_lot(call_id).unwrap(), Some(lot_id));
+        let detail = db
+            .get_trade_detail(lot_id, CostBasisMethod::Fifo)
+            .unwrap()
+            .unwrap();
+        assert_eq!(detail.linked.len(), 1);
+        assert_eq!(detail.linked[0].0, "covered call");
+        assert!(detail.matches.is_empty());
 
-        // Edit the assigned option's strike and quantity while keeping it assigned.
-        let mut edited = db.get_trade(put_id).unwrap().unwrap();
-        edited.strike = Some(dec!(90.0));
-        edited.quantity = dec!(2.0);
-        db.update_trade(&edited).unwrap();
+        let open = &db.get_covered_positions().unwrap()[0];
+        assert_eq!(open.shares, dec!(100));
+        assert_eq!(open.stock_cost, dec!(10001));
+        assert_eq!(open.premium, dec!(200));
+        assert!(!open.called_away);
+        assert_eq!(open.total_return, dec!(200));
 
-        let linked: Vec<Trade> = db
-            .get_all_trades()
+        // Called away at 105: +$499 on the shares plus the premium.
+        db.assign_option(call_id, OptionStatus::Assigned).unwrap();
+        let called = &db.get_covered_positions().unwrap()[0];
+        assert!(called.called_away);
+        assert_eq!(called.stock_gain, dec!(499));
+        assert_eq!(called.total_return, dec!(699));
+        let detail = db
+            .get_trade_detail(call_id, CostBasisMethod::Fifo)
             .unwrap()
-            .into_iter()
-            .filter(|t| t.assigned_from == Some(put_id))
-            .collect();
-        assert_eq!(linked.len(), 1);
-        assert_eq!(linked[0].price, dec!(90));
-        assert_eq!(linked[0].quantity, dec!(200)); // 2 contracts * 100
-        assert_eq!(db.net_shares("AAPL").unwrap(), dec!(200));
+            .unwrap();
+        let links: Vec<&str> = detail.linked.iter().map(|(how, _)| *how).collect();
+        assert_eq!(links, ["covering lot", "assignment"]);
+        // The premium, kept when the call was assigned.
+        assert_eq!(detail.realized(), dec!(200));
+
+        // Deleting the lot drops the link.
+        db.delete_trade(lot_id).unwrap();
+        assert!(db.get_covered_positions().unwrap().is_empty());
     }
 
     #[test]
-    fn editing_assigned_option_to_stock_removes_linked_rows() {
+    fn covered_call_link_requires_a_large_enough_lot() {
         let db = new_test_db();
-        let put_id = db
+        let small_lot = db
+            .add_trade(&stock(
+                "AAPL",
+                Action::BuyToOpen,
+                dec!(100.0),
+                dec!(50.0),
+                dec!(0.0),
+            ))
+            .unwrap();
+        let call_id = db
             .add_trade(&option(
                 "AAPL",
                 Action::SellToOpen,
-                OptionType::Put,
+                OptionType::Call,
                 dec!(2.0),
                 dec!(1.0),
-                dec!(100.0),
+                dec!(105.0),
                 "2024-06-21",
             ))
             .unwrap();
-        db.assign_option(put_id, OptionStatus::Assigned).unwrap();
-        assert_eq!(db.get_all_trades().unwrap().len(), 2);
-
-        // Change the option row to a plain stock trade (status/option fields cleared).
-        let mut edited = db.get_trade(put_id).unwrap().unwrap();
-        edited.trade_type = TradeType::Stock;
-        edited.option_type = None;
-        edited.strike = None;
-        edited.expiration = None;
-        edited.status = None;
-        db.update_trade(&edited).unwrap();
-
-        // No orphaned linked stock row should remain.
-        assert!(db
-            .get_all_trades()
-            .unwrap()
-            .iter()
-            .all(|t| t.assigned_from.is_none()));
+        assert!(db.link_covered_call(call_id, small_lot).is_err());
+        assert_eq!(db.get_covering_lot(call_id).unwrap(), None);
     }
 
     #[test]
@@ -1018,6 +5138,428 @@ mod tests {
         );
     }
 
+    #[test]
+    fn report_separates_realized_and_unrealized() {
+        let db = new_test_db();
+        db.add_trade(&stock(
+            "AAPL",
+            Action::BuyToOpen,
+            dec!(100.0),
+            dec!(20.0),
+            dec!(0.0),
+        ))
+        .unwrap();
+        db.add_trade(&stock(
+            "AAPL",
+            Action::SellToClose,
+            dec!(110.0),
+            dec!(10.0),
+            dec!(0.0),
+        ))
+        .unwrap();
+        let call = option(
+            "AAPL",
+            Action::SellToOpen,
+            OptionType::Call,
+            dec!(2.0),
+            dec!(1.0),
+            dec!(120.0),
+            "2024-06-21",
+        );
+        let call_instrument = call.position_key().instrument();
+        assert_eq!(call_instrument, "AAPL240621C00120000");
+        db.add_trade(&call).unwrap();
+
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        assert_eq!(report[0].profit_loss, dec!(100));
+        // The shares and the call are open but have no marks yet.
+        assert_eq!(report[0].unrealized_pl, None);
+
+        for (instrument, price) in [("AAPL", dec!(105)), (call_instrument.as_str(), dec!(1))] {
+            db.set_quote(&Quote {
+                instrument: instrument.to_string(),
+                price,
+                fetched_at: "2024-06-01".to_string(),
+                source: "manual".to_string(),
+            })
+            .unwrap();
+        }
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        // 10 shares up $5 plus the call down $1 of its $2 premium.
+        assert_eq!(report[0].unrealized_pl, Some(dec!(150)));
+
+        db.delete_quote("AAPL").unwrap();
+        assert_eq!(db.get_quotes().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn manual_lot_matches_override_fifo_and_follow_deletes() {
+        let db = new_test_db();
+        let cheap = db
+            .add_trade(&stock(
+                "AAPL",
+                Action::BuyToOpen,
+                dec!(100.0),
+                dec!(10.0),
+                dec!(0.0),
+            ))
+            .unwrap();
+        let dear = db
+            .add_trade(&stock(
+                "AAPL",
+                Action::BuyToOpen,
+                dec!(120.0),
+                dec!(10.0),
+                dec!(0.0),
+            ))
+            .unwrap();
+        let sale = stock(
+            "AAPL",
+            Action::SellToClose,
+            dec!(130.0),
+            dec!(10.0),
+            dec!(0.0),
+        );
+        let sale_id = db.add_trade(&sale).unwrap();
+        let sale = db.get_trade(sale_id).unwrap().unwrap();
+
+        let candidates = db.get_lot_candidates(&sale).unwrap();
+        assert_eq!(candidates.len(), 2);
+        let fifo = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        assert_eq!(fifo[0].profit_loss, dec!(300));
+
+        db.set_lot_overrides(sale_id, &[(dear, dec!(10))]).unwrap();
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        assert_eq!(report[0].profit_loss, dec!(100));
+
+        // A trashed lot is skipped; purging it drops the match too.
+        db.delete_trade(dear).unwrap();
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        assert_eq!(report[0].profit_loss, dec!(300));
+        assert!(db.get_trade(cheap).unwrap().is_some());
+        assert_eq!(db.purge_trash().unwrap(), 1);
+        assert!(db.get_lot_overrides().unwrap().is_empty());
+    }
+
+    #[test]
+    fn short_sale_reports_sell_then_buy_profit() {
+        let db = new_test_db();
+        db.add_trade(&stock(
+            "TSLA",
+            Action::SellToOpen,
+            dec!(200.0),
+            dec!(10.0),
+            dec!(0.0),
+        ))
+        .unwrap();
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        assert_eq!(report[0].net_shares, dec!(-10));
+        assert_eq!(report[0].profit_loss, dec!(0));
+
+        db.add_trade(&stock(
+            "TSLA",
+            Action::BuyToClose,
+            dec!(180.0),
+            dec!(10.0),
+            dec!(0.0),
+        ))
+        .unwrap();
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
+        assert_eq!(report[0].net_shares, dec!(0));
+        assert_eq!(report[0].profit_loss, dec!(200));
+    }
+
+    #[test]
+    fn open_options_filtered_by_expiration() {
+        let db = new_test_db();
+        for (strike, expiration) in [
+            (dec!(100.0), "2024-07-19"),
+            (dec!(105.0), "2024-06-21"),
+            (dec!(110.0), "2024-08-16"),
+        ] {
+            db.add_trade(&option(
+                "AAPL",
+                Action::SellToOpen,
+                OptionType::Put,
+                dec!(1.0),
+                dec!(1.0),
+                strike,
+                expiration,
+            ))
+            .unwrap();
+        }
+        let expired = db
+            .add_trade(&option(
+                "AAPL",
+                Action::SellToOpen,
+                OptionType::Call,
+                dec!(1.0),
+                dec!(1.0),
+                dec!(120.0),
+                "2024-06-21",
+            ))
+            .unwrap();
+        db.expire_option(expired).unwrap();
+
+        let all = db.get_open_options_by_expiration(None, None).unwrap();
+        let expirations: Vec<_> = all.iter().filter_map(|t| t.expiration.clone()).collect();
+        assert_eq!(expirations, vec!["2024-06-21", "2024-07-19", "2024-08-16"]);
+
+        let window = db
+            .get_open_options_by_expiration(Some("2024-07-01"), Some("2024-07-31"))
+            .unwrap();
+        assert_eq!(window.len(), 1);
+        assert_eq!(window[0].strike, Some(dec!(100)));
+    }
+
+    #[test]
+    fn batched_inserts_save_every_leg() {
+        let db = new_test_db();
+        let legs = vec![
+            option(
+                "SPY",
+                Action::BuyToOpen,
+                OptionType::Put,
+                dec!(0.5),
+                dec!(1.0),
+                dec!(480.0),
+                "2024-06-21",
+            ),
+            option(
+                "SPY",
+                Action::SellToOpen,
+                OptionType::Put,
+                dec!(1.2),
+                dec!(1.0),
+                dec!(490.0),
+                "2024-06-21",
+            ),
+        ];
+        let ids = db.add_trades(&legs).unwrap();
+        assert_eq!(ids.len(), 2);
+        assert!(ids[0] < ids[1]);
+
+        let strategy_id = db
+            .add_strategy_with_legs(
+                &Strategy {
+                    id: None,
+                    name: "Put spread".to_string(),
+                    kind: StrategyKind::VerticalSpread,
+                },
+                &legs,
+            )
+            .unwrap();
+        let trades = db.get_all_trades().unwrap();
+        assert_eq!(trades.len(), 4);
+        let grouped = trades
+            .iter()
+            .filter(|t| t.strategy_id == Some(strategy_id))
+            .count();
+        assert_eq!(grouped, 2);
+        let summaries = db.get_strategy_summaries().unwrap();
+        assert_eq!(summaries[0].leg_count, 2);
+        assert_eq!(summaries[0].profit_loss, dec!(70));
+
+        // Imported orders name their legs by index.
+        let spread = Strategy {
+            id: None,
+            name: "Imported spread".to_string(),
+            kind: StrategyKind::VerticalSpread,
+        };
+        let ids = db
+            .add_trades_with_strategies(&legs, &[(spread, vec![1])])
+            .unwrap();
+        assert_eq!(db.get_trade(ids[0]).unwrap().unwrap().strategy_id, None);
+        let imported = db.get_trade(ids[1]).unwrap().unwrap().strategy_id;
+        assert!(imported.is_some_and(|id| id != strategy_id));
+
+        // An option imported as assigned brings its shares along.
+        let mut assigned = legs[1].clone();
+        assigned.status = Some(OptionStatus::Assigned);
+        let ids = db.add_trades(&[assigned]).unwrap();
+        let shares = db
+            .get_all_trades()
+            .unwrap()
+            .into_iter()
+            .find(|t| t.assigned_from == Some(ids[0]))
+            .unwrap();
+        assert_eq!(shares.action, Action::BuyToOpen);
+        assert_eq!(shares.quantity, dec!(100));
+    }
+
+    #[test]
+    fn split_adjusts_earlier_trades_and_keeps_cash_flows() {
+        let db = new_test_db();
+        let shares = db
+            .add_trade(&stock(
+                "NVDA",
+                Action::BuyToOpen,
+                dec!(800.0),
+                dec!(10.0),
+                dec!(1.0),
+            ))
+            .unwrap();
+        let call = db
+            .add_trade(&option(
+                "NVDA",
+                Action::SellToOpen,
+                OptionType::Call,
+                dec!(20.0),
+                dec!(1.0),
+                dec!(900.0),
+                "2024-07-19",
+            ))
+            .unwrap();
+        let mut later = stock("NVDA", Action::BuyToOpen, dec!(120.0), dec!(5.0), dec!(0.0));
+        later.date = "2024-06-20".to_string();
+        let later = db.add_trade(&later).unwrap();
+        let before: Decimal = db
+            .get_all_trades()
+            .unwrap()
+            .iter()
+            .map(|t| t.cash_flow())
+            .sum();
+
+        db.apply_corporate_action(&CorporateAction {
+            id: None,
+            symbol: "NVDA".to_string(),
+            kind: CorporateActionKind::Split,
+            date: "2024-06-10".to_string(),
+            ratio: Some(dec!(4)),
+            new_symbol: None,
+        })
+        .unwrap();
+
+        let shares = db.get_trade(shares).unwrap().unwrap();
+        assert_eq!(shares.quantity, dec!(40));
+        assert_eq!(shares.price, dec!(200));
+        let call = db.get_trade(call).unwrap().unwrap();
+        assert_eq!(call.quantity, dec!(4));
+        assert_eq!(call.strike, Some(dec!(225)));
+        assert_eq!(call.price, dec!(5));
+        assert_eq!(call.multiplier(), dec!(100));
+        // Trades on or after the split date are already post-split.
+        assert_eq!(db.get_trade(later).unwrap().unwrap().quantity, dec!(5));
+        let after: Decimal = db
+            .get_all_trades()
+            .unwrap()
+            .iter()
+            .map(|t| t.cash_flow())
+            .sum();
+        assert_eq!(after, before);
+        assert_eq!(db.get_corporate_actions().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn uneven_split_adjusts_multiplier_and_rename_moves_symbol() {
+        let db = new_test_db();
+        let put = db
+            .add_trade(&option(
+                "ABC",
+                Action::SellToOpen,
+                OptionType::Put,
+                dec!(3.0),
+                dec!(2.0),
+                dec!(60.0),
+                "2024-07-19",
+            ))
+            .unwrap();
+        db.set_quote(&Quote {
+            instrument: "ABC".to_string(),
+            price: dec!(61),
+            fetched_at: "2024-06-01".to_string(),
+            source: "manual".to_string(),
+        })
+        .unwrap();
+
+        db.apply_corporate_action(&CorporateAction {
+            id: None,
+            symbol: "ABC".to_string(),
+            kind: CorporateActionKind::Split,
+            date: "2024-06-10".to_string(),
+            ratio: Some(dec!(1.5)),
+            new_symbol: None,
+        })
+        .unwrap();
+        let adjusted = db.get_trade(put).unwrap().unwrap();
+        assert_eq!(adjusted.quantity, dec!(2));
+        assert_eq!(adjusted.strike, Some(dec!(40)));
+        assert_eq!(adjusted.contract_multiplier, Some(dec!(150)));
+        assert!(db.get_quotes().unwrap().is_empty());
+
+        db.apply_corporate_action(&CorporateAction {
+            id: None,
+            symbol: "ABC".to_string(),
+            kind: CorporateActionKind::SymbolChange,
+            date: "2024-06-15".to_string(),
+            ratio: None,
+            new_symbol: Some("XYZ".to_string()),
+        })
+        .unwrap();
+        assert_eq!(db.get_trade(put).unwrap().unwrap().symbol, "XYZ");
+    }
+
+    #[test]
+    fn snapshots_keep_one_row_per_day() {
+        let db = new_test_db();
+        let snapshot = |date: &str, value: Decimal| ValuationSnapshot {
+            date: date.to_string(),
+            market_value: value,
+            cash: dec!(-100),
+            account_value: value - dec!(100),
+        };
+        db.save_snapshot(&snapshot("2024-06-04", dec!(1200)))
+            .unwrap();
+        db.save_snapshot(&snapshot("2024-06-03", dec!(1000)))
+            .unwrap();
+        db.save_snapshot(&snapshot("2024-06-04", dec!(1300)))
+            .unwrap();
+        let snapshots = db.get_snapshots().unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].date, "2024-06-03");
+        assert_eq!(snapshots[1].account_value, dec!(1200));
+    }
+
+    #[test]
+    fn price_bars_round_trip_by_symbol() {
+        let db = new_test_db();
+        let bar = |symbol: &str, date: &str, close: Decimal| PriceBar {
+            symbol: symbol.to_string(),
+            date: date.to_string(),
+            open: dec!(100),
+            high: dec!(105.5),
+            low: dec!(99.25),
+            close,
+            volume: 1_000_000,
+        };
+        db.save_price_bars(&[
+            bar("AAPL", "2024-06-04", dec!(102)),
+            bar("AAPL", "2024-06-03", dec!(101)),
+            bar("MSFT", "2024-06-03", dec!(410)),
+        ])
+        .unwrap();
+        db.save_price_bars(&[bar("AAPL", "2024-06-04", dec!(103))])
+            .unwrap();
+        let bars = db.get_price_bars("AAPL").unwrap();
+        assert_eq!(bars.len(), 2);
+        assert_eq!(bars[0], bar("AAPL", "2024-06-03", dec!(101)));
+        assert_eq!(bars[1].close, dec!(103));
+    }
+
     #[test]
     fn report_orders_by_symbol_and_counts_trades() {
         let db = new_test_db();
@@ -1046,12 +5588,16 @@ mod tests {
         ))
         .unwrap();
 
-        let report = db.get_report_by_symbol().unwrap();
+        let report = db
+            .get_report_by_symbol(CostBasisMethod::Fifo, &DateRange::default())
+            .unwrap();
         assert_eq!(report.len(), 2);
         assert_eq!(report[0].symbol, "AAPL");
         assert_eq!(report[0].trade_count, 2);
         assert_eq!(report[0].profit_loss, dec!(20));
         assert_eq!(report[1].symbol, "TSLA");
+        // An open lot has no realized P/L yet.
+        assert_eq!(report[1].profit_loss, dec!(0));
     }
 
     #[test]
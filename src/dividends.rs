@@ -0,0 +1,110 @@
+//! Dividend income.
+//!
+//! Dividends are entered by hand, one cash payment per symbol and pay date,
+//! and totaled by symbol and by month. Qualified dividends are taxed like
+//! long-term gains and ordinary ones like income, so each total keeps the two
+//! apart.
+
+use crate::db::Dividend;
+use crate::periods::Period;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// Dividends received in one group: a symbol or a month (`YYYY-MM`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DividendGroup {
+    pub name: String,
+    pub total: Decimal,
+    /// The part of `total` that was qualified.
+    pub qualified: Decimal,
+    pub payments: usize,
+}
+
+impl DividendGroup {
+    /// The part of `total` taxed as ordinary income.
+    pub fn ordinary(&self) -> Decimal {
+        self.total - self.qualified
+    }
+}
+
+/// Dividend totals by symbol and by month, each ordered by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DividendReport {
+    pub by_symbol: Vec<DividendGroup>,
+    pub by_month: Vec<DividendGroup>,
+    pub total: DividendGroup,
+}
+
+/// Totals of `dividends` by symbol and by month.
+pub fn dividend_income(dividends: &[Dividend]) -> DividendReport {
+    let mut by_symbol = BTreeMap::new();
+    let mut by_month = BTreeMap::new();
+    let mut total = DividendGroup {
+        name: "Total".to_string(),
+        ..Default::default()
+    };
+    for dividend in dividends {
+        add(group(&mut by_symbol, dividend.symbol.clone()), dividend);
+        add(
+            group(&mut by_month, Period::Month.label(&dividend.date)),
+            dividend,
+        );
+        add(&mut total, dividend);
+    }
+    DividendReport {
+        by_symbol: by_symbol.into_values().collect(),
+        by_month: by_month.into_values().collect(),
+        total,
+    }
+}
+
+fn group(groups: &mut BTreeMap<String, DividendGroup>, name: String) -> &mut DividendGroup {
+    groups.entry(name.clone()).or_insert_with(|| DividendGroup {
+        name,
+        ..Default::default()
+    })
+}
+
+fn add(group: &mut DividendGroup, dividend: &Dividend) {
+    group.total += dividend.amount;
+    if dividend.qualified {
+        group.qualified += dividend.amount;
+    }
+    group.payments += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    fn dividend(symbol: &str, date: &str, amount: Decimal, qualified: bool) -> Dividend {
+        Dividend {
+            id: None,
+            symbol: symbol.to_string(),
+            date: date.to_string(),
+            amount,
+            qualified,
+            account_id: None,
+        }
+    }
+
+    #[test]
+    fn totals_by_symbol_and_month() {
+        let report = dividend_income(&[
+            dividend("KO", "2024-04-01", dec!(48.50), true),
+            dividend("KO", "2024-07-01", dec!(48.50), true),
+            dividend("REIT", "2024-04-15", dec!(20), false),
+        ]);
+        assert_eq!(report.by_symbol.len(), 2);
+        assert_eq!(report.by_symbol[0].total, dec!(97));
+        assert_eq!(report.by_symbol[0].payments, 2);
+        assert_eq!(report.by_symbol[1].ordinary(), dec!(20));
+
+        assert_eq!(report.by_month[0].name, "2024-04");
+        assert_eq!(report.by_month[0].total, dec!(68.50));
+        assert_eq!(report.by_month[0].qualified, dec!(48.50));
+        assert_eq!(report.total.total, dec!(117));
+        assert_eq!(report.total.ordinary(), dec!(20));
+    }
+}
@@ -0,0 +1,214 @@
+//! Encrypting the database with SQLCipher.
+//!
+//! Builds with the `encryption` feature link SQLCipher in place of plain
+//! SQLite; without it, encrypted databases can't be opened. An encrypted file
+//! has no plaintext header, which is how it is told apart before opening, and
+//! each connection to it starts with `PRAGMA key`. Snapshots of an encrypted
+//! database are encrypted with the same passphrase.
+//!
+//! Turning encryption on or off rewrites the database with `sqlcipher_export`
+//! into a new file that replaces the old one only once it is complete;
+//! changing the passphrase re-encrypts the file in place with `PRAGMA rekey`.
+
+use crate::snapshot::failure;
+use cursive::backends::crossterm::crossterm::event::{
+    self, Event, KeyCode, KeyEventKind, KeyModifiers,
+};
+use cursive::backends::crossterm::crossterm::terminal;
+use rusqlite::{ffi, params, Connection, Result};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Whether this build can open encrypted databases.
+pub const SUPPORTED: bool = cfg!(feature = "encryption");
+
+/// Environment variable a passphrase is read from instead of prompting.
+pub const PASSPHRASE_VAR: &str = "OPTIONS_TRACKER_PASSPHRASE";
+
+// The first bytes of every plaintext SQLite database.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Whether the database file at `path` is encrypted. A missing or empty file
+/// is a new, plaintext database.
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0; 16];
+    match File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+/// Gives `conn` the passphrase of its encrypted database. Fails if the
+/// passphrase is wrong or this build can't decrypt.
+pub fn unlock(conn: &Connection, passphrase: &str) -> Result<()> {
+    require_support()?;
+    conn.pragma_update(None, "key", passphrase)?;
+    match conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| {
+        row.get::<_, i64>(0)
+    }) {
+        Ok(_) => Ok(()),
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if e.code == rusqlite::ErrorCode::NotADatabase =>
+        {
+            Err(failure(
+                ffi::SQLITE_NOTADB,
+                "wrong passphrase, or the file is not an encrypted database".to_string(),
+            ))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Encrypts the database at `path` with `passphrase`. `current` is its
+/// passphrase when it is already encrypted, which is then changed.
+pub fn encrypt(path: &Path, current: Option<&str>, passphrase: &str) -> Result<(), String> {
+    require_support().map_err(|e| e.to_string())?;
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    match current {
+        Some(current) => {
+            unlock(&conn, current).map_err(|e| e.to_string())?;
+            conn.pragma_update(None, "rekey", passphrase)
+                .map_err(|e| e.to_string())
+        }
+        None => export(conn, path, passphrase),
+    }
+}
+
+/// Stores the encrypted database at `path` as plaintext again.
+pub fn decrypt(path: &Path, passphrase: &str) -> Result<(), String> {
+    let conn = Connection::open(path).map_err(|e| e.to_string())?;
+    unlock(&conn, passphrase).map_err(|e| e.to_string())?;
+    export(conn, path, "")
+}
+
+/// Asks for a passphrase on the terminal without echoing it.
+pub fn prompt_passphrase(prompt: &str) -> io::Result<String> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    terminal::enable_raw_mode()?;
+    let passphrase = read_hidden();
+    terminal::disable_raw_mode()?;
+    eprintln!();
+    passphrase
+}
+
+// Reads keys until Enter, keeping what was typed off the screen.
+fn read_hidden() -> io::Result<String> {
+    let mut text = String::new();
+    loop {
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Enter => return Ok(text),
+            KeyCode::Esc => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Char(c) => text.push(c),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            _ => {}
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"))
+}
+
+// Copies the database open on `conn` to a new file encrypted with
+// `passphrase` (plaintext when empty), then moves it over `path`.
+fn export(conn: Connection, path: &Path, passphrase: &str) -> Result<(), String> {
+    let exported = path.with_extension("export.tmp");
+    let _ = fs::remove_file(&exported);
+    conn.execute(
+        "ATTACH DATABASE ?1 AS exported KEY ?2",
+        params![exported.to_string_lossy(), passphrase],
+    )
+    .and_then(|_| conn.query_row("SELECT sqlcipher_export('exported')", [], |_| Ok(())))
+    .and_then(|_| conn.execute("DETACH DATABASE exported", []))
+    .map_err(|e| e.to_string())?;
+    conn.close().map_err(|(_, e)| e.to_string())?;
+    fs::rename(&exported, path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn require_support() -> Result<()> {
+    if SUPPORTED {
+        return Ok(());
+    }
+    Err(failure(
+        ffi::SQLITE_NOTADB,
+        "this build of options_tracker can't open encrypted databases; \
+         rebuild it with `cargo build --release --features encryption`"
+            .to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, Database, Trade};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn encrypts_and_decrypts_in_place() {
+        let path = std::env::temp_dir().join(format!(
+            "options_tracker_encryption_{}.db",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let name = path.to_string_lossy().into_owned();
+        Database::new(&name)
+            .unwrap()
+            .add_trade(&Trade {
+                symbol: "AAPL".to_string(),
+                action: Action::BuyToOpen,
+                price: dec!(150),
+                quantity: dec!(10),
+                date: "2024-01-05".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(!is_encrypted(&path));
+
+        if !SUPPORTED {
+            assert!(encrypt(&path, None, "secret").is_err());
+            assert!(Database::open(&name, Some("secret")).is_err());
+            fs::remove_file(&path).unwrap();
+            return;
+        }
+        encrypt(&path, None, "secret").unwrap();
+        assert!(is_encrypted(&path));
+        assert!(Database::new(&name).is_err());
+        assert!(Database::open(&name, Some("wrong")).is_err());
+        let mut db = Database::open(&name, Some("secret")).unwrap();
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
+        assert_eq!(db.search_trades("aap").unwrap().len(), 1);
+        // Snapshots stay encrypted and restore in place.
+        let snapshot = path.with_extension("snapshot.db");
+        let _ = fs::remove_file(&snapshot);
+        db.write_snapshot(&snapshot).unwrap();
+        assert!(is_encrypted(&snapshot));
+        let trade = db.get_all_trades().unwrap().remove(0);
+        db.add_trade(&trade).unwrap();
+        db.restore_snapshot(&snapshot).unwrap();
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
+        drop(db);
+        fs::remove_file(&snapshot).unwrap();
+
+        encrypt(&path, Some("secret"), "changed").unwrap();
+        assert!(Database::open(&name, Some("secret")).is_err());
+        decrypt(&path, "changed").unwrap();
+        assert!(!is_encrypted(&path));
+        assert_eq!(
+            Database::new(&name)
+                .unwrap()
+                .get_all_trades()
+                .unwrap()
+                .len(),
+            1
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
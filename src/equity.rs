@@ -0,0 +1,129 @@
+//! Equity curve: cumulative realized P/L over time.
+//!
+//! Each lot match adds its realized P/L on its close date, so the curve steps
+//! once per day that something closed. Zooming to a [`ChartRange`] keeps the
+//! running total from before the range, so the curve picks up at the level it
+//! had reached instead of starting over at zero.
+
+use crate::cost_basis::LotMatch;
+use crate::date::add_days;
+use crate::heatmap::daily_pl;
+use rust_decimal::Decimal;
+
+string_enum! {
+    /// How far back from today an equity curve is drawn.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum ChartRange {
+        #[default]
+        All => "all",
+        Year => "1y",
+        SixMonths => "6m",
+        ThreeMonths => "3m",
+        Month => "1m",
+    }
+    error = "chart range",
+}
+
+impl ChartRange {
+    /// The first date in range when it's `today`, or `None` for no limit.
+    pub fn start(&self, today: &str) -> Option<String> {
+        let days = match self {
+            ChartRange::All => return None,
+            ChartRange::Year => 365,
+            ChartRange::SixMonths => 182,
+            ChartRange::ThreeMonths => 91,
+            ChartRange::Month => 30,
+        };
+        add_days(today, -days)
+    }
+
+    /// The next shorter range; the shortest stays put.
+    pub fn zoom_in(&self) -> ChartRange {
+        let variants = ChartRange::variants();
+        let index = variants.iter().position(|r| r == self).unwrap_or(0);
+        variants[(index + 1).min(variants.len() - 1)]
+    }
+
+    /// The next longer range; `All` stays put.
+    pub fn zoom_out(&self) -> ChartRange {
+        let variants = ChartRange::variants();
+        let index = variants.iter().position(|r| r == self).unwrap_or(0);
+        variants[index.saturating_sub(1)]
+    }
+}
+
+/// Cumulative realized P/L of `matches` at the end of each close date, oldest
+/// first.
+pub fn equity_curve(matches: &[LotMatch]) -> Vec<(String, Decimal)> {
+    let mut total = Decimal::ZERO;
+    daily_pl(matches)
+        .into_iter()
+        .map(|(date, realized)| {
+            total += realized;
+            (date, total)
+        })
+        .collect()
+}
+
+/// The points of `curve` on or after `start`.
+pub fn curve_since(curve: &[(String, Decimal)], start: Option<&str>) -> Vec<(String, Decimal)> {
+    curve
+        .iter()
+        .filter(|(date, _)| start.is_none_or(|start| date.as_str() >= start))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, CostBasisMethod};
+    use crate::db::Action::{self, BuyToOpen, SellToClose};
+    use crate::db::Trade;
+    use rust_decimal_macros::dec;
+
+    fn stock(id: i64, action: Action, price: Decimal, date: &str) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            action,
+            price,
+            quantity: dec!(10),
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accumulates_realized_pl_by_close_date() {
+        let trades = vec![
+            stock(1, BuyToOpen, dec!(100), "2024-01-02"),
+            stock(2, SellToClose, dec!(110), "2024-02-01"),
+            stock(3, BuyToOpen, dec!(100), "2024-03-01"),
+            stock(4, SellToClose, dec!(95), "2024-06-03"),
+        ];
+        let curve = equity_curve(&match_lots(&trades, CostBasisMethod::Fifo, &[]));
+        assert_eq!(
+            curve,
+            vec![
+                ("2024-02-01".to_string(), dec!(100)),
+                ("2024-06-03".to_string(), dec!(50)),
+            ]
+        );
+
+        // Zoomed in, the curve keeps its level from before the range.
+        let start = ChartRange::ThreeMonths.start("2024-06-30");
+        assert_eq!(start.as_deref(), Some("2024-03-31"));
+        assert_eq!(curve_since(&curve, start.as_deref()), curve[1..].to_vec());
+        assert_eq!(curve_since(&curve, None).len(), 2);
+    }
+
+    #[test]
+    fn zooms_between_ranges() {
+        assert_eq!(ChartRange::All.zoom_in(), ChartRange::Year);
+        assert_eq!(ChartRange::Month.zoom_in(), ChartRange::Month);
+        assert_eq!(ChartRange::Year.zoom_out(), ChartRange::All);
+        assert_eq!(ChartRange::All.zoom_out(), ChartRange::All);
+        assert_eq!(ChartRange::All.start("2024-06-30"), None);
+    }
+}
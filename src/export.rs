@@ -0,0 +1,695 @@
+//! Report export to CSV and Excel.
+//!
+//! A report screen describes what it shows as a [`Table`] of plain values —
+//! amounts without `$` or padding — and the same code writes any table out, so
+//! no screen has its own CSV code. Files are named after the report and the
+//! time of the export, so exporting again never overwrites an earlier file.
+//! Screens with no layout of their own, such as custom reports, show the table
+//! as [`Table::to_text`] lays it out. Several tables can also go to one Excel
+//! workbook, a [`Sheet`] each, with numbers stored as numbers and dollar
+//! amounts formatted as currency.
+
+use crate::date::format_timestamp;
+use crate::db::{Quote, SymbolReport, Trade};
+use crate::fees::FeeReport;
+use crate::periods::PeriodReport;
+use crate::positions::Position;
+use crate::premium::PremiumMonth;
+use crate::statistics::{RiskMetrics, TradeStatistics};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use rust_xlsxwriter::{Format, Workbook};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A report's rows under its column headers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(headers: &[&str]) -> Table {
+        Table {
+            headers: headers.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row of values, each written with its `Display`.
+    pub fn row(&mut self, values: &[&dyn std::fmt::Display]) {
+        self.rows
+            .push(values.iter().map(|v| v.to_string()).collect());
+    }
+
+    /// Plain-text columns under an underlined header, each as wide as its
+    /// widest value: columns of numbers to the right, the rest to the left.
+    pub fn to_text(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, field) in widths.iter_mut().zip(row) {
+                *width = (*width).max(field.chars().count());
+            }
+        }
+        let numeric = self.numeric_columns();
+        let line = |fields: &[String]| {
+            fields
+                .iter()
+                .zip(widths.iter().zip(&numeric))
+                .map(|(field, (&width, &numeric))| {
+                    if numeric {
+                        format!("{:>w$}", field, w = width)
+                    } else {
+                        format!("{:<w$}", field, w = width)
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        };
+        let rule = "=".repeat(widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1));
+        std::iter::once(line(&self.headers))
+            .chain(std::iter::once(rule))
+            .chain(self.rows.iter().map(|row| line(row)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    // Whether each column holds only numbers (or blanks).
+    fn numeric_columns(&self) -> Vec<bool> {
+        (0..self.headers.len())
+            .map(|column| {
+                let mut fields = self.rows.iter().filter_map(|row| row.get(column));
+                !self.rows.is_empty()
+                    && fields.all(|f| f.is_empty() || f.parse::<Decimal>().is_ok())
+            })
+            .collect()
+    }
+
+    /// RFC 4180 CSV: a header line, then one line per row.
+    pub fn to_csv(&self) -> String {
+        std::iter::once(&self.headers)
+            .chain(&self.rows)
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|field| csv_field(field))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .map(|line| line + "\r\n")
+            .collect()
+    }
+
+    /// Tab-separated lines, header first, as spreadsheets paste them. Tabs
+    /// and line breaks inside a field become spaces.
+    pub fn to_tsv(&self) -> String {
+        std::iter::once(&self.headers)
+            .chain(&self.rows)
+            .map(|fields| {
+                fields
+                    .iter()
+                    .map(|field| field.replace(['\t', '\r', '\n'], " "))
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .map(|line| line + "\n")
+            .collect()
+    }
+
+    /// A JSON array with an object per row, keyed by the headers. Columns of
+    /// numbers are JSON numbers, and their blanks `null`.
+    pub fn to_json(&self) -> String {
+        let numeric = self.numeric_columns();
+        let rows: Vec<Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let fields = self.headers.iter().zip(row).zip(&numeric);
+                let object: Map<String, Value> = fields
+                    .map(|((header, field), &numeric)| {
+                        let value = match numeric {
+                            true => json_number(field).map_or(Value::Null, Value::Number),
+                            false => Value::String(field.clone()),
+                        };
+                        (header.clone(), value)
+                    })
+                    .collect();
+                Value::Object(object)
+            })
+            .collect();
+        serde_json::to_string_pretty(&rows).expect("report tables always serialize")
+    }
+}
+
+/// Every field of every trade, one row each. Columns are named after the
+/// fields, so a CSV import maps them back without being told; a field added
+/// to [`Trade`] gets a column here too.
+pub fn trades_table(trades: &[Trade]) -> Table {
+    let mut table = Table::new(&[
+        "id",
+        "date",
+        "symbol",
+        "trade_type",
+        "action",
+        "quantity",
+        "price",
+        "fees",
+        "currency",
+        "broker",
+        "option_type",
+        "strike",
+        "expiration",
+        "status",
+        "contract_multiplier",
+        "assigned_from",
+        "roll_group_id",
+        "strategy_id",
+        "delta",
+        "gamma",
+        "theta",
+        "vega",
+        "iv",
+        "comment",
+        "tags",
+    ]);
+    for trade in trades {
+        let greeks = &trade.greeks;
+        table.row(&[
+            &or_blank(trade.id),
+            &trade.date,
+            &trade.symbol,
+            &trade.trade_type,
+            &trade.action,
+            &trade.quantity,
+            &trade.price,
+            &trade.fees,
+            &trade.currency,
+            &or_blank(trade.broker.as_ref()),
+            &or_blank(trade.option_type),
+            &or_blank(trade.strike),
+            &or_blank(trade.expiration.as_ref()),
+            &or_blank(trade.status.as_ref()),
+            &or_blank(trade.contract_multiplier),
+            &or_blank(trade.assigned_from),
+            &or_blank(trade.roll_group_id),
+            &or_blank(trade.strategy_id),
+            &or_blank(greeks.delta),
+            &or_blank(greeks.gamma),
+            &or_blank(greeks.theta),
+            &or_blank(greeks.vega),
+            &or_blank(greeks.iv),
+            &trade.comment,
+            &trade.tags.join(", "),
+        ]);
+    }
+    table
+}
+
+/// Open positions with their marks from `quotes`, where there are any.
+pub fn positions_table(positions: &[Position], quotes: &HashMap<String, Quote>) -> Table {
+    let mut table = Table::new(&[
+        "Symbol",
+        "Instrument",
+        "Position",
+        "Avg Cost",
+        "Cost Basis",
+        "Opened",
+        "Days",
+        "Mark",
+        "Unrealized",
+    ]);
+    for position in positions {
+        let mark = quotes.get(&position.key.instrument()).map(|q| q.price);
+        table.row(&[
+            &position.key.symbol,
+            &position.key.instrument(),
+            &position.quantity,
+            &position.average_cost.round_dp(4),
+            &position.cost_basis().round_dp(2),
+            &position.opened,
+            &position.days_held,
+            &or_blank(mark),
+            &or_blank(mark.map(|m| position.unrealized(m).round_dp(2))),
+        ]);
+    }
+    table
+}
+
+/// Realized P/L, fees, and trade counts per period.
+pub fn periods_table(reports: &[PeriodReport]) -> Table {
+    let mut table = Table::new(&["Period", "Realized P/L", "Fees", "Trades"]);
+    for report in reports {
+        table.row(&[
+            &report.period,
+            &report.realized,
+            &report.fees,
+            &report.trade_count,
+        ]);
+    }
+    table
+}
+
+/// Realized, unrealized, and net position per symbol, with realized P/L
+/// split by holding period.
+pub fn symbols_table(reports: &[SymbolReport]) -> Table {
+    let mut table = Table::new(&[
+        "Symbol",
+        "Realized P/L",
+        "Short-Term",
+        "Long-Term",
+        "Unrealized P/L",
+        "Trades",
+        "Net Position",
+        "Break-Even",
+    ]);
+    for report in reports {
+        table.row(&[
+            &report.symbol,
+            &report.profit_loss,
+            &report.short_term,
+            &report.long_term,
+            &or_blank(report.unrealized_pl),
+            &report.trade_count,
+            &report.net_shares,
+            &or_blank(report.break_even),
+        ]);
+    }
+    table
+}
+
+/// Win/loss statistics of closed positions and risk measures of the equity
+/// curve, one figure a row.
+pub fn statistics_table(stats: &TradeStatistics, risk: &RiskMetrics) -> Table {
+    let mut table = Table::new(&["Statistic", "Value"]);
+    let figures: [(&str, String); 16] = [
+        ("Closed positions", stats.count.to_string()),
+        ("Wins", stats.wins.to_string()),
+        ("Losses", stats.losses.to_string()),
+        ("Win rate %", or_blank(stats.win_rate())),
+        ("Net realized P/L", stats.net().to_string()),
+        ("Average win", or_blank(stats.average_win())),
+        ("Average loss", or_blank(stats.average_loss())),
+        ("Profit factor", or_blank(stats.profit_factor())),
+        (
+            "Largest win",
+            or_blank(stats.largest_win.as_ref().map(|p| p.realized)),
+        ),
+        (
+            "Largest loss",
+            or_blank(stats.largest_loss.as_ref().map(|p| p.realized)),
+        ),
+        ("Average days held", or_blank(stats.average_days)),
+        ("Max drawdown", risk.max_drawdown.to_string()),
+        ("Average monthly P/L", or_blank(risk.average_month())),
+        ("Monthly P/L std. dev.", or_blank(risk.monthly_std_dev())),
+        ("Months", risk.monthly.len().to_string()),
+        ("Sharpe-style ratio", or_blank(risk.sharpe())),
+    ];
+    for (name, value) in &figures {
+        table.row(&[name, value]);
+    }
+    table
+}
+
+/// Premium written per month and symbol, and each month's total with its
+/// running total for the year.
+pub fn premium_table(months: &[PremiumMonth]) -> Table {
+    let mut table = Table::new(&[
+        "Month",
+        "Symbol",
+        "Collected",
+        "Bought Back",
+        "Fees",
+        "Net",
+        "YTD",
+    ]);
+    for month in months {
+        for row in &month.rows {
+            table.row(&[
+                &month.month,
+                &row.symbol,
+                &row.collected,
+                &row.bought_back,
+                &row.fees,
+                &row.net(),
+                &"",
+            ]);
+        }
+        table.row(&[
+            &month.month,
+            &"total",
+            &"",
+            &"",
+            &"",
+            &month.net(),
+            &month.year_to_date,
+        ]);
+    }
+    table
+}
+
+/// Fees against realized P/L by month, symbol, and broker, each grouping
+/// followed by the total.
+pub fn fees_table(report: &FeeReport) -> Table {
+    let mut table = Table::new(&[
+        "Group", "Name", "Fees", "Realized", "Gross", "Fee %", "Trades",
+    ]);
+    let grouped = [
+        ("month", &report.by_month),
+        ("symbol", &report.by_symbol),
+        ("broker", &report.by_broker),
+    ];
+    for (kind, groups) in grouped {
+        for group in groups.iter().chain([&report.total]) {
+            table.row(&[
+                &kind,
+                &group.name,
+                &group.fees,
+                &group.realized,
+                &group.gross(),
+                &or_blank(group.fee_percent().map(|p| p.round_dp(2))),
+                &group.trade_count,
+            ]);
+        }
+    }
+    table
+}
+
+/// A worksheet: a table under its tab name, with the headers of the columns
+/// holding dollar amounts.
+#[derive(Debug, Clone)]
+pub struct Sheet {
+    pub name: String,
+    pub table: Table,
+    pub currency: Vec<&'static str>,
+}
+
+/// The workbook for an accountant: `trades`, the open `positions` marked at
+/// `quotes`, and realized P/L by month.
+pub fn report_workbook(
+    trades: &[Trade],
+    positions: &[Position],
+    quotes: &HashMap<String, Quote>,
+    months: &[PeriodReport],
+) -> Vec<Sheet> {
+    vec![
+        Sheet {
+            name: "Trades".to_string(),
+            table: trades_table(trades),
+            currency: vec!["price", "fees", "strike"],
+        },
+        Sheet {
+            name: "Open Positions".to_string(),
+            table: positions_table(positions, quotes),
+            currency: vec!["Avg Cost", "Cost Basis", "Mark", "Unrealized"],
+        },
+        // Tab names can't hold a slash.
+        Sheet {
+            name: "Monthly P&L".to_string(),
+            table: periods_table(months),
+            currency: vec!["Realized P/L", "Fees"],
+        },
+    ]
+}
+
+/// A value's text, or an empty field for `None`.
+pub fn or_blank<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+// Quotes a field holding a comma, quote, or line break, doubling its quotes.
+// A number as written, or as near as a float comes when JSON can't read it
+// (`.5`); `None` for a blank.
+fn json_number(field: &str) -> Option<Number> {
+    field.parse::<Number>().ok().or_else(|| {
+        let value = field.parse::<Decimal>().ok()?.to_f64()?;
+        Number::from_f64(value)
+    })
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Writes `table` to `dir` (created if missing) as `<name>-<timestamp>.csv`,
+/// stamped with `now` in Unix seconds, and returns the file's path.
+pub fn write_csv(table: &Table, dir: &str, name: &str, now: i64) -> Result<PathBuf, String> {
+    write_file(&table.to_csv(), dir, name, "csv", now)
+}
+
+/// Writes `contents` to `dir` as `<name>-<timestamp>.<extension>`, like
+/// [`write_csv`].
+pub fn write_file(
+    contents: &str,
+    dir: &str,
+    name: &str,
+    extension: &str,
+    now: i64,
+) -> Result<PathBuf, String> {
+    let path = export_path(dir, name, now, extension)?;
+    fs::write(&path, contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Writes `sheets` to `dir` as the workbook `<name>-<timestamp>.xlsx`, like
+/// [`write_csv`].
+pub fn write_xlsx(sheets: &[Sheet], dir: &str, name: &str, now: i64) -> Result<PathBuf, String> {
+    let path = export_path(dir, name, now, "xlsx")?;
+    save_xlsx(sheets, &path)?;
+    Ok(path)
+}
+
+/// Writes `sheets` as a workbook at `path`. Headers are bold and stay in
+/// view, columns of numbers are stored as numbers, and the currency columns
+/// show as dollars.
+pub fn save_xlsx(sheets: &[Sheet], path: &Path) -> Result<(), String> {
+    let failed = |e: rust_xlsxwriter::XlsxError| format!("{}: {}", path.display(), e);
+    let bold = Format::new().set_bold();
+    let dollars = Format::new().set_num_format("$#,##0.00;[Red]-$#,##0.00");
+    let mut workbook = Workbook::new();
+    for sheet in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(&sheet.name).map_err(failed)?;
+        let numeric = sheet.table.numeric_columns();
+        for (column, header) in sheet.table.headers.iter().enumerate() {
+            worksheet
+                .write_string_with_format(0, column as u16, header, &bold)
+                .map_err(failed)?;
+        }
+        for (index, row) in sheet.table.rows.iter().enumerate() {
+            let row_number = index as u32 + 1;
+            for (column, field) in row.iter().enumerate() {
+                let number = field
+                    .parse::<Decimal>()
+                    .ok()
+                    .filter(|_| numeric[column])
+                    .and_then(|n| n.to_f64());
+                let column_number = column as u16;
+                match number {
+                    Some(n)
+                        if sheet
+                            .currency
+                            .contains(&sheet.table.headers[column].as_str()) =>
+                    {
+                        worksheet.write_number_with_format(row_number, column_number, n, &dollars)
+                    }
+                    Some(n) => worksheet.write_number(row_number, column_number, n),
+                    None if field.is_empty() => continue,
+                    None => worksheet.write_string(row_number, column_number, field),
+                }
+                .map_err(failed)?;
+            }
+        }
+        worksheet.set_freeze_panes(1, 0).map_err(failed)?;
+        worksheet.autofit();
+    }
+    workbook.save(path).map_err(failed)
+}
+
+// `dir/<name>-<timestamp>.<extension>`, creating `dir` if missing.
+fn export_path(dir: &str, name: &str, now: i64, extension: &str) -> Result<PathBuf, String> {
+    let stamp = file_stamp(now);
+    let slug: String = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    Ok(dir.join(format!("{}-{}.{}", slug, stamp, extension)))
+}
+
+/// The Unix time `now` as `YYYYMMDD-HHMMSS` (UTC), for file names that sort
+/// by when they were written.
+pub(crate) fn file_stamp(now: i64) -> String {
+    format_timestamp(now)
+        .chars()
+        .filter_map(|c| match c {
+            '-' | ':' => None,
+            ' ' => Some('-'),
+            c => Some(c),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn quotes_fields_that_need_it() {
+        let mut table = Table::new(&["Symbol", "Note"]);
+        table.row(&[&"AAPL", &dec!(12.50)]);
+        table.row(&[&"MSFT", &"sold \"high\", bought low"]);
+        assert_eq!(
+            table.to_csv(),
+            "Symbol,Note\r\nAAPL,12.50\r\nMSFT,\"sold \"\"high\"\", bought low\"\r\n"
+        );
+        table.row(&[&"KO", &"rolled\tout\nand up"]);
+        assert_eq!(table.to_tsv().lines().last(), Some("KO\trolled out and up"));
+    }
+
+    #[test]
+    fn lays_out_text_columns() {
+        let mut table = Table::new(&["Symbol", "Net"]);
+        table.row(&[&"AAPL", &dec!(-12.50)]);
+        table.row(&[&"KO", &dec!(240)]);
+        assert_eq!(
+            table.to_text(),
+            "Symbol     Net\n==============\nAAPL    -12.50\nKO         240"
+        );
+    }
+
+    #[test]
+    fn writes_json_objects() {
+        let mut table = Table::new(&["Symbol", "Net", "Break-Even"]);
+        table.row(&[&"AAPL", &dec!(-12.50), &or_blank(Some(".5"))]);
+        table.row(&[&"KO", &dec!(240), &or_blank(None::<Decimal>)]);
+        let rows: Value = serde_json::from_str(&table.to_json()).unwrap();
+        // Keys keep the columns' order.
+        let keys: Vec<&String> = rows[0].as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["Symbol", "Net", "Break-Even"]);
+        assert_eq!(
+            rows,
+            serde_json::json!([
+                {"Symbol": "AAPL", "Net": -12.5, "Break-Even": 0.5},
+                {"Symbol": "KO", "Net": 240, "Break-Even": null},
+            ])
+        );
+    }
+
+    #[test]
+    fn exported_trades_import_back() {
+        use crate::db::{Action, OptionStatus, OptionType, TradeType};
+        use crate::import::{
+            guess_mapping, parse_csv, parse_json, parse_pasted, read_trades, ReadResult,
+        };
+
+        let put = Trade {
+            id: Some(7),
+            symbol: "KO".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(55)),
+            expiration: Some("2024-02-16".to_string()),
+            status: Some(OptionStatus::Open),
+            price: dec!(1.20),
+            quantity: dec!(2),
+            date: "2024-01-10".to_string(),
+            fees: dec!(1.30),
+            comment: "rolled, again".to_string(),
+            currency: "CAD".to_string(),
+            broker: Some("Questrade".to_string()),
+            ..Default::default()
+        };
+        let table = trades_table(std::slice::from_ref(&put));
+        assert_eq!(table.headers.len(), table.rows[0].len());
+
+        let records = parse_csv(&table.to_csv());
+        let ReadResult { trades, errors, .. } = read_trades(&records, &guess_mapping(&records[0]));
+        assert!(errors.is_empty());
+        assert_eq!(trades.len(), 1);
+        let read = &trades[0];
+        assert_eq!(read.id, None);
+        assert_eq!(read.action, put.action);
+        assert_eq!(read.option_type, put.option_type);
+        assert_eq!(read.strike, put.strike);
+        assert_eq!(read.expiration, put.expiration);
+        assert_eq!(
+            (read.price, read.quantity, read.fees),
+            (put.price, put.quantity, put.fees)
+        );
+        assert_eq!(read.comment, put.comment);
+        assert_eq!(read.currency, "CAD");
+        assert_eq!(read.broker.as_deref(), Some("Questrade"));
+
+        // Copied as TSV and pasted back in, the trade reads the same.
+        let pasted = parse_pasted(&table.to_tsv());
+        let ReadResult { trades: again, .. } = read_trades(&pasted, &guess_mapping(&pasted[0]));
+        assert_eq!(format!("{:?}", again), format!("{:?}", trades));
+
+        // So does the JSON export read back, amounts being numbers there.
+        let records = parse_json(&table.to_json()).unwrap();
+        assert_eq!(records[0], table.headers);
+        let ReadResult {
+            trades: again,
+            errors,
+            ..
+        } = read_trades(&records, &guess_mapping(&records[0]));
+        assert!(errors.is_empty());
+        let again = &again[0];
+        assert_eq!(
+            (again.price, again.fees, again.strike),
+            (put.price, put.fees, put.strike)
+        );
+        assert_eq!(
+            (
+                again.action,
+                again.expiration.as_deref(),
+                again.comment.as_str()
+            ),
+            (put.action, Some("2024-02-16"), "rolled, again")
+        );
+    }
+
+    #[test]
+    fn writes_timestamped_files() {
+        let dir =
+            std::env::temp_dir().join(format!("options_tracker_export_{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let table = Table::new(&["Month"]);
+        // 2024-06-03 14:05:09 UTC.
+        let path = write_csv(&table, dir, "P/L by Month (FIFO)", 1_717_423_509).unwrap();
+        assert!(path.ends_with("p-l-by-month-fifo-20240603-140509.csv"));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Month\r\n");
+
+        let months = [PeriodReport {
+            period: "2024-06".to_string(),
+            realized: dec!(-125.50),
+            fees: dec!(2.60),
+            trade_count: 4,
+        }];
+        let sheets = report_workbook(&[], &[], &HashMap::new(), &months);
+        assert_eq!(
+            sheets[2].table.rows,
+            vec![vec!["2024-06", "-125.50", "2.60", "4"]]
+        );
+        let path = write_xlsx(&sheets, dir, "Report Workbook", 1_717_423_509).unwrap();
+        assert!(path.ends_with("report-workbook-20240603-140509.xlsx"));
+        // An xlsx file is a zip archive.
+        assert!(fs::read(&path).unwrap().starts_with(b"PK"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,250 @@
+//! Fees and commissions analysis.
+//!
+//! A broker's [`CommissionSchedule`] gives the fees of a trade from its size,
+//! to fill in the trade form.
+//!
+//! Fees are totaled per group of trades — by month, by symbol, and by broker
+//! — next to the group's realized P/L, to show how much of the gross P/L they
+//! take. Realized P/L already has the fees of both sides of each lot match
+//! folded in (see [`crate::cost_basis`]); those fees, prorated by the matched
+//! quantity, are added back to give the gross. Fees paid go by trade date and
+//! realized P/L by close date, and fees on lots still open are paid but not
+//! yet part of any P/L.
+
+use crate::cost_basis::LotMatch;
+use crate::db::{Trade, TradeType};
+use crate::periods::Period;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+
+/// Group of the trades with no broker in the by-broker totals.
+pub const NO_BROKER: &str = "(none)";
+
+/// What a broker charges for an order: a flat amount plus an amount per
+/// option contract or per share, and never less than a minimum.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommissionSchedule {
+    /// The broker, as trades name it.
+    pub broker: String,
+    pub per_order: Decimal,
+    pub per_contract: Decimal,
+    pub per_share: Decimal,
+    pub minimum: Decimal,
+}
+
+impl CommissionSchedule {
+    /// The commission on `quantity` contracts or shares of `trade_type`;
+    /// nothing for no quantity.
+    pub fn commission(&self, trade_type: TradeType, quantity: Decimal) -> Decimal {
+        if quantity.is_zero() {
+            return Decimal::ZERO;
+        }
+        let per_unit = match trade_type {
+            TradeType::Option => self.per_contract,
+            TradeType::Stock => self.per_share,
+        };
+        (self.per_order + per_unit * quantity.abs()).max(self.minimum)
+    }
+}
+
+/// The schedule among `schedules` of `broker`, matched ignoring case.
+pub fn schedule_for<'a>(
+    schedules: &'a [CommissionSchedule],
+    broker: &str,
+) -> Option<&'a CommissionSchedule> {
+    let broker = broker.trim();
+    schedules
+        .iter()
+        .find(|schedule| schedule.broker.eq_ignore_ascii_case(broker))
+}
+
+/// Fees and realized P/L of one group of trades.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeGroup {
+    /// The month (`YYYY-MM`), symbol, or broker.
+    pub name: String,
+    /// Fees paid on the group's trades.
+    pub fees: Decimal,
+    /// Realized P/L, fees included.
+    pub realized: Decimal,
+    /// The fees included in `realized`.
+    pub realized_fees: Decimal,
+    pub trade_count: usize,
+}
+
+impl FeeGroup {
+    /// Realized P/L before fees.
+    pub fn gross(&self) -> Decimal {
+        self.realized + self.realized_fees
+    }
+
+    /// Percent of the gross P/L taken by fees; `None` unless the gross is
+    /// positive.
+    pub fn fee_percent(&self) -> Option<Decimal> {
+        let gross = self.gross();
+        (gross > Decimal::ZERO).then(|| self.realized_fees / gross * Decimal::ONE_HUNDRED)
+    }
+}
+
+/// Fee totals by month, by symbol, and by broker, each group ordered by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeReport {
+    pub by_month: Vec<FeeGroup>,
+    pub by_symbol: Vec<FeeGroup>,
+    /// Realized P/L goes to the broker of the closing trade.
+    pub by_broker: Vec<FeeGroup>,
+    pub total: FeeGroup,
+}
+
+/// Fees of `trades` with the realized P/L of their lot `matches`.
+pub fn fee_report(trades: &[Trade], matches: &[LotMatch]) -> FeeReport {
+    let mut total = group_fees(
+        trades,
+        matches,
+        |_| "Total".to_string(),
+        |_| "Total".to_string(),
+    )
+    .pop()
+    .unwrap_or_default();
+    total.name = "Total".to_string();
+    let broker = |trade: &Trade| {
+        trade
+            .broker
+            .clone()
+            .unwrap_or_else(|| NO_BROKER.to_string())
+    };
+    let brokers: HashMap<i64, String> = trades
+        .iter()
+        .filter_map(|t| Some((t.id?, broker(t))))
+        .collect();
+    FeeReport {
+        by_month: group_fees(
+            trades,
+            matches,
+            |t| Period::Month.label(&t.date),
+            |m| Period::Month.label(&m.close_date),
+        ),
+        by_symbol: group_fees(
+            trades,
+            matches,
+            |t| t.symbol.clone(),
+            |m| m.key.symbol.clone(),
+        ),
+        by_broker: group_fees(trades, matches, broker, |m| {
+            m.close_id
+                .and_then(|id| brokers.get(&id).cloned())
+                .unwrap_or_else(|| NO_BROKER.to_string())
+        }),
+        total,
+    }
+}
+
+// Totals per group, naming each trade's group with `trade_group` and each
+// match's with `match_group`.
+fn group_fees(
+    trades: &[Trade],
+    matches: &[LotMatch],
+    trade_group: impl Fn(&Trade) -> String,
+    match_group: impl Fn(&LotMatch) -> String,
+) -> Vec<FeeGroup> {
+    let by_id: HashMap<i64, &Trade> = trades.iter().filter_map(|t| Some((t.id?, t))).collect();
+    // The share of a trade's fees that `quantity` of it carries.
+    let prorated = |id: Option<i64>, quantity: Decimal| -> Decimal {
+        match id.and_then(|id| by_id.get(&id)) {
+            Some(trade) if trade.quantity > Decimal::ZERO => trade.fees * quantity / trade.quantity,
+            _ => Decimal::ZERO,
+        }
+    };
+
+    let mut groups: BTreeMap<String, FeeGroup> = BTreeMap::new();
+    for trade in trades {
+        let entry = group(&mut groups, trade_group(trade));
+        entry.fees += trade.fees;
+        entry.trade_count += 1;
+    }
+    for lot in matches {
+        let entry = group(&mut groups, match_group(lot));
+        entry.realized += lot.realized;
+        entry.realized_fees +=
+            prorated(lot.open_id, lot.quantity) + prorated(lot.close_id, lot.quantity);
+    }
+    groups.into_values().collect()
+}
+
+fn group(groups: &mut BTreeMap<String, FeeGroup>, name: String) -> &mut FeeGroup {
+    groups.entry(name.clone()).or_insert_with(|| FeeGroup {
+        name,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, CostBasisMethod};
+    use crate::db::Action;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn totals_fees_against_gross_pl() {
+        let stock = |id: i64, symbol: &str, action: Action, price: Decimal, date: &str| Trade {
+            id: Some(id),
+            symbol: symbol.to_string(),
+            action,
+            price,
+            quantity: dec!(10),
+            date: date.to_string(),
+            fees: dec!(5),
+            broker: (symbol == "AAPL").then(|| "Schwab".to_string()),
+            ..Default::default()
+        };
+        let trades = vec![
+            stock(1, "AAPL", Action::BuyToOpen, dec!(100), "2024-05-30"),
+            stock(2, "AAPL", Action::SellToClose, dec!(110), "2024-06-03"),
+            stock(3, "MSFT", Action::BuyToOpen, dec!(400), "2024-06-04"),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let report = fee_report(&trades, &matches);
+
+        // $100 gross on AAPL, $10 of it to fees.
+        let aapl = &report.by_symbol[0];
+        assert_eq!((aapl.fees, aapl.realized), (dec!(10), dec!(90)));
+        assert_eq!(aapl.fee_percent(), Some(dec!(10)));
+        // Nothing realized on MSFT yet: its fee is paid but in no P/L.
+        assert_eq!(report.by_symbol[1].fees, dec!(5));
+        assert_eq!(report.by_symbol[1].fee_percent(), None);
+
+        assert_eq!(report.by_month.len(), 2);
+        assert_eq!(report.by_month[0].name, "2024-05");
+        assert_eq!(report.by_month[1].fees, dec!(10));
+        assert_eq!(report.by_month[1].realized, dec!(90));
+        assert_eq!(report.by_month[1].fee_percent(), Some(dec!(10)));
+        assert_eq!(report.total.fees, dec!(15));
+        assert_eq!(report.total.gross(), dec!(100));
+        assert_eq!(report.total.trade_count, 3);
+
+        assert_eq!(report.by_broker.len(), 2);
+        assert_eq!(report.by_broker[0].name, NO_BROKER);
+        assert_eq!(report.by_broker[0].fees, dec!(5));
+        assert_eq!(report.by_broker[1].name, "Schwab");
+        assert_eq!(report.by_broker[1].fee_percent(), Some(dec!(10)));
+    }
+
+    #[test]
+    fn charges_per_unit_with_a_minimum() {
+        let schedules = [CommissionSchedule {
+            broker: "Tastytrade".to_string(),
+            per_contract: dec!(1),
+            per_share: dec!(0.005),
+            minimum: dec!(1),
+            ..Default::default()
+        }];
+        let schedule = schedule_for(&schedules, " tastytrade").unwrap();
+        assert_eq!(schedule.commission(TradeType::Option, dec!(3)), dec!(3));
+        // 100 shares at half a cent come to 50 cents, under the minimum.
+        assert_eq!(schedule.commission(TradeType::Stock, dec!(100)), dec!(1));
+        assert_eq!(schedule.commission(TradeType::Stock, dec!(1000)), dec!(5));
+        assert_eq!(schedule.commission(TradeType::Option, dec!(0)), dec!(0));
+        assert!(schedule_for(&schedules, "Schwab").is_none());
+    }
+}
@@ -0,0 +1,218 @@
+//! Exchange rates and totals converted into the base currency.
+//!
+//! Each trade is priced in its own currency (a US option in dollars, say), so
+//! adding up trades in several currencies means nothing until each is
+//! converted into the one base currency reports are read in (see
+//! [`crate::config::Config::base_currency`]). Rates come from the `fx_rates`
+//! table, entered by hand or fetched as the quote of an instrument like
+//! `EURUSD=X` (see [`fx_instrument`]).
+//!
+//! An amount converts at the pair's latest rate on or before its date, or its
+//! earliest rate when every one is later. A pair with no rates of its own
+//! uses the inverse of the opposite pair.
+
+use crate::db::{FxRate, Trade};
+use crate::market_data::MarketData;
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+
+/// `text` as an ISO currency code: three letters, uppercased.
+pub fn parse_currency(text: &str) -> Result<String, String> {
+    let code = text.trim().to_uppercase();
+    if code.len() == 3 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Ok(code)
+    } else {
+        Err(format!(
+            "Invalid currency {:?}: use a three-letter code like USD",
+            text.trim()
+        ))
+    }
+}
+
+/// The instrument a provider quotes one `from` in `to` under, Yahoo's
+/// `EURUSD=X` form. A `csv` quotes file can list the same names.
+pub fn fx_instrument(from: &str, to: &str) -> String {
+    format!("{}{}=X", from, to)
+}
+
+/// Fetches today's rate of `from` in `to` from `provider`, dated `date`.
+pub fn fetch_fx_rate(
+    provider: &dyn MarketData,
+    from: &str,
+    to: &str,
+    date: &str,
+) -> Result<FxRate, String> {
+    let (rate, source) = provider.sourced_price(&fx_instrument(from, to))?;
+    if rate <= Decimal::ZERO {
+        return Err(format!("{} gave a rate of {} for {}", source, rate, from));
+    }
+    Ok(FxRate {
+        date: date.to_string(),
+        from: from.to_string(),
+        to: to.to_string(),
+        rate,
+        source,
+    })
+}
+
+/// Exchange rates by currency pair, for looking up the rate on a date.
+#[derive(Debug, Clone, Default)]
+pub struct FxRates {
+    // Rates of each (from, to) pair by date.
+    pairs: HashMap<(String, String), BTreeMap<String, Decimal>>,
+}
+
+impl FxRates {
+    pub fn new(rates: &[FxRate]) -> Self {
+        let mut pairs: HashMap<(String, String), BTreeMap<String, Decimal>> = HashMap::new();
+        for rate in rates {
+            pairs
+                .entry((rate.from.clone(), rate.to.clone()))
+                .or_default()
+                .insert(rate.date.clone(), rate.rate);
+        }
+        FxRates { pairs }
+    }
+
+    /// What one `from` was worth in `to` on `date`; `None` when neither the
+    /// pair nor its opposite has a rate.
+    pub fn rate(&self, from: &str, to: &str, date: &str) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+        let on = |rates: &BTreeMap<String, Decimal>| {
+            rates
+                .range(..=date.to_string())
+                .next_back()
+                .or_else(|| rates.iter().next())
+                .map(|(_, rate)| *rate)
+        };
+        if let Some(rates) = self.pairs.get(&(from.to_string(), to.to_string())) {
+            return on(rates);
+        }
+        let inverse = self.pairs.get(&(to.to_string(), from.to_string()))?;
+        on(inverse)
+            .filter(|rate| !rate.is_zero())
+            .map(|rate| Decimal::ONE / rate)
+    }
+
+    /// `amount` in `from` converted to `to` at the rate on `date`.
+    pub fn convert(&self, amount: Decimal, from: &str, to: &str, date: &str) -> Option<Decimal> {
+        self.rate(from, to, date).map(|rate| amount * rate)
+    }
+}
+
+/// Trades in one currency, in it and converted into the base currency.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrencyTotal {
+    pub currency: String,
+    pub trades: usize,
+    /// Net cash flow of the trades (see [`Trade::cash_flow`]), in
+    /// `currency`.
+    pub cash_flow: Decimal,
+    pub fees: Decimal,
+    /// The cash flow in the base currency, each trade converted at the rate
+    /// on its date; `None` when some trade had no rate.
+    pub converted: Option<Decimal>,
+}
+
+/// Totals of `trades` per currency, converted into `base` with `rates`.
+pub fn currency_totals(trades: &[Trade], rates: &FxRates, base: &str) -> Vec<CurrencyTotal> {
+    let mut totals: BTreeMap<&str, CurrencyTotal> = BTreeMap::new();
+    for trade in trades {
+        let total = totals
+            .entry(trade.currency.as_str())
+            .or_insert_with(|| CurrencyTotal {
+                currency: trade.currency.clone(),
+                trades: 0,
+                cash_flow: Decimal::ZERO,
+                fees: Decimal::ZERO,
+                converted: Some(Decimal::ZERO),
+            });
+        let cash_flow = trade.cash_flow();
+        total.trades += 1;
+        total.cash_flow += cash_flow;
+        total.fees += trade.fees;
+        total.converted = total.converted.and_then(|converted| {
+            rates
+                .convert(cash_flow, &trade.currency, base, &trade.date)
+                .map(|amount| converted + amount)
+        });
+    }
+    totals.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Action;
+    use rust_decimal_macros::dec;
+
+    fn rate(date: &str, from: &str, to: &str, rate: Decimal) -> FxRate {
+        FxRate {
+            date: date.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            rate,
+            source: "manual".to_string(),
+        }
+    }
+
+    #[test]
+    fn looks_up_rates_by_date_and_inverts_pairs() {
+        let rates = FxRates::new(&[
+            rate("2024-01-02", "EUR", "USD", dec!(1.10)),
+            rate("2024-03-01", "EUR", "USD", dec!(1.25)),
+        ]);
+        assert_eq!(rates.rate("EUR", "USD", "2024-02-15"), Some(dec!(1.10)));
+        assert_eq!(rates.rate("EUR", "USD", "2024-03-01"), Some(dec!(1.25)));
+        // Before the first rate, the first one is used.
+        assert_eq!(rates.rate("EUR", "USD", "2023-12-29"), Some(dec!(1.10)));
+        assert_eq!(
+            rates.convert(dec!(500), "USD", "EUR", "2024-03-04"),
+            Some(dec!(400))
+        );
+        assert_eq!(rates.rate("GBP", "GBP", "2024-01-02"), Some(Decimal::ONE));
+        assert_eq!(rates.rate("GBP", "USD", "2024-01-02"), None);
+        assert_eq!(parse_currency(" eur ").unwrap(), "EUR");
+        assert!(parse_currency("EURO").is_err());
+    }
+
+    #[test]
+    fn converts_each_trade_at_its_dates_rate() {
+        let trade = |currency: &str, action: Action, price: Decimal, date: &str| Trade {
+            symbol: "SAP".to_string(),
+            action,
+            price,
+            quantity: dec!(10),
+            date: date.to_string(),
+            fees: dec!(1),
+            currency: currency.to_string(),
+            ..Default::default()
+        };
+        let trades = vec![
+            trade("USD", Action::BuyToOpen, dec!(100), "2024-01-05"),
+            trade("USD", Action::SellToClose, dec!(110), "2024-03-05"),
+            trade("EUR", Action::BuyToOpen, dec!(50), "2024-01-05"),
+            trade("GBP", Action::BuyToOpen, dec!(20), "2024-01-05"),
+        ];
+        let rates = FxRates::new(&[
+            rate("2024-01-02", "EUR", "USD", dec!(1.25)),
+            rate("2024-03-01", "EUR", "USD", dec!(1.28)),
+        ]);
+        let totals = currency_totals(&trades, &rates, "EUR");
+        assert_eq!(
+            totals
+                .iter()
+                .map(|t| t.currency.as_str())
+                .collect::<Vec<_>>(),
+            ["EUR", "GBP", "USD"]
+        );
+        assert_eq!(totals[0].converted, Some(dec!(-501)));
+        assert_eq!(totals[1].converted, None);
+        // -1001 USD at 1.25, then +1099 USD at 1.28.
+        assert_eq!(totals[2].cash_flow, dec!(98));
+        assert_eq!(totals[2].fees, dec!(2));
+        assert_eq!(totals[2].converted, Some(dec!(57.79375)));
+    }
+}
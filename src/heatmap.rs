@@ -0,0 +1,145 @@
+//! Calendar heatmap of daily realized P/L.
+//!
+//! Each lot match adds its realized P/L to the day it closed. A year is laid
+//! out GitHub-style as columns of Monday-to-Sunday weeks, and each day with
+//! P/L is shaded by its size relative to the biggest day in view, so clusters
+//! of gains or losses — around expiration Fridays, say — stand out.
+
+use crate::cost_basis::LotMatch;
+use crate::date::{add_days, format_ymd, weekday, DateRange, MONTHS};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+pub const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// A day in the calendar and its realized P/L, `None` when nothing closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Day {
+    pub date: String,
+    pub pl: Option<Decimal>,
+}
+
+/// A week of the calendar, Monday first; days outside the year or the shown
+/// range are `None`.
+pub type Week = [Option<Day>; 7];
+
+/// Realized P/L of `matches` summed by close date.
+pub fn daily_pl(matches: &[LotMatch]) -> BTreeMap<String, Decimal> {
+    let mut by_date = BTreeMap::new();
+    for lot in matches {
+        *by_date.entry(lot.close_date.clone()).or_default() += lot.realized;
+    }
+    by_date
+}
+
+/// The weeks of `year` that overlap `range`, with each day's P/L from `daily`.
+pub fn year_weeks(daily: &BTreeMap<String, Decimal>, year: i64, range: &DateRange) -> Vec<Week> {
+    let first = format_ymd(year, 1, 1);
+    let last = format_ymd(year, 12, 31);
+    let Some(mut date) = weekday(&first).and_then(|day| add_days(&first, -day)) else {
+        return Vec::new();
+    };
+    let mut weeks = Vec::new();
+    while date <= last {
+        let mut week: Week = Default::default();
+        for slot in week.iter_mut() {
+            if date >= first && date <= last && range.contains(&date) {
+                *slot = Some(Day {
+                    date: date.clone(),
+                    pl: daily.get(&date).copied(),
+                });
+            }
+            date = add_days(&date, 1).unwrap_or_default();
+        }
+        if week.iter().any(Option::is_some) {
+            weeks.push(week);
+        }
+    }
+    weeks
+}
+
+/// How strongly to shade a day's P/L: 0 for a flat day, then 1 to 4 by its
+/// share of `largest`, the biggest gain or loss in view.
+pub fn intensity(pl: Decimal, largest: Decimal) -> u8 {
+    if pl.is_zero() || largest.is_zero() {
+        return 0;
+    }
+    let level = (pl.abs() * Decimal::from(4) / largest.abs()).ceil();
+    level
+        .clamp(Decimal::ONE, Decimal::from(4))
+        .try_into()
+        .unwrap_or(4)
+}
+
+/// Month names over the week columns, each `cell_width` wide, starting at the
+/// week holding the month's first day.
+pub fn month_header(weeks: &[Week], cell_width: usize) -> String {
+    let mut header = String::new();
+    for (column, week) in weeks.iter().enumerate() {
+        let first = week
+            .iter()
+            .flatten()
+            .find(|day| day.date.ends_with("-01") || column == 0);
+        if let Some(day) = first {
+            let month = day.date[5..7].parse::<usize>().unwrap_or(1);
+            let at = column * cell_width;
+            if header.chars().count() <= at {
+                header.push_str(&" ".repeat(at - header.chars().count()));
+                header.push_str(MONTHS[month - 1]);
+            }
+        }
+    }
+    header
+}
+
+/// Realized P/L of `daily` summed by day of the week, Monday first.
+pub fn weekday_totals(daily: &BTreeMap<String, Decimal>) -> [Decimal; 7] {
+    let mut totals = [Decimal::ZERO; 7];
+    for (date, pl) in daily {
+        if let Some(day) = weekday(date) {
+            totals[day as usize] += pl;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn lays_out_a_year_in_weeks() {
+        let daily = BTreeMap::from([
+            ("2024-01-05".to_string(), dec!(120)),
+            ("2024-01-19".to_string(), dec!(-40)),
+        ]);
+        let weeks = year_weeks(&daily, 2024, &DateRange::default());
+        // 2024-01-01 was a Monday and 2024-12-31 a Tuesday.
+        assert_eq!(weeks.len(), 53);
+        assert_eq!(weeks[0][0].as_ref().unwrap().date, "2024-01-01");
+        assert_eq!(weeks[0][4].as_ref().unwrap().pl, Some(dec!(120)));
+        assert_eq!(weeks[2][4].as_ref().unwrap().pl, Some(dec!(-40)));
+        assert_eq!(weeks[1][4].as_ref().unwrap().pl, None);
+        assert!(weeks[52][2].is_none());
+
+        let range = DateRange {
+            start: Some("2024-12-01".to_string()),
+            end: None,
+        };
+        let december = year_weeks(&daily, 2024, &range);
+        assert_eq!(december.len(), 6);
+        assert!(december[0][5].is_none());
+        assert_eq!(month_header(&december, 2), "Dec");
+
+        assert_eq!(weekday_totals(&daily)[4], dec!(80));
+    }
+
+    #[test]
+    fn shades_by_share_of_the_largest_day() {
+        assert_eq!(intensity(dec!(120), dec!(120)), 4);
+        assert_eq!(intensity(dec!(-40), dec!(120)), 2);
+        assert_eq!(intensity(dec!(1), dec!(120)), 1);
+        assert_eq!(intensity(Decimal::ZERO, dec!(120)), 0);
+    }
+}
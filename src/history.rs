@@ -0,0 +1,136 @@
+//! Reading the trade history log.
+//!
+//! Triggers write a row to `trade_history` for every insert, update, and
+//! delete of a trade and every tag added or removed, holding the trade's
+//! columns before and after as JSON. This turns a row back into what changed:
+//! a one-line summary and the fields whose values differ.
+
+use crate::db::{HistoryChange, HistoryEntry};
+use serde_json::{Map, Value};
+
+/// A column whose value a change set, altered, or cleared. Blank values are
+/// empty strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// The fields `entry` changed, by column name. An insert lists the fields it
+/// set and a delete those the trade had; tag changes list none.
+pub fn field_changes(entry: &HistoryEntry) -> Vec<FieldChange> {
+    if matches!(
+        entry.change,
+        HistoryChange::TagAdded | HistoryChange::TagRemoved
+    ) {
+        return Vec::new();
+    }
+    let before = object(entry.before.as_deref());
+    let after = object(entry.after.as_deref());
+    let fields = if after.is_empty() { &before } else { &after };
+    fields
+        .keys()
+        .map(|field| FieldChange {
+            field: field.clone(),
+            before: text(before.get(field)),
+            after: text(after.get(field)),
+        })
+        .filter(|change| change.before != change.after)
+        .collect()
+}
+
+/// What `entry` did, e.g. `edited price, quantity` or `tagged hedge`.
+pub fn summary(entry: &HistoryEntry) -> String {
+    match entry.change {
+        HistoryChange::Insert => "added".to_string(),
+        HistoryChange::Delete => "deleted for good".to_string(),
+        HistoryChange::TagAdded => format!("tagged {}", tag(entry.after.as_deref())),
+        HistoryChange::TagRemoved => format!("untagged {}", tag(entry.before.as_deref())),
+        HistoryChange::Update => {
+            let changes = field_changes(entry);
+            match changes.as_slice() {
+                [only] if only.field == "deleted_at" && only.after.is_empty() => {
+                    "restored from the Trash".to_string()
+                }
+                [only] if only.field == "deleted_at" => "moved to the Trash".to_string(),
+                _ => {
+                    let fields: Vec<&str> = changes.iter().map(|c| c.field.as_str()).collect();
+                    format!("edited {}", fields.join(", "))
+                }
+            }
+        }
+    }
+}
+
+// The JSON object in `json`, empty when there is none.
+fn object(json: Option<&str>) -> Map<String, Value> {
+    json.and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+fn text(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn tag(json: Option<&str>) -> String {
+    text(object(json).get("tag"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, Database, Trade};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn logs_and_describes_every_change() {
+        let db = Database::new(":memory:").unwrap();
+        let id = db
+            .add_trade(&Trade {
+                symbol: "AAPL".to_string(),
+                action: Action::BuyToOpen,
+                price: dec!(150),
+                quantity: dec!(10),
+                date: "2024-01-05".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        let mut trade = db.get_trade(id).unwrap().unwrap();
+        trade.price = dec!(151.25);
+        trade.tags = vec!["hedge".to_string()];
+        db.update_trade(&trade).unwrap();
+        // Saving again unchanged logs nothing.
+        db.update_trade(&trade).unwrap();
+        db.delete_trade(id).unwrap();
+        db.restore_trade(id).unwrap();
+
+        let history = db.get_trade_history(id).unwrap();
+        let summaries: Vec<String> = history.iter().map(summary).collect();
+        assert_eq!(
+            summaries,
+            vec![
+                "added",
+                "edited price",
+                "tagged hedge",
+                "moved to the Trash",
+                "restored from the Trash",
+            ]
+        );
+        assert_eq!(
+            field_changes(&history[1]),
+            vec![FieldChange {
+                field: "price".to_string(),
+                before: "150".to_string(),
+                after: "151.25".to_string(),
+            }]
+        );
+        assert!(field_changes(&history[0])
+            .iter()
+            .any(|c| c.field == "symbol" && c.before.is_empty() && c.after == "AAPL"));
+    }
+}
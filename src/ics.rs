@@ -0,0 +1,151 @@
+//! Upcoming option expirations as an iCalendar (.ics) file.
+//!
+//! Each expiration date with open contracts becomes one all-day event whose
+//! description lists the positions expiring that day. Event UIDs depend only
+//! on the date, so importing a newer export updates the same events instead
+//! of adding duplicates.
+
+use crate::date::{add_days, format_timestamp};
+use crate::db::TradeType;
+use crate::positions::Position;
+
+// Longest content line, in octets, before it is folded (RFC 5545 3.1).
+const LINE_LIMIT: usize = 75;
+
+/// The calendar of option positions in `positions` expiring on or after
+/// `today`, stamped with the Unix time `now`. Lines end in CRLF.
+pub fn expiration_calendar(positions: &[Position], today: &str, now: i64) -> String {
+    let mut options: Vec<(&str, &Position)> = positions
+        .iter()
+        .filter(|p| p.key.trade_type == TradeType::Option)
+        .filter_map(|p| Some((p.key.expiration.as_deref()?, p)))
+        .filter(|(expiration, _)| *expiration >= today)
+        .collect();
+    options.sort_by(|a, b| {
+        a.0.cmp(b.0)
+            .then_with(|| a.1.key.symbol.cmp(&b.1.key.symbol))
+    });
+
+    let stamp = format_timestamp(now)
+        .replace(['-', ':'], "")
+        .replace(' ', "T")
+        + "Z";
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//options_tracker//Expirations//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+    ];
+    let mut rest = options.as_slice();
+    while let Some(&(expiration, _)) = rest.first() {
+        let count = rest.iter().take_while(|(e, _)| *e == expiration).count();
+        let (day, later) = rest.split_at(count);
+        rest = later;
+
+        let mut symbols: Vec<&str> = day.iter().map(|(_, p)| p.key.symbol.as_str()).collect();
+        symbols.dedup();
+        let details: Vec<String> = day.iter().map(|(_, p)| describe(p)).collect();
+        let date = expiration.replace('-', "");
+        let next = add_days(expiration, 1).unwrap_or_default().replace('-', "");
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:expiration-{}@options_tracker", date),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", date),
+            format!("DTEND;VALUE=DATE:{}", next),
+            format!(
+                "SUMMARY:{}",
+                escape(&format!("{} options expire", symbols.join(", ")))
+            ),
+            format!("DESCRIPTION:{}", escape(&details.join("\n"))),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+// `Short 2 AAPL $180 put, opened 2024-01-05 at $1.25`.
+fn describe(position: &Position) -> String {
+    let key = &position.key;
+    format!(
+        "{} {} {} ${} {}, opened {} at ${:.2}",
+        if position.is_short() { "Short" } else { "Long" },
+        position.quantity.abs().normalize(),
+        key.symbol,
+        key.strike.unwrap_or_default().normalize(),
+        key.option_type.map(|t| t.as_str()).unwrap_or_default(),
+        position.opened,
+        position.average_cost
+    )
+}
+
+// Escapes text property values: backslashes, commas, semicolons, and
+// newlines.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+// Splits a line longer than the limit into continuation lines, each starting
+// with a space, without breaking a UTF-8 character.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionType, Trade};
+    use crate::positions::open_positions;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn writes_an_event_per_expiration() {
+        let option = |symbol: &str, action, strike, expiration: &str, quantity| Trade {
+            symbol: symbol.to_string(),
+            trade_type: TradeType::Option,
+            action,
+            option_type: Some(OptionType::Put),
+            strike: Some(strike),
+            expiration: Some(expiration.to_string()),
+            price: dec!(1.25),
+            quantity,
+            date: "2024-01-05".to_string(),
+            ..Default::default()
+        };
+        let trades = vec![
+            option("AAPL", Action::SellToOpen, dec!(180), "2024-02-16", dec!(2)),
+            option("KO", Action::BuyToOpen, dec!(55), "2024-02-16", dec!(1)),
+            option("MSFT", Action::SellToOpen, dec!(400), "2024-03-15", dec!(1)),
+            option("SPY", Action::SellToOpen, dec!(450), "2024-01-19", dec!(1)),
+        ];
+        let positions = open_positions(&trades, "2024-02-01");
+        let text = expiration_calendar(&positions, "2024-02-01", 1_706_745_600);
+
+        assert!(text.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(text.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(text.matches("BEGIN:VEVENT").count(), 2);
+        assert!(!text.contains("SPY"));
+        assert!(text.contains(
+            "UID:expiration-20240216@options_tracker\r\nDTSTAMP:20240201T000000Z\r\n\
+             DTSTART;VALUE=DATE:20240216\r\nDTEND;VALUE=DATE:20240217\r\n\
+             SUMMARY:AAPL\\, KO options expire\r\n"
+        ));
+        assert!(text.contains("DESCRIPTION:Short 2 AAPL $180 put\\, opened 2024-01-05 at $1.25\\n"));
+        assert!(text.lines().all(|line| line.len() <= LINE_LIMIT));
+    }
+}
@@ -0,0 +1,557 @@
+//! Trade import from arbitrary CSV files.
+//!
+//! Brokers and spreadsheets lay their exports out differently, so nothing is
+//! assumed about the columns: each is mapped to a [`TradeField`] or skipped.
+//! [`guess_mapping`] proposes a mapping from the header row and the user
+//! corrects it before anything is saved. Values are read leniently — `$` and
+//! thousands separators are dropped, `MM/DD/YYYY` dates are accepted, and
+//! actions may be abbreviated (`STO`, `BTC`, ...) — and a row that still can't
+//! be read is reported with the reason rather than imported.
+
+use crate::date::{format_ymd, parse_unix_day};
+use crate::db::{
+    Action, Greeks, OptionStatus, OptionType, Strategy, Trade, TradeType, DEFAULT_CURRENCY,
+};
+use crate::fx::parse_currency;
+use rust_decimal::Decimal;
+use rust_decimal_macros::dec;
+use serde_json::{Map, Value};
+
+string_enum! {
+    /// A trade field a CSV column can be mapped to.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TradeField {
+        Symbol => "symbol",
+        TradeType => "trade_type",
+        Action => "action",
+        Price => "price",
+        Quantity => "quantity",
+        Date => "date",
+        Fees => "fees",
+        Comment => "comment",
+        OptionType => "option_type",
+        Strike => "strike",
+        Expiration => "expiration",
+        Multiplier => "multiplier",
+        Currency => "currency",
+        Broker => "broker",
+        /// An option's status; values that aren't one, such as an order's
+        /// `Filled`, leave it open.
+        Status => "status",
+        /// Tags separated by commas.
+        Tags => "tags",
+        Delta => "delta",
+        Gamma => "gamma",
+        Theta => "theta",
+        Vega => "vega",
+        Iv => "iv",
+    }
+    error = "trade field",
+}
+
+impl TradeField {
+    // Header names, squashed to lowercase letters, that suggest the field.
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            TradeField::Symbol => &["symbol", "ticker", "underlying", "instrument"],
+            TradeField::TradeType => &["tradetype", "type", "assettype", "securitytype"],
+            TradeField::Action => &["action", "side", "transaction", "buysell"],
+            TradeField::Price => &["price", "fillprice", "tradeprice"],
+            TradeField::Quantity => &["quantity", "qty", "shares", "contracts"],
+            TradeField::Date => &["date", "tradedate", "executiondate", "filldate"],
+            TradeField::Fees => &["fees", "fee", "commission", "commissions"],
+            TradeField::Comment => &["comment", "comments", "note", "notes", "memo"],
+            TradeField::OptionType => &["optiontype", "putcall", "callput", "right"],
+            TradeField::Strike => &["strike", "strikeprice"],
+            TradeField::Expiration => &["expiration", "expiry", "exp", "expirationdate"],
+            TradeField::Multiplier => &["multiplier", "contractmultiplier"],
+            TradeField::Currency => &["currency", "ccy", "tradecurrency"],
+            TradeField::Broker => &["broker", "brokerage"],
+            TradeField::Status => &["status", "optionstatus"],
+            TradeField::Tags => &["tags", "tag", "labels"],
+            TradeField::Delta => &["delta"],
+            TradeField::Gamma => &["gamma"],
+            TradeField::Theta => &["theta"],
+            TradeField::Vega => &["vega"],
+            TradeField::Iv => &["iv", "impliedvolatility", "impliedvol"],
+        }
+    }
+}
+
+/// Which field each CSV column holds, `None` for a skipped column.
+pub type Mapping = Vec<Option<TradeField>>;
+
+/// Splits RFC 4180 CSV text into rows of fields, dropping blank lines.
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                let line = std::mem::take(&mut row);
+                if line.iter().any(|f| !f.trim().is_empty()) {
+                    rows.push(line);
+                }
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    row.push(field);
+    if row.iter().any(|f| !f.trim().is_empty()) {
+        rows.push(row);
+    }
+    rows
+}
+
+/// Splits text pasted from a broker web page or a spreadsheet into rows.
+/// Copying a table puts a tab between cells, so a first line with a tab is
+/// read as tab-separated and anything else as CSV. Fields are trimmed and
+/// blank lines dropped.
+pub fn parse_pasted(text: &str) -> Vec<Vec<String>> {
+    let tabbed = text
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .is_some_and(|line| line.contains('\t'));
+    let rows = if tabbed {
+        text.lines()
+            .map(|line| line.split('\t').map(str::to_string).collect())
+            .collect()
+    } else {
+        parse_csv(text)
+    };
+    rows.into_iter()
+        .map(|row: Vec<String>| row.iter().map(|f| f.trim().to_string()).collect())
+        .filter(|row: &Vec<String>| row.iter().any(|f| !f.is_empty()))
+        .collect()
+}
+
+/// Reads a JSON array of objects, as [`crate::export::Table::to_json`]
+/// writes trades, into a header row of the keys, in the order first seen,
+/// and a row of fields per object. Numbers are written out and `null` is
+/// blank, so the rows map and read like a CSV's.
+pub fn parse_json(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let objects: Vec<Map<String, Value>> =
+        serde_json::from_str(text).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let mut headers: Vec<String> = Vec::new();
+    for key in objects.iter().flat_map(|object| object.keys()) {
+        if !headers.contains(key) {
+            headers.push(key.clone());
+        }
+    }
+    let rows = objects.iter().map(|object| {
+        headers
+            .iter()
+            .map(|header| match object.get(header) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(text)) => text.clone(),
+                Some(value) => value.to_string(),
+            })
+            .collect()
+    });
+    Ok(std::iter::once(headers.clone()).chain(rows).collect())
+}
+
+/// A mapping guessed from the header row; unrecognized columns are skipped,
+/// and no field is mapped twice.
+pub fn guess_mapping(headers: &[String]) -> Mapping {
+    let mut mapping: Mapping = Vec::with_capacity(headers.len());
+    for header in headers {
+        let squashed: String = header
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        let field = TradeField::variants()
+            .iter()
+            .copied()
+            .find(|field| field.aliases().contains(&squashed.as_str()))
+            .filter(|field| !mapping.contains(&Some(*field)));
+        mapping.push(field);
+    }
+    mapping
+}
+
+/// Reads one CSV row into a trade under `mapping`.
+pub fn parse_row(row: &[String], mapping: &[Option<TradeField>]) -> Result<Trade, String> {
+    let get = |wanted: TradeField| -> Option<&str> {
+        mapping
+            .iter()
+            .position(|field| *field == Some(wanted))
+            .and_then(|column| row.get(column))
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+    let required = |field: TradeField| get(field).ok_or_else(|| format!("no {}", field));
+
+    let option_type = get(TradeField::OptionType)
+        .map(parse_option_type)
+        .transpose()?;
+    let strike = get(TradeField::Strike)
+        .map(|v| parse_number(v, "strike"))
+        .transpose()?;
+    let trade_type = match get(TradeField::TradeType) {
+        Some(value) => parse_trade_type(value)?,
+        None if option_type.is_some() || strike.is_some() => TradeType::Option,
+        None => TradeType::Stock,
+    };
+    let is_option = trade_type == TradeType::Option;
+    let number = |field: TradeField| {
+        get(field)
+            .map(|v| parse_number(v, field.as_str()))
+            .transpose()
+    };
+    let trade = Trade {
+        symbol: required(TradeField::Symbol)?.to_uppercase(),
+        trade_type,
+        action: parse_action(required(TradeField::Action)?)?,
+        price: parse_number(required(TradeField::Price)?, "price")?.abs(),
+        quantity: parse_number(required(TradeField::Quantity)?, "quantity")?.abs(),
+        date: parse_date(required(TradeField::Date)?)?,
+        fees: get(TradeField::Fees)
+            .map(|v| parse_number(v, "fees"))
+            .transpose()?
+            .unwrap_or_default()
+            .abs(),
+        comment: get(TradeField::Comment).unwrap_or_default().to_string(),
+        option_type: option_type.filter(|_| is_option),
+        strike: strike.filter(|_| is_option),
+        expiration: get(TradeField::Expiration)
+            .filter(|_| is_option)
+            .map(parse_date)
+            .transpose()?,
+        status: is_option.then(|| {
+            get(TradeField::Status)
+                .and_then(|v| v.to_lowercase().parse().ok())
+                .unwrap_or(OptionStatus::Open)
+        }),
+        contract_multiplier: get(TradeField::Multiplier)
+            .filter(|_| is_option)
+            .map(|v| parse_number(v, "multiplier"))
+            .transpose()?,
+        currency: match get(TradeField::Currency) {
+            Some(value) => parse_currency(value)?,
+            None => DEFAULT_CURRENCY.to_string(),
+        },
+        broker: get(TradeField::Broker).map(str::to_string),
+        tags: get(TradeField::Tags)
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        greeks: Greeks {
+            delta: number(TradeField::Delta)?,
+            gamma: number(TradeField::Gamma)?,
+            theta: number(TradeField::Theta)?,
+            vega: number(TradeField::Vega)?,
+            iv: number(TradeField::Iv)?,
+        },
+        ..Default::default()
+    };
+    if trade.quantity.is_zero() {
+        return Err("quantity is zero".to_string());
+    }
+    if is_option && (trade.option_type.is_none() || trade.strike.is_none()) {
+        return Err("option without type or strike".to_string());
+    }
+    if is_option && trade.expiration.is_none() {
+        return Err("option without expiration".to_string());
+    }
+    Ok(trade)
+}
+
+/// What was read from a file.
+#[derive(Debug, Clone, Default)]
+pub struct ReadResult {
+    pub trades: Vec<Trade>,
+    /// The number (the first row being 1) and reason of each row that
+    /// couldn't be read; an OFX statement numbers its transactions instead.
+    pub errors: Vec<(usize, String)>,
+    /// Multi-leg orders kept together: a strategy and the indexes of its legs
+    /// in `trades`.
+    pub strategies: Vec<(Strategy, Vec<usize>)>,
+}
+
+impl ReadResult {
+    /// Drops the trades whose `skip` flag is set, renumbering the legs of the
+    /// strategies; a strategy left without legs is dropped too.
+    pub fn without(self, skip: &[bool]) -> ReadResult {
+        let mut renumbered = Vec::with_capacity(self.trades.len());
+        let mut trades = Vec::with_capacity(self.trades.len());
+        for (index, trade) in self.trades.into_iter().enumerate() {
+            if skip.get(index).copied().unwrap_or(false) {
+                renumbered.push(None);
+            } else {
+                renumbered.push(Some(trades.len()));
+                trades.push(trade);
+            }
+        }
+        let strategies = self
+            .strategies
+            .into_iter()
+            .filter_map(|(strategy, legs)| {
+                let legs: Vec<usize> = legs.iter().filter_map(|&leg| renumbered[leg]).collect();
+                (!legs.is_empty()).then_some((strategy, legs))
+            })
+            .collect();
+        ReadResult {
+            trades,
+            errors: self.errors,
+            strategies,
+        }
+    }
+}
+
+/// Largest price difference still taken for the same fill: half a cent.
+const PRICE_TOLERANCE: Decimal = dec!(0.005);
+/// Largest quantity difference still taken for the same fill, leaving room
+/// for fractional shares rounded differently.
+const QUANTITY_TOLERANCE: Decimal = dec!(0.0001);
+
+/// For each of `trades`, the id of an `existing` trade it repeats, if any:
+/// the same symbol, date, action, and contract, with price and quantity
+/// within a small tolerance. Each existing trade is matched at most once, so
+/// two identical fills in a file against one saved are one duplicate.
+pub fn find_duplicates(trades: &[Trade], existing: &[Trade]) -> Vec<Option<i64>> {
+    let mut unmatched: Vec<&Trade> = existing.iter().collect();
+    trades
+        .iter()
+        .map(|trade| {
+            // Imported trades have no account yet, so only the instrument is
+            // compared.
+            let key = trade.position_key();
+            let at = unmatched.iter().position(|saved| {
+                saved.date == trade.date
+                    && saved.action == trade.action
+                    && saved.position_key().same_instrument(&key)
+                    && (saved.price - trade.price).abs() <= PRICE_TOLERANCE
+                    && (saved.quantity - trade.quantity).abs() <= QUANTITY_TOLERANCE
+            })?;
+            unmatched.remove(at).id
+        })
+        .collect()
+}
+
+/// Reads every row of `records` after the header row under `mapping`.
+pub fn read_trades(records: &[Vec<String>], mapping: &[Option<TradeField>]) -> ReadResult {
+    let mut result = ReadResult::default();
+    for (index, row) in records.iter().enumerate().skip(1) {
+        match parse_row(row, mapping) {
+            Ok(trade) => result.trades.push(trade),
+            Err(e) => result.errors.push((index + 1, e)),
+        }
+    }
+    result
+}
+
+pub(crate) fn parse_number(raw: &str, what: &str) -> Result<Decimal, String> {
+    let cleaned: String = raw.chars().filter(|c| !matches!(c, '$' | ',')).collect();
+    // Accountants write negatives in parentheses.
+    let cleaned = match cleaned.strip_prefix('(').and_then(|c| c.strip_suffix(')')) {
+        Some(inner) => format!("-{}", inner),
+        None => cleaned,
+    };
+    cleaned
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} \"{}\" is not a number", what, raw))
+}
+
+// `YYYY-MM-DD`, or `M/D/YYYY` as US brokers write it.
+pub(crate) fn parse_date(raw: &str) -> Result<String, String> {
+    let invalid = || format!("date \"{}\" is not YYYY-MM-DD or MM/DD/YYYY", raw);
+    // Drop a time, as in `01/05/2024 10:31` or `2024-01-05T10:31:02-0500`.
+    let date = raw
+        .split(|c: char| c.is_whitespace() || c == 'T')
+        .next()
+        .unwrap_or_default();
+    let iso = match date.split('/').collect::<Vec<_>>()[..] {
+        [month, day, year] => {
+            let year: i64 = year.parse().map_err(|_| invalid())?;
+            let year = if year < 100 { 2000 + year } else { year };
+            format_ymd(
+                year,
+                month.parse().map_err(|_| invalid())?,
+                day.parse().map_err(|_| invalid())?,
+            )
+        }
+        _ => date.to_string(),
+    };
+    parse_unix_day(&iso).map(|_| iso).ok_or_else(invalid)
+}
+
+pub(crate) fn parse_action(raw: &str) -> Result<Action, String> {
+    let squashed: String = raw
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    match squashed.as_str() {
+        "bto" | "buytoopen" | "buy" | "bought" => Ok(Action::BuyToOpen),
+        "sto" | "selltoopen" | "sellshort" => Ok(Action::SellToOpen),
+        "btc" | "buytoclose" | "buytocover" => Ok(Action::BuyToClose),
+        "stc" | "selltoclose" | "sell" | "sold" => Ok(Action::SellToClose),
+        _ => Err(format!("unknown action \"{}\"", raw)),
+    }
+}
+
+fn parse_trade_type(raw: &str) -> Result<TradeType, String> {
+    match raw.to_lowercase().as_str() {
+        "stock" | "equity" | "stk" | "etf" => Ok(TradeType::Stock),
+        "option" | "options" | "opt" => Ok(TradeType::Option),
+        _ => Err(format!("unknown trade type \"{}\"", raw)),
+    }
+}
+
+fn parse_option_type(raw: &str) -> Result<OptionType, String> {
+    match raw.to_lowercase().as_str() {
+        "call" | "c" => Ok(OptionType::Call),
+        "put" | "p" => Ok(OptionType::Put),
+        _ => Err(format!("unknown option type \"{}\"", raw)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn parses_quoted_csv() {
+        let rows = parse_csv("Symbol,Note\r\nAAPL,\"sold \"\"high\"\", twice\"\n\nKO,\n");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Symbol", "Note"],
+                vec!["AAPL", "sold \"high\", twice"],
+                vec!["KO", ""],
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_pasted_tables() {
+        let rows = parse_pasted("Date\tSymbol\t Price \r\n\r\n01/05/2024\tAAPL\t$1,234.50\n");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["Date", "Symbol", "Price"],
+                vec!["01/05/2024", "AAPL", "$1,234.50"],
+            ]
+        );
+        let rows = parse_pasted("Date, Symbol\n01/05/2024, AAPL\n");
+        assert_eq!(
+            rows,
+            vec![vec!["Date", "Symbol"], vec!["01/05/2024", "AAPL"]]
+        );
+    }
+
+    #[test]
+    fn maps_columns_and_reads_rows() {
+        let rows = parse_csv(
+            "Trade Date,Ticker,Side,Qty,Price,Commission,Put/Call,Strike,Expiry,Account,Status,Tags,Delta\n\
+             01/05/2024,aapl,STO,-2,$1.25,1.30,P,180,02/16/2024,IRA,Expired,\"wheel, aapl\",-0.3\n\
+             2024-01-08,MSFT,Buy,10,\"1,050.00\",0,,,,IRA,Filled,,\n\
+             2024-01-09,MSFT,hold,10,400,0,,,,IRA,,,\n",
+        );
+        let mapping = guess_mapping(&rows[0]);
+        assert_eq!(
+            mapping,
+            vec![
+                Some(TradeField::Date),
+                Some(TradeField::Symbol),
+                Some(TradeField::Action),
+                Some(TradeField::Quantity),
+                Some(TradeField::Price),
+                Some(TradeField::Fees),
+                Some(TradeField::OptionType),
+                Some(TradeField::Strike),
+                Some(TradeField::Expiration),
+                None,
+                Some(TradeField::Status),
+                Some(TradeField::Tags),
+                Some(TradeField::Delta),
+            ]
+        );
+
+        let put = parse_row(&rows[1], &mapping).unwrap();
+        assert_eq!(put.symbol, "AAPL");
+        assert_eq!(put.trade_type, TradeType::Option);
+        assert_eq!(put.action, Action::SellToOpen);
+        assert_eq!(put.quantity, dec!(2));
+        assert_eq!(put.date, "2024-01-05");
+        assert_eq!(put.expiration.as_deref(), Some("2024-02-16"));
+        assert_eq!(put.status, Some(OptionStatus::Expired));
+        assert_eq!(put.tags, ["wheel", "aapl"]);
+        assert_eq!(put.greeks.delta, Some(dec!(-0.3)));
+
+        let stock = parse_row(&rows[2], &mapping).unwrap();
+        assert_eq!(stock.trade_type, TradeType::Stock);
+        assert_eq!(stock.price, dec!(1050.00));
+        assert_eq!(stock.status, None);
+
+        assert_eq!(
+            parse_row(&rows[3], &mapping).unwrap_err(),
+            "unknown action \"hold\""
+        );
+        let read = read_trades(&rows, &mapping);
+        assert_eq!(read.trades.len(), 2);
+        assert_eq!(
+            read.errors,
+            vec![(4, "unknown action \"hold\"".to_string())]
+        );
+
+        let mut unmapped = mapping.clone();
+        unmapped[0] = None;
+        assert_eq!(parse_row(&rows[2], &unmapped).unwrap_err(), "no date");
+    }
+
+    #[test]
+    fn flags_trades_already_saved() {
+        let rows = parse_csv(
+            "Date,Symbol,Action,Quantity,Price,Put/Call,Strike,Expiration\n\
+             2024-01-05,AAPL,STO,2,1.25,P,180,2024-02-16\n\
+             2024-01-05,AAPL,STO,2,1.25,P,180,2024-02-16\n\
+             2024-01-05,AAPL,STO,2,1.25,P,175,2024-02-16\n\
+             2024-01-08,VOO,Buy,0.12345,435.124,,,\n",
+        );
+        let read = read_trades(&rows, &guess_mapping(&rows[0]));
+        let mut saved = read.trades.clone();
+        saved[0].id = Some(7);
+        saved[3].id = Some(9);
+        saved[3].quantity = dec!(0.1235);
+        saved[3].price = dec!(435.12);
+        let existing = vec![saved[0].clone(), saved[3].clone()];
+        let duplicates = find_duplicates(&read.trades, &existing);
+        assert_eq!(duplicates, vec![Some(7), None, None, Some(9)]);
+
+        let order = Strategy {
+            id: None,
+            name: "AAPL order".to_string(),
+            kind: crate::db::StrategyKind::Custom,
+        };
+        let read = ReadResult {
+            strategies: vec![(order.clone(), vec![0, 2]), (order, vec![3])],
+            ..read
+        };
+        let kept = read.without(&[true, false, false, true]);
+        assert_eq!(kept.trades.len(), 2);
+        assert_eq!(kept.strategies.len(), 1);
+        assert_eq!(kept.strategies[0].1, vec![1]);
+    }
+}
@@ -0,0 +1,255 @@
+//! Keys bound to actions on every screen.
+//!
+//! The defaults add vim-style movement to the arrow keys — `j`/`k`/`h`/`l`,
+//! `gg` for the top and `G` for the bottom — and the undo and redo keys. The
+//! `[keys]` table of the settings file (see [`crate::config`]) replaces the
+//! keys of any action. A key is written as a character (`j`, `G`), a named key
+//! (`down`, `pagedown`, `esc`), or `ctrl-` and a letter; a binding may be a
+//! sequence of keys, such as `gg`.
+//!
+//! Keys only act when the focused view has no use for them, so typing into a
+//! text field is never taken for movement.
+
+use cursive::event::{Event, Key};
+
+string_enum! {
+    /// What a bound key does.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum KeyAction {
+        Down => "down",
+        Up => "up",
+        Left => "left",
+        Right => "right",
+        Top => "top",
+        Bottom => "bottom",
+        PageDown => "page_down",
+        PageUp => "page_up",
+        Undo => "undo",
+        Redo => "redo",
+        /// Keys and fields of the screen on top.
+        Help => "help",
+    }
+    error = "key action",
+}
+
+impl KeyAction {
+    /// The key a movement stands in for; `None` for the other actions.
+    pub fn key(&self) -> Option<Key> {
+        match self {
+            KeyAction::Down => Some(Key::Down),
+            KeyAction::Up => Some(Key::Up),
+            KeyAction::Left => Some(Key::Left),
+            KeyAction::Right => Some(Key::Right),
+            KeyAction::Top => Some(Key::Home),
+            KeyAction::Bottom => Some(Key::End),
+            KeyAction::PageDown => Some(Key::PageDown),
+            KeyAction::PageUp => Some(Key::PageUp),
+            KeyAction::Undo | KeyAction::Redo | KeyAction::Help => None,
+        }
+    }
+
+    /// What the action does, for the help screen.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            KeyAction::Down => "Move down",
+            KeyAction::Up => "Move up",
+            KeyAction::Left => "Move left",
+            KeyAction::Right => "Move right",
+            KeyAction::Top => "Jump to the top",
+            KeyAction::Bottom => "Jump to the bottom",
+            KeyAction::PageDown => "Page down",
+            KeyAction::PageUp => "Page up",
+            KeyAction::Undo => "Undo the last edit or delete",
+            KeyAction::Redo => "Redo what was undone",
+            KeyAction::Help => "Show this help",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            KeyAction::Down => &["j"],
+            KeyAction::Up => &["k"],
+            KeyAction::Left => &["h"],
+            KeyAction::Right => &["l"],
+            KeyAction::Top => &["gg"],
+            KeyAction::Bottom => &["G"],
+            KeyAction::PageDown | KeyAction::PageUp => &[],
+            KeyAction::Undo => &["ctrl-z", "u"],
+            KeyAction::Redo => &["ctrl-y"],
+            KeyAction::Help => &["?"],
+        }
+    }
+}
+
+// Keys written by name.
+const NAMED_KEYS: [(&str, Key); 12] = [
+    ("up", Key::Up),
+    ("down", Key::Down),
+    ("left", Key::Left),
+    ("right", Key::Right),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("esc", Key::Esc),
+    ("tab", Key::Tab),
+    ("enter", Key::Enter),
+    ("backspace", Key::Backspace),
+];
+
+/// Reads a binding: a named key, `ctrl-` and a letter, or else one key per
+/// character, so `gg` is `g` twice.
+pub fn parse_keys(text: &str) -> Result<Vec<Event>, String> {
+    let lower = text.to_lowercase();
+    if let Some((_, key)) = NAMED_KEYS.iter().find(|(name, _)| *name == lower) {
+        return Ok(vec![Event::Key(*key)]);
+    }
+    if let Some(letter) = lower.strip_prefix("ctrl-") {
+        let mut chars = letter.chars();
+        return match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_lowercase() => Ok(vec![Event::CtrlChar(c)]),
+            _ => Err(format!("Invalid key: {}", text)),
+        };
+    }
+    if text.is_empty() || text.chars().any(char::is_whitespace) {
+        return Err(format!("Invalid key: {}", text));
+    }
+    Ok(text.chars().map(Event::Char).collect())
+}
+
+/// How a binding reads on screen: `gg`, `Ctrl+Z`, `PageDown`.
+pub fn key_label(keys: &[Event]) -> String {
+    keys.iter()
+        .map(|key| match key {
+            Event::Char(c) => c.to_string(),
+            Event::CtrlChar(c) => format!("Ctrl+{}", c.to_ascii_uppercase()),
+            Event::Key(key) => format!("{:?}", key),
+            other => format!("{:?}", other),
+        })
+        .collect()
+}
+
+/// Every binding, in the order actions are declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(Vec<Event>, KeyAction)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let mut keymap = Keymap {
+            bindings: Vec::new(),
+        };
+        for action in KeyAction::variants() {
+            let keys = action.default_keys().iter();
+            let keys = keys.map(|keys| parse_keys(keys).expect("default keys parse"));
+            keymap.bind(*action, keys.collect());
+        }
+        keymap
+    }
+}
+
+impl Keymap {
+    /// Replaces the keys of `action` with `keys`; none unbinds it.
+    pub fn bind(&mut self, action: KeyAction, keys: Vec<Vec<Event>>) {
+        self.bindings.retain(|(_, bound)| *bound != action);
+        self.bindings
+            .extend(keys.into_iter().map(|keys| (keys, action)));
+    }
+
+    /// Every key a binding uses, each once.
+    pub fn keys(&self) -> Vec<Event> {
+        let mut used: Vec<Event> = Vec::new();
+        for key in self.bindings.iter().flat_map(|(keys, _)| keys) {
+            if !used.contains(key) {
+                used.push(key.clone());
+            }
+        }
+        used
+    }
+
+    /// Adds `event` to the keys pressed so far and returns the action they
+    /// complete. `pending` keeps a partly typed sequence and is cleared when
+    /// it completes or can no longer match; a key that breaks a sequence may
+    /// still start a new one.
+    pub fn feed(&self, pending: &mut Vec<Event>, event: Event) -> Option<KeyAction> {
+        pending.push(event.clone());
+        if !self.is_prefix(pending) {
+            pending.clear();
+            pending.push(event);
+        }
+        let action = self
+            .bindings
+            .iter()
+            .find(|(keys, _)| keys == pending)
+            .map(|(_, action)| *action);
+        if action.is_some() || !self.is_prefix(pending) {
+            pending.clear();
+        }
+        action
+    }
+
+    /// Each bound action with its keys, in declaration order.
+    pub fn help(&self) -> Vec<(KeyAction, String)> {
+        KeyAction::variants()
+            .iter()
+            .filter_map(|action| {
+                let keys: Vec<String> = self
+                    .bindings
+                    .iter()
+                    .filter(|(_, bound)| bound == action)
+                    .map(|(keys, _)| key_label(keys))
+                    .collect();
+                (!keys.is_empty()).then(|| (*action, keys.join(", ")))
+            })
+            .collect()
+    }
+
+    fn is_prefix(&self, pending: &[Event]) -> bool {
+        self.bindings
+            .iter()
+            .any(|(keys, _)| keys.starts_with(pending))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_keys_by_name_and_by_character() {
+        assert_eq!(parse_keys("PageDown"), Ok(vec![Event::Key(Key::PageDown)]));
+        assert_eq!(parse_keys("ctrl-d"), Ok(vec![Event::CtrlChar('d')]));
+        assert_eq!(
+            parse_keys("gg"),
+            Ok(vec![Event::Char('g'), Event::Char('g')])
+        );
+        assert!(parse_keys("ctrl-").is_err());
+        assert!(parse_keys("g g").is_err());
+    }
+
+    #[test]
+    fn sequences_complete_and_restart() {
+        let mut keymap = Keymap::default();
+        keymap.bind(KeyAction::PageDown, vec![parse_keys("ctrl-d").unwrap()]);
+        let mut pending = Vec::new();
+        let mut feed = |c| keymap.feed(&mut pending, c);
+        assert_eq!(feed(Event::Char('j')), Some(KeyAction::Down));
+        assert_eq!(feed(Event::Char('g')), None);
+        assert_eq!(feed(Event::Char('g')), Some(KeyAction::Top));
+        assert_eq!(feed(Event::Char('G')), Some(KeyAction::Bottom));
+        // A key that breaks `gg` counts on its own.
+        assert_eq!(feed(Event::Char('g')), None);
+        assert_eq!(feed(Event::Char('k')), Some(KeyAction::Up));
+        assert_eq!(feed(Event::CtrlChar('d')), Some(KeyAction::PageDown));
+        assert_eq!(feed(Event::Char('u')), Some(KeyAction::Undo));
+
+        keymap.bind(KeyAction::Undo, Vec::new());
+        assert_eq!(keymap.feed(&mut Vec::new(), Event::Char('u')), None);
+        assert!(!keymap.keys().contains(&Event::Char('u')));
+        let help = keymap.help();
+        assert_eq!(help[0], (KeyAction::Down, "j".to_string()));
+        assert!(help.contains(&(KeyAction::PageDown, "Ctrl+D".to_string())));
+        assert!(!help.iter().any(|(action, _)| *action == KeyAction::Undo));
+    }
+}
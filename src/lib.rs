@@ -1,6 +1,53 @@
 #[macro_use]
 mod macros;
 
+pub mod alerts;
+pub mod archive;
+pub mod attachments;
+pub mod backup;
+pub mod benchmark;
+pub mod brokers;
+pub mod bulk_edit;
+pub mod cash;
+pub mod chart;
+pub mod clipboard;
+pub mod columns;
+pub mod concentration;
+pub mod config;
+pub mod cost_basis;
+pub mod custom_report;
 pub mod date;
 pub mod db;
+pub mod dividends;
+pub mod encryption;
+pub mod equity;
+pub mod export;
+pub mod fees;
+pub mod fx;
+pub mod heatmap;
+pub mod history;
+pub mod ics;
+pub mod import;
+pub mod keymap;
+pub mod market_data;
+pub mod migrations;
+pub mod occ;
+pub mod ofx;
+pub mod outcomes;
+pub mod payoff;
+pub mod periods;
+pub mod positions;
+pub mod premium;
+pub mod quick_add;
+pub mod snapshot;
+pub mod statistics;
+pub mod strategy_report;
+pub mod templates;
+pub mod theme;
+pub mod toast;
+pub mod txf;
 pub mod ui;
+pub mod valuation;
+pub mod warnings;
+pub mod wash_sale;
+pub mod wheel;
@@ -7,19 +7,20 @@
 /// - `variants` returning all variants in declaration order,
 /// - a case-insensitive `FromStr` (erroring with `Invalid <error>: <input>`),
 /// - `rusqlite`'s `ToSql`/`FromSql` (stored as text, parsed via `FromStr`),
+/// - `serde`'s `Serialize`/`Deserialize` (the same text),
 /// - `Display`,
 /// - `From<Self> for String`.
 macro_rules! string_enum {
     (
         $(#[$meta:meta])*
         $vis:vis enum $name:ident {
-            $($variant:ident => $text:literal),+ $(,)?
+            $($(#[$variant_meta:meta])* $variant:ident => $text:literal),+ $(,)?
         }
         error = $error:literal $(,)?
     ) => {
         $(#[$meta])*
         $vis enum $name {
-            $($variant),+
+            $($(#[$variant_meta])* $variant),+
         }
 
         impl $name {
@@ -70,6 +71,24 @@ macro_rules! string_enum {
             }
         }
 
+        impl ::serde::Serialize for $name {
+            fn serialize<S: ::serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> ::std::result::Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> ::serde::Deserialize<'de> for $name {
+            fn deserialize<D: ::serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> ::std::result::Result<Self, D::Error> {
+                let text = String::deserialize(deserializer)?;
+                text.parse().map_err(::serde::de::Error::custom)
+            }
+        }
+
         impl ::std::fmt::Display for $name {
             fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
                 write!(f, "{}", self.as_str())
@@ -1,12 +1,1202 @@
-use options_tracker::db::Database;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use options_tracker::archive::archive_note;
+use options_tracker::backup::Backup;
+use options_tracker::brokers::{detect, format_named, FORMATS};
+use options_tracker::columns::{format_header, format_row, TradeColumn};
+use options_tracker::config::{Config, CONFIG_FILE};
+use options_tracker::cost_basis::CostBasisMethod;
+use options_tracker::custom_report::Filter;
+use options_tracker::date::{parse_date_input, today, unix_now, DateRange, RangePreset};
+use options_tracker::db::{
+    Account, Action, Database, OptionStatus, OptionType, Trade, TradeFilter, TradeType,
+    OPTION_MULTIPLIER,
+};
+use options_tracker::encryption::{
+    decrypt, encrypt, is_encrypted, prompt_passphrase, PASSPHRASE_VAR,
+};
+use options_tracker::export::{
+    fees_table, periods_table, positions_table, premium_table, report_workbook, save_xlsx,
+    statistics_table, symbols_table, trades_table, write_file, write_xlsx, Table,
+};
+use options_tracker::fees::schedule_for;
+use options_tracker::ics::expiration_calendar;
+use options_tracker::import::{find_duplicates, guess_mapping, parse_csv, parse_json, read_trades};
+use options_tracker::ofx::{is_ofx, read_ofx};
+use options_tracker::periods::Period;
+use options_tracker::positions::open_positions;
+use options_tracker::premium::premium_income;
+use options_tracker::quick_add::parse_quick_add;
+use options_tracker::snapshot::{auto_snapshot, manual_snapshot, snapshot_before_upgrade};
+use options_tracker::statistics::{risk_metrics, trade_statistics};
+use options_tracker::txf::txf;
 use options_tracker::ui;
+use options_tracker::warnings::trade_warnings;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+const DB_FILE: &str = "options_tracker.db";
+
+/// Tracks stock and option trades. With no command, opens the interactive
+/// interface.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open the interactive interface (the default)
+    Tui,
+    /// Add a trade from flags or a Quick Add line
+    Add(Box<AddArgs>),
+    /// List trades, newest first, in the trade list's columns
+    List {
+        /// Only trades matching a custom report filter, e.g. "symbol = AAPL"
+        #[arg(long)]
+        filter: Option<String>,
+        /// Only trades whose symbol or comment contains this
+        #[arg(long)]
+        search: Option<String>,
+        /// Only trades in this account
+        #[arg(long)]
+        account: Option<String>,
+        /// At most this many trades
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Move trades to the Trash
+    Delete {
+        /// Ids of the trades, as the list shows them
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Import trades from an OFX statement, a broker export, or a CSV file
+    Import(ImportArgs),
+    /// Export trades to CSV or JSON, or a report workbook to Excel
+    Export(ExportArgs),
+    /// Print a report to standard output as a table, CSV, or JSON
+    Report(ReportArgs),
+    /// Export the lots closed in a tax year, or ever, to TXF
+    Txf { year: Option<String> },
+    /// Export upcoming option expirations to an iCalendar file
+    Ics,
+    /// Write the whole database as JSON
+    Backup { file: String },
+    /// Replace the database with a JSON backup
+    Restore {
+        file: String,
+        /// Overwrite a database that already has trades
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Copy the database file to a path or into the backup directory
+    Snapshot { file: Option<String> },
+    /// Replace the database with a snapshot
+    RestoreSnapshot { file: String },
+    /// Delete the trades in the Trash for good
+    Purge,
+    /// Encrypt the database, or change its passphrase
+    Encrypt,
+    /// Store an encrypted database as plaintext again
+    Decrypt,
+}
+
+/// The trade to add: a Quick Add line, or flags for each field.
+#[derive(Args)]
+struct AddArgs {
+    /// A Quick Add line, e.g. "sell 2 AAPL 190c 1/19 @ 2.35 fee .66"
+    #[arg(conflicts_with_all = ["symbol", "action", "quantity", "price"])]
+    line: Option<String>,
+    #[arg(long, required_unless_present = "line")]
+    symbol: Option<String>,
+    /// buy, sell, bto, sto, btc, stc, or an action as stored, e.g.
+    /// buy_to_close. A bare sell closes stock and writes an option.
+    #[arg(long, required_unless_present = "line")]
+    action: Option<String>,
+    /// Shares or contracts
+    #[arg(long, required_unless_present = "line")]
+    quantity: Option<Decimal>,
+    /// Per share
+    #[arg(long, required_unless_present = "line")]
+    price: Option<Decimal>,
+    /// YYYY-MM-DD or a shortcut such as y or -3d; today by default
+    #[arg(long, conflicts_with = "line")]
+    date: Option<String>,
+    /// From the broker's commission schedule, or none, by default
+    #[arg(long)]
+    fees: Option<Decimal>,
+    /// call or put; makes the trade an option
+    #[arg(long, conflicts_with = "line", requires_all = ["strike", "expiration"])]
+    option_type: Option<OptionType>,
+    #[arg(long, conflicts_with = "line", requires = "option_type")]
+    strike: Option<Decimal>,
+    /// YYYY-MM-DD or a shortcut
+    #[arg(long, conflicts_with = "line", requires = "option_type")]
+    expiration: Option<String>,
+    /// Shares per contract, for adjusted contracts
+    #[arg(long, conflicts_with = "line", requires = "option_type")]
+    multiplier: Option<Decimal>,
+    #[arg(long)]
+    comment: Option<String>,
+    /// A tag; repeat for more
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// The account, by name
+    #[arg(long)]
+    account: Option<String>,
+    #[arg(long)]
+    broker: Option<String>,
+    /// Save despite warnings such as closing more than is open
+    #[arg(long)]
+    force: bool,
+}
+
+/// The file to import and how.
+#[derive(Args)]
+struct ImportArgs {
+    file: String,
+    /// csv, which takes in OFX statements and broker exports too, or json
+    /// as export writes it. Taken from the file's extension if not given.
+    #[arg(long, value_enum)]
+    format: Option<ImportFormat>,
+    /// Read the file as this broker's export rather than detecting its
+    /// layout: schwab, thinkorswim, fidelity, tastytrade, or robinhood
+    #[arg(long)]
+    broker: Option<String>,
+    /// Show what would be imported without saving it
+    #[arg(long)]
+    dry_run: bool,
+    /// Import trades that duplicate ones already saved
+    #[arg(long)]
+    force: bool,
+    /// Print only a JSON summary of the trades imported and the rows skipped
+    #[arg(long)]
+    json: bool,
+}
+
+/// What `import` reads.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+enum ImportFormat {
+    Csv,
+    Json,
+}
+
+/// What `import --json` prints.
+#[derive(Serialize)]
+struct ImportSummary {
+    file: String,
+    /// `OFX`, the broker's name, or `CSV` or `JSON` for a file mapped by its
+    /// headers
+    format: String,
+    dry_run: bool,
+    /// Trades saved, or that would be on a dry run
+    inserted: usize,
+    /// Ids of the trades saved
+    ids: Vec<i64>,
+    /// Multi-leg orders kept together as strategies
+    strategies: usize,
+    duplicates: Vec<DuplicateTrade>,
+    errors: Vec<RowError>,
+}
+
+/// A trade in the file that is already saved.
+#[derive(Serialize)]
+struct DuplicateTrade {
+    trade: String,
+    /// The id of the saved trade
+    of: i64,
+    /// Whether it was left out; it is imported anyway with `--force`
+    skipped: bool,
+}
+
+/// A row of the file that couldn't be read.
+#[derive(Serialize)]
+struct RowError {
+    row: usize,
+    error: String,
+}
+
+/// The trades to export and where.
+#[derive(Args)]
+struct ExportArgs {
+    /// Where to write; - for standard output. By default a timestamped file
+    /// in the export directory.
+    file: Option<String>,
+    /// csv or json for the trades, or xlsx for a workbook that adds the open
+    /// positions and P/L by month. Taken from the file's extension if not
+    /// given, else csv.
+    #[arg(long, value_enum)]
+    format: Option<ExportFormat>,
+    /// Only trades matching a custom report filter
+    #[arg(long)]
+    filter: Option<String>,
+}
+
+/// What `export` writes.
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+enum ExportFormat {
+    Csv,
+    /// An array of objects, one per trade, keyed by field
+    Json,
+    Xlsx,
+}
+
+/// The report to print and how.
+#[derive(Args)]
+struct ReportArgs {
+    report: ReportKind,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    /// Only trades from this day on: YYYY-MM-DD or a shortcut such as -7d
+    #[arg(long, allow_hyphen_values = true)]
+    from: Option<String>,
+    /// Only trades up to this day
+    #[arg(long, allow_hyphen_values = true)]
+    to: Option<String>,
+    /// A preset range instead of --from and --to: ytd, "last 30 days", or
+    /// "previous year"
+    #[arg(long, conflicts_with_all = ["from", "to"])]
+    range: Option<RangePreset>,
+    /// How the period report groups: month, quarter, or year
+    #[arg(long, default_value_t = Period::Month)]
+    period: Period,
+    /// Which lots a close consumes: fifo, lifo, or average
+    #[arg(long, default_value_t = CostBasisMethod::Fifo)]
+    method: CostBasisMethod,
+    /// Only trades in this account
+    #[arg(long)]
+    account: Option<String>,
+}
+
+/// The reports `report` prints, as the TUI's Reports menu shows them.
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportKind {
+    /// Realized and unrealized P/L per symbol
+    Symbol,
+    /// P/L per month, quarter, or year
+    Period,
+    /// Open positions, as of today whatever the range
+    Positions,
+    /// Win rate, profit factor, drawdown, and the like
+    Statistics,
+    /// Option premium collected per month
+    Premium,
+    /// Fees against realized P/L
+    Fees,
+}
+
+/// How `report` writes its table.
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Aligned columns to read
+    Table,
+    Csv,
+    /// An array of objects keyed by column, for jq and the like
+    Json,
+}
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize database
-    let db = Database::new("options_tracker.db")?;
+    let command = Cli::parse().command.unwrap_or(Command::Tui);
+
+    // Load settings (defaults when the file is absent)
+    let config = Config::load(CONFIG_FILE)?;
+
+    // An encrypted database needs its passphrase before anything else
+    let passphrase = if is_encrypted(Path::new(DB_FILE)) {
+        Some(passphrase(&format!("Passphrase for {}: ", DB_FILE))?)
+    } else {
+        None
+    };
+
+    match command {
+        Command::Encrypt => return encrypt_db(passphrase.as_deref()),
+        Command::Decrypt => return decrypt_db(passphrase.as_deref()),
+        _ => {}
+    }
+
+    // Snapshot a database this version is about to upgrade, then open it
+    if let Some(path) = snapshot_before_upgrade(
+        DB_FILE,
+        passphrase.as_deref(),
+        &config.backup_dir,
+        config.backup_keep,
+        unix_now(),
+    )? {
+        eprintln!("Saved a snapshot to {} before upgrading", path.display());
+    }
+    let mut db = Database::open(DB_FILE, passphrase.as_deref())?;
+
+    match command {
+        Command::Add(args) => add(&db, &config, args),
+        Command::List {
+            filter,
+            search,
+            account,
+            limit,
+        } => list(&mut db, &config, filter, search, account, limit),
+        Command::Delete { ids } => delete(&db, &ids),
+        Command::Import(args) => import(&db, &config, args),
+        Command::Export(args) => export(&db, &config, args),
+        Command::Report(args) => report(&mut db, args),
+        Command::Txf { year } => export_txf(&db, &config, year.as_deref()),
+        Command::Ics => export_ics(&db, &config),
+        Command::Backup { file } => backup(&db, &file),
+        Command::Restore { file, replace } => restore(&db, &config, &file, replace),
+        Command::Snapshot { file } => snapshot(&db, &config, file.as_deref()),
+        Command::RestoreSnapshot { file } => restore_snapshot(&mut db, &config, &file),
+        Command::Purge => purge(&db),
+        Command::Tui => {
+            ui::run_ui(db, config);
+            Ok(())
+        }
+        Command::Encrypt | Command::Decrypt => unreachable!("run before the database is opened"),
+    }
+}
+
+// The passphrase from the environment, or asked for on the terminal.
+fn passphrase(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match std::env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => Ok(passphrase),
+        Err(_) => Ok(prompt_passphrase(prompt)?),
+    }
+}
+
+// `encrypt`: encrypts the database with a new passphrase, typed twice, or
+// changes the passphrase of one already encrypted.
+fn encrypt_db(current: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let new = prompt_passphrase("New passphrase: ")?;
+    if new.is_empty() {
+        return Err("the passphrase must not be empty".into());
+    }
+    if prompt_passphrase("Repeat the new passphrase: ")? != new {
+        return Err("the passphrases don't match".into());
+    }
+    encrypt(Path::new(DB_FILE), current, &new)?;
+    if current.is_some() {
+        println!("Changed the passphrase of {}", DB_FILE);
+    } else {
+        println!(
+            "Encrypted {}. Snapshots taken before now are not encrypted; \
+             delete the ones you don't need.",
+            DB_FILE
+        );
+    }
+    Ok(())
+}
+
+// `decrypt`: stores an encrypted database as plaintext again.
+fn decrypt_db(current: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(current) = current else {
+        return Err(format!("{} is not encrypted", DB_FILE).into());
+    };
+    decrypt(Path::new(DB_FILE), current)?;
+    println!("Decrypted {}", DB_FILE);
+    Ok(())
+}
 
-    // Run UI
-    ui::run_ui(db);
+// `add`: saves a trade given by its fields or a Quick Add line, in the named
+// account, with fees from its broker's commission schedule unless given.
+// Warnings the trade form would ask about stop it unless `--force` is given.
+fn add(
+    db: &Database,
+    config: &Config,
+    args: Box<AddArgs>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if args.fees.is_some_and(|fees| fees < Decimal::ZERO) {
+        return Err("the fees can't be negative".into());
+    }
+    let today = today();
+    let mut trade = match &args.line {
+        Some(line) => parse_quick_add(line, &today)?,
+        None => trade_from_flags(&args, &today)?,
+    };
+    if let Some(name) = &args.account {
+        let account = find_account(db, name)?;
+        trade.account_id = account.id;
+        trade.currency = account.currency;
+    }
+    trade.broker = args.broker.clone();
+    if let Some(fees) = args.fees {
+        trade.fees = fees;
+    } else if let Some(schedule) = args
+        .broker
+        .as_deref()
+        .and_then(|broker| schedule_for(&config.commissions, broker))
+    {
+        trade.fees = schedule.commission(trade.trade_type, trade.quantity);
+    }
+    if let Some(comment) = args.comment {
+        trade.comment = comment;
+    }
+    trade.tags.extend(args.tags);
+    trade.tags.sort();
+    trade.tags.dedup();
+
+    let warnings = trade_warnings(db, &trade);
+    for (_, warning) in &warnings {
+        eprintln!("{}", warning);
+    }
+    if !warnings.is_empty() && !args.force {
+        return Err("not saved; pass --force to save it anyway".into());
+    }
+    let id = db.add_trade(&trade)?;
+    trade.id = Some(id);
+    println!("Added trade #{}: {}", id, describe(&trade));
+    Ok(())
+}
+
+// The trade `add` was given flag by flag.
+fn trade_from_flags(args: &AddArgs, today: &str) -> Result<Trade, Box<dyn std::error::Error>> {
+    let (Some(symbol), Some(action), Some(quantity), Some(price)) =
+        (&args.symbol, &args.action, args.quantity, args.price)
+    else {
+        return Err("give the symbol, action, quantity, and price, or a Quick Add line".into());
+    };
+    let option = args.option_type.is_some();
+    let action = match action.to_lowercase().as_str() {
+        "buy" | "bto" => Action::BuyToOpen,
+        "sell" | "sto" if option => Action::SellToOpen,
+        "sell" | "stc" => Action::SellToClose,
+        "sto" => Action::SellToOpen,
+        "btc" => Action::BuyToClose,
+        other => other.parse()?,
+    };
+    if quantity <= Decimal::ZERO {
+        return Err("the quantity must be more than zero".into());
+    }
+    if price < Decimal::ZERO {
+        return Err("the price can't be negative".into());
+    }
+    if args.strike.is_some_and(|strike| strike <= Decimal::ZERO) {
+        return Err("the strike must be more than zero".into());
+    }
+    let date =
+        |text: &str| parse_date_input(text, today).ok_or_else(|| format!("{} is not a date", text));
+    let mut trade = Trade {
+        symbol: symbol.to_uppercase(),
+        action,
+        quantity,
+        price,
+        date: args.date.as_deref().map_or(Ok(today.to_string()), date)?,
+        ..Default::default()
+    };
+    if option {
+        trade.trade_type = TradeType::Option;
+        trade.option_type = args.option_type;
+        trade.strike = args.strike;
+        trade.expiration = args.expiration.as_deref().map(date).transpose()?;
+        trade.status = Some(OptionStatus::Open);
+        trade.contract_multiplier = args.multiplier.filter(|m| *m != OPTION_MULTIPLIER);
+    }
+    Ok(trade)
+}
 
+// `list`: prints the trades matching the filter and search, newest first, in
+// the columns the settings give the trade list.
+fn list(
+    db: &mut Database,
+    config: &Config,
+    filter: Option<String>,
+    search: Option<String>,
+    account: Option<String>,
+    limit: Option<usize>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(name) = account {
+        let account = find_account(db, &name)?;
+        db.set_account(account.id);
+    }
+    let mut trades = filtered_trades(db, filter.as_deref().unwrap_or(""), search)?;
+    trades.truncate(limit.unwrap_or(usize::MAX));
+    let columns = &config.trade_columns;
+    let realized = if columns.iter().any(|c| c.column == TradeColumn::Pl) {
+        db.get_realized_by_trade(CostBasisMethod::default())?
+    } else {
+        HashMap::new()
+    };
+    let today = today();
+    println!("{}", format_header(columns));
+    for trade in &trades {
+        let pl = trade.id.and_then(|id| realized.get(&id).copied());
+        println!("{}", format_row(columns, trade, &today, pl));
+    }
     Ok(())
 }
+
+// `delete <id>...`: moves trades to the Trash, with the stock rows their
+// assignments generated. Generated rows go only with their options.
+fn delete(db: &Database, ids: &[i64]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    for &id in &ids {
+        let trade = db
+            .get_trade(id)?
+            .filter(|trade| trade.deleted_at.is_none())
+            .ok_or_else(|| format!("there is no trade #{}", id))?;
+        if let Some(option) = trade.assigned_from {
+            return Err(format!(
+                "trade #{} was generated by option #{}; delete or edit that option instead",
+                id, option
+            )
+            .into());
+        }
+    }
+    db.delete_trades(&ids)?;
+    println!("Moved {} trade(s) to the Trash", ids.len());
+    Ok(())
+}
+
+// Trades in the current account matching a custom report filter and a
+// search of symbols and comments, newest first.
+fn filtered_trades(
+    db: &Database,
+    filter: &str,
+    search: Option<String>,
+) -> Result<Vec<Trade>, Box<dyn std::error::Error>> {
+    let filter: Filter = filter.parse()?;
+    let (mut query, rest) = filter.split();
+    query.search = search.filter(|search| !search.trim().is_empty());
+    Ok(db
+        .query_trades(&query, None, 0)?
+        .into_iter()
+        .filter(|t| rest.matches(t))
+        .collect())
+}
+
+// The account called `name`, ignoring case.
+fn find_account(db: &Database, name: &str) -> Result<Account, Box<dyn std::error::Error>> {
+    db.get_accounts()?
+        .into_iter()
+        .find(|account| account.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| format!("there is no account named {}", name).into())
+}
+
+// A trade on one line: `2024-03-04 buy_to_open 100 KO @ 58.10`.
+fn describe(trade: &Trade) -> String {
+    format!(
+        "{} {} {} {} @ {}",
+        trade.date,
+        trade.action,
+        trade.quantity.normalize(),
+        trade.position_key().instrument(),
+        trade.price
+    )
+}
+
+// `import <file> [--broker <name>] [--dry-run] [--force] [--json]`: imports
+// trades from an OFX statement or a broker export, or from a CSV with the
+// column mapping guessed from the header row. Trades already saved are
+// skipped unless `--force` is given. With `--json` only the summary is
+// printed, as JSON.
+fn import(
+    db: &Database,
+    config: &Config,
+    args: ImportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = &args.file;
+    let text = std::fs::read_to_string(path)?;
+    let json = args
+        .format
+        .or_else(|| extension_format(Some(path)))
+        .is_some_and(|format| format == ImportFormat::Json);
+    let records = if json {
+        parse_json(&text)?
+    } else {
+        parse_csv(&text)
+    };
+    let Some(headers) = records.first().filter(|headers| !headers.is_empty()) else {
+        return Err(format!("{} is empty", path).into());
+    };
+    let broker = match &args.broker {
+        None => None,
+        Some(_) if json => return Err("--broker reads a broker's CSV export, not JSON".into()),
+        Some(_) if is_ofx(&text) => {
+            return Err(format!("{} is an OFX statement, not a broker export", path).into())
+        }
+        Some(name) => {
+            let format = format_named(name).ok_or_else(|| {
+                let names: Vec<&str> = FORMATS.iter().map(|format| format.name()).collect();
+                format!("there is no broker {}; choose {}", name, names.join(", "))
+            })?;
+            if !format.detects(&records) {
+                return Err(format!("{} is not a {} export", path, format.name()).into());
+            }
+            Some(format)
+        }
+    };
+    let (format, mut read) = if !json && is_ofx(&text) {
+        ("OFX".to_string(), read_ofx(&text))
+    } else if let Some(format) = broker.or_else(|| detect(&records).filter(|_| !json)) {
+        (format.name().to_string(), format.import(&records))
+    } else {
+        let mapping = guess_mapping(headers);
+        if !args.json {
+            for (header, field) in headers.iter().zip(&mapping) {
+                match field {
+                    Some(field) => println!("{:<24} -> {}", header, field),
+                    None => println!("{:<24} -> skipped", header),
+                }
+            }
+        }
+        let format = if json { "JSON" } else { "CSV" };
+        (format.to_string(), read_trades(&records, &mapping))
+    };
+    // A file none of whose rows could be read is an error, not an import of
+    // nothing.
+    let unreadable = read.trades.is_empty() && !read.errors.is_empty();
+    let duplicates = find_duplicates(&read.trades, &db.get_all_trades()?);
+    let mut summary = ImportSummary {
+        file: path.clone(),
+        format,
+        dry_run: args.dry_run,
+        inserted: 0,
+        ids: Vec::new(),
+        strategies: 0,
+        duplicates: read
+            .trades
+            .iter()
+            .zip(&duplicates)
+            .filter_map(|(trade, duplicate)| {
+                duplicate.map(|of| DuplicateTrade {
+                    trade: describe(trade),
+                    of,
+                    skipped: !args.force,
+                })
+            })
+            .collect(),
+        errors: read
+            .errors
+            .iter()
+            .map(|(row, error)| RowError {
+                row: *row,
+                error: error.clone(),
+            })
+            .collect(),
+    };
+    if !args.force {
+        let skip: Vec<bool> = duplicates.iter().map(Option::is_some).collect();
+        read = read.without(&skip);
+    }
+    summary.inserted = read.trades.len();
+    summary.strategies = read.strategies.len();
+    if !args.json {
+        match summary.format.as_str() {
+            "OFX" => println!("Reading an OFX statement"),
+            "CSV" | "JSON" => {}
+            name => println!("Reading a {} export", name),
+        }
+        for error in &summary.errors {
+            eprintln!("row {}: {}", error.row, error.error);
+        }
+        for duplicate in &summary.duplicates {
+            println!(
+                "{} duplicates trade #{}{}",
+                duplicate.trade,
+                duplicate.of,
+                if duplicate.skipped { ", skipped" } else { "" }
+            );
+        }
+        for (strategy, legs) in &read.strategies {
+            println!("strategy {} ({} legs)", strategy.name, legs.len());
+        }
+    }
+    if !args.dry_run && !unreadable {
+        auto_snapshot(
+            db,
+            &config.backup_dir,
+            "import",
+            config.backup_keep,
+            unix_now(),
+        )?;
+        summary.ids = db.add_trades_with_strategies(&read.trades, &read.strategies)?;
+    }
+    let skipped = summary.duplicates.iter().filter(|d| d.skipped).count();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else if args.dry_run {
+        println!(
+            "{} trade(s) would be imported, {} duplicate(s) and {} row(s) skipped.",
+            summary.inserted,
+            skipped,
+            summary.errors.len()
+        );
+    } else {
+        println!(
+            "Imported {} trade(s), skipped {} duplicate(s) and {} row(s).",
+            summary.ids.len(),
+            skipped,
+            summary.errors.len()
+        );
+    }
+    if unreadable {
+        return Err(format!("no row of {} could be read", path).into());
+    }
+    Ok(())
+}
+
+// `export [<file>] [--format csv|json|xlsx] [--filter <expr>]`: writes every
+// trade, or those matching the filter, as CSV or JSON, or to an Excel
+// workbook together with the open positions and P/L by month. Without a file
+// it goes to a timestamped file in the export directory; `-` is standard
+// output, with the count going to standard error.
+fn export(
+    db: &Database,
+    config: &Config,
+    args: ExportArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let format = export_format(&args);
+    note_archived(db, &DateRange::default())?;
+    let trades = filtered_trades(db, args.filter.as_deref().unwrap_or(""), None)?;
+    let text = match format {
+        ExportFormat::Csv => Some(trades_table(&trades).to_csv()),
+        ExportFormat::Json => Some(trades_table(&trades).to_json() + "\n"),
+        ExportFormat::Xlsx => None,
+    };
+    let path = match (args.file.as_deref(), text) {
+        (Some("-"), None) => return Err("a workbook can't go to standard output".into()),
+        (Some("-"), Some(text)) => {
+            print!("{}", text);
+            eprintln!("Exported {} trade(s)", trades.len());
+            return Ok(());
+        }
+        (Some(path), Some(text)) => {
+            std::fs::write(path, text).map_err(|e| format!("{}: {}", path, e))?;
+            path.into()
+        }
+        (None, Some(text)) => {
+            let extension = if format == ExportFormat::Json {
+                "json"
+            } else {
+                "csv"
+            };
+            write_file(&text, &config.export_dir, "trades", extension, unix_now())?
+        }
+        (path, None) => {
+            let sheets = report_workbook(
+                &trades,
+                &open_positions(&db.get_all_trades()?, &today()),
+                &db.get_quotes()?,
+                &db.get_period_report(
+                    CostBasisMethod::default(),
+                    Period::Month,
+                    &DateRange::default(),
+                )?,
+            );
+            match path {
+                Some(path) => {
+                    save_xlsx(&sheets, Path::new(path))?;
+                    path.into()
+                }
+                None => write_xlsx(&sheets, &config.export_dir, "report workbook", unix_now())?,
+            }
+        }
+    };
+    println!("Exported {} trade(s) to {}", trades.len(), path.display());
+    Ok(())
+}
+
+// The format `export` was asked for: `--format`, or the file's extension, or
+// CSV.
+fn export_format(args: &ExportArgs) -> ExportFormat {
+    args.format
+        .or_else(|| extension_format(args.file.as_deref()))
+        .unwrap_or(ExportFormat::Csv)
+}
+
+// The format `file`'s extension names, if any: `.json` for JSON.
+fn extension_format<T: ValueEnum>(file: Option<&str>) -> Option<T> {
+    let extension = Path::new(file?).extension()?.to_str()?;
+    T::from_str(extension, true).ok()
+}
+
+// `report <kind>`: prints a report over the date range, in the account if
+// one is named, to standard output and nothing else, so it pipes cleanly.
+fn report(db: &mut Database, args: ReportArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(name) = &args.account {
+        let account = find_account(db, name)?;
+        db.set_account(account.id);
+    }
+    let today = today();
+    let range = match args.range {
+        Some(preset) => preset.range(&today),
+        None => {
+            let date = |text: &String| {
+                parse_date_input(text, &today).ok_or_else(|| format!("{} is not a date", text))
+            };
+            DateRange {
+                start: args.from.as_ref().map(date).transpose()?,
+                end: args.to.as_ref().map(date).transpose()?,
+            }
+        }
+    };
+    let method = args.method;
+    if !matches!(args.report, ReportKind::Positions) {
+        note_archived(db, &range)?;
+    }
+    let table: Table = match args.report {
+        ReportKind::Symbol => symbols_table(&db.get_report_by_symbol(method, &range)?),
+        ReportKind::Period => periods_table(&db.get_period_report(method, args.period, &range)?),
+        ReportKind::Positions => positions_table(
+            &open_positions(&db.get_all_trades()?, &today),
+            &db.get_quotes()?,
+        ),
+        ReportKind::Statistics => statistics_table(
+            &trade_statistics(&db.get_closed_positions(method, &range)?),
+            &risk_metrics(&db.get_equity_curve(method, &range)?),
+        ),
+        ReportKind::Premium => premium_table(&premium_income(&db.get_trades_in(&range)?)),
+        ReportKind::Fees => fees_table(&db.get_fee_report(method, &range)?),
+    };
+    match args.format {
+        Format::Table => println!("{}", table.to_text()),
+        Format::Csv => print!("{}", table.to_csv()),
+        Format::Json => println!("{}", table.to_json()),
+    }
+    Ok(())
+}
+
+// Warns on standard error when the range reaches into archived years, whose
+// trades live only in their summaries and so are left out.
+fn note_archived(db: &Database, range: &DateRange) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(note) = archive_note(db.archived_through()?, range) {
+        eprintln!("Note: {}", note);
+    }
+    Ok(())
+}
+
+// `txf [<year>]`: writes the lots closed in the tax year, or ever, to a TXF
+// file in the export directory for TurboTax to import.
+fn export_txf(
+    db: &Database,
+    config: &Config,
+    year: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let range = match year {
+        None => DateRange::default(),
+        Some(year) if year.len() == 4 && year.parse::<u32>().is_ok() => DateRange {
+            start: Some(format!("{}-01-01", year)),
+            end: Some(format!("{}-12-31", year)),
+        },
+        Some(year) => return Err(format!("{} is not a year", year).into()),
+    };
+    note_archived(db, &range)?;
+    let lots = db.get_realized_lots(CostBasisMethod::default(), &range)?;
+    let name = match year {
+        Some(year) => format!("realized gains {}", year),
+        None => "realized gains".to_string(),
+    };
+    let path = write_file(
+        &txf(&lots, &today()),
+        &config.export_dir,
+        &name,
+        "txf",
+        unix_now(),
+    )?;
+    println!("Exported {} sale(s) to {}", lots.len(), path.display());
+    Ok(())
+}
+
+// `ics`: writes upcoming option expirations to an iCalendar file in the
+// export directory.
+fn export_ics(db: &Database, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let today = today();
+    let positions = open_positions(&db.get_all_trades()?, &today);
+    let path = write_file(
+        &expiration_calendar(&positions, &today, unix_now()),
+        &config.export_dir,
+        "option expirations",
+        "ics",
+        unix_now(),
+    )?;
+    println!("Exported expirations to {}", path.display());
+    Ok(())
+}
+
+// `backup <file.json>`: writes the whole database as JSON.
+fn backup(db: &Database, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let backup = db.get_backup()?;
+    std::fs::write(path, backup.to_json())?;
+    println!("Backed up {} trade(s) to {}", backup.trades.len(), path);
+    Ok(())
+}
+
+// `restore <file.json> [--replace]`: replaces the database with a JSON
+// backup. Without `--replace` it only restores into an empty database.
+fn restore(
+    db: &Database,
+    config: &Config,
+    path: &str,
+    replace: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let backup = Backup::from_json(&std::fs::read_to_string(path)?)?;
+    if !replace && db.count_trades(&TradeFilter::default())? > 0 {
+        return Err("the database already has trades; pass --replace to overwrite them".into());
+    }
+    auto_snapshot(
+        db,
+        &config.backup_dir,
+        "restore",
+        config.backup_keep,
+        unix_now(),
+    )?;
+    db.restore_backup(&backup)?;
+    println!("Restored {} trade(s) from {}", backup.trades.len(), path);
+    Ok(())
+}
+
+// `snapshot [<file.db>]`: copies the database file, checked for damage, to
+// the given path or into the backup directory.
+fn snapshot(
+    db: &Database,
+    config: &Config,
+    path: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = match path {
+        None => manual_snapshot(db, &config.backup_dir, unix_now())?,
+        Some(path) => {
+            db.write_snapshot(Path::new(path))?;
+            path.into()
+        }
+    };
+    println!("Saved a snapshot to {}", path.display());
+    Ok(())
+}
+
+// `restore-snapshot <file.db>`: replaces the database with a snapshot after
+// checking it, taking an automatic snapshot of the current one first.
+fn restore_snapshot(
+    db: &mut Database,
+    config: &Config,
+    path: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let saved = auto_snapshot(
+        db,
+        &config.backup_dir,
+        "restore",
+        config.backup_keep,
+        unix_now(),
+    )?;
+    db.restore_snapshot(Path::new(path))?;
+    println!(
+        "Restored {} trade(s) from {}; the previous database is in {}",
+        db.count_trades(&TradeFilter::default())?,
+        path,
+        saved.display()
+    );
+    Ok(())
+}
+
+// `purge`: deletes the trades in the Trash for good.
+fn purge(db: &Database) -> Result<(), Box<dyn std::error::Error>> {
+    let purged = db.purge_trash()?;
+    println!("Purged {} trade(s) from the Trash", purged);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn add_reads_a_trade_from_flags() {
+        let args = |line: &str| {
+            let cli = Cli::try_parse_from(line.split_whitespace()).unwrap();
+            match cli.command {
+                Some(Command::Add(args)) => args,
+                _ => panic!("not add"),
+            }
+        };
+        let put = trade_from_flags(
+            &args(
+                "options_tracker add --symbol aapl --action sell --quantity 2 --price 1.5 \
+                 --option-type put --strike 150 --expiration 2024-06-21 --date y",
+            ),
+            "2024-06-03",
+        )
+        .unwrap();
+        assert_eq!(put.symbol, "AAPL");
+        assert_eq!(put.action, Action::SellToOpen);
+        assert_eq!(put.trade_type, TradeType::Option);
+        assert_eq!(
+            (put.strike, put.date.as_str()),
+            (Some(dec!(150)), "2024-06-02")
+        );
+        let stock = trade_from_flags(
+            &args("options_tracker add --symbol KO --action sell --quantity 100 --price 60"),
+            "2024-06-03",
+        )
+        .unwrap();
+        assert_eq!(stock.action, Action::SellToClose);
+        assert_eq!(stock.date, "2024-06-03");
+        let error = |line: &str| {
+            trade_from_flags(&args(line), "2024-06-03")
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("options_tracker add --symbol KO --action buy --quantity 10 --price=-5"),
+            "the price can't be negative"
+        );
+        assert_eq!(
+            error(
+                "options_tracker add --symbol KO --action sell --quantity 1 --price 1 \
+                 --option-type put --strike=0 --expiration 2024-06-21"
+            ),
+            "the strike must be more than zero"
+        );
+
+        // An option needs its strike and expiration; a line stands alone.
+        assert!(Cli::try_parse_from([
+            "options_tracker",
+            "add",
+            "--symbol",
+            "KO",
+            "--action",
+            "buy",
+            "--quantity",
+            "1",
+            "--price",
+            "1",
+            "--option-type",
+            "call",
+        ])
+        .is_err());
+        assert!(
+            Cli::try_parse_from(["options_tracker", "add", "buy 1 KO @ 1", "--symbol", "KO"])
+                .is_err()
+        );
+        assert!(Cli::try_parse_from(["options_tracker"])
+            .unwrap()
+            .command
+            .is_none());
+    }
+
+    #[test]
+    fn report_reads_its_range_and_format() {
+        let cli = Cli::try_parse_from(
+            "options_tracker report fees --from -1m --to t --format json --method lifo"
+                .split_whitespace(),
+        )
+        .unwrap();
+        let Some(Command::Report(args)) = cli.command else {
+            panic!("not report");
+        };
+        assert!(matches!(args.report, ReportKind::Fees));
+        assert!(matches!(args.format, Format::Json));
+        assert_eq!(
+            (args.from.as_deref(), args.to.as_deref()),
+            (Some("-1m"), Some("t"))
+        );
+        assert_eq!(
+            (args.method, args.period),
+            (CostBasisMethod::Lifo, Period::Month)
+        );
+        assert!(Cli::try_parse_from([
+            "options_tracker",
+            "report",
+            "symbol",
+            "--range",
+            "ytd",
+            "--from",
+            "2024-01-01",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn export_takes_its_format_from_the_file() {
+        let format = |line: &str| {
+            let cli = Cli::try_parse_from(line.split_whitespace()).unwrap();
+            match cli.command {
+                Some(Command::Export(args)) => export_format(&args),
+                _ => panic!("not export"),
+            }
+        };
+        assert_eq!(format("options_tracker export"), ExportFormat::Csv);
+        assert_eq!(
+            format("options_tracker export out.JSON"),
+            ExportFormat::Json
+        );
+        assert_eq!(
+            format("options_tracker export --format csv out.json"),
+            ExportFormat::Csv
+        );
+        assert_eq!(
+            format("options_tracker export --format xlsx"),
+            ExportFormat::Xlsx
+        );
+        assert_eq!(
+            format("options_tracker export trades.txt"),
+            ExportFormat::Csv
+        );
+    }
+
+    #[test]
+    fn imports_what_it_exported_as_json() {
+        let dir = std::env::temp_dir().join(format!("options_tracker_cli_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("trades.json").to_str().unwrap().to_string();
+        let config = Config {
+            backup_dir: dir.join("backups").to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let command = |args: &[&str]| {
+            let args = ["options_tracker"].iter().chain(args);
+            Cli::try_parse_from(args).unwrap().command.unwrap()
+        };
+
+        let from = Database::new(":memory:").unwrap();
+        for line in [
+            "sell 2 AAPL 190c 1/19 @ 2.35 fee .66",
+            "buy 100 KO @ 58.10 #div",
+        ] {
+            from.add_trade(&parse_quick_add(line, "2024-01-03").unwrap())
+                .unwrap();
+        }
+        let Command::Export(args) = command(&["export", &file]) else {
+            panic!("not export");
+        };
+        export(&from, &config, args).unwrap();
+
+        let to = Database::new(":memory:").unwrap();
+        let Command::Import(args) = command(&["import", &file]) else {
+            panic!("not import");
+        };
+        import(&to, &config, args).unwrap();
+        let by_symbol = |db: &Database| {
+            let mut trades = db.get_all_trades().unwrap();
+            trades.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+            trades
+        };
+        let (sent, read) = (by_symbol(&from), by_symbol(&to));
+        assert_eq!(read.len(), 2);
+        for (sent, read) in sent.iter().zip(&read) {
+            assert_eq!(read.position_key(), sent.position_key());
+            assert_eq!((read.action, &read.date), (sent.action, &sent.date));
+            assert_eq!(
+                (read.quantity, read.price, read.fees),
+                (sent.quantity, sent.price, sent.fees)
+            );
+            assert_eq!((&read.tags, &read.status), (&sent.tags, &sent.status));
+        }
+
+        // A file none of whose rows read fails.
+        std::fs::write(&file, r#"[{"symbol": "KO"}, {"price": 1}]"#).unwrap();
+        let Command::Import(args) = command(&["import", &file, "--json"]) else {
+            panic!("not import");
+        };
+        assert!(import(&to, &config, args).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1042 @@
+//! Last-price quotes from a market data provider.
+//!
+//! Providers implement [`MarketData`]; [`refresh_quotes`] asks one for every
+//! instrument of interest and stores the answers as marks, where they feed
+//! unrealized P/L exactly like manually entered ones. Stored quotes double as
+//! a cache: one younger than the configured TTL is reused instead of fetched
+//! again, and when a fetch fails the last-known price stays in place. Instruments are named as
+//! in [`PositionKey::instrument`](crate::db::PositionKey::instrument): the
+//! ticker for stock, the OCC symbol for an option contract.
+//!
+//! Providers may also list option chains, so a contract can be picked instead
+//! of typed, and daily price history, which [`fetch_history`] tops up for the
+//! price chart.
+//!
+//! The backends are [`YahooFinance`], [`Polygon`], [`Finnhub`], and
+//! [`CsvQuotes`], a local file of prices. The configured ones are chained by
+//! [`Fallback`], which asks each in priority order until one answers (see
+//! [`configured_provider`]). The HTTP backends fetch through the system
+//! `curl`, so no HTTP or TLS stack is linked into the app. In offline mode
+//! they are left out of the chain, so nothing touches the network and only a
+//! local [`CsvQuotes`] file can answer.
+
+use crate::config::Config;
+use crate::date::{civil_from_days, format_timestamp, format_ymd, parse_unix_day};
+use crate::db::{Database, PriceBar, Quote};
+use crate::occ::OccSymbol;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// A source of last prices.
+pub trait MarketData {
+    /// Short name stored as the quote's source, e.g. `yahoo`.
+    fn name(&self) -> &str;
+
+    /// Last traded price of `instrument` per share.
+    fn last_price(&self, instrument: &str) -> Result<Decimal, String>;
+
+    /// [`last_price`](MarketData::last_price) with the name of the provider
+    /// that answered, which differs from [`name`](MarketData::name) for a
+    /// provider that delegates, like [`Fallback`].
+    fn sourced_price(&self, instrument: &str) -> Result<(Decimal, String), String> {
+        self.last_price(instrument)
+            .map(|price| (price, self.name().to_string()))
+    }
+
+    /// The option chain of `symbol` for one expiration (`YYYY-MM-DD`), or for
+    /// the nearest one when `expiration` is `None`. Providers without option
+    /// data keep this default.
+    fn option_chain(&self, symbol: &str, expiration: Option<&str>) -> Result<OptionChain, String> {
+        let _ = (symbol, expiration);
+        Err(format!("{} does not provide option chains", self.name()))
+    }
+
+    /// Daily bars of `symbol` from `from` through `to` (`YYYY-MM-DD`,
+    /// inclusive), oldest first. Providers without history keep this default.
+    fn daily_history(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<PriceBar>, String> {
+        let _ = (symbol, from, to);
+        Err(format!("{} does not provide price history", self.name()))
+    }
+}
+
+/// One listed contract of an option chain. Prices are per share.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainContract {
+    pub occ: OccSymbol,
+    pub bid: Option<Decimal>,
+    pub ask: Option<Decimal>,
+    pub last: Option<Decimal>,
+    /// Implied volatility in percent.
+    pub iv: Option<Decimal>,
+}
+
+impl ChainContract {
+    /// Midpoint of bid and ask when both are quoted, otherwise the last price.
+    pub fn mid(&self) -> Option<Decimal> {
+        match (self.bid, self.ask) {
+            (Some(bid), Some(ask)) if ask > Decimal::ZERO => Some((bid + ask) / Decimal::TWO),
+            _ => self.last,
+        }
+    }
+}
+
+/// Listed expirations of an underlying and the contracts of one of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OptionChain {
+    /// Every listed expiration, soonest first.
+    pub expirations: Vec<String>,
+    /// Calls then puts, each by ascending strike.
+    pub contracts: Vec<ChainContract>,
+}
+
+/// Yahoo Finance's public chart and options endpoints. Needs `curl` on the
+/// `PATH` and network access.
+#[derive(Debug, Clone)]
+pub struct YahooFinance {
+    pub base_url: String,
+}
+
+impl Default for YahooFinance {
+    fn default() -> Self {
+        YahooFinance {
+            base_url: "https://query1.finance.yahoo.com".to_string(),
+        }
+    }
+}
+
+impl YahooFinance {
+    fn fetch(&self, path: &str) -> Result<String, String> {
+        curl_get(&format!("{}{}", self.base_url, path), None)
+    }
+}
+
+// GETs `url` with the system `curl`, returning the body or curl's error. A
+// `header`, which carries an API key, goes to curl on its standard input
+// rather than its command line, where any user's `ps` would show it.
+fn curl_get(url: &str, header: Option<&str>) -> Result<String, String> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--fail", "--max-time", "10"])
+        .args(["--user-agent", "Mozilla/5.0"])
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if header.is_some() {
+        command.args(["--header", "@-"]).stdin(Stdio::piped());
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("could not run curl: {}", e))?;
+    if let (Some(header), Some(mut stdin)) = (header, child.stdin.take()) {
+        // Dropping `stdin` closes it, so curl reads the header and goes on.
+        stdin
+            .write_all(format!("{}\n", header).as_bytes())
+            .map_err(|e| format!("could not run curl: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("could not run curl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl MarketData for YahooFinance {
+    fn name(&self) -> &str {
+        "yahoo"
+    }
+
+    fn last_price(&self, instrument: &str) -> Result<Decimal, String> {
+        let body = self.fetch(&format!(
+            "/v8/finance/chart/{}?interval=1d&range=1d",
+            instrument
+        ))?;
+        parse_chart_price(&body)
+    }
+
+    fn option_chain(&self, symbol: &str, expiration: Option<&str>) -> Result<OptionChain, String> {
+        let mut path = format!("/v7/finance/options/{}", symbol);
+        if let Some(expiration) = expiration {
+            // Yahoo keys expirations by their midnight-UTC Unix timestamp.
+            let day = parse_unix_day(expiration)
+                .ok_or_else(|| format!("invalid expiration {}", expiration))?;
+            path.push_str(&format!("?date={}", day * SECONDS_PER_DAY));
+        }
+        parse_option_chain(&self.fetch(&path)?)
+    }
+
+    fn daily_history(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<PriceBar>, String> {
+        let start = parse_unix_day(from).ok_or_else(|| format!("invalid date {}", from))?;
+        let end = parse_unix_day(to).ok_or_else(|| format!("invalid date {}", to))?;
+        // `period2` is exclusive.
+        let body = self.fetch(&format!(
+            "/v8/finance/chart/{}?interval=1d&period1={}&period2={}",
+            symbol,
+            start * SECONDS_PER_DAY,
+            (end + 1) * SECONDS_PER_DAY
+        ))?;
+        parse_chart_history(&body, symbol)
+    }
+}
+
+/// Polygon.io's aggregates API. Last prices are the previous session's
+/// close, which is what the free tier offers. Options are quoted by their OCC
+/// symbol with Polygon's `O:` prefix.
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    pub api_key: String,
+    pub base_url: String,
+}
+
+impl Polygon {
+    pub fn new(api_key: &str) -> Self {
+        Polygon {
+            api_key: api_key.to_string(),
+            base_url: "https://api.polygon.io".to_string(),
+        }
+    }
+
+    fn ticker(instrument: &str) -> String {
+        if instrument.parse::<OccSymbol>().is_ok() {
+            format!("O:{}", instrument)
+        } else {
+            instrument.to_string()
+        }
+    }
+
+    fn fetch(&self, path: &str) -> Result<String, String> {
+        curl_get(
+            &format!("{}{}", self.base_url, path),
+            Some(&format!("Authorization: Bearer {}", self.api_key)),
+        )
+    }
+}
+
+impl MarketData for Polygon {
+    fn name(&self) -> &str {
+        "polygon"
+    }
+
+    fn last_price(&self, instrument: &str) -> Result<Decimal, String> {
+        let body = self.fetch(&format!(
+            "/v2/aggs/ticker/{}/prev",
+            Polygon::ticker(instrument)
+        ))?;
+        let bars = parse_polygon_aggregates(&body, instrument)?;
+        bars.last()
+            .map(|bar| bar.close)
+            .ok_or_else(|| "no price in response".to_string())
+    }
+
+    fn daily_history(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<PriceBar>, String> {
+        let body = self.fetch(&format!(
+            "/v2/aggs/ticker/{}/range/1/day/{}/{}?adjusted=true&sort=asc&limit=5000",
+            Polygon::ticker(symbol),
+            from,
+            to
+        ))?;
+        parse_polygon_aggregates(&body, symbol)
+    }
+}
+
+/// Finnhub's quote and candle API. Stock only: option instruments are
+/// refused so a fallback can try the next provider.
+#[derive(Debug, Clone)]
+pub struct Finnhub {
+    pub api_key: String,
+    pub base_url: String,
+}
+
+impl Finnhub {
+    pub fn new(api_key: &str) -> Self {
+        Finnhub {
+            api_key: api_key.to_string(),
+            base_url: "https://finnhub.io/api/v1".to_string(),
+        }
+    }
+
+    fn fetch(&self, path: &str) -> Result<String, String> {
+        curl_get(
+            &format!("{}{}", self.base_url, path),
+            Some(&format!("X-Finnhub-Token: {}", self.api_key)),
+        )
+    }
+
+    fn stock_only(instrument: &str) -> Result<(), String> {
+        match instrument.parse::<OccSymbol>() {
+            Ok(_) => Err("finnhub does not quote options".to_string()),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl MarketData for Finnhub {
+    fn name(&self) -> &str {
+        "finnhub"
+    }
+
+    fn last_price(&self, instrument: &str) -> Result<Decimal, String> {
+        Finnhub::stock_only(instrument)?;
+        parse_finnhub_quote(&self.fetch(&format!("/quote?symbol={}", instrument))?)
+    }
+
+    fn daily_history(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<PriceBar>, String> {
+        Finnhub::stock_only(symbol)?;
+        let start = parse_unix_day(from).ok_or_else(|| format!("invalid date {}", from))?;
+        let end = parse_unix_day(to).ok_or_else(|| format!("invalid date {}", to))?;
+        let body = self.fetch(&format!(
+            "/stock/candle?symbol={}&resolution=D&from={}&to={}",
+            symbol,
+            start * SECONDS_PER_DAY,
+            (end + 1) * SECONDS_PER_DAY - 1
+        ))?;
+        parse_finnhub_candles(&body, symbol)
+    }
+}
+
+/// Prices read from a local CSV file of `instrument,price` lines, for offline
+/// use and testing. A header line, blank lines, and `#` comments are skipped.
+/// The file is read on every lookup, so edits apply on the next refresh.
+#[derive(Debug, Clone)]
+pub struct CsvQuotes {
+    pub path: String,
+}
+
+impl MarketData for CsvQuotes {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn last_price(&self, instrument: &str) -> Result<Decimal, String> {
+        let text = fs::read_to_string(&self.path).map_err(|e| format!("{}: {}", self.path, e))?;
+        parse_csv_prices(&text)
+            .remove(&instrument.to_uppercase())
+            .ok_or_else(|| format!("{} not in {}", instrument, self.path))
+    }
+}
+
+// Reads `instrument,price` lines, skipping any line whose price does not parse.
+fn parse_csv_prices(text: &str) -> HashMap<String, Decimal> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let (instrument, price) = line.split_once(',')?;
+            let price = price.trim().parse().ok()?;
+            Some((instrument.trim().to_uppercase(), price))
+        })
+        .collect()
+}
+
+/// Providers asked in order: each request goes to the first provider, then to
+/// the next whenever one fails, and errors only when every provider did.
+pub struct Fallback {
+    pub providers: Vec<Box<dyn MarketData + Send>>,
+    /// Network providers were left out for offline mode.
+    pub offline: bool,
+}
+
+impl Fallback {
+    // Returns the first provider's answer, or every provider's error.
+    fn first_ok<T>(
+        &self,
+        request: impl Fn(&dyn MarketData) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut errors = Vec::new();
+        for provider in &self.providers {
+            match request(provider.as_ref()) {
+                Ok(answer) => return Ok(answer),
+                Err(e) => errors.push(format!("{}: {}", provider.name(), e)),
+            }
+        }
+        if errors.is_empty() && self.offline {
+            return Err("offline mode is on and no local provider is configured".to_string());
+        }
+        if errors.is_empty() {
+            return Err("no market data provider configured".to_string());
+        }
+        Err(errors.join("; "))
+    }
+}
+
+impl MarketData for Fallback {
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    fn last_price(&self, instrument: &str) -> Result<Decimal, String> {
+        self.sourced_price(instrument).map(|(price, _)| price)
+    }
+
+    fn sourced_price(&self, instrument: &str) -> Result<(Decimal, String), String> {
+        self.first_ok(|provider| provider.sourced_price(instrument))
+    }
+
+    fn option_chain(&self, symbol: &str, expiration: Option<&str>) -> Result<OptionChain, String> {
+        self.first_ok(|provider| provider.option_chain(symbol, expiration))
+    }
+
+    fn daily_history(&self, symbol: &str, from: &str, to: &str) -> Result<Vec<PriceBar>, String> {
+        self.first_ok(|provider| provider.daily_history(symbol, from, to))
+    }
+}
+
+string_enum! {
+    /// A market data backend that can be named in the config.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ProviderKind {
+        Yahoo => "yahoo",
+        Polygon => "polygon",
+        Finnhub => "finnhub",
+        Csv => "csv",
+    }
+    error = "provider",
+}
+
+impl ProviderKind {
+    /// Whether the provider fetches over the network, which offline mode
+    /// forbids.
+    pub fn uses_network(&self) -> bool {
+        !matches!(self, ProviderKind::Csv)
+    }
+}
+
+/// The providers listed in `config`, chained in their configured order. In
+/// offline mode the network ones are left out.
+pub fn configured_provider(config: &Config) -> Fallback {
+    let providers = config
+        .providers
+        .iter()
+        .filter(|kind| !(config.offline && kind.uses_network()))
+        .map(|kind| -> Box<dyn MarketData + Send> {
+            match kind {
+                ProviderKind::Yahoo => Box::new(YahooFinance::default()),
+                ProviderKind::Polygon => Box::new(Polygon::new(
+                    config.polygon_api_key.as_deref().unwrap_or_default(),
+                )),
+                ProviderKind::Finnhub => Box::new(Finnhub::new(
+                    config.finnhub_api_key.as_deref().unwrap_or_default(),
+                )),
+                ProviderKind::Csv => Box::new(CsvQuotes {
+                    path: config.csv_path.clone().unwrap_or_default(),
+                }),
+            }
+        })
+        .collect();
+    Fallback {
+        providers,
+        offline: config.offline,
+    }
+}
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// Parses a JSON number's text so prices keep their decimal digits exactly.
+fn json_decimal(value: &serde_json::Value) -> Option<Decimal> {
+    match value {
+        serde_json::Value::Number(number) => {
+            let text = number.to_string();
+            Decimal::from_str(&text)
+                .or_else(|_| Decimal::from_scientific(&text))
+                .ok()
+        }
+        _ => None,
+    }
+}
+
+// Parses a response body, turning Yahoo's `{"<root>": {"error": ...}}` into
+// an `Err` and returning the root's first result.
+fn first_result(body: &str, root: &str) -> Result<serde_json::Value, String> {
+    let mut json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("invalid response: {}", e))?;
+    let root = json[root].take();
+    if let Some(description) = root["error"]["description"].as_str() {
+        return Err(description.to_string());
+    }
+    match &root["result"][0] {
+        serde_json::Value::Null => Err("no data in response".to_string()),
+        result => Ok(result.clone()),
+    }
+}
+
+// Pulls `chart.result[0].meta.regularMarketPrice` out of a chart response.
+fn parse_chart_price(body: &str) -> Result<Decimal, String> {
+    let result = first_result(body, "chart")?;
+    json_decimal(&result["meta"]["regularMarketPrice"])
+        .ok_or_else(|| "no price in response".to_string())
+}
+
+// Reads the daily bars of a chart response: parallel `timestamp` and
+// `indicators.quote[0]` arrays. Days with a missing price (halts, or today's
+// bar before the open) are skipped.
+fn parse_chart_history(body: &str, symbol: &str) -> Result<Vec<PriceBar>, String> {
+    let result = first_result(body, "chart")?;
+    let timestamps = result["timestamp"].as_array().cloned().unwrap_or_default();
+    let quote = &result["indicators"]["quote"][0];
+    let mut bars = Vec::new();
+    for (i, timestamp) in timestamps.iter().enumerate() {
+        let Some(secs) = timestamp.as_i64() else {
+            continue;
+        };
+        let field = |name: &str| json_decimal(&quote[name][i]);
+        let (Some(open), Some(high), Some(low), Some(close)) =
+            (field("open"), field("high"), field("low"), field("close"))
+        else {
+            continue;
+        };
+        let (year, month, day) = civil_from_days(secs.div_euclid(SECONDS_PER_DAY));
+        bars.push(PriceBar {
+            symbol: symbol.to_string(),
+            date: format_ymd(year, month, day),
+            open,
+            high,
+            low,
+            close,
+            volume: quote["volume"][i].as_i64().unwrap_or(0),
+        });
+    }
+    Ok(bars)
+}
+
+// Reads a Polygon aggregates response: `results` of `{o, h, l, c, v, t}`
+// with `t` in Unix milliseconds. A missing `results` means no bars.
+fn parse_polygon_aggregates(body: &str, symbol: &str) -> Result<Vec<PriceBar>, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("invalid response: {}", e))?;
+    if json["status"].as_str() == Some("ERROR") {
+        return Err(json["error"]
+            .as_str()
+            .unwrap_or("request failed")
+            .to_string());
+    }
+    let results = json["results"].as_array().cloned().unwrap_or_default();
+    Ok(results
+        .iter()
+        .filter_map(|bar| {
+            let millis = bar["t"].as_i64()?;
+            let (year, month, day) = civil_from_days(millis.div_euclid(SECONDS_PER_DAY * 1000));
+            Some(PriceBar {
+                symbol: symbol.to_string(),
+                date: format_ymd(year, month, day),
+                open: json_decimal(&bar["o"])?,
+                high: json_decimal(&bar["h"])?,
+                low: json_decimal(&bar["l"])?,
+                close: json_decimal(&bar["c"])?,
+                volume: json_decimal(&bar["v"])
+                    .and_then(|v| v.trunc().to_string().parse().ok())
+                    .unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+// Reads the current price `c` of a Finnhub quote; Finnhub answers an unknown
+// symbol with zeros rather than an error.
+fn parse_finnhub_quote(body: &str) -> Result<Decimal, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("invalid response: {}", e))?;
+    if let Some(error) = json["error"].as_str() {
+        return Err(error.to_string());
+    }
+    match json_decimal(&json["c"]) {
+        Some(price) if price > Decimal::ZERO => Ok(price),
+        _ => Err("no price in response".to_string()),
+    }
+}
+
+// Reads Finnhub candles: parallel `t`, `o`, `h`, `l`, `c`, `v` arrays, with
+// `s` set to `no_data` when there are none.
+fn parse_finnhub_candles(body: &str, symbol: &str) -> Result<Vec<PriceBar>, String> {
+    let json: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("invalid response: {}", e))?;
+    if let Some(error) = json["error"].as_str() {
+        return Err(error.to_string());
+    }
+    let timestamps = json["t"].as_array().cloned().unwrap_or_default();
+    Ok(timestamps
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| {
+            let (year, month, day) = civil_from_days(t.as_i64()?.div_euclid(SECONDS_PER_DAY));
+            Some(PriceBar {
+                symbol: symbol.to_string(),
+                date: format_ymd(year, month, day),
+                open: json_decimal(&json["o"][i])?,
+                high: json_decimal(&json["h"][i])?,
+                low: json_decimal(&json["l"][i])?,
+                close: json_decimal(&json["c"][i])?,
+                volume: json["v"][i].as_i64().unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+// Reads the expirations and contracts of an options response. Contracts whose
+// symbol is not a valid OCC symbol are skipped.
+fn parse_option_chain(body: &str) -> Result<OptionChain, String> {
+    let result = first_result(body, "optionChain")?;
+    let expirations = result["expirationDates"]
+        .as_array()
+        .map(|dates| {
+            dates
+                .iter()
+                .filter_map(|d| d.as_i64())
+                .map(|secs| {
+                    let (year, month, day) = civil_from_days(secs.div_euclid(SECONDS_PER_DAY));
+                    format_ymd(year, month, day)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut contracts = Vec::new();
+    for side in ["calls", "puts"] {
+        let listed = result["options"][0][side]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let mut side_contracts: Vec<ChainContract> = listed
+            .iter()
+            .filter_map(|c| {
+                let occ: OccSymbol = c["contractSymbol"].as_str()?.parse().ok()?;
+                Some(ChainContract {
+                    occ,
+                    bid: json_decimal(&c["bid"]),
+                    ask: json_decimal(&c["ask"]),
+                    last: json_decimal(&c["lastPrice"]),
+                    iv: json_decimal(&c["impliedVolatility"])
+                        .map(|iv| (iv * Decimal::ONE_HUNDRED).round_dp(2)),
+                })
+            })
+            .collect();
+        side_contracts.sort_by_key(|c| c.occ.strike);
+        contracts.extend(side_contracts);
+    }
+    Ok(OptionChain {
+        expirations,
+        contracts,
+    })
+}
+
+/// Outcome of [`fetch_quotes`] and [`refresh_quotes`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RefreshSummary {
+    /// New quotes from the provider.
+    pub fetched: Vec<Quote>,
+    /// Instruments whose stored quote was still fresh.
+    pub cached: usize,
+    /// Instruments that could not be quoted, with why.
+    pub failures: Vec<(String, String)>,
+}
+
+/// Fetches the last price of each instrument from `provider`, skipping those
+/// whose quote in `stored` is at most `ttl_secs` old at Unix time `now`.
+/// Nothing is saved, so this can run without holding the database.
+pub fn fetch_quotes(
+    provider: &dyn MarketData,
+    instruments: &[String],
+    stored: &HashMap<String, Quote>,
+    ttl_secs: i64,
+    now: i64,
+) -> RefreshSummary {
+    let mut summary = RefreshSummary::default();
+    for instrument in instruments {
+        if let Some(quote) = stored.get(instrument) {
+            if quote.is_fresh(now, ttl_secs) {
+                summary.cached += 1;
+                continue;
+            }
+        }
+        match provider.sourced_price(instrument) {
+            Ok((price, source)) => summary.fetched.push(Quote {
+                instrument: instrument.clone(),
+                price,
+                fetched_at: format_timestamp(now),
+                source,
+            }),
+            Err(e) => summary.failures.push((instrument.clone(), e)),
+        }
+    }
+    summary
+}
+
+/// [`fetch_quotes`] against the stored quotes, saving each new one as its
+/// instrument's mark. Failures leave the stored quote untouched and do not
+/// stop the other instruments from being saved.
+pub fn refresh_quotes(
+    db: &Database,
+    provider: &dyn MarketData,
+    instruments: &[String],
+    ttl_secs: i64,
+    now: i64,
+) -> rusqlite::Result<RefreshSummary> {
+    let summary = fetch_quotes(provider, instruments, &db.get_quotes()?, ttl_secs, now);
+    for quote in &summary.fetched {
+        db.set_quote(quote)?;
+    }
+    Ok(summary)
+}
+
+/// Days of history fetched for a symbol with none stored.
+pub const HISTORY_DAYS: i64 = 365;
+
+/// Fetches the daily bars of `symbol` missing from `stored` (its bars, oldest
+/// first) through `today`: everything after the last stored day, or the last
+/// [`HISTORY_DAYS`] when nothing is stored. Nothing is saved, so this can run
+/// without holding the database.
+pub fn fetch_history(
+    provider: &dyn MarketData,
+    symbol: &str,
+    stored: &[PriceBar],
+    today: &str,
+) -> Result<Vec<PriceBar>, String> {
+    let end = parse_unix_day(today).ok_or_else(|| format!("invalid date {}", today))?;
+    let start = match stored.last() {
+        Some(bar) => parse_unix_day(&bar.date).map_or(end - HISTORY_DAYS, |day| day + 1),
+        None => end - HISTORY_DAYS,
+    };
+    if start > end {
+        return Ok(Vec::new());
+    }
+    let (year, month, day) = civil_from_days(start);
+    provider.daily_history(symbol, &format_ymd(year, month, day), today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date::parse_timestamp;
+    use crate::db::OptionType;
+    use rust_decimal_macros::dec;
+
+    struct FixedPrices(HashMap<&'static str, Decimal>);
+
+    impl MarketData for FixedPrices {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn last_price(&self, instrument: &str) -> Result<Decimal, String> {
+            self.0
+                .get(instrument)
+                .copied()
+                .ok_or_else(|| "unknown symbol".to_string())
+        }
+    }
+
+    #[test]
+    fn parses_chart_responses() {
+        let body = r#"{"chart":{"result":[{"meta":{"symbol":"AAPL",
+            "regularMarketPrice":189.37}}],"error":null}}"#;
+        assert_eq!(parse_chart_price(body), Ok(dec!(189.37)));
+
+        let missing = r#"{"chart":{"result":null,"error":{"code":"Not Found",
+            "description":"No data found, symbol may be delisted"}}}"#;
+        assert_eq!(
+            parse_chart_price(missing),
+            Err("No data found, symbol may be delisted".to_string())
+        );
+        assert!(parse_chart_price("<html>").is_err());
+    }
+
+    #[test]
+    fn parses_option_chains() {
+        let body = r#"{"optionChain":{"result":[{"underlyingSymbol":"AAPL",
+            "expirationDates":[1718928000,1719532800],
+            "options":[{"expirationDate":1718928000,
+              "calls":[
+                {"contractSymbol":"AAPL240621C00190000","strike":190.0,
+                 "lastPrice":2.5,"bid":2.4,"ask":2.6,"impliedVolatility":0.2531},
+                {"contractSymbol":"AAPL240621C00185000","strike":185.0,
+                 "lastPrice":5.1,"impliedVolatility":0.27}],
+              "puts":[
+                {"contractSymbol":"AAPL240621P00180000","strike":180.0,
+                 "lastPrice":1.05,"bid":1.0,"ask":1.1}]}]}],"error":null}}"#;
+        let chain = parse_option_chain(body).unwrap();
+        assert_eq!(chain.expirations, vec!["2024-06-21", "2024-06-28"]);
+        assert_eq!(chain.contracts.len(), 3);
+        // Calls by strike, then puts.
+        assert_eq!(chain.contracts[0].occ.strike, dec!(185));
+        assert_eq!(chain.contracts[0].mid(), Some(dec!(5.1)));
+        assert_eq!(chain.contracts[1].iv, Some(dec!(25.31)));
+        assert_eq!(chain.contracts[1].mid(), Some(dec!(2.5)));
+        assert_eq!(chain.contracts[2].occ.option_type, OptionType::Put);
+        assert_eq!(chain.contracts[2].occ.expiration, "2024-06-21");
+    }
+
+    #[test]
+    fn parses_chart_history() {
+        // 2024-06-03 and 2024-06-04 at the 13:30 UTC open; the second day has
+        // no close yet.
+        let body = r#"{"chart":{"result":[{"meta":{"symbol":"AAPL"},
+            "timestamp":[1717421400,1717507800],
+            "indicators":{"quote":[{"open":[192.9,194.64],"high":[194.99,195.32],
+              "low":[192.52,193.03],"close":[194.03,null],
+              "volume":[50080500,47471400]}]}}],"error":null}}"#;
+        let bars = parse_chart_history(body, "AAPL").unwrap();
+        assert_eq!(
+            bars,
+            vec![PriceBar {
+                symbol: "AAPL".to_string(),
+                date: "2024-06-03".to_string(),
+                open: dec!(192.9),
+                high: dec!(194.99),
+                low: dec!(192.52),
+                close: dec!(194.03),
+                volume: 50080500,
+            }]
+        );
+    }
+
+    #[test]
+    fn history_resumes_after_the_last_stored_bar() {
+        struct Requests(std::cell::RefCell<Vec<(String, String)>>);
+
+        impl MarketData for Requests {
+            fn name(&self) -> &str {
+                "test"
+            }
+
+            fn last_price(&self, _: &str) -> Result<Decimal, String> {
+                Err("no quotes".to_string())
+            }
+
+            fn daily_history(
+                &self,
+                _: &str,
+                from: &str,
+                to: &str,
+            ) -> Result<Vec<PriceBar>, String> {
+                self.0.borrow_mut().push((from.to_string(), to.to_string()));
+                Ok(Vec::new())
+            }
+        }
+
+        let provider = Requests(Default::default());
+        fetch_history(&provider, "AAPL", &[], "2024-06-03").unwrap();
+        let stored = PriceBar {
+            symbol: "AAPL".to_string(),
+            date: "2024-05-31".to_string(),
+            open: dec!(1),
+            high: dec!(1),
+            low: dec!(1),
+            close: dec!(1),
+            volume: 0,
+        };
+        fetch_history(
+            &provider,
+            "AAPL",
+            std::slice::from_ref(&stored),
+            "2024-06-03",
+        )
+        .unwrap();
+        // Up to date: no request at all.
+        let current = PriceBar {
+            date: "2024-06-03".to_string(),
+            ..stored
+        };
+        fetch_history(&provider, "AAPL", &[current], "2024-06-03").unwrap();
+        assert_eq!(
+            provider.0.into_inner(),
+            vec![
+                ("2023-06-04".to_string(), "2024-06-03".to_string()),
+                ("2024-06-01".to_string(), "2024-06-03".to_string()),
+            ]
+        );
+        assert!(FixedPrices(HashMap::new())
+            .daily_history("AAPL", "2024-06-01", "2024-06-03")
+            .is_err());
+    }
+
+    #[test]
+    fn parses_polygon_and_finnhub_responses() {
+        let aggregates = r#"{"ticker":"AAPL","status":"OK","results":[
+            {"o":192.9,"h":194.99,"l":192.52,"c":194.03,"v":50080500.0,"t":1717387200000}]}"#;
+        let bars = parse_polygon_aggregates(aggregates, "AAPL").unwrap();
+        assert_eq!(bars[0].date, "2024-06-03");
+        assert_eq!(bars[0].close, dec!(194.03));
+        assert_eq!(bars[0].volume, 50080500);
+        assert!(
+            parse_polygon_aggregates(r#"{"status":"OK","resultsCount":0}"#, "AAPL")
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            parse_polygon_aggregates(r#"{"status":"ERROR","error":"Unknown API Key"}"#, "AAPL"),
+            Err("Unknown API Key".to_string())
+        );
+        assert_eq!(
+            Polygon::ticker("AAPL240621C00190000"),
+            "O:AAPL240621C00190000"
+        );
+
+        assert_eq!(
+            parse_finnhub_quote(r#"{"c":189.98,"d":1.2,"pc":188.78}"#),
+            Ok(dec!(189.98))
+        );
+        assert!(parse_finnhub_quote(r#"{"c":0,"d":null,"pc":0}"#).is_err());
+        let candles = r#"{"s":"ok","t":[1717372800],"o":[192.9],"h":[194.99],
+            "l":[192.52],"c":[194.03],"v":[50080500]}"#;
+        assert_eq!(
+            parse_finnhub_candles(candles, "AAPL").unwrap()[0].date,
+            "2024-06-03"
+        );
+        assert!(Finnhub::new("key")
+            .last_price("AAPL240621C00190000")
+            .is_err());
+    }
+
+    #[test]
+    fn reads_csv_prices() {
+        let prices = parse_csv_prices(
+            "instrument,price\n# marks from my broker\naapl, 190.50\n\nAAPL240621P00180000,2.15\n",
+        );
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices["AAPL"], dec!(190.50));
+        assert_eq!(prices["AAPL240621P00180000"], dec!(2.15));
+    }
+
+    #[test]
+    fn fallback_tries_providers_in_order() {
+        let fallback = Fallback {
+            providers: vec![
+                Box::new(FixedPrices(HashMap::from([("AAPL", dec!(190))]))),
+                Box::new(CsvQuotes {
+                    path: "does/not/exist.csv".to_string(),
+                }),
+            ],
+            offline: false,
+        };
+        assert_eq!(
+            fallback.sourced_price("AAPL"),
+            Ok((dec!(190), "test".to_string()))
+        );
+        let error = fallback.last_price("MSFT").unwrap_err();
+        assert!(error.starts_with("test: unknown symbol; csv: does/not/exist.csv"));
+        assert!(fallback.option_chain("MSFT", None).is_err());
+
+        let offline = Fallback {
+            providers: vec![],
+            offline: true,
+        };
+        assert_eq!(
+            offline.last_price("AAPL"),
+            Err("offline mode is on and no local provider is configured".to_string())
+        );
+    }
+
+    #[test]
+    fn builds_configured_providers_in_priority_order() {
+        let config = Config::parse(
+            "[market_data]\nproviders = [\"csv\", \"polygon\", \"yahoo\"]\n\
+             [market_data.polygon]\napi_key = \"abc\"\n\
+             [market_data.csv]\npath = \"quotes.csv\"\n",
+        )
+        .unwrap();
+        let names = |config: &Config| -> Vec<String> {
+            configured_provider(config)
+                .providers
+                .iter()
+                .map(|p| p.name().to_string())
+                .collect()
+        };
+        assert_eq!(names(&config), vec!["csv", "polygon", "yahoo"]);
+        assert_eq!(names(&Config::default()), vec!["yahoo"]);
+        let offline = Config {
+            offline: true,
+            ..config
+        };
+        assert_eq!(names(&offline), vec!["csv"]);
+    }
+
+    #[test]
+    fn refresh_saves_quotes_and_reports_failures() {
+        let db = Database::new(":memory:").unwrap();
+        let provider = FixedPrices(HashMap::from([
+            ("AAPL", dec!(190.5)),
+            ("AAPL240621P00180000", dec!(2.15)),
+        ]));
+        let instruments = [
+            "AAPL".to_string(),
+            "AAPL240621P00180000".to_string(),
+            "NOPE".to_string(),
+        ];
+        let now = parse_timestamp("2024-06-03 15:00:00").unwrap();
+        let summary = refresh_quotes(&db, &provider, &instruments, 900, now).unwrap();
+        assert_eq!(summary.fetched.len(), 2);
+        assert_eq!(
+            summary.failures,
+            vec![("NOPE".to_string(), "unknown symbol".to_string())]
+        );
+        let quotes = db.get_quotes().unwrap();
+        assert_eq!(quotes.len(), 2);
+        assert_eq!(quotes["AAPL240621P00180000"].price, dec!(2.15));
+        assert_eq!(quotes["AAPL"].source, "test");
+        assert_eq!(quotes["AAPL"].fetched_at, "2024-06-03 15:00:00");
+    }
+
+    #[test]
+    fn fresh_quotes_are_reused_and_stale_ones_kept_on_failure() {
+        let db = Database::new(":memory:").unwrap();
+        for (instrument, fetched_at) in [("AAPL", "2024-06-03 14:50:00"), ("MSFT", "2024-06-02")] {
+            db.set_quote(&Quote {
+                instrument: instrument.to_string(),
+                price: dec!(100),
+                fetched_at: fetched_at.to_string(),
+                source: "manual".to_string(),
+            })
+            .unwrap();
+        }
+        let provider = FixedPrices(HashMap::new());
+        let now = parse_timestamp("2024-06-03 15:00:00").unwrap();
+        let instruments = ["AAPL".to_string(), "MSFT".to_string()];
+        let summary = refresh_quotes(&db, &provider, &instruments, 900, now).unwrap();
+        // AAPL is ten minutes old; MSFT is stale but the provider is down.
+        assert_eq!(summary.cached, 1);
+        assert!(summary.fetched.is_empty());
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(db.get_quotes().unwrap()["MSFT"].price, dec!(100));
+    }
+
+    #[test]
+    fn api_keys_go_in_headers_not_urls() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                request.push(line.trim_end().to_string());
+                line.clear();
+            }
+            let body = r#"{"c":101.5}"#;
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+            request
+        });
+        let finnhub = Finnhub {
+            api_key: "secret".to_string(),
+            base_url: format!("http://127.0.0.1:{}", port),
+        };
+        assert_eq!(finnhub.last_price("KO").unwrap(), dec!(101.5));
+        let request = server.join().unwrap();
+        assert_eq!(request[0], "GET /quote?symbol=KO HTTP/1.1");
+        assert!(request.iter().any(|line| line == "X-Finnhub-Token: secret"));
+    }
+}
@@ -0,0 +1,620 @@
+//! Versioned schema upgrades.
+//!
+//! The `schema_version` table records each migration applied to a database.
+//! Opening one runs, in order and each in its own transaction, the steps it
+//! hasn't had yet, so an old database is upgraded in place. A database whose
+//! version is newer than this build knows is refused rather than misread.
+//!
+//! Schema changes go in a new step at the end of [`MIGRATIONS`]; a step that
+//! has shipped is never edited, since databases already past it won't run it
+//! again.
+
+use rusqlite::{ffi, params, Connection, Result};
+
+// A schema upgrade, run inside a transaction.
+type Migration = fn(&Connection) -> Result<()>;
+
+/// The upgrade steps in order; step `n` brings a database to version `n + 1`.
+const MIGRATIONS: &[Migration] = &[
+    baseline,
+    accounts,
+    tags,
+    trash,
+    trade_history,
+    trade_search,
+    trade_indexes,
+    attachments,
+    cash_transactions,
+    currencies,
+    brokers,
+    archive,
+];
+
+/// The schema version this build writes.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Applies the migrations `conn` hasn't had. Fails without changing anything
+/// if its schema is newer than [`SCHEMA_VERSION`].
+pub fn migrate(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            applied_at TEXT NOT NULL
+        )",
+        [],
+    )?;
+    check_version(conn)?;
+    let current = schema_version(conn)?;
+    for (version, step) in (1..).zip(MIGRATIONS).skip(current as usize) {
+        let tx = conn.unchecked_transaction()?;
+        step(&tx)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, applied_at) VALUES (?1, datetime('now'))",
+            params![version],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Fails if `conn`'s schema is newer than [`SCHEMA_VERSION`], so this build
+/// would misread it.
+pub fn check_version(conn: &Connection) -> Result<()> {
+    if !table_exists(conn, "schema_version")? {
+        return Ok(());
+    }
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        return Err(rusqlite::Error::SqliteFailure(
+            ffi::Error::new(ffi::SQLITE_CANTOPEN),
+            Some(format!(
+                "the database is at schema version {}, but this version of \
+                 options_tracker only knows up to {}; upgrade options_tracker to open it",
+                current, SCHEMA_VERSION
+            )),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether opening `conn` will upgrade trades saved by an older version. A
+/// new, empty database doesn't count.
+pub fn needs_upgrade(conn: &Connection) -> Result<bool> {
+    if !table_exists(conn, "trades")? {
+        return Ok(false);
+    }
+    if !table_exists(conn, "schema_version")? {
+        return Ok(true);
+    }
+    Ok(schema_version(conn)? < SCHEMA_VERSION)
+}
+
+/// The last migration applied to `conn`, 0 for none.
+pub fn schema_version(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// Trade columns added before the schema was versioned, which databases from
+// then may lack.
+const BASELINE_TRADE_COLUMNS: &[(&str, &str)] = &[
+    ("contract_multiplier", "TEXT"),
+    ("roll_group_id", "INTEGER"),
+    ("strategy_id", "INTEGER REFERENCES strategies(id)"),
+    ("delta", "TEXT"),
+    ("gamma", "TEXT"),
+    ("theta", "TEXT"),
+    ("vega", "TEXT"),
+    ("iv", "TEXT"),
+];
+
+// Version 1: every table as of versioning. Tables an unversioned database
+// already has are kept, with any trade columns it predates added.
+fn baseline(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trades (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            symbol TEXT NOT NULL,
+            trade_type TEXT NOT NULL,
+            action TEXT NOT NULL,
+            price TEXT NOT NULL,
+            quantity TEXT NOT NULL,
+            date TEXT NOT NULL,
+            fees TEXT NOT NULL,
+            comment TEXT,
+            option_type TEXT,
+            strike TEXT,
+            expiration TEXT,
+            status TEXT,
+            contract_multiplier TEXT,
+            assigned_from INTEGER,
+            roll_group_id INTEGER,
+            strategy_id INTEGER REFERENCES strategies(id),
+            delta TEXT,
+            gamma TEXT,
+            theta TEXT,
+            vega TEXT,
+            iv TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS covered_calls (
+            call_id INTEGER PRIMARY KEY REFERENCES trades(id),
+            stock_id INTEGER NOT NULL REFERENCES trades(id)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS strategies (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS lot_matches (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            close_id INTEGER NOT NULL REFERENCES trades(id),
+            open_id INTEGER NOT NULL REFERENCES trades(id),
+            quantity TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS corporate_actions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            symbol TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            date TEXT NOT NULL,
+            ratio TEXT,
+            new_symbol TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS quotes (
+            instrument TEXT PRIMARY KEY,
+            price TEXT NOT NULL,
+            fetched_at TEXT NOT NULL,
+            source TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS alerts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            instrument TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            threshold TEXT NOT NULL,
+            triggered_at TEXT,
+            triggered_value TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS valuation_snapshots (
+            date TEXT PRIMARY KEY,
+            market_value TEXT NOT NULL,
+            cash TEXT NOT NULL,
+            account_value TEXT NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS prices (
+            symbol TEXT NOT NULL,
+            date TEXT NOT NULL,
+            open TEXT NOT NULL,
+            high TEXT NOT NULL,
+            low TEXT NOT NULL,
+            close TEXT NOT NULL,
+            volume INTEGER NOT NULL,
+            PRIMARY KEY (symbol, date)
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS dividends (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            symbol TEXT NOT NULL,
+            date TEXT NOT NULL,
+            amount TEXT NOT NULL,
+            qualified INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS saved_reports (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            filter TEXT NOT NULL,
+            group_by TEXT NOT NULL
+        )",
+        [],
+    )?;
+    let columns = table_columns(conn, "trades")?;
+    for (column, definition) in BASELINE_TRADE_COLUMNS {
+        if !columns.iter().any(|c| c == column) {
+            conn.execute(
+                &format!("ALTER TABLE trades ADD COLUMN {} {}", column, definition),
+                [],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// Version 2: brokerage accounts, each trade optionally in one.
+fn accounts(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE accounts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "ALTER TABLE trades ADD COLUMN account_id INTEGER REFERENCES accounts(id)",
+        [],
+    )?;
+    Ok(())
+}
+
+// Version 3: free-form tags, any number per trade.
+fn tags(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE trade_tags (
+            trade_id INTEGER NOT NULL REFERENCES trades(id),
+            tag_id INTEGER NOT NULL REFERENCES tags(id),
+            PRIMARY KEY (trade_id, tag_id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+// Version 4: deleted trades wait in the Trash until purged.
+fn trash(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE trades ADD COLUMN deleted_at TEXT", [])?;
+    Ok(())
+}
+
+// Version 5: a log of every change to a trade, written by triggers so that
+// no statement can skip it. Rows hold the trade's columns before and after
+// as JSON objects.
+fn trade_history(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE trade_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            trade_id INTEGER NOT NULL,
+            changed_at TEXT NOT NULL,
+            change TEXT NOT NULL,
+            before TEXT,
+            after TEXT
+        )",
+        [],
+    )?;
+    let tag = |row: &str| {
+        format!(
+            "json_object('tag', (SELECT name FROM tags WHERE id = {}.tag_id))",
+            row
+        )
+    };
+    conn.execute_batch(&trade_history_triggers(TRADE_HISTORY_COLUMNS))?;
+    conn.execute_batch(&format!(
+        "CREATE TRIGGER trade_tags_insert_history AFTER INSERT ON trade_tags BEGIN {} END;
+         CREATE TRIGGER trade_tags_delete_history AFTER DELETE ON trade_tags BEGIN {} END;",
+        record_history("NEW.trade_id", "tag_added", "NULL", &tag("NEW")),
+        record_history("OLD.trade_id", "tag_removed", &tag("OLD"), "NULL"),
+    ))
+}
+
+// The trade columns the version 5 history triggers record.
+const TRADE_HISTORY_COLUMNS: &[&str] = &[
+    "symbol",
+    "trade_type",
+    "action",
+    "price",
+    "quantity",
+    "date",
+    "fees",
+    "comment",
+    "option_type",
+    "strike",
+    "expiration",
+    "status",
+    "contract_multiplier",
+    "assigned_from",
+    "roll_group_id",
+    "strategy_id",
+    "delta",
+    "gamma",
+    "theta",
+    "vega",
+    "iv",
+    "account_id",
+    "deleted_at",
+];
+
+// The triggers logging every insert, update, and delete of a trade, with
+// `columns` before and after.
+fn trade_history_triggers(columns: &[&str]) -> String {
+    let json = |row: &str| {
+        let pairs: Vec<String> = columns
+            .iter()
+            .map(|column| format!("'{}', {}.{}", column, row, column))
+            .collect();
+        format!("json_object({})", pairs.join(", "))
+    };
+    format!(
+        "CREATE TRIGGER trades_insert_history AFTER INSERT ON trades BEGIN {} END;
+         CREATE TRIGGER trades_update_history AFTER UPDATE ON trades
+         WHEN {} IS NOT {} BEGIN {} END;
+         CREATE TRIGGER trades_delete_history AFTER DELETE ON trades BEGIN {} END;",
+        record_history("NEW.id", "insert", "NULL", &json("NEW")),
+        json("OLD"),
+        json("NEW"),
+        record_history("NEW.id", "update", &json("OLD"), &json("NEW")),
+        record_history("OLD.id", "delete", &json("OLD"), "NULL"),
+    )
+}
+
+// A statement adding a trade_history row.
+fn record_history(trade_id: &str, change: &str, before: &str, after: &str) -> String {
+    format!(
+        "INSERT INTO trade_history (trade_id, changed_at, change, before, after)
+         VALUES ({}, datetime('now'), '{}', {}, {});",
+        trade_id, change, before, after
+    )
+}
+
+// Version 6: a full-text index over trade symbols and comments, kept
+// current by triggers and filled from the trades already there.
+fn trade_search(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE trades_fts USING fts5(
+            symbol, comment, content = 'trades', content_rowid = 'id'
+         );
+         CREATE TRIGGER trades_insert_search AFTER INSERT ON trades BEGIN
+            INSERT INTO trades_fts (rowid, symbol, comment)
+            VALUES (NEW.id, NEW.symbol, NEW.comment);
+         END;
+         CREATE TRIGGER trades_update_search AFTER UPDATE OF symbol, comment ON trades BEGIN
+            INSERT INTO trades_fts (trades_fts, rowid, symbol, comment)
+            VALUES ('delete', OLD.id, OLD.symbol, OLD.comment);
+            INSERT INTO trades_fts (rowid, symbol, comment)
+            VALUES (NEW.id, NEW.symbol, NEW.comment);
+         END;
+         CREATE TRIGGER trades_delete_search AFTER DELETE ON trades BEGIN
+            INSERT INTO trades_fts (trades_fts, rowid, symbol, comment)
+            VALUES ('delete', OLD.id, OLD.symbol, OLD.comment);
+         END;
+         INSERT INTO trades_fts (trades_fts) VALUES ('rebuild');",
+    )
+}
+
+/// Whether `conn` has a table named `table`.
+pub(crate) fn table_exists(conn: &Connection, table: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        params![table],
+        |row| row.get(0),
+    )
+}
+
+// Version 7: indexes for reading one symbol's trades, trades by date, and
+// one account's trades by date.
+fn trade_indexes(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX trades_symbol ON trades (symbol);
+         CREATE INDEX trades_date ON trades (date);
+         CREATE INDEX trades_account_date ON trades (account_id, date);",
+    )
+}
+
+// Version 8: files attached to trades, either linked by path or stored in
+// the database.
+fn attachments(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE attachments (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            trade_id INTEGER NOT NULL REFERENCES trades(id),
+            name TEXT NOT NULL,
+            path TEXT,
+            data BLOB,
+            added_at TEXT NOT NULL,
+            CHECK ((path IS NULL) != (data IS NULL))
+        );
+        CREATE INDEX attachments_trade ON attachments (trade_id);",
+    )
+}
+
+// Version 9: deposits, withdrawals, interest, and fees, and dividends kept
+// per account like trades.
+fn cash_transactions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE cash_transactions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            date TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            amount TEXT NOT NULL,
+            comment TEXT NOT NULL DEFAULT '',
+            account_id INTEGER REFERENCES accounts(id)
+        );
+        ALTER TABLE dividends ADD COLUMN account_id INTEGER REFERENCES accounts(id);",
+    )
+}
+
+// Version 10: the currency each trade is priced in and each account is kept
+// in, and exchange rates between currencies. Everything so far was in US
+// dollars, history entries included; the history triggers are recreated to
+// record the currency too.
+fn currencies(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE trades ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+         ALTER TABLE accounts ADD COLUMN currency TEXT NOT NULL DEFAULT 'USD';
+         CREATE TABLE fx_rates (
+            date TEXT NOT NULL,
+            from_currency TEXT NOT NULL,
+            to_currency TEXT NOT NULL,
+            rate TEXT NOT NULL,
+            source TEXT NOT NULL,
+            PRIMARY KEY (date, from_currency, to_currency)
+         );
+         DROP TRIGGER trades_insert_history;
+         DROP TRIGGER trades_update_history;
+         DROP TRIGGER trades_delete_history;",
+    )?;
+    conn.execute_batch(&trade_history_triggers(
+        &[TRADE_HISTORY_COLUMNS, &["currency"]].concat(),
+    ))?;
+    default_history_currency(conn)
+}
+
+// Version 11: the broker each trade was placed with; the history triggers
+// are recreated to record it too.
+fn brokers(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "ALTER TABLE trades ADD COLUMN broker TEXT;
+         DROP TRIGGER trades_insert_history;
+         DROP TRIGGER trades_update_history;
+         DROP TRIGGER trades_delete_history;",
+    )?;
+    conn.execute_batch(&trade_history_triggers(
+        &[TRADE_HISTORY_COLUMNS, &["currency", "broker"]].concat(),
+    ))
+}
+
+// Version 12: trades of closed years moved out of `trades`, each kept whole
+// as JSON, with per year, account, and symbol totals of them.
+fn archive(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE archived_trades (
+            id INTEGER PRIMARY KEY,
+            year INTEGER NOT NULL,
+            trade TEXT NOT NULL
+        );
+        CREATE INDEX archived_trades_year ON archived_trades (year);
+        CREATE TABLE archive_summaries (
+            year INTEGER NOT NULL,
+            account_id INTEGER REFERENCES accounts(id),
+            symbol TEXT NOT NULL,
+            trades INTEGER NOT NULL,
+            cash_flow TEXT NOT NULL,
+            fees TEXT NOT NULL,
+            realized TEXT NOT NULL
+        );",
+    )
+}
+
+/// Gives the trades in history entries from before currencies were tracked
+/// the US dollars they were in, so undoing back to them sets a currency.
+pub(crate) fn default_history_currency(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "UPDATE trade_history SET
+            before = CASE WHEN before IS NULL THEN NULL
+                          ELSE json_insert(before, '$.currency', 'USD') END,
+            after = CASE WHEN after IS NULL THEN NULL
+                         ELSE json_insert(after, '$.currency', 'USD') END
+         WHERE change IN ('insert', 'update', 'delete')",
+        [],
+    )?;
+    Ok(())
+}
+
+// The names of `table`'s columns.
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt.query_map([], |row| row.get(1))?;
+    columns.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+
+    #[test]
+    fn upgrades_an_unversioned_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE trades (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                symbol TEXT NOT NULL,
+                trade_type TEXT NOT NULL,
+                action TEXT NOT NULL,
+                price TEXT NOT NULL,
+                quantity TEXT NOT NULL,
+                date TEXT NOT NULL,
+                fees TEXT NOT NULL,
+                comment TEXT,
+                option_type TEXT,
+                strike TEXT,
+                expiration TEXT,
+                status TEXT,
+                assigned_from INTEGER
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO trades (symbol, trade_type, action, price, quantity, date, fees)
+             VALUES ('AAPL', 'stock', 'buy_to_open', '150', '10', '2024-01-05', '0')",
+            [],
+        )
+        .unwrap();
+
+        assert!(needs_upgrade(&conn).unwrap());
+        migrate(&conn).unwrap();
+        assert!(!needs_upgrade(&conn).unwrap());
+        assert_eq!(schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        let columns = table_columns(&conn, "trades").unwrap();
+        assert!(columns.iter().any(|c| c == "iv"));
+        let symbol: String = conn
+            .query_row("SELECT symbol FROM trades WHERE delta IS NULL", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(symbol, "AAPL");
+
+        // Running again applies nothing new.
+        migrate(&conn).unwrap();
+        let applied: i64 = conn
+            .query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(applied, SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn refuses_a_newer_database() {
+        let path = std::env::temp_dir().join(format!(
+            "options_tracker_newer_schema_{}.db",
+            std::process::id()
+        ));
+        let conn = Connection::open(&path).unwrap();
+        migrate(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_version (version, applied_at) VALUES (?1, '2030-01-01')",
+            params![SCHEMA_VERSION + 1],
+        )
+        .unwrap();
+        drop(conn);
+
+        let result = Database::new(path.to_str().unwrap());
+        std::fs::remove_file(&path).ok();
+        let error = result.err().expect("a newer database should be refused");
+        assert!(error.to_string().contains("schema version"));
+    }
+}
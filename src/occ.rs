@@ -0,0 +1,169 @@
+//! OCC (Options Clearing Corporation) option symbols.
+//!
+//! An OCC symbol packs an option contract into one string: the underlying root,
+//! the expiration as `YYMMDD`, `C`/`P`, and the strike times 1000 as 8 digits —
+//! e.g. `AAPL240119C00190000` is the AAPL 2024-01-19 $190 call. The official OSI
+//! form pads the root with spaces to 6 characters (`AAPL  240119C00190000`);
+//! both forms are accepted on input and the compact form is produced by
+//! [`Display`](std::fmt::Display).
+
+use crate::date::parse_unix_day;
+use crate::db::OptionType;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use std::fmt;
+use std::str::FromStr;
+
+/// Length of the fixed `YYMMDD` + `C`/`P` + 8-digit strike suffix.
+const SUFFIX_LEN: usize = 15;
+/// Longest underlying root allowed by the OSI format.
+const MAX_ROOT_LEN: usize = 6;
+/// The strike is encoded as an integer number of thousandths of a dollar.
+const STRIKE_SCALE: u32 = 3;
+
+/// A parsed OCC option symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OccSymbol {
+    pub underlying: String,
+    /// Expiration as ISO 8601 `YYYY-MM-DD`, matching how trades store it.
+    pub expiration: String,
+    pub option_type: OptionType,
+    pub strike: Decimal,
+}
+
+impl OccSymbol {
+    /// The 21-character OSI form with the root space-padded to 6 characters.
+    pub fn to_padded(&self) -> String {
+        format!(
+            "{:<width$}{}",
+            self.underlying,
+            self.suffix(),
+            width = MAX_ROOT_LEN
+        )
+    }
+
+    fn suffix(&self) -> String {
+        let yymmdd: String = self
+            .expiration
+            .get(2..)
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| *c != '-')
+            .collect();
+        let cp = match self.option_type {
+            OptionType::Call => 'C',
+            OptionType::Put => 'P',
+        };
+        let thousandths = (self.strike * Decimal::from(1000))
+            .trunc()
+            .to_i64()
+            .unwrap_or(0);
+        format!("{}{}{:08}", yymmdd, cp, thousandths)
+    }
+}
+
+impl FromStr for OccSymbol {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let invalid = || format!("Invalid OCC symbol: {}", value);
+        if !value.is_ascii() || value.len() <= SUFFIX_LEN {
+            return Err(invalid());
+        }
+
+        let (root, suffix) = value.split_at(value.len() - SUFFIX_LEN);
+        let underlying = root.trim_end().to_uppercase();
+        if underlying.is_empty()
+            || underlying.len() > MAX_ROOT_LEN
+            || !underlying
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.')
+        {
+            return Err(invalid());
+        }
+
+        let (yymmdd, rest) = suffix.split_at(6);
+        let (cp, strike_digits) = rest.split_at(1);
+        if !yymmdd.bytes().all(|b| b.is_ascii_digit())
+            || !strike_digits.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+
+        let expiration = format!("20{}-{}-{}", &yymmdd[0..2], &yymmdd[2..4], &yymmdd[4..6]);
+        if parse_unix_day(&expiration).is_none() {
+            return Err(invalid());
+        }
+
+        let option_type = match cp {
+            "C" | "c" => OptionType::Call,
+            "P" | "p" => OptionType::Put,
+            _ => return Err(invalid()),
+        };
+
+        let thousandths: i64 = strike_digits.parse().map_err(|_| invalid())?;
+        let strike = Decimal::new(thousandths, STRIKE_SCALE).normalize();
+
+        Ok(OccSymbol {
+            underlying,
+            expiration,
+            option_type,
+            strike,
+        })
+    }
+}
+
+impl fmt::Display for OccSymbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.underlying, self.suffix())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn parses_compact_symbol() {
+        let occ: OccSymbol = "AAPL240119C00190000".parse().unwrap();
+        assert_eq!(occ.underlying, "AAPL");
+        assert_eq!(occ.expiration, "2024-01-19");
+        assert_eq!(occ.option_type, OptionType::Call);
+        assert_eq!(occ.strike, dec!(190));
+    }
+
+    #[test]
+    fn parses_padded_symbol_and_fractional_strike() {
+        let occ: OccSymbol = "SPY   240621P00512500".parse().unwrap();
+        assert_eq!(occ.underlying, "SPY");
+        assert_eq!(occ.option_type, OptionType::Put);
+        assert_eq!(occ.strike, dec!(512.5));
+    }
+
+    #[test]
+    fn round_trips_both_forms() {
+        for raw in [
+            "AAPL240119C00190000",
+            "F250117P00012500",
+            "BRKB261218C01234567",
+        ] {
+            let occ: OccSymbol = raw.parse().unwrap();
+            assert_eq!(occ.to_string(), raw);
+            let padded = occ.to_padded();
+            assert_eq!(padded.len(), 21);
+            assert_eq!(padded.parse::<OccSymbol>().unwrap(), occ);
+        }
+    }
+
+    #[test]
+    fn rejects_plain_tickers_and_malformed_symbols() {
+        assert!("AAPL".parse::<OccSymbol>().is_err());
+        assert!("240119C00190000".parse::<OccSymbol>().is_err()); // no root
+        assert!("AAPL240119X00190000".parse::<OccSymbol>().is_err()); // not C/P
+        assert!("AAPL241319C00190000".parse::<OccSymbol>().is_err()); // month 13
+        assert!("AAPL240119C0019000A".parse::<OccSymbol>().is_err());
+        assert!("TOOLONGX240119C00190000".parse::<OccSymbol>().is_err());
+    }
+}
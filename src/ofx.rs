@@ -0,0 +1,399 @@
+//! Trade import from OFX and QFX statements.
+//!
+//! Many brokers still offer their history as an OFX investment statement
+//! (QFX is the same with Quicken's extras). Version 1 files are SGML, where a
+//! value's closing tag is optional, and version 2 files are XML; [`Element`]
+//! reads either into one tree. The statement lists each security once, in
+//! `SECLIST`, and its transactions refer to them by CUSIP or other id:
+//! `BUYSTOCK`, `SELLSTOCK`, `BUYOPT`, `SELLOPT`, and the like become trades,
+//! and `CLOSUREOPT` expirations, assignments, and exercises end the options
+//! they name (see [`crate::brokers::end_options`]). Income, transfers, and
+//! other transactions are left out.
+
+use crate::brokers::{end_options, leg, option_description, push, OptionEvent};
+use crate::db::{Action, OptionStatus, OptionType, TradeType, OPTION_MULTIPLIER};
+use crate::import::{parse_action, parse_date, parse_number, ReadResult};
+use crate::occ::OccSymbol;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Whether `text` is an OFX statement rather than CSV.
+pub fn is_ofx(text: &str) -> bool {
+    let head = text.get(..text.len().min(1024)).unwrap_or(text);
+    head.contains("OFXHEADER") || text.contains("<OFX>")
+}
+
+/// The trades in an OFX statement. Errors are numbered by the transaction's
+/// place in the statement's transaction list, the first being 1.
+pub fn read_ofx(text: &str) -> ReadResult {
+    let mut result = ReadResult::default();
+    let root = Element::parse(text);
+    let securities = securities(&root);
+    let Some(list) = root.find("INVTRANLIST") else {
+        return result;
+    };
+    let mut events = Vec::new();
+    // The list's start and end dates are values; transactions are aggregates.
+    let transactions = list.children.iter().filter(|e| !e.children.is_empty());
+    for (index, transaction) in transactions.enumerate() {
+        let number = index + 1;
+        let kind = transaction.name.as_str();
+        if kind == "CLOSUREOPT" {
+            match closure(transaction, &securities, number) {
+                Ok(event) => events.push(event),
+                Err(e) => result.errors.push((number, e)),
+            }
+            continue;
+        }
+        let Some(side) = transaction
+            .child("INVBUY")
+            .or_else(|| transaction.child("INVSELL"))
+        else {
+            continue;
+        };
+        // Bonds aren't tracked.
+        if kind.ends_with("DEBT") {
+            continue;
+        }
+        let trade = (|| {
+            let action = match ["BUYTYPE", "SELLTYPE", "OPTBUYTYPE", "OPTSELLTYPE"]
+                .iter()
+                .find_map(|name| transaction.value(name))
+            {
+                Some(raw) => parse_action(raw)?,
+                None if side.name == "INVBUY" => Action::BuyToOpen,
+                None => Action::SellToClose,
+            };
+            let security = security(side, &securities)?;
+            let multiplier = match transaction.value("SHPERCTRCT") {
+                Some(raw) => parse_number(raw, "shares per contract")?,
+                None => security.multiplier,
+            };
+            let fees = ["COMMISSION", "FEES", "TAXES"]
+                .iter()
+                .filter_map(|name| side.value(name))
+                .map(|raw| parse_number(raw, "fees").map(|fee| fee.abs()))
+                .sum::<Result<Decimal, String>>()?;
+            let mut trade = leg(
+                &security.ticker,
+                security.option.clone(),
+                action,
+                parse_number(side.value("UNITS").unwrap_or_default(), "units")?,
+                parse_number(side.value("UNITPRICE").unwrap_or_default(), "unit price")?,
+                date(side.value("DTTRADE"))?,
+                fees,
+            );
+            if trade.trade_type == TradeType::Option && multiplier != OPTION_MULTIPLIER {
+                trade.contract_multiplier = Some(multiplier);
+            }
+            Ok(trade)
+        })();
+        push(&mut result, number, trade);
+    }
+    end_options(&mut result, events);
+    result
+}
+
+// A security as `SECLIST` describes it.
+#[derive(Debug, Clone)]
+struct Security {
+    ticker: String,
+    option: Option<OccSymbol>,
+    multiplier: Decimal,
+}
+
+// The statement's securities by id.
+fn securities(root: &Element) -> HashMap<String, Security> {
+    let mut securities = HashMap::new();
+    let Some(list) = root.find("SECLIST") else {
+        return securities;
+    };
+    let id_of = |info: &Element| {
+        info.find("SECID")
+            .and_then(|id| id.value("UNIQUEID"))
+            .map(str::to_string)
+    };
+    // Stocks first, so options can name their underlying.
+    for info in list.children.iter().filter(|info| info.name != "OPTINFO") {
+        if let (Some(id), Some(ticker)) = (id_of(info), info.value("TICKER")) {
+            let security = Security {
+                ticker: ticker.to_uppercase(),
+                option: None,
+                multiplier: Decimal::ONE,
+            };
+            securities.insert(id, security);
+        }
+    }
+    for info in list.children.iter().filter(|info| info.name == "OPTINFO") {
+        let Some(id) = id_of(info) else {
+            continue;
+        };
+        let ticker = info.value("TICKER").unwrap_or_default();
+        // The underlying's id is the one outside `SECINFO`.
+        let underlying = info
+            .child("SECID")
+            .and_then(|id| id.value("UNIQUEID"))
+            .and_then(|id| securities.get(id))
+            .map(|stock| stock.ticker.clone())
+            .unwrap_or_else(|| {
+                ticker
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphabetic())
+                    .collect::<String>()
+                    .to_uppercase()
+            });
+        let option = option_description(ticker)
+            .or_else(|| option_description(info.value("SECNAME").unwrap_or_default()))
+            .or_else(|| {
+                Some(OccSymbol {
+                    underlying: underlying.clone(),
+                    expiration: date(info.value("DTEXPIRE")).ok()?,
+                    option_type: match info.value("OPTTYPE")? {
+                        "PUT" => OptionType::Put,
+                        "CALL" => OptionType::Call,
+                        _ => return None,
+                    },
+                    strike: info
+                        .value("STRIKEPRICE")?
+                        .parse::<Decimal>()
+                        .ok()?
+                        .normalize(),
+                })
+            });
+        let multiplier = info
+            .value("SHPERCTRCT")
+            .and_then(|raw| raw.parse().ok())
+            .unwrap_or(OPTION_MULTIPLIER);
+        let security = Security {
+            ticker: underlying,
+            option,
+            multiplier,
+        };
+        securities.insert(id, security);
+    }
+    securities
+}
+
+// The security a transaction's `SECID` names.
+fn security<'a>(
+    transaction: &Element,
+    securities: &'a HashMap<String, Security>,
+) -> Result<&'a Security, String> {
+    let id = transaction
+        .find("SECID")
+        .and_then(|id| id.value("UNIQUEID"))
+        .ok_or("no security id")?;
+    securities
+        .get(id)
+        .ok_or_else(|| format!("security {} is not in the statement's list", id))
+}
+
+// The option event a `CLOSUREOPT` records.
+fn closure(
+    transaction: &Element,
+    securities: &HashMap<String, Security>,
+    number: usize,
+) -> Result<OptionEvent, String> {
+    let status = match transaction.value("OPTACTION") {
+        Some("EXPIRE") => OptionStatus::Expired,
+        Some("ASSIGN") => OptionStatus::Assigned,
+        Some("EXERCISE") => OptionStatus::Exercised,
+        other => return Err(format!("unknown option action {:?}", other.unwrap_or(""))),
+    };
+    let security = security(transaction, securities)?;
+    let option = security
+        .option
+        .clone()
+        .ok_or_else(|| format!("{} is not an option", security.ticker))?;
+    Ok(OptionEvent {
+        row: number,
+        option,
+        date: date(transaction.value("DTTRADE"))?,
+        status,
+        quantity: parse_number(transaction.value("UNITS").unwrap_or_default(), "units")?.abs(),
+    })
+}
+
+// OFX dates are `YYYYMMDD`, often followed by a time and zone.
+fn date(raw: Option<&str>) -> Result<String, String> {
+    let raw = raw.ok_or("no date")?;
+    match (raw.get(0..4), raw.get(4..6), raw.get(6..8)) {
+        (Some(year), Some(month), Some(day)) => parse_date(&format!("{}-{}-{}", year, month, day)),
+        _ => Err(format!("date \"{}\" is not YYYYMMDD", raw)),
+    }
+}
+
+/// An OFX element: an aggregate of child elements, or a value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    /// Reads a statement into a nameless root holding its top-level
+    /// elements. A tag followed by text is a value and needs no closing tag;
+    /// any other tag holds what follows up to its closing tag, or to the end
+    /// of its parent when that's missing.
+    pub fn parse(text: &str) -> Element {
+        let mut stack = vec![Element::default()];
+        // Skip the SGML header or XML declaration.
+        let body = text.find('<').map_or("", |at| &text[at..]);
+        let mut rest = body;
+        while let Some(open) = rest.find('<') {
+            let value = rest[..open].trim();
+            let leaf = stack.len() > 1 && stack.last().is_some_and(|e| e.children.is_empty());
+            if !value.is_empty() && leaf {
+                let mut element = stack.pop().unwrap_or_default();
+                element.text = unescape(value);
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(element);
+                }
+            }
+            let Some(close) = rest[open..].find('>') else {
+                break;
+            };
+            let tag = rest[open + 1..open + close].trim();
+            rest = &rest[open + close + 1..];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                let name = name.trim().to_uppercase();
+                // A value's closing tag in XML finds nothing left to close.
+                if let Some(at) = stack.iter().skip(1).rposition(|e| e.name == name) {
+                    while stack.len() > at + 1 {
+                        Element::close(&mut stack);
+                    }
+                }
+            } else {
+                stack.push(Element {
+                    name: tag.to_uppercase(),
+                    ..Default::default()
+                });
+            }
+        }
+        while stack.len() > 1 {
+            Element::close(&mut stack);
+        }
+        stack.pop().unwrap_or_default()
+    }
+
+    // Moves the innermost open element into its parent.
+    fn close(stack: &mut Vec<Element>) {
+        if let Some(element) = stack.pop() {
+            if let Some(parent) = stack.last_mut() {
+                parent.children.push(element);
+            }
+        }
+    }
+
+    /// The first child named `name`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|e| e.name == name)
+    }
+
+    /// The first element named `name` under this one, at any depth.
+    pub fn find(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|e| {
+            if e.name == name {
+                Some(e)
+            } else {
+                e.find(name)
+            }
+        })
+    }
+
+    /// The text of the first value named `name` under this one.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.find(name)
+            .map(|e| e.text.as_str())
+            .filter(|text| !text.is_empty())
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Action;
+    use rust_decimal_macros::dec;
+
+    const STATEMENT: &str = "OFXHEADER:100\n\
+DATA:OFXSGML\n\
+VERSION:102\n\
+\n\
+<OFX><INVSTMTMSGSRSV1><INVSTMTTRNRS><INVSTMTRS><INVTRANLIST>\n\
+<DTSTART>20240101<DTEND>20240220\n\
+<BUYSTOCK><INVBUY><INVTRAN><FITID>1<DTTRADE>20240103120000.000[-5:EST]</INVTRAN>\n\
+<SECID><UNIQUEID>191216100<UNIQUEIDTYPE>CUSIP</SECID><UNITS>100<UNITPRICE>59.80\n\
+<COMMISSION>0<TOTAL>-5980<SUBACCTSEC>CASH<SUBACCTFUND>CASH</INVBUY><BUYTYPE>BUY</BUYSTOCK>\n\
+<SELLOPT><INVSELL><INVTRAN><FITID>2<DTTRADE>20240105</INVTRAN>\n\
+<SECID><UNIQUEID>AAPL240216P180<UNIQUEIDTYPE>OTHER</SECID><UNITS>-2<UNITPRICE>1.25\n\
+<COMMISSION>1.30<FEES>0.04<TOTAL>248.66<SUBACCTSEC>CASH<SUBACCTFUND>CASH</INVSELL>\n\
+<OPTSELLTYPE>SELLTOOPEN<SHPERCTRCT>100</SELLOPT>\n\
+<INCOME><INVTRAN><FITID>3<DTTRADE>20240110</INVTRAN><SECID><UNIQUEID>191216100\n\
+<UNIQUEIDTYPE>CUSIP</SECID><INCOMETYPE>DIV<TOTAL>46.00</INCOME>\n\
+<CLOSUREOPT><INVTRAN><FITID>4<DTTRADE>20240216</INVTRAN><SECID><UNIQUEID>AAPL240216P180\n\
+<UNIQUEIDTYPE>OTHER</SECID><OPTACTION>EXPIRE<UNITS>1<SHPERCTRCT>100</CLOSUREOPT>\n\
+<BUYOPT><INVBUY><INVTRAN><FITID>5<DTTRADE>20240109</INVTRAN>\n\
+<SECID><UNIQUEID>999<UNIQUEIDTYPE>OTHER</SECID><UNITS>1<UNITPRICE>0.50\n\
+</INVBUY><OPTBUYTYPE>BUYTOCLOSE</BUYOPT>\n\
+</INVTRANLIST></INVSTMTRS></INVSTMTTRNRS></INVSTMTMSGSRSV1>\n\
+<SECLISTMSGSRSV1><SECLIST>\n\
+<OPTINFO><SECINFO><SECID><UNIQUEID>AAPL240216P180<UNIQUEIDTYPE>OTHER</SECID>\n\
+<SECNAME>PUT APPLE INC $180 EXP 02/16/24<TICKER>AAPL240216P180</SECINFO>\n\
+<OPTTYPE>PUT<STRIKEPRICE>180<DTEXPIRE>20240216<SHPERCTRCT>100</OPTINFO>\n\
+<STOCKINFO><SECINFO><SECID><UNIQUEID>191216100<UNIQUEIDTYPE>CUSIP</SECID>\n\
+<SECNAME>COCA-COLA CO<TICKER>KO</SECINFO></STOCKINFO>\n\
+</SECLIST></SECLISTMSGSRSV1></OFX>\n";
+
+    #[test]
+    fn reads_sgml_statement() {
+        assert!(is_ofx(STATEMENT));
+        assert!(!is_ofx("Date,Symbol\n2024-01-05,AAPL\n"));
+        let read = read_ofx(STATEMENT);
+        assert_eq!(
+            read.errors,
+            vec![(5, "security 999 is not in the statement's list".to_string())]
+        );
+        assert_eq!(read.trades.len(), 3);
+        let ko = &read.trades[0];
+        assert_eq!((ko.symbol.as_str(), ko.action), ("KO", Action::BuyToOpen));
+        assert_eq!(ko.date, "2024-01-03");
+        assert_eq!(ko.quantity, dec!(100));
+
+        // One of the two puts expired; the other is still open.
+        let put = &read.trades[1];
+        assert_eq!(put.symbol, "AAPL");
+        assert_eq!(put.action, Action::SellToOpen);
+        assert_eq!(put.strike, Some(dec!(180)));
+        assert_eq!(put.expiration.as_deref(), Some("2024-02-16"));
+        assert_eq!(put.fees, dec!(1.34));
+        assert_eq!(put.quantity, dec!(1));
+        assert_eq!(put.status, Some(OptionStatus::Expired));
+        assert_eq!(read.trades[2].status, Some(OptionStatus::Open));
+    }
+
+    #[test]
+    fn reads_xml_elements() {
+        let root = Element::parse(
+            "<?xml version=\"1.0\"?>\n<?OFX OFXHEADER=\"200\"?>\n\
+             <OFX><SECINFO><SECNAME>AT&amp;T INC</SECNAME><TICKER>T</TICKER>\
+             <MEMO></MEMO></SECINFO></OFX>",
+        );
+        let info = root.find("SECINFO").unwrap();
+        assert_eq!(info.children.len(), 3);
+        assert_eq!(info.value("SECNAME"), Some("AT&T INC"));
+        assert_eq!(info.value("TICKER"), Some("T"));
+        assert_eq!(info.value("MEMO"), None);
+    }
+}
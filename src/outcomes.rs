@@ -0,0 +1,192 @@
+//! How sold options ended.
+//!
+//! Every contract sold to open ends one of three ways: it expires worthless,
+//! it is assigned (or exercised), or it is bought back — rolls included. The
+//! lot matches of short option lots say which, contract by contract, so a
+//! partly bought-back position counts both ways. Outcomes are totaled by
+//! symbol and by the absolute delta the option was sold at, when one was
+//! entered, to show how strike selection plays out.
+
+use crate::cost_basis::LotMatch;
+use crate::db::{OptionStatus, Trade, TradeType};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
+
+string_enum! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Outcome {
+        Expired => "expired",
+        Assigned => "assigned",
+        BoughtBack => "bought back",
+    }
+    error = "outcome",
+}
+
+/// Contracts sold in one group, a symbol or a delta bucket, by how they ended.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutcomeGroup {
+    pub name: String,
+    pub expired: Decimal,
+    pub assigned: Decimal,
+    pub bought_back: Decimal,
+    /// Realized P/L of the contracts.
+    pub realized: Decimal,
+}
+
+impl OutcomeGroup {
+    pub fn contracts(&self) -> Decimal {
+        self.expired + self.assigned + self.bought_back
+    }
+
+    pub fn count(&self, outcome: Outcome) -> Decimal {
+        match outcome {
+            Outcome::Expired => self.expired,
+            Outcome::Assigned => self.assigned,
+            Outcome::BoughtBack => self.bought_back,
+        }
+    }
+
+    /// Share of the contracts that ended in `outcome`, in percent; `None`
+    /// without any contracts.
+    pub fn percent(&self, outcome: Outcome) -> Option<Decimal> {
+        let contracts = self.contracts();
+        (!contracts.is_zero()).then(|| self.count(outcome) / contracts * Decimal::ONE_HUNDRED)
+    }
+}
+
+/// Outcomes by symbol and by delta bucket, each ordered by name.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutcomeReport {
+    pub by_symbol: Vec<OutcomeGroup>,
+    pub by_delta: Vec<OutcomeGroup>,
+    pub total: OutcomeGroup,
+}
+
+/// Outcomes of the options sold to open in `trades` that `matches` closed.
+pub fn option_outcomes(trades: &[Trade], matches: &[LotMatch]) -> OutcomeReport {
+    let by_id: HashMap<i64, &Trade> = trades
+        .iter()
+        .filter_map(|t| t.id.map(|id| (id, t)))
+        .collect();
+    let mut by_symbol = BTreeMap::new();
+    let mut by_delta = BTreeMap::new();
+    let mut total = OutcomeGroup {
+        name: "Total".to_string(),
+        ..Default::default()
+    };
+    for lot in matches {
+        if !lot.short || lot.key.trade_type != TradeType::Option {
+            continue;
+        }
+        let Some(sold) = lot.open_id.and_then(|id| by_id.get(&id)) else {
+            continue;
+        };
+        let outcome = match (lot.close_id, &sold.status) {
+            (Some(_), _) => Outcome::BoughtBack,
+            (None, Some(OptionStatus::Expired)) => Outcome::Expired,
+            (None, _) => Outcome::Assigned,
+        };
+        add(group(&mut by_symbol, sold.symbol.clone()), outcome, lot);
+        add(
+            group(&mut by_delta, delta_bucket(sold.greeks.delta)),
+            outcome,
+            lot,
+        );
+        add(&mut total, outcome, lot);
+    }
+    OutcomeReport {
+        by_symbol: by_symbol.into_values().collect(),
+        by_delta: by_delta.into_values().collect(),
+        total,
+    }
+}
+
+/// The tenth of absolute delta an option was sold at, e.g. `0.20-0.30`, with
+/// everything from 0.50 up together.
+pub fn delta_bucket(delta: Option<Decimal>) -> String {
+    let Some(delta) = delta else {
+        return "no delta".to_string();
+    };
+    let tenths = (delta.abs() * Decimal::TEN).floor();
+    if tenths >= Decimal::from(5) {
+        return "0.50+".to_string();
+    }
+    let low = tenths / Decimal::TEN;
+    format!("{:.2}-{:.2}", low, low + Decimal::new(1, 1))
+}
+
+fn group(groups: &mut BTreeMap<String, OutcomeGroup>, name: String) -> &mut OutcomeGroup {
+    groups.entry(name.clone()).or_insert_with(|| OutcomeGroup {
+        name,
+        ..Default::default()
+    })
+}
+
+fn add(group: &mut OutcomeGroup, outcome: Outcome, lot: &LotMatch) {
+    match outcome {
+        Outcome::Expired => group.expired += lot.quantity,
+        Outcome::Assigned => group.assigned += lot.quantity,
+        Outcome::BoughtBack => group.bought_back += lot.quantity,
+    }
+    group.realized += lot.realized;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, CostBasisMethod};
+    use crate::db::{Action, Greeks, OptionType};
+    use rust_decimal_macros::dec;
+
+    fn put(
+        id: i64,
+        symbol: &str,
+        action: Action,
+        quantity: Decimal,
+        delta: Option<Decimal>,
+    ) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: symbol.to_string(),
+            trade_type: TradeType::Option,
+            action,
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(50)),
+            expiration: Some(format!("2024-0{}-21", id)),
+            price: dec!(1),
+            quantity,
+            date: "2024-01-02".to_string(),
+            status: Some(OptionStatus::Open),
+            greeks: Greeks {
+                delta,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn counts_how_sold_contracts_ended() {
+        let mut expired = put(1, "KO", Action::SellToOpen, dec!(2), Some(dec!(-0.15)));
+        expired.status = Some(OptionStatus::Expired);
+        let mut assigned = put(2, "KO", Action::SellToOpen, dec!(1), Some(dec!(-0.32)));
+        assigned.status = Some(OptionStatus::Assigned);
+        // Three sold, two bought back, one still open.
+        let sold = put(3, "PEP", Action::SellToOpen, dec!(3), None);
+        let mut bought = put(3, "PEP", Action::BuyToClose, dec!(2), None);
+        bought.id = Some(4);
+        let trades = vec![expired, assigned, sold, bought];
+
+        let report = option_outcomes(&trades, &match_lots(&trades, CostBasisMethod::Fifo, &[]));
+        assert_eq!(report.by_symbol.len(), 2);
+        assert_eq!(report.by_symbol[0].expired, dec!(2));
+        assert_eq!(report.by_symbol[0].assigned, dec!(1));
+        assert_eq!(report.by_symbol[1].bought_back, dec!(2));
+        assert_eq!(report.total.contracts(), dec!(5));
+        assert_eq!(report.total.percent(Outcome::Expired), Some(dec!(40)));
+
+        let buckets: Vec<&str> = report.by_delta.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(buckets, vec!["0.10-0.20", "0.30-0.40", "no delta"]);
+        assert_eq!(delta_bucket(Some(dec!(0.72))), "0.50+");
+    }
+}
@@ -0,0 +1,175 @@
+//! Payoff at expiration for option positions.
+//!
+//! The P/L of a set of legs held to expiration is a piecewise-linear function
+//! of the underlying price: flat or sloped between strikes, with a kink at
+//! each strike. Evaluating it at zero and at every strike, plus the slope above
+//! the highest strike, is enough to find its break-even prices and its maximum
+//! profit and loss. Any mix of legs works — a single option, a spread, a
+//! condor, or options together with stock.
+//!
+//! Premiums and fees enter through each leg's cash flow. Options that were
+//! assigned, exercised, or expired keep their cash flow but no longer pay out
+//! at expiration. All legs are treated as expiring together.
+
+use crate::db::{OptionStatus, OptionType, Trade, TradeType};
+use rust_decimal::Decimal;
+
+/// Break-even prices and P/L extremes of a position at expiration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Payoff {
+    /// Underlying prices at which the position breaks even, ascending.
+    pub break_evens: Vec<Decimal>,
+    /// Largest profit, or `None` when it is unlimited.
+    pub max_profit: Option<Decimal>,
+    /// Largest loss (negative or zero), or `None` when it is unlimited.
+    pub max_loss: Option<Decimal>,
+}
+
+/// P/L of `legs` at expiration if the underlying settles at `price`.
+pub fn pl_at(legs: &[Trade], price: Decimal) -> Decimal {
+    legs.iter()
+        .map(|leg| leg.cash_flow() + settlement_value(leg, price))
+        .sum()
+}
+
+// What the leg is worth at `price` on expiration: intrinsic value for live
+// options, market value for stock, positive for what is held and negative
+// for what is owed.
+fn settlement_value(leg: &Trade, price: Decimal) -> Decimal {
+    let sign = if leg.action.is_buy() {
+        Decimal::ONE
+    } else {
+        -Decimal::ONE
+    };
+    match leg.trade_type {
+        TradeType::Stock => sign * price * leg.quantity,
+        TradeType::Option => {
+            let resolved = matches!(
+                leg.status,
+                Some(OptionStatus::Assigned | OptionStatus::Exercised | OptionStatus::Expired)
+            );
+            let intrinsic = match (leg.option_type, leg.strike) {
+                (Some(OptionType::Call), Some(strike)) if !resolved => {
+                    (price - strike).max(Decimal::ZERO)
+                }
+                (Some(OptionType::Put), Some(strike)) if !resolved => {
+                    (strike - price).max(Decimal::ZERO)
+                }
+                _ => Decimal::ZERO,
+            };
+            sign * intrinsic * leg.quantity * leg.multiplier()
+        }
+    }
+}
+
+// Change in P/L per $1 rise of the underlying above every strike.
+fn upper_slope(legs: &[Trade]) -> Decimal {
+    let above: Decimal = legs
+        .iter()
+        .filter_map(|leg| leg.strike)
+        .max()
+        .unwrap_or(Decimal::ZERO)
+        + Decimal::ONE;
+    pl_at(legs, above + Decimal::ONE) - pl_at(legs, above)
+}
+
+/// Analyzes `legs` held to expiration.
+pub fn analyze(legs: &[Trade]) -> Payoff {
+    let mut prices: Vec<Decimal> = legs.iter().filter_map(|leg| leg.strike).collect();
+    prices.push(Decimal::ZERO);
+    prices.sort();
+    prices.dedup();
+    let values: Vec<Decimal> = prices.iter().map(|p| pl_at(legs, *p)).collect();
+    let slope = upper_slope(legs);
+
+    let mut break_evens = Vec::new();
+    for i in 0..prices.len() {
+        let (x0, v0) = (prices[i], values[i]);
+        if v0 == Decimal::ZERO {
+            break_evens.push(x0);
+            continue;
+        }
+        if let (Some(x1), Some(v1)) = (prices.get(i + 1), values.get(i + 1)) {
+            if (v0 < Decimal::ZERO) != (*v1 < Decimal::ZERO) && *v1 != Decimal::ZERO {
+                break_evens.push(x0 + (*x1 - x0) * -v0 / (*v1 - v0));
+            }
+        } else if slope != Decimal::ZERO && (v0 < Decimal::ZERO) == (slope > Decimal::ZERO) {
+            break_evens.push(x0 - v0 / slope);
+        }
+    }
+    let break_evens = break_evens.into_iter().map(|b| b.normalize()).collect();
+
+    let highest = values.iter().copied().max().unwrap_or(Decimal::ZERO);
+    let lowest = values.iter().copied().min().unwrap_or(Decimal::ZERO);
+    Payoff {
+        break_evens,
+        max_profit: (slope <= Decimal::ZERO).then_some(highest),
+        max_loss: (slope >= Decimal::ZERO).then_some(lowest.min(Decimal::ZERO)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Action;
+    use rust_decimal_macros::dec;
+
+    fn leg(action: Action, option_type: OptionType, strike: Decimal, price: Decimal) -> Trade {
+        Trade {
+            symbol: "SPY".to_string(),
+            trade_type: TradeType::Option,
+            action,
+            price,
+            quantity: dec!(1),
+            option_type: Some(option_type),
+            strike: Some(strike),
+            expiration: Some("2024-06-21".to_string()),
+            status: Some(OptionStatus::Open),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn short_put() {
+        let payoff = analyze(&[leg(Action::SellToOpen, OptionType::Put, dec!(100), dec!(2))]);
+        assert_eq!(payoff.break_evens, vec![dec!(98)]);
+        assert_eq!(payoff.max_profit, Some(dec!(200)));
+        // Assigned at 100 with the stock worthless, less the premium.
+        assert_eq!(payoff.max_loss, Some(dec!(-9800)));
+    }
+
+    #[test]
+    fn long_call_has_unlimited_profit() {
+        let payoff = analyze(&[leg(Action::BuyToOpen, OptionType::Call, dec!(50), dec!(3))]);
+        assert_eq!(payoff.break_evens, vec![dec!(53)]);
+        assert_eq!(payoff.max_profit, None);
+        assert_eq!(payoff.max_loss, Some(dec!(-300)));
+    }
+
+    #[test]
+    fn iron_condor() {
+        let legs = [
+            leg(Action::BuyToOpen, OptionType::Put, dec!(90), dec!(0.5)),
+            leg(Action::SellToOpen, OptionType::Put, dec!(95), dec!(1.5)),
+            leg(Action::SellToOpen, OptionType::Call, dec!(105), dec!(1.5)),
+            leg(Action::BuyToOpen, OptionType::Call, dec!(110), dec!(0.5)),
+        ];
+        let payoff = analyze(&legs);
+        // $2 net credit on $5-wide wings.
+        assert_eq!(payoff.break_evens, vec![dec!(93), dec!(107)]);
+        assert_eq!(payoff.max_profit, Some(dec!(200)));
+        assert_eq!(payoff.max_loss, Some(dec!(-300)));
+    }
+
+    #[test]
+    fn short_strangle_has_unlimited_loss() {
+        let legs = [
+            leg(Action::SellToOpen, OptionType::Put, dec!(95), dec!(1)),
+            leg(Action::SellToOpen, OptionType::Call, dec!(105), dec!(1)),
+        ];
+        let payoff = analyze(&legs);
+        assert_eq!(payoff.break_evens, vec![dec!(93), dec!(107)]);
+        assert_eq!(payoff.max_profit, Some(dec!(200)));
+        assert_eq!(payoff.max_loss, None);
+    }
+}
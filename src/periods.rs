@@ -0,0 +1,189 @@
+//! Profit/loss bucketed by calendar period.
+//!
+//! Realized P/L lands in the period its lot was closed (see
+//! [`LotMatch::close_date`]), so an option opened in March and bought back in
+//! May counts toward May. Fees and the trade count go by each trade's own date.
+
+use crate::cost_basis::LotMatch;
+use crate::db::Trade;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+string_enum! {
+    /// How long each bucket of a [`period_report`] is.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum Period {
+        #[default]
+        Month => "month",
+        Quarter => "quarter",
+        Year => "year",
+    }
+    error = "period",
+}
+
+impl Period {
+    /// The bucket an ISO `YYYY-MM-DD` date falls in: `2024-06`, `2024-Q2`, or
+    /// `2024`. Labels sort chronologically.
+    pub fn label(&self, date: &str) -> String {
+        let year = date.get(..4).unwrap_or(date);
+        let month = date.get(5..7).and_then(|m| m.parse::<u32>().ok());
+        match (self, month) {
+            (Period::Month, Some(month)) => format!("{}-{:02}", year, month),
+            (Period::Quarter, Some(month)) => format!("{}-Q{}", year, month.div_ceil(3)),
+            _ => year.to_string(),
+        }
+    }
+}
+
+/// One period's totals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PeriodReport {
+    pub period: String,
+    /// Realized P/L of lots closed in the period, fees included.
+    pub realized: Decimal,
+    /// Fees paid on trades made in the period.
+    pub fees: Decimal,
+    pub trade_count: usize,
+}
+
+/// Totals per `period` of `trades`, with realized P/L from their lot
+/// `matches`, oldest period first. Periods without trades or closes are left
+/// out.
+pub fn period_report(trades: &[Trade], matches: &[LotMatch], period: Period) -> Vec<PeriodReport> {
+    let mut reports: BTreeMap<String, PeriodReport> = BTreeMap::new();
+    for trade in trades {
+        let report = bucket(&mut reports, period.label(&trade.date));
+        report.fees += trade.fees;
+        report.trade_count += 1;
+    }
+    for lot in matches {
+        bucket(&mut reports, period.label(&lot.close_date)).realized += lot.realized;
+    }
+    reports.into_values().collect()
+}
+
+/// Each symbol's realized P/L running total at the end of every `period`
+/// in which any lot of `matches` was closed, oldest first. All symbols share
+/// those periods, so a symbol's total stays flat through periods it had no
+/// closes in.
+pub fn cumulative_by_symbol(
+    matches: &[LotMatch],
+    period: Period,
+) -> BTreeMap<String, Vec<Decimal>> {
+    let mut periods: BTreeMap<String, BTreeMap<&str, Decimal>> = BTreeMap::new();
+    for lot in matches {
+        *periods
+            .entry(period.label(&lot.close_date))
+            .or_default()
+            .entry(&lot.key.symbol)
+            .or_default() += lot.realized;
+    }
+    let mut totals: BTreeMap<String, Vec<Decimal>> = BTreeMap::new();
+    for lot in matches {
+        totals.entry(lot.key.symbol.clone()).or_default();
+    }
+    for closed in periods.values() {
+        for (symbol, series) in totals.iter_mut() {
+            let last = series.last().copied().unwrap_or_default();
+            series.push(last + closed.get(symbol.as_str()).copied().unwrap_or_default());
+        }
+    }
+    totals
+}
+
+fn bucket(reports: &mut BTreeMap<String, PeriodReport>, label: String) -> &mut PeriodReport {
+    reports
+        .entry(label.clone())
+        .or_insert_with(|| PeriodReport {
+            period: label,
+            ..Default::default()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, CostBasisMethod};
+    use crate::db::{Action, OptionStatus, OptionType, TradeType};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn labels_periods() {
+        assert_eq!(Period::Month.label("2024-06-03"), "2024-06");
+        assert_eq!(Period::Quarter.label("2024-06-03"), "2024-Q2");
+        assert_eq!(Period::Quarter.label("2024-10-01"), "2024-Q4");
+        assert_eq!(Period::Year.label("2024-06-03"), "2024");
+    }
+
+    #[test]
+    fn buckets_realized_pl_by_close_date() {
+        let option = |id: i64, action: Action, price: Decimal, date: &str| Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action,
+            price,
+            quantity: dec!(1),
+            date: date.to_string(),
+            fees: dec!(0.65),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(180)),
+            expiration: Some("2024-06-21".to_string()),
+            status: Some(OptionStatus::Open),
+            ..Default::default()
+        };
+        let trades = vec![
+            option(1, Action::SellToOpen, dec!(3), "2024-03-28"),
+            option(2, Action::BuyToClose, dec!(1), "2024-05-02"),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+
+        let months = period_report(&trades, &matches, Period::Month);
+        assert_eq!(months.len(), 2);
+        assert_eq!(
+            months[0],
+            PeriodReport {
+                period: "2024-03".to_string(),
+                realized: Decimal::ZERO,
+                fees: dec!(0.65),
+                trade_count: 1,
+            }
+        );
+        // $200 of premium kept, less both fees.
+        assert_eq!(months[1].realized, dec!(198.70));
+
+        let quarters = period_report(&trades, &matches, Period::Quarter);
+        assert_eq!(quarters[0].period, "2024-Q1");
+        assert_eq!(quarters[1].period, "2024-Q2");
+        let years = period_report(&trades, &matches, Period::Year);
+        assert_eq!(years.len(), 1);
+        assert_eq!(years[0].trade_count, 2);
+        assert_eq!(years[0].fees, dec!(1.30));
+    }
+
+    #[test]
+    fn running_totals_share_the_periods() {
+        let stock = |id: i64, symbol: &str, action: Action, price: Decimal, date: &str| Trade {
+            id: Some(id),
+            symbol: symbol.to_string(),
+            trade_type: TradeType::Stock,
+            action,
+            price,
+            quantity: dec!(10),
+            date: date.to_string(),
+            ..Default::default()
+        };
+        let trades = vec![
+            stock(1, "AAPL", Action::BuyToOpen, dec!(100), "2024-01-05"),
+            stock(2, "AAPL", Action::SellToClose, dec!(110), "2024-02-05"),
+            stock(3, "KO", Action::BuyToOpen, dec!(60), "2024-01-05"),
+            stock(4, "KO", Action::SellToClose, dec!(55), "2024-03-05"),
+            stock(5, "AAPL", Action::BuyToOpen, dec!(100), "2024-03-06"),
+            stock(6, "AAPL", Action::SellToClose, dec!(130), "2024-04-01"),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let totals = cumulative_by_symbol(&matches, Period::Month);
+        assert_eq!(totals["AAPL"], [dec!(100), dec!(100), dec!(400)]);
+        assert_eq!(totals["KO"], [dec!(0), dec!(-50), dec!(-50)]);
+    }
+}
@@ -0,0 +1,299 @@
+//! Open positions aggregated from the trade ledger.
+//!
+//! Positions are built from the lots the cost-basis engine leaves open (see
+//! [`open_lots`]), grouped per [`PositionKey`] (a stock symbol or a single
+//! option contract) and side. Stock carries a signed share balance, so selling
+//! shares you don't hold shows up as a short position. Average cost is the
+//! engine's average-cost basis for the side.
+//!
+//! Option positions carry the greeks of the most recent trade in the contract
+//! that has any entered, scaled to the whole position by
+//! [`Position::exposure`]; [`portfolio_greeks`] sums those across positions.
+
+use crate::cost_basis::{open_lots, CostBasisMethod, OpenLot};
+use crate::date::days_to_expiration;
+use crate::db::{Greeks, PositionKey, Trade, TradeType};
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// A currently open position in one stock or option contract.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub key: PositionKey,
+    /// Open quantity in shares or contracts: positive long, negative short.
+    pub quantity: Decimal,
+    /// Shares per unit (the contract multiplier for options, 1 for stock).
+    pub multiplier: Decimal,
+    /// Average price per share paid (long) or received (short) for the open
+    /// quantity, with fees folded in.
+    pub average_cost: Decimal,
+    /// Date of the oldest lot still open.
+    pub opened: String,
+    /// Days from `opened` until today.
+    pub days_held: i64,
+    /// Per-share greeks of the contract; always empty for stock.
+    pub greeks: Greeks,
+}
+
+/// Greeks of a whole position or portfolio: share-equivalent delta and gamma,
+/// and theta and vega in dollars.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Exposure {
+    pub delta: Decimal,
+    pub gamma: Decimal,
+    /// Dollars gained or lost per day.
+    pub theta: Decimal,
+    /// Dollars gained or lost per point of implied volatility.
+    pub vega: Decimal,
+}
+
+/// Net exposure across positions.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PortfolioGreeks {
+    pub exposure: Exposure,
+    /// Option positions left out because their greeks were never entered.
+    pub missing: usize,
+}
+
+impl Position {
+    pub fn is_short(&self) -> bool {
+        self.quantity < Decimal::ZERO
+    }
+
+    /// Total cost (long) or proceeds (short) of the open quantity.
+    pub fn cost_basis(&self) -> Decimal {
+        self.average_cost * self.quantity.abs() * self.multiplier
+    }
+
+    /// P/L of the position if it were closed at `mark` per share.
+    pub fn unrealized(&self, mark: Decimal) -> Decimal {
+        (mark - self.average_cost) * self.quantity * self.multiplier
+    }
+
+    /// The position's greeks scaled by its signed size. Stock counts as one
+    /// delta per share. `None` for an option with no greeks entered; greeks
+    /// left blank count as zero.
+    pub fn exposure(&self) -> Option<Exposure> {
+        if self.key.trade_type == TradeType::Stock {
+            return Some(Exposure {
+                delta: self.quantity,
+                ..Default::default()
+            });
+        }
+        if self.greeks.is_empty() {
+            return None;
+        }
+        let scale = self.quantity * self.multiplier;
+        let scaled = |greek: Option<Decimal>| greek.unwrap_or(Decimal::ZERO) * scale;
+        Some(Exposure {
+            delta: scaled(self.greeks.delta),
+            gamma: scaled(self.greeks.gamma),
+            theta: scaled(self.greeks.theta),
+            vega: scaled(self.greeks.vega),
+        })
+    }
+}
+
+/// Sums the exposure of every position.
+pub fn portfolio_greeks(positions: &[Position]) -> PortfolioGreeks {
+    let mut total = PortfolioGreeks::default();
+    for position in positions {
+        match position.exposure() {
+            Some(exposure) => {
+                total.exposure.delta += exposure.delta;
+                total.exposure.gamma += exposure.gamma;
+                total.exposure.theta += exposure.theta;
+                total.exposure.vega += exposure.vega;
+            }
+            None => total.missing += 1,
+        }
+    }
+    total
+}
+
+/// Every open position in `trades`, ordered by position key (long before
+/// short for the same key). `today` is ISO `YYYY-MM-DD`.
+pub fn open_positions(trades: &[Trade], today: &str) -> Vec<Position> {
+    let mut by_side: BTreeMap<(PositionKey, bool), Vec<OpenLot>> = BTreeMap::new();
+    for lot in open_lots(trades, CostBasisMethod::Average, &[]) {
+        by_side
+            .entry((lot.key.clone(), lot.short))
+            .or_default()
+            .push(lot);
+    }
+
+    // Latest greeks entered per contract, by trade date then id.
+    let mut dated: Vec<&Trade> = trades.iter().filter(|t| !t.greeks.is_empty()).collect();
+    dated.sort_by(|a, b| (&a.date, a.id).cmp(&(&b.date, b.id)));
+    let latest_greeks: BTreeMap<PositionKey, Greeks> = dated
+        .into_iter()
+        .map(|t| (t.position_key(), t.greeks))
+        .collect();
+
+    by_side
+        .into_iter()
+        .map(|((key, short), lots)| {
+            let quantity: Decimal = lots.iter().map(|l| l.quantity).sum();
+            // Average cost leaves every lot on a side at the same price.
+            let first = &lots[0];
+            let opened = lots
+                .iter()
+                .map(|l| l.opened.clone())
+                .min()
+                .unwrap_or_default();
+            Position {
+                quantity: if short { -quantity } else { quantity },
+                multiplier: first.multiplier,
+                average_cost: first.price,
+                days_held: days_to_expiration(&opened, today).unwrap_or(0),
+                opened,
+                greeks: latest_greeks.get(&key).copied().unwrap_or_default(),
+                key,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionStatus, OptionType, TradeType};
+    use rust_decimal_macros::dec;
+
+    fn stock(id: i64, date: &str, action: Action, price: Decimal, quantity: Decimal) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Stock,
+            action,
+            price,
+            quantity,
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nets_buys_against_sells_with_average_cost() {
+        let mut first = stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(100));
+        first.fees = dec!(10);
+        let trades = vec![
+            first,
+            stock(2, "2024-01-10", Action::BuyToOpen, dec!(110), dec!(100)),
+            stock(3, "2024-01-20", Action::SellToClose, dec!(120), dec!(50)),
+        ];
+        let positions = open_positions(&trades, "2024-01-31");
+        assert_eq!(positions.len(), 1);
+        let position = &positions[0];
+        assert_eq!(position.quantity, dec!(150));
+        // (100.10 * 100 + 110 * 100) / 200; the sale leaves the average alone.
+        assert_eq!(position.average_cost, dec!(105.05));
+        assert_eq!(position.cost_basis(), dec!(15757.5));
+        assert_eq!(position.opened, "2024-01-02");
+        assert_eq!(position.days_held, 29);
+    }
+
+    #[test]
+    fn flat_positions_disappear_and_reopen_fresh() {
+        let trades = vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-01-05", Action::SellToClose, dec!(105), dec!(10)),
+            stock(3, "2024-02-01", Action::BuyToOpen, dec!(90), dec!(5)),
+        ];
+        let positions = open_positions(&trades, "2024-02-01");
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].quantity, dec!(5));
+        assert_eq!(positions[0].average_cost, dec!(90));
+        assert_eq!(positions[0].opened, "2024-02-01");
+    }
+
+    #[test]
+    fn short_options_and_resolved_contracts() {
+        let put = Trade {
+            id: Some(1),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            price: dec!(2),
+            quantity: dec!(2),
+            date: "2024-01-02".to_string(),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(100)),
+            expiration: Some("2024-02-16".to_string()),
+            status: Some(OptionStatus::Open),
+            ..Default::default()
+        };
+        let mut expired = put.clone();
+        expired.id = Some(2);
+        expired.strike = Some(dec!(95));
+        expired.status = Some(OptionStatus::Expired);
+
+        let positions = open_positions(&[put, expired], "2024-01-10");
+        assert_eq!(positions.len(), 1);
+        assert!(positions[0].is_short());
+        assert_eq!(positions[0].quantity, dec!(-2));
+        assert_eq!(positions[0].multiplier, dec!(100));
+        assert_eq!(positions[0].cost_basis(), dec!(400));
+        // Short two contracts at $2: buying back at $0.50 makes $300.
+        assert_eq!(positions[0].unrealized(dec!(0.5)), dec!(300));
+    }
+
+    #[test]
+    fn overselling_stock_opens_a_short() {
+        let trades = vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-01-05", Action::SellToClose, dec!(105), dec!(25)),
+        ];
+        let positions = open_positions(&trades, "2024-01-10");
+        assert_eq!(positions.len(), 1);
+        assert!(positions[0].is_short());
+        assert_eq!(positions[0].quantity, dec!(-15));
+        assert_eq!(positions[0].average_cost, dec!(105));
+        assert_eq!(positions[0].opened, "2024-01-05");
+        // Short 15 at $105: a $100 mark is $75 in the money.
+        assert_eq!(positions[0].unrealized(dec!(100)), dec!(75));
+    }
+
+    #[test]
+    fn portfolio_greeks_use_latest_entry_and_position_size() {
+        let open = Trade {
+            id: Some(1),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            price: dec!(2),
+            quantity: dec!(2),
+            date: "2024-01-02".to_string(),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(100)),
+            expiration: Some("2024-02-16".to_string()),
+            status: Some(OptionStatus::Open),
+            greeks: Greeks {
+                delta: Some(dec!(-0.30)),
+                theta: Some(dec!(-0.04)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        // A later partial close carries fresher greeks for the same contract.
+        let mut close = open.clone();
+        close.id = Some(2);
+        close.date = "2024-01-09".to_string();
+        close.action = Action::BuyToClose;
+        close.quantity = dec!(1);
+        close.greeks.delta = Some(dec!(-0.25));
+        let mut call = open.clone();
+        call.id = Some(3);
+        call.option_type = Some(OptionType::Call);
+        call.greeks = Greeks::default();
+        let shares = stock(4, "2024-01-02", Action::BuyToOpen, dec!(95), dec!(50));
+
+        let positions = open_positions(&[open, close, call, shares], "2024-01-10");
+        let total = portfolio_greeks(&positions);
+        // Short one put: -(-0.25 * 100) = +25 delta, -(-0.04 * 100) = +$4/day;
+        // plus 50 shares. The call has no greeks and is left out.
+        assert_eq!(total.exposure.delta, dec!(75));
+        assert_eq!(total.exposure.theta, dec!(4));
+        assert_eq!(total.missing, 1);
+    }
+}
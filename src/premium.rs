@@ -0,0 +1,161 @@
+//! Option premium income for sellers.
+//!
+//! Counts only the short side of options: premium received when selling to
+//! open, less what buying back to close cost, less the fees on both. Bought
+//! options and stock are left out, so the figures are the income from writing
+//! options alone. Amounts land in the month of the trade, and each month
+//! carries the running total for its calendar year.
+
+use crate::db::{Action, Trade, TradeType};
+use crate::periods::Period;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+
+/// Premium on one symbol in one month.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PremiumRow {
+    pub symbol: String,
+    /// Premium received on options sold to open.
+    pub collected: Decimal,
+    /// Cost of buying short options back.
+    pub bought_back: Decimal,
+    pub fees: Decimal,
+}
+
+impl PremiumRow {
+    pub fn net(&self) -> Decimal {
+        self.collected - self.bought_back - self.fees
+    }
+}
+
+/// A month's premium by symbol.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PremiumMonth {
+    /// `YYYY-MM`.
+    pub month: String,
+    /// By symbol.
+    pub rows: Vec<PremiumRow>,
+    /// Net premium from the start of the month's year through its end.
+    pub year_to_date: Decimal,
+}
+
+impl PremiumMonth {
+    pub fn net(&self) -> Decimal {
+        self.rows.iter().map(PremiumRow::net).sum()
+    }
+}
+
+/// Net premium per month and symbol of `trades`, oldest month first.
+pub fn premium_income(trades: &[Trade]) -> Vec<PremiumMonth> {
+    let mut months: BTreeMap<String, BTreeMap<String, PremiumRow>> = BTreeMap::new();
+    for trade in trades {
+        if trade.trade_type != TradeType::Option
+            || !matches!(trade.action, Action::SellToOpen | Action::BuyToClose)
+        {
+            continue;
+        }
+        let row = months
+            .entry(Period::Month.label(&trade.date))
+            .or_default()
+            .entry(trade.symbol.clone())
+            .or_insert_with(|| PremiumRow {
+                symbol: trade.symbol.clone(),
+                ..Default::default()
+            });
+        let gross = trade.price * trade.quantity * trade.multiplier();
+        if trade.action == Action::SellToOpen {
+            row.collected += gross;
+        } else {
+            row.bought_back += gross;
+        }
+        row.fees += trade.fees;
+    }
+
+    let mut year = String::new();
+    let mut year_to_date = Decimal::ZERO;
+    months
+        .into_iter()
+        .map(|(month, rows)| {
+            let mut month = PremiumMonth {
+                rows: rows.into_values().collect(),
+                month,
+                year_to_date: Decimal::ZERO,
+            };
+            let month_year = Period::Year.label(&month.month);
+            if month_year != year {
+                year = month_year;
+                year_to_date = Decimal::ZERO;
+            }
+            year_to_date += month.net();
+            month.year_to_date = year_to_date;
+            month
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{OptionStatus, OptionType};
+    use rust_decimal_macros::dec;
+
+    fn option(symbol: &str, action: Action, price: Decimal, date: &str) -> Trade {
+        Trade {
+            symbol: symbol.to_string(),
+            trade_type: TradeType::Option,
+            action,
+            price,
+            quantity: dec!(1),
+            date: date.to_string(),
+            fees: dec!(1),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(100)),
+            expiration: Some("2025-01-17".to_string()),
+            status: Some(OptionStatus::Open),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nets_buybacks_and_fees_with_a_yearly_running_total() {
+        let trades = vec![
+            option("AAPL", Action::SellToOpen, dec!(3), "2024-11-04"),
+            option("AAPL", Action::BuyToClose, dec!(1), "2024-11-20"),
+            option("MSFT", Action::SellToOpen, dec!(2), "2024-11-05"),
+            option("AAPL", Action::SellToOpen, dec!(4), "2024-12-02"),
+            option("AAPL", Action::SellToOpen, dec!(5), "2025-01-06"),
+            // Long options and stock are not premium income.
+            option("TSLA", Action::BuyToOpen, dec!(6), "2024-12-03"),
+            Trade {
+                symbol: "AAPL".to_string(),
+                action: Action::SellToClose,
+                price: dec!(190),
+                quantity: dec!(100),
+                date: "2024-12-04".to_string(),
+                ..Default::default()
+            },
+        ];
+        let months = premium_income(&trades);
+        assert_eq!(months.len(), 3);
+
+        let november = &months[0];
+        assert_eq!(november.month, "2024-11");
+        assert_eq!(
+            november.rows[0],
+            PremiumRow {
+                symbol: "AAPL".to_string(),
+                collected: dec!(300),
+                bought_back: dec!(100),
+                fees: dec!(2),
+            }
+        );
+        assert_eq!(november.rows[1].net(), dec!(199));
+        assert_eq!(november.net(), dec!(397));
+        assert_eq!(november.year_to_date, dec!(397));
+
+        assert_eq!(months[1].rows.len(), 1);
+        assert_eq!(months[1].year_to_date, dec!(796));
+        // A new year starts the running total over.
+        assert_eq!(months[2].year_to_date, dec!(499));
+    }
+}
@@ -0,0 +1,273 @@
+//! One-line trade entry.
+//!
+//! [`parse_quick_add`] reads a fill written the way it is said:
+//!
+//! ```text
+//! sell 2 AAPL 190c 1/19 @ 2.35 fee .66
+//! btc 2 AAPL 190 call 2024-01-19 @ .40
+//! buy 100 KO @ 58.10 on 3/4 #dividend
+//! ```
+//!
+//! The action comes first — `buy` or `sell`, optionally `to open` or `to
+//! close`, or `bto`/`sto`/`btc`/`stc` — then the quantity and the symbol. An
+//! option adds its strike with `c` or `p` (or `call`/`put` after it) and its
+//! expiration, in either order, or its OCC symbol stands in for all three.
+//! `@` and the price follow. After the price, `fee` and an amount, `on` and
+//! the trade date, and `#tags` may come in any order.
+//!
+//! A bare `buy` opens. A bare `sell` opens an option (writing it) but closes
+//! stock; `sell short` opens stock. Dates are `YYYY-MM-DD`, `M/D/YYYY`, or
+//! `M/D`: the next such day for an expiration and the last one for a trade
+//! date. The trade date defaults to today and fees to none.
+
+use crate::date::{add_months, parse_date_input};
+use crate::db::{Action, OptionStatus, OptionType, Trade, TradeType};
+use crate::import::{parse_date, parse_number};
+use crate::occ::OccSymbol;
+use rust_decimal::Decimal;
+
+/// Reads `text` into a trade, dated `today` unless it says otherwise.
+pub fn parse_quick_add(text: &str, today: &str) -> Result<Trade, String> {
+    let spaced = text.replace('@', " @ ");
+    let mut tokens = spaced.split_whitespace().peekable();
+
+    let verb = tokens.next().ok_or("Type a trade, e.g. buy 100 KO @ 58")?;
+    let mut action = match verb.to_lowercase().as_str() {
+        "bto" => Some(Action::BuyToOpen),
+        "sto" => Some(Action::SellToOpen),
+        "btc" => Some(Action::BuyToClose),
+        "stc" => Some(Action::SellToClose),
+        "buy" | "bought" | "sell" | "sold" => None,
+        _ => return Err(format!("Start with buy or sell, not \"{}\"", verb)),
+    };
+    let buy = matches!(
+        verb.to_lowercase().as_str(),
+        "bto" | "btc" | "buy" | "bought"
+    );
+    // `to open`, `to close`, `open`, `close`, or `short`.
+    if action.is_none() {
+        if tokens.peek().is_some_and(|t| t.eq_ignore_ascii_case("to")) {
+            tokens.next();
+        }
+        let side = tokens.peek().map(|t| t.to_lowercase());
+        action = match (side.as_deref(), buy) {
+            (Some("open"), true) => Some(Action::BuyToOpen),
+            (Some("open" | "short"), false) => Some(Action::SellToOpen),
+            (Some("close" | "cover"), true) => Some(Action::BuyToClose),
+            (Some("close"), false) => Some(Action::SellToClose),
+            _ => None,
+        };
+        if action.is_some() {
+            tokens.next();
+        }
+    }
+
+    let quantity = tokens
+        .next()
+        .ok_or("Expected a quantity after the action")?;
+    let quantity = parse_number(quantity, "Quantity")?;
+    if quantity <= Decimal::ZERO {
+        return Err("Quantity must be more than zero".to_string());
+    }
+    let symbol = tokens
+        .next()
+        .ok_or("Expected a symbol after the quantity")?;
+    let mut trade = Trade {
+        quantity,
+        date: today.to_string(),
+        ..Default::default()
+    };
+    match symbol.parse::<OccSymbol>() {
+        Ok(occ) => {
+            trade.symbol = occ.underlying;
+            trade.option_type = Some(occ.option_type);
+            trade.strike = Some(occ.strike);
+            trade.expiration = Some(occ.expiration);
+        }
+        Err(_)
+            if symbol
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.') =>
+        {
+            trade.symbol = symbol.to_uppercase();
+        }
+        Err(_) => return Err(format!("\"{}\" is not a symbol", symbol)),
+    }
+
+    // The option's strike, type, and expiration, up to the price.
+    loop {
+        let token = tokens.next().ok_or("Expected @ and the price")?;
+        if token == "@" {
+            break;
+        }
+        let lower = token.to_lowercase();
+        if let Some(option_type) = option_type(&lower) {
+            if trade.strike.is_none() {
+                return Err(format!("Expected the strike before \"{}\"", token));
+            }
+            trade.option_type = Some(option_type);
+        } else if lower.contains(['/', '-']) {
+            trade.expiration =
+                Some(read_date(&lower, today, true).ok_or(format!("\"{}\" is not a date", token))?);
+        } else {
+            let (strike, suffix) =
+                lower.split_at(lower.find(char::is_alphabetic).unwrap_or(lower.len()));
+            let strike = parse_number(strike, "Strike")?;
+            if strike <= Decimal::ZERO {
+                return Err("Strike must be more than zero".to_string());
+            }
+            trade.strike = Some(strike);
+            if !suffix.is_empty() {
+                trade.option_type = Some(option_type(suffix).ok_or(format!(
+                    "Write the strike as 190c or 190p, not \"{}\"",
+                    token
+                ))?);
+            }
+        }
+    }
+    let price = tokens.next().ok_or("Expected the price after @")?;
+    trade.price = parse_number(price, "Price")?;
+    if trade.price < Decimal::ZERO {
+        return Err("Price can't be negative".to_string());
+    }
+
+    while let Some(token) = tokens.next() {
+        let lower = token.to_lowercase();
+        match lower.as_str() {
+            "fee" | "fees" | "comm" => {
+                let fees = tokens.next().ok_or("Expected an amount after fee")?;
+                trade.fees = parse_number(fees, "Fees")?;
+                if trade.fees < Decimal::ZERO {
+                    return Err("Fees can't be negative".to_string());
+                }
+            }
+            "on" => {
+                let date = tokens.next().ok_or("Expected a date after on")?;
+                trade.date = read_date(&date.to_lowercase(), today, false)
+                    .ok_or(format!("\"{}\" is not a date", date))?;
+            }
+            _ => match token.strip_prefix('#') {
+                Some(tag) if !tag.is_empty() => trade.tags.push(tag.to_string()),
+                _ => return Err(format!("Didn't understand \"{}\"", token)),
+            },
+        }
+    }
+
+    let option = trade.strike.is_some() || trade.expiration.is_some();
+    if option {
+        if trade.option_type.is_none() {
+            return Err("Add c or p to the strike, as in 190c".to_string());
+        }
+        if trade.strike.is_none() {
+            return Err("Expected the option's strike, as in 190c".to_string());
+        }
+        if trade.expiration.is_none() {
+            return Err("Expected the option's expiration, as in 1/19".to_string());
+        }
+        trade.trade_type = TradeType::Option;
+        trade.status = Some(OptionStatus::Open);
+    }
+    trade.action = action.unwrap_or(match (buy, option) {
+        (true, _) => Action::BuyToOpen,
+        (false, true) => Action::SellToOpen,
+        (false, false) => Action::SellToClose,
+    });
+    Ok(trade)
+}
+
+fn option_type(word: &str) -> Option<OptionType> {
+    match word {
+        "c" | "call" | "calls" => Some(OptionType::Call),
+        "p" | "put" | "puts" => Some(OptionType::Put),
+        _ => None,
+    }
+}
+
+// A full date, a shortcut such as `t` or `+3d`, or `M/D`: the next such day
+// from `today` when `ahead`, else the last one.
+fn read_date(text: &str, today: &str, ahead: bool) -> Option<String> {
+    if let Some(date) = parse_date_input(text, today) {
+        return Some(date);
+    }
+    if let [month, day] = text.split('/').collect::<Vec<_>>()[..] {
+        let year: i64 = today.get(..4)?.parse().ok()?;
+        let date = parse_date(&format!("{}/{}/{}", month, day, year)).ok()?;
+        return match (ahead, date.as_str() < today, date.as_str() > today) {
+            (true, true, _) => add_months(&date, 12),
+            (false, _, true) => add_months(&date, -12),
+            _ => Some(date),
+        };
+    }
+    parse_date(text).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn reads_an_option_fill() {
+        let trade = parse_quick_add("sell 2 AAPL 190c 1/19 @ 2.35 fee .66", "2023-11-02").unwrap();
+        assert_eq!(trade.action, Action::SellToOpen);
+        assert_eq!(trade.trade_type, TradeType::Option);
+        assert_eq!((trade.symbol.as_str(), trade.quantity), ("AAPL", dec!(2)));
+        assert_eq!(trade.option_type, Some(OptionType::Call));
+        assert_eq!(trade.strike, Some(dec!(190)));
+        // January is past in November, so it is next January.
+        assert_eq!(trade.expiration.as_deref(), Some("2024-01-19"));
+        assert_eq!((trade.price, trade.fees), (dec!(2.35), dec!(0.66)));
+        assert_eq!(trade.date, "2023-11-02");
+        assert_eq!(trade.status, Some(OptionStatus::Open));
+
+        let closed =
+            parse_quick_add("buy to close 2 aapl 190 call 2024-01-19 @.40", "2023-12-01").unwrap();
+        assert_eq!(closed.action, Action::BuyToClose);
+        assert_eq!(closed.option_type, Some(OptionType::Call));
+        let occ = parse_quick_add("stc 1 AAPL240119P00185000 @ 3", "2023-12-01").unwrap();
+        assert_eq!((occ.symbol.as_str(), occ.strike), ("AAPL", Some(dec!(185))));
+        assert_eq!(occ.option_type, Some(OptionType::Put));
+    }
+
+    #[test]
+    fn reads_a_stock_fill_with_date_and_tags() {
+        let trade = parse_quick_add("sell 100 KO @ 58.10 on 12/4 #trim #ko", "2024-01-03").unwrap();
+        assert_eq!(trade.trade_type, TradeType::Stock);
+        assert_eq!(trade.action, Action::SellToClose);
+        // December is ahead in January, so it is last December.
+        assert_eq!(trade.date, "2023-12-04");
+        assert_eq!(trade.tags, ["trim", "ko"]);
+        let short = parse_quick_add("sell short 10 TSLA @ 250 on y", "2024-01-03").unwrap();
+        assert_eq!(
+            (short.action, short.date.as_str()),
+            (Action::SellToOpen, "2024-01-02")
+        );
+    }
+
+    #[test]
+    fn says_what_is_missing() {
+        let today = "2024-01-03";
+        let error = |text| parse_quick_add(text, today).unwrap_err();
+        assert_eq!(
+            error("hold 2 AAPL @ 1"),
+            "Start with buy or sell, not \"hold\""
+        );
+        assert_eq!(
+            error("buy 2 AAPL 190c @ 1"),
+            "Expected the option's expiration, as in 1/19"
+        );
+        assert_eq!(
+            error("buy 2 AAPL 190 1/19 @ 1"),
+            "Add c or p to the strike, as in 190c"
+        );
+        assert_eq!(error("buy 2 AAPL"), "Expected @ and the price");
+        assert_eq!(error("buy 2 AAPL @ 1 lots"), "Didn't understand \"lots\"");
+        assert_eq!(error("buy x AAPL @ 1"), "Quantity \"x\" is not a number");
+        assert_eq!(error("buy 10 KO @ -3"), "Price can't be negative");
+        assert_eq!(
+            error("sell 1 AAPL 0p 1/19 @ 1"),
+            "Strike must be more than zero"
+        );
+        assert_eq!(error("buy 10 KO @ 58 fee -1"), "Fees can't be negative");
+    }
+}
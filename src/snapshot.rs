@@ -0,0 +1,232 @@
+//! Copies of the database file.
+//!
+//! A snapshot is the whole database as a standalone SQLite file, written with
+//! `VACUUM INTO` so the copy is consistent and compact even while the tracker
+//! has the database open, then read back through `PRAGMA integrity_check`
+//! before it counts. Restoring checks a file the same way first, and refuses
+//! one written by a newer version. Snapshots of an encrypted database are
+//! encrypted with its passphrase (see [`crate::encryption`]).
+//!
+//! Snapshots go in the backup directory: `backup-<timestamp>.db` when taken
+//! by hand, and `auto-<timestamp>-<reason>.db` when taken before an upgrade,
+//! an import, or a restore. Only the newest few automatic ones are kept.
+
+use crate::db::Database;
+use crate::encryption::{is_encrypted, unlock};
+use crate::export::file_stamp;
+use crate::migrations::{check_version, needs_upgrade, table_exists};
+use rusqlite::{ffi, params, Connection, OpenFlags, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Writes the database open on `conn`, encrypted with `passphrase` if any,
+/// to a new file at `path`, then checks the copy.
+pub fn write_snapshot(conn: &Connection, path: &Path, passphrase: Option<&str>) -> Result<()> {
+    conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+    check_snapshot(path, passphrase)
+}
+
+/// Fails unless `path` is an intact options_tracker database this version
+/// can open, encrypted with `passphrase` or, for `None`, not encrypted.
+pub fn check_snapshot(path: &Path, passphrase: Option<&str>) -> Result<()> {
+    if is_encrypted(path) != passphrase.is_some() {
+        return Err(failure(
+            ffi::SQLITE_NOTADB,
+            format!(
+                "{} is {}encrypted, unlike the database",
+                path.display(),
+                if passphrase.is_some() { "not " } else { "" }
+            ),
+        ));
+    }
+    // Not read-only: checking the full-text index writes scratch pages.
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    if let Some(passphrase) = passphrase {
+        unlock(&conn, passphrase)?;
+    }
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(failure(
+            ffi::SQLITE_CORRUPT,
+            format!("{} is damaged: {}", path.display(), integrity),
+        ));
+    }
+    if !table_exists(&conn, "trades")? {
+        return Err(failure(
+            ffi::SQLITE_NOTADB,
+            format!("{} is not an options_tracker database", path.display()),
+        ));
+    }
+    check_version(&conn)
+}
+
+/// Takes a snapshot of `db` by hand, as `backup-<timestamp>.db` in `dir`.
+pub fn manual_snapshot(db: &Database, dir: &str, now: i64) -> Result<PathBuf, String> {
+    let path = snapshot_path(dir, &format!("backup-{}.db", file_stamp(now)))?;
+    db.write_snapshot(&path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Takes an automatic snapshot of `db` before the change `reason` names (e.g.
+/// `import`), then deletes all but the newest `keep` automatic snapshots in
+/// `dir`. A snapshot already taken this second is reused.
+pub fn auto_snapshot(
+    db: &Database,
+    dir: &str,
+    reason: &str,
+    keep: usize,
+    now: i64,
+) -> Result<PathBuf, String> {
+    let path = snapshot_path(dir, &format!("auto-{}-{}.db", file_stamp(now), reason))?;
+    if !path.exists() {
+        db.write_snapshot(&path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    prune(dir, keep)?;
+    Ok(path)
+}
+
+/// Takes an automatic snapshot of the database file at `db_path`, encrypted
+/// with `passphrase` if any, if opening it is about to upgrade its schema.
+/// Returns where it went, if anywhere.
+pub fn snapshot_before_upgrade(
+    db_path: &str,
+    passphrase: Option<&str>,
+    dir: &str,
+    keep: usize,
+    now: i64,
+) -> Result<Option<PathBuf>, String> {
+    if !Path::new(db_path).exists() {
+        return Ok(None);
+    }
+    let conn = Connection::open(db_path).map_err(|e| format!("{}: {}", db_path, e))?;
+    if let Some(passphrase) = passphrase {
+        unlock(&conn, passphrase).map_err(|e| format!("{}: {}", db_path, e))?;
+    }
+    if !needs_upgrade(&conn).map_err(|e| format!("{}: {}", db_path, e))? {
+        return Ok(None);
+    }
+    let path = snapshot_path(dir, &format!("auto-{}-upgrade.db", file_stamp(now)))?;
+    write_snapshot(&conn, &path, passphrase).map_err(|e| format!("{}: {}", path.display(), e))?;
+    prune(dir, keep)?;
+    Ok(Some(path))
+}
+
+/// The snapshots in `dir`, newest first; none when it doesn't exist yet.
+pub fn list_snapshots(dir: &str) -> Result<Vec<PathBuf>, String> {
+    let mut snapshots = snapshot_files(dir)?;
+    // Timestamps follow the prefix, so this orders each kind by age.
+    snapshots.sort_by_key(|path| {
+        let name = file_name(path);
+        let stamp = name
+            .strip_prefix("auto-")
+            .or_else(|| name.strip_prefix("backup-"))
+            .unwrap_or(&name)
+            .to_string();
+        std::cmp::Reverse(stamp)
+    });
+    Ok(snapshots)
+}
+
+// Deletes all but the newest `keep` automatic snapshots in `dir`.
+fn prune(dir: &str, keep: usize) -> Result<(), String> {
+    let mut automatic: Vec<PathBuf> = snapshot_files(dir)?
+        .into_iter()
+        .filter(|path| file_name(path).starts_with("auto-"))
+        .collect();
+    automatic.sort();
+    let excess = automatic.len().saturating_sub(keep);
+    for path in &automatic[..excess] {
+        fs::remove_file(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+// The `.db` files in `dir`.
+fn snapshot_files(dir: &str) -> Result<Vec<PathBuf>, String> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", dir, e)),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry.map_err(|e| format!("{}: {}", dir, e))?.path();
+        if path.extension().is_some_and(|ext| ext == "db") {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+// `dir/<name>`, creating `dir` if missing.
+fn snapshot_path(dir: &str, name: &str) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir, e))?;
+    Ok(Path::new(dir).join(name))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// An SQLite error with result `code` and `message`.
+pub(crate) fn failure(code: i32, message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(ffi::Error::new(code), Some(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, Trade};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn snapshots_restore_and_rotate() {
+        let dir =
+            std::env::temp_dir().join(format!("options_tracker_snapshots_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dir_name = dir.to_string_lossy().into_owned();
+        let mut db = Database::new(":memory:").unwrap();
+        let trade = Trade {
+            symbol: "AAPL".to_string(),
+            action: Action::BuyToOpen,
+            price: dec!(150),
+            quantity: dec!(10),
+            date: "2024-01-05".to_string(),
+            ..Default::default()
+        };
+        db.add_trade(&trade).unwrap();
+
+        let saved = manual_snapshot(&db, &dir_name, 1_706_745_600).unwrap();
+        assert_eq!(file_name(&saved), "backup-20240201-000000.db");
+        db.add_trade(&trade).unwrap();
+        for minute in 0..3 {
+            auto_snapshot(&db, &dir_name, "import", 2, 1_706_745_600 + minute * 60).unwrap();
+        }
+        let names: Vec<String> = list_snapshots(&dir_name)
+            .unwrap()
+            .iter()
+            .map(|path| file_name(path))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "auto-20240201-000200-import.db",
+                "auto-20240201-000100-import.db",
+                "backup-20240201-000000.db",
+            ]
+        );
+
+        db.restore_snapshot(&saved).unwrap();
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
+
+        let junk = dir.join("junk.db");
+        fs::write(&junk, "not a database").unwrap();
+        assert!(db.restore_snapshot(&junk).is_err());
+        assert_eq!(db.get_all_trades().unwrap().len(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,339 @@
+//! Win/loss statistics over closed positions.
+//!
+//! A closed position is an opening trade whose lots have all been closed,
+//! whether by one closing trade or several; its realized P/L is the sum of
+//! its lot matches, and it is held from the opening trade's date to the last
+//! close. Positions still partly open are left out until they finish.
+//!
+//! Risk figures come from the equity curve (see [`crate::equity`]) instead:
+//! drawdowns from its daily levels, and volatility from its monthly changes,
+//! counting months without a close as zero.
+
+use crate::cost_basis::{LotMatch, OpenLot};
+use crate::date::parse_unix_day;
+use crate::db::{PositionKey, Trade};
+use crate::periods::Period;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// An opening trade with every lot of it closed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClosedPosition {
+    pub key: PositionKey,
+    pub open_id: i64,
+    pub opened: String,
+    /// Date of the last close.
+    pub closed: String,
+    pub realized: Decimal,
+}
+
+impl ClosedPosition {
+    pub fn days_held(&self) -> Option<i64> {
+        Some(parse_unix_day(&self.closed)? - parse_unix_day(&self.opened)?)
+    }
+}
+
+/// The closed positions among `trades`, from their lot `matches` and the
+/// `open` lots left, in the order they were opened.
+pub fn closed_positions(
+    trades: &[Trade],
+    matches: &[LotMatch],
+    open: &[OpenLot],
+) -> Vec<ClosedPosition> {
+    let still_open: HashSet<i64> = open.iter().filter_map(|lot| lot.open_id).collect();
+    let opened: HashMap<i64, &Trade> = trades.iter().filter_map(|t| Some((t.id?, t))).collect();
+    let mut closed: BTreeMap<(String, i64), ClosedPosition> = BTreeMap::new();
+    for lot in matches {
+        let Some(open_id) = lot.open_id.filter(|id| !still_open.contains(id)) else {
+            continue;
+        };
+        let Some(trade) = opened.get(&open_id) else {
+            continue;
+        };
+        let position = closed
+            .entry((trade.date.clone(), open_id))
+            .or_insert_with(|| ClosedPosition {
+                key: lot.key.clone(),
+                open_id,
+                opened: trade.date.clone(),
+                closed: lot.close_date.clone(),
+                realized: Decimal::ZERO,
+            });
+        position.realized += lot.realized;
+        if lot.close_date > position.closed {
+            position.closed = lot.close_date.clone();
+        }
+    }
+    closed.into_values().collect()
+}
+
+/// Summary figures over a set of closed positions. A position that broke
+/// exactly even is neither a win nor a loss.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TradeStatistics {
+    pub count: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub gross_profit: Decimal,
+    /// Sum of the losses, as a negative amount.
+    pub gross_loss: Decimal,
+    pub largest_win: Option<ClosedPosition>,
+    pub largest_loss: Option<ClosedPosition>,
+    /// Mean days held.
+    pub average_days: Option<Decimal>,
+}
+
+impl TradeStatistics {
+    /// Percent of positions that made money.
+    pub fn win_rate(&self) -> Option<Decimal> {
+        (self.count > 0)
+            .then(|| Decimal::from(self.wins) / Decimal::from(self.count) * Decimal::ONE_HUNDRED)
+    }
+
+    pub fn average_win(&self) -> Option<Decimal> {
+        (self.wins > 0).then(|| self.gross_profit / Decimal::from(self.wins))
+    }
+
+    /// Mean loss, as a negative amount.
+    pub fn average_loss(&self) -> Option<Decimal> {
+        (self.losses > 0).then(|| self.gross_loss / Decimal::from(self.losses))
+    }
+
+    /// Gross profit over gross loss; `None` without losses.
+    pub fn profit_factor(&self) -> Option<Decimal> {
+        (self.gross_loss != Decimal::ZERO).then(|| self.gross_profit / self.gross_loss.abs())
+    }
+
+    pub fn net(&self) -> Decimal {
+        self.gross_profit + self.gross_loss
+    }
+}
+
+/// Win/loss figures over `positions`.
+pub fn trade_statistics(positions: &[ClosedPosition]) -> TradeStatistics {
+    let mut stats = TradeStatistics {
+        count: positions.len(),
+        ..Default::default()
+    };
+    let mut days = Vec::new();
+    for position in positions {
+        if position.realized > Decimal::ZERO {
+            stats.wins += 1;
+            stats.gross_profit += position.realized;
+            if stats
+                .largest_win
+                .as_ref()
+                .is_none_or(|win| position.realized > win.realized)
+            {
+                stats.largest_win = Some(position.clone());
+            }
+        } else if position.realized < Decimal::ZERO {
+            stats.losses += 1;
+            stats.gross_loss += position.realized;
+            if stats
+                .largest_loss
+                .as_ref()
+                .is_none_or(|loss| position.realized < loss.realized)
+            {
+                stats.largest_loss = Some(position.clone());
+            }
+        }
+        days.extend(position.days_held());
+    }
+    if !days.is_empty() {
+        stats.average_days =
+            Some(Decimal::from(days.iter().sum::<i64>()) / Decimal::from(days.len()));
+    }
+    stats
+}
+
+/// Drawdown and volatility of realized P/L.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RiskMetrics {
+    /// Largest fall of cumulative realized P/L from an earlier high, as a
+    /// positive amount.
+    pub max_drawdown: Decimal,
+    /// Dates of the high and the low of the largest fall.
+    pub drawdown_peak: Option<String>,
+    pub drawdown_trough: Option<String>,
+    /// Realized P/L of each month from the first close to the last, oldest
+    /// first.
+    pub monthly: Vec<Decimal>,
+}
+
+impl RiskMetrics {
+    pub fn average_month(&self) -> Option<Decimal> {
+        (!self.monthly.is_empty())
+            .then(|| self.monthly.iter().sum::<Decimal>() / Decimal::from(self.monthly.len()))
+    }
+
+    /// Sample standard deviation of monthly P/L; needs two months.
+    pub fn monthly_std_dev(&self) -> Option<Decimal> {
+        let mean = self.average_month().filter(|_| self.monthly.len() > 1)?;
+        let variance = self
+            .monthly
+            .iter()
+            .map(|m| (m - mean) * (m - mean))
+            .sum::<Decimal>()
+            / Decimal::from(self.monthly.len() - 1);
+        Decimal::from_f64(variance.to_f64()?.sqrt())
+    }
+
+    /// Average monthly P/L over its standard deviation, annualized by √12,
+    /// with no risk-free rate: a Sharpe ratio in dollars rather than returns.
+    pub fn sharpe(&self) -> Option<Decimal> {
+        let std_dev = self.monthly_std_dev().filter(|s| !s.is_zero())?;
+        let annualize = Decimal::from_f64(12f64.sqrt())?;
+        Some(self.average_month()? / std_dev * annualize)
+    }
+}
+
+/// Risk figures of an equity `curve`: cumulative realized P/L per close date,
+/// oldest first.
+pub fn risk_metrics(curve: &[(String, Decimal)]) -> RiskMetrics {
+    let mut metrics = RiskMetrics::default();
+    // Nothing was realized before the first close, so the first high is zero.
+    let mut peak: (Option<&str>, Decimal) = (None, Decimal::ZERO);
+    for (date, level) in curve {
+        if *level > peak.1 {
+            peak = (Some(date), *level);
+        } else if peak.1 - level > metrics.max_drawdown {
+            metrics.max_drawdown = peak.1 - level;
+            metrics.drawdown_peak = peak.0.map(str::to_string);
+            metrics.drawdown_trough = Some(date.clone());
+        }
+    }
+
+    let mut previous = Decimal::ZERO;
+    let mut month: Option<String> = None;
+    for (date, level) in curve {
+        let label = Period::Month.label(date);
+        match month {
+            // Zero months fill any gap since the last close.
+            Some(mut current) => {
+                while current < label {
+                    metrics.monthly.push(Decimal::ZERO);
+                    current = next_month(&current);
+                }
+            }
+            None => metrics.monthly.push(Decimal::ZERO),
+        }
+        month = Some(label);
+        if let Some(last) = metrics.monthly.last_mut() {
+            *last += level - previous;
+        }
+        previous = *level;
+    }
+    metrics
+}
+
+// The `YYYY-MM` label after `month`.
+fn next_month(month: &str) -> String {
+    let year = month
+        .get(..4)
+        .and_then(|y| y.parse::<i64>().ok())
+        .unwrap_or(0);
+    match month.get(5..7).and_then(|m| m.parse::<u32>().ok()) {
+        Some(12) | None => format!("{:04}-01", year + 1),
+        Some(m) => format!("{:04}-{:02}", year, m + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, open_lots, CostBasisMethod};
+    use crate::db::Action::{self, BuyToOpen, SellToClose};
+    use rust_decimal_macros::dec;
+
+    fn stock(
+        id: i64,
+        symbol: &str,
+        action: Action,
+        price: Decimal,
+        qty: Decimal,
+        date: &str,
+    ) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: symbol.to_string(),
+            action,
+            price,
+            quantity: qty,
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn computes_statistics_from_closed_lots() {
+        let trades = vec![
+            // Closed in two sales: +$100 then +$200, 30 days.
+            stock(1, "AAPL", BuyToOpen, dec!(100), dec!(20), "2024-01-01"),
+            stock(2, "AAPL", SellToClose, dec!(110), dec!(10), "2024-01-11"),
+            stock(3, "AAPL", SellToClose, dec!(120), dec!(10), "2024-01-31"),
+            // A $150 loss over 10 days.
+            stock(4, "MSFT", BuyToOpen, dec!(400), dec!(5), "2024-02-01"),
+            stock(5, "MSFT", SellToClose, dec!(370), dec!(5), "2024-02-11"),
+            // Still partly open: left out.
+            stock(6, "TSLA", BuyToOpen, dec!(200), dec!(10), "2024-03-01"),
+            stock(7, "TSLA", SellToClose, dec!(250), dec!(5), "2024-03-05"),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let open = open_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let closed = closed_positions(&trades, &matches, &open);
+        assert_eq!(closed.len(), 2);
+        assert_eq!(closed[0].realized, dec!(300));
+        assert_eq!(closed[0].closed, "2024-01-31");
+        assert_eq!(closed[0].days_held(), Some(30));
+
+        let stats = trade_statistics(&closed);
+        assert_eq!((stats.count, stats.wins, stats.losses), (2, 1, 1));
+        assert_eq!(stats.win_rate(), Some(dec!(50)));
+        assert_eq!(stats.average_win(), Some(dec!(300)));
+        assert_eq!(stats.average_loss(), Some(dec!(-150)));
+        assert_eq!(stats.profit_factor(), Some(dec!(2)));
+        assert_eq!(stats.net(), dec!(150));
+        assert_eq!(stats.largest_win.unwrap().open_id, 1);
+        assert_eq!(stats.largest_loss.unwrap().key.symbol, "MSFT");
+        assert_eq!(stats.average_days, Some(dec!(20)));
+    }
+
+    #[test]
+    fn measures_drawdown_and_monthly_volatility() {
+        let curve: Vec<(String, Decimal)> = [
+            ("2024-01-10", dec!(100)),
+            ("2024-01-20", dec!(40)),
+            ("2024-03-05", dec!(300)),
+            ("2024-03-20", dec!(150)),
+        ]
+        .iter()
+        .map(|(date, level)| (date.to_string(), *level))
+        .collect();
+        let risk = risk_metrics(&curve);
+        assert_eq!(risk.max_drawdown, dec!(150));
+        assert_eq!(risk.drawdown_peak.as_deref(), Some("2024-03-05"));
+        assert_eq!(risk.drawdown_trough.as_deref(), Some("2024-03-20"));
+        // February had no closes.
+        assert_eq!(risk.monthly, vec![dec!(40), dec!(0), dec!(110)]);
+        assert_eq!(risk.average_month(), Some(dec!(50)));
+        assert_eq!(
+            risk.monthly_std_dev().map(|s| s.round_dp(2)),
+            Some(dec!(55.68))
+        );
+        assert_eq!(risk.sharpe().map(|s| s.round_dp(2)), Some(dec!(3.11)));
+    }
+
+    #[test]
+    fn empty_statistics_have_no_ratios() {
+        let stats = trade_statistics(&[]);
+        assert_eq!(stats.win_rate(), None);
+        assert_eq!(stats.average_win(), None);
+        assert_eq!(stats.profit_factor(), None);
+        assert_eq!(stats.average_days, None);
+        let risk = risk_metrics(&[]);
+        assert_eq!(risk.max_drawdown, Decimal::ZERO);
+        assert_eq!(risk.sharpe(), None);
+    }
+}
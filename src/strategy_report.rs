@@ -0,0 +1,423 @@
+//! Profit/loss grouped by the kind of strategy traded.
+//!
+//! Every position is attributed to one *play*: the named strategy its legs
+//! belong to, or otherwise the opening trade itself. A play's type comes from
+//! its strategy's kind, or for a lone trade from what it is: a sold call
+//! linked to covering shares is a covered call, any other sold call a naked
+//! call, a sold put a cash-secured put, and so on. Realized P/L follows the
+//! lot matches back to the play that opened them; unrealized P/L values its
+//! still-open lots at their marks. A play with nothing left open is closed,
+//! and closed plays give the win rate and the average holding period, from
+//! the first open to the last close.
+
+use crate::cost_basis::{LotMatch, OpenLot};
+use crate::date::parse_unix_day;
+use crate::db::{Action, OptionType, Quote, Strategy, StrategyKind, Trade, TradeType};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// What kind of play a position was.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StrategyType {
+    /// A named multi-leg strategy.
+    Strategy(StrategyKind),
+    CoveredCall,
+    CashSecuredPut,
+    NakedCall,
+    LongCall,
+    LongPut,
+    LongStock,
+    ShortStock,
+}
+
+impl StrategyType {
+    /// The type of a lone opening trade, given whether it is a call linked to
+    /// covering shares.
+    pub fn of_trade(trade: &Trade, covered: bool) -> StrategyType {
+        let sold = trade.action == Action::SellToOpen;
+        match (trade.trade_type, trade.option_type, sold) {
+            (TradeType::Stock, _, false) => StrategyType::LongStock,
+            (TradeType::Stock, _, true) => StrategyType::ShortStock,
+            (_, Some(OptionType::Put), true) => StrategyType::CashSecuredPut,
+            (_, Some(OptionType::Put), false) => StrategyType::LongPut,
+            (_, _, true) if covered => StrategyType::CoveredCall,
+            (_, _, true) => StrategyType::NakedCall,
+            (_, _, false) => StrategyType::LongCall,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            StrategyType::Strategy(kind) => kind.as_str().replace('_', " "),
+            StrategyType::CoveredCall => "covered call".to_string(),
+            StrategyType::CashSecuredPut => "cash-secured put".to_string(),
+            StrategyType::NakedCall => "naked call".to_string(),
+            StrategyType::LongCall => "long call".to_string(),
+            StrategyType::LongPut => "long put".to_string(),
+            StrategyType::LongStock => "long stock".to_string(),
+            StrategyType::ShortStock => "short stock".to_string(),
+        }
+    }
+}
+
+/// Combined figures of every play of one type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrategyTypeReport {
+    pub strategy_type: StrategyType,
+    pub plays: usize,
+    /// Plays with nothing left open.
+    pub closed: usize,
+    /// Closed plays with a positive realized P/L.
+    pub wins: usize,
+    pub realized: Decimal,
+    /// P/L of the still-open lots at their marks; `None` when any lacks one.
+    pub unrealized: Option<Decimal>,
+    /// Mean days from first open to last close over the closed plays.
+    pub average_days: Option<Decimal>,
+}
+
+impl StrategyTypeReport {
+    /// Percent of closed plays that made money.
+    pub fn win_rate(&self) -> Option<Decimal> {
+        (self.closed > 0)
+            .then(|| Decimal::from(self.wins) / Decimal::from(self.closed) * Decimal::ONE_HUNDRED)
+    }
+}
+
+// A strategy, or a lone opening trade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Play {
+    Strategy(i64),
+    Trade(i64),
+}
+
+#[derive(Default)]
+struct PlayTotals {
+    realized: Decimal,
+    unrealized: Option<Decimal>,
+    open: bool,
+    opened: Option<String>,
+    closed: Option<String>,
+}
+
+/// Figures per strategy type of `trades`, from their lot `matches` and the
+/// `open` lots left, with open lots valued at `quotes`. `covered_calls` are
+/// the ids of calls linked to covering shares. Ordered by type.
+pub fn strategy_report(
+    trades: &[Trade],
+    strategies: &[Strategy],
+    covered_calls: &HashSet<i64>,
+    matches: &[LotMatch],
+    open: &[OpenLot],
+    quotes: &HashMap<String, Quote>,
+) -> Vec<StrategyTypeReport> {
+    let kinds: HashMap<i64, StrategyKind> = strategies
+        .iter()
+        .filter_map(|s| Some((s.id?, s.kind)))
+        .collect();
+    let mut plays: HashMap<i64, Play> = HashMap::new();
+    let mut types: HashMap<Play, StrategyType> = HashMap::new();
+    let mut totals: HashMap<Play, PlayTotals> = HashMap::new();
+    for trade in trades {
+        let Some(id) = trade.id else {
+            continue;
+        };
+        let (play, strategy_type) = match trade.strategy_id.and_then(|s| Some((s, kinds.get(&s)?)))
+        {
+            Some((strategy_id, kind)) => {
+                (Play::Strategy(strategy_id), StrategyType::Strategy(*kind))
+            }
+            None if trade.action.is_open() => (
+                Play::Trade(id),
+                StrategyType::of_trade(trade, covered_calls.contains(&id)),
+            ),
+            // Closing trades count toward the play whose lots they close.
+            None => continue,
+        };
+        plays.insert(id, play);
+        types.insert(play, strategy_type);
+        let play_totals = totals.entry(play).or_insert_with(|| PlayTotals {
+            unrealized: Some(Decimal::ZERO),
+            ..Default::default()
+        });
+        if trade.action.is_open() && play_totals.opened.as_ref().is_none_or(|d| trade.date < *d) {
+            play_totals.opened = Some(trade.date.clone());
+        }
+    }
+
+    for lot in matches {
+        let Some(play_totals) = lot.open_id.and_then(|id| totals.get_mut(plays.get(&id)?)) else {
+            continue;
+        };
+        play_totals.realized += lot.realized;
+        if play_totals
+            .closed
+            .as_ref()
+            .is_none_or(|d| lot.close_date > *d)
+        {
+            play_totals.closed = Some(lot.close_date.clone());
+        }
+    }
+    for lot in open {
+        let Some(play_totals) = lot.open_id.and_then(|id| totals.get_mut(plays.get(&id)?)) else {
+            continue;
+        };
+        play_totals.open = true;
+        let mark = quotes.get(&lot.key.instrument()).map(|q| q.price);
+        play_totals.unrealized = match (play_totals.unrealized, mark) {
+            (Some(total), Some(mark)) => Some(total + lot.unrealized(mark)),
+            _ => None,
+        };
+    }
+
+    let mut reports: BTreeMap<StrategyType, StrategyTypeReport> = BTreeMap::new();
+    let mut days: HashMap<StrategyType, Vec<i64>> = HashMap::new();
+    for (play, play_totals) in totals {
+        let strategy_type = types[&play];
+        let report = reports
+            .entry(strategy_type)
+            .or_insert_with(|| StrategyTypeReport {
+                strategy_type,
+                plays: 0,
+                closed: 0,
+                wins: 0,
+                realized: Decimal::ZERO,
+                unrealized: Some(Decimal::ZERO),
+                average_days: None,
+            });
+        report.plays += 1;
+        report.realized += play_totals.realized;
+        report.unrealized = match (report.unrealized, play_totals.unrealized) {
+            (Some(total), Some(play)) => Some(total + play),
+            _ => None,
+        };
+        if play_totals.open {
+            continue;
+        }
+        report.closed += 1;
+        if play_totals.realized > Decimal::ZERO {
+            report.wins += 1;
+        }
+        let held = play_totals
+            .opened
+            .as_deref()
+            .and_then(parse_unix_day)
+            .zip(play_totals.closed.as_deref().and_then(parse_unix_day));
+        if let Some((opened, closed)) = held {
+            days.entry(strategy_type).or_default().push(closed - opened);
+        }
+    }
+    for (strategy_type, held) in days {
+        if let Some(report) = reports.get_mut(&strategy_type) {
+            let total: i64 = held.iter().sum();
+            report.average_days = Some(Decimal::from(total) / Decimal::from(held.len()));
+        }
+    }
+    reports.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, open_lots, CostBasisMethod};
+    use crate::db::OptionStatus;
+    use rust_decimal_macros::dec;
+
+    fn option(
+        id: i64,
+        option_type: OptionType,
+        action: Action,
+        price: Decimal,
+        strike: Decimal,
+        date: &str,
+    ) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action,
+            price,
+            quantity: dec!(1),
+            date: date.to_string(),
+            option_type: Some(option_type),
+            strike: Some(strike),
+            expiration: Some("2024-06-21".to_string()),
+            status: Some(OptionStatus::Open),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn classifies_lone_trades() {
+        let put = option(
+            1,
+            OptionType::Put,
+            Action::SellToOpen,
+            dec!(2),
+            dec!(180),
+            "2024-06-03",
+        );
+        assert_eq!(
+            StrategyType::of_trade(&put, false),
+            StrategyType::CashSecuredPut
+        );
+        let call = Trade {
+            option_type: Some(OptionType::Call),
+            ..put.clone()
+        };
+        assert_eq!(
+            StrategyType::of_trade(&call, true),
+            StrategyType::CoveredCall
+        );
+        assert_eq!(
+            StrategyType::of_trade(&call, false),
+            StrategyType::NakedCall
+        );
+        let bought = Trade {
+            action: Action::BuyToOpen,
+            ..call
+        };
+        assert_eq!(
+            StrategyType::of_trade(&bought, false),
+            StrategyType::LongCall
+        );
+        assert_eq!(
+            StrategyType::Strategy(StrategyKind::VerticalSpread).label(),
+            "vertical spread"
+        );
+    }
+
+    #[test]
+    fn groups_plays_by_type() {
+        let spread = Strategy {
+            id: Some(1),
+            name: "AAPL bull put".to_string(),
+            kind: StrategyKind::VerticalSpread,
+        };
+        let leg = |trade: Trade| Trade {
+            strategy_id: Some(1),
+            ..trade
+        };
+        let trades = vec![
+            // Two puts: one bought back for a $150 gain in 10 days, one for a
+            // $100 loss in 20 days.
+            option(
+                1,
+                OptionType::Put,
+                Action::SellToOpen,
+                dec!(2),
+                dec!(180),
+                "2024-05-01",
+            ),
+            option(
+                2,
+                OptionType::Put,
+                Action::BuyToClose,
+                dec!(0.5),
+                dec!(180),
+                "2024-05-11",
+            ),
+            option(
+                3,
+                OptionType::Put,
+                Action::SellToOpen,
+                dec!(1),
+                dec!(170),
+                "2024-05-01",
+            ),
+            option(
+                4,
+                OptionType::Put,
+                Action::BuyToClose,
+                dec!(2),
+                dec!(170),
+                "2024-05-21",
+            ),
+            // A covered call still open, marked at $1.
+            option(
+                5,
+                OptionType::Call,
+                Action::SellToOpen,
+                dec!(3),
+                dec!(200),
+                "2024-05-01",
+            ),
+            // A vertical spread closed for a $100 gain.
+            leg(option(
+                6,
+                OptionType::Put,
+                Action::SellToOpen,
+                dec!(4),
+                dec!(190),
+                "2024-05-01",
+            )),
+            leg(option(
+                7,
+                OptionType::Put,
+                Action::BuyToOpen,
+                dec!(2),
+                dec!(185),
+                "2024-05-01",
+            )),
+            leg(option(
+                8,
+                OptionType::Put,
+                Action::BuyToClose,
+                dec!(1),
+                dec!(190),
+                "2024-05-06",
+            )),
+            leg(option(
+                9,
+                OptionType::Put,
+                Action::SellToClose,
+                dec!(0),
+                dec!(185),
+                "2024-05-06",
+            )),
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let open = open_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let quotes = HashMap::from([(
+            "AAPL240621C00200000".to_string(),
+            Quote {
+                instrument: "AAPL240621C00200000".to_string(),
+                price: dec!(1),
+                fetched_at: "2024-06-03 15:00:00".to_string(),
+                source: "manual".to_string(),
+            },
+        )]);
+        let reports = strategy_report(
+            &trades,
+            &[spread],
+            &HashSet::from([5]),
+            &matches,
+            &open,
+            &quotes,
+        );
+
+        assert_eq!(reports.len(), 3);
+        let spreads = &reports[0];
+        assert_eq!(
+            spreads.strategy_type,
+            StrategyType::Strategy(StrategyKind::VerticalSpread)
+        );
+        assert_eq!((spreads.plays, spreads.closed, spreads.wins), (1, 1, 1));
+        assert_eq!(spreads.realized, dec!(100));
+        assert_eq!(spreads.average_days, Some(dec!(5)));
+
+        let covered = &reports[1];
+        assert_eq!(covered.strategy_type, StrategyType::CoveredCall);
+        assert_eq!((covered.plays, covered.closed), (1, 0));
+        assert_eq!(covered.unrealized, Some(dec!(200)));
+        assert_eq!(covered.win_rate(), None);
+
+        let puts = &reports[2];
+        assert_eq!(puts.strategy_type, StrategyType::CashSecuredPut);
+        assert_eq!((puts.plays, puts.closed, puts.wins), (2, 2, 1));
+        assert_eq!(puts.realized, dec!(50));
+        assert_eq!(puts.win_rate(), Some(dec!(50)));
+        assert_eq!(puts.average_days, Some(dec!(15)));
+        assert_eq!(puts.unrealized, Some(Decimal::ZERO));
+    }
+}
@@ -0,0 +1,192 @@
+//! Multi-leg strategy templates.
+//!
+//! A template lists the legs of a common option strategy — which side and
+//! option type each leg is — so every leg can be entered in one form with a
+//! shared symbol, expiration, date, and quantity. Legs are listed from the
+//! lowest strike to the highest.
+
+use crate::db::{Action, OptionStatus, OptionType, StrategyKind, Trade, TradeType};
+use rust_decimal::Decimal;
+
+/// One leg of a template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LegTemplate {
+    pub action: Action,
+    pub option_type: OptionType,
+}
+
+impl LegTemplate {
+    /// Short description such as `Sell Put`.
+    pub fn label(&self) -> String {
+        let side = if self.action.is_buy() { "Buy" } else { "Sell" };
+        let option_type = match self.option_type {
+            OptionType::Call => "Call",
+            OptionType::Put => "Put",
+        };
+        format!("{} {}", side, option_type)
+    }
+}
+
+/// A named strategy and its legs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrategyTemplate {
+    pub name: &'static str,
+    pub kind: StrategyKind,
+    pub legs: &'static [LegTemplate],
+}
+
+const fn leg(action: Action, option_type: OptionType) -> LegTemplate {
+    LegTemplate {
+        action,
+        option_type,
+    }
+}
+
+pub const TEMPLATES: &[StrategyTemplate] = &[
+    StrategyTemplate {
+        name: "Bull Put Spread",
+        kind: StrategyKind::VerticalSpread,
+        legs: &[
+            leg(Action::BuyToOpen, OptionType::Put),
+            leg(Action::SellToOpen, OptionType::Put),
+        ],
+    },
+    StrategyTemplate {
+        name: "Bear Call Spread",
+        kind: StrategyKind::VerticalSpread,
+        legs: &[
+            leg(Action::SellToOpen, OptionType::Call),
+            leg(Action::BuyToOpen, OptionType::Call),
+        ],
+    },
+    StrategyTemplate {
+        name: "Bull Call Spread",
+        kind: StrategyKind::VerticalSpread,
+        legs: &[
+            leg(Action::BuyToOpen, OptionType::Call),
+            leg(Action::SellToOpen, OptionType::Call),
+        ],
+    },
+    StrategyTemplate {
+        name: "Bear Put Spread",
+        kind: StrategyKind::VerticalSpread,
+        legs: &[
+            leg(Action::SellToOpen, OptionType::Put),
+            leg(Action::BuyToOpen, OptionType::Put),
+        ],
+    },
+    StrategyTemplate {
+        name: "Iron Condor",
+        kind: StrategyKind::IronCondor,
+        legs: &[
+            leg(Action::BuyToOpen, OptionType::Put),
+            leg(Action::SellToOpen, OptionType::Put),
+            leg(Action::SellToOpen, OptionType::Call),
+            leg(Action::BuyToOpen, OptionType::Call),
+        ],
+    },
+    StrategyTemplate {
+        name: "Short Strangle",
+        kind: StrategyKind::Strangle,
+        legs: &[
+            leg(Action::SellToOpen, OptionType::Put),
+            leg(Action::SellToOpen, OptionType::Call),
+        ],
+    },
+    StrategyTemplate {
+        name: "Short Straddle",
+        kind: StrategyKind::Straddle,
+        legs: &[
+            leg(Action::SellToOpen, OptionType::Put),
+            leg(Action::SellToOpen, OptionType::Call),
+        ],
+    },
+];
+
+/// Fields every leg of a template entry shares.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedLegFields {
+    pub symbol: String,
+    pub expiration: String,
+    pub date: String,
+    pub quantity: Decimal,
+    /// Fees charged on each leg.
+    pub fees: Decimal,
+}
+
+impl StrategyTemplate {
+    /// Builds the template's option trades from the shared fields and each
+    /// leg's `(strike, price)`, in leg order. Legs beyond `strikes_and_prices`
+    /// are left out.
+    pub fn build_legs(
+        &self,
+        shared: &SharedLegFields,
+        strikes_and_prices: &[(Decimal, Decimal)],
+    ) -> Vec<Trade> {
+        self.legs
+            .iter()
+            .zip(strikes_and_prices)
+            .map(|(leg, (strike, price))| Trade {
+                symbol: shared.symbol.clone(),
+                trade_type: TradeType::Option,
+                action: leg.action,
+                price: *price,
+                quantity: shared.quantity,
+                date: shared.date.clone(),
+                fees: shared.fees,
+                comment: self.name.to_string(),
+                option_type: Some(leg.option_type),
+                strike: Some(*strike),
+                expiration: Some(shared.expiration.clone()),
+                status: Some(OptionStatus::Open),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn iron_condor_builds_four_legs() {
+        let condor = TEMPLATES
+            .iter()
+            .find(|t| t.kind == StrategyKind::IronCondor)
+            .unwrap();
+        let shared = SharedLegFields {
+            symbol: "SPY".to_string(),
+            expiration: "2024-06-21".to_string(),
+            date: "2024-05-20".to_string(),
+            quantity: dec!(2),
+            fees: dec!(0.65),
+        };
+        let legs = condor.build_legs(
+            &shared,
+            &[
+                (dec!(480), dec!(0.5)),
+                (dec!(490), dec!(1.2)),
+                (dec!(540), dec!(1.1)),
+                (dec!(550), dec!(0.4)),
+            ],
+        );
+        assert_eq!(legs.len(), 4);
+        assert!(legs
+            .iter()
+            .all(|l| l.symbol == "SPY" && l.quantity == dec!(2)));
+        assert_eq!(legs[1].action, Action::SellToOpen);
+        assert_eq!(legs[1].option_type, Some(OptionType::Put));
+        assert_eq!(legs[3].strike, Some(dec!(550)));
+        // Net credit of 1.2 + 1.1 - 0.5 - 0.4 per share, less four legs of fees.
+        let net: Decimal = legs.iter().map(Trade::cash_flow).sum();
+        assert_eq!(net, dec!(277.40));
+    }
+
+    #[test]
+    fn leg_labels() {
+        assert_eq!(TEMPLATES[0].legs[0].label(), "Buy Put");
+        assert_eq!(TEMPLATES[1].legs[0].label(), "Sell Call");
+    }
+}
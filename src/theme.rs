@@ -0,0 +1,195 @@
+//! Colors the interface is drawn in.
+//!
+//! A [`Theme`] starts from one of the built-in schemes — `dark` for the usual
+//! dark terminal, `light` for white backgrounds, and `high-contrast` — and
+//! the `[theme]` table of the settings file can override any of its colors
+//! (see [`crate::config`]). Colors are named as cursive reads them: `red`,
+//! `light red`, `default` for the terminal's own color, or `#rrggbb`.
+
+use cursive::theme::{BaseColor, Color, Palette, PaletteColor};
+use rust_decimal::Decimal;
+
+/// Days to expiration within which an open option is drawn in the loss color.
+pub const URGENT_DAYS: i64 = 3;
+/// Days to expiration within which an open option is drawn in the warning
+/// color.
+pub const SOON_DAYS: i64 = 10;
+
+string_enum! {
+    /// A built-in color scheme.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum ThemeName {
+        #[default]
+        Dark => "dark",
+        Light => "light",
+        HighContrast => "high-contrast",
+    }
+    error = "theme",
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub name: ThemeName,
+    pub text: Color,
+    pub background: Color,
+    pub title: Color,
+    /// The selected row's background, and its text.
+    pub selection: Color,
+    pub selection_text: Color,
+    /// Gains; also the gain side of heatmaps.
+    pub profit: Color,
+    /// Losses, short positions, triggered alerts, and anything past due.
+    pub loss: Color,
+    /// Things coming due soon, and the offline marker.
+    pub warning: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::named(ThemeName::default())
+    }
+}
+
+impl Theme {
+    pub fn named(name: ThemeName) -> Theme {
+        use BaseColor::*;
+        use Color::{Dark, Light, TerminalDefault};
+        match name {
+            ThemeName::Dark => Theme {
+                name,
+                text: Light(White),
+                background: TerminalDefault,
+                title: Dark(Red),
+                selection: Dark(Red),
+                selection_text: Light(White),
+                profit: Light(Green),
+                loss: Light(Red),
+                warning: Light(Yellow),
+            },
+            // Light colors wash out on white, so everything is dark.
+            ThemeName::Light => Theme {
+                name,
+                text: Dark(Black),
+                background: TerminalDefault,
+                title: Dark(Blue),
+                selection: Dark(Blue),
+                selection_text: Light(White),
+                profit: Dark(Green),
+                loss: Dark(Red),
+                warning: Dark(Magenta),
+            },
+            ThemeName::HighContrast => Theme {
+                name,
+                text: Light(White),
+                background: Dark(Black),
+                title: Light(Yellow),
+                selection: Light(Yellow),
+                selection_text: Dark(Black),
+                profit: Light(Green),
+                loss: Light(Red),
+                warning: Light(Cyan),
+            },
+        }
+    }
+
+    /// Every color by the key the settings file sets it with.
+    pub fn colors_mut(&mut self) -> [(&'static str, &mut Color); 8] {
+        [
+            ("text", &mut self.text),
+            ("background", &mut self.background),
+            ("title", &mut self.title),
+            ("selection", &mut self.selection),
+            ("selection_text", &mut self.selection_text),
+            ("profit", &mut self.profit),
+            ("loss", &mut self.loss),
+            ("warning", &mut self.warning),
+        ]
+    }
+
+    /// Writes the theme's colors into cursive's `palette`.
+    pub fn apply(&self, palette: &mut Palette) {
+        palette[PaletteColor::Background] = self.background;
+        palette[PaletteColor::View] = self.background;
+        palette[PaletteColor::Primary] = self.text;
+        palette[PaletteColor::TitlePrimary] = self.title;
+        palette[PaletteColor::TitleSecondary] = self.title;
+        palette[PaletteColor::Highlight] = self.selection;
+        palette[PaletteColor::HighlightText] = self.selection_text;
+    }
+
+    /// The profit color for a gain, the loss color for a loss.
+    pub fn pl(&self, value: Decimal) -> Color {
+        if value < Decimal::ZERO {
+            self.loss
+        } else {
+            self.profit
+        }
+    }
+
+    /// The color for an open option `dte` days from expiring: loss within
+    /// [`URGENT_DAYS`] or past it, warning within [`SOON_DAYS`], and `None`
+    /// further out.
+    pub fn expiring(&self, dte: i64) -> Option<Color> {
+        if dte <= URGENT_DAYS {
+            Some(self.loss)
+        } else if dte <= SOON_DAYS {
+            Some(self.warning)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reads a color as the settings file names it: `default`, a base color
+/// with an optional `light` or `dark` in front, or `#rrggbb`.
+pub fn parse_color(text: &str) -> Option<Color> {
+    let text = text.trim().to_lowercase();
+    let valid = match text.strip_prefix('#') {
+        Some(hex) => hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => {
+            let base = text
+                .strip_prefix("light ")
+                .or_else(|| text.strip_prefix("dark "))
+                .unwrap_or(&text);
+            text == "default" || BaseColor::parse(base).is_some()
+        }
+    };
+    if valid {
+        Color::parse(&text)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn light_theme_keeps_to_dark_colors() {
+        let theme = Theme::named("light".parse().unwrap());
+        assert_eq!(theme.name, ThemeName::Light);
+        for color in [theme.text, theme.title, theme.profit, theme.warning] {
+            assert!(matches!(color, Color::Dark(_)), "{:?}", color);
+        }
+        assert_eq!(theme.pl(dec!(-1)), Color::Dark(BaseColor::Red));
+        assert_eq!(theme.pl(dec!(0)), Color::Dark(BaseColor::Green));
+        assert_eq!(theme.expiring(-1), Some(theme.loss));
+        assert_eq!(theme.expiring(URGENT_DAYS), Some(theme.loss));
+        assert_eq!(theme.expiring(SOON_DAYS), Some(theme.warning));
+        assert_eq!(theme.expiring(SOON_DAYS + 1), None);
+        assert!("solarized".parse::<ThemeName>().is_err());
+    }
+
+    #[test]
+    fn parses_only_named_and_hex_colors() {
+        assert_eq!(parse_color("Light Red"), Some(Color::Light(BaseColor::Red)));
+        assert_eq!(parse_color("blue"), Some(Color::Dark(BaseColor::Blue)));
+        assert_eq!(parse_color("default"), Some(Color::TerminalDefault));
+        assert_eq!(parse_color("#1E90FF"), Some(Color::Rgb(30, 144, 255)));
+        for text in ["orange", "#12345", "c00000", "light"] {
+            assert_eq!(parse_color(text), None, "{}", text);
+        }
+    }
+}
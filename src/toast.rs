@@ -0,0 +1,128 @@
+//! Short messages that show above the footer and go away by themselves.
+//!
+//! A [`Toasts`] queue holds what is on screen: save confirmations, import
+//! results, finished refreshes, and errors. Each toast expires on its own
+//! timer, errors later than the rest, so a new one never hides one shown
+//! before it. When more than [`MAX_SHOWN`] pile up, the oldest gives way
+//! early, and a message repeated while still shown is counted instead of
+//! shown twice.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Toasts on screen at once.
+pub const MAX_SHOWN: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastKind {
+    Info,
+    Error,
+}
+
+impl ToastKind {
+    /// How long a toast of this kind stays up.
+    pub fn duration(&self) -> Duration {
+        match self {
+            ToastKind::Info => Duration::from_secs(3),
+            ToastKind::Error => Duration::from_secs(8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    pub text: String,
+    pub kind: ToastKind,
+    /// Times the message was shown in a row; 1 unless repeated.
+    pub count: usize,
+    pub expires: Instant,
+}
+
+impl Toast {
+    /// The text with the repeat count, as `Copied 1 trade (×3)`.
+    pub fn label(&self) -> String {
+        if self.count > 1 {
+            format!("{} (×{})", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// The toasts shown, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct Toasts {
+    shown: VecDeque<Toast>,
+}
+
+impl Toasts {
+    /// Shows `text` from `now` and returns when it expires.
+    pub fn push(&mut self, text: String, kind: ToastKind, now: Instant) -> Instant {
+        let expires = now + kind.duration();
+        if let Some(last) = self
+            .shown
+            .back_mut()
+            .filter(|last| last.text == text && last.kind == kind)
+        {
+            last.count += 1;
+            last.expires = expires;
+            return expires;
+        }
+        self.shown.push_back(Toast {
+            text,
+            kind,
+            count: 1,
+            expires,
+        });
+        if self.shown.len() > MAX_SHOWN {
+            self.shown.pop_front();
+        }
+        expires
+    }
+
+    /// Drops the toasts expired by `now`.
+    pub fn expire(&mut self, now: Instant) {
+        self.shown.retain(|toast| toast.expires > now);
+    }
+
+    pub fn shown(&self) -> impl Iterator<Item = &Toast> {
+        self.shown.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toasts_expire_on_their_own_timers() {
+        let start = Instant::now();
+        let mut toasts = Toasts::default();
+        toasts.push("Saved #1 AAPL".to_string(), ToastKind::Info, start);
+        toasts.push("Quotes failed".to_string(), ToastKind::Error, start);
+        let later = start + Duration::from_secs(2);
+        toasts.push("Saved #1 AAPL".to_string(), ToastKind::Info, later);
+        let labels = |toasts: &Toasts| toasts.shown().map(Toast::label).collect::<Vec<_>>();
+        // A repeat of another message is shown again rather than counted.
+        assert_eq!(
+            labels(&toasts),
+            ["Saved #1 AAPL", "Quotes failed", "Saved #1 AAPL"]
+        );
+        toasts.push("Saved #1 AAPL".to_string(), ToastKind::Info, later);
+        assert_eq!(
+            toasts.shown().last().map(Toast::label).unwrap(),
+            "Saved #1 AAPL (×2)"
+        );
+
+        toasts.expire(start + Duration::from_secs(4));
+        assert_eq!(labels(&toasts), ["Quotes failed", "Saved #1 AAPL (×2)"]);
+        toasts.expire(start + Duration::from_secs(6));
+        assert_eq!(labels(&toasts), ["Quotes failed"]);
+
+        for n in 0..MAX_SHOWN {
+            toasts.push(format!("Copied {}", n), ToastKind::Info, later);
+        }
+        assert_eq!(toasts.shown().count(), MAX_SHOWN);
+        assert_eq!(toasts.shown().next().unwrap().text, "Copied 0");
+    }
+}
@@ -0,0 +1,133 @@
+//! Realized gains as a TurboTax import file (TXF).
+//!
+//! A TXF file is a short header followed by one record per sale. Each lot the
+//! cost-basis engine closed becomes a Form 8949 detail record: short-term
+//! sales under reference number 321 and long-term ones under 323, with what
+//! was sold, the dates acquired and sold, the cost basis, and the proceeds,
+//! fees included on both sides. Wash sale adjustments aren't written; the
+//! Wash Sales report lists them for entering by hand.
+
+use crate::cost_basis::{HoldingPeriod, LotMatch};
+use crate::db::TradeType;
+
+/// TXF reference number for a short-term gain or loss on a security.
+pub const SHORT_TERM_REF: u32 = 321;
+/// TXF reference number for a long-term gain or loss on a security.
+pub const LONG_TERM_REF: u32 = 323;
+
+/// The TXF file for `matches`, dated `today` (ISO `YYYY-MM-DD`). Lines end
+/// in CRLF, as TurboTax writes them.
+pub fn txf(matches: &[LotMatch], today: &str) -> String {
+    let mut lines = vec![
+        "V042".to_string(),
+        "Aoptions_tracker".to_string(),
+        format!("D{}", us_date(today)),
+        "^".to_string(),
+    ];
+    for lot in matches {
+        let reference = match lot.holding_period() {
+            HoldingPeriod::ShortTerm => SHORT_TERM_REF,
+            HoldingPeriod::LongTerm => LONG_TERM_REF,
+        };
+        lines.extend([
+            "TD".to_string(),
+            format!("N{}", reference),
+            "C1".to_string(),
+            "L1".to_string(),
+            format!("P{}", description(lot)),
+            format!("D{}", us_date(&lot.open_date)),
+            format!("D{}", us_date(&lot.close_date)),
+            format!("${:.2}", lot.cost()),
+            format!("${:.2}", lot.proceeds()),
+            "^".to_string(),
+        ]);
+    }
+    lines.iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+// `100 AAPL`, or `2 AAPL 02/16/2024 180 PUT` for option contracts.
+fn description(lot: &LotMatch) -> String {
+    let key = &lot.key;
+    let mut description = format!("{} {}", lot.quantity.normalize(), key.symbol);
+    if key.trade_type == TradeType::Option {
+        if let (Some(expiration), Some(strike), Some(option_type)) =
+            (&key.expiration, key.strike, key.option_type)
+        {
+            description.push_str(&format!(
+                " {} {} {}",
+                us_date(expiration),
+                strike.normalize(),
+                option_type.as_str().to_uppercase()
+            ));
+        }
+    }
+    description
+}
+
+// `YYYY-MM-DD` as `MM/DD/YYYY`.
+fn us_date(date: &str) -> String {
+    match (date.get(0..4), date.get(5..7), date.get(8..10)) {
+        (Some(year), Some(month), Some(day)) => format!("{}/{}/{}", month, day, year),
+        _ => date.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, CostBasisMethod};
+    use crate::db::{Action, OptionStatus, OptionType, Trade};
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn writes_a_record_per_lot() {
+        let trades = vec![
+            Trade {
+                id: Some(1),
+                symbol: "KO".to_string(),
+                action: Action::BuyToOpen,
+                price: dec!(55),
+                quantity: dec!(100),
+                date: "2023-01-10".to_string(),
+                fees: dec!(1),
+                ..Default::default()
+            },
+            Trade {
+                id: Some(2),
+                symbol: "KO".to_string(),
+                action: Action::SellToClose,
+                price: dec!(60),
+                quantity: dec!(100),
+                date: "2024-03-01".to_string(),
+                fees: dec!(1),
+                ..Default::default()
+            },
+            Trade {
+                id: Some(3),
+                symbol: "AAPL".to_string(),
+                trade_type: TradeType::Option,
+                action: Action::SellToOpen,
+                option_type: Some(OptionType::Put),
+                strike: Some(dec!(180)),
+                expiration: Some("2024-02-16".to_string()),
+                status: Some(OptionStatus::Expired),
+                price: dec!(1.25),
+                quantity: dec!(2),
+                date: "2024-01-05".to_string(),
+                fees: dec!(1.30),
+                ..Default::default()
+            },
+        ];
+        let matches = match_lots(&trades, CostBasisMethod::Fifo, &[]);
+        let text = txf(&matches, "2025-02-01");
+        assert!(text.starts_with("V042\r\nAoptions_tracker\r\nD02/01/2025\r\n^\r\n"));
+        assert!(text.contains(
+            "TD\r\nN323\r\nC1\r\nL1\r\nP100 KO\r\nD01/10/2023\r\nD03/01/2024\r\n\
+             $5501.00\r\n$5999.00\r\n^\r\n"
+        ));
+        assert!(text.contains(
+            "TD\r\nN321\r\nC1\r\nL1\r\nP2 AAPL 02/16/2024 180 PUT\r\nD01/05/2024\r\n\
+             D02/16/2024\r\n$0.00\r\n$248.70\r\n^\r\n"
+        ));
+    }
+}
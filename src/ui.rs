@@ -1,68 +1,436 @@
-use crate::date::{days_to_expiration, format_dte, today};
-use crate::db::{Action, Database, OptionStatus, OptionType, Trade, TradeType};
+use crate::alerts::check_alerts;
+use crate::archive::{archive_note, archived_years};
+use crate::attachments::{attach_file, location, open_attachment, remove_temporary};
+use crate::benchmark::compare;
+use crate::brokers::detect;
+use crate::bulk_edit::BulkEdit;
+use crate::cash::{cash_summary, time_weighted_return};
+use crate::chart::{
+    render_bar_chart, render_candlestick_chart, render_comparison_chart, render_line_chart,
+    render_price_chart, render_sparkline, ChartWindow,
+};
+use crate::clipboard;
+use crate::columns::{fit, format_header, format_row, Column, TradeColumn};
+use crate::concentration::concentration;
+use crate::config::{Config, CONFIG_FILE};
+use crate::cost_basis::CostBasisMethod;
+use crate::custom_report::{run_report, Field, Filter, GroupBy, QuickFilter};
+use crate::date::{
+    add_days, add_months, days_to_expiration, end_of_week, format_dte, format_timestamp,
+    parse_date_input, today, unix_now, weekday, DateRange, RangePreset, MONTHS,
+};
+use crate::db::{
+    Account, Action, Alert, AlertKind, ArchiveSummary, Attachment, CashKind, CashTransaction,
+    CorporateAction, CorporateActionKind, Database, Dividend, FxRate, Greeks, HistoryChange,
+    OptionStatus, OptionType, PositionKey, PriceBar, Quote, SavedReport, Strategy, StrategyKind,
+    StrategySummary, Trade, TradeDetail, TradeFilter, TradeOrder, TradeSort, TradeType,
+    ValuationSnapshot, DEFAULT_CURRENCY, OPTION_MULTIPLIER,
+};
+use crate::dividends::{dividend_income, DividendGroup};
+use crate::equity::{curve_since, ChartRange};
+use crate::export::{
+    fees_table, or_blank, periods_table, premium_table, report_workbook, statistics_table,
+    symbols_table, trades_table, write_csv, write_file, write_xlsx, Table,
+};
+use crate::fees::{schedule_for, FeeGroup};
+use crate::fx::{currency_totals, fetch_fx_rate, parse_currency, FxRates};
+use crate::heatmap::{intensity, month_header, weekday_totals, year_weeks, WEEKDAYS};
+use crate::history;
+use crate::ics::expiration_calendar;
+use crate::import::{
+    find_duplicates, guess_mapping, parse_csv, parse_pasted, read_trades, ReadResult, TradeField,
+};
+use crate::keymap::{KeyAction, Keymap};
+use crate::market_data::{
+    configured_provider, fetch_history, fetch_quotes, ChainContract, Fallback, MarketData,
+    RefreshSummary,
+};
+use crate::occ::OccSymbol;
+use crate::ofx::{is_ofx, read_ofx};
+use crate::outcomes::{Outcome, OutcomeGroup};
+use crate::payoff::analyze;
+use crate::periods::{cumulative_by_symbol, Period};
+use crate::positions::{open_positions, portfolio_greeks};
+use crate::premium::premium_income;
+use crate::quick_add::parse_quick_add;
+use crate::snapshot::{auto_snapshot, list_snapshots, manual_snapshot};
+use crate::statistics::{risk_metrics, trade_statistics, ClosedPosition};
+use crate::templates::{SharedLegFields, StrategyTemplate, TEMPLATES};
+use crate::theme::Theme;
+use crate::toast::{ToastKind, Toasts};
+use crate::txf::txf;
+use crate::valuation::{day_change, value_portfolio, Valuation};
+use crate::warnings::trade_warnings;
 use cursive::align::HAlign;
-use cursive::theme::{Color, PaletteColor};
+use cursive::event::{Event, EventResult, Key};
 use cursive::traits::*;
+use cursive::utils::markup::StyledString;
+use cursive::view::Selector;
 use cursive::views::{
-    Dialog, EditView, HideableView, LinearLayout, ListView, SelectView, TextView,
+    Button, Dialog, DummyView, EditView, HideableView, LayerPosition, LinearLayout, ListView,
+    NamedView, OnEventView, ScrollView, SelectView, TextArea, TextView,
 };
 use cursive::Cursive;
 use rust_decimal::Decimal;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// UI state that must survive rebuilding a screen, kept as Cursive user data.
+#[derive(Default)]
+struct UiState {
+    // View/Edit Trades shows one row per strategy instead of its legs.
+    collapse_strategies: bool,
+    // Lot matching used for realized P/L in the symbol report.
+    cost_basis_method: CostBasisMethod,
+    // Settings loaded at startup.
+    config: Config,
+    // Background jobs still running; the footer spinner clears at zero.
+    background_jobs: usize,
+    // Messages shown above the footer until they expire.
+    toasts: Toasts,
+    // Dates every report is limited to.
+    report_range: DateRange,
+    // Filter View/Edit Trades lists and exports by, in the custom report
+    // syntax; empty for every trade.
+    trade_filter: String,
+    // Words View/Edit Trades searches symbols and comments for; empty for no
+    // search.
+    trade_search: String,
+    // The page of the View/Edit Trades list shown, from 0 for the first
+    // trades in its order.
+    trade_page: usize,
+    // The column and direction View/Edit Trades sorts by; its lot matching
+    // follows `cost_basis_method` when it is read.
+    trade_order: TradeOrder,
+    // The selected row of the View/Edit Trades page, kept when the list is
+    // rebuilt after an edit or delete.
+    trade_row: usize,
+    // Trades marked in View/Edit Trades for a bulk delete, tag, or export,
+    // kept across pages, and the row a `V` range started from.
+    trade_marks: BTreeSet<i64>,
+    mark_anchor: Option<usize>,
+    // Keys pressed so far of a bound sequence such as `gg`.
+    pending_keys: Vec<Event>,
+    // Edits and deletes Ctrl+Z can reverse, most recent last, and those
+    // reversed since, for Ctrl+Y. A new change clears the redo stack.
+    undo: Vec<UndoStep>,
+    redo: Vec<UndoStep>,
+    // The last archived year, read when the Reports menu opens, for report
+    // titles to say they leave it out.
+    archived_through: Option<i32>,
+}
 
-pub fn run_ui(db: Database) {
+// A change to undo or redo: the history entries it logged, after `after` up
+// to and including `upto`.
+#[derive(Clone)]
+struct UndoStep {
+    // What the change was, e.g. "delete of #12 AAPL".
+    label: String,
+    after: i64,
+    upto: i64,
+}
+
+// One row of the View/Edit Trades list.
+#[derive(Clone)]
+enum TradeListItem {
+    Trade(Box<Trade>),
+    Strategy(StrategySummary),
+}
+
+pub fn run_ui(db: Database, config: Config) {
     let db = Arc::new(Mutex::new(db));
     let mut siv = cursive::default();
+    siv.set_user_data(UiState {
+        config,
+        ..Default::default()
+    });
 
     // Set up theme
     let mut theme = siv.current_theme().clone();
-    theme.palette[PaletteColor::Background] = Color::TerminalDefault;
-    theme.palette[PaletteColor::View] = Color::TerminalDefault;
-    theme.palette[PaletteColor::Primary] = Color::Light(cursive::theme::BaseColor::White);
-    theme.palette[PaletteColor::TitlePrimary] = Color::Dark(cursive::theme::BaseColor::Black);
-    theme.palette[PaletteColor::TitlePrimary] = Color::Dark(cursive::theme::BaseColor::Red);
+    colors(&mut siv).apply(&mut theme.palette);
     siv.set_theme(theme);
 
+    // Bound keys, undo and redo among them, work from any screen; text fields
+    // keep their own keys.
+    let keymap = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.keys.clone())
+        .unwrap_or_default();
+    for event in keymap.keys() {
+        let (db, keymap) = (db.clone(), keymap.clone());
+        siv.add_global_callback(event.clone(), move |s| {
+            let Some(state) = s.user_data::<UiState>() else {
+                return;
+            };
+            match keymap.feed(&mut state.pending_keys, event.clone()) {
+                Some(KeyAction::Undo) => undo(s, &db),
+                Some(KeyAction::Redo) => redo(s, &db),
+                Some(KeyAction::Help) => show_help(s, &keymap),
+                Some(action) => {
+                    if let Some(key) = action.key() {
+                        s.on_event(Event::Key(key));
+                    }
+                }
+                None => {}
+            }
+        });
+    }
+
     show_main_menu(&mut siv, db);
 
     siv.run();
+    remove_temporary();
 }
 
 fn show_main_menu(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let colors = colors(siv);
     // Clear all layers first
     while siv.pop_layer().is_some() {}
 
+    // Footer for background job status and offline mode, under every other
+    // layer.
+    let offline = siv
+        .user_data::<UiState>()
+        .is_some_and(|state| state.config.offline);
+    let mut offline_label = StyledString::new();
+    if offline {
+        offline_label.append_styled("OFFLINE", colors.warning);
+    }
+    siv.screen_mut().add_transparent_layer(
+        LinearLayout::vertical()
+            .child(DummyView.full_height())
+            .child(
+                TextView::new("")
+                    .h_align(HAlign::Right)
+                    .with_name("toasts")
+                    .full_width(),
+            )
+            .child(
+                LinearLayout::horizontal()
+                    .child(TextView::new("").with_name("status_bar").full_width())
+                    .child(TextView::new(offline_label)),
+            )
+            .full_screen(),
+    );
+    draw_toasts(siv);
+
+    // Reports and lists cover only the account being viewed.
+    let (account, account_name) = {
+        let db = db.lock().expect("Failed to lock database");
+        let account = db.account();
+        let name = db
+            .get_accounts()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|a| a.id.is_some() && a.id == account)
+            .map_or_else(|| "All accounts".to_string(), |a| a.name);
+        (account, name)
+    };
+
     let mut select = SelectView::new().h_align(HAlign::Center);
 
     select.add_item("Add New Trade", 1);
-    select.add_item("View/Edit Trades", 2);
-    select.add_item("View Reports", 3);
-    select.add_item("Quit", 4);
+    select.add_item("Quick Add", 18);
+    select.add_item("Add From Template", 2);
+    select.add_item("View/Edit Trades", 3);
+    select.add_item("Import Trades", 4);
+    select.add_item("Trash", 14);
+    select.add_item("Archive", 17);
+    select.add_item("Open Positions", 5);
+    select.add_item("Expiration Calendar", 6);
+    select.add_item("Past Expirations", 19);
+    select.add_item("View Reports", 7);
+    select.add_item("Corporate Actions", 8);
+    select.add_item("Dividends", 9);
+    select.add_item("Cash", 16);
+    select.add_item("Alerts", 10);
+    select.add_item(format!("Account: {}", account_name), 11);
+    select.add_item(if offline { "Go Online" } else { "Go Offline" }, 12);
+    select.add_item("Settings", 15);
+    select.add_item("Quit", 13);
 
     let db_clone = db.clone();
     select.set_on_submit(move |s, item: &i32| match item {
         1 => show_add_trade(s, db_clone.clone(), None),
-        2 => show_view_trades(s, db_clone.clone()),
-        3 => show_reports(s, db_clone.clone()),
-        4 => s.quit(),
+        2 => show_templates(s, db_clone.clone()),
+        18 => show_quick_add(s, db_clone.clone()),
+        3 => {
+            if let Some(state) = s.user_data::<UiState>() {
+                state.trade_page = 0;
+                state.trade_row = 0;
+                state.trade_marks.clear();
+                state.mark_anchor = None;
+            }
+            show_view_trades(s, db_clone.clone())
+        }
+        4 => show_import(s, db_clone.clone()),
+        5 => show_positions(s, db_clone.clone()),
+        6 => show_expiration_calendar(s, db_clone.clone()),
+        19 => show_past_expirations(s, db_clone.clone()),
+        7 => show_reports(s, db_clone.clone()),
+        8 => show_corporate_actions(s, db_clone.clone()),
+        9 => show_dividends(s, db_clone.clone()),
+        10 => show_alerts(s, db_clone.clone()),
+        11 => show_accounts(s, db_clone.clone()),
+        12 => {
+            if let Some(state) = s.user_data::<UiState>() {
+                state.config.offline = !state.config.offline;
+            }
+            show_main_menu(s, db_clone.clone());
+        }
+        13 => s.quit(),
+        14 => show_trash(s, db_clone.clone()),
+        15 => show_settings(s, db_clone.clone()),
+        16 => show_cash(s, db_clone.clone()),
+        17 => show_archive(s, db_clone.clone()),
         _ => {}
     });
 
+    let body = LinearLayout::vertical()
+        .child(TextView::new("").with_name("alert_banner"))
+        .child(
+            LinearLayout::horizontal()
+                .child(select.scrollable().fixed_size((26, 17)))
+                .child(DummyView.fixed_width(2))
+                .child(
+                    TextView::new("Loading...")
+                        .with_name("dashboard")
+                        .scrollable()
+                        .fixed_size((60, 17)),
+                ),
+        );
+    let title = match account {
+        Some(_) => format!("Stock Options Tracker — {}", account_name),
+        None => "Stock Options Tracker".to_string(),
+    };
     siv.add_layer(
-        Dialog::around(select.scrollable().fixed_size((40, 10)))
-            .title("Stock Options Tracker")
+        Dialog::around(body)
+            .title(title)
             .button("Quit", |s| s.quit()),
     );
+    update_alert_banner(siv, &db);
+    load_dashboard(siv, db.clone());
 
     // Surface a non-blocking alert about options past expiration that are still
     // open, so the user can go resolve them.
-    if let Ok(unresolved) = db.lock().expect("Failed to lock database").get_all_trades() {
-        maybe_show_expiration_alert(siv, &unresolved);
+    let open_options = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_open_options_by_expiration(None, None);
+    if let Ok(open_options) = open_options {
+        maybe_show_expiration_alert(siv, &db, &open_options);
+    }
+}
+
+// The colors of the theme in the settings.
+fn colors(siv: &mut Cursive) -> Theme {
+    siv.user_data::<UiState>()
+        .map(|state| state.config.theme)
+        .unwrap_or_default()
+}
+
+// Fills the main menu's dashboard: the account's value, its open positions,
+// this month's realized P/L, the next expirations, and the latest trades.
+fn load_dashboard(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    run_in_background(
+        siv,
+        "Loading dashboard",
+        move || {
+            let now = today();
+            let db = db.lock().expect("Failed to lock database");
+            let valuation = account_valuation(&db, &now)?;
+            let month = DateRange {
+                start: Some(format!("{}-01", &now[..7])),
+                end: None,
+            };
+            let realized = db
+                .get_realized_lots(method, &month)?
+                .iter()
+                .map(|lot| lot.realized)
+                .sum();
+            let expirations = db.get_open_options_by_expiration(Some(&now), None)?;
+            let recent = db.query_trades(&TradeFilter::default(), Some(DASHBOARD_ROWS), 0)?;
+            Ok(format_dashboard(
+                &valuation,
+                realized,
+                &expirations,
+                &recent,
+                &now,
+            ))
+        },
+        |s, result: rusqlite::Result<String>| {
+            let text = result.unwrap_or_else(|e| format!("Database error: {}", e));
+            s.call_on_name("dashboard", |view: &mut TextView| view.set_content(text));
+        },
+    );
+}
+
+// Upcoming expirations and recent trades listed on the dashboard.
+const DASHBOARD_ROWS: usize = 5;
+
+fn format_dashboard(
+    valuation: &Valuation,
+    realized: Decimal,
+    expirations: &[Trade],
+    recent: &[Trade],
+    today: &str,
+) -> String {
+    let mut lines = vec![
+        format!("Account value:     {:.2}", valuation.account_value()),
+        format!("Open positions:    {}", valuation.positions.len()),
+        format!("Realized P/L ({}): {:.2}", &today[..7], realized),
+        String::new(),
+        "Upcoming expirations".to_string(),
+    ];
+    if expirations.is_empty() {
+        lines.push("  none".to_string());
+    }
+    for trade in expirations.iter().take(DASHBOARD_ROWS) {
+        let expiration = trade.expiration.as_deref().unwrap_or("");
+        lines.push(format!(
+            "  {} {:<6} {} {} x{} ({})",
+            expiration,
+            trade.symbol,
+            trade.option_type.map(|t| t.to_string()).unwrap_or_default(),
+            trade.strike.map(|s| s.to_string()).unwrap_or_default(),
+            trade.quantity.normalize(),
+            days_to_expiration(today, expiration)
+                .map(format_dte)
+                .unwrap_or_default()
+        ));
+    }
+    lines.push(String::new());
+    lines.push("Recent trades".to_string());
+    if recent.is_empty() {
+        lines.push("  none".to_string());
+    }
+    for trade in recent {
+        lines.push(format!(
+            "  {} {:<6} {} {} @ {}",
+            trade.date,
+            trade.symbol,
+            trade.action,
+            trade.quantity.normalize(),
+            trade.price
+        ));
     }
+    lines.join("\n")
 }
 
+// The trade form: empty for a new trade, filled in from `trade` to edit it,
+// or from a trade without an id (see `duplicate_trade`) to add one like it.
 fn show_add_trade(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Option<Trade>) {
-    let is_edit = trade.is_some();
+    // A copy keeps its account and broker rather than taking the defaults.
+    let prefilled = trade.is_some();
+    let is_edit = trade.as_ref().is_some_and(|t| t.id.is_some());
     let title = if is_edit {
         "Edit Trade"
     } else {
@@ -73,20 +441,24 @@ fn show_add_trade(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Option<Tra
 
     let is_option = trade.trade_type == TradeType::Option;
 
-    // Type dropdown: floats over the form, arrow/Enter or mouse to pick. Its
-    // submit handler shows/hides the option-only rows.
+    // Type dropdown: floats over the form, arrow/Enter or mouse to pick, or
+    // Left/Right to step through. Picking a type shows/hides the option-only
+    // rows.
     let mut trade_type_select = SelectView::<TradeType>::new().popup();
     for t in TradeType::variants() {
         trade_type_select.add_item(t.to_string(), *t);
     }
+    let pick_type = |s: &mut Cursive, t: &TradeType| {
+        let show = *t == TradeType::Option;
+        s.call_on_name("option_fields", |v: &mut HideableView<ListView>| {
+            v.set_visible(show);
+        });
+        fill_commission(s);
+    };
     let trade_type_select = trade_type_select
         .selected(selected_index(TradeType::variants(), trade.trade_type))
-        .on_submit(|s, t: &TradeType| {
-            let show = *t == TradeType::Option;
-            s.call_on_name("option_fields", |v: &mut HideableView<ListView>| {
-                v.set_visible(show);
-            });
-        });
+        .on_submit(pick_type)
+        .on_select(pick_type);
 
     let mut action_select = SelectView::<Action>::new().popup();
     for a in Action::variants() {
@@ -112,40 +484,51 @@ fn show_add_trade(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Option<Tra
             "Symbol:",
             EditView::new()
                 .content(trade.symbol.clone())
+                .on_edit(|s, text, _| populate_from_occ(s, text))
                 .with_name("symbol")
+                .word_keys()
                 .fixed_width(20),
         )
         .child(
             "Type:",
-            trade_type_select.with_name("trade_type").fixed_width(20),
+            stepped(trade_type_select, "trade_type").fixed_width(20),
         )
-        .child("Action:", action_select.with_name("action").fixed_width(20))
+        .child("Action:", stepped(action_select, "action").fixed_width(20))
         .child(
             "Price:",
             EditView::new()
                 .content(format_amount(trade.price))
                 .with_name("price")
+                .word_keys()
                 .fixed_width(20),
         )
         .child(
             "Quantity:",
             EditView::new()
                 .content(format_amount(trade.quantity))
+                .on_edit(|s, _, _| fill_commission(s))
                 .with_name("quantity")
+                .word_keys()
                 .fixed_width(20),
         )
         .child(
             "Date (YYYY-MM-DD):",
-            EditView::new()
-                .content(trade.date.clone())
-                .with_name("date")
-                .fixed_width(20),
+            date_field(
+                "date",
+                if trade.date.is_empty() {
+                    today()
+                } else {
+                    trade.date.clone()
+                },
+                20,
+            ),
         )
         .child(
             "Fees:",
             EditView::new()
                 .content(format_amount(trade.fees))
                 .with_name("fees")
+                .word_keys()
                 .fixed_width(20),
         );
 
@@ -153,33 +536,160 @@ fn show_add_trade(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Option<Tra
     let option_form = ListView::new()
         .child(
             "Option Type:",
-            option_type_select.with_name("option_type").fixed_width(20),
+            stepped(option_type_select, "option_type").fixed_width(20),
         )
         .child(
             "Strike:",
             EditView::new()
                 .content(trade.strike.map(format_amount).unwrap_or_default())
                 .with_name("strike")
+                .word_keys()
                 .fixed_width(20),
         )
         .child(
             "Expiration (YYYY-MM-DD):",
+            date_field(
+                "expiration",
+                trade.expiration.clone().unwrap_or_default(),
+                20,
+            ),
+        )
+        .child(
+            "Multiplier:",
             EditView::new()
-                .content(trade.expiration.clone().unwrap_or_default())
-                .with_name("expiration")
+                .content(
+                    trade
+                        .contract_multiplier
+                        .unwrap_or(OPTION_MULTIPLIER)
+                        .normalize()
+                        .to_string(),
+                )
+                .with_name("multiplier")
+                .word_keys()
                 .fixed_width(20),
         );
+    // Greeks are optional and may be negative, so blanks are kept blank.
+    let greek_fields = [
+        ("Delta:", "delta", trade.greeks.delta),
+        ("Gamma:", "gamma", trade.greeks.gamma),
+        ("Theta:", "theta", trade.greeks.theta),
+        ("Vega:", "vega", trade.greeks.vega),
+        ("IV (%):", "iv", trade.greeks.iv),
+    ];
+    let option_form = greek_fields
+        .into_iter()
+        .fold(option_form, |form, (label, name, value)| {
+            form.child(
+                label,
+                EditView::new()
+                    .content(value.map(|v| v.normalize().to_string()).unwrap_or_default())
+                    .with_name(name)
+                    .word_keys()
+                    .fixed_width(20),
+            )
+        });
     let option_form = HideableView::new(option_form)
         .visible(is_option)
         .with_name("option_fields");
 
-    let bottom_form = ListView::new().child(
-        "Comment:",
+    // A new trade goes in the account being viewed unless another is picked.
+    let (accounts, current_account, known_tags) = {
+        let db = db.lock().expect("Failed to lock database");
+        (
+            db.get_accounts().unwrap_or_default(),
+            db.account(),
+            db.get_tags().unwrap_or_default(),
+        )
+    };
+    let mut bottom_form = ListView::new()
+        .child(
+            "Comment:",
+            EditView::new()
+                .content(trade.comment.clone())
+                .with_name("comment")
+                .word_keys()
+                .fixed_width(20),
+        )
+        .child(
+            "Tags (comma-separated):",
+            EditView::new()
+                .content(trade.tags.join(", "))
+                .with_name("tags")
+                .word_keys()
+                .fixed_width(30),
+        );
+    if !known_tags.is_empty() {
+        bottom_form.add_child(
+            "",
+            TextView::new(format!("In use: {}", known_tags.join(", "))),
+        );
+    }
+    let account_id = if prefilled {
+        trade.account_id
+    } else {
+        current_account
+    };
+    // A new trade is priced in its account's currency until told otherwise.
+    let currency = accounts
+        .iter()
+        .find(|a| !prefilled && a.id.is_some() && a.id == account_id)
+        .map_or_else(|| trade.currency.clone(), |a| a.currency.clone());
+    bottom_form.add_child(
+        "Currency:",
+        EditView::new()
+            .content(currency)
+            .with_name("currency")
+            .word_keys()
+            .fixed_width(20),
+    );
+    // A new trade is placed with the first broker that has a commission
+    // schedule until told otherwise.
+    let schedules = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.commissions.clone())
+        .unwrap_or_default();
+    let broker = if prefilled {
+        trade.broker.clone().unwrap_or_default()
+    } else {
+        schedules
+            .first()
+            .map(|schedule| schedule.broker.clone())
+            .unwrap_or_default()
+    };
+    bottom_form.add_child(
+        "Broker:",
         EditView::new()
-            .content(trade.comment.clone())
-            .with_name("comment")
+            .content(broker)
+            .on_edit(|s, _, _| fill_commission(s))
+            .with_name("broker")
+            .word_keys()
             .fixed_width(20),
     );
+    if !schedules.is_empty() {
+        let names: Vec<&str> = schedules.iter().map(|s| s.broker.as_str()).collect();
+        bottom_form.add_child(
+            "",
+            TextView::new(format!("Fees filled in for: {}", names.join(", "))),
+        );
+    }
+    if !accounts.is_empty() {
+        let mut account_select = SelectView::<Option<i64>>::new().popup();
+        account_select.add_item("(none)", None);
+        for account in &accounts {
+            account_select.add_item(account.name.clone(), account.id);
+        }
+        let selected = accounts
+            .iter()
+            .position(|a| a.id.is_some() && a.id == account_id)
+            .map_or(0, |i| i + 1);
+        bottom_form.add_child(
+            "Account:",
+            account_select
+                .selected(selected)
+                .with_name("account")
+                .fixed_width(20),
+        );
+    }
 
     let form = LinearLayout::vertical()
         .child(top_form)
@@ -189,99 +699,422 @@ fn show_add_trade(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Option<Tra
     let trade_id = trade.id;
     let existing_status = trade.status.clone();
     let existing_assigned_from = trade.assigned_from;
+    let existing_roll_group_id = trade.roll_group_id;
+    let existing_strategy_id = trade.strategy_id;
     let db_clone = db.clone();
 
     let help = TextView::new(
         "Type, Action, and Option Type are dropdowns: Tab to focus, Enter or click\n\
          to open, arrow keys + Enter (or a click) to pick.\n\
-         Option Type / Strike / Expiration apply only when Type is 'option'.",
+         Option Type / Strike / Expiration / Multiplier / greeks apply only when Type\n\
+         is 'option'; greeks are per share and optional.\n\
+         Typing a full OCC symbol (e.g. AAPL240119C00190000) fills in the option,\n\
+         or press Ctrl+O (Chain) to pick a contract from the symbol's option chain.",
     );
     let body = LinearLayout::vertical()
         .child(help)
         .child(form.scrollable().fixed_size((56, 18)));
 
+    let dialog = Dialog::around(body)
+        .title(title)
+        .button("Chain", show_option_chain);
     siv.add_layer(
-        Dialog::around(body)
-            .title(title)
+        OnEventView::new(
+            dialog
+                .button("Save", move |s| {
+                    let parsed = match read_and_validate_form(s) {
+                        Some(p) => p,
+                        None => return,
+                    };
+
+                    let currency =
+                        match parse_currency(&read_field(s, "currency").unwrap_or_default()) {
+                            Ok(currency) => currency,
+                            Err(e) => {
+                                s.add_layer(Dialog::info(e));
+                                return;
+                            }
+                        };
+
+                    // A broker with a schedule is saved under the schedule's
+                    // spelling, so the fee report groups it as one.
+                    let broker = read_field(s, "broker").unwrap_or_default();
+                    let broker = match schedule_for(&schedules, &broker) {
+                        Some(schedule) => Some(schedule.broker.clone()),
+                        None => Some(broker.trim().to_string()).filter(|b| !b.is_empty()),
+                    };
+
+                    let status = if parsed.trade_type == TradeType::Option {
+                        // Preserve an existing option's lifecycle status on edit;
+                        // new options start Open.
+                        Some(existing_status.clone().unwrap_or(OptionStatus::Open))
+                    } else {
+                        None
+                    };
+
+                    let new_trade = Trade {
+                        id: trade_id,
+                        symbol: parsed.symbol.clone(),
+                        trade_type: parsed.trade_type,
+                        action: parsed.action,
+                        price: parsed.price,
+                        quantity: parsed.quantity,
+                        date: parsed.date,
+                        fees: parsed.fees,
+                        comment: parsed.comment,
+                        option_type: parsed.option_type,
+                        strike: parsed.strike,
+                        expiration: parsed.expiration,
+                        status,
+                        contract_multiplier: parsed.contract_multiplier,
+                        assigned_from: existing_assigned_from,
+                        roll_group_id: existing_roll_group_id,
+                        strategy_id: existing_strategy_id,
+                        account_id: read_select(s, "account").unwrap_or(account_id),
+                        tags: parse_tags(&read_field(s, "tags").unwrap_or_default()),
+                        deleted_at: None,
+                        greeks: parsed.greeks,
+                        currency,
+                        broker,
+                    };
+
+                    save_trade(s, &db_clone, new_trade);
+                })
+                .button("Cancel", move |s| {
+                    s.pop_layer();
+                }),
+        )
+        .on_event(Event::CtrlChar('o'), show_option_chain)
+        .with_name(trade_id.map_or_else(|| "add_trade".to_string(), edit_form_name)),
+    );
+}
+
+// The name of the form editing trade `id`, for undo to find.
+fn edit_form_name(id: i64) -> String {
+    format!("edit_trade_{}", id)
+}
+
+// Picks a multi-leg strategy template to enter.
+fn show_templates(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let mut select = SelectView::<&'static StrategyTemplate>::new().h_align(HAlign::Left);
+    for template in TEMPLATES {
+        let legs: Vec<String> = template.legs.iter().map(|l| l.label()).collect();
+        select.add_item(
+            format!("{:<18} {}", template.name, legs.join(" / ")),
+            template,
+        );
+    }
+    select.set_on_submit(move |s, template: &&'static StrategyTemplate| {
+        s.pop_layer();
+        show_template_entry(s, db.clone(), template);
+    });
+
+    siv.add_layer(
+        Dialog::around(select.scrollable().fixed_size((70, 10)))
+            .title("Add From Template")
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Entry form for every leg of a template at once: shared symbol, expiration,
+// date, quantity, and fees, plus a strike and price per leg. Saving creates
+// the legs and a strategy grouping them in one transaction.
+fn show_template_entry(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    template: &'static StrategyTemplate,
+) {
+    let mut form = ListView::new()
+        .child(
+            "Symbol:",
+            EditView::new()
+                .with_name("tpl_symbol")
+                .word_keys()
+                .fixed_width(20),
+        )
+        .child(
+            "Expiration (YYYY-MM-DD):",
+            date_field("tpl_expiration", String::new(), 20),
+        )
+        .child("Date (YYYY-MM-DD):", date_field("tpl_date", today(), 20))
+        .child(
+            "Quantity:",
+            EditView::new()
+                .content("1")
+                .with_name("tpl_quantity")
+                .word_keys()
+                .fixed_width(20),
+        )
+        .child(
+            "Fees per leg:",
+            EditView::new()
+                .content("0")
+                .with_name("tpl_fees")
+                .word_keys()
+                .fixed_width(20),
+        );
+    form.add_delimiter();
+    for (i, leg) in template.legs.iter().enumerate() {
+        form.add_child(
+            format!("Leg {} {} strike:", i + 1, leg.label()),
+            EditView::new()
+                .with_name(format!("tpl_strike_{}", i))
+                .word_keys()
+                .fixed_width(20),
+        );
+        form.add_child(
+            format!("Leg {} {} price:", i + 1, leg.label()),
+            EditView::new()
+                .with_name(format!("tpl_price_{}", i))
+                .word_keys()
+                .fixed_width(20),
+        );
+    }
+
+    siv.add_layer(
+        Dialog::around(form.scrollable().fixed_size((56, 18)))
+            .title(template.name)
             .button("Save", move |s| {
-                let parsed = match read_and_validate_form(s) {
-                    Some(p) => p,
+                let legs = match read_template_form(s, template) {
+                    Some(legs) => legs,
                     None => return,
                 };
-
-                let status = if parsed.trade_type == TradeType::Option {
-                    // Preserve an existing option's lifecycle status on edit;
-                    // new options start Open.
-                    Some(existing_status.clone().unwrap_or(OptionStatus::Open))
-                } else {
-                    None
-                };
-
-                let new_trade = Trade {
-                    id: trade_id,
-                    symbol: parsed.symbol.clone(),
-                    trade_type: parsed.trade_type,
-                    action: parsed.action,
-                    price: parsed.price,
-                    quantity: parsed.quantity,
-                    date: parsed.date,
-                    fees: parsed.fees,
-                    comment: parsed.comment,
-                    option_type: parsed.option_type,
-                    strike: parsed.strike,
-                    expiration: parsed.expiration,
-                    status,
-                    assigned_from: existing_assigned_from,
+                let strategy = Strategy {
+                    id: None,
+                    name: format!("{} {}", legs[0].symbol, template.name),
+                    kind: template.kind,
                 };
-
-                // Covered-call warning: writing a call below the underlying's
-                // break-even would lock in a loss if assigned. Warn (do not
-                // block) and let the user confirm.
-                if matches!(parsed.action, Action::SellToOpen)
-                    && parsed.trade_type == TradeType::Option
-                    && parsed.option_type == Some(OptionType::Call)
-                {
-                    // Exclude the option being edited so its pre-edit premium
-                    // doesn't skew the threshold (no-op for a brand-new trade,
-                    // whose id is None).
-                    let break_even = db_clone
-                        .lock()
-                        .expect("Failed to lock database")
-                        .get_break_even_excluding(&parsed.symbol, trade_id)
-                        .ok()
-                        .flatten();
-                    if let (Some(be), Some(strike)) = (break_even, parsed.strike) {
-                        if strike < be {
-                            let db_inner = db_clone.clone();
-                            let trade_inner = new_trade.clone();
-                            s.add_layer(
-                                Dialog::text(format!(
-                                    "Warning: strike ${:.2} is below the {} break-even of ${:.2}. \
-                                     If assigned, this covered call locks in a loss.",
-                                    strike, parsed.symbol, be
-                                ))
-                                .title("Covered call below break-even")
-                                .button("Save Anyway", move |s| {
-                                    s.pop_layer();
-                                    persist_trade(s, &db_inner, &trade_inner);
-                                })
-                                .button("Cancel", |s| {
-                                    s.pop_layer();
-                                }),
-                            );
-                            return;
-                        }
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .add_strategy_with_legs(&strategy, &legs);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.add_layer(Dialog::text(format!("Saved {} legs", legs.len())).button(
+                            "OK",
+                            |s| {
+                                s.pop_layer();
+                            },
+                        ));
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
                     }
                 }
-
-                persist_trade(s, &db_clone, &new_trade);
             })
-            .button("Cancel", move |s| {
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Reads and validates the template form, returning the legs to insert.
+fn read_template_form(s: &mut Cursive, template: &StrategyTemplate) -> Option<Vec<Trade>> {
+    let symbol = read_field(s, "tpl_symbol")?.trim().to_uppercase();
+    if symbol.is_empty() {
+        s.add_layer(Dialog::info("Symbol is required"));
+        return None;
+    }
+    let expiration = read_field(s, "tpl_expiration")?.trim().to_string();
+    let Some(expiration) = parse_date_field(&expiration) else {
+        s.add_layer(Dialog::info(
+            "Invalid expiration format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+        ));
+        return None;
+    };
+    let date = read_field(s, "tpl_date")?.trim().to_string();
+    let Some(date) = parse_date_field(&date) else {
+        s.add_layer(Dialog::info(
+            "Invalid date format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+        ));
+        return None;
+    };
+    let quantity = read_field(s, "tpl_quantity")?;
+    let quantity = parse_amount(s, quantity.trim(), "quantity", false)?;
+    let fees = read_field(s, "tpl_fees")?;
+    let fees = parse_amount(s, fees.trim(), "fees", true)?;
+
+    let mut strikes_and_prices = Vec::with_capacity(template.legs.len());
+    for i in 0..template.legs.len() {
+        let strike = read_field(s, &format!("tpl_strike_{}", i))?;
+        let strike = parse_amount(s, strike.trim(), &format!("leg {} strike", i + 1), false)?;
+        let price = read_field(s, &format!("tpl_price_{}", i))?;
+        let price = parse_amount(s, price.trim(), &format!("leg {} price", i + 1), true)?;
+        strikes_and_prices.push((strike, price));
+    }
+
+    let shared = SharedLegFields {
+        symbol,
+        expiration,
+        date,
+        quantity,
+        fees,
+    };
+    Some(template.build_legs(&shared, &strikes_and_prices))
+}
+
+// When the Symbol field holds a full OCC symbol, splits it into the underlying
+// and the option fields (switching Type to option so they are visible).
+fn populate_from_occ(s: &mut Cursive, text: &str) {
+    let occ: OccSymbol = match text.parse() {
+        Ok(occ) => occ,
+        Err(_) => return,
+    };
+    s.call_on_name("symbol", |v: &mut EditView| {
+        v.set_content(occ.underlying.clone());
+    });
+    s.call_on_name("trade_type", |v: &mut SelectView<TradeType>| {
+        v.set_selection(selected_index(TradeType::variants(), TradeType::Option));
+    });
+    s.call_on_name("option_fields", |v: &mut HideableView<ListView>| {
+        v.set_visible(true);
+    });
+    s.call_on_name("option_type", |v: &mut SelectView<OptionType>| {
+        v.set_selection(selected_index(OptionType::variants(), occ.option_type));
+    });
+    s.call_on_name("strike", |v: &mut EditView| {
+        v.set_content(occ.strike.to_string());
+    });
+    s.call_on_name("expiration", |v: &mut EditView| {
+        v.set_content(occ.expiration.clone());
+    });
+    fill_commission(s);
+}
+
+// Fills in the trade form's fees from the commission schedule of its broker,
+// for the type and quantity entered. Brokers without one leave them alone.
+fn fill_commission(s: &mut Cursive) {
+    let broker = read_field(s, "broker").unwrap_or_default();
+    let schedule = s
+        .user_data::<UiState>()
+        .and_then(|state| schedule_for(&state.config.commissions, &broker).cloned());
+    let Some(schedule) = schedule else {
+        return;
+    };
+    let Ok(quantity) = read_field(s, "quantity")
+        .unwrap_or_default()
+        .trim()
+        .parse::<Decimal>()
+    else {
+        return;
+    };
+    let trade_type = read_select::<TradeType>(s, "trade_type").unwrap_or(TradeType::Stock);
+    let fees = schedule.commission(trade_type, quantity);
+    s.call_on_name("fees", |v: &mut EditView| {
+        v.set_content(format_amount(fees));
+    });
+}
+
+// Downloads the option chain of the symbol typed in the trade form and lets
+// the user pick an expiration, then a contract, to fill in the option fields.
+fn show_option_chain(s: &mut Cursive) {
+    let symbol = read_field(s, "symbol")
+        .unwrap_or_default()
+        .trim()
+        .to_uppercase();
+    if symbol.is_empty() {
+        s.add_layer(Dialog::info("Enter a symbol first"));
+        return;
+    }
+    let chain = match market_data(s).option_chain(&symbol, None) {
+        Ok(chain) => chain,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Could not fetch the chain: {}", e)));
+            return;
+        }
+    };
+    if chain.expirations.is_empty() {
+        s.add_layer(Dialog::info(format!("No options listed for {}", symbol)));
+        return;
+    }
+
+    let now = today();
+    let mut select = SelectView::<String>::new().h_align(HAlign::Left);
+    for expiration in chain.expirations {
+        let dte = days_to_expiration(&now, &expiration)
+            .map(format_dte)
+            .unwrap_or_default();
+        select.add_item(format!("{}  {}", expiration, dte), expiration);
+    }
+    select.set_on_submit(move |s, expiration: &String| {
+        let chain = match market_data(s).option_chain(&symbol, Some(expiration)) {
+            Ok(chain) => chain,
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Could not fetch the chain: {}", e)));
+                return;
+            }
+        };
+        show_chain_contracts(s, expiration, chain.contracts);
+    });
+    s.add_layer(
+        Dialog::around(select.scrollable().fixed_size((30, 15)))
+            .title("Expiration")
+            .button("Cancel", |s| {
                 s.pop_layer();
             }),
     );
 }
 
+// Lists one expiration's contracts; picking one fills the trade form with it,
+// priced at the bid/ask midpoint.
+fn show_chain_contracts(s: &mut Cursive, expiration: &str, contracts: Vec<ChainContract>) {
+    let quote = |value: Option<Decimal>| {
+        value
+            .map(|v| format!("{:.2}", v))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let header = format!(
+        "{:<5} {:>10} {:>8} {:>8} {:>8} {:>7}",
+        "Type", "Strike", "Bid", "Ask", "Last", "IV"
+    );
+    let mut select = SelectView::<ChainContract>::new().h_align(HAlign::Left);
+    for contract in contracts {
+        let row = format!(
+            "{:<5} {:>10} {:>8} {:>8} {:>8} {:>7}",
+            contract.occ.option_type.as_str().to_uppercase(),
+            format!("${:.2}", contract.occ.strike),
+            quote(contract.bid),
+            quote(contract.ask),
+            quote(contract.last),
+            contract
+                .iv
+                .map(|iv| format!("{:.1}%", iv))
+                .unwrap_or_else(|| "-".to_string()),
+        );
+        select.add_item(row, contract);
+    }
+    select.set_on_submit(|s, contract: &ChainContract| {
+        // Close the contract and expiration lists.
+        s.pop_layer();
+        s.pop_layer();
+        populate_from_occ(s, &contract.occ.to_string());
+        if let Some(mid) = contract.mid() {
+            s.call_on_name("price", |v: &mut EditView| {
+                v.set_content(format_amount(mid));
+            });
+        }
+        if let Some(iv) = contract.iv {
+            s.call_on_name("iv", |v: &mut EditView| {
+                v.set_content(iv.normalize().to_string());
+            });
+        }
+    });
+    s.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(header))
+                .child(select.scrollable().fixed_size((52, 18))),
+        )
+        .title(format!("Contracts expiring {}", expiration))
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
 // Values read from the Add/Edit form after validation.
 struct ParsedTrade {
     symbol: String,
@@ -295,6 +1128,8 @@ struct ParsedTrade {
     option_type: Option<OptionType>,
     strike: Option<Decimal>,
     expiration: Option<String>,
+    contract_multiplier: Option<Decimal>,
+    greeks: Greeks,
 }
 
 // Index of `value` within `variants`, used to preselect a dropdown. Falls back
@@ -303,6 +1138,32 @@ fn selected_index<T: PartialEq>(variants: &[T], value: T) -> usize {
     variants.iter().position(|v| *v == value).unwrap_or(0)
 }
 
+// Names a popup selector `name` and lets Left and Right step it through its
+// choices, wrapping around, and a typed letter jump to the first choice
+// starting with it. Only its choices can be entered.
+fn stepped<T: Send + Sync + 'static>(
+    select: SelectView<T>,
+    name: &str,
+) -> OnEventView<NamedView<SelectView<T>>> {
+    let step = |view: &mut NamedView<SelectView<T>>, forward: bool| {
+        let mut select = view.get_mut();
+        let len = select.len();
+        if len == 0 {
+            return None;
+        }
+        let current = select.selected_id().unwrap_or(0);
+        let next = if forward {
+            (current + 1) % len
+        } else {
+            (current + len - 1) % len
+        };
+        Some(EventResult::Consumed(Some(select.set_selection(next))))
+    };
+    OnEventView::new(select.autojump().with_name(name))
+        .on_pre_event_inner(Key::Left, move |view, _| step(view, false))
+        .on_pre_event_inner(Key::Right, move |view, _| step(view, true))
+}
+
 // Reads the current selection of a popup `SelectView` by name.
 fn read_select<T: Clone + Send + Sync + 'static>(s: &mut Cursive, name: &str) -> Option<T> {
     s.call_on_name(name, |view: &mut SelectView<T>| {
@@ -311,42 +1172,125 @@ fn read_select<T: Clone + Send + Sync + 'static>(s: &mut Cursive, name: &str) ->
     .flatten()
 }
 
-// Reads and validates every form field, showing an error dialog and returning
-// None on the first problem.
-fn read_and_validate_form(s: &mut Cursive) -> Option<ParsedTrade> {
-    let read_field = |s: &mut Cursive, name: &str| {
-        s.call_on_name(name, |view: &mut EditView| view.get_content().to_string())
-    };
+// Word-wise editing for text fields, on top of `EditView`'s own keys (arrows,
+// Home/End, Delete, Ctrl+A/E/K/U): Ctrl+Left/Right move the cursor a word at
+// a time and Ctrl+W deletes the word before it.
+trait WordKeys: View + Sized {
+    fn with_edit<R>(&mut self, f: impl FnOnce(&mut EditView) -> R) -> R;
 
-    // Dropdowns guarantee a valid enum value, so these only fail to read on an
-    // internal wiring error.
-    let trade_type = read_select::<TradeType>(s, "trade_type");
-    let action = read_select::<Action>(s, "action");
-    let option_type_sel = read_select::<OptionType>(s, "option_type");
+    fn word_keys(self) -> OnEventView<Self> {
+        OnEventView::new(self)
+            .on_pre_event_inner(Event::Ctrl(Key::Left), |view, _| {
+                view.with_edit(|edit| {
+                    edit.set_cursor(word_left(&edit.get_content(), edit.get_cursor()))
+                });
+                Some(EventResult::Consumed(None))
+            })
+            .on_pre_event_inner(Event::Ctrl(Key::Right), |view, _| {
+                view.with_edit(|edit| {
+                    edit.set_cursor(word_right(&edit.get_content(), edit.get_cursor()))
+                });
+                Some(EventResult::Consumed(None))
+            })
+            .on_pre_event_inner(Event::CtrlChar('w'), |view, _| {
+                let on_edit = view.with_edit(|edit| {
+                    let cursor = edit.get_cursor();
+                    let start = word_left(&edit.get_content(), cursor);
+                    edit.set_cursor(start);
+                    edit.remove(cursor - start)
+                });
+                Some(EventResult::Consumed(Some(on_edit)))
+            })
+    }
+}
 
-    let fields = (|| {
-        Some((
-            read_field(s, "symbol")?,
+impl WordKeys for EditView {
+    fn with_edit<R>(&mut self, f: impl FnOnce(&mut EditView) -> R) -> R {
+        f(self)
+    }
+}
+
+impl WordKeys for NamedView<EditView> {
+    fn with_edit<R>(&mut self, f: impl FnOnce(&mut EditView) -> R) -> R {
+        f(&mut self.get_mut())
+    }
+}
+
+// The start of the word before byte `cursor` of `text`, skipping spaces and
+// punctuation first.
+fn word_left(text: &str, cursor: usize) -> usize {
+    let before = &text[..cursor];
+    let end = before
+        .trim_end_matches(|c: char| !c.is_alphanumeric())
+        .len();
+    before[..end]
+        .rfind(|c: char| !c.is_alphanumeric())
+        .map_or(0, |at| {
+            at + before[at..].chars().next().map_or(1, char::len_utf8)
+        })
+}
+
+// The end of the word after byte `cursor` of `text`, skipping spaces and
+// punctuation first.
+fn word_right(text: &str, cursor: usize) -> usize {
+    let after = &text[cursor..];
+    let start = after.len()
+        - after
+            .trim_start_matches(|c: char| !c.is_alphanumeric())
+            .len();
+    cursor
+        + after[start..]
+            .find(|c: char| !c.is_alphanumeric())
+            .map_or(after.len(), |at| start + at)
+}
+
+// Reads the text of an `EditView` by name.
+fn read_field(s: &mut Cursive, name: &str) -> Option<String> {
+    s.call_on_name(name, |view: &mut EditView| view.get_content().to_string())
+}
+
+// Reads and validates every form field, showing an error dialog and returning
+// None on the first problem.
+fn read_and_validate_form(s: &mut Cursive) -> Option<ParsedTrade> {
+    // Dropdowns guarantee a valid enum value, so these only fail to read on an
+    // internal wiring error.
+    let trade_type = read_select::<TradeType>(s, "trade_type");
+    let action = read_select::<Action>(s, "action");
+    let option_type_sel = read_select::<OptionType>(s, "option_type");
+
+    let fields = (|| {
+        Some((
+            read_field(s, "symbol")?,
             read_field(s, "price")?,
             read_field(s, "quantity")?,
             read_field(s, "date")?,
             read_field(s, "fees")?,
             read_field(s, "strike")?,
             read_field(s, "expiration")?,
+            read_field(s, "multiplier")?,
             read_field(s, "comment")?,
         ))
     })();
 
-    let (symbol, price_str, quantity_str, date, fees_str, strike_str, expiration_str, comment) =
-        match fields {
-            Some(values) => values,
-            None => {
-                s.add_layer(Dialog::info(
-                    "Internal error: could not read one or more form fields",
-                ));
-                return None;
-            }
-        };
+    let (
+        symbol,
+        price_str,
+        quantity_str,
+        date,
+        fees_str,
+        strike_str,
+        expiration_str,
+        multiplier_str,
+        comment,
+    ) = match fields {
+        Some(values) => values,
+        None => {
+            s.add_layer(Dialog::info(
+                "Internal error: could not read one or more form fields",
+            ));
+            return None;
+        }
+    };
 
     let (trade_type, action) = match (trade_type, action) {
         (Some(t), Some(a)) => (t, a),
@@ -372,37 +1316,58 @@ fn read_and_validate_form(s: &mut Cursive) -> Option<ParsedTrade> {
         s.add_layer(Dialog::info("Date is required"));
         return None;
     }
-    if !is_valid_date_format(&date) {
-        s.add_layer(Dialog::info("Invalid date format. Use YYYY-MM-DD"));
+    let Some(date) = parse_date_field(&date) else {
+        s.add_layer(Dialog::info(
+            "Invalid date format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+        ));
         return None;
-    }
+    };
 
     // Option-specific fields are required (and validated) only for options. The
     // Option Type dropdown always holds a valid call/put value, so it needs no
     // parse check.
-    let (option_type, strike, expiration) = if trade_type == TradeType::Option {
-        let option_type = match option_type_sel {
-            Some(t) => t,
-            None => {
+    let (option_type, strike, expiration, contract_multiplier, greeks) =
+        if trade_type == TradeType::Option {
+            let option_type = match option_type_sel {
+                Some(t) => t,
+                None => {
+                    s.add_layer(Dialog::info(
+                        "Internal error: could not read the Option Type selector",
+                    ));
+                    return None;
+                }
+            };
+            let strike = parse_amount(s, &strike_str, "strike", false)?;
+            if expiration_str.is_empty() {
+                s.add_layer(Dialog::info("Expiration is required for options"));
+                return None;
+            }
+            let Some(expiration_str) = parse_date_field(&expiration_str) else {
                 s.add_layer(Dialog::info(
-                    "Internal error: could not read the Option Type selector",
+                    "Invalid expiration format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
                 ));
                 return None;
-            }
+            };
+            let multiplier = parse_amount(s, &multiplier_str, "multiplier", false)?;
+            // Standard contracts leave the column NULL so the default applies.
+            let contract_multiplier = (multiplier != OPTION_MULTIPLIER).then_some(multiplier);
+            let greeks = Greeks {
+                delta: parse_greek(s, "delta", "delta")?,
+                gamma: parse_greek(s, "gamma", "gamma")?,
+                theta: parse_greek(s, "theta", "theta")?,
+                vega: parse_greek(s, "vega", "vega")?,
+                iv: parse_greek(s, "iv", "IV")?,
+            };
+            (
+                Some(option_type),
+                Some(strike),
+                Some(expiration_str),
+                contract_multiplier,
+                greeks,
+            )
+        } else {
+            (None, None, None, None, Greeks::default())
         };
-        let strike = parse_amount(s, &strike_str, "strike", false)?;
-        if expiration_str.is_empty() {
-            s.add_layer(Dialog::info("Expiration is required for options"));
-            return None;
-        }
-        if !is_valid_date_format(&expiration_str) {
-            s.add_layer(Dialog::info("Invalid expiration format. Use YYYY-MM-DD"));
-            return None;
-        }
-        (Some(option_type), Some(strike), Some(expiration_str))
-    } else {
-        (None, None, None)
-    };
 
     Some(ParsedTrade {
         symbol,
@@ -416,28 +1381,163 @@ fn read_and_validate_form(s: &mut Cursive) -> Option<ParsedTrade> {
         option_type,
         strike,
         expiration,
+        contract_multiplier,
+        greeks,
     })
 }
 
+// Reads an optional greek field: `Some(None)` when blank, `None` (after an
+// error dialog) when it does not parse.
+fn parse_greek(s: &mut Cursive, name: &str, label: &str) -> Option<Option<Decimal>> {
+    let raw = read_field(s, name).unwrap_or_default();
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Some(None);
+    }
+    match raw.parse::<Decimal>() {
+        Ok(value) => Some(Some(value)),
+        Err(_) => {
+            s.add_layer(Dialog::info(format!("Invalid {}", label)));
+            None
+        }
+    }
+}
+
 // Adds or updates a trade, then shows a confirmation dialog (or an error).
+// Saves `trade` from the form on top, first warning (not blocking) about
+// likely mistakes and letting the user confirm.
+fn save_trade(s: &mut Cursive, db: &Arc<Mutex<Database>>, trade: Trade) {
+    let warnings = trade_warnings(&db.lock().expect("Failed to lock database"), &trade);
+    if warnings.is_empty() {
+        persist_trade(s, db, &trade);
+        return;
+    }
+    let title = match warnings.as_slice() {
+        [(title, _)] => *title,
+        _ => "Warnings",
+    };
+    let text = warnings
+        .iter()
+        .map(|(_, text)| text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let db = db.clone();
+    s.add_layer(
+        Dialog::text(text)
+            .title(title)
+            .button("Save Anyway", move |s| {
+                s.pop_layer();
+                persist_trade(s, &db, &trade);
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// One line, such as `sell 2 AAPL 190c 1/19 @ 2.35 fee .66`, read into a trade
+// as it is typed (see `quick_add`). Enter saves it; Form opens the trade
+// form filled in from it to change what the line can't say.
+fn show_quick_add(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let db_form = db.clone();
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(
+                    EditView::new()
+                        .on_edit(|s, text, _| {
+                            let preview = match parse_quick_add(text, &today()) {
+                                Ok(trade) => format_trade_row(&trade, &today()),
+                                Err(e) => e,
+                            };
+                            s.call_on_name("quick_add_preview", |v: &mut TextView| {
+                                v.set_content(preview)
+                            });
+                        })
+                        .on_submit({
+                            let db = db.clone();
+                            move |s, _| save_quick_add(s, &db)
+                        })
+                        .with_name("quick_add")
+                        .word_keys()
+                        .fixed_width(70),
+                )
+                .child(
+                    TextView::new("e.g. sell 2 AAPL 190c 1/19 @ 2.35 fee .66")
+                        .with_name("quick_add_preview")
+                        .fixed_height(2),
+                ),
+        )
+        .title("Quick Add")
+        .button("Save", move |s| save_quick_add(s, &db))
+        .button("Form", move |s| {
+            if let Some(trade) = read_quick_add(s, &db_form) {
+                s.pop_layer();
+                show_add_trade(s, db_form.clone(), Some(trade));
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+fn save_quick_add(s: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    if let Some(trade) = read_quick_add(s, db) {
+        save_trade(s, db, trade);
+    }
+}
+
+// The Quick Add line as a trade in the account being viewed, priced in its
+// currency; `None` after saying what is wrong with it.
+fn read_quick_add(s: &mut Cursive, db: &Arc<Mutex<Database>>) -> Option<Trade> {
+    let text = read_field(s, "quick_add").unwrap_or_default();
+    let mut trade = match parse_quick_add(&text, &today()) {
+        Ok(trade) => trade,
+        Err(e) => {
+            s.add_layer(Dialog::info(e));
+            return None;
+        }
+    };
+    let db = db.lock().expect("Failed to lock database");
+    trade.account_id = db.account();
+    let accounts = db.get_accounts().unwrap_or_default();
+    if let Some(account) = accounts
+        .into_iter()
+        .find(|a| a.id.is_some() && a.id == trade.account_id)
+    {
+        trade.currency = account.currency;
+    }
+    Some(trade)
+}
+
 fn persist_trade(s: &mut Cursive, db: &Arc<Mutex<Database>>, trade: &Trade) {
     let result = if trade.id.is_some() {
-        db.lock()
-            .expect("Failed to lock database")
-            .update_trade(trade)
+        let db = db.lock().expect("Failed to lock database");
+        db.last_history_id().and_then(|after| {
+            db.update_trade(trade)?;
+            Ok(Some((after, db.last_history_id()?)))
+        })
     } else {
         db.lock()
             .expect("Failed to lock database")
             .add_trade(trade)
-            .map(|_| ())
+            .map(|_| None)
     };
 
     match result {
-        Ok(_) => {
+        Ok(logged) => {
+            if let Some((after, upto)) = logged {
+                push_undo(s, format!("edit of {}", describe_trade(trade)), after, upto);
+            }
             s.pop_layer();
-            s.add_layer(Dialog::text("Trade saved successfully!").button("OK", |s| {
+            // The edit can move the trade in or out of the View/Edit Trades
+            // list underneath; rebuild it under the same filter.
+            if is_top_layer(s, "trade_list") {
                 s.pop_layer();
-            }));
+                show_view_trades(s, db.clone());
+            }
+            show_toast(s, format!("Saved {}", describe_trade(trade)));
         }
         Err(e) => {
             s.add_layer(Dialog::info(format!("Error: {}", e)));
@@ -445,157 +1545,7186 @@ fn persist_trade(s: &mut Cursive, db: &Arc<Mutex<Database>>, trade: &Trade) {
     }
 }
 
+// A new trade like `trade`, dated `today`: the same fields, without its id or
+// anything tying it to other trades, and a new option's status.
+fn duplicate_trade(trade: &Trade, today: &str) -> Trade {
+    Trade {
+        id: None,
+        date: today.to_string(),
+        status: None,
+        assigned_from: None,
+        roll_group_id: None,
+        strategy_id: None,
+        deleted_at: None,
+        ..trade.clone()
+    }
+}
+
+// Opens the trade form filled in from the selected View/Edit Trades row, to
+// add a trade like it.
+fn duplicate_selected_trade(s: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let item = s
+        .call_on_name("trade_list", |v: &mut SelectView<TradeListItem>| {
+            v.selection()
+        })
+        .flatten();
+    match item.as_deref() {
+        Some(TradeListItem::Trade(trade)) => {
+            show_add_trade(s, db.clone(), Some(duplicate_trade(trade, &today())))
+        }
+        Some(TradeListItem::Strategy(_)) => {
+            show_toast(s, "Show Legs to duplicate a strategy's trades".to_string())
+        }
+        None => {}
+    }
+}
+
+// Trades the View/Edit Trades list shows at a time.
+const TRADE_PAGE_SIZE: usize = 100;
+
+// The View/Edit Trades list's width, and its rows', short of the scrollbar.
+// Rows are cut to fit rather than scrolled sideways, which would leave the
+// header behind.
+const TRADE_LIST_WIDTH: usize = 90;
+const TRADE_ROW_WIDTH: usize = TRADE_LIST_WIDTH - 2;
+
 fn show_view_trades(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
-    let trades = match db.lock().expect("Failed to lock database").get_all_trades() {
-        Ok(trades) => trades,
+    let (collapse, filter_text, search, page) = siv
+        .user_data::<UiState>()
+        .map(|state| {
+            (
+                state.collapse_strategies,
+                state.trade_filter.clone(),
+                state.trade_search.clone(),
+                state.trade_page,
+            )
+        })
+        .unwrap_or_default();
+    let order = trade_order(siv);
+    let (columns, method) = siv
+        .user_data::<UiState>()
+        .map(|state| (state.config.trade_columns.clone(), state.cost_basis_method))
+        .unwrap_or_default();
+    let result = {
+        let db = db.lock().expect("Failed to lock database");
+        let page = load_trade_page(&db, &filter_text, &search, order, Some(page));
+        page.and_then(|(trades, total)| {
+            Ok((
+                trades,
+                total,
+                db.get_strategy_summaries()?,
+                db.get_open_options_by_expiration(None, None)?,
+                TradeLayout::new(&db, columns, method)?,
+            ))
+        })
+    };
+    let (trades, total, strategies, open_options, layout) = match result {
+        Ok(result) => result,
         Err(e) => {
             show_dialog_with_back(siv, format!("Database error: {}", e));
             return;
         }
     };
 
-    if trades.is_empty() {
+    if total == 0 && filter_text.is_empty() && search.is_empty() {
         show_dialog_with_back(siv, "No trades found".to_string());
         return;
     }
+    // Deletes can leave the page past the end; show the last one instead.
+    let last_page = total.saturating_sub(1) / TRADE_PAGE_SIZE;
+    if page > last_page {
+        if let Some(state) = siv.user_data::<UiState>() {
+            state.trade_page = last_page;
+        }
+        show_view_trades(siv, db);
+        return;
+    }
 
-    let now = today();
+    let marks = siv
+        .user_data::<UiState>()
+        .map(|state| state.trade_marks.clone())
+        .unwrap_or_default();
+    let (colors, now) = (colors(siv), today());
     let mut select = SelectView::new().h_align(HAlign::Left);
-
-    for trade in trades.iter() {
-        select.add_item(format_trade_row(trade, &now), trade.clone());
+    for (label, item) in trade_list_items(&trades, &strategies, collapse, &layout) {
+        let marked =
+            matches!(&item, TradeListItem::Trade(t) if t.id.is_some_and(|id| marks.contains(&id)));
+        select.add_item(mark_row(&label, &item, marked, &colors, &now), item);
     }
+    let rows = select.len();
+    let row = siv
+        .user_data::<UiState>()
+        .map_or(0, |state| state.trade_row)
+        .min(rows.saturating_sub(1));
+    select.set_selection(row);
+    // Wide terminals show the selected trade beside the list.
+    let detail_width = trade_detail_width(siv.screen_size().x);
+    let preview = match (detail_width, select.selection()) {
+        (Some(_), Some(item)) => {
+            let method = siv
+                .user_data::<UiState>()
+                .map(|state| state.cost_basis_method)
+                .unwrap_or_default();
+            trade_preview(&db.lock().expect("Failed to lock database"), &item, method)
+        }
+        _ => String::new(),
+    };
+    let position = move |s: &mut Cursive| {
+        let (row, marked) = s
+            .user_data::<UiState>()
+            .map(|state| (state.trade_row, state.trade_marks.len()))
+            .unwrap_or_default();
+        s.call_on_name("trade_position", |v: &mut TextView| {
+            v.set_content(trade_position(row, rows, page, last_page, marked))
+        });
+    };
+    let db_preview = db.clone();
+    select.set_on_select(move |s, item| {
+        if detail_width.is_some() {
+            let method = s
+                .user_data::<UiState>()
+                .map(|state| state.cost_basis_method)
+                .unwrap_or_default();
+            let text = trade_preview(
+                &db_preview.lock().expect("Failed to lock database"),
+                item,
+                method,
+            );
+            s.call_on_name("trade_preview", |v: &mut TextView| v.set_content(text));
+        }
+        let row = s
+            .call_on_name("trade_list", |v: &mut SelectView<TradeListItem>| {
+                v.selected_id()
+            })
+            .flatten()
+            .unwrap_or_default();
+        if let Some(state) = s.user_data::<UiState>() {
+            state.trade_row = row;
+        }
+        position(s);
+    });
 
     let db_clone = db.clone();
-    select.set_on_submit(move |s, trade: &Trade| {
-        show_trade_actions(s, db_clone.clone(), trade.clone());
+    select.set_on_submit(move |s, item: &TradeListItem| match item {
+        TradeListItem::Trade(trade) => show_trade_actions(s, db_clone.clone(), (**trade).clone()),
+        TradeListItem::Strategy(summary) => {
+            show_strategy_legs(s, db_clone.clone(), summary.clone())
+        }
     });
 
-    siv.add_layer(
-        Dialog::around(select.scrollable().scroll_x(true).fixed_size((90, 20)))
-            .title("View/Edit Trades")
-            .button("Back", |s| {
+    let mut title = "View/Edit Trades".to_string();
+    if !filter_text.is_empty() {
+        title += &format!(" — {}", filter_text);
+    }
+    if !search.is_empty() {
+        title += &format!(" — \"{}\"", search);
+    }
+    if order.by != TradeSort::Date || order.ascending {
+        title += &format!(
+            " — by {} {}",
+            order.by,
+            if order.ascending { "↑" } else { "↓" }
+        );
+    }
+    let export_name = title.clone();
+    if total > TRADE_PAGE_SIZE {
+        title += &format!(
+            " — {}–{} of {}",
+            page * TRADE_PAGE_SIZE + 1,
+            page * TRADE_PAGE_SIZE + trades.len(),
+            total
+        );
+    }
+    let (db_toggle, db_filter, db_search) = (db.clone(), db.clone(), db.clone());
+    let (db_export, db_x) = (db.clone(), db.clone());
+    let (db_marked, db_bulk) = (db.clone(), db.clone());
+    let (db_copy, db_yank, db_quick) = (db.clone(), db.clone(), db.clone());
+    // As many rows as the terminal has room for, scrolled to keep the
+    // selection in view.
+    let height = trade_list_height(siv.screen_size().y);
+    // Two columns in from the mark gutter, over the trade rows.
+    let header = fit(
+        &format!("  {}", format_header(&layout.columns)),
+        TRADE_ROW_WIDTH,
+    );
+    let mut panes = LinearLayout::horizontal().child(
+        LinearLayout::vertical()
+            .child(
+                TextView::new(header)
+                    .no_wrap()
+                    .fixed_width(TRADE_LIST_WIDTH),
+            )
+            .child(
+                select
+                    .with_name("trade_list")
+                    .scrollable()
+                    .with_name("trade_scroll")
+                    .fixed_size((TRADE_LIST_WIDTH, height)),
+            )
+            .child(
+                TextView::new(trade_position(row, rows, page, last_page, marks.len()))
+                    .h_align(HAlign::Right)
+                    .with_name("trade_position"),
+            ),
+    );
+    if let Some(width) = detail_width {
+        panes.add_child(DummyView.fixed_width(2));
+        panes.add_child(
+            TextView::new(preview)
+                .with_name("trade_preview")
+                .scrollable()
+                .fixed_size((width, height + 2)),
+        );
+    }
+    let mut dialog = Dialog::around(panes).title(title);
+    if page > 0 {
+        let db = db.clone();
+        dialog = dialog.button("Newer", move |s| turn_trade_page(s, db.clone(), page - 1));
+    }
+    if page < last_page {
+        let db = db.clone();
+        dialog = dialog.button("Older", move |s| turn_trade_page(s, db.clone(), page + 1));
+    }
+    let export_key = export_name.clone();
+    let (db_f, filter_key) = (db.clone(), filter_text.clone());
+    dialog = dialog
+        .button("Filter", move |s| {
+            show_trade_filter(s, db_filter.clone(), filter_text.clone())
+        })
+        .button("Export", move |s| {
+            export_trade_list(s, &db_export, &export_name)
+        })
+        .button("Selected", move |s| {
+            show_marked_trades(s, db_marked.clone())
+        })
+        .button("Bulk Edit", move |s| show_bulk_edit(s, db_bulk.clone()))
+        .button(
+            if collapse { "Show Legs" } else { "Group Legs" },
+            move |s| {
+                if let Some(state) = s.user_data::<UiState>() {
+                    state.collapse_strategies = !state.collapse_strategies;
+                }
                 s.pop_layer();
+                show_view_trades(s, db_toggle.clone());
+            },
+        )
+        .button("Back", |s| {
+            s.pop_layer();
+        });
+    siv.add_layer(
+        OnEventView::new(dialog)
+            .on_event('x', move |s| export_trade_list(s, &db_x, &export_key))
+            .on_event('c', copy_selected_trade)
+            .on_event('y', move |s| duplicate_selected_trade(s, &db_yank))
+            .on_event('a', move |s| show_quick_add(s, db_quick.clone()))
+            .on_event('e', jump_to_expiring_trade)
+            .on_event('C', move |s| copy_trade_list(s, &db_copy))
+            .on_event('f', move |s| {
+                show_trade_filter(s, db_f.clone(), filter_key.clone())
+            })
+            .on_event('/', move |s| {
+                show_trade_search(
+                    s,
+                    db_search.clone(),
+                    strategies.clone(),
+                    collapse,
+                    layout.clone(),
+                )
+            })
+            .on_event(' ', move |s| {
+                mark_trades(s, false);
+                position(s);
+            })
+            .on_event('V', move |s| {
+                mark_trades(s, true);
+                position(s);
+            })
+            .with(|view| {
+                for (key, by) in ('1'..).zip(TradeSort::variants()) {
+                    let db = db.clone();
+                    view.set_on_event(key, move |s| sort_trade_list(s, db.clone(), *by));
+                }
             }),
     );
 
-    maybe_show_expiration_alert(siv, &trades);
+    // A row kept from before the rebuild can be past the first screenful;
+    // scroll to it once the list has been laid out.
+    if row > 0 {
+        siv.cb_sink()
+            .send(Box::new(|s| {
+                s.call_on_name(
+                    "trade_scroll",
+                    |v: &mut ScrollView<NamedView<SelectView<TradeListItem>>>| {
+                        v.scroll_to_important_area()
+                    },
+                );
+            }))
+            .ok();
+    }
+
+    maybe_show_expiration_alert(siv, &db, &open_options);
 }
 
-// Builds the per-trade action dialog (lifecycle actions for open options,
-// edit/delete otherwise). Linked auto-generated stock rows are read-only.
-fn show_trade_actions(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Trade) {
-    if let Some(option_id) = trade.assigned_from {
-        siv.add_layer(
-            Dialog::text(format!(
-                "This stock row was auto-generated by the assignment/exercise of \
-                 option #{}. Manage or remove it via that option.",
-                option_id
-            ))
-            .button("Back", |s| {
-                s.pop_layer();
-            }),
-        );
-        return;
+// The trades matching the View/Edit Trades filter text and search, in
+// `order`: one page of them, or all for `None`, and how many there are in
+// all. The database applies what it can of the filter and the rest is tested
+// on what it reads.
+fn load_trade_page(
+    db: &Database,
+    filter_text: &str,
+    search: &str,
+    order: TradeOrder,
+    page: Option<usize>,
+) -> rusqlite::Result<(Vec<Trade>, usize)> {
+    // Checked when it was set.
+    let filter: Filter = filter_text.parse().unwrap_or_default();
+    let (mut query, rest) = filter.split();
+    query.search = Some(search.to_string()).filter(|search| !search.trim().is_empty());
+    query.order = order;
+    if rest.is_empty() {
+        let trades = match page {
+            Some(page) => db.query_trades(&query, Some(TRADE_PAGE_SIZE), page * TRADE_PAGE_SIZE)?,
+            None => db.query_trades(&query, None, 0)?,
+        };
+        return Ok((trades, db.count_trades(&query)?));
     }
+    let matching: Vec<Trade> = db
+        .query_trades(&query, None, 0)?
+        .into_iter()
+        .filter(|t| rest.matches(t))
+        .collect();
+    let total = matching.len();
+    let trades = match page {
+        Some(page) => matching
+            .into_iter()
+            .skip(page * TRADE_PAGE_SIZE)
+            .take(TRADE_PAGE_SIZE)
+            .collect(),
+        None => matching,
+    };
+    Ok((trades, total))
+}
 
-    let mut dialog = Dialog::text("What would you like to do?");
+// Rows of the View/Edit Trades list that fit a terminal `screen_rows` tall
+// next to the dialog's border, title, column header, position line, and
+// buttons, and the footer.
+fn trade_list_height(screen_rows: usize) -> usize {
+    screen_rows.saturating_sub(9).clamp(5, TRADE_PAGE_SIZE)
+}
 
-    let is_open_option =
-        trade.trade_type == TradeType::Option && trade.status == Some(OptionStatus::Open);
-    if is_open_option {
-        for (label, status) in [
-            ("Assign", OptionStatus::Assigned),
-            ("Exercise", OptionStatus::Exercised),
-        ] {
-            let db = db.clone();
-            let id = trade.id;
-            dialog = dialog.button(label, move |s| {
-                if let Some(id) = id {
-                    let res = db
-                        .lock()
-                        .expect("Failed to lock database")
-                        .assign_option(id, status.clone());
-                    match res {
-                        Ok(_) => {
-                            s.pop_layer();
-                            s.pop_layer();
-                            show_view_trades(s, db.clone());
-                        }
-                        Err(e) => {
-                            s.add_layer(Dialog::info(format!("Error: {}", e)));
-                        }
-                    }
-                }
-            });
-        }
-        let db_expire = db.clone();
-        let expire_id = trade.id;
-        dialog = dialog.button("Expire", move |s| {
-            if let Some(id) = expire_id {
-                // Bind the result so the database lock is released before we
-                // rebuild the trade list (which re-locks the same Mutex).
-                let res = db_expire
-                    .lock()
-                    .expect("Failed to lock database")
-                    .expire_option(id);
-                match res {
-                    Ok(_) => {
-                        s.pop_layer();
-                        s.pop_layer();
-                        show_view_trades(s, db_expire.clone());
-                    }
-                    Err(e) => {
-                        s.add_layer(Dialog::info(format!("Error: {}", e)));
-                    }
-                }
+// Columns for the detail pane beside the View/Edit Trades list on a terminal
+// `screen_cols` wide, past the list, the gap, and the dialog's border; `None`
+// when there isn't room for one and the list is shown alone.
+fn trade_detail_width(screen_cols: usize) -> Option<usize> {
+    let room = screen_cols.saturating_sub(90 + 2 + 8);
+    (room >= 50).then(|| room.min(80))
+}
+
+// The detail pane's text for a View/Edit Trades row: every field of a trade
+// and the realized P/L it accounts for, or a strategy's summary.
+fn trade_preview(db: &Database, item: &TradeListItem, method: CostBasisMethod) -> String {
+    match item {
+        TradeListItem::Trade(trade) => {
+            let detail = trade
+                .id
+                .map_or(Ok(None), |id| db.get_trade_detail(id, method));
+            match detail {
+                Ok(Some(detail)) => format_trade_detail(&detail),
+                Ok(None) => format_trade_row(trade, &today()),
+                Err(e) => format!("Database error: {}", e),
             }
-        });
+        }
+        TradeListItem::Strategy(summary) => format!(
+            "{}\n\nEnter lists its legs; Show Legs lists them here.",
+            format_strategy_row(summary)
+        ),
     }
+}
 
-    let db_edit = db.clone();
-    let trade_edit = trade.clone();
-    dialog = dialog.button("Edit", move |s| {
-        s.pop_layer();
-        show_add_trade(s, db_edit.clone(), Some(trade_edit.clone()));
-    });
+// "Row 3 of 100", and the page when there is more than one.
+fn trade_position(row: usize, rows: usize, page: usize, last_page: usize, marked: usize) -> String {
+    let mut position = format!("Row {} of {}", (row + 1).min(rows), rows);
+    if last_page > 0 {
+        position += &format!(" · page {} of {}", page + 1, last_page + 1);
+    }
+    if marked > 0 {
+        position += &format!(" · {} selected", marked);
+    }
+    position
+}
 
-    let db_delete = db.clone();
-    let delete_id = trade.id;
-    dialog = dialog.button("Delete", move |s| {
-        if let Some(id) = delete_id {
-            // Release the lock before rebuilding the list (see Expire above).
-            let res = db_delete
-                .lock()
-                .expect("Failed to lock database")
-                .delete_trade(id);
-            match res {
-                Ok(_) => {
-                    s.pop_layer();
-                    s.pop_layer();
-                    show_view_trades(s, db_delete.clone());
-                }
-                Err(e) => {
-                    s.add_layer(Dialog::info(format!("Error deleting trade: {}", e)));
-                }
+// A View/Edit Trades row with the gutter that shows it is marked, colored
+// by how soon it expires when it is an open option.
+fn mark_row(
+    label: &str,
+    item: &TradeListItem,
+    marked: bool,
+    colors: &Theme,
+    today: &str,
+) -> StyledString {
+    let row = fit(
+        &format!("{} {}", if marked { "●" } else { " " }, label),
+        TRADE_ROW_WIDTH,
+    );
+    let dte = match item {
+        TradeListItem::Trade(trade) => open_option_dte(trade, today),
+        TradeListItem::Strategy(_) => None,
+    };
+    match dte.and_then(|dte| colors.expiring(dte)) {
+        Some(color) => StyledString::styled(row, color),
+        None => StyledString::plain(row),
+    }
+}
+
+// Days until an open option expires; `None` for stock and resolved options.
+fn open_option_dte(trade: &Trade, today: &str) -> Option<i64> {
+    if trade.trade_type != TradeType::Option
+        || trade
+            .status
+            .as_ref()
+            .is_some_and(|s| *s != OptionStatus::Open)
+    {
+        return None;
+    }
+    days_to_expiration(today, trade.expiration.as_deref()?)
+}
+
+// The row `e` jumps to among rows expiring in `dtes` days (`None` for rows
+// that don't expire): the soonest expiring today or later, or, from one of
+// those, the next by expiration, wrapping around to the soonest.
+fn next_expiring(dtes: &[Option<i64>], current: usize) -> Option<usize> {
+    let upcoming = |row: usize| dtes.get(row).copied().flatten().filter(|dte| *dte >= 0);
+    let mut rows: Vec<(i64, usize)> = (0..dtes.len())
+        .filter_map(|row| Some((upcoming(row)?, row)))
+        .collect();
+    rows.sort();
+    let after = upcoming(current).and_then(|dte| rows.iter().find(|&&row| row > (dte, current)));
+    after.or(rows.first()).map(|&(_, row)| row)
+}
+
+// Selects the next open option in the View/Edit Trades page by expiration.
+fn jump_to_expiring_trade(s: &mut Cursive) {
+    let now = today();
+    let step = s
+        .call_on_name("trade_list", |v: &mut SelectView<TradeListItem>| {
+            let dtes: Vec<Option<i64>> = v
+                .iter()
+                .map(|(_, item)| match item {
+                    TradeListItem::Trade(trade) => open_option_dte(trade, &now),
+                    TradeListItem::Strategy(_) => None,
+                })
+                .collect();
+            next_expiring(&dtes, v.selected_id()?).map(|row| v.set_selection(row))
+        })
+        .flatten();
+    match step {
+        Some(step) => {
+            step(s);
+            s.call_on_name(
+                "trade_scroll",
+                |v: &mut ScrollView<NamedView<SelectView<TradeListItem>>>| {
+                    v.scroll_to_important_area()
+                },
+            );
+        }
+        None => show_toast(
+            s,
+            "No open option on this page expires from today on".to_string(),
+        ),
+    }
+}
+
+// Space marks or unmarks the selected trade and moves down. The first `V`
+// (`range`) anchors a range at the selected row; the second marks every
+// trade from there to the selected row.
+fn mark_trades(s: &mut Cursive, range: bool) {
+    let Some((row, anchor)) = s
+        .user_data::<UiState>()
+        .map(|state| (state.trade_row, state.mark_anchor))
+    else {
+        return;
+    };
+    let rows = match (range, anchor) {
+        (true, None) => {
+            if let Some(state) = s.user_data::<UiState>() {
+                state.mark_anchor = Some(row);
+            }
+            show_toast(s, "Move to the other end and press V".to_string());
+            return;
+        }
+        (true, Some(anchor)) => anchor.min(row)..=anchor.max(row),
+        (false, _) => row..=row,
+    };
+    let mut marks = s
+        .user_data::<UiState>()
+        .map(|state| std::mem::take(&mut state.trade_marks))
+        .unwrap_or_default();
+    let mut strategies = false;
+    let (colors, now) = (colors(s), today());
+    let step = s
+        .call_on_name("trade_list", |v: &mut SelectView<TradeListItem>| {
+            for i in rows {
+                let Some((label, item)) = v.get_item_mut(i) else {
+                    continue;
+                };
+                let id = match item {
+                    TradeListItem::Trade(trade) => trade.id,
+                    TradeListItem::Strategy(_) => {
+                        strategies = true;
+                        None
+                    }
+                };
+                let Some(id) = id else { continue };
+                // A range marks every row; Space flips one.
+                let marked = range || !marks.contains(&id);
+                if marked {
+                    marks.insert(id);
+                } else {
+                    marks.remove(&id);
+                }
+                let text: String = label.source().chars().skip(2).collect();
+                *label = mark_row(&text, item, marked, &colors, &now);
+            }
+            (!range).then(|| v.select_down(1))
+        })
+        .flatten();
+    if let Some(state) = s.user_data::<UiState>() {
+        state.trade_marks = marks;
+        state.mark_anchor = None;
+    }
+    if let Some(step) = step {
+        step(s);
+    }
+    if strategies {
+        show_toast(s, "Show Legs to select a strategy's trades".to_string());
+    }
+}
+
+// What can be done to the marked trades at once: delete, tag, or export
+// them, or clear the marks.
+fn show_marked_trades(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let ids: Vec<i64> = siv
+        .user_data::<UiState>()
+        .map(|state| state.trade_marks.iter().copied().collect())
+        .unwrap_or_default();
+    if ids.is_empty() {
+        siv.add_layer(Dialog::info(
+            "Mark trades with Space, or a range with V at each end, first",
+        ));
+        return;
+    }
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        ids.iter()
+            .filter_map(|&id| db.get_trade(id).transpose())
+            .collect::<rusqlite::Result<Vec<Trade>>>()
+    };
+    let trades = match res {
+        Ok(trades) => trades,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    let count = trades.len();
+    // Generated stock rows go and come back with their options.
+    let deletable: Vec<i64> = trades
+        .iter()
+        .filter(|t| t.assigned_from.is_none())
+        .filter_map(|t| t.id)
+        .collect();
+    let (db_delete, db_tag) = (db.clone(), db.clone());
+    let (tag_ids, export) = (ids.clone(), trades_table(&trades));
+    siv.add_layer(
+        Dialog::text(format!("{} trade(s) selected.", count))
+            .title("Selected Trades")
+            .button("Delete", move |s| {
+                confirm_delete_marked(s, db_delete.clone(), deletable.clone(), count)
+            })
+            .button("Tag", move |s| {
+                show_tag_marked(s, db_tag.clone(), tag_ids.clone())
+            })
+            .button("Export", move |s| {
+                export_table(s, &export, "Selected trades")
+            })
+            .button("Clear", move |s| {
+                if let Some(state) = s.user_data::<UiState>() {
+                    state.trade_marks.clear();
+                }
+                s.pop_layer();
+                reopen_trade_list(s, db.clone());
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn confirm_delete_marked(siv: &mut Cursive, db: Arc<Mutex<Database>>, ids: Vec<i64>, count: usize) {
+    let mut msg = format!("Move {} trade(s) to the Trash?", ids.len());
+    if ids.len() < count {
+        msg += &format!(
+            "\n{} generated stock row(s) are left to go with their options.",
+            count - ids.len()
+        );
+    }
+    siv.add_layer(
+        Dialog::text(msg)
+            .title("Delete Selected")
+            .button("Delete", move |s| {
+                let res = {
+                    let db = db.lock().expect("Failed to lock database");
+                    db.last_history_id().and_then(|after| {
+                        db.delete_trades(&ids)?;
+                        Ok((after, db.last_history_id()?))
+                    })
+                };
+                match res {
+                    Ok((after, upto)) => {
+                        push_undo(s, format!("delete of {} trades", ids.len()), after, upto);
+                        if let Some(state) = s.user_data::<UiState>() {
+                            state.trade_marks.clear();
+                        }
+                        s.pop_layer();
+                        s.pop_layer();
+                        reopen_trade_list(s, db.clone());
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error deleting trades: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+fn show_tag_marked(siv: &mut Cursive, db: Arc<Mutex<Database>>, ids: Vec<i64>) {
+    siv.add_layer(
+        Dialog::around(
+            ListView::new().child(
+                "Add tags:",
+                EditView::new()
+                    .with_name("marked_tags")
+                    .word_keys()
+                    .fixed_width(40),
+            ),
+        )
+        .title(format!("Tag {} Trade(s)", ids.len()))
+        .button("Tag", move |s| {
+            let tags = parse_tags(&read_field(s, "marked_tags").unwrap_or_default());
+            if tags.is_empty() {
+                s.add_layer(Dialog::info("Enter tags separated by commas"));
+                return;
+            }
+            let res = {
+                let db = db.lock().expect("Failed to lock database");
+                db.last_history_id().and_then(|after| {
+                    db.tag_trades(&ids, &tags)?;
+                    Ok((after, db.last_history_id()?))
+                })
+            };
+            match res {
+                Ok((after, upto)) => {
+                    push_undo(s, format!("tagging of {} trades", ids.len()), after, upto);
+                    s.pop_layer();
+                    s.pop_layer();
+                    reopen_trade_list(s, db.clone());
+                }
+                Err(e) => s.add_layer(Dialog::info(format!("Error tagging trades: {}", e))),
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// A change to make to every trade the View/Edit Trades filter and search
+// match: a symbol, fees, or account to set and tags to add. Fields left
+// blank are left alone.
+fn show_bulk_edit(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let accounts = match db.lock().expect("Failed to lock database").get_accounts() {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    let mut account = SelectView::<Option<Option<i64>>>::new().popup();
+    account.add_item("unchanged", None);
+    account.add_item("no account", Some(None));
+    for a in &accounts {
+        account.add_item(a.name.clone(), Some(a.id));
+    }
+    let field = |name: &str| EditView::new().with_name(name).word_keys().fixed_width(20);
+    siv.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child("Set symbol:", field("bulk_symbol"))
+                .child("Set fees:", field("bulk_fees"))
+                .child("Account:", account.with_name("bulk_account"))
+                .child("Add tags:", field("bulk_tags")),
+        )
+        .title("Bulk Edit Filtered Trades")
+        .button("Preview", move |s| {
+            if let Some(edit) = read_bulk_edit(s) {
+                preview_bulk_edit(s, db.clone(), edit);
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+fn read_bulk_edit(s: &mut Cursive) -> Option<BulkEdit> {
+    let symbol = read_field(s, "bulk_symbol")?.trim().to_uppercase();
+    let fees = read_field(s, "bulk_fees")?.trim().to_string();
+    let edit = BulkEdit {
+        symbol: Some(symbol).filter(|symbol| !symbol.is_empty()),
+        fees: if fees.is_empty() {
+            None
+        } else {
+            Some(parse_amount(s, &fees, "fees", true)?)
+        },
+        account: read_select(s, "bulk_account")?,
+        tags: parse_tags(&read_field(s, "bulk_tags")?),
+    };
+    if edit.is_empty() {
+        s.add_layer(Dialog::info("Fill in at least one change"));
+        return None;
+    }
+    Some(edit)
+}
+
+// Lists what `edit` does to each filtered trade before anything is saved;
+// Apply makes every change in one transaction, undone as one.
+fn preview_bulk_edit(siv: &mut Cursive, db: Arc<Mutex<Database>>, edit: BulkEdit) {
+    let (filter_text, search) = siv
+        .user_data::<UiState>()
+        .map(|state| (state.trade_filter.clone(), state.trade_search.clone()))
+        .unwrap_or_default();
+    let order = trade_order(siv);
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        load_trade_page(&db, &filter_text, &search, order, None)
+            .and_then(|(trades, _)| Ok((trades, db.get_accounts()?)))
+    };
+    let (trades, accounts) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    let account_name = |id: Option<i64>| {
+        accounts
+            .iter()
+            .find(|a| id.is_some() && a.id == id)
+            .map_or_else(|| "none".to_string(), |a| a.name.clone())
+    };
+    let mut lines = Vec::new();
+    let mut ids = Vec::new();
+    let mut generated = 0;
+    for trade in &trades {
+        let changes = edit.changes(trade, account_name);
+        if changes.is_empty() {
+            continue;
+        }
+        if trade.assigned_from.is_some() {
+            generated += 1;
+            continue;
+        }
+        ids.extend(trade.id);
+        lines.push(format!(
+            "{} {} {}: {}",
+            describe_trade(trade),
+            trade.date,
+            trade.action,
+            changes.join("; ")
+        ));
+    }
+    if ids.is_empty() {
+        siv.add_layer(Dialog::info("No filtered trade would change"));
+        return;
+    }
+    let scope = if filter_text.is_empty() && search.is_empty() {
+        "every trade — no filter or search is set".to_string()
+    } else {
+        format!("the {} trade(s) the filter and search match", trades.len())
+    };
+    let mut text = format!("Changes to {}:\n\n{}", scope, lines.join("\n"));
+    if generated > 0 {
+        text += &format!(
+            "\n\n{} generated stock row(s) are skipped; they follow their options.",
+            generated
+        );
+    }
+    siv.add_layer(
+        Dialog::around(TextView::new(text).scrollable().max_height(20))
+            .title(format!("Apply to {} Trade(s)?", ids.len()))
+            .button("Apply", move |s| {
+                let res = {
+                    let db = db.lock().expect("Failed to lock database");
+                    db.last_history_id().and_then(|after| {
+                        let changed = db.bulk_edit(&ids, &edit)?;
+                        Ok((changed, after, db.last_history_id()?))
+                    })
+                };
+                match res {
+                    Ok((changed, after, upto)) => {
+                        push_undo(s, format!("bulk edit of {} trades", changed), after, upto);
+                        s.pop_layer();
+                        s.pop_layer();
+                        reopen_trade_list(s, db.clone());
+                        show_toast(s, format!("Changed {} trade(s)", changed));
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Bulk edit failed: {}", e))),
+                }
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Rebuilds View/Edit Trades when it is on top.
+fn reopen_trade_list(s: &mut Cursive, db: Arc<Mutex<Database>>) {
+    if is_top_layer(s, "trade_list") {
+        s.pop_layer();
+        show_view_trades(s, db);
+    }
+}
+
+// The View/Edit Trades order, matching lots as the reports do.
+fn trade_order(s: &mut Cursive) -> TradeOrder {
+    s.user_data::<UiState>()
+        .map(|state| TradeOrder {
+            method: state.cost_basis_method,
+            ..state.trade_order
+        })
+        .unwrap_or_default()
+}
+
+// Sorts View/Edit Trades by `by`, or the other way if it already is. Dates and
+// numbers start largest first, symbols from A.
+fn sort_trade_list(s: &mut Cursive, db: Arc<Mutex<Database>>, by: TradeSort) {
+    if let Some(state) = s.user_data::<UiState>() {
+        let order = &mut state.trade_order;
+        order.ascending = if order.by == by {
+            !order.ascending
+        } else {
+            by == TradeSort::Symbol
+        };
+        order.by = by;
+        state.trade_page = 0;
+        state.trade_row = 0;
+    }
+    s.pop_layer();
+    show_view_trades(s, db);
+}
+
+fn turn_trade_page(s: &mut Cursive, db: Arc<Mutex<Database>>, page: usize) {
+    if let Some(state) = s.user_data::<UiState>() {
+        state.trade_page = page;
+        state.trade_row = 0;
+    }
+    s.pop_layer();
+    show_view_trades(s, db);
+}
+
+// Writes every trade the View/Edit Trades list matches, not just the page
+// shown, to the export directory.
+fn export_trade_list(s: &mut Cursive, db: &Arc<Mutex<Database>>, name: &str) {
+    let (filter_text, search) = s
+        .user_data::<UiState>()
+        .map(|state| (state.trade_filter.clone(), state.trade_search.clone()))
+        .unwrap_or_default();
+    let order = trade_order(s);
+    let res = load_trade_page(
+        &db.lock().expect("Failed to lock database"),
+        &filter_text,
+        &search,
+        order,
+        None,
+    );
+    match res {
+        Ok((trades, _)) => export_table(s, &trades_table(&trades), name),
+        Err(e) => s.add_layer(Dialog::info(format!("Database error: {}", e))),
+    }
+}
+
+// Copies the selected View/Edit Trades row to the clipboard as TSV.
+fn copy_selected_trade(s: &mut Cursive) {
+    let item = s
+        .call_on_name("trade_list", |v: &mut SelectView<TradeListItem>| {
+            v.selection()
+        })
+        .flatten();
+    match item.as_deref() {
+        Some(TradeListItem::Trade(trade)) => {
+            let trades = [(**trade).clone()];
+            copy_trades(s, &trades);
+        }
+        Some(TradeListItem::Strategy(_)) => {
+            show_toast(s, "Show Legs to copy a strategy's trades".to_string())
+        }
+        None => {}
+    }
+}
+
+// Copies every trade the View/Edit Trades filter and search match, on every
+// page, to the clipboard as TSV.
+fn copy_trade_list(s: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let (filter_text, search) = s
+        .user_data::<UiState>()
+        .map(|state| (state.trade_filter.clone(), state.trade_search.clone()))
+        .unwrap_or_default();
+    let order = trade_order(s);
+    let res = load_trade_page(
+        &db.lock().expect("Failed to lock database"),
+        &filter_text,
+        &search,
+        order,
+        None,
+    );
+    match res {
+        Ok((trades, _)) => copy_trades(s, &trades),
+        Err(e) => s.add_layer(Dialog::info(format!("Database error: {}", e))),
+    }
+}
+
+fn copy_trades(s: &mut Cursive, trades: &[Trade]) {
+    match clipboard::copy(&trades_table(trades).to_tsv()) {
+        Ok(()) => show_toast(
+            s,
+            format!(
+                "Copied {} trade{}",
+                trades.len(),
+                if trades.len() == 1 { "" } else { "s" }
+            ),
+        ),
+        Err(e) => s.add_layer(Dialog::info(format!("Couldn't copy: {}", e))),
+    }
+}
+
+// The View/Edit Trades rows for `trades`. When collapsed, a strategy's row
+// takes the place of its most recent leg.
+// The settings' trade list columns and, when P/L is one of them, the realized
+// P/L of every trade that closes lots.
+#[derive(Clone)]
+struct TradeLayout {
+    columns: Vec<Column>,
+    realized: HashMap<i64, Decimal>,
+}
+
+impl TradeLayout {
+    fn new(db: &Database, columns: Vec<Column>, method: CostBasisMethod) -> rusqlite::Result<Self> {
+        let realized = if columns.iter().any(|c| c.column == TradeColumn::Pl) {
+            db.get_realized_by_trade(method)?
+        } else {
+            HashMap::new()
+        };
+        Ok(TradeLayout { columns, realized })
+    }
+
+    fn row(&self, trade: &Trade, today: &str) -> String {
+        let pl = trade.id.and_then(|id| self.realized.get(&id).copied());
+        format_row(&self.columns, trade, today, pl)
+    }
+}
+
+fn trade_list_items(
+    trades: &[Trade],
+    strategies: &[StrategySummary],
+    collapse: bool,
+    layout: &TradeLayout,
+) -> Vec<(String, TradeListItem)> {
+    let now = today();
+    let mut items = Vec::new();
+    let mut shown_strategies = Vec::new();
+    for trade in trades {
+        let summary = trade
+            .strategy_id
+            .filter(|_| collapse)
+            .and_then(|id| strategies.iter().find(|s| s.strategy.id == Some(id)));
+        match summary {
+            Some(summary) => {
+                if !shown_strategies.contains(&summary.strategy.id) {
+                    shown_strategies.push(summary.strategy.id);
+                    items.push((
+                        format_strategy_row(summary),
+                        TradeListItem::Strategy(summary.clone()),
+                    ));
+                }
+            }
+            None => items.push((
+                layout.row(trade, &now),
+                TradeListItem::Trade(Box::new(trade.clone())),
+            )),
+        }
+    }
+    items
+}
+
+// Searches the symbols and comments of the trades the View/Edit Trades filter
+// matches, narrowing the list underneath to the first page of results with
+// each keystroke. Enter keeps the search; Clear drops it.
+fn show_trade_search(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    strategies: Vec<StrategySummary>,
+    collapse: bool,
+    layout: TradeLayout,
+) {
+    let (current, filter_text) = siv
+        .user_data::<UiState>()
+        .map(|state| (state.trade_search.clone(), state.trade_filter.clone()))
+        .unwrap_or_default();
+    let order = trade_order(siv);
+    let db_edit = db.clone();
+    let search = EditView::new()
+        .content(current)
+        .on_edit(move |s, text, _| {
+            let res = load_trade_page(
+                &db_edit.lock().expect("Failed to lock database"),
+                &filter_text,
+                text,
+                order,
+                Some(0),
+            );
+            let Ok((shown, _)) = res else {
+                return;
+            };
+            let marks = s
+                .user_data::<UiState>()
+                .map(|state| state.trade_marks.clone())
+                .unwrap_or_default();
+            let (colors, now) = (colors(s), today());
+            let items = trade_list_items(&shown, &strategies, collapse, &layout);
+            s.call_on_name("trade_list", |v: &mut SelectView<TradeListItem>| {
+                v.clear();
+                for (label, item) in items {
+                    let marked = matches!(&item, TradeListItem::Trade(t)
+                        if t.id.is_some_and(|id| marks.contains(&id)));
+                    v.add_item(mark_row(&label, &item, marked, &colors, &now), item);
+                }
+            });
+        })
+        .on_submit({
+            let db = db.clone();
+            move |s, text| set_trade_search(s, db.clone(), text.trim().to_string())
+        })
+        .word_keys()
+        .fixed_width(40);
+    siv.add_layer(
+        Dialog::around(ListView::new().child("Search:", search))
+            .title("Search Trades")
+            .button("Clear", move |s| {
+                set_trade_search(s, db.clone(), String::new())
+            }),
+    );
+}
+
+// Keeps `search` for View/Edit Trades and rebuilds the list under the search
+// dialog.
+fn set_trade_search(s: &mut Cursive, db: Arc<Mutex<Database>>, search: String) {
+    if let Some(state) = s.user_data::<UiState>() {
+        state.trade_search = search;
+        state.trade_page = 0;
+        state.trade_row = 0;
+    }
+    s.pop_layer();
+    s.pop_layer();
+    show_view_trades(s, db);
+}
+
+// Sets the filter View/Edit Trades lists, edits, and exports by: fields for
+// the common conditions, and anything else in the custom report syntax.
+fn show_trade_filter(siv: &mut Cursive, db: Arc<Mutex<Database>>, current: String) {
+    // Checked when it was set.
+    let (quick, rest) = current.parse::<Filter>().unwrap_or_default().quick();
+
+    let mut type_select = SelectView::<Option<TradeType>>::new().popup();
+    type_select.add_item("any", None);
+    for t in TradeType::variants() {
+        type_select.add_item(t.to_string(), Some(*t));
+    }
+    let type_select = type_select.selected(
+        quick
+            .trade_type
+            .map_or(0, |t| selected_index(TradeType::variants(), t) + 1),
+    );
+    let mut action_select = SelectView::<Option<Action>>::new().popup();
+    action_select.add_item("any", None);
+    for a in Action::variants() {
+        action_select.add_item(a.to_string(), Some(*a));
+    }
+    let action_select = action_select.selected(
+        quick
+            .action
+            .map_or(0, |a| selected_index(Action::variants(), a) + 1),
+    );
+    let text_field = |name: &str, content: Option<String>, width: usize| {
+        EditView::new()
+            .content(content.unwrap_or_default())
+            .with_name(name)
+            .word_keys()
+            .fixed_width(width)
+    };
+
+    let form = LinearLayout::vertical()
+        .child(
+            ListView::new()
+                .child(
+                    "Symbol contains:",
+                    text_field("filter_symbol", quick.symbol, 20),
+                )
+                .child("Type:", stepped(type_select, "filter_type").fixed_width(20))
+                .child(
+                    "Action:",
+                    stepped(action_select, "filter_action").fixed_width(20),
+                )
+                .child(
+                    "From (YYYY-MM-DD):",
+                    date_field("filter_from", quick.range.start.unwrap_or_default(), 20),
+                )
+                .child(
+                    "To (YYYY-MM-DD):",
+                    date_field("filter_to", quick.range.end.unwrap_or_default(), 20),
+                )
+                .child(
+                    "Other conditions:",
+                    text_field("trade_filter", Some(rest.to_string()), 50),
+                ),
+        )
+        .child(TextView::new(
+            "\nOther conditions are \"field op value\" joined by \"and\", as in\n\
+             custom reports, e.g. strike > 50 and tag = hedge.\n\
+             Leave everything empty for all trades.",
+        ));
+    let db_clear = db.clone();
+    siv.add_layer(
+        Dialog::around(form)
+            .title("Filter Trades")
+            .button("Apply", move |s| {
+                let Some(text) = read_trade_filter(s) else {
+                    return;
+                };
+                set_trade_filter(s, db.clone(), text);
+            })
+            .button("Clear", move |s| {
+                set_trade_filter(s, db_clear.clone(), String::new())
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// The filter text of the Filter Trades form, or `None` after showing what is
+// wrong with it.
+fn read_trade_filter(s: &mut Cursive) -> Option<String> {
+    let rest = match read_field(s, "trade_filter")
+        .unwrap_or_default()
+        .parse::<Filter>()
+    {
+        Ok(rest) => rest,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Invalid filter: {}", e)));
+            return None;
+        }
+    };
+    let mut date = |name: &str, label: &str| -> Option<Option<String>> {
+        let text = read_field(s, name).unwrap_or_default().trim().to_string();
+        if text.is_empty() {
+            return Some(None);
+        }
+        let Some(text) = parse_date_field(&text) else {
+            s.add_layer(Dialog::info(format!("Invalid {} date: {}", label, text)));
+            return None;
+        };
+        Some(Some(text))
+    };
+    let range = DateRange {
+        start: date("filter_from", "from")?,
+        end: date("filter_to", "to")?,
+    };
+    let quick = QuickFilter {
+        symbol: read_field(s, "filter_symbol")
+            .map(|symbol| symbol.trim().to_string())
+            .filter(|symbol| !symbol.is_empty()),
+        trade_type: read_select(s, "filter_type").flatten(),
+        action: read_select(s, "filter_action").flatten(),
+        range,
+    };
+    Some(quick.and(rest).to_string())
+}
+
+// Keeps `text` as the View/Edit Trades filter and rebuilds the list under the
+// filter form.
+fn set_trade_filter(s: &mut Cursive, db: Arc<Mutex<Database>>, text: String) {
+    if let Some(state) = s.user_data::<UiState>() {
+        state.trade_filter = text;
+        state.trade_page = 0;
+        state.trade_row = 0;
+    }
+    // Close the form and the old list.
+    s.pop_layer();
+    s.pop_layer();
+    show_view_trades(s, db);
+}
+
+// Shows a trade in detail with its actions (lifecycle actions for open
+// options, edit/delete otherwise). Linked auto-generated stock rows are
+// read-only.
+fn show_trade_actions(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Trade) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let res = trade.id.map_or(Ok(None), |id| {
+        db.lock()
+            .expect("Failed to lock database")
+            .get_trade_detail(id, method)
+    });
+    let detail = match res {
+        Ok(Some(detail)) => format_trade_detail(&detail),
+        Ok(None) => format_trade_row(&trade, &today()),
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    if let Some(option_id) = trade.assigned_from {
+        siv.add_layer(
+            Dialog::around(
+                TextView::new(format!(
+                    "This stock row was auto-generated by the assignment/exercise of \
+                     option #{}. Manage or remove it via that option.\n\n{}",
+                    option_id, detail
+                ))
+                .scrollable()
+                .max_size((90, 20)),
+            )
+            .title(format!("Trade #{}", trade.id.unwrap_or(0)))
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        );
+        return;
+    }
+
+    let mut dialog = Dialog::around(TextView::new(detail).scrollable().max_size((90, 20)))
+        .title(format!("Trade #{}", trade.id.unwrap_or(0)));
+
+    let is_open_option =
+        trade.trade_type == TradeType::Option && trade.status == Some(OptionStatus::Open);
+    if is_open_option {
+        for (label, status) in [
+            ("Assign", OptionStatus::Assigned),
+            ("Exercise", OptionStatus::Exercised),
+        ] {
+            let db = db.clone();
+            let id = trade.id;
+            dialog = dialog.button(label, move |s| {
+                if let Some(id) = id {
+                    let res = db
+                        .lock()
+                        .expect("Failed to lock database")
+                        .assign_option(id, status.clone());
+                    match res {
+                        Ok(_) => {
+                            s.pop_layer();
+                            s.pop_layer();
+                            show_view_trades(s, db.clone());
+                        }
+                        Err(e) => {
+                            s.add_layer(Dialog::info(format!("Error: {}", e)));
+                        }
+                    }
+                }
+            });
+        }
+        let db_roll = db.clone();
+        let trade_roll = trade.clone();
+        dialog = dialog.button("Roll", move |s| {
+            s.pop_layer();
+            show_roll_option(s, db_roll.clone(), trade_roll.clone());
+        });
+
+        let payoff_leg = trade.clone();
+        dialog = dialog.button("Payoff", move |s| {
+            let title = format!("Payoff of trade #{}", payoff_leg.id.unwrap_or(0));
+            show_payoff(s, title, vec![payoff_leg.clone()]);
+        });
+
+        let db_expire = db.clone();
+        let expire_id = trade.id;
+        dialog = dialog.button("Expire", move |s| {
+            if let Some(id) = expire_id {
+                // Bind the result so the database lock is released before we
+                // rebuild the trade list (which re-locks the same Mutex).
+                let res = db_expire
+                    .lock()
+                    .expect("Failed to lock database")
+                    .expire_option(id);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_view_trades(s, db_expire.clone());
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            }
+        });
+    }
+
+    if trade.option_type == Some(OptionType::Call) && !trade.action.is_buy() {
+        let db_cover = db.clone();
+        let call = trade.clone();
+        dialog = dialog.button("Cover Lot", move |s| {
+            s.pop_layer();
+            show_link_covered_call(s, db_cover.clone(), call.clone());
+        });
+    }
+
+    if !trade.action.is_open() {
+        let db_match = db.clone();
+        let close = trade.clone();
+        dialog = dialog.button("Match Lots", move |s| {
+            s.pop_layer();
+            show_match_lots(s, db_match.clone(), close.clone());
+        });
+    }
+
+    let db_group = db.clone();
+    let group_id = trade.id;
+    dialog = dialog.button("Strategy", move |s| {
+        if let Some(id) = group_id {
+            s.pop_layer();
+            show_assign_strategy(s, db_group.clone(), id);
+        }
+    });
+
+    let db_edit = db.clone();
+    let trade_edit = trade.clone();
+    dialog = dialog.button("Edit", move |s| {
+        s.pop_layer();
+        show_add_trade(s, db_edit.clone(), Some(trade_edit.clone()));
+    });
+
+    let db_history = db.clone();
+    let history_id = trade.id;
+    dialog = dialog.button("History", move |s| {
+        if let Some(id) = history_id {
+            show_trade_history(s, db_history.clone(), id);
+        }
+    });
+
+    let db_files = db.clone();
+    let files_id = trade.id;
+    dialog = dialog.button("Files", move |s| {
+        if let Some(id) = files_id {
+            show_attachments(s, db_files.clone(), id);
+        }
+    });
+
+    let db_delete = db.clone();
+    let delete_id = trade.id;
+    let delete_label = describe_trade(&trade);
+    dialog = dialog.button("Delete", move |s| {
+        if let Some(id) = delete_id {
+            // Release the lock before rebuilding the list (see Expire above).
+            let res = {
+                let db = db_delete.lock().expect("Failed to lock database");
+                db.last_history_id().and_then(|after| {
+                    db.delete_trade(id)?;
+                    Ok((after, db.last_history_id()?))
+                })
+            };
+            match res {
+                Ok((after, upto)) => {
+                    push_undo(s, format!("delete of {}", delete_label), after, upto);
+                    s.pop_layer();
+                    s.pop_layer();
+                    show_view_trades(s, db_delete.clone());
+                }
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Error deleting trade: {}", e)));
+                }
+            }
+        }
+    });
+
+    dialog = dialog.button("Cancel", |s| {
+        s.pop_layer();
+    });
+
+    siv.add_layer(dialog);
+}
+
+// Picks the stock lot covering a sold call (or clears the link).
+fn show_link_covered_call(siv: &mut Cursive, db: Arc<Mutex<Database>>, call: Trade) {
+    let call_id = match call.id {
+        Some(id) => id,
+        None => return,
+    };
+    let result = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_all_trades()
+            .and_then(|trades| Ok((trades, db.get_covering_lot(call_id)?)))
+    };
+    let (trades, current) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+
+    let now = today();
+    let mut select = SelectView::<Option<i64>>::new().h_align(HAlign::Left);
+    select.add_item("(not covered)", None);
+    for lot in trades.iter().filter(|t| Database::lot_covers(t, &call)) {
+        let marker = if lot.id == current { "* " } else { "  " };
+        select.add_item(format!("{}{}", marker, format_trade_row(lot, &now)), lot.id);
+    }
+    let selected = trades
+        .iter()
+        .filter(|t| Database::lot_covers(t, &call))
+        .position(|t| t.id == current)
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let mut select = select.selected(selected);
+
+    let db_pick = db.clone();
+    select.set_on_submit(move |s, lot_id: &Option<i64>| {
+        let res = {
+            let db = db_pick.lock().expect("Failed to lock database");
+            match lot_id {
+                Some(lot_id) => db.link_covered_call(call_id, *lot_id),
+                None => db.unlink_covered_call(call_id),
+            }
+        };
+        match res {
+            Ok(_) => {
+                s.pop_layer();
+            }
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Error: {}", e)));
+            }
+        }
+    });
+
+    siv.add_layer(
+        Dialog::around(select.scrollable().scroll_x(true).fixed_size((90, 12)))
+            .title(format!(
+                "Stock lot covering call #{} ({} shares)",
+                call_id,
+                (call.quantity * call.multiplier()).normalize()
+            ))
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Specific identification: how much of a closing trade closes each open lot.
+// Quantity left unassigned is matched automatically by the cost-basis method.
+fn show_match_lots(siv: &mut Cursive, db: Arc<Mutex<Database>>, close: Trade) {
+    let close_id = match close.id {
+        Some(id) => id,
+        None => return,
+    };
+    let result = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_lot_candidates(&close)
+            .and_then(|lots| Ok((lots, db.get_lot_overrides()?)))
+    };
+    let (lots, overrides) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    if lots.is_empty() {
+        siv.add_layer(Dialog::info("No opening lots for this trade to close"));
+        return;
+    }
+
+    let mut form = ListView::new();
+    let mut lot_ids = Vec::new();
+    for lot in &lots {
+        let lot_id = match lot.id {
+            Some(id) => id,
+            None => continue,
+        };
+        let assigned = overrides
+            .iter()
+            .filter(|o| o.close_id == close_id && o.open_id == lot_id)
+            .map(|o| o.quantity.normalize().to_string())
+            .next()
+            .unwrap_or_default();
+        form.add_child(
+            format!(
+                "#{} {} {} @ ${:.2}:",
+                lot_id,
+                lot.date,
+                lot.quantity.normalize(),
+                lot.price
+            ),
+            EditView::new()
+                .content(assigned)
+                .with_name(format!("lot_{}", lot_id))
+                .word_keys()
+                .fixed_width(10),
+        );
+        lot_ids.push((lot_id, lot.quantity));
+    }
+
+    let db_save = db.clone();
+    let close_quantity = close.quantity;
+    siv.add_layer(
+        Dialog::around(form.scrollable())
+            .title(format!(
+                "Lots closed by trade #{} ({})",
+                close_id,
+                close_quantity.normalize()
+            ))
+            .button("Save", move |s| {
+                let mut matches = Vec::new();
+                for (lot_id, available) in &lot_ids {
+                    let raw = read_field(s, &format!("lot_{}", lot_id)).unwrap_or_default();
+                    let raw = raw.trim();
+                    if raw.is_empty() {
+                        continue;
+                    }
+                    let quantity = match parse_amount(s, raw, "quantity", false) {
+                        Some(quantity) => quantity,
+                        None => return,
+                    };
+                    if quantity > *available {
+                        s.add_layer(Dialog::info(format!(
+                            "Lot #{} only has {}",
+                            lot_id,
+                            available.normalize()
+                        )));
+                        return;
+                    }
+                    matches.push((*lot_id, quantity));
+                }
+                let total: Decimal = matches.iter().map(|(_, q)| *q).sum();
+                if total > close_quantity {
+                    s.add_layer(Dialog::info(format!(
+                        "Matched {} but the trade only closes {}",
+                        total.normalize(),
+                        close_quantity.normalize()
+                    )));
+                    return;
+                }
+                let res = db_save
+                    .lock()
+                    .expect("Failed to lock database")
+                    .set_lot_overrides(close_id, &matches);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            })
+            .button("Clear", move |s| {
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .set_lot_overrides(close_id, &[]);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Lists the legs of a strategy; picking one opens its usual trade actions.
+fn show_strategy_legs(siv: &mut Cursive, db: Arc<Mutex<Database>>, summary: StrategySummary) {
+    let legs = match db.lock().expect("Failed to lock database").get_all_trades() {
+        Ok(trades) => trades
+            .into_iter()
+            .filter(|t| t.strategy_id.is_some() && t.strategy_id == summary.strategy.id)
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+
+    let now = today();
+    let has_options = legs.iter().any(|leg| leg.trade_type == TradeType::Option);
+    let payoff_legs = legs.clone();
+    let mut select = SelectView::new().h_align(HAlign::Left);
+    for leg in legs {
+        select.add_item(format_trade_row(&leg, &now), leg);
+    }
+    let db_leg = db.clone();
+    select.set_on_submit(move |s, leg: &Trade| {
+        s.pop_layer();
+        show_trade_actions(s, db_leg.clone(), leg.clone());
+    });
+
+    let strategy_id = summary.strategy.id;
+    let mut dialog = Dialog::around(select.scrollable().scroll_x(true).fixed_size((90, 10)))
+        .title(format_strategy_row(&summary));
+    if has_options {
+        let title = format!("Payoff of {}", summary.strategy.name);
+        dialog = dialog.button("Payoff", move |s| {
+            show_payoff(s, title.clone(), payoff_legs.clone());
+        });
+    }
+    siv.add_layer(
+        dialog
+            .button("Ungroup", move |s| {
+                if let Some(id) = strategy_id {
+                    let res = db
+                        .lock()
+                        .expect("Failed to lock database")
+                        .delete_strategy(id);
+                    match res {
+                        Ok(_) => {
+                            s.pop_layer();
+                            s.pop_layer();
+                            show_view_trades(s, db.clone());
+                        }
+                        Err(e) => {
+                            s.add_layer(Dialog::info(format!("Error: {}", e)));
+                        }
+                    }
+                }
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Break-even prices and max profit/loss of `legs` held to expiration.
+fn show_payoff(siv: &mut Cursive, title: String, legs: Vec<Trade>) {
+    let payoff = analyze(&legs);
+    let break_evens = if payoff.break_evens.is_empty() {
+        "none".to_string()
+    } else {
+        payoff
+            .break_evens
+            .iter()
+            .map(|price| format!("${:.2}", price))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut content = format!(
+        "Break-even:  {}\nMax profit:  {}\nMax loss:    {}\n",
+        break_evens,
+        payoff
+            .max_profit
+            .map(|p| format!("${:.2}", p))
+            .unwrap_or_else(|| "unlimited".to_string()),
+        payoff
+            .max_loss
+            .map(|l| format!("${:.2}", l))
+            .unwrap_or_else(|| "unlimited".to_string()),
+    );
+    let mut expirations: Vec<&String> = legs.iter().filter_map(|l| l.expiration.as_ref()).collect();
+    expirations.sort();
+    expirations.dedup();
+    if expirations.len() > 1 {
+        content.push_str(
+            "\nLegs expire on different dates; all are valued as if they expire together.\n",
+        );
+    }
+    siv.add_layer(
+        Dialog::around(TextView::new(content))
+            .title(title)
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Picks the strategy a trade belongs to: an existing one, none, or a new one.
+fn show_assign_strategy(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade_id: i64) {
+    let strategies = match db.lock().expect("Failed to lock database").get_strategies() {
+        Ok(strategies) => strategies,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<Option<i64>>::new().h_align(HAlign::Left);
+    select.add_item("(no strategy)", None);
+    for strategy in strategies {
+        select.add_item(
+            format!(
+                "#{} {} ({})",
+                strategy.id.unwrap_or(0),
+                strategy.name,
+                strategy.kind
+            ),
+            strategy.id,
+        );
+    }
+    let db_pick = db.clone();
+    select.set_on_submit(move |s, strategy_id: &Option<i64>| {
+        let res = db_pick
+            .lock()
+            .expect("Failed to lock database")
+            .set_trade_strategy(trade_id, *strategy_id);
+        match res {
+            Ok(_) => {
+                s.pop_layer();
+                s.pop_layer();
+                show_view_trades(s, db_pick.clone());
+            }
+            Err(e) => {
+                s.add_layer(Dialog::info(format!("Error: {}", e)));
+            }
+        }
+    });
+
+    siv.add_layer(
+        Dialog::around(select.scrollable().fixed_size((50, 10)))
+            .title(format!("Strategy for trade #{}", trade_id))
+            .button("New Strategy", move |s| {
+                show_new_strategy(s, db.clone(), trade_id);
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Creates a strategy and makes `trade_id` its first leg.
+fn show_new_strategy(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade_id: i64) {
+    let mut kind_select = SelectView::<StrategyKind>::new().popup();
+    for kind in StrategyKind::variants() {
+        kind_select.add_item(kind.to_string(), *kind);
+    }
+    let form = ListView::new()
+        .child(
+            "Name:",
+            EditView::new()
+                .with_name("strategy_name")
+                .word_keys()
+                .fixed_width(30),
+        )
+        .child(
+            "Kind:",
+            kind_select.with_name("strategy_kind").fixed_width(30),
+        );
+
+    siv.add_layer(
+        Dialog::around(form)
+            .title("New Strategy")
+            .button("Save", move |s| {
+                let name = read_field(s, "strategy_name").unwrap_or_default();
+                let name = name.trim().to_string();
+                if name.is_empty() {
+                    s.add_layer(Dialog::info("Name is required"));
+                    return;
+                }
+                let kind = match read_select::<StrategyKind>(s, "strategy_kind") {
+                    Some(kind) => kind,
+                    None => {
+                        s.add_layer(Dialog::info(
+                            "Internal error: could not read the Kind selector",
+                        ));
+                        return;
+                    }
+                };
+                let res = {
+                    let db = db.lock().expect("Failed to lock database");
+                    db.add_strategy(&Strategy {
+                        id: None,
+                        name,
+                        kind,
+                    })
+                    .and_then(|id| db.set_trade_strategy(trade_id, Some(id)))
+                };
+                match res {
+                    Ok(_) => {
+                        // This form, the strategy picker, and the stale list.
+                        s.pop_layer();
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_view_trades(s, db.clone());
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Form for rolling an open option: buys/sells it back and opens a replacement
+// leg (new strike/expiration, same type and side) in one step.
+fn show_roll_option(siv: &mut Cursive, db: Arc<Mutex<Database>>, option: Trade) {
+    let option_id = match option.id {
+        Some(id) => id,
+        None => return,
+    };
+
+    let field = |name: &str, content: String| {
+        EditView::new()
+            .content(content)
+            .with_name(name)
+            .word_keys()
+            .fixed_width(20)
+    };
+    let form = ListView::new()
+        .child("Close price:", field("roll_close_price", String::new()))
+        .child("Close fees:", field("roll_close_fees", String::new()))
+        .delimiter()
+        .child(
+            "New strike:",
+            field(
+                "roll_strike",
+                option.strike.map(format_amount).unwrap_or_default(),
+            ),
+        )
+        .child(
+            "New expiration:",
+            date_field(
+                "roll_expiration",
+                option.expiration.clone().unwrap_or_default(),
+                20,
+            ),
+        )
+        .child("New price:", field("roll_price", String::new()))
+        .child(
+            "Quantity:",
+            field("roll_quantity", format_amount(option.quantity)),
+        )
+        .child("Fees:", field("roll_fees", String::new()))
+        .child("Date (YYYY-MM-DD):", date_field("roll_date", today(), 20));
+
+    let title = format!(
+        "Roll #{} {} {} {}",
+        option_id,
+        option.symbol,
+        option
+            .option_type
+            .map(|t| t.as_str().to_uppercase())
+            .unwrap_or_default(),
+        option
+            .strike
+            .map(|s| format!("${:.2}", s))
+            .unwrap_or_default(),
+    );
+
+    siv.add_layer(
+        Dialog::around(form)
+            .title(title)
+            .button("Roll", move |s| {
+                let (close_price, close_fees, new_leg) = match read_roll_form(s, &option) {
+                    Some(values) => values,
+                    None => return,
+                };
+                // Release the lock before rebuilding the list.
+                let res = db.lock().expect("Failed to lock database").roll_option(
+                    option_id,
+                    close_price,
+                    close_fees,
+                    &new_leg,
+                );
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_view_trades(s, db.clone());
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error rolling option: {}", e)));
+                    }
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Reads and validates the roll form, returning the closing price and fees plus
+// the replacement leg (a copy of `option` with the new terms), or None after
+// showing an error dialog.
+fn read_roll_form(s: &mut Cursive, option: &Trade) -> Option<(Decimal, Decimal, Trade)> {
+    let fields = (|| {
+        Some((
+            read_field(s, "roll_close_price")?,
+            read_field(s, "roll_close_fees")?,
+            read_field(s, "roll_strike")?,
+            read_field(s, "roll_expiration")?,
+            read_field(s, "roll_price")?,
+            read_field(s, "roll_quantity")?,
+            read_field(s, "roll_fees")?,
+            read_field(s, "roll_date")?,
+        ))
+    })();
+    let (close_price, close_fees, strike, expiration, price, quantity, fees, date) = match fields {
+        Some(values) => values,
+        None => {
+            s.add_layer(Dialog::info(
+                "Internal error: could not read one or more form fields",
+            ));
+            return None;
+        }
+    };
+
+    let close_price = parse_amount(s, &close_price, "close price", true)?;
+    let close_fees = parse_amount(s, &close_fees, "close fees", true)?;
+    let strike = parse_amount(s, &strike, "strike", false)?;
+    let price = parse_amount(s, &price, "price", true)?;
+    let quantity = parse_amount(s, &quantity, "quantity", false)?;
+    let fees = parse_amount(s, &fees, "fees", true)?;
+    let Some(expiration) = parse_date_field(&expiration) else {
+        s.add_layer(Dialog::info(
+            "Invalid expiration format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+        ));
+        return None;
+    };
+    let Some(date) = parse_date_field(&date) else {
+        s.add_layer(Dialog::info(
+            "Invalid date format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+        ));
+        return None;
+    };
+
+    let new_leg = Trade {
+        id: None,
+        price,
+        quantity,
+        date,
+        fees,
+        comment: format!("Roll of option #{}", option.id.unwrap_or(0)),
+        strike: Some(strike),
+        expiration: Some(expiration),
+        ..option.clone()
+    };
+    Some((close_price, close_fees, new_leg))
+}
+
+// Currently open positions per stock or option contract, netting buys against
+// sells. Selecting a row sets its mark price.
+fn show_positions(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let colors = colors(siv);
+    let result = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_all_trades()
+            .and_then(|trades| Ok((trades, db.get_quotes()?)))
+    };
+    let (trades, quotes) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    let ttl_secs = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.quote_ttl_secs())
+        .unwrap_or_default();
+    let now = unix_now();
+
+    let positions = open_positions(&trades, &today());
+    if positions.is_empty() {
+        show_dialog_with_back(siv, "No open positions".to_string());
+        return;
+    }
+
+    let header = format!(
+        "{:<8} {:<28} {:>10} {:>10} {:>12} {:<10} {:>5} {:>10} {:>12} {:>9} {:>9} {:>7}\n{}",
+        "Symbol",
+        "Instrument",
+        "Position",
+        "Avg Cost",
+        "Cost Basis",
+        "Opened",
+        "Days",
+        "Mark",
+        "Unrealized",
+        "Delta",
+        "Theta",
+        "IV",
+        "=".repeat(145)
+    );
+    let total = portfolio_greeks(&positions);
+    let mut instruments: Vec<String> = positions.iter().map(|p| p.key.instrument()).collect();
+    instruments.dedup();
+
+    let now_date = today();
+    let dtes: Vec<Option<i64>> = positions
+        .iter()
+        .map(|position| days_to_expiration(&now_date, position.key.expiration.as_deref()?))
+        .collect();
+    let mut select = SelectView::<String>::new().h_align(HAlign::Left);
+    for (position, dte) in positions.into_iter().zip(&dtes) {
+        let instrument = position.key.instrument();
+        let quote = quotes.get(&instrument);
+        let mark = quote.map(|quote| quote.price);
+        let stale = quote
+            .map(|quote| !quote.is_fresh(now, ttl_secs))
+            .unwrap_or(false);
+        let exposure = position.exposure();
+        let row = format!(
+            "{:<8} {:<28} {:>10} {:>10} {:>12} {:<10} {:>5} {:>10} {:>12} {:>9} {:>9} {:>7}",
+            position.key.symbol,
+            format_instrument(&position.key),
+            format_position(position.quantity),
+            format!("${:.2}", position.average_cost),
+            format!("${:.2}", position.cost_basis()),
+            position.opened,
+            position.days_held,
+            mark.map(|m| format!("${:.2}{}", m, if stale { "*" } else { "" }))
+                .unwrap_or_else(|| "-".to_string()),
+            mark.map(|m| format!("${:.2}", position.unrealized(m)))
+                .unwrap_or_else(|| "-".to_string()),
+            exposure
+                .map(|e| format!("{:.1}", e.delta))
+                .unwrap_or_else(|| "-".to_string()),
+            exposure
+                .filter(|_| position.greeks.theta.is_some())
+                .map(|e| format!("${:.2}", e.theta))
+                .unwrap_or_else(|| "-".to_string()),
+            position
+                .greeks
+                .iv
+                .map(|iv| format!("{:.1}%", iv))
+                .unwrap_or_else(|| "-".to_string()),
+        );
+        // Options coming due stand out by how soon, and short positions in
+        // red.
+        let color = dte
+            .and_then(|dte| colors.expiring(dte))
+            .or(position.is_short().then_some(colors.loss));
+        let row = match color {
+            Some(color) => StyledString::styled(row, color),
+            None => StyledString::plain(row),
+        };
+        select.add_item(row, instrument);
+    }
+    let db_mark = db.clone();
+    select.set_on_submit(move |s, instrument: &String| {
+        show_set_mark(s, db_mark.clone(), instrument.clone());
+    });
+
+    let mut summary = format!(
+        "Portfolio: net delta {:.1} shares, gamma {:.2}, theta ${:.2}/day, vega ${:.2}",
+        total.exposure.delta, total.exposure.gamma, total.exposure.theta, total.exposure.vega,
+    );
+    if total.missing > 0 {
+        summary.push_str(&format!(
+            " ({} option position(s) without greeks left out)",
+            total.missing
+        ));
+    }
+
+    let body = LinearLayout::vertical()
+        .child(TextView::new(header))
+        .child(
+            select
+                .with_name("position_list")
+                .scrollable()
+                .with_name("position_scroll"),
+        )
+        .child(TextView::new(format!("{}\n{}", "-".repeat(145), summary)))
+        .child(TextView::new(
+            "\nPosition is shares or contracts; short positions are shown in red,\n\
+             options within 3 days of expiring in red and within 10 in yellow\n\
+             (e jumps to the next to expire).\n\
+             Avg Cost and Mark are per share; Avg Cost is what a long paid or a\n\
+             short received, with fees folded in. Select a row to set its mark, or\n\
+             Refresh Quotes to fetch every mark from Yahoo Finance. Marks older\n\
+             than the quote TTL are marked * (last known price).\n\
+             Delta is in shares and Theta in dollars per day for the whole position,\n\
+             from the latest greeks entered on the contract's trades.",
+        ));
+
+    let layer = Dialog::around(body)
+        .title("Open Positions")
+        .button("Refresh Quotes", move |s| {
+            refresh_quotes_in_background(
+                s,
+                db.clone(),
+                instruments.clone(),
+                "positions",
+                show_positions,
+            );
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        })
+        .with_name("positions");
+    siv.add_layer(OnEventView::new(layer).on_event('e', move |s| {
+        let step = s
+            .call_on_name("position_list", |v: &mut SelectView<String>| {
+                next_expiring(&dtes, v.selected_id()?).map(|row| v.set_selection(row))
+            })
+            .flatten();
+        match step {
+            Some(step) => {
+                step(s);
+                s.call_on_name(
+                    "position_scroll",
+                    |v: &mut ScrollView<NamedView<SelectView<String>>>| {
+                        v.scroll_to_important_area()
+                    },
+                );
+            }
+            None => show_toast(s, "No open option expires from today on".to_string()),
+        }
+    }));
+}
+
+// Fetches marks for `instruments` on a background thread, reading and saving
+// quotes only briefly so the database stays usable meanwhile. When done,
+// reopens the screen named `layer` if it is still on top, checks alerts, and
+// lists any instrument that could not be quoted.
+fn refresh_quotes_in_background(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    instruments: Vec<String>,
+    layer: &'static str,
+    reopen: fn(&mut Cursive, Arc<Mutex<Database>>),
+) {
+    let ttl_secs = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.quote_ttl_secs())
+        .unwrap_or_default();
+    let provider = market_data(siv);
+    if provider.offline && provider.providers.is_empty() {
+        siv.add_layer(Dialog::info(
+            "Offline mode is on, so no quotes are fetched. The last-known marks \
+             are kept; enter marks by hand, or configure the csv provider.",
+        ));
+        return;
+    }
+    let db_job = db.clone();
+    run_in_background(
+        siv,
+        "Refreshing quotes",
+        move || -> rusqlite::Result<RefreshSummary> {
+            let stored = db_job
+                .lock()
+                .expect("Failed to lock database")
+                .get_quotes()?;
+            let summary = fetch_quotes(&provider, &instruments, &stored, ttl_secs, unix_now());
+            let db = db_job.lock().expect("Failed to lock database");
+            for quote in &summary.fetched {
+                db.set_quote(quote)?;
+            }
+            Ok(summary)
+        },
+        move |s, result| {
+            let summary = match result {
+                Ok(summary) => summary,
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    return;
+                }
+            };
+            show_toast(
+                s,
+                format!(
+                    "Quotes: {} fetched, {} cached, {} failed",
+                    summary.fetched.len(),
+                    summary.cached,
+                    summary.failures.len()
+                ),
+            );
+            if is_top_layer(s, layer) {
+                s.pop_layer();
+                reopen(s, db.clone());
+            }
+            notify_alerts(s, &db);
+            for (instrument, e) in &summary.failures {
+                show_error_toast(
+                    s,
+                    format!("No quote for {} (last-known price kept): {}", instrument, e),
+                );
+            }
+        },
+    );
+}
+
+// Whether the layer containing the view named `name` is the topmost one.
+fn is_top_layer(siv: &mut Cursive, name: &str) -> bool {
+    let screen = siv.screen_mut();
+    let top = screen.len().checked_sub(1);
+    matches!(screen.find_layer_from_name(name), Some(LayerPosition::FromBack(i)) if Some(i) == top)
+}
+
+// True when a view named `name` is in the top layer, wherever other layers
+// use the same name.
+fn top_layer_has(siv: &mut Cursive, name: &str) -> bool {
+    let mut found = false;
+    if let Some(layer) = siv.screen_mut().get_mut(LayerPosition::FromFront(0)) {
+        layer.call_on_any(&Selector::Name(name), &mut |_| found = true);
+    }
+    found
+}
+
+// The keys and fields `?` explains on a screen, found by a view it names.
+struct ScreenHelp {
+    view: &'static str,
+    title: &'static str,
+    keys: &'static [(&'static str, &'static str)],
+    fields: &'static [(&'static str, &'static str)],
+}
+
+// Date fields take the same shortcuts everywhere.
+const DATE_KEYS: (&str, &str) = (
+    "t, y, -3d, +2w, 1m",
+    "In a date field, Enter turns today, yesterday, or days/weeks/months \
+     from today into a date; ▾ opens a calendar",
+);
+
+const SCREEN_HELP: &[ScreenHelp] = &[
+    ScreenHelp {
+        view: "calendar",
+        title: "Pick a Date",
+        keys: &[
+            ("Left/Right", "Previous or next day"),
+            ("Up/Down", "Previous or next week"),
+            ("PageUp/PageDown", "Previous or next month"),
+            ("t", "Today"),
+            ("Enter", "Fill in the date"),
+            ("Esc", "Close without picking"),
+        ],
+        fields: &[],
+    },
+    ScreenHelp {
+        view: "dashboard",
+        title: "Main Menu",
+        keys: &[("Enter", "Open the highlighted screen")],
+        fields: &[
+            (
+                "Account value",
+                "Cash plus every open position at its mark, as on the valuation report",
+            ),
+            ("Open positions", "Stock and option positions not yet flat"),
+            (
+                "Realized P/L",
+                "Lots closed this month, by the cost-basis method in use",
+            ),
+            (
+                "Upcoming expirations",
+                "Open options from today on, soonest first",
+            ),
+            ("Recent trades", "The latest trades entered"),
+        ],
+    },
+    ScreenHelp {
+        view: "trade_list",
+        title: "View/Edit Trades",
+        keys: &[
+            (
+                "Enter",
+                "Open the trade: its details and what can be done to it",
+            ),
+            (
+                "f",
+                "Filter by symbol, type, action, dates, or other conditions",
+            ),
+            ("/", "Search symbols and comments"),
+            ("x", "Export the list to CSV"),
+            ("c", "Copy the trade to the clipboard as TSV"),
+            ("C", "Copy every filtered trade as TSV"),
+            ("y", "Add a trade like this one, dated today"),
+            ("a", "Quick Add a trade typed on one line"),
+            (
+                "e",
+                "Jump to the open option expiring soonest; again for the next",
+            ),
+            ("Space", "Mark or unmark the trade for Selected"),
+            ("V", "Start a range of marks; V again marks up to here"),
+            (
+                "Bulk Edit",
+                "Set a symbol, fees, account, or tags on every filtered trade",
+            ),
+            ("PageUp/PageDown, Home/End", "Jump through the page"),
+        ],
+        fields: &[
+            (
+                "Row N of M",
+                "The selected row, the page of the list it is on, and how many trades are marked",
+            ),
+            (
+                "Colored rows",
+                "Open options within 3 days of expiring in the loss color, within 10 in the warning color",
+            ),
+        ],
+    },
+    ScreenHelp {
+        view: "filter_symbol",
+        title: "Filter Trades",
+        keys: &[
+            ("Left/Right", "Step through the type and action choices"),
+            DATE_KEYS,
+        ],
+        fields: &[
+            ("Symbol contains", "Part of the symbol, in any case"),
+            ("Type, Action", "Only trades of that kind; any for all"),
+            ("From, To", "Trade dates, both included"),
+            (
+                "Other conditions",
+                "\"field op value\" joined by \"and\", as in custom reports",
+            ),
+        ],
+    },
+    ScreenHelp {
+        view: "price",
+        title: "Trade",
+        keys: &[
+            (
+                "Left/Right",
+                "Step through the type, action, and option type",
+            ),
+            ("a letter", "Jump to the choice starting with it"),
+            ("Ctrl+O", "Pick a contract from the option chain"),
+            DATE_KEYS,
+        ],
+        fields: &[
+            ("Quantity", "Shares, or contracts for an option"),
+            ("Price", "Per share; for an option, the per-share premium"),
+            (
+                "Fees",
+                "Commissions and fees, filled in from the broker's schedule",
+            ),
+            ("Strike, Expiration", "The option contract"),
+            (
+                "Multiplier",
+                "Shares per contract; blank for the standard 100",
+            ),
+            ("Tags", "Comma-separated labels to filter and report by"),
+        ],
+    },
+    ScreenHelp {
+        view: "quick_add",
+        title: "Quick Add",
+        keys: &[("Enter", "Save the trade the line reads as")],
+        fields: &[
+            (
+                "Action",
+                "buy or sell, with to open/to close, or bto/sto/btc/stc",
+            ),
+            ("Option", "Strike with c or p, and expiration: 190c 1/19"),
+            ("@", "The price, per share or contract"),
+            (
+                "fee, on, #tag",
+                "Fees, the trade date, and tags, after the price",
+            ),
+        ],
+    },
+    ScreenHelp {
+        view: "price_chart",
+        title: "Price Chart",
+        keys: &[
+            ("Left/Right", "Pan a quarter of the chart earlier or later"),
+            ("+ / -", "Zoom in to half the days, or out to twice as many"),
+            ("Home/End", "Jump to the oldest or latest days"),
+            ("c", "Switch between candles and a line of closes"),
+        ],
+        fields: &[
+            ("Candle", "Wick from high to low; body from open to close"),
+            ("█, ░", "A day that closed up, or down"),
+            (
+                "B, S, X",
+                "A buy, a sell, or both; stock trades at their price, options at the close",
+            ),
+        ],
+    },
+    ScreenHelp {
+        view: "past_expirations",
+        title: "Past Expirations",
+        keys: &[
+            (
+                "Enter",
+                "Record the option as expired worthless, or assigned or exercised",
+            ),
+            ("Expire All", "Record every one as expired worthless"),
+            ("Later", "Leave them open; they are listed again at startup"),
+        ],
+        fields: &[(
+            "Assigned, Exercised",
+            "A short option is assigned and a long one exercised, into stock at the strike",
+        )],
+    },
+    ScreenHelp {
+        view: "positions",
+        title: "Open Positions",
+        keys: &[
+            ("Enter", "Set the position's mark by hand"),
+            (
+                "e",
+                "Jump to the option expiring soonest; again for the next",
+            ),
+        ],
+        fields: &[
+            (
+                "Position",
+                "Shares or contracts; shorts are in the loss color",
+            ),
+            (
+                "Colored options",
+                "Within 3 days of expiring in the loss color, within 10 in the warning color",
+            ),
+            (
+                "Avg Cost, Mark",
+                "Per share, with fees folded into the cost",
+            ),
+            ("*", "A mark older than the quote TTL"),
+        ],
+    },
+    ScreenHelp {
+        view: "custom_filter",
+        title: "Custom Report",
+        keys: &[],
+        fields: &[
+            (
+                "Filter",
+                "\"field op value\" joined by \"and\"; ~ means contains",
+            ),
+            ("Group by", "What each row of the report totals"),
+        ],
+    },
+];
+
+// Opens the help for the screen on top, or closes it when it is on top.
+fn show_help(siv: &mut Cursive, keymap: &Keymap) {
+    if top_layer_has(siv, "help") {
+        siv.pop_layer();
+        return;
+    }
+    let screen = SCREEN_HELP
+        .iter()
+        .find(|screen| top_layer_has(siv, screen.view));
+    let exportable = top_layer_has(siv, "exportable");
+    siv.add_layer(
+        Dialog::around(
+            TextView::new(format_help(keymap, screen, exportable))
+                .with_name("help")
+                .scrollable()
+                .max_height(30),
+        )
+        .title("Help")
+        .button("Close", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// The keys bound everywhere, from `keymap`, then those and the fields of
+// `screen`.
+fn format_help(keymap: &Keymap, screen: Option<&ScreenHelp>, exportable: bool) -> String {
+    let line = |key: &str, what: &str| format!("  {:<16} {}", key, what);
+    let mut lines = vec!["Everywhere".to_string()];
+    for (action, keys) in keymap.help() {
+        lines.push(line(&keys, action.describe()));
+    }
+    lines.push(line("Tab/Shift+Tab", "Next or previous field or button"));
+    lines.push(line("Ctrl+Left/Right", "Move by word in a text field"));
+    lines.push(line("Ctrl+W", "Delete the word before the cursor"));
+    if let Some(screen) = screen {
+        lines.push(String::new());
+        lines.push(screen.title.to_string());
+        for (key, what) in screen.keys {
+            lines.push(line(key, what));
+        }
+        if screen.view == "trade_list" {
+            let columns: Vec<&str> = TradeSort::variants().iter().map(|by| by.as_str()).collect();
+            lines.push(line(
+                &format!("1-{}", columns.len()),
+                &format!("Sort by {}; again to reverse", columns.join(", ")),
+            ));
+        }
+        if !screen.fields.is_empty() {
+            lines.push(String::new());
+            lines.push("Fields".to_string());
+            for (field, what) in screen.fields {
+                lines.push(line(field, what));
+            }
+        }
+    }
+    if exportable {
+        lines.push(String::new());
+        lines.push(line("x", "Export this report to CSV"));
+    }
+    lines.join("\n")
+}
+
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+const SPINNER_INTERVAL: Duration = Duration::from_millis(150);
+
+// Runs `job` on a worker thread and hands its result to `done` on the UI
+// thread. Meanwhile the footer shows `label` with a spinner, ticked by a
+// supervising thread that waits on the job's channel.
+fn run_in_background<T, J, D>(siv: &mut Cursive, label: &str, job: J, done: D)
+where
+    T: Send + 'static,
+    J: FnOnce() -> T + Send + 'static,
+    D: FnOnce(&mut Cursive, T) + Send + 'static,
+{
+    if let Some(state) = siv.user_data::<UiState>() {
+        state.background_jobs += 1;
+    }
+    set_status(siv, format!("{} {}...", SPINNER[0], label));
+
+    let sink = siv.cb_sink().clone();
+    let label = label.to_string();
+    thread::spawn(move || {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(job());
+        });
+        let mut frame = 0;
+        let result = loop {
+            match rx.recv_timeout(SPINNER_INTERVAL) {
+                Ok(result) => break Some(result),
+                Err(RecvTimeoutError::Timeout) => {
+                    frame += 1;
+                    let text = format!("{} {}...", SPINNER[frame % SPINNER.len()], label);
+                    let _ = sink.send(Box::new(move |s| set_status(s, text)));
+                }
+                // The job panicked before sending a result.
+                Err(RecvTimeoutError::Disconnected) => break None,
+            }
+        };
+        let _ = sink.send(Box::new(move |s| {
+            let remaining = s
+                .user_data::<UiState>()
+                .map(|state| {
+                    state.background_jobs = state.background_jobs.saturating_sub(1);
+                    state.background_jobs
+                })
+                .unwrap_or(0);
+            if remaining == 0 {
+                set_status(s, String::new());
+            }
+            match result {
+                Some(result) => done(s, result),
+                None => show_error_toast(s, format!("{} failed", label)),
+            }
+        }));
+    });
+}
+
+// The configured market data providers, chained for fallback.
+fn market_data(siv: &mut Cursive) -> Fallback {
+    let config = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.clone())
+        .unwrap_or_default();
+    configured_provider(&config)
+}
+
+// Replaces the footer text.
+fn set_status(siv: &mut Cursive, text: String) {
+    siv.call_on_name("status_bar", |v: &mut TextView| v.set_content(text));
+}
+
+// Shows `text` above the footer for a few seconds.
+fn show_toast(siv: &mut Cursive, text: String) {
+    push_toast(siv, text, ToastKind::Info);
+}
+
+// Shows `text` above the footer in the loss color, for longer than a toast.
+fn show_error_toast(siv: &mut Cursive, text: String) {
+    push_toast(siv, text, ToastKind::Error);
+}
+
+fn push_toast(siv: &mut Cursive, text: String, kind: ToastKind) {
+    let Some(state) = siv.user_data::<UiState>() else {
+        return;
+    };
+    let expires = state.toasts.push(text, kind, Instant::now());
+    draw_toasts(siv);
+    let sink = siv.cb_sink().clone();
+    thread::spawn(move || {
+        thread::sleep(expires.saturating_duration_since(Instant::now()));
+        let _ = sink.send(Box::new(|s| {
+            if let Some(state) = s.user_data::<UiState>() {
+                state.toasts.expire(Instant::now());
+            }
+            draw_toasts(s);
+        }));
+    });
+}
+
+// Writes the toasts shown into the footer layer, one a line.
+fn draw_toasts(siv: &mut Cursive) {
+    let colors = colors(siv);
+    let mut text = StyledString::new();
+    if let Some(state) = siv.user_data::<UiState>() {
+        for toast in state.toasts.shown() {
+            let line = format!("{}\n", toast.label());
+            match toast.kind {
+                ToastKind::Info => text.append_plain(line),
+                ToastKind::Error => text.append_styled(line, colors.loss),
+            }
+        }
+    }
+    siv.call_on_name("toasts", |v: &mut TextView| v.set_content(text));
+}
+
+// `#12 AAPL`, for naming a trade in messages.
+fn describe_trade(trade: &Trade) -> String {
+    match trade.id {
+        Some(id) => format!("#{} {}", id, trade.symbol),
+        None => trade.symbol.clone(),
+    }
+}
+
+// Remembers a change for undo if it logged anything.
+fn push_undo(siv: &mut Cursive, label: String, after: i64, upto: i64) {
+    if upto <= after {
+        return;
+    }
+    if let Some(state) = siv.user_data::<UiState>() {
+        state.undo.push(UndoStep { label, after, upto });
+        state.redo.clear();
+    }
+}
+
+// Reverses the most recent edit or delete still on the undo stack.
+fn undo(siv: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let Some(step) = siv
+        .user_data::<UiState>()
+        .and_then(|state| state.undo.pop())
+    else {
+        show_toast(siv, "Nothing to undo".to_string());
+        return;
+    };
+    let result = db
+        .lock()
+        .expect("Failed to lock database")
+        .undo_changes(step.after, step.upto);
+    match result {
+        Ok(touched) => {
+            refresh_after_history(siv, db, &touched);
+            show_toast(siv, format!("Undid {}", step.label));
+            if let Some(state) = siv.user_data::<UiState>() {
+                state.redo.push(step);
+            }
+        }
+        Err(e) => siv.add_layer(Dialog::info(format!("Undo failed: {}", e))),
+    }
+}
+
+// After an undo or redo, closes any form editing a trade it changed, whose
+// fields are now stale, and rebuilds View/Edit Trades where it stands among
+// the layers.
+fn refresh_after_history(siv: &mut Cursive, db: &Arc<Mutex<Database>>, touched: &[i64]) {
+    for &id in touched {
+        let name = edit_form_name(id);
+        while let Some(position) = siv.screen_mut().find_layer_from_name(&name) {
+            siv.screen_mut().remove_layer(position);
+        }
+    }
+    let Some(position) = siv.screen_mut().find_layer_from_name("trade_list") else {
+        return;
+    };
+    siv.screen_mut().remove_layer(position);
+    show_view_trades(siv, db.clone());
+    siv.screen_mut()
+        .move_layer(LayerPosition::FromFront(0), position);
+}
+
+// Makes the most recently undone change again.
+fn redo(siv: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let Some(step) = siv
+        .user_data::<UiState>()
+        .and_then(|state| state.redo.pop())
+    else {
+        show_toast(siv, "Nothing to redo".to_string());
+        return;
+    };
+    let result = db
+        .lock()
+        .expect("Failed to lock database")
+        .redo_changes(step.after, step.upto);
+    match result {
+        Ok(touched) => {
+            refresh_after_history(siv, db, &touched);
+            show_toast(siv, format!("Redid {}", step.label));
+            if let Some(state) = siv.user_data::<UiState>() {
+                state.undo.push(step);
+            }
+        }
+        Err(e) => siv.add_layer(Dialog::info(format!("Redo failed: {}", e))),
+    }
+}
+
+// Open option positions grouped by expiration date, soonest first. Dates this
+// week are highlighted in yellow and unresolved past expirations in red.
+// Export writes the upcoming ones to an .ics file for a phone calendar.
+fn show_expiration_calendar(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let colors = colors(siv);
+    let result = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_open_options_by_expiration(None, None)
+            .and_then(|options| Ok((options, db.get_all_trades()?)))
+    };
+    let (options, trades) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let now = today();
+    let week_end = end_of_week(&now).unwrap_or_else(|| now.clone());
+    // Net what is still open per contract; openers closed by later trades drop
+    // out.
+    let positions = open_positions(&trades, &now);
+
+    let mut content = StyledString::new();
+    let mut current_expiration: Option<String> = None;
+    let mut shown = Vec::new();
+    for option in &options {
+        let key = option.position_key();
+        let short = option.action == Action::SellToOpen;
+        if shown.contains(&(key.clone(), short)) {
+            continue;
+        }
+        let position = match positions
+            .iter()
+            .find(|p| p.key == key && p.is_short() == short)
+        {
+            Some(position) => position,
+            None => continue,
+        };
+        shown.push((key.clone(), short));
+
+        let expiration = key.expiration.clone().unwrap_or_default();
+        if current_expiration.as_ref() != Some(&expiration) {
+            let dte = days_to_expiration(&now, &expiration).unwrap_or(0);
+            let heading = format!(
+                "{}{}  ({})\n",
+                if current_expiration.is_some() {
+                    "\n"
+                } else {
+                    ""
+                },
+                expiration,
+                format_dte(dte)
+            );
+            if dte < 0 {
+                content.append_styled(heading, colors.loss);
+            } else if expiration <= week_end {
+                content.append_styled(heading, colors.warning);
+            } else {
+                content.append_plain(heading);
+            }
+            current_expiration = Some(expiration);
+        }
+
+        let ids: Vec<String> = options
+            .iter()
+            .filter(|o| o.position_key() == key && (o.action == Action::SellToOpen) == short)
+            .filter_map(|o| o.id.map(|id| format!("#{}", id)))
+            .collect();
+        content.append_plain(format!(
+            "  {:<8} {:<28} {:>12}  {}\n",
+            key.symbol,
+            format_instrument(&key),
+            format_position(position.quantity),
+            ids.join(", ")
+        ));
+    }
+
+    if shown.is_empty() {
+        show_dialog_with_back(siv, "No open option positions".to_string());
+        return;
+    }
+
+    siv.add_layer(
+        Dialog::around(TextView::new(content).scrollable())
+            .title("Expiration Calendar")
+            .button("Export", move |s| {
+                let dir = s
+                    .user_data::<UiState>()
+                    .map(|state| state.config.export_dir.clone())
+                    .unwrap_or_default();
+                let calendar = expiration_calendar(&positions, &now, unix_now());
+                match write_file(&calendar, &dir, "Option Expirations", "ics", unix_now()) {
+                    Ok(path) => s.add_layer(Dialog::info(format!(
+                        "Exported expirations to {}",
+                        path.display()
+                    ))),
+                    Err(e) => s.add_layer(Dialog::info(format!("Export failed: {}", e))),
+                }
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Prompts for the mark price of one instrument; an empty price clears it.
+fn show_set_mark(siv: &mut Cursive, db: Arc<Mutex<Database>>, instrument: String) {
+    let existing = match db.lock().expect("Failed to lock database").get_quotes() {
+        Ok(quotes) => quotes
+            .get(&instrument)
+            .map(|quote| quote.price.to_string())
+            .unwrap_or_default(),
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+
+    let form = ListView::new().child(
+        "Mark price:",
+        EditView::new()
+            .content(existing)
+            .with_name("mark_price")
+            .word_keys()
+            .fixed_width(15),
+    );
+    let title = format!("Mark for {}", instrument);
+    siv.add_layer(
+        Dialog::around(form)
+            .title(title)
+            .button("Save", move |s| {
+                let raw = read_field(s, "mark_price").unwrap_or_default();
+                let raw = raw.trim();
+                let res = if raw.is_empty() {
+                    db.lock()
+                        .expect("Failed to lock database")
+                        .delete_quote(&instrument)
+                } else {
+                    let price = match parse_amount(s, raw, "mark price", true) {
+                        Some(price) => price,
+                        None => return,
+                    };
+                    db.lock()
+                        .expect("Failed to lock database")
+                        .set_quote(&Quote {
+                            instrument: instrument.clone(),
+                            price,
+                            fetched_at: format_timestamp(unix_now()),
+                            source: "manual".to_string(),
+                        })
+                };
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_positions(s, db.clone());
+                        notify_alerts(s, &db);
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Checks alerts after marks change: refreshes the main-menu banner and pops up
+// any alert that just triggered.
+fn notify_alerts(siv: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let colors = colors(siv);
+    let res = check_alerts(
+        &db.lock().expect("Failed to lock database"),
+        &today(),
+        unix_now(),
+    );
+    let triggered = match res {
+        Ok(triggered) => triggered,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Error checking alerts: {}", e)));
+            return;
+        }
+    };
+    update_alert_banner(siv, db);
+    if triggered.is_empty() {
+        return;
+    }
+    let mut text = StyledString::new();
+    for alert in &triggered {
+        text.append_styled(format!("{}\n", format_alert_status(alert)), colors.loss);
+    }
+    siv.add_layer(
+        Dialog::around(TextView::new(text))
+            .title(format!("{} alert(s) triggered", triggered.len()))
+            .button("OK", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Highlights the main-menu banner while any alert is triggered.
+fn update_alert_banner(siv: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let colors = colors(siv);
+    let triggered = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_alerts()
+        .map(|alerts| alerts.iter().filter(|a| a.triggered_at.is_some()).count())
+        .unwrap_or(0);
+    let banner = if triggered > 0 {
+        StyledString::styled(
+            format!("! {} alert(s) triggered - see Alerts\n", triggered),
+            colors.loss,
+        )
+    } else {
+        StyledString::new()
+    };
+    siv.call_on_name("alert_banner", |v: &mut TextView| v.set_content(banner));
+}
+
+// One line describing an alert and whether it has triggered.
+fn format_alert_status(alert: &Alert) -> String {
+    match (&alert.triggered_at, alert.triggered_value) {
+        (Some(at), Some(value)) => format!(
+            "{}  TRIGGERED {} at {}",
+            alert.describe(),
+            at,
+            if alert.kind.is_percent() {
+                format!("{}%", value.normalize())
+            } else {
+                format!("${:.2}", value)
+            }
+        ),
+        (Some(at), None) => format!("{}  TRIGGERED {}", alert.describe(), at),
+        _ => format!("{}  watching", alert.describe()),
+    }
+}
+
+// Every alert rule; triggered ones are red. Selecting one re-arms or deletes
+// it.
+fn show_alerts(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let colors = colors(siv);
+    let alerts = match db.lock().expect("Failed to lock database").get_alerts() {
+        Ok(alerts) => alerts,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<Alert>::new().h_align(HAlign::Left);
+    for alert in alerts {
+        let row = format_alert_status(&alert);
+        let row = if alert.triggered_at.is_some() {
+            StyledString::styled(row, colors.loss)
+        } else {
+            StyledString::plain(row)
+        };
+        select.add_item(row, alert);
+    }
+    let db_select = db.clone();
+    select.set_on_submit(move |s, alert: &Alert| {
+        let id = match alert.id {
+            Some(id) => id,
+            None => return,
+        };
+        let db_rearm = db_select.clone();
+        let db_delete = db_select.clone();
+        s.add_layer(
+            Dialog::text(alert.describe())
+                .button("Re-arm", move |s| {
+                    let res = db_rearm
+                        .lock()
+                        .expect("Failed to lock database")
+                        .set_alert_triggered(id, None);
+                    refresh_alerts_screen(s, &db_rearm, res);
+                })
+                .button("Delete", move |s| {
+                    let res = db_delete
+                        .lock()
+                        .expect("Failed to lock database")
+                        .delete_alert(id);
+                    refresh_alerts_screen(s, &db_delete, res);
+                })
+                .button("Cancel", |s| {
+                    s.pop_layer();
+                }),
+        );
+    });
+
+    let db_add = db.clone();
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(select.scrollable().fixed_size((80, 12)))
+                .child(TextView::new(
+                    "\nAlerts are checked whenever marks are set or refreshed. A triggered\n\
+                     alert stays triggered (and the main menu flags it) until re-armed.",
+                )),
+        )
+        .title("Alerts")
+        .button("Add", move |s| show_alert_form(s, db_add.clone()))
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Every recorded change to a trade, oldest first, with the fields each edit
+// changed from and to.
+fn show_trade_history(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade_id: i64) {
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_trade_history(trade_id);
+    let entries = match res {
+        Ok(entries) => entries,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if entries.is_empty() {
+        show_dialog_with_back(siv, "No changes recorded".to_string());
+        return;
+    }
+
+    let mut content = String::new();
+    for entry in &entries {
+        content.push_str(&format!(
+            "{} UTC  {}\n",
+            entry.changed_at,
+            history::summary(entry)
+        ));
+        if entry.change == HistoryChange::Update {
+            for change in history::field_changes(entry) {
+                content.push_str(&format!(
+                    "    {}: {} → {}\n",
+                    change.field,
+                    or_dash(&change.before),
+                    or_dash(&change.after)
+                ));
+            }
+        }
+    }
+    siv.add_layer(
+        Dialog::around(TextView::new(content).scrollable().fixed_size((80, 18)))
+            .title(format!("History of Trade #{}", trade_id))
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// The files attached to a trade. Enter opens one with the system's opener.
+fn show_attachments(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade_id: i64) {
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_attachments(trade_id);
+    let attachments = match res {
+        Ok(attachments) => attachments,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<Attachment>::new().h_align(HAlign::Left);
+    for attachment in attachments {
+        let row = format!(
+            "{:<30} {}  {}",
+            attachment.name,
+            &attachment.added_at[..attachment.added_at.len().min(10)],
+            location(&attachment)
+        );
+        select.add_item(row, attachment);
+    }
+    select.set_on_submit(|s, attachment: &Attachment| {
+        if let Err(e) = open_attachment(attachment) {
+            s.add_layer(Dialog::info(format!("Error: {}", e)));
+        }
+    });
+
+    let (db_add, db_remove) = (db.clone(), db.clone());
+    siv.add_layer(
+        Dialog::around(
+            select
+                .with_name("attachment_list")
+                .scrollable()
+                .scroll_x(true)
+                .fixed_size((80, 10)),
+        )
+        .title(format!("Files of Trade #{}", trade_id))
+        .button("Add", move |s| {
+            show_attachment_form(s, db_add.clone(), trade_id)
+        })
+        .button("Remove", move |s| {
+            let selected = read_select::<Attachment>(s, "attachment_list");
+            let Some(id) = selected.and_then(|attachment| attachment.id) else {
+                return;
+            };
+            let res = db_remove
+                .lock()
+                .expect("Failed to lock database")
+                .delete_attachment(id);
+            match res {
+                Ok(_) => {
+                    s.pop_layer();
+                    show_attachments(s, db_remove.clone(), trade_id);
+                }
+                Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+            }
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Form attaching a file to a trade, by path or as a copy.
+fn show_attachment_form(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade_id: i64) {
+    let mut store = SelectView::<bool>::new().popup();
+    store.add_item("Link to the file", false);
+    store.add_item("Copy into the database", true);
+    let form = ListView::new()
+        .child(
+            "File:",
+            EditView::new()
+                .with_name("attach_path")
+                .word_keys()
+                .fixed_width(50),
+        )
+        .child("Keep:", store.with_name("attach_store").fixed_width(25));
+
+    siv.add_layer(
+        Dialog::around(form)
+            .title("Attach File")
+            .button("Attach", move |s| {
+                let path = read_field(s, "attach_path")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                if path.is_empty() {
+                    s.add_layer(Dialog::info("File is required"));
+                    return;
+                }
+                let store = read_select::<bool>(s, "attach_store").unwrap_or(false);
+                let res = attach_file(trade_id, Path::new(&path), store).and_then(|attachment| {
+                    db.lock()
+                        .expect("Failed to lock database")
+                        .add_attachment(&attachment)
+                        .map_err(|e| e.to_string())
+                });
+                match res {
+                    Ok(_) => {
+                        // Close the form and the old list.
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_attachments(s, db.clone(), trade_id);
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// A blank history value as a dash.
+fn or_dash(value: &str) -> &str {
+    if value.is_empty() {
+        "—"
+    } else {
+        value
+    }
+}
+
+// Deleted trades, newest deletion first. Enter restores one; Purge deletes
+// them all for good.
+fn show_trash(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let trades = match db
+        .lock()
+        .expect("Failed to lock database")
+        .get_deleted_trades()
+    {
+        Ok(trades) => trades,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if trades.is_empty() {
+        show_dialog_with_back(siv, "The Trash is empty".to_string());
+        return;
+    }
+
+    let now = today();
+    let mut select = SelectView::<i64>::new().h_align(HAlign::Left);
+    for trade in &trades {
+        if let Some(id) = trade.id {
+            let deleted = trade.deleted_at.clone().unwrap_or_default();
+            select.add_item(
+                format!(
+                    "{}  (deleted {} UTC)",
+                    format_trade_row(trade, &now),
+                    deleted
+                ),
+                id,
+            );
+        }
+    }
+    let db_select = db.clone();
+    select.set_on_submit(move |s, id: &i64| {
+        let id = *id;
+        let db = db_select.clone();
+        s.add_layer(
+            Dialog::text(format!("Restore trade #{}?", id))
+                .button("Restore", move |s| {
+                    let res = db
+                        .lock()
+                        .expect("Failed to lock database")
+                        .restore_trade(id);
+                    match res {
+                        Ok(()) => {
+                            s.pop_layer();
+                            s.pop_layer();
+                            show_trash(s, db.clone());
+                        }
+                        Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                    }
+                })
+                .button("Cancel", |s| {
+                    s.pop_layer();
+                }),
+        );
+    });
+
+    let count = trades.len();
+    siv.add_layer(
+        Dialog::around(select.scrollable().fixed_size((100, 16)))
+            .title("Trash")
+            .button("Purge", move |s| {
+                let db = db.clone();
+                s.add_layer(
+                    Dialog::text(format!(
+                        "Delete the {} trade(s) in the Trash for good? This can't be undone.",
+                        count
+                    ))
+                    .button("Purge", move |s| {
+                        let res = db.lock().expect("Failed to lock database").purge_trash();
+                        match res {
+                            Ok(_) => {
+                                s.pop_layer();
+                                s.pop_layer();
+                            }
+                            Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                        }
+                    })
+                    .button("Cancel", |s| {
+                        s.pop_layer();
+                    }),
+                );
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Totals of the archived years, per year; Enter on one offers to bring back
+// its trades and those of every later archived year.
+fn show_archive(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_archive_summaries();
+    let summaries = match res {
+        Ok(summaries) => summaries,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let header = format!(
+        "{:<6} {:>7} {:>14} {:>12} {:>14}",
+        "Year", "Trades", "Cash Flow", "Fees", "Realized"
+    );
+    let mut select = SelectView::<i32>::new().h_align(HAlign::Left);
+    let mut export = Table::new(&[
+        "Year",
+        "Account",
+        "Symbol",
+        "Trades",
+        "Cash Flow",
+        "Fees",
+        "Realized",
+    ]);
+    for year in archived_years(&summaries) {
+        let in_year: Vec<&ArchiveSummary> = summaries.iter().filter(|s| s.year == year).collect();
+        let total = |amount: fn(&ArchiveSummary) -> Decimal| -> Decimal {
+            in_year.iter().map(|s| amount(s)).sum()
+        };
+        select.add_item(
+            format!(
+                "{:<6} {:>7} {:>14.2} {:>12.2} {:>14.2}",
+                year,
+                in_year.iter().map(|s| s.trades).sum::<usize>(),
+                total(|s| s.cash_flow),
+                total(|s| s.fees),
+                total(|s| s.realized),
+            ),
+            year,
+        );
+        for summary in in_year {
+            export.row(&[
+                &summary.year,
+                &or_blank(summary.account_id),
+                &summary.symbol,
+                &summary.trades,
+                &summary.cash_flow,
+                &summary.fees,
+                &summary.realized,
+            ]);
+        }
+    }
+    let db_select = db.clone();
+    select.set_on_submit(move |s, year: &i32| {
+        let (db, year) = (db_select.clone(), *year);
+        s.add_layer(
+            Dialog::text(format!(
+                "Bring the trades of {} and every later archived year back?",
+                year
+            ))
+            .button("Restore", move |s| {
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .restore_archive_from(year);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_archive(s, db.clone());
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+        );
+    });
+
+    let body = LinearLayout::vertical()
+        .child(TextView::new(header))
+        .child(select.scrollable().fixed_size((60, 10)))
+        .child(TextView::new(
+            "\nArchived trades are left out of every list and report; the cash\n\
+             balance still counts their cash flow. Realized P/L is as matched\n\
+             when they were archived.",
+        ));
+    let db_archive = db.clone();
+    siv.add_layer(exportable(
+        Dialog::around(body)
+            .title("Archive")
+            .button("Archive Years", move |s| {
+                show_archivable_years(s, db_archive.clone())
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        "Archive".to_string(),
+        export,
+    ));
+}
+
+// The closed years trades can be archived through; picking one archives it
+// and every year before it.
+fn show_archivable_years(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_archivable_years(&today());
+    let years = match res {
+        Ok(years) => years,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    if years.is_empty() {
+        siv.add_layer(Dialog::info(
+            "No year can be archived: every past year ends with a position still\n\
+             open, or holds trades tied to later ones by an assignment, a roll, or\n\
+             a strategy.",
+        ));
+        return;
+    }
+
+    let mut select = SelectView::<i32>::new().h_align(HAlign::Left);
+    for year in years.into_iter().rev() {
+        select.add_item(format!("Through {}", year), year);
+    }
+    select.set_on_submit(move |s, year: &i32| {
+        let year = *year;
+        let method = s
+            .user_data::<UiState>()
+            .map(|state| state.cost_basis_method)
+            .unwrap_or_default();
+        let res =
+            db.lock()
+                .expect("Failed to lock database")
+                .archive_through(year, method, &today());
+        match res {
+            Ok(count) => {
+                // Undo steps may name trades now archived.
+                if let Some(state) = s.user_data::<UiState>() {
+                    state.undo.clear();
+                    state.redo.clear();
+                }
+                s.pop_layer();
+                s.pop_layer();
+                show_archive(s, db.clone());
+                s.add_layer(Dialog::info(format!(
+                    "Archived {} trade(s) dated through {}.",
+                    count, year
+                )));
+            }
+            Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+        }
+    });
+    siv.add_layer(
+        Dialog::around(select.scrollable().fixed_size((30, 10)))
+            .title("Archive Years")
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Settings, read from the config file, and the database snapshots in the
+// backup directory, newest first. Back Up Now takes a snapshot; Enter on one
+// restores it after confirming, snapshotting the current database first.
+fn show_settings(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let Some(config) = siv.user_data::<UiState>().map(|state| state.config.clone()) else {
+        return;
+    };
+    let snapshots = match list_snapshots(&config.backup_dir) {
+        Ok(snapshots) => snapshots,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Error reading snapshots: {}", e));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<PathBuf>::new().h_align(HAlign::Left);
+    for path in snapshots {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        select.add_item(name, path);
+    }
+    let db_restore = db.clone();
+    select.set_on_submit(move |s, path: &PathBuf| {
+        let (db, path) = (db_restore.clone(), path.clone());
+        s.add_layer(
+            Dialog::text(format!(
+                "Replace the database with {}? A snapshot of the current one is taken first.",
+                path.display()
+            ))
+            .button("Restore", move |s| restore_snapshot(s, &db, &path))
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+        );
+    });
+
+    let encrypted = db.lock().expect("Failed to lock database").is_encrypted();
+    let db_rates = db.clone();
+    let info = format!(
+        "Settings are read from {} at startup.\n\
+         Reports convert other currencies into {}.\n\
+         Colors follow the {} theme ([theme] in the settings).\n\
+         The database is {}.\n\n\
+         Snapshots in {} (automatic ones are taken before upgrades,\n\
+         imports, and restores; the newest {} are kept):",
+        CONFIG_FILE,
+        config.base_currency,
+        config.theme.name,
+        if encrypted {
+            "encrypted; `options_tracker encrypt` changes the passphrase"
+        } else {
+            "not encrypted; `options_tracker encrypt` encrypts it"
+        },
+        config.backup_dir,
+        config.backup_keep
+    );
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(info))
+                .child(DummyView)
+                .child(select.scrollable().fixed_size((60, 10))),
+        )
+        .title("Settings")
+        .button("FX Rates", move |s| show_fx_rates(s, db_rates.clone()))
+        .button("Back Up Now", move |s| {
+            let res = manual_snapshot(
+                &db.lock().expect("Failed to lock database"),
+                &config.backup_dir,
+                unix_now(),
+            );
+            match res {
+                Ok(path) => {
+                    s.pop_layer();
+                    show_settings(s, db.clone());
+                    s.add_layer(Dialog::info(format!(
+                        "Saved a snapshot to {}",
+                        path.display()
+                    )));
+                }
+                Err(e) => s.add_layer(Dialog::info(format!("Backup failed: {}", e))),
+            }
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Replaces the database with the snapshot at `path`, then starts over from
+// the main menu, since every screen and the undo history describe the old
+// one.
+fn restore_snapshot(s: &mut Cursive, db: &Arc<Mutex<Database>>, path: &Path) {
+    let (dir, keep) = s
+        .user_data::<UiState>()
+        .map(|state| (state.config.backup_dir.clone(), state.config.backup_keep))
+        .unwrap_or_default();
+    let res = {
+        let mut db = db.lock().expect("Failed to lock database");
+        auto_snapshot(&db, &dir, "restore", keep, unix_now()).and_then(|saved| {
+            db.restore_snapshot(path)
+                .map(|_| saved)
+                .map_err(|e| e.to_string())
+        })
+    };
+    match res {
+        Ok(saved) => {
+            if let Some(state) = s.user_data::<UiState>() {
+                state.undo.clear();
+                state.redo.clear();
+            }
+            show_main_menu(s, db.clone());
+            s.add_layer(Dialog::info(format!(
+                "Restored {}. The previous database is in {}.",
+                path.display(),
+                saved.display()
+            )));
+        }
+        Err(e) => s.add_layer(Dialog::info(format!("Restore failed: {}", e))),
+    }
+}
+
+// The accounts to switch between, with consolidated figures for all of them
+// at the top. Picking one limits trades and reports to it.
+fn show_accounts(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_accounts().map(|accounts| (accounts, db.account()))
+    };
+    let (accounts, current) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<Option<i64>>::new().h_align(HAlign::Left);
+    select.add_item("All accounts (consolidated)", None);
+    for account in &accounts {
+        select.add_item(
+            format!("{} ({})", account.name, account.currency),
+            account.id,
+        );
+    }
+    let selected = accounts
+        .iter()
+        .position(|a| a.id.is_some() && a.id == current)
+        .map_or(0, |i| i + 1);
+    select.set_selection(selected);
+    let db_select = db.clone();
+    select.set_on_submit(move |s, account: &Option<i64>| {
+        db_select
+            .lock()
+            .expect("Failed to lock database")
+            .set_account(*account);
+        show_main_menu(s, db_select.clone());
+    });
+
+    let db_add = db.clone();
+    let db_delete = db.clone();
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(
+                    select
+                        .with_name("accounts")
+                        .scrollable()
+                        .fixed_size((50, 10)),
+                )
+                .child(TextView::new(
+                    "\nEnter switches to an account. Trades without an account only\n\
+                     show under All accounts.",
+                )),
+        )
+        .title("Accounts")
+        .button("Add", move |s| show_add_account(s, db_add.clone()))
+        .button("Delete", move |s| {
+            let Some(Some(id)) = read_select::<Option<i64>>(s, "accounts") else {
+                return;
+            };
+            let name = accounts
+                .iter()
+                .find(|a| a.id == Some(id))
+                .map(|a| a.name.clone())
+                .unwrap_or_default();
+            let db = db_delete.clone();
+            s.add_layer(
+                Dialog::text(format!(
+                    "Delete the account {}? Its trades are kept without an account.",
+                    name
+                ))
+                .button("Delete", move |s| {
+                    let res = db
+                        .lock()
+                        .expect("Failed to lock database")
+                        .delete_account(id);
+                    match res {
+                        Ok(()) => {
+                            s.pop_layer();
+                            s.pop_layer();
+                            show_accounts(s, db.clone());
+                        }
+                        Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                    }
+                })
+                .button("Cancel", |s| {
+                    s.pop_layer();
+                }),
+            );
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Every exchange rate, newest first; Enter on one offers to delete it.
+fn show_fx_rates(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let res = db.lock().expect("Failed to lock database").get_fx_rates();
+    let rates = match res {
+        Ok(rates) => rates,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<FxRate>::new().h_align(HAlign::Left);
+    for rate in rates {
+        let row = format!(
+            "{:<10} {}/{} {:>12} {}",
+            rate.date,
+            rate.from,
+            rate.to,
+            rate.rate.normalize(),
+            rate.source
+        );
+        select.add_item(row, rate);
+    }
+    let db_select = db.clone();
+    select.set_on_submit(move |s, rate: &FxRate| {
+        let (db, rate) = (db_select.clone(), rate.clone());
+        s.add_layer(
+            Dialog::text(format!(
+                "Delete the {}/{} rate of {}?",
+                rate.from, rate.to, rate.date
+            ))
+            .button("Delete", move |s| {
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .delete_fx_rate(&rate);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_fx_rates(s, db.clone());
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+        );
+    });
+
+    let (db_add, db_fetch) = (db.clone(), db.clone());
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(
+                    select
+                        .with_name("fx_rates")
+                        .scrollable()
+                        .fixed_size((50, 12)),
+                )
+                .child(TextView::new(
+                    "\nA rate is what one of the first currency is worth in the\n\
+                     second. Fetch gets today's rate into the base currency for\n\
+                     every currency trades and accounts use.",
+                )),
+        )
+        .title("FX Rates")
+        .button("Add", move |s| show_fx_rate_form(s, db_add.clone()))
+        .button("Fetch", move |s| fetch_fx_rates(s, db_fetch.clone()))
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Form for an exchange rate entered by hand.
+fn show_fx_rate_form(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let base = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.base_currency.clone())
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_string());
+    let form = ListView::new()
+        .child("Date (YYYY-MM-DD):", date_field("fx_date", today(), 15))
+        .child(
+            "From:",
+            EditView::new()
+                .with_name("fx_from")
+                .word_keys()
+                .fixed_width(15),
+        )
+        .child(
+            "To:",
+            EditView::new()
+                .content(base)
+                .with_name("fx_to")
+                .word_keys()
+                .fixed_width(15),
+        )
+        .child(
+            "Rate:",
+            EditView::new()
+                .with_name("fx_rate")
+                .word_keys()
+                .fixed_width(15),
+        );
+
+    siv.add_layer(
+        Dialog::around(form)
+            .title("New FX Rate")
+            .button("Save", move |s| {
+                let date = read_field(s, "fx_date")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                let Some(date) = parse_date_field(&date) else {
+                    s.add_layer(Dialog::info(
+                        "Invalid date format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+                    ));
+                    return;
+                };
+                let pair = parse_currency(&read_field(s, "fx_from").unwrap_or_default()).and_then(
+                    |from| {
+                        parse_currency(&read_field(s, "fx_to").unwrap_or_default())
+                            .map(|to| (from, to))
+                    },
+                );
+                let (from, to) = match pair {
+                    Ok((from, to)) if from != to => (from, to),
+                    Ok(_) => {
+                        s.add_layer(Dialog::info("The two currencies must differ"));
+                        return;
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+                };
+                let raw = read_field(s, "fx_rate").unwrap_or_default();
+                let Some(rate) = parse_amount(s, raw.trim(), "rate", false) else {
+                    return;
+                };
+                if rate.is_zero() {
+                    s.add_layer(Dialog::info("Rate must be greater than zero"));
+                    return;
+                }
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .set_fx_rate(&FxRate {
+                        date,
+                        from,
+                        to,
+                        rate,
+                        source: "manual".to_string(),
+                    });
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_fx_rates(s, db.clone());
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Fetches today's rate into the base currency of every other currency in use,
+// in the background.
+fn fetch_fx_rates(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let base = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.base_currency.clone())
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_string());
+    let currencies = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_all_trades().and_then(|trades| {
+            let mut currencies: Vec<String> = trades
+                .into_iter()
+                .map(|t| t.currency)
+                .chain(db.get_accounts()?.into_iter().map(|a| a.currency))
+                .filter(|c| *c != base)
+                .collect();
+            currencies.sort();
+            currencies.dedup();
+            Ok(currencies)
+        })
+    };
+    let currencies = match currencies {
+        Ok(currencies) => currencies,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    if currencies.is_empty() {
+        siv.add_layer(Dialog::info(format!(
+            "Every trade and account is in {}, so there is nothing to fetch.",
+            base
+        )));
+        return;
+    }
+    let provider = market_data(siv);
+    let db_job = db.clone();
+    run_in_background(
+        siv,
+        "Fetching FX rates",
+        move || -> rusqlite::Result<Vec<String>> {
+            let now = today();
+            let mut failures = Vec::new();
+            for currency in &currencies {
+                match fetch_fx_rate(&provider, currency, &base, &now) {
+                    Ok(rate) => db_job
+                        .lock()
+                        .expect("Failed to lock database")
+                        .set_fx_rate(&rate)?,
+                    Err(e) => failures.push(format!("{}: {}", currency, e)),
+                }
+            }
+            Ok(failures)
+        },
+        move |s, result| match result {
+            Ok(failures) => {
+                if is_top_layer(s, "fx_rates") {
+                    s.pop_layer();
+                    show_fx_rates(s, db.clone());
+                }
+                if !failures.is_empty() {
+                    s.add_layer(Dialog::info(format!(
+                        "Some rates could not be fetched:\n{}",
+                        failures.join("\n")
+                    )));
+                }
+            }
+            Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+        },
+    );
+}
+
+// Prompts for a new account's name and currency.
+fn show_add_account(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let base_currency = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.base_currency.clone())
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_string());
+    siv.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child(
+                    "Name:",
+                    EditView::new()
+                        .with_name("account_name")
+                        .word_keys()
+                        .fixed_width(30),
+                )
+                .child(
+                    "Currency:",
+                    EditView::new()
+                        .content(base_currency)
+                        .with_name("account_currency")
+                        .word_keys()
+                        .fixed_width(30),
+                ),
+        )
+        .title("New Account")
+        .button("Save", move |s| {
+            let name = read_field(s, "account_name").unwrap_or_default();
+            let name = name.trim().to_string();
+            if name.is_empty() {
+                s.add_layer(Dialog::info("Name is required"));
+                return;
+            }
+            let currency =
+                match parse_currency(&read_field(s, "account_currency").unwrap_or_default()) {
+                    Ok(currency) => currency,
+                    Err(e) => {
+                        s.add_layer(Dialog::info(e));
+                        return;
+                    }
+                };
+            let res = db
+                .lock()
+                .expect("Failed to lock database")
+                .add_account(&Account {
+                    id: None,
+                    name,
+                    currency,
+                });
+            match res {
+                Ok(_) => {
+                    // This form and the stale list.
+                    s.pop_layer();
+                    s.pop_layer();
+                    show_accounts(s, db.clone());
+                }
+                Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// After changing an alert: closes the action dialog and rebuilds the list and
+// the banner, or shows the error.
+fn refresh_alerts_screen(s: &mut Cursive, db: &Arc<Mutex<Database>>, res: rusqlite::Result<()>) {
+    match res {
+        Ok(_) => {
+            s.pop_layer();
+            s.pop_layer();
+            update_alert_banner(s, db);
+            show_alerts(s, db.clone());
+        }
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Error: {}", e)));
+        }
+    }
+}
+
+// Form for a new alert; the threshold is a price or a P/L percent depending
+// on the kind.
+fn show_alert_form(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let mut kind_select = SelectView::<AlertKind>::new().popup();
+    for kind in AlertKind::variants() {
+        let label = match kind {
+            AlertKind::PriceBelow => "price below",
+            AlertKind::PriceAbove => "price above",
+            AlertKind::PlBelow => "P/L % below",
+            AlertKind::PlAbove => "P/L % above",
+        };
+        kind_select.add_item(label, *kind);
+    }
+    let form = ListView::new()
+        .child(
+            "Symbol or OCC:",
+            EditView::new()
+                .with_name("alert_instrument")
+                .word_keys()
+                .fixed_width(22),
+        )
+        .child("When:", kind_select.with_name("alert_kind").fixed_width(22))
+        .child(
+            "Threshold:",
+            EditView::new()
+                .with_name("alert_threshold")
+                .word_keys()
+                .fixed_width(22),
+        );
+    siv.add_layer(
+        Dialog::around(LinearLayout::vertical().child(form).child(TextView::new(
+            "\nPrices are per share; P/L is a percent of cost basis\n(e.g. -20).",
+        )))
+        .title("New Alert")
+        .button("Save", move |s| {
+            let instrument = read_field(s, "alert_instrument")
+                .unwrap_or_default()
+                .trim()
+                .to_uppercase();
+            if instrument.is_empty() {
+                s.add_layer(Dialog::info("Symbol is required"));
+                return;
+            }
+            let kind = match read_select::<AlertKind>(s, "alert_kind") {
+                Some(kind) => kind,
+                None => return,
+            };
+            let raw = read_field(s, "alert_threshold").unwrap_or_default();
+            let threshold = if kind.is_percent() {
+                match raw.trim().parse::<Decimal>() {
+                    Ok(threshold) => threshold,
+                    Err(_) => {
+                        s.add_layer(Dialog::info("Invalid threshold"));
+                        return;
+                    }
+                }
+            } else {
+                match parse_amount(s, raw.trim(), "threshold", false) {
+                    Some(threshold) => threshold,
+                    None => return,
+                }
+            };
+            let res = db
+                .lock()
+                .expect("Failed to lock database")
+                .add_alert(&Alert {
+                    id: None,
+                    instrument,
+                    kind,
+                    threshold,
+                    triggered_at: None,
+                    triggered_value: None,
+                });
+            match res {
+                Ok(_) => {
+                    s.pop_layer();
+                    s.pop_layer();
+                    show_alerts(s, db.clone());
+                    notify_alerts(s, &db);
+                }
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Error: {}", e)));
+                }
+            }
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Asks for a CSV or OFX file to import trades from, or offers to paste rows
+// instead.
+fn show_import(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let db_paste = db.clone();
+    siv.add_layer(
+        Dialog::around(
+            ListView::new().child(
+                "CSV or OFX file:",
+                EditView::new()
+                    .with_name("import_path")
+                    .word_keys()
+                    .fixed_width(50),
+            ),
+        )
+        .title("Import Trades")
+        .button("Next", move |s| {
+            let path = read_field(s, "import_path").unwrap_or_default();
+            let text = match std::fs::read_to_string(path.trim()) {
+                Ok(text) => text,
+                Err(e) => {
+                    s.add_layer(Dialog::info(format!("Cannot read {}: {}", path.trim(), e)));
+                    return;
+                }
+            };
+            if is_ofx(&text) {
+                let title = "OFX statement".to_string();
+                show_import_preview(s, db.clone(), title, read_ofx(&text), 2);
+                return;
+            }
+            import_records(s, db.clone(), parse_csv(&text), "file");
+        })
+        .button("Paste", move |s| {
+            s.pop_layer();
+            show_paste_import(s, db_paste.clone());
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// A box to paste rows copied from a broker web page or spreadsheet into,
+// tab- or comma-separated with a header row, read like an imported file.
+fn show_paste_import(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    siv.add_layer(
+        Dialog::around(
+            TextArea::new()
+                .with_name("import_paste")
+                .scrollable()
+                .fixed_size((78, 16)),
+        )
+        .title("Paste Trades")
+        .button("Next", move |s| {
+            let text = s
+                .call_on_name("import_paste", |v: &mut TextArea| {
+                    v.get_content().to_string()
+                })
+                .unwrap_or_default();
+            import_records(s, db.clone(), parse_pasted(&text), "pasted text");
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Previews the rows of a broker export as is, or asks how the columns map to
+// trade fields first. `source` names where the rows came from in errors.
+fn import_records(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    records: Vec<Vec<String>>,
+    source: &str,
+) {
+    if records.len() < 2 {
+        siv.add_layer(Dialog::info(format!(
+            "The {} has no rows under its header",
+            source
+        )));
+        return;
+    }
+    // A broker export needs no mapping.
+    match detect(&records) {
+        Some(format) => {
+            let title = format!("{} export", format.name());
+            show_import_preview(siv, db, title, format.import(&records), 2)
+        }
+        None => show_import_mapping(siv, db, Arc::new(records)),
+    }
+}
+
+// One dropdown per CSV column choosing the trade field it holds, preset to
+// the guessed mapping, next to the column's first value.
+fn show_import_mapping(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    records: Arc<Vec<Vec<String>>>,
+) {
+    let headers = &records[0];
+    let mut list = ListView::new();
+    for (column, (header, guess)) in headers.iter().zip(guess_mapping(headers)).enumerate() {
+        let mut field = SelectView::<Option<TradeField>>::new().popup();
+        field.add_item("skip", None);
+        for option in TradeField::variants() {
+            field.add_item(option.as_str(), Some(*option));
+        }
+        if let Some(guess) = guess {
+            let index = TradeField::variants().iter().position(|f| *f == guess);
+            field.set_selection(index.map_or(0, |i| i + 1));
+        }
+        let sample = records[1].get(column).cloned().unwrap_or_default();
+        list.add_child(
+            header,
+            LinearLayout::horizontal()
+                .child(
+                    field
+                        .with_name(format!("import_column_{}", column))
+                        .fixed_width(16),
+                )
+                .child(TextView::new(format!("e.g. {}", sample))),
+        );
+    }
+
+    let columns = headers.len();
+    siv.add_layer(
+        Dialog::around(list.scrollable().fixed_size((70, 16)))
+            .title("Map Columns")
+            .button("Preview", move |s| {
+                let mapping: Vec<Option<TradeField>> = (0..columns)
+                    .map(|column| read_select(s, &format!("import_column_{}", column)).flatten())
+                    .collect();
+                let read = read_trades(&records, &mapping);
+                show_import_preview(s, db.clone(), "Preview".to_string(), read, 3);
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// The trades read from a file and the rows that couldn't be, with an Import
+// button that saves the trades together and closes this and the `layers` - 1
+// import dialogs under it. Trades already saved are marked as duplicates and
+// skipped unless imported with Import All.
+fn show_import_preview(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    title: String,
+    read: ReadResult,
+    layers: usize,
+) {
+    let res = db.lock().expect("Failed to lock database").get_all_trades();
+    let duplicates = match res {
+        Ok(existing) => find_duplicates(&read.trades, &existing),
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    let mut table = Table::new(&[
+        "Date",
+        "Symbol",
+        "Type",
+        "Action",
+        "Option",
+        "Strike",
+        "Expiration",
+        "Quantity",
+        "Price",
+        "Fees",
+        "Duplicate",
+    ]);
+    for (trade, duplicate) in read.trades.iter().zip(&duplicates) {
+        table.row(&[
+            &trade.date,
+            &trade.symbol,
+            &trade.trade_type,
+            &trade.action,
+            &or_blank(trade.option_type),
+            &or_blank(trade.strike),
+            &or_blank(trade.expiration.as_ref()),
+            &trade.quantity,
+            &trade.price,
+            &trade.fees,
+            &or_blank(duplicate.map(|id| format!("of #{}", id))),
+        ]);
+    }
+    let mut content = if read.trades.is_empty() {
+        "No rows could be read.".to_string()
+    } else {
+        table.to_text()
+    };
+    if !read.strategies.is_empty() {
+        content.push_str(&format!(
+            "\n\n{} multi-leg order(s) become strategies:",
+            read.strategies.len()
+        ));
+        for (strategy, legs) in &read.strategies {
+            content.push_str(&format!("\n  {} ({} legs)", strategy.name, legs.len()));
+        }
+    }
+    let duplicate_count = duplicates.iter().flatten().count();
+    if duplicate_count > 0 {
+        content.push_str(&format!(
+            "\n\n{} trade(s) are already saved and will be skipped; Import All \
+             imports them again.",
+            duplicate_count
+        ));
+    }
+    if !read.errors.is_empty() {
+        content.push_str(&format!(
+            "\n\n{} row(s) will be skipped:",
+            read.errors.len()
+        ));
+        for (row, error) in &read.errors {
+            content.push_str(&format!("\n  row {}: {}", row, error));
+        }
+    }
+
+    let count = read.trades.len();
+    let skip: Vec<bool> = duplicates.iter().map(Option::is_some).collect();
+    let fresh = Arc::new(read.clone().without(&skip));
+    let read = Arc::new(read);
+    let db_all = db.clone();
+    let mut dialog = Dialog::around(TextView::new(content).scrollable().fixed_size((110, 20)))
+        .title(format!("{}: {} trade(s)", title, count))
+        .button("Import", move |s| {
+            import_read(s, &db, &fresh, duplicate_count, layers)
+        });
+    if duplicate_count > 0 {
+        dialog.add_button("Import All", move |s| {
+            import_read(s, &db_all, &read, 0, layers)
+        });
+    }
+    siv.add_layer(dialog.button("Back", |s| {
+        s.pop_layer();
+    }));
+}
+
+// Saves the trades of `read` and closes the `layers` import dialogs,
+// reporting `duplicates` left out besides the unreadable rows.
+fn import_read(
+    s: &mut Cursive,
+    db: &Arc<Mutex<Database>>,
+    read: &ReadResult,
+    duplicates: usize,
+    layers: usize,
+) {
+    if read.trades.is_empty() {
+        s.add_layer(Dialog::info("Nothing to import"));
+        return;
+    }
+    let (dir, keep) = s
+        .user_data::<UiState>()
+        .map(|state| (state.config.backup_dir.clone(), state.config.backup_keep))
+        .unwrap_or_default();
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        if let Err(e) = auto_snapshot(&db, &dir, "import", keep, unix_now()) {
+            drop(db);
+            s.add_layer(Dialog::info(format!(
+                "Not imported: the snapshot before importing failed: {}",
+                e
+            )));
+            return;
+        }
+        // Imported trades go in the account being viewed.
+        let trades: Vec<Trade> = read
+            .trades
+            .iter()
+            .map(|trade| Trade {
+                account_id: trade.account_id.or(db.account()),
+                ..trade.clone()
+            })
+            .collect();
+        db.add_trades_with_strategies(&trades, &read.strategies)
+    };
+    match res {
+        Ok(ids) => {
+            for _ in 0..layers {
+                s.pop_layer();
+            }
+            show_toast(
+                s,
+                format!(
+                    "Imported {} trade(s), skipped {} duplicate(s) and {} row(s)",
+                    ids.len(),
+                    duplicates,
+                    read.errors.len()
+                ),
+            );
+        }
+        Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+    }
+}
+
+// Every dividend entered, newest first; Enter on one offers to delete it.
+fn show_dividends(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_dividends(&DateRange::default());
+    let dividends = match res {
+        Ok(dividends) => dividends,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<Dividend>::new().h_align(HAlign::Left);
+    for dividend in dividends {
+        let row = format!(
+            "{:<10} {:<8} {:>12} {}",
+            dividend.date,
+            dividend.symbol,
+            format!("${:.2}", dividend.amount),
+            if dividend.qualified {
+                "qualified"
+            } else {
+                "ordinary"
+            }
+        );
+        select.add_item(row, dividend);
+    }
+    let db_select = db.clone();
+    select.set_on_submit(move |s, dividend: &Dividend| {
+        let Some(id) = dividend.id else {
+            return;
+        };
+        let db = db_select.clone();
+        s.add_layer(
+            Dialog::text(format!(
+                "Delete the ${:.2} {} dividend of {}?",
+                dividend.amount, dividend.symbol, dividend.date
+            ))
+            .button("Delete", move |s| {
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .delete_dividend(id);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_dividends(s, db.clone());
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+        );
+    });
+
+    let db_add = db.clone();
+    siv.add_layer(
+        Dialog::around(select.scrollable().fixed_size((50, 12)))
+            .title("Dividends")
+            .button("Add", move |s| show_dividend_form(s, db_add.clone()))
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Form for a dividend payment.
+fn show_dividend_form(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let mut qualified = SelectView::<bool>::new().popup();
+    qualified.add_item("qualified", true);
+    qualified.add_item("ordinary", false);
+    let form = ListView::new()
+        .child(
+            "Symbol:",
+            EditView::new()
+                .with_name("div_symbol")
+                .word_keys()
+                .fixed_width(15),
+        )
+        .child(
+            "Pay date (YYYY-MM-DD):",
+            date_field("div_date", today(), 15),
+        )
+        .child(
+            "Amount:",
+            EditView::new()
+                .with_name("div_amount")
+                .word_keys()
+                .fixed_width(15),
+        )
+        .child("Tax:", qualified.with_name("div_qualified").fixed_width(15));
+
+    siv.add_layer(
+        Dialog::around(form)
+            .title("New Dividend")
+            .button("Save", move |s| {
+                let symbol = read_field(s, "div_symbol")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_uppercase();
+                if symbol.is_empty() {
+                    s.add_layer(Dialog::info("Symbol is required"));
+                    return;
+                }
+                let date = read_field(s, "div_date")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                let Some(date) = parse_date_field(&date) else {
+                    s.add_layer(Dialog::info(
+                        "Invalid date format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+                    ));
+                    return;
+                };
+                let raw = read_field(s, "div_amount").unwrap_or_default();
+                let Some(amount) = parse_amount(s, raw.trim(), "amount", false) else {
+                    return;
+                };
+                let qualified = read_select::<bool>(s, "div_qualified").unwrap_or(false);
+                let res = {
+                    let db = db.lock().expect("Failed to lock database");
+                    db.add_dividend(&Dividend {
+                        id: None,
+                        symbol,
+                        date,
+                        amount,
+                        qualified,
+                        account_id: db.account(),
+                    })
+                };
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_dividends(s, db.clone());
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// The cash summary over the ledger of deposits, withdrawals, interest, and
+// fees, newest first; Enter on an entry offers to delete it.
+fn show_cash(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        let range = DateRange::default();
+        db.get_all_trades().and_then(|trades| {
+            let transactions = db.get_cash_transactions(&range)?;
+            let mut summary = cash_summary(
+                &trades,
+                &transactions,
+                &db.get_dividends(&range)?,
+                &open_positions(&trades, &today()),
+            );
+            summary.trading += archived_cash_flow(&db)?;
+            let twr = time_weighted_return(&db.get_snapshots()?, &transactions);
+            Ok((summary, twr, transactions))
+        })
+    };
+    let (summary, twr, transactions) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let line = |label: &str, amount: Decimal| format!("{:<24}${:>14.2}\n", label, amount);
+    let mut content = String::new();
+    content.push_str(&line("Deposits:", summary.deposits));
+    content.push_str(&line("Withdrawals:", -summary.withdrawals));
+    content.push_str(&line("Interest:", summary.interest));
+    content.push_str(&line("Fees:", -summary.fees));
+    content.push_str(&line("Dividends:", summary.dividends));
+    content.push_str(&line("Trading:", summary.trading));
+    content.push_str(&line("Balance:", summary.balance()));
+    content.push_str(&line("Held for short puts:", -summary.collateral));
+    content.push_str(&line("Available:", summary.available()));
+    content.push_str(&match twr {
+        Some(twr) => format!("{:<24}{:>14.2}%\n", "Time-weighted return:", twr),
+        None => "Time-weighted return:   needs two valuation snapshots\n".to_string(),
+    });
+
+    let mut select = SelectView::<CashTransaction>::new().h_align(HAlign::Left);
+    for transaction in transactions {
+        let row = format!(
+            "{:<10} {:<10} {:>12} {}",
+            transaction.date,
+            transaction.kind.to_string(),
+            format!("${:.2}", transaction.signed_amount()),
+            transaction.comment
+        );
+        select.add_item(row, transaction);
+    }
+    let db_select = db.clone();
+    select.set_on_submit(move |s, transaction: &CashTransaction| {
+        let Some(id) = transaction.id else {
+            return;
+        };
+        let db = db_select.clone();
+        s.add_layer(
+            Dialog::text(format!(
+                "Delete the ${:.2} {} of {}?",
+                transaction.amount, transaction.kind, transaction.date
+            ))
+            .button("Delete", move |s| {
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .delete_cash_transaction(id);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_cash(s, db.clone());
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+        );
+    });
+
+    let body = LinearLayout::vertical()
+        .child(TextView::new(content))
+        .child(TextView::new(" "))
+        .child(select.scrollable().fixed_size((60, 10)));
+    let db_add = db.clone();
+    siv.add_layer(
+        Dialog::around(body)
+            .title("Cash")
+            .button("Add", move |s| show_cash_form(s, db_add.clone()))
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Net cash flow of the current account's archived trades, which the cash
+// balance still counts.
+fn archived_cash_flow(db: &Database) -> rusqlite::Result<Decimal> {
+    Ok(db
+        .get_archive_summaries()?
+        .iter()
+        .map(|summary| summary.cash_flow)
+        .sum())
+}
+
+// Form for a deposit, withdrawal, interest payment, or fee.
+fn show_cash_form(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let mut kind = SelectView::<CashKind>::new().popup();
+    for k in CashKind::variants() {
+        kind.add_item(k.to_string(), *k);
+    }
+    let form = ListView::new()
+        .child("Date (YYYY-MM-DD):", date_field("cash_date", today(), 15))
+        .child("Kind:", kind.with_name("cash_kind").fixed_width(15))
+        .child(
+            "Amount:",
+            EditView::new()
+                .with_name("cash_amount")
+                .word_keys()
+                .fixed_width(15),
+        )
+        .child(
+            "Comment:",
+            EditView::new()
+                .with_name("cash_comment")
+                .word_keys()
+                .fixed_width(30),
+        );
+
+    siv.add_layer(
+        Dialog::around(form)
+            .title("New Cash Entry")
+            .button("Save", move |s| {
+                let date = read_field(s, "cash_date")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                let Some(date) = parse_date_field(&date) else {
+                    s.add_layer(Dialog::info(
+                        "Invalid date format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+                    ));
+                    return;
+                };
+                let raw = read_field(s, "cash_amount").unwrap_or_default();
+                let Some(amount) = parse_amount(s, raw.trim(), "amount", false) else {
+                    return;
+                };
+                let kind = read_select::<CashKind>(s, "cash_kind").unwrap_or(CashKind::Deposit);
+                let comment = read_field(s, "cash_comment")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                let res = {
+                    let db = db.lock().expect("Failed to lock database");
+                    db.add_cash_transaction(&CashTransaction {
+                        id: None,
+                        date,
+                        kind,
+                        amount,
+                        comment,
+                        account_id: db.account(),
+                    })
+                };
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_cash(s, db.clone());
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Splits and ticker changes applied so far, with buttons to apply new ones.
+fn show_corporate_actions(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let actions = match db
+        .lock()
+        .expect("Failed to lock database")
+        .get_corporate_actions()
+    {
+        Ok(actions) => actions,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let mut content = String::new();
+    if actions.is_empty() {
+        content.push_str("No corporate actions applied yet.");
+    }
+    for action in actions {
+        let detail = match action.kind {
+            CorporateActionKind::Split => format!(
+                "split {}:1",
+                action.ratio.map(|r| r.normalize()).unwrap_or_default()
+            ),
+            CorporateActionKind::SymbolChange => format!(
+                "renamed to {}",
+                action.new_symbol.clone().unwrap_or_default()
+            ),
+        };
+        content.push_str(&format!(
+            "{:<10} {:<8} {}\n",
+            action.date, action.symbol, detail
+        ));
+    }
+
+    let db_split = db.clone();
+    let db_rename = db.clone();
+    siv.add_layer(
+        Dialog::around(TextView::new(content).scrollable())
+            .title("Corporate Actions")
+            .button("Apply Split", move |s| {
+                show_corporate_action_form(s, db_split.clone(), CorporateActionKind::Split);
+            })
+            .button("Symbol Change", move |s| {
+                show_corporate_action_form(s, db_rename.clone(), CorporateActionKind::SymbolChange);
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Form for a new split or symbol change. Applying it rewrites every earlier
+// trade in the symbol.
+fn show_corporate_action_form(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    kind: CorporateActionKind,
+) {
+    let (title, detail_label) = match kind {
+        CorporateActionKind::Split => ("Apply Split", "Ratio (e.g. 4:1, 3:2, 1:10):"),
+        CorporateActionKind::SymbolChange => ("Symbol Change", "New symbol:"),
+    };
+    let form = ListView::new()
+        .child(
+            "Symbol:",
+            EditView::new()
+                .with_name("ca_symbol")
+                .word_keys()
+                .fixed_width(15),
+        )
+        .child(
+            "Effective date (YYYY-MM-DD):",
+            date_field("ca_date", today(), 15),
+        )
+        .child(
+            detail_label,
+            EditView::new()
+                .with_name("ca_detail")
+                .word_keys()
+                .fixed_width(15),
+        );
+    let help = TextView::new("Trades in the symbol dated before the effective date are rewritten.");
+
+    siv.add_layer(
+        Dialog::around(LinearLayout::vertical().child(help).child(form))
+            .title(title)
+            .button("Apply", move |s| {
+                let symbol = read_field(s, "ca_symbol")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_uppercase();
+                let date = read_field(s, "ca_date")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                let detail = read_field(s, "ca_detail")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_uppercase();
+                if symbol.is_empty() {
+                    s.add_layer(Dialog::info("Symbol is required"));
+                    return;
+                }
+                let Some(date) = parse_date_field(&date) else {
+                    s.add_layer(Dialog::info(
+                        "Invalid date format. Use YYYY-MM-DD or a shortcut like t, y, or -3d",
+                    ));
+                    return;
+                };
+                let (ratio, new_symbol) = match kind {
+                    CorporateActionKind::Split => match parse_split_ratio(&detail) {
+                        Some(ratio) => (Some(ratio), None),
+                        None => {
+                            s.add_layer(Dialog::info(
+                                "Invalid ratio. Use new:old, e.g. 4:1 or 3:2",
+                            ));
+                            return;
+                        }
+                    },
+                    CorporateActionKind::SymbolChange => {
+                        if detail.is_empty() {
+                            s.add_layer(Dialog::info("New symbol is required"));
+                            return;
+                        }
+                        (None, Some(detail))
+                    }
+                };
+                let action = CorporateAction {
+                    id: None,
+                    symbol,
+                    kind,
+                    date,
+                    ratio,
+                    new_symbol,
+                };
+                let res = db
+                    .lock()
+                    .expect("Failed to lock database")
+                    .apply_corporate_action(&action);
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_corporate_actions(s, db.clone());
+                    }
+                    Err(e) => {
+                        s.add_layer(Dialog::info(format!("Error: {}", e)));
+                    }
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Parses a split ratio written `new:old` (or a bare `new` for `new:1`) into new
+// shares per old share.
+fn parse_split_ratio(raw: &str) -> Option<Decimal> {
+    let (new, old) = raw.split_once(':').unwrap_or((raw, "1"));
+    let new: Decimal = new.trim().parse().ok()?;
+    let old: Decimal = old.trim().parse().ok()?;
+    if new <= Decimal::ZERO || old <= Decimal::ZERO {
+        return None;
+    }
+    Some(new / old)
+}
+
+fn show_reports(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let archived_through = db
+        .lock()
+        .expect("Failed to lock database")
+        .archived_through()
+        .unwrap_or_default();
+    if let Some(state) = siv.user_data::<UiState>() {
+        state.archived_through = archived_through;
+    }
+    let (db_range, db_workbook, db_txf) = (db.clone(), db.clone(), db.clone());
+    let mut select = SelectView::new().h_align(HAlign::Center);
+
+    select.add_item("P/L by Symbol", 1);
+    select.add_item("Wheel Cycles", 2);
+    select.add_item("Wash Sales", 3);
+    select.add_item("Portfolio Valuation", 4);
+    select.add_item("Price Chart", 5);
+    select.add_item("P/L by Period", 6);
+    select.add_item("P/L by Strategy", 7);
+    select.add_item("Trade Statistics", 8);
+    select.add_item("Premium Income", 9);
+    select.add_item("Fees & Commissions", 10);
+    select.add_item("Equity Curve", 11);
+    select.add_item("Dividend Income", 12);
+    select.add_item("P/L Calendar", 13);
+    select.add_item("Benchmark Comparison", 14);
+    select.add_item("Concentration", 15);
+    select.add_item("Option Outcomes", 16);
+    select.add_item("Custom Reports", 17);
+    select.add_item("Currency Conversion", 18);
+
+    select.set_on_submit(move |s, item: &i32| match item {
+        1 => show_symbol_report(s, db.clone()),
+        2 => show_wheel_report(s, db.clone()),
+        3 => show_wash_sale_report(s, db.clone()),
+        4 => show_valuation_report(s, db.clone()),
+        5 => show_price_chart_prompt(s, db.clone()),
+        6 => show_period_report(s, db.clone(), Period::default()),
+        7 => show_strategy_report(s, db.clone()),
+        8 => show_statistics_report(s, db.clone()),
+        9 => show_premium_report(s, db.clone()),
+        10 => show_fee_report(s, db.clone()),
+        11 => show_equity_curve(s, db.clone(), ChartRange::default()),
+        12 => show_dividend_report(s, db.clone()),
+        13 => show_pl_calendar(s, db.clone()),
+        14 => show_benchmark(s, db.clone()),
+        15 => show_concentration_report(s, db.clone()),
+        16 => show_outcome_report(s, db.clone()),
+        17 => show_custom_reports(s, db.clone()),
+        18 => show_currency_report(s, db.clone()),
+        _ => {}
+    });
+
+    let range = report_range(siv);
+    let title = report_title(siv, "Reports", &range);
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(select.scrollable().fixed_size((40, 17)))
+                .child(TextView::new(
+                    "Press x in a report to export it to CSV,\n\
+                     Workbook for an Excel workbook, or\n\
+                     TurboTax for the realized gains as TXF.",
+                )),
+        )
+        .title(title)
+        .button("Workbook", move |s| export_workbook(s, &db_workbook))
+        .button("TurboTax", move |s| export_txf(s, &db_txf))
+        .button("Date Range", move |s| {
+            let db = db_range.clone();
+            show_date_range_picker(s, range.clone(), move |s, range| {
+                if let Some(state) = s.user_data::<UiState>() {
+                    state.report_range = range;
+                }
+                // Reopen the menu so its title shows the new range.
+                s.pop_layer();
+                show_reports(s, db.clone());
+            });
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Wraps a report so pressing x writes `table`, the report's figures, to a CSV
+// file named after `name` in the configured export directory.
+fn exportable<V: View>(view: V, name: String, table: Table) -> OnEventView<NamedView<V>> {
+    OnEventView::new(view.with_name("exportable"))
+        .on_event('x', move |s| export_table(s, &table, &name))
+}
+
+// Writes `table` to the export directory and says where.
+fn export_table(s: &mut Cursive, table: &Table, name: &str) {
+    let dir = s
+        .user_data::<UiState>()
+        .map(|state| state.config.export_dir.clone())
+        .unwrap_or_default();
+    match write_csv(table, &dir, name, unix_now()) {
+        Ok(path) => s.add_layer(Dialog::info(format!("Exported to {}", path.display()))),
+        Err(e) => s.add_layer(Dialog::info(format!("Export failed: {}", e))),
+    }
+}
+
+// Writes the trades in the report range, the open positions, and P/L by
+// month to an Excel workbook in the export directory.
+fn export_workbook(s: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let Some(state) = s.user_data::<UiState>() else {
+        return;
+    };
+    let (method, range) = (state.cost_basis_method, state.report_range.clone());
+    let dir = state.config.export_dir.clone();
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_all_trades().and_then(|all| {
+            Ok(report_workbook(
+                &db.get_trades_in(&range)?,
+                &open_positions(&all, &today()),
+                &db.get_quotes()?,
+                &db.get_period_report(method, Period::Month, &range)?,
+            ))
+        })
+    };
+    let sheets = match res {
+        Ok(sheets) => sheets,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    match write_xlsx(
+        &sheets,
+        &dir,
+        &report_title(s, "Report Workbook", &range),
+        unix_now(),
+    ) {
+        Ok(path) => s.add_layer(Dialog::info(format!("Exported to {}", path.display()))),
+        Err(e) => s.add_layer(Dialog::info(format!("Export failed: {}", e))),
+    }
+}
+
+// Writes the lots closed in the report range to a TXF file TurboTax imports.
+fn export_txf(s: &mut Cursive, db: &Arc<Mutex<Database>>) {
+    let Some(state) = s.user_data::<UiState>() else {
+        return;
+    };
+    let (method, range) = (state.cost_basis_method, state.report_range.clone());
+    let dir = state.config.export_dir.clone();
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_realized_lots(method, &range);
+    let lots = match res {
+        Ok(lots) => lots,
+        Err(e) => {
+            s.add_layer(Dialog::info(format!("Database error: {}", e)));
+            return;
+        }
+    };
+    let name = report_title(s, "Realized Gains", &range);
+    match write_file(&txf(&lots, &today()), &dir, &name, "txf", unix_now()) {
+        Ok(path) => s.add_layer(Dialog::info(format!(
+            "Exported {} sale(s) to {}",
+            lots.len(),
+            path.display()
+        ))),
+        Err(e) => s.add_layer(Dialog::info(format!("Export failed: {}", e))),
+    }
+}
+
+// The dates reports are limited to.
+fn report_range(siv: &mut Cursive) -> DateRange {
+    siv.user_data::<UiState>()
+        .map(|state| state.report_range.clone())
+        .unwrap_or_default()
+}
+
+// A report title with its date range, unless it covers all dates.
+fn ranged(title: impl Into<String>, range: &DateRange) -> String {
+    let title = title.into();
+    if range.is_all() {
+        title
+    } else {
+        format!("{} — {}", title, range.label())
+    }
+}
+
+// The title of a report of trades: `ranged`, with a note when the range
+// reaches into archived years, whose trades it leaves out.
+fn report_title(siv: &mut Cursive, title: impl Into<String>, range: &DateRange) -> String {
+    let title = ranged(title, range);
+    let archived = siv
+        .user_data::<UiState>()
+        .and_then(|state| state.archived_through);
+    match archive_note(archived, range) {
+        Some(note) => format!("{} ({})", title, note),
+        None => title,
+    }
+}
+
+// Start and end date inputs, filled in by picking a preset or typed by hand
+// (blank for no limit). Apply checks the dates and passes the range to
+// `on_apply` after closing the picker.
+fn show_date_range_picker<F>(siv: &mut Cursive, current: DateRange, on_apply: F)
+where
+    F: Fn(&mut Cursive, DateRange) + Send + Sync + 'static,
+{
+    let mut presets = SelectView::new().popup();
+    presets.add_item("Custom", None);
+    for preset in RangePreset::variants() {
+        presets.add_item(preset.as_str(), Some(*preset));
+    }
+    presets.set_on_submit(|s, preset: &Option<RangePreset>| {
+        let Some(preset) = preset else {
+            return;
+        };
+        let range = preset.range(&today());
+        for (name, date) in [("range_start", range.start), ("range_end", range.end)] {
+            s.call_on_name(name, |view: &mut EditView| {
+                view.set_content(date.unwrap_or_default());
+            });
+        }
+    });
+
+    siv.add_layer(
+        Dialog::around(
+            ListView::new()
+                .child("Preset:", presets)
+                .child(
+                    "Start (YYYY-MM-DD):",
+                    date_field("range_start", current.start.unwrap_or_default(), 12),
+                )
+                .child(
+                    "End (YYYY-MM-DD):",
+                    date_field("range_end", current.end.unwrap_or_default(), 12),
+                ),
+        )
+        .title("Report Date Range")
+        .button("Apply", move |s| {
+            let mut dates = Vec::new();
+            for name in ["range_start", "range_end"] {
+                let text = s
+                    .call_on_name(name, |view: &mut EditView| view.get_content())
+                    .map(|content| content.trim().to_string())
+                    .unwrap_or_default();
+                if text.is_empty() {
+                    dates.push(None);
+                } else if let Some(date) = parse_date_field(&text) {
+                    dates.push(Some(date));
+                } else {
+                    s.add_layer(Dialog::info(format!("Invalid date: {}", text)));
+                    return;
+                }
+            }
+            let range = DateRange {
+                end: dates.pop().flatten(),
+                start: dates.pop().flatten(),
+            };
+            if let (Some(start), Some(end)) = (&range.start, &range.end) {
+                if start > end {
+                    s.add_layer(Dialog::info("Start date is after the end date"));
+                    return;
+                }
+            }
+            s.pop_layer();
+            on_apply(s, range);
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Open positions at their marks, the account value, and its change since the
+// previous snapshot. Viewing records today's snapshot, building the equity
+// curve listed below the totals.
+fn show_valuation_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let db_job = db.clone();
+    run_in_background(
+        siv,
+        "Valuing portfolio",
+        move || {
+            let now = today();
+            let db = db_job.lock().expect("Failed to lock database");
+            account_valuation(&db, &now).and_then(|valuation| {
+                db.save_snapshot(&valuation.snapshot())?;
+                let mut instruments: Vec<String> = valuation
+                    .positions
+                    .iter()
+                    .map(|p| p.position.key.instrument())
+                    .collect();
+                instruments.dedup();
+                Ok((instruments, valuation, db.get_snapshots()?))
+            })
+        },
+        move |s, result| match result {
+            Ok((instruments, valuation, snapshots)) => {
+                add_valuation_layer(s, db, instruments, valuation, snapshots)
+            }
+            Err(e) => show_dialog_with_back(s, format!("Database error: {}", e)),
+        },
+    );
+}
+
+// The account valued today: every trade at its mark, plus the cash ledger and
+// the cash flow of archived trades.
+fn account_valuation(db: &Database, now: &str) -> rusqlite::Result<Valuation> {
+    let mut valuation = value_portfolio(&db.get_all_trades()?, &db.get_quotes()?, now);
+    let range = DateRange::default();
+    valuation.cash += archived_cash_flow(db)?;
+    valuation.cash += cash_summary(
+        &[],
+        &db.get_cash_transactions(&range)?,
+        &db.get_dividends(&range)?,
+        &[],
+    )
+    .ledger();
+    Ok(valuation)
+}
+
+// Lays out a valuation computed by `show_valuation_report`.
+fn add_valuation_layer(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    instruments: Vec<String>,
+    valuation: Valuation,
+    snapshots: Vec<ValuationSnapshot>,
+) {
+    let now = valuation.date.clone();
+
+    let mut content = format!(
+        "{:<8} {:<28} {:>10} {:>10} {:>14}\n{}\n",
+        "Symbol",
+        "Instrument",
+        "Position",
+        "Mark",
+        "Market Value",
+        "=".repeat(74)
+    );
+    let mut table = Table::new(&["Symbol", "Instrument", "Position", "Mark", "Market Value"]);
+    for value in &valuation.positions {
+        table.row(&[
+            &value.position.key.symbol,
+            &value.position.key.instrument(),
+            &value.position.quantity,
+            &or_blank(value.mark),
+            &value.market_value,
+        ]);
+        content.push_str(&format!(
+            "{:<8} {:<28} {:>10} {:>10} {:>14}\n",
+            value.position.key.symbol,
+            format_instrument(&value.position.key),
+            format_position(value.position.quantity),
+            value
+                .mark
+                .map(|m| format!("${:.2}", m))
+                .unwrap_or_else(|| "at cost".to_string()),
+            format!("${:.2}", value.market_value),
+        ));
+    }
+    content.push_str(&format!(
+        "\nMarket value:   ${:>14.2}\nCash:           ${:>14.2}\nAccount value:  ${:>14.2}\n",
+        valuation.market_value,
+        valuation.cash,
+        valuation.account_value()
+    ));
+    match day_change(&snapshots, &now, valuation.account_value()) {
+        Some((change, percent)) => content.push_str(&format!(
+            "Day change:     ${:>14.2}{}\n",
+            change,
+            percent
+                .map(|p| format!(" ({:+.2}%)", p))
+                .unwrap_or_default()
+        )),
+        None => content.push_str("Day change:     no earlier snapshot\n"),
+    }
+    if valuation.unpriced() > 0 {
+        content.push_str(&format!(
+            "\n{} position(s) have no mark and are carried at cost.\n",
+            valuation.unpriced()
+        ));
+    }
+    content.push_str(
+        "Cash is every trade's net cash flow plus deposits, withdrawals, interest,\nfees, and dividends (see Cash on the main menu).\n",
+    );
+
+    content.push_str(&format!(
+        "\nEquity curve\n{:<10} {:>16} {:>14}\n{}\n",
+        "Date",
+        "Account Value",
+        "Change",
+        "-".repeat(42)
+    ));
+    let mut previous: Option<Decimal> = None;
+    for snapshot in &snapshots {
+        content.push_str(&format!(
+            "{:<10} {:>16} {:>14}\n",
+            snapshot.date,
+            format!("${:.2}", snapshot.account_value),
+            previous
+                .map(|p| format!("${:.2}", snapshot.account_value - p))
+                .unwrap_or_default(),
+        ));
+        previous = Some(snapshot.account_value);
+    }
+
+    let title = format!("Portfolio Valuation ({})", now);
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Refresh Quotes", move |s| {
+                refresh_quotes_in_background(
+                    s,
+                    db.clone(),
+                    instruments.clone(),
+                    "valuation",
+                    show_valuation_report,
+                );
+            })
+            .button("Back", |s| {
+                s.pop_layer();
+            })
+            .with_name("valuation"),
+        title,
+        table,
+    ));
+}
+
+// Loss sales repurchased within the wash-sale window, with the loss that can't
+// be deducted and the replacement shares' adjusted basis.
+fn show_wash_sale_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let washes = match db
+        .lock()
+        .expect("Failed to lock database")
+        .get_wash_sales(method, &range)
+    {
+        Ok(washes) => washes,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    if washes.is_empty() {
+        show_dialog_with_back(siv, "No wash sales found".to_string());
+        return;
+    }
+
+    let mut content = String::new();
+    content.push_str(&format!(
+        "{:<6} {:<6} {:<8} {:<10} {:>8} {:>12} {:>12} {:<6} {:<10} {:>11}\n",
+        "Sale",
+        "Lot",
+        "Symbol",
+        "Sold",
+        "Qty",
+        "Loss",
+        "Disallowed",
+        "Repl",
+        "Bought",
+        "Adj. Basis"
+    ));
+    content.push_str(&"=".repeat(98));
+    content.push('\n');
+
+    let mut table = Table::new(&[
+        "Sale",
+        "Lot",
+        "Symbol",
+        "Instrument",
+        "Sold",
+        "Quantity",
+        "Loss",
+        "Disallowed",
+        "Replacement",
+        "Bought",
+        "Adjusted Basis",
+    ]);
+    let mut total_disallowed = Decimal::ZERO;
+    for wash in washes {
+        total_disallowed += wash.disallowed;
+        table.row(&[
+            &or_blank(wash.close_id),
+            &or_blank(wash.open_id),
+            &wash.key.symbol,
+            &wash.key.instrument(),
+            &wash.close_date,
+            &wash.quantity,
+            &wash.loss,
+            &wash.disallowed,
+            &or_blank(wash.replacement_id),
+            &wash.replacement_date,
+            &wash.adjusted_basis,
+        ]);
+        content.push_str(&format!(
+            "{:<6} {:<6} {:<8} {:<10} {:>8} {:>12} {:>12} {:<6} {:<10} {:>11}\n",
+            format!("#{}", wash.close_id.unwrap_or(0)),
+            format!("#{}", wash.open_id.unwrap_or(0)),
+            wash.key.symbol,
+            wash.close_date,
+            wash.quantity.normalize(),
+            format!("${:.2}", wash.loss),
+            format!("${:.2}", wash.disallowed),
+            format!("#{}", wash.replacement_id.unwrap_or(0)),
+            wash.replacement_date,
+            format!("${:.2}", wash.adjusted_basis),
+        ));
+    }
+    content.push_str(&format!(
+        "\nTotal disallowed loss: ${:.2} (lots matched by {})",
+        total_disallowed,
+        method.as_str().to_uppercase()
+    ));
+
+    let title = report_title(siv, "Wash Sales", &range);
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+// Follows each wheel cycle: cash-secured puts → assignment → covered calls →
+// called away.
+fn show_wheel_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_wheel_cycles(&range);
+    let cycles = match res {
+        Ok(cycles) => cycles,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if cycles.is_empty() {
+        show_dialog_with_back(
+            siv,
+            "No wheel cycles found (a cycle starts with a sold put)".to_string(),
+        );
+        return;
+    }
+
+    let mut content = String::new();
+    content.push_str(&format!(
+        "{:<8} {:<10} {:<10} {:>4} {:>5} {:>12} {:>10} {:>12} {:<7}\n",
+        "Symbol", "Start", "End", "Puts", "Calls", "Premium", "Cost/Sh", "Return", "Status"
+    ));
+    content.push_str(&"=".repeat(86));
+    content.push('\n');
+
+    let mut table = Table::new(&[
+        "Symbol",
+        "Start",
+        "End",
+        "Puts Sold",
+        "Calls Sold",
+        "Premium",
+        "Cost per Share",
+        "Return",
+        "Status",
+    ]);
+    for cycle in cycles {
+        table.row(&[
+            &cycle.symbol,
+            &cycle.start_date,
+            &or_blank(cycle.end_date.as_ref()),
+            &cycle.puts_sold,
+            &cycle.calls_sold,
+            &cycle.premium_collected,
+            &or_blank(cycle.effective_cost_basis),
+            &cycle.total_return,
+            &if cycle.is_complete() {
+                "done"
+            } else {
+                "running"
+            },
+        ]);
+        content.push_str(&format!(
+            "{:<8} {:<10} {:<10} {:>4} {:>5} {:>12} {:>10} {:>12} {:<7}\n",
+            cycle.symbol,
+            cycle.start_date,
+            cycle.end_date.clone().unwrap_or_else(|| "-".to_string()),
+            cycle.puts_sold,
+            cycle.calls_sold,
+            format!("${:.2}", cycle.premium_collected),
+            cycle
+                .effective_cost_basis
+                .map(|b| format!("${:.2}", b))
+                .unwrap_or_else(|| "-".to_string()),
+            format!("${:.2}", cycle.total_return),
+            if cycle.is_complete() {
+                "done"
+            } else {
+                "running"
+            },
+        ));
+    }
+    content.push_str(
+        "\nReturn is realized once a cycle is done; a running cycle still carries\n\
+         the cost of the shares it holds.",
+    );
+
+    let title = report_title(siv, "Wheel Cycles", &range);
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+const BAR_CHART_WIDTH: usize = 60;
+
+// Realized P/L, fees, and trade counts per month, quarter, or year, as a
+// table and a bar chart. The m, q, and y keys switch the period.
+fn show_period_report(siv: &mut Cursive, db: Arc<Mutex<Database>>, period: Period) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_period_report(method, period, &range);
+    let reports = match res {
+        Ok(reports) => reports,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if reports.is_empty() {
+        show_dialog_with_back(siv, "No trades found".to_string());
+        return;
+    }
+
+    let mut content = format!(
+        "{:<10} {:>14} {:>10} {:>7}\n{}\n",
+        "Period",
+        "Realized P/L",
+        "Fees",
+        "Trades",
+        "=".repeat(44)
+    );
+    let table = periods_table(&reports);
+    for report in &reports {
+        content.push_str(&format!(
+            "{:<10} {:>14} {:>10} {:>7}\n",
+            report.period,
+            format!("${:.2}", report.realized),
+            format!("${:.2}", report.fees),
+            report.trade_count,
+        ));
+    }
+    content.push_str(&format!(
+        "{}\n{:<10} {:>14} {:>10} {:>7}\n\n",
+        "-".repeat(44),
+        "Total",
+        format!(
+            "${:.2}",
+            reports.iter().map(|r| r.realized).sum::<Decimal>()
+        ),
+        format!("${:.2}", reports.iter().map(|r| r.fees).sum::<Decimal>()),
+        reports.iter().map(|r| r.trade_count).sum::<usize>(),
+    ));
+    let bars: Vec<(String, Decimal)> = reports
+        .iter()
+        .map(|r| (r.period.clone(), r.realized))
+        .collect();
+    content.push_str(&render_bar_chart(&bars, BAR_CHART_WIDTH));
+    content.push_str(
+        "\n\nRealized P/L is counted on the close date. \
+         Press m, q, or y for months, quarters, or years.",
+    );
+
+    let title = report_title(
+        siv,
+        format!(
+            "P/L by {} ({})",
+            period.as_str(),
+            method.as_str().to_uppercase()
+        ),
+        &range,
+    );
+    let mut view = exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    );
+    for (key, period) in [
+        ('m', Period::Month),
+        ('q', Period::Quarter),
+        ('y', Period::Year),
+    ] {
+        let db = db.clone();
+        view.set_on_event(key, move |s| {
+            s.pop_layer();
+            show_period_report(s, db.clone(), period);
+        });
+    }
+    siv.add_layer(view);
+}
+
+// Realized and unrealized P/L, win rate, and average holding period per kind
+// of play: each named strategy kind, covered calls, cash-secured puts, and so
+// on.
+fn show_strategy_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_strategy_report(method, &range);
+    let reports = match res {
+        Ok(reports) => reports,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if reports.is_empty() {
+        show_dialog_with_back(siv, "No trades found".to_string());
+        return;
+    }
+
+    let mut content = format!(
+        "{:<18} {:>6} {:>7} {:>14} {:>14} {:>8} {:>9}\n{}\n",
+        "Strategy",
+        "Plays",
+        "Closed",
+        "Realized P/L",
+        "Unrealized",
+        "Win %",
+        "Avg Days",
+        "=".repeat(82)
+    );
+    let mut table = Table::new(&[
+        "Strategy",
+        "Plays",
+        "Closed",
+        "Realized P/L",
+        "Unrealized P/L",
+        "Win %",
+        "Average Days",
+    ]);
+    for report in &reports {
+        table.row(&[
+            &report.strategy_type.label(),
+            &report.plays,
+            &report.closed,
+            &report.realized,
+            &or_blank(report.unrealized),
+            &or_blank(report.win_rate()),
+            &or_blank(report.average_days),
+        ]);
+        content.push_str(&format!(
+            "{:<18} {:>6} {:>7} {:>14} {:>14} {:>8} {:>9}\n",
+            report.strategy_type.label(),
+            report.plays,
+            report.closed,
+            format!("${:.2}", report.realized),
+            report
+                .unrealized
+                .map(|u| format!("${:.2}", u))
+                .unwrap_or_else(|| "no mark".to_string()),
+            report
+                .win_rate()
+                .map(|w| format!("{:.1}%", w))
+                .unwrap_or_else(|| "-".to_string()),
+            report
+                .average_days
+                .map(|d| format!("{:.1}", d))
+                .unwrap_or_else(|| "-".to_string()),
+        ));
+    }
+    content.push_str(
+        "\nA play is a named strategy, or a lone opening trade with whatever closed \
+         it.\nClosed plays give the win rate and the days from first open to last \
+         close.",
+    );
+
+    let title = report_title(
+        siv,
+        format!("P/L by Strategy ({})", method.as_str().to_uppercase()),
+        &range,
+    );
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+// Win rate, average and largest win and loss, profit factor, and holding
+// period over every fully closed position.
+fn show_statistics_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_closed_positions(method, &range)
+            .and_then(|closed| Ok((closed, db.get_equity_curve(method, &range)?)))
+    };
+    let (closed, curve) = match res {
+        Ok(result) => result,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if closed.is_empty() {
+        show_dialog_with_back(siv, "No closed positions yet".to_string());
+        return;
+    }
+
+    let stats = trade_statistics(&closed);
+    let risk = risk_metrics(&curve);
+    let amount = |value: Option<Decimal>| {
+        value
+            .map(|v| format!("${:.2}", v))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let position = |position: &Option<ClosedPosition>| match position {
+        Some(p) => format!(
+            "${:.2}  {} {}",
+            p.realized,
+            p.key.symbol,
+            format_instrument(&p.key)
+        ),
+        None => "-".to_string(),
+    };
+    let content = format!(
+        "Closed positions:  {}\n\
+         Wins / losses:     {} / {}\n\
+         Win rate:          {}\n\
+         Net realized P/L:  ${:.2}\n\n\
+         Average win:       {}\n\
+         Average loss:      {}\n\
+         Profit factor:     {}\n\
+         Largest win:       {}\n\
+         Largest loss:      {}\n\
+         Average held:      {}\n\n\
+         Max drawdown:      {}\n\
+         Monthly P/L:       {} average, {} std. dev. over {} months\n\
+         Sharpe-style:      {}\n\n\
+         A closed position is an opening trade with all of its lots closed.\n\
+         Profit factor is gross profit over gross loss. Drawdown is the largest\n\
+         fall in cumulative realized P/L; the Sharpe-style ratio is average\n\
+         monthly P/L over its std. dev., annualized, with no risk-free rate.",
+        stats.count,
+        stats.wins,
+        stats.losses,
+        stats
+            .win_rate()
+            .map(|w| format!("{:.1}%", w))
+            .unwrap_or_else(|| "-".to_string()),
+        stats.net(),
+        amount(stats.average_win()),
+        amount(stats.average_loss()),
+        stats
+            .profit_factor()
+            .map(|f| format!("{:.2}", f))
+            .unwrap_or_else(|| "no losses".to_string()),
+        position(&stats.largest_win),
+        position(&stats.largest_loss),
+        stats
+            .average_days
+            .map(|d| format!("{:.1} days", d))
+            .unwrap_or_else(|| "-".to_string()),
+        match (&risk.drawdown_peak, &risk.drawdown_trough) {
+            (Some(peak), Some(trough)) => {
+                format!("${:.2}  {} to {}", risk.max_drawdown, peak, trough)
+            }
+            (None, Some(trough)) => format!("${:.2}  to {}", risk.max_drawdown, trough),
+            _ => "none".to_string(),
+        },
+        amount(risk.average_month()),
+        amount(risk.monthly_std_dev()),
+        risk.monthly.len(),
+        risk.sharpe()
+            .map(|r| format!("{:.2}", r))
+            .unwrap_or_else(|| "-".to_string()),
+    );
+
+    let table = statistics_table(&stats, &risk);
+    let title = report_title(
+        siv,
+        format!("Trade Statistics ({})", method.as_str().to_uppercase()),
+        &range,
+    );
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content))
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+// Premium from writing options per month and symbol, net of buybacks and
+// fees, with each month's running total for its year.
+fn show_premium_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_trades_in(&range);
+    let trades = match res {
+        Ok(trades) => trades,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    let months = premium_income(&trades);
+    if months.is_empty() {
+        show_dialog_with_back(siv, "No options sold yet".to_string());
+        return;
+    }
+
+    let mut content = format!(
+        "{:<8} {:<8} {:>12} {:>12} {:>10} {:>12} {:>12}\n{}\n",
+        "Month",
+        "Symbol",
+        "Collected",
+        "Bought Back",
+        "Fees",
+        "Net",
+        "YTD",
+        "=".repeat(80)
+    );
+    for month in &months {
+        for row in &month.rows {
+            content.push_str(&format!(
+                "{:<8} {:<8} {:>12} {:>12} {:>10} {:>12}\n",
+                month.month,
+                row.symbol,
+                format!("${:.2}", row.collected),
+                format!("${:.2}", row.bought_back),
+                format!("${:.2}", row.fees),
+                format!("${:.2}", row.net()),
+            ));
+        }
+        content.push_str(&format!(
+            "{:<8} {:<8} {:>12} {:>12} {:>10} {:>12} {:>12}\n{}\n",
+            month.month,
+            "total",
+            "",
+            "",
+            "",
+            format!("${:.2}", month.net()),
+            format!("${:.2}", month.year_to_date),
+            "-".repeat(80)
+        ));
+    }
+    content.push_str(
+        "\nOnly options sold to open and bought back to close count; long options\n\
+         and stock gains are left out. YTD restarts each January.",
+    );
+
+    let title = report_title(siv, "Premium Income", &range);
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        premium_table(&months),
+    ));
+}
+
+// Fees paid by month and by symbol, and the share of gross realized P/L
+// they took.
+fn show_fee_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_fee_report(method, &range);
+    let report = match res {
+        Ok(report) => report,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if report.total.trade_count == 0 {
+        show_dialog_with_back(siv, "No trades yet".to_string());
+        return;
+    }
+
+    let row = |group: &FeeGroup| {
+        format!(
+            "{:<10} {:>10} {:>12} {:>12} {:>8} {:>7}\n",
+            group.name,
+            format!("${:.2}", group.fees),
+            format!("${:.2}", group.realized),
+            format!("${:.2}", group.gross()),
+            group
+                .fee_percent()
+                .map(|p| format!("{:.1}%", p))
+                .unwrap_or_else(|| "-".to_string()),
+            group.trade_count,
+        )
+    };
+    let table = |heading: &str, groups: &[FeeGroup]| {
+        let mut table = format!(
+            "{:<10} {:>10} {:>12} {:>12} {:>8} {:>7}\n{}\n",
+            heading,
+            "Fees",
+            "Realized",
+            "Gross",
+            "Fee %",
+            "Trades",
+            "=".repeat(64)
+        );
+        for group in groups {
+            table.push_str(&row(group));
+        }
+        table.push_str(&format!("{}\n{}", "-".repeat(64), row(&report.total)));
+        table
+    };
+    let content = format!(
+        "{}\n{}\n{}\n\
+         Fees are counted in the month of the trade; realized P/L in the month\n\
+         its lot closed, and with the broker that closed it. Fee % is the fees\n\
+         inside realized P/L over the P/L before them, so fees on lots still\n\
+         open are paid but not yet in it.",
+        table("Month", &report.by_month),
+        table("Symbol", &report.by_symbol),
+        table("Broker", &report.by_broker),
+    );
+
+    let title = report_title(
+        siv,
+        format!("Fees & Commissions ({})", method.as_str().to_uppercase()),
+        &range,
+    );
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        fees_table(&report),
+    ));
+}
+
+// Trades per currency with their cash flow converted into the base currency
+// at each trade's date.
+fn show_currency_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let base = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.base_currency.clone())
+        .unwrap_or_else(|| DEFAULT_CURRENCY.to_string());
+    let range = report_range(siv);
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_trades_in(&range)
+            .and_then(|trades| Ok((trades, db.get_fx_rates()?)))
+    };
+    let (trades, rates) = match res {
+        Ok(res) => res,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if trades.is_empty() {
+        show_dialog_with_back(siv, "No trades yet".to_string());
+        return;
+    }
+
+    let totals = currency_totals(&trades, &FxRates::new(&rates), &base);
+    let mut content = format!(
+        "{:<8} {:>7} {:>14} {:>12} {:>16}\n{}\n",
+        "Currency",
+        "Trades",
+        "Cash Flow",
+        "Fees",
+        format!("In {}", base),
+        "=".repeat(61)
+    );
+    let mut export = Table::new(&["Currency", "Trades", "Cash Flow", "Fees", "Converted"]);
+    for total in &totals {
+        content.push_str(&format!(
+            "{:<8} {:>7} {:>14.2} {:>12.2} {:>16}\n",
+            total.currency,
+            total.trades,
+            total.cash_flow,
+            total.fees,
+            total
+                .converted
+                .map(|c| format!("{:.2}", c))
+                .unwrap_or_else(|| "no rate".to_string()),
+        ));
+        export.row(&[
+            &total.currency,
+            &total.trades,
+            &total.cash_flow,
+            &total.fees,
+            &or_blank(total.converted.map(|c| c.round_dp(2))),
+        ]);
+    }
+    match totals.iter().map(|t| t.converted).sum::<Option<Decimal>>() {
+        Some(total) => content.push_str(&format!(
+            "{}\n{:<44} {:>16.2}\n",
+            "-".repeat(61),
+            format!("Total in {}", base),
+            total
+        )),
+        None => {
+            let missing: Vec<&str> = totals
+                .iter()
+                .filter(|t| t.converted.is_none())
+                .map(|t| t.currency.as_str())
+                .collect();
+            content.push_str(&format!(
+                "\nNo total: add {} to {} rates under Settings > FX Rates.\n",
+                missing.join(", "),
+                base
+            ));
+        }
+    }
+    content.push_str(
+        "\nCash flow is in each trade's own currency. Converted, each trade is\n\
+         taken at the latest rate on or before its date.",
+    );
+
+    let title = report_title(siv, format!("Currency Conversion ({})", base), &range);
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        export,
+    ));
+}
+
+// How sold options ended, by symbol and by the delta they were sold at.
+fn show_outcome_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_option_outcomes(method, &range);
+    let report = match res {
+        Ok(report) => report,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if report.total.contracts().is_zero() {
+        show_dialog_with_back(siv, "No sold options have closed yet".to_string());
+        return;
+    }
+
+    let row = |group: &OutcomeGroup| {
+        let outcome = |outcome: Outcome| {
+            format!(
+                "{} ({:.0}%)",
+                group.count(outcome).normalize(),
+                group.percent(outcome).unwrap_or_default()
+            )
+        };
+        format!(
+            "{:<10} {:>9} {:>12} {:>12} {:>12} {:>12}\n",
+            group.name,
+            group.contracts().normalize(),
+            outcome(Outcome::Expired),
+            outcome(Outcome::Assigned),
+            outcome(Outcome::BoughtBack),
+            format!("${:.2}", group.realized),
+        )
+    };
+    let table = |heading: &str, groups: &[OutcomeGroup]| {
+        let mut table = format!(
+            "{:<10} {:>9} {:>12} {:>12} {:>12} {:>12}\n{}\n",
+            heading,
+            "Contracts",
+            "Expired",
+            "Assigned",
+            "Bought Back",
+            "Realized",
+            "=".repeat(72)
+        );
+        for group in groups {
+            table.push_str(&row(group));
+        }
+        table.push_str(&format!("{}\n{}", "-".repeat(72), row(&report.total)));
+        table
+    };
+    let content = format!(
+        "{}\n{}\n\
+         Contracts sold to open, counted when they closed. Rolls count as bought\n\
+         back. Delta is the absolute delta entered on the sale.",
+        table("Symbol", &report.by_symbol),
+        table("Delta", &report.by_delta),
+    );
+
+    let mut export = Table::new(&[
+        "Group",
+        "Name",
+        "Contracts",
+        "Expired",
+        "Assigned",
+        "Bought Back",
+        "Realized",
+    ]);
+    let grouped = [("symbol", &report.by_symbol), ("delta", &report.by_delta)];
+    for (kind, groups) in grouped {
+        for group in groups.iter().chain([&report.total]) {
+            export.row(&[
+                &kind,
+                &group.name,
+                &group.contracts(),
+                &group.expired,
+                &group.assigned,
+                &group.bought_back,
+                &group.realized,
+            ]);
+        }
+    }
+    let title = report_title(
+        siv,
+        format!("Option Outcomes ({})", method.as_str().to_uppercase()),
+        &range,
+    );
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        export,
+    ));
+}
+
+// Saved custom reports: Enter (or Run) runs the highlighted one.
+fn show_custom_reports(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_saved_reports();
+    let reports = match res {
+        Ok(reports) => reports,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let mut select = SelectView::<SavedReport>::new().h_align(HAlign::Left);
+    for report in reports {
+        let row = format!(
+            "{:<20} {:<12} {}",
+            report.name,
+            format!("by {}", report.group_by),
+            if report.filter.is_empty() {
+                "all trades"
+            } else {
+                &report.filter
+            }
+        );
+        select.add_item(row, report);
+    }
+    let db_run = db.clone();
+    select
+        .set_on_submit(move |s, report: &SavedReport| run_custom_report(s, db_run.clone(), report));
+
+    let selected = |s: &mut Cursive| {
+        s.call_on_name("saved_reports", |v: &mut SelectView<SavedReport>| {
+            v.selection()
+        })
+        .flatten()
+    };
+    let (db_run, db_new, db_edit, db_delete) = (db.clone(), db.clone(), db.clone(), db);
+    siv.add_layer(
+        Dialog::around(
+            select
+                .with_name("saved_reports")
+                .scrollable()
+                .fixed_size((70, 12)),
+        )
+        .title("Custom Reports")
+        .button("Run", move |s| {
+            if let Some(report) = selected(s) {
+                run_custom_report(s, db_run.clone(), &report);
+            }
+        })
+        .button("New", move |s| {
+            show_custom_report_form(s, db_new.clone(), None)
+        })
+        .button("Edit", move |s| {
+            if let Some(report) = selected(s) {
+                show_custom_report_form(s, db_edit.clone(), Some((*report).clone()));
+            }
+        })
+        .button("Delete", move |s| {
+            let Some(id) = selected(s).and_then(|report| report.id) else {
+                return;
+            };
+            let res = db_delete
+                .lock()
+                .expect("Failed to lock database")
+                .delete_saved_report(id);
+            match res {
+                Ok(_) => {
+                    s.pop_layer();
+                    show_custom_reports(s, db_delete.clone());
+                }
+                Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+            }
+        })
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+// Runs a saved report over the trades in the report date range.
+fn run_custom_report(siv: &mut Cursive, db: Arc<Mutex<Database>>, report: &SavedReport) {
+    let filter: Filter = match report.filter.parse() {
+        Ok(filter) => filter,
+        Err(e) => {
+            siv.add_layer(Dialog::info(format!("Invalid filter: {}", e)));
+            return;
+        }
+    };
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_trades_in(&range);
+    let trades = match res {
+        Ok(trades) => trades,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    let table = run_report(&trades, &filter, report.group_by);
+    let content = if table.rows.is_empty() {
+        "No trades match.".to_string()
+    } else {
+        format!("{}\n\n{} row(s).", table.to_text(), table.rows.len())
+    };
+    let title = report_title(siv, report.name.clone(), &range);
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+// Form for a new custom report, or for editing `existing`.
+fn show_custom_report_form(
+    siv: &mut Cursive,
+    db: Arc<Mutex<Database>>,
+    existing: Option<SavedReport>,
+) {
+    let mut group_by = SelectView::<GroupBy>::new().popup();
+    for option in GroupBy::variants() {
+        group_by.add_item(option.as_str(), *option);
+    }
+    if let Some(report) = &existing {
+        let index = GroupBy::variants()
+            .iter()
+            .position(|g| *g == report.group_by)
+            .unwrap_or(0);
+        group_by.set_selection(index);
+    }
+    let fields = Field::variants()
+        .iter()
+        .map(Field::as_str)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let form = LinearLayout::vertical()
+        .child(
+            ListView::new()
+                .child(
+                    "Name:",
+                    EditView::new()
+                        .content(
+                            existing
+                                .as_ref()
+                                .map(|r| r.name.clone())
+                                .unwrap_or_default(),
+                        )
+                        .with_name("custom_name")
+                        .word_keys()
+                        .fixed_width(30),
+                )
+                .child(
+                    "Filter:",
+                    EditView::new()
+                        .content(
+                            existing
+                                .as_ref()
+                                .map(|r| r.filter.clone())
+                                .unwrap_or_default(),
+                        )
+                        .with_name("custom_filter")
+                        .word_keys()
+                        .fixed_width(60),
+                )
+                .child("Group by:", group_by.with_name("custom_group_by")),
+        )
+        .child(TextView::new(format!(
+            "\nConditions are \"field op value\" joined by \"and\", e.g.\n\
+             option_type = put and date >= 2024-01-01\n\
+             Operators: = != < <= > >= ~ (contains). Leave empty for all trades.\n\
+             Fields: {}",
+            fields
+        )));
+
+    let id = existing.as_ref().and_then(|r| r.id);
+    siv.add_layer(
+        Dialog::around(form.fixed_width(80))
+            .title(if id.is_some() {
+                "Edit Custom Report"
+            } else {
+                "New Custom Report"
+            })
+            .button("Save", move |s| {
+                let name = read_field(s, "custom_name")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                if name.is_empty() {
+                    s.add_layer(Dialog::info("Name is required"));
+                    return;
+                }
+                let filter = read_field(s, "custom_filter")
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+                if let Err(e) = filter.parse::<Filter>() {
+                    s.add_layer(Dialog::info(format!("Invalid filter: {}", e)));
+                    return;
+                }
+                let report = SavedReport {
+                    id,
+                    name,
+                    filter,
+                    group_by: read_select::<GroupBy>(s, "custom_group_by").unwrap_or_default(),
+                };
+                let res = {
+                    let db = db.lock().expect("Failed to lock database");
+                    match id {
+                        Some(_) => db.update_saved_report(&report),
+                        None => db.add_saved_report(&report).map(|_| ()),
+                    }
+                };
+                match res {
+                    Ok(_) => {
+                        s.pop_layer();
+                        s.pop_layer();
+                        show_custom_reports(s, db.clone());
+                    }
+                    Err(e) => s.add_layer(Dialog::info(format!("Error: {}", e))),
+                }
+            })
+            .button("Cancel", |s| {
+                s.pop_layer();
+            }),
+    );
+}
+
+// Dividends received by symbol and by month, qualified and ordinary apart.
+fn show_dividend_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_dividends(&range);
+    let dividends = match res {
+        Ok(dividends) => dividends,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if dividends.is_empty() {
+        show_dialog_with_back(
+            siv,
+            "No dividends entered yet (add them under Dividends)".to_string(),
+        );
+        return;
+    }
+
+    let report = dividend_income(&dividends);
+    let row = |group: &DividendGroup| {
+        format!(
+            "{:<10} {:>12} {:>12} {:>12} {:>9}\n",
+            group.name,
+            format!("${:.2}", group.total),
+            format!("${:.2}", group.qualified),
+            format!("${:.2}", group.ordinary()),
+            group.payments,
+        )
+    };
+    let table = |heading: &str, groups: &[DividendGroup]| {
+        let mut table = format!(
+            "{:<10} {:>12} {:>12} {:>12} {:>9}\n{}\n",
+            heading,
+            "Total",
+            "Qualified",
+            "Ordinary",
+            "Payments",
+            "=".repeat(59)
+        );
+        for group in groups {
+            table.push_str(&row(group));
+        }
+        table.push_str(&format!("{}\n{}", "-".repeat(59), row(&report.total)));
+        table
+    };
+    let content = format!(
+        "{}\n{}",
+        table("Symbol", &report.by_symbol),
+        table("Month", &report.by_month)
+    );
+
+    let mut export = Table::new(&[
+        "Group",
+        "Name",
+        "Total",
+        "Qualified",
+        "Ordinary",
+        "Payments",
+    ]);
+    let grouped = [("symbol", &report.by_symbol), ("month", &report.by_month)];
+    for (kind, groups) in grouped {
+        for group in groups.iter().chain([&report.total]) {
+            export.row(&[
+                &kind,
+                &group.name,
+                &group.total,
+                &group.qualified,
+                &group.ordinary(),
+                &group.payments,
+            ]);
+        }
+    }
+    let title = ranged("Dividend Income", &range);
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        export,
+    ));
+}
+
+// Cumulative realized P/L over the chosen range, zoomed in and out with + and
+// -.
+fn show_equity_curve(siv: &mut Cursive, db: Arc<Mutex<Database>>, zoom: ChartRange) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_equity_curve(method, &range);
+    let curve = match res {
+        Ok(curve) => curve,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    if curve.is_empty() {
+        show_dialog_with_back(siv, "No closed lots yet".to_string());
+        return;
+    }
+
+    let shown = curve_since(&curve, zoom.start(&today()).as_deref());
+    let mut content = if shown.is_empty() {
+        format!("Nothing closed in the last {}.", zoom.as_str())
+    } else {
+        render_line_chart(&shown, CHART_WIDTH, CHART_HEIGHT)
+    };
+    if let Some((date, total)) = curve.last() {
+        content.push_str(&format!(
+            "\n\nRealized P/L to date: ${:.2} (last close {}).\n\
+             Zoom: {}. Press + to zoom in, - to zoom out.",
+            total,
+            date,
+            zoom.as_str()
+        ));
+    }
+
+    let mut table = Table::new(&["Date", "Cumulative P/L"]);
+    for (date, total) in &shown {
+        table.row(&[date, total]);
+    }
+    let title = report_title(
+        siv,
+        format!("Equity Curve ({})", method.as_str().to_uppercase()),
+        &range,
+    );
+    let mut view = exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    );
+    for (key, zoomed) in [('+', zoom.zoom_in()), ('-', zoom.zoom_out())] {
+        let db = db.clone();
+        view.set_on_event(key, move |s| {
+            s.pop_layer();
+            show_equity_curve(s, db.clone(), zoomed);
+        });
+    }
+    siv.add_layer(view);
+}
+
+// Days shaded by realized P/L, a block per calendar year, newest first:
+// green for gains and red for losses, darker glyphs for bigger days.
+fn show_pl_calendar(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let colors = colors(siv);
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_daily_pl(method, &range);
+    let daily = match res {
+        Ok(daily) => daily,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    let (Some((first, _)), Some((last, _))) = (daily.first_key_value(), daily.last_key_value())
+    else {
+        show_dialog_with_back(siv, "No closed lots yet".to_string());
+        return;
+    };
+
+    // Days after today haven't happened, so they stay blank.
+    let now = today();
+    let shown = DateRange {
+        start: range.start.clone(),
+        end: Some(range.end.clone().unwrap_or(now.clone()).min(now)),
+    };
+    let largest = daily.values().map(|pl| pl.abs()).max().unwrap_or_default();
+    let year = |date: &str| date[..4].parse::<i64>().unwrap_or_default();
+    let mut content = StyledString::new();
+    for year in (year(first)..=year(last)).rev() {
+        let weeks = year_weeks(&daily, year, &shown);
+        let total: Decimal = daily
+            .iter()
+            .filter(|(date, _)| date.starts_with(&year.to_string()))
+            .map(|(_, pl)| *pl)
+            .sum();
+        content.append_plain(format!(
+            "{}  realized ${:.2}\n    {}\n",
+            year,
+            total,
+            month_header(&weeks, 2)
+        ));
+        for (weekday, name) in WEEKDAYS.iter().enumerate() {
+            content.append_plain(format!("{} ", name));
+            for week in &weeks {
+                match &week[weekday] {
+                    None => content.append_plain("  "),
+                    Some(day) => match day.pl {
+                        None => content.append_plain("· "),
+                        Some(pl) => {
+                            let glyph = ["□", "░", "▒", "▓", "█"][intensity(pl, largest) as usize];
+                            content.append_styled(glyph, colors.pl(pl));
+                            content.append_plain(" ");
+                        }
+                    },
+                }
+            }
+            content.append_plain("\n");
+        }
+        content.append_plain("\n");
+    }
+
+    content.append_plain("Gains are green, losses red; ░ ▒ ▓ █ grow with the day's size\n");
+    content.append_plain(format!(
+        "up to the biggest day (${:.2}). · is a day with nothing closed.\n\n\
+         Realized P/L by weekday:\n",
+        largest
+    ));
+    for (name, total) in WEEKDAYS.iter().zip(weekday_totals(&daily)) {
+        content.append_plain(format!("  {} {:>12}\n", name, format!("${:.2}", total)));
+    }
+
+    let mut table = Table::new(&["Date", "Realized P/L"]);
+    for (date, pl) in &daily {
+        table.row(&[date, pl]);
+    }
+    let title = report_title(
+        siv,
+        format!("P/L Calendar ({})", method.as_str().to_uppercase()),
+        &range,
+    );
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+// Monthly returns of realized P/L on the configured capital against buying
+// and holding the benchmark symbol, whose daily bars are topped up in the
+// background first, as for the price chart.
+fn show_benchmark(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let (symbol, capital) = siv
+        .user_data::<UiState>()
+        .map(|state| (state.config.benchmark_symbol.clone(), state.config.capital))
+        .unwrap_or_default();
+    let Some(capital) = capital else {
+        show_dialog_with_back(
+            siv,
+            format!(
+                "Set your account capital to compare returns, e.g.\n\n\
+                 [benchmark]\ncapital = 25000\n\nin {}.",
+                CONFIG_FILE
+            ),
+        );
+        return;
+    };
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let provider = market_data(siv);
+    let job_range = range.clone();
+    run_in_background(
+        siv,
+        &format!("Loading {} history", symbol),
+        move || -> rusqlite::Result<_> {
+            let error = top_up_history(&db, &provider, &symbol)?;
+            let db = db.lock().expect("Failed to lock database");
+            Ok((
+                db.get_daily_pl(method, &job_range)?,
+                db.get_price_bars(&symbol)?,
+                error,
+            ))
+        },
+        move |s, result| match result {
+            Ok((daily, bars, error)) => {
+                add_benchmark_layer(s, method, range, capital, daily, bars, error)
+            }
+            Err(e) => s.add_layer(Dialog::info(format!("Database error: {}", e))),
+        },
+    );
+}
+
+fn add_benchmark_layer(
+    siv: &mut Cursive,
+    method: CostBasisMethod,
+    range: DateRange,
+    capital: Decimal,
+    daily: BTreeMap<String, Decimal>,
+    bars: Vec<PriceBar>,
+    error: Option<String>,
+) {
+    let symbol = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.benchmark_symbol.clone())
+        .unwrap_or_default();
+    let start = range
+        .start
+        .as_deref()
+        .or(daily.keys().next().map(String::as_str))
+        .map(|date| Period::Month.label(date));
+    let Some(start) = start else {
+        show_dialog_with_back(siv, "No closed lots yet".to_string());
+        return;
+    };
+    let end = range.end.as_deref().map(|date| Period::Month.label(date));
+    let months = compare(&daily, &bars, capital, &start, end.as_deref());
+    if months.is_empty() {
+        show_dialog_with_back(
+            siv,
+            format!(
+                "No {} closes stored for {} onward{}",
+                symbol,
+                start,
+                error
+                    .map(|e| format!("; download failed: {}", e))
+                    .unwrap_or_default()
+            ),
+        );
+        return;
+    }
+
+    let points: Vec<(String, Decimal, Decimal)> = months
+        .iter()
+        .map(|m| (m.month.clone(), m.portfolio_total, m.benchmark_total))
+        .collect();
+    let mut content = render_comparison_chart(&points, CHART_WIDTH, CHART_HEIGHT);
+    content.push_str(&format!(
+        "\n\n• portfolio  ○ {} buy-and-hold  ◉ both (cumulative return %)\n\n\
+         {:<8} {:>11} {:>11} {:>11} {:>11}\n{}\n",
+        symbol,
+        "Month",
+        "Portfolio",
+        symbol,
+        "Difference",
+        "Cumulative",
+        "=".repeat(56)
+    ));
+    let mut table = Table::new(&[
+        "Month",
+        "Portfolio %",
+        "Benchmark %",
+        "Portfolio Total %",
+        "Benchmark Total %",
+    ]);
+    for month in &months {
+        content.push_str(&format!(
+            "{:<8} {:>11} {:>11} {:>11} {:>11}\n",
+            month.month,
+            format!("{:.2}%", month.portfolio),
+            format!("{:.2}%", month.benchmark),
+            format!("{:+.2}%", month.portfolio - month.benchmark),
+            format!("{:+.2}%", month.portfolio_total - month.benchmark_total),
+        ));
+        table.row(&[
+            &month.month,
+            &month.portfolio.round_dp(4),
+            &month.benchmark.round_dp(4),
+            &month.portfolio_total.round_dp(4),
+            &month.benchmark_total.round_dp(4),
+        ]);
+    }
+    content.push_str(&format!(
+        "\nPortfolio returns are realized P/L over ${:.2} of capital; open\n\
+         positions don't count until they close.\n",
+        capital
+    ));
+    if let Some(e) = error {
+        content.push_str(&format!(
+            "Could not download new {} bars ({}); using stored history.\n",
+            symbol, e
+        ));
+    }
+
+    let title = report_title(
+        siv,
+        format!(
+            "Benchmark vs {} ({})",
+            symbol,
+            method.as_str().to_uppercase()
+        ),
+        &range,
+    );
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+// Share of capital tied up in each underlying, at cost and at market value,
+// with underlyings over the configured threshold in red.
+fn show_concentration_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let colors = colors(siv);
+    let threshold = siv
+        .user_data::<UiState>()
+        .map(|state| state.config.concentration_threshold)
+        .unwrap_or_default();
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_all_trades()
+            .and_then(|trades| Ok(value_portfolio(&trades, &db.get_quotes()?, &today())))
+    };
+    let valuation = match res {
+        Ok(valuation) => valuation,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+    let exposures = concentration(&valuation.positions);
+    if exposures.is_empty() {
+        show_dialog_with_back(siv, "No open positions".to_string());
+        return;
+    }
+
+    let mut content = StyledString::plain(format!(
+        "{:<8} {:>14} {:>8} {:>14} {:>8} {:>14}\n{}\n",
+        "Symbol",
+        "Cost",
+        "Cost %",
+        "Market",
+        "Market %",
+        "Put Collateral",
+        "=".repeat(71)
+    ));
+    let mut table = Table::new(&[
+        "Symbol",
+        "Cost",
+        "Cost %",
+        "Market Value",
+        "Market %",
+        "Put Collateral",
+    ]);
+    for exposure in &exposures {
+        let row = format!(
+            "{:<8} {:>14} {:>8} {:>14} {:>8} {:>14}\n",
+            exposure.symbol,
+            format!("${:.2}", exposure.cost),
+            format!("{:.1}%", exposure.cost_percent),
+            format!("${:.2}", exposure.market),
+            format!("{:.1}%", exposure.market_percent),
+            format!("${:.2}", exposure.collateral),
+        );
+        if exposure.exceeds(threshold) {
+            content.append_styled(row, colors.loss);
+        } else {
+            content.append_plain(row);
+        }
+        table.row(&[
+            &exposure.symbol,
+            &exposure.cost,
+            &exposure.cost_percent.round_dp(2),
+            &exposure.market,
+            &exposure.market_percent.round_dp(2),
+            &exposure.collateral,
+        ]);
+    }
+    let total_cost: Decimal = exposures.iter().map(|e| e.cost).sum();
+    let total_market: Decimal = exposures.iter().map(|e| e.market).sum();
+    content.append_plain(format!(
+        "{}\n{:<8} {:>14} {:>8} {:>14}\n",
+        "-".repeat(71),
+        "Total",
+        format!("${:.2}", total_cost),
+        "",
+        format!("${:.2}", total_market),
+    ));
+
+    let over = exposures.iter().filter(|e| e.exceeds(threshold)).count();
+    if over > 0 {
+        content.append_styled(
+            format!(
+                "\n{} underlying(s) over {}% of capital.\n",
+                over,
+                threshold.normalize()
+            ),
+            colors.loss,
+        );
+    }
+    content.append_plain(format!(
+        "\nShort puts count the cash to buy the shares (strike x shares); short\n\
+         calls count nothing extra. Positions without a mark are valued at cost.\n\
+         Flagged above {}% (set threshold under [concentration]).",
+        threshold.normalize()
+    ));
+
+    let title = "Concentration".to_string();
+    siv.add_layer(exportable(
+        Dialog::around(TextView::new(content).scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    ));
+}
+
+const CHART_WIDTH: usize = 100;
+const CHART_HEIGHT: usize = 20;
+
+fn show_price_chart_prompt(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    siv.add_layer(
+        Dialog::around(
+            ListView::new().child(
+                "Symbol:",
+                EditView::new()
+                    .on_submit({
+                        let db = db.clone();
+                        move |s, _| submit_price_chart(s, db.clone())
+                    })
+                    .with_name("chart_symbol")
+                    .word_keys()
+                    .fixed_width(12),
+            ),
+        )
+        .title("Price Chart")
+        .button("Show", move |s| submit_price_chart(s, db.clone()))
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
+    );
+}
+
+fn submit_price_chart(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let symbol = read_field(siv, "chart_symbol")
+        .unwrap_or_default()
+        .trim()
+        .to_uppercase();
+    if symbol.is_empty() {
+        siv.add_layer(Dialog::info("Symbol is required"));
+        return;
+    }
+    siv.pop_layer();
+    show_price_chart(siv, db, symbol);
+}
+
+// Tops up the symbol's stored daily bars in the background, then charts them
+// with the symbol's trades marked. When the download fails the stored bars
+// are charted anyway, with the error noted.
+fn show_price_chart(siv: &mut Cursive, db: Arc<Mutex<Database>>, symbol: String) {
+    let provider = market_data(siv);
+    run_in_background(
+        siv,
+        &format!("Loading {} history", symbol),
+        move || -> rusqlite::Result<(Vec<PriceBar>, Vec<Trade>, Option<String>)> {
+            let error = top_up_history(&db, &provider, &symbol)?;
+            let db = db.lock().expect("Failed to lock database");
+            let trades: Vec<Trade> = db
+                .get_all_trades()?
+                .into_iter()
+                .filter(|t| t.symbol == symbol)
+                .collect();
+            Ok((db.get_price_bars(&symbol)?, trades, error))
+        },
+        |s, result| match result {
+            Ok((bars, trades, error)) => {
+                let window = ChartWindow::latest(bars.len(), CHART_WIDTH);
+                add_price_chart_layer(
+                    s,
+                    PriceChart {
+                        bars: Arc::new(bars),
+                        trades: Arc::new(trades),
+                        error,
+                        window,
+                        candles: true,
+                    },
+                )
             }
+            Err(e) => s.add_layer(Dialog::info(format!("Database error: {}", e))),
+        },
+    );
+}
+
+// Downloads the symbol's daily bars since the last stored one and stores
+// them, returning the download error, if any, for the caller to note. Runs in
+// a background job.
+fn top_up_history(
+    db: &Mutex<Database>,
+    provider: &Fallback,
+    symbol: &str,
+) -> rusqlite::Result<Option<String>> {
+    let stored = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_price_bars(symbol)?;
+    match fetch_history(provider, symbol, &stored, &today()) {
+        Ok(bars) => {
+            db.lock()
+                .expect("Failed to lock database")
+                .save_price_bars(&bars)?;
+            Ok(None)
         }
-    });
+        Err(e) => Ok(Some(e)),
+    }
+}
 
-    dialog = dialog.button("Cancel", |s| {
-        s.pop_layer();
-    });
+// A symbol's bars and trades on the Price Chart screen, and which of the bars
+// are shown and how. Its keys redraw it with another window.
+#[derive(Clone)]
+struct PriceChart {
+    bars: Arc<Vec<PriceBar>>,
+    trades: Arc<Vec<Trade>>,
+    error: Option<String>,
+    window: ChartWindow,
+    candles: bool,
+}
 
-    siv.add_layer(dialog);
+fn add_price_chart_layer(siv: &mut Cursive, chart: PriceChart) {
+    let bars = &chart.bars;
+    let Some(last) = bars.last() else {
+        siv.add_layer(Dialog::info(format!(
+            "No price history stored{}",
+            chart
+                .error
+                .as_ref()
+                .map(|e| format!("; download failed: {}", e))
+                .unwrap_or_default()
+        )));
+        return;
+    };
+    let window = chart.window;
+    let shown = &bars[window.range()];
+    let title = format!("{} Daily Bars", last.symbol);
+    let mut content = if chart.candles {
+        render_candlestick_chart(shown, &chart.trades, CHART_WIDTH, CHART_HEIGHT)
+    } else {
+        render_price_chart(shown, &chart.trades, CHART_WIDTH, CHART_HEIGHT)
+    };
+    content.push_str(&format!(
+        "\n\nLast close ${:.2} on {}. {} of {} days shown. B = buy, S = sell, X = both.\n\
+         Left/Right pan, +/- zoom, Home/End jump to either end, c switches to {}.\n",
+        last.close,
+        last.date,
+        shown.len(),
+        bars.len(),
+        if chart.candles { "closes" } else { "candles" }
+    ));
+    if let Some(e) = &chart.error {
+        content.push_str(&format!(
+            "Could not download new bars ({}); showing stored history.\n",
+            e
+        ));
+    }
+    let mut table = Table::new(&["Date", "Open", "High", "Low", "Close", "Volume"]);
+    for bar in bars.iter() {
+        table.row(&[
+            &bar.date,
+            &bar.open,
+            &bar.high,
+            &bar.low,
+            &bar.close,
+            &bar.volume,
+        ]);
+    }
+    let mut view = exportable(
+        Dialog::around(TextView::new(content).with_name("price_chart").scrollable())
+            .title(title.clone())
+            .button("Back", |s| {
+                s.pop_layer();
+            }),
+        title,
+        table,
+    );
+    let len = bars.len();
+    let step = (window.span / 4).max(1) as isize;
+    let redraws = [
+        (Event::Char('+'), window.zoom_in(), chart.candles),
+        (Event::Char('-'), window.zoom_out(len), chart.candles),
+        (Event::Key(Key::Left), window.pan(-step, len), chart.candles),
+        (Event::Key(Key::Right), window.pan(step, len), chart.candles),
+        (
+            Event::Key(Key::Home),
+            window.pan(isize::MIN, len),
+            chart.candles,
+        ),
+        (
+            Event::Key(Key::End),
+            window.pan(isize::MAX, len),
+            chart.candles,
+        ),
+        (Event::Char('c'), window, !chart.candles),
+    ];
+    // Before the dialog, whose buttons would take the arrows.
+    for (event, window, candles) in redraws {
+        let chart = PriceChart {
+            window,
+            candles,
+            ..chart.clone()
+        };
+        view.set_on_pre_event(event, move |s| {
+            s.pop_layer();
+            add_price_chart_layer(s, chart.clone());
+        });
+    }
+    siv.add_layer(view);
 }
 
-fn show_reports(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
-    let reports = match db
-        .lock()
-        .expect("Failed to lock database")
-        .get_report_by_symbol()
-    {
-        Ok(reports) => reports,
+// Months of P/L trend drawn beside each symbol of the P/L by Symbol report.
+const SPARKLINE_MONTHS: usize = 12;
+
+fn show_symbol_report(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    let method = siv
+        .user_data::<UiState>()
+        .map(|state| state.cost_basis_method)
+        .unwrap_or_default();
+    let range = report_range(siv);
+    let result = {
+        let db = db.lock().expect("Failed to lock database");
+        db.get_report_by_symbol(method, &range).and_then(|reports| {
+            Ok((
+                reports,
+                db.get_roll_chains()?,
+                db.get_covered_positions()?,
+                db.get_realized_lots(method, &range)?,
+            ))
+        })
+    };
+    let (reports, roll_chains, covered, lots) = match result {
+        Ok(result) => result,
         Err(e) => {
             show_dialog_with_back(siv, format!("Database error: {}", e));
             return;
@@ -607,39 +8736,306 @@ fn show_reports(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
         return;
     }
 
-    let mut content = String::new();
-    content.push_str(&format!(
-        "{:<8} {:>14} {:>7} {:>14} {:>12}\n",
-        "Symbol", "Profit/Loss", "Trades", "Net Position", "Break-Even"
-    ));
-    content.push_str(&"=".repeat(60));
-    content.push('\n');
+    let header = format!(
+        "{:<8} {:>14} {:>14} {:>7} {:>14} {:>12}  {}\n{}",
+        "Symbol",
+        "Realized P/L",
+        "Unrealized P/L",
+        "Trades",
+        "Net Position",
+        "Break-Even",
+        "Trend",
+        "=".repeat(77 + SPARKLINE_MONTHS)
+    );
+    // Each symbol's realized P/L running total by month, drawn beside it.
+    let trends = cumulative_by_symbol(&lots, Period::Month);
+    let colors = colors(siv);
 
+    // Each symbol row drills down into the trades behind it.
+    let mut rows = SelectView::new();
+    let table = symbols_table(&reports);
+    let short_term: Decimal = reports.iter().map(|r| r.short_term).sum();
+    let long_term: Decimal = reports.iter().map(|r| r.long_term).sum();
     for report in reports {
-        content.push_str(&format!(
-            "{:<8} {:>14} {:>7} {:>14} {:>12}\n",
+        let row = format!(
+            "{:<8} {:>14} {:>14} {:>7} {:>14} {:>12}",
             report.symbol,
             format!("${:.2}", report.profit_loss),
+            report
+                .unrealized_pl
+                .map(|u| format!("${:.2}", u))
+                .unwrap_or_else(|| "no mark".to_string()),
             report.trade_count,
             format_position(report.net_shares),
             report
                 .break_even
                 .map(|b| format!("${:.2}", b))
                 .unwrap_or_else(|| "-".to_string()),
+        );
+        let mut row = StyledString::plain(row + "  ");
+        if let Some(trend) = trends.get(&report.symbol) {
+            let last = trend.last().copied().unwrap_or_default();
+            row.append_styled(render_sparkline(trend, SPARKLINE_MONTHS), colors.pl(last));
+        }
+        rows.add_item(row, report.symbol);
+    }
+    let db_rows = db.clone();
+    rows.set_on_submit(move |s, symbol: &String| {
+        show_symbol_trades(s, db_rows.clone(), symbol.clone())
+    });
+
+    let mut content = format!(
+        "\nShort-term realized: ${:.2}  (held a year or less)\n\
+         Long-term realized:  ${:.2}  (held more than a year)\n\
+         Trend: realized P/L to date over the last {} months with closes.\n\
+         Press Enter on a symbol to see its trades.\n",
+        short_term, long_term, SPARKLINE_MONTHS
+    );
+
+    if !roll_chains.is_empty() {
+        content.push_str(&format!(
+            "\nRoll chains\n{:<8} {:<8} {:>5} {:>14}\n",
+            "Chain", "Symbol", "Legs", "Net Credit"
         ));
+        content.push_str(&"=".repeat(38));
+        content.push('\n');
+        for chain in roll_chains {
+            content.push_str(&format!(
+                "{:<8} {:<8} {:>5} {:>14}\n",
+                format!("#{}", chain.roll_group_id),
+                chain.symbol,
+                chain.leg_count,
+                format!("${:.2}", chain.net_credit),
+            ));
+        }
     }
 
-    siv.add_layer(
-        Dialog::around(TextView::new(content))
-            .title("Profit/Loss Report by Symbol")
-            .button("Back", |s| {
+    if !covered.is_empty() {
+        content.push_str(&format!(
+            "\nCovered calls\n{:<8} {:<6} {:<8} {:>7} {:>12} {:>10} {:>10} {:>8}\n",
+            "Call", "Lot", "Symbol", "Shares", "Stock Cost", "Premium", "Return", "Return%"
+        ));
+        content.push_str(&"=".repeat(75));
+        content.push('\n');
+        for position in covered {
+            content.push_str(&format!(
+                "{:<8} {:<6} {:<8} {:>7} {:>12} {:>10} {:>10} {:>8}{}\n",
+                format!("#{}", position.call_id),
+                format!("#{}", position.stock_id),
+                position.symbol,
+                position.shares.normalize(),
+                format!("${:.2}", position.stock_cost),
+                format!("${:.2}", position.premium),
+                format!("${:.2}", position.total_return),
+                format!("{:.2}%", position.return_pct),
+                if position.called_away {
+                    " called away"
+                } else {
+                    ""
+                },
+            ));
+        }
+    }
+
+    let next_method = CostBasisMethod::variants()
+        .iter()
+        .cycle()
+        .skip_while(|m| **m != method)
+        .nth(1)
+        .copied()
+        .unwrap_or_default();
+    let title = report_title(
+        siv,
+        format!(
+            "Profit/Loss Report by Symbol ({})",
+            method.as_str().to_uppercase()
+        ),
+        &range,
+    );
+    siv.add_layer(exportable(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(header))
+                .child(rows)
+                .child(TextView::new(content))
+                .scrollable(),
+        )
+        .title(title.clone())
+        .button(
+            format!("Use {}", next_method.as_str().to_uppercase()),
+            move |s| {
+                if let Some(state) = s.user_data::<UiState>() {
+                    state.cost_basis_method = next_method;
+                }
                 s.pop_layer();
-            }),
+                show_symbol_report(s, db.clone());
+            },
+        )
+        .button("Back", |s| {
+            s.pop_layer();
+        }),
+        title,
+        table,
+    ));
+}
+
+// The trades of one symbol from the P/L by Symbol report, newest first. Esc or
+// Back returns to the report.
+fn show_symbol_trades(siv: &mut Cursive, db: Arc<Mutex<Database>>, symbol: String) {
+    let res = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_trades_by_symbol(&symbol);
+    let trades = match res {
+        Ok(trades) => trades,
+        Err(e) => {
+            show_dialog_with_back(siv, format!("Database error: {}", e));
+            return;
+        }
+    };
+
+    let now = today();
+    let content = trades
+        .iter()
+        .map(|trade| format_trade_row(trade, &now))
+        .collect::<Vec<_>>()
+        .join("\n");
+    siv.add_layer(
+        OnEventView::new(
+            Dialog::around(TextView::new(content).scrollable().scroll_x(true))
+                .title(format!("{} Trades", symbol))
+                .button("Back", |s| {
+                    s.pop_layer();
+                })
+                .max_size((90, 20)),
+        )
+        .on_event(Key::Esc, |s| {
+            s.pop_layer();
+        }),
     );
 }
 
 // Formats one row of the trade list, including option details and DTE.
+// Comma-separated tags, trimmed, without blanks or repeats (ignoring case),
+// sorted.
+fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags.sort_by_key(|tag| tag.to_lowercase());
+    tags
+}
+
 fn format_trade_row(trade: &Trade, today: &str) -> String {
+    let row = format_trade_base(trade, today);
+    if trade.tags.is_empty() {
+        row
+    } else {
+        format!("{}  tags: {}", row, trade.tags.join(", "))
+    }
+}
+
+// The trade detail view: every field, the whole comment, the trades tied to
+// it, its lot matches, and the realized P/L it accounts for.
+fn format_trade_detail(detail: &TradeDetail) -> String {
+    let trade = &detail.trade;
+    let mut lines = vec![
+        format!("Date:        {}", trade.date),
+        format!("Symbol:      {}", trade.symbol),
+        format!("Type:        {}", trade.trade_type),
+        format!("Action:      {}", trade.action),
+        format!("Quantity:    {}", trade.quantity.normalize()),
+        format!("Price:       {} {}", trade.price, trade.currency),
+        format!("Fees:        {}", trade.fees),
+        format!("Cash flow:   {:.2}", trade.cash_flow()),
+        format!(
+            "Broker:      {}",
+            or_dash(trade.broker.as_deref().unwrap_or(""))
+        ),
+    ];
+    if trade.trade_type == TradeType::Option {
+        lines.push(format!(
+            "Option:      {} {} exp {}, x{}",
+            trade.option_type.map(|t| t.to_string()).unwrap_or_default(),
+            trade.strike.map(|s| s.to_string()).unwrap_or_default(),
+            trade.expiration.as_deref().unwrap_or(""),
+            trade.multiplier().normalize()
+        ));
+        lines.push(format!(
+            "Status:      {}",
+            trade.status.as_ref().map_or("open", |s| s.as_str())
+        ));
+        let greeks = trade.greeks;
+        if !greeks.is_empty() {
+            let greek =
+                |name: &str, value: Option<Decimal>| value.map(|v| format!("{} {}", name, v));
+            let quoted: Vec<String> = [
+                greek("delta", greeks.delta),
+                greek("gamma", greeks.gamma),
+                greek("theta", greeks.theta),
+                greek("vega", greeks.vega),
+                greek("IV", greeks.iv),
+            ]
+            .into_iter()
+            .flatten()
+            .collect();
+            lines.push(format!("Greeks:      {}", quoted.join(", ")));
+        }
+    }
+    lines.push(format!("Tags:        {}", or_dash(&trade.tags.join(", "))));
+    lines.push(String::new());
+    lines.push("Comment:".to_string());
+    lines.push(or_dash(&trade.comment).to_string());
+
+    lines.push(String::new());
+    lines.push("Linked trades:".to_string());
+    if detail.linked.is_empty() {
+        lines.push("—".to_string());
+    }
+    for (how, other) in &detail.linked {
+        lines.push(format!(
+            "{:<13} #{} {} {} {} x{} @ {}",
+            how,
+            other.id.unwrap_or(0),
+            other.date,
+            other.symbol,
+            other.action,
+            other.quantity.normalize(),
+            other.price
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Lot matches:".to_string());
+    if detail.matches.is_empty() {
+        lines.push("—".to_string());
+    }
+    for lot in &detail.matches {
+        let close = lot
+            .close_id
+            .map_or("expiry/assignment".to_string(), |id| format!("#{}", id));
+        lines.push(format!(
+            "#{} {} @ {} -> {} {} @ {}  x{}  P/L {:.2}",
+            lot.open_id.unwrap_or(0),
+            lot.open_date,
+            lot.open_price,
+            close,
+            lot.close_date,
+            lot.close_price,
+            lot.quantity.normalize(),
+            lot.realized
+        ));
+    }
+    lines.push(String::new());
+    lines.push(format!("Realized P/L: {:.2}", detail.realized()));
+    lines.join("\n")
+}
+
+// The trade row without its tags.
+fn format_trade_base(trade: &Trade, today: &str) -> String {
     let base = format!(
         "#{:<4} {:<6} {:<7} {:<13} ${:<8.2} x{:<6.2} {} fee ${:.2}",
         trade.id.unwrap_or(0),
@@ -677,11 +9073,24 @@ fn format_trade_row(trade: &Trade, today: &str) -> String {
             String::new()
         };
 
-        let mut extra = format!(" [{} {} exp {} {}", option_type, strike, expiration, status);
+        let mut extra = format!(
+            " [{} {} x{} exp {} {}",
+            option_type,
+            strike,
+            trade.multiplier().normalize(),
+            expiration,
+            status
+        );
         if !dte.is_empty() {
             extra.push_str(&format!(", {}", dte));
         }
         extra.push(']');
+        if let Some(group) = trade.roll_group_id {
+            extra.push_str(&format!(" roll #{}", group));
+        }
+        if let Some(strategy) = trade.strategy_id {
+            extra.push_str(&format!(" strategy #{}", strategy));
+        }
 
         // Flag an open option whose expiration has passed.
         if status == "open" {
@@ -699,6 +9108,31 @@ fn format_trade_row(trade: &Trade, today: &str) -> String {
     }
 }
 
+// Describes what a position holds: "stock", or the option contract.
+fn format_instrument(key: &PositionKey) -> String {
+    match (key.option_type, key.strike, key.expiration.as_ref()) {
+        (Some(option_type), Some(strike), Some(expiration)) => format!(
+            "{} ${:.2} {}",
+            option_type.as_str().to_uppercase(),
+            strike,
+            expiration
+        ),
+        _ => key.trade_type.to_string(),
+    }
+}
+
+// Formats the single row that stands in for a strategy's legs.
+fn format_strategy_row(summary: &StrategySummary) -> String {
+    format!(
+        "[strategy #{}] {} ({}), {} legs, P/L ${:.2}",
+        summary.strategy.id.unwrap_or(0),
+        summary.strategy.name,
+        summary.strategy.kind,
+        summary.leg_count,
+        summary.profit_loss,
+    )
+}
+
 // Open options whose expiration has already passed.
 fn unresolved_expirations<'a>(trades: &'a [Trade], today: &str) -> Vec<&'a Trade> {
     trades
@@ -714,43 +9148,165 @@ fn unresolved_expirations<'a>(trades: &'a [Trade], today: &str) -> Vec<&'a Trade
         .collect()
 }
 
-// If any open option has passed its expiration, layer a non-blocking alert on
-// top prompting the user to resolve it (mark expired, assign, or exercise).
-fn maybe_show_expiration_alert(siv: &mut Cursive, trades: &[Trade]) {
-    let now = today();
-    let unresolved = unresolved_expirations(trades, &now);
-    if unresolved.is_empty() {
-        return;
+// If any open option has passed its expiration, lists them on top to record
+// how each ended.
+fn maybe_show_expiration_alert(siv: &mut Cursive, db: &Arc<Mutex<Database>>, trades: &[Trade]) {
+    let unresolved: Vec<Trade> = unresolved_expirations(trades, &today())
+        .into_iter()
+        .cloned()
+        .collect();
+    if !unresolved.is_empty() {
+        list_past_expirations(siv, db.clone(), unresolved);
     }
+}
+
+// The Past Expirations menu item: the open options past their expiration, or
+// a toast that there are none.
+fn show_past_expirations(siv: &mut Cursive, db: Arc<Mutex<Database>>) {
+    match load_past_expirations(&db) {
+        Ok(unresolved) if unresolved.is_empty() => {
+            show_toast(siv, "No open option is past its expiration".to_string())
+        }
+        Ok(unresolved) => list_past_expirations(siv, db, unresolved),
+        Err(e) => siv.add_layer(Dialog::info(format!("Database error: {}", e))),
+    }
+}
+
+fn load_past_expirations(db: &Arc<Mutex<Database>>) -> rusqlite::Result<Vec<Trade>> {
+    let options = db
+        .lock()
+        .expect("Failed to lock database")
+        .get_open_options_by_expiration(None, None)?;
+    Ok(unresolved_expirations(&options, &today())
+        .into_iter()
+        .cloned()
+        .collect())
+}
 
-    let mut msg = format!(
-        "{} open option(s) are past expiration and need to be resolved \
-         (mark Expired, or record Assignment/Exercise):\n\n",
+// Lists `unresolved` options to record how each ended: Enter picks expired
+// worthless or assigned (exercised, for a long option) for one, and Expire
+// All records every one as expired worthless.
+fn list_past_expirations(siv: &mut Cursive, db: Arc<Mutex<Database>>, unresolved: Vec<Trade>) {
+    let mut select = SelectView::new().h_align(HAlign::Left);
+    for trade in &unresolved {
+        select.add_item(format_trade_base(trade, &today()), trade.clone());
+    }
+    let db_one = db.clone();
+    select.set_on_submit(move |s, trade: &Trade| {
+        show_resolve_expiration(s, db_one.clone(), trade.clone())
+    });
+    let ids: Vec<i64> = unresolved.iter().filter_map(|t| t.id).collect();
+    let intro = format!(
+        "{} open option(s) are past expiration. Enter records how one ended;\n\
+         Expire All records every one as expired worthless.\n",
         unresolved.len()
     );
-    for t in unresolved {
-        msg.push_str(&format!(
-            "  #{} {} {} strike {} exp {}\n",
-            t.id.unwrap_or(0),
-            t.symbol,
-            t.option_type
-                .as_ref()
-                .map(|o| o.as_str())
-                .unwrap_or("option"),
-            t.strike.map(|s| format!("${:.2}", s)).unwrap_or_default(),
-            t.expiration.clone().unwrap_or_default(),
-        ));
-    }
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical().child(TextView::new(intro)).child(
+                select
+                    .with_name("past_expirations")
+                    .scrollable()
+                    .scroll_x(true)
+                    .max_size((100, 15)),
+            ),
+        )
+        .title("Past Expirations")
+        .button("Expire All", move |s| {
+            let (db, ids) = (db.clone(), ids.clone());
+            s.add_layer(
+                Dialog::text(format!(
+                    "Record {} option(s) as expired worthless?",
+                    ids.len()
+                ))
+                .title("Expire All")
+                .button("Expire", move |s| {
+                    let label = format!("expiry of {} options", ids.len());
+                    resolve_expirations(s, &db, label, |db| db.expire_options(&ids))
+                })
+                .button("Cancel", |s| {
+                    s.pop_layer();
+                }),
+            );
+        })
+        .button("Later", |s| {
+            s.pop_layer();
+        }),
+    );
+}
 
+// Asks how a past-expiration option ended: worthless, or assigned (short) or
+// exercised (long) into the stock at its strike.
+fn show_resolve_expiration(siv: &mut Cursive, db: Arc<Mutex<Database>>, trade: Trade) {
+    let Some(id) = trade.id else { return };
+    let (label, status, event) = if trade.action.is_buy() {
+        ("Exercised", OptionStatus::Exercised, "exercise")
+    } else {
+        ("Assigned", OptionStatus::Assigned, "assignment")
+    };
+    let db_assign = db.clone();
+    let what = describe_trade(&trade);
     siv.add_layer(
-        Dialog::around(TextView::new(msg))
-            .title("Unresolved expirations")
-            .button("OK", |s| {
-                s.pop_layer();
-            }),
+        Dialog::text(format!(
+            "How did {} end?\n\n\
+             Expired Worthless closes it with no cash flow; the premium stays as booked.\n\
+             {} records the stock trade at its strike on the expiration date.",
+            what, label
+        ))
+        .title(format!("Option #{}", id))
+        .button("Expired Worthless", move |s| {
+            let label = format!("expiry of #{}", id);
+            resolve_expirations(s, &db, label, |db| db.expire_option(id))
+        })
+        .button(label, move |s| {
+            let label = format!("{} of #{}", event, id);
+            let status = status.clone();
+            resolve_expirations(s, &db_assign, label, |db| {
+                db.assign_option(id, status).map(|_| ())
+            })
+        })
+        .button("Cancel", |s| {
+            s.pop_layer();
+        }),
     );
 }
 
+// Runs `resolve` for undo as `label`, then closes the question and the list
+// and reopens the list with the options still unresolved, or the trade list
+// beneath, which shows them itself.
+fn resolve_expirations(
+    s: &mut Cursive,
+    db: &Arc<Mutex<Database>>,
+    label: String,
+    resolve: impl FnOnce(&Database) -> rusqlite::Result<()>,
+) {
+    let res = {
+        let db = db.lock().expect("Failed to lock database");
+        db.last_history_id().and_then(|after| {
+            resolve(&db)?;
+            Ok((after, db.last_history_id()?))
+        })
+    };
+    let (after, upto) = match res {
+        Ok(ids) => ids,
+        Err(e) => {
+            show_error_toast(s, format!("Couldn't record the {}: {}", label, e));
+            return;
+        }
+    };
+    show_toast(s, format!("Recorded the {}", label));
+    push_undo(s, label, after, upto);
+    s.pop_layer();
+    s.pop_layer();
+    if is_top_layer(s, "trade_list") {
+        reopen_trade_list(s, db.clone());
+    } else if let Ok(unresolved) = load_past_expirations(db) {
+        if !unresolved.is_empty() {
+            list_past_expirations(s, db.clone(), unresolved);
+        }
+    }
+}
+
 // Formats a monetary amount for an edit field, leaving it blank for
 // non-positive values.
 fn format_amount(value: Decimal) -> String {
@@ -792,6 +9348,117 @@ fn show_dialog_with_back(siv: &mut Cursive, message: String) {
     }));
 }
 
+// The date typed into a form field, YYYY-MM-DD or a shortcut (see
+// `parse_date_input`), as YYYY-MM-DD; `None` unless it is a valid date.
+fn parse_date_field(text: &str) -> Option<String> {
+    parse_date_input(text, &today()).filter(|date| is_valid_date_format(date))
+}
+
+// A date field named `name` with a button that picks the date from a
+// calendar. Enter in the field spells out a shortcut such as `y`.
+fn date_field(name: &str, content: String, width: usize) -> LinearLayout {
+    let (field, picker) = (name.to_string(), name.to_string());
+    LinearLayout::horizontal()
+        .child(
+            EditView::new()
+                .content(content)
+                .on_submit(move |s, text| {
+                    if let Some(date) = parse_date_field(text) {
+                        s.call_on_name(&field, |v: &mut EditView| v.set_content(date));
+                    }
+                })
+                .with_name(name)
+                .word_keys()
+                .fixed_width(width),
+        )
+        .child(Button::new_raw(" ▾ ", move |s| {
+            show_calendar(s, picker.clone())
+        }))
+}
+
+// Picks the date of the field named `field` from a month calendar, starting
+// at its date or today: arrows move a day or a week, Page Up/Down a month,
+// `t` goes to today, Enter fills the field in, and Esc leaves it.
+fn show_calendar(siv: &mut Cursive, field: String) {
+    let typed = siv
+        .call_on_name(&field, |v: &mut EditView| v.get_content())
+        .unwrap_or_default();
+    let start = parse_date_field(&typed).unwrap_or_else(today);
+    let selected = Arc::new(Mutex::new(start.clone()));
+
+    let mut view = OnEventView::new(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(calendar_text(&start)).with_name("calendar"))
+                .child(TextView::new(
+                    "\n←→ day  ↑↓ week  PgUp/PgDn month\nt today  Enter pick  Esc cancel",
+                )),
+        )
+        .title("Pick a Date"),
+    );
+    type Step = fn(&str) -> Option<String>;
+    let moves: [(Event, Step); 7] = [
+        (Key::Left.into(), |date| add_days(date, -1)),
+        (Key::Right.into(), |date| add_days(date, 1)),
+        (Key::Up.into(), |date| add_days(date, -7)),
+        (Key::Down.into(), |date| add_days(date, 7)),
+        (Key::PageUp.into(), |date| add_months(date, -1)),
+        (Key::PageDown.into(), |date| add_months(date, 1)),
+        ('t'.into(), |_| Some(today())),
+    ];
+    for (event, step) in moves {
+        let selected = selected.clone();
+        view.set_on_event(event, move |s| {
+            let mut date = selected.lock().expect("Failed to lock calendar");
+            if let Some(next) = step(&date).filter(|next| is_valid_date_format(next)) {
+                *date = next;
+                s.call_on_name("calendar", |v: &mut TextView| {
+                    v.set_content(calendar_text(&date))
+                });
+            }
+        });
+    }
+    view.set_on_event(Key::Enter, move |s| {
+        let date = selected.lock().expect("Failed to lock calendar").clone();
+        s.pop_layer();
+        s.call_on_name(&field, |v: &mut EditView| v.set_content(date));
+    });
+    view.set_on_event(Key::Esc, |s| {
+        s.pop_layer();
+    });
+    siv.add_layer(view);
+}
+
+// The month of `date` as a calendar, weeks from Monday, with its day in
+// brackets.
+fn calendar_text(date: &str) -> String {
+    let (year, month, day) = match (
+        date.get(..4).and_then(|y| y.parse::<i32>().ok()),
+        date.get(5..7).and_then(|m| m.parse::<u32>().ok()),
+        date.get(8..).and_then(|d| d.parse::<u32>().ok()),
+    ) {
+        (Some(year), Some(month), Some(day)) => (year, month, day),
+        _ => return String::new(),
+    };
+    let first = weekday(&format!("{:04}-{:02}-01", year, month)).unwrap_or(0) as usize;
+    let mut text = format!(
+        "{:^28}\n",
+        format!("{} {}", MONTHS[month as usize - 1], year)
+    );
+    text += " Mo  Tu  We  Th  Fr  Sa  Su\n";
+    let mut cells = vec!["    ".to_string(); first];
+    for d in 1..=days_in_month(year, month) {
+        cells.push(if d == day {
+            format!("[{:>2}]", d)
+        } else {
+            format!(" {:>2} ", d)
+        });
+    }
+    let weeks: Vec<String> = cells.chunks(7).map(|week| week.concat()).collect();
+    text += &weeks.join("\n");
+    text
+}
+
 fn is_valid_date_format(date: &str) -> bool {
     // Check basic format: YYYY-MM-DD
     if date.len() != 10 {
@@ -835,8 +9502,53 @@ fn is_leap_year(year: i32) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cost_basis::match_lots;
     use rust_decimal_macros::dec;
 
+    #[test]
+    fn undo_closes_stale_forms_and_rebuilds_the_list() {
+        let db = Database::new(":memory:").unwrap();
+        let mut trade = Trade {
+            symbol: "KO".to_string(),
+            action: Action::BuyToOpen,
+            price: dec!(58),
+            quantity: dec!(100),
+            date: "2024-03-04".to_string(),
+            ..Default::default()
+        };
+        let id = db.add_trade(&trade).unwrap();
+        trade.id = Some(id);
+        let after = db.last_history_id().unwrap();
+        trade.price = dec!(61);
+        db.update_trade(&trade).unwrap();
+        let upto = db.last_history_id().unwrap();
+        let db = Arc::new(Mutex::new(db));
+
+        let mut siv = Cursive::new();
+        siv.set_user_data(UiState::default());
+        show_view_trades(&mut siv, db.clone());
+        push_undo(&mut siv, "edit of KO".to_string(), after, upto);
+        show_add_trade(&mut siv, db.clone(), Some(trade));
+        siv.add_layer(Dialog::info("over the form"));
+
+        undo(&mut siv, &db);
+        // The form still showing $61 is gone, and the list shows $58.
+        let form = edit_form_name(id);
+        assert!(siv.screen_mut().find_layer_from_name(&form).is_none());
+        assert_eq!(
+            siv.screen_mut().find_layer_from_name("trade_list"),
+            Some(LayerPosition::FromBack(0))
+        );
+        let row = siv
+            .call_on_name("trade_list", |v: &mut SelectView<TradeListItem>| {
+                v.get_item(0).map(|(label, _)| label.to_string())
+            })
+            .flatten()
+            .unwrap();
+        assert!(row.contains("$58.00"), "{}", row);
+        assert_eq!(siv.screen().len(), 2);
+    }
+
     #[test]
     fn accepts_valid_dates() {
         assert!(is_valid_date_format("2024-01-15"));
@@ -898,6 +9610,23 @@ mod tests {
         assert_eq!(format_position(dec!(-200.0)), "short 200");
     }
 
+    #[test]
+    fn strategy_row_summarizes_legs() {
+        let summary = StrategySummary {
+            strategy: Strategy {
+                id: Some(3),
+                name: "AAPL condor".to_string(),
+                kind: StrategyKind::IronCondor,
+            },
+            leg_count: 4,
+            profit_loss: dec!(125.5),
+        };
+        assert_eq!(
+            format_strategy_row(&summary),
+            "[strategy #3] AAPL condor (iron_condor), 4 legs, P/L $125.50"
+        );
+    }
+
     #[test]
     fn unresolved_expirations_flags_only_past_open_options() {
         let mut open_past = Trade {
@@ -923,4 +9652,188 @@ mod tests {
         assert_eq!(unresolved.len(), 1);
         assert_eq!(unresolved[0].id, Some(1));
     }
+
+    #[test]
+    fn split_ratios_parse_new_over_old() {
+        assert_eq!(parse_split_ratio("4:1"), Some(dec!(4)));
+        assert_eq!(parse_split_ratio("3:2"), Some(dec!(1.5)));
+        assert_eq!(parse_split_ratio("1:10"), Some(dec!(0.1)));
+        assert_eq!(parse_split_ratio("2"), Some(dec!(2)));
+        assert_eq!(parse_split_ratio("0:1"), None);
+        assert_eq!(parse_split_ratio("four"), None);
+    }
+
+    #[test]
+    fn help_lists_the_keymap_and_the_screen() {
+        let keymap = Config::parse("[keys]\ndown = [\"j\", \"ctrl-n\"]\n")
+            .unwrap()
+            .keys;
+        let trade_list = SCREEN_HELP
+            .iter()
+            .find(|screen| screen.view == "trade_list");
+        let text = format_help(&keymap, trade_list, false);
+        assert!(text.contains("  j, Ctrl+N        Move down"));
+        assert!(text.contains("  gg               Jump to the top"));
+        assert!(text.contains("  1-5              Sort by date, symbol, price, quantity, p/l"));
+        assert!(!text.contains("Export this report"));
+        assert!(format_help(&keymap, None, true).contains("Export this report"));
+    }
+
+    #[test]
+    fn dashboard_sums_up_the_account() {
+        let valuation = Valuation {
+            date: "2024-03-15".to_string(),
+            positions: Vec::new(),
+            market_value: dec!(1000),
+            cash: dec!(250.5),
+        };
+        let put = Trade {
+            id: Some(1),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            quantity: dec!(1),
+            price: dec!(2.5),
+            date: "2024-03-01".to_string(),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(150)),
+            expiration: Some("2024-03-22".to_string()),
+            status: Some(OptionStatus::Open),
+            ..Default::default()
+        };
+        let trades = [put];
+        let text = format_dashboard(&valuation, dec!(-42), &trades, &trades, "2024-03-15");
+        assert!(text.contains("Account value:     1250.50"));
+        assert!(text.contains("Open positions:    0"));
+        assert!(text.contains("Realized P/L (2024-03): -42.00"));
+        assert!(text.contains("2024-03-22 AAPL   put 150 x1 (7 days)"));
+        assert!(text.contains("2024-03-01 AAPL   sell_to_open 1 @ 2.5"));
+    }
+
+    #[test]
+    fn trade_detail_shows_the_whole_comment_and_links() {
+        let trade = Trade {
+            id: Some(2),
+            symbol: "AAPL".to_string(),
+            action: Action::SellToClose,
+            price: dec!(110),
+            quantity: dec!(10),
+            date: "2024-02-01".to_string(),
+            comment: "Sold into the earnings gap; the whole note is kept".to_string(),
+            ..Default::default()
+        };
+        let opened = Trade {
+            id: Some(1),
+            action: Action::BuyToOpen,
+            price: dec!(100),
+            date: "2024-01-02".to_string(),
+            ..trade.clone()
+        };
+        let matches = match_lots(&[opened.clone(), trade.clone()], CostBasisMethod::Fifo, &[]);
+        let detail = TradeDetail {
+            trade,
+            linked: vec![("roll", opened)],
+            matches,
+        };
+        let text = format_trade_detail(&detail);
+        assert!(text.contains("Sold into the earnings gap; the whole note is kept"));
+        assert!(text.contains("roll          #1 2024-01-02 AAPL buy_to_open x10 @ 100"));
+        assert!(text.contains("#1 2024-01-02 @ 100 -> #2 2024-02-01 @ 110  x10  P/L 100.00"));
+        assert!(text.ends_with("Realized P/L: 100.00"));
+    }
+
+    #[test]
+    fn calendar_marks_the_day_in_its_month() {
+        let text = calendar_text("2024-02-14");
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0].trim(), "Feb 2024");
+        // February 2024 starts on a Thursday and has 29 days.
+        assert_eq!(lines[2], "              1   2   3   4 ");
+        assert_eq!(lines[4], " 12  13 [14] 15  16  17  18 ");
+        assert_eq!(lines.last().unwrap().trim_end(), " 26  27  28  29");
+        assert_eq!(parse_date_field("2024-02-30"), None);
+        assert_eq!(parse_date_field("1899-12-31"), None);
+    }
+
+    #[test]
+    fn moves_a_word_at_a_time() {
+        let text = "Sold at 2.50, gap-up";
+        assert_eq!(word_left(text, text.len()), 18);
+        assert_eq!(word_left(text, 18), 14);
+        assert_eq!(word_left(text, 12), 10);
+        assert_eq!(word_left(text, 5), 0);
+        assert_eq!(word_left(text, 0), 0);
+        assert_eq!(word_right(text, 0), 4);
+        assert_eq!(word_right(text, 4), 7);
+        assert_eq!(word_right(text, 13), 17);
+        assert_eq!(word_right(text, 17), text.len());
+        assert_eq!(word_right(text, text.len()), text.len());
+        assert_eq!(word_left("café au", 5), 0);
+    }
+
+    #[test]
+    fn trade_list_fits_the_terminal() {
+        assert_eq!(trade_list_height(24), 15);
+        assert_eq!(trade_list_height(10), 5);
+        assert_eq!(trade_list_height(500), TRADE_PAGE_SIZE);
+        assert_eq!(trade_detail_width(120), None);
+        assert_eq!(trade_detail_width(150), Some(50));
+        assert_eq!(trade_detail_width(250), Some(80));
+        assert_eq!(trade_position(2, 100, 0, 0, 0), "Row 3 of 100");
+        assert_eq!(trade_position(0, 40, 3, 3, 0), "Row 1 of 40 · page 4 of 4");
+        assert_eq!(trade_position(0, 0, 0, 0, 0), "Row 0 of 0");
+        assert_eq!(
+            trade_position(4, 100, 0, 1, 3),
+            "Row 5 of 100 · page 1 of 2 · 3 selected"
+        );
+        let wide = TradeListItem::Trade(Box::default());
+        let row = mark_row(&"x".repeat(120), &wide, true, &Theme::default(), "");
+        assert_eq!(row.source().chars().count(), TRADE_ROW_WIDTH);
+        assert!(row.source().starts_with("● x") && row.source().ends_with('…'));
+    }
+
+    #[test]
+    fn e_steps_through_expirations_soonest_first() {
+        let dtes = [None, Some(12), Some(-2), Some(3), None, Some(3), Some(0)];
+        assert_eq!(next_expiring(&dtes, 0), Some(6));
+        assert_eq!(next_expiring(&dtes, 6), Some(3));
+        assert_eq!(next_expiring(&dtes, 3), Some(5));
+        assert_eq!(next_expiring(&dtes, 5), Some(1));
+        assert_eq!(next_expiring(&dtes, 1), Some(6));
+        // Past expirations are left to their color.
+        assert_eq!(next_expiring(&dtes, 2), Some(6));
+        assert_eq!(next_expiring(&[None, Some(-1)], 0), None);
+    }
+
+    #[test]
+    fn duplicates_keep_the_fields_but_not_the_ties() {
+        let put = Trade {
+            id: Some(12),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Option,
+            action: Action::SellToOpen,
+            price: dec!(2.10),
+            date: "2024-05-01".to_string(),
+            option_type: Some(OptionType::Put),
+            strike: Some(dec!(180)),
+            expiration: Some("2024-06-21".to_string()),
+            status: Some(OptionStatus::Assigned),
+            roll_group_id: Some(3),
+            strategy_id: Some(4),
+            account_id: Some(2),
+            tags: vec!["wheel".to_string()],
+            ..Default::default()
+        };
+        let copy = duplicate_trade(&put, "2024-06-03");
+        assert_eq!((copy.id, copy.date.as_str()), (None, "2024-06-03"));
+        assert_eq!(
+            (copy.status, copy.roll_group_id, copy.strategy_id),
+            (None, None, None)
+        );
+        assert_eq!(
+            (copy.strike, copy.price, copy.account_id),
+            (put.strike, put.price, put.account_id)
+        );
+        assert_eq!(copy.tags, put.tags);
+    }
 }
@@ -0,0 +1,184 @@
+//! Mark-to-market valuation of the portfolio.
+//!
+//! Every open position is valued at its mark (see [`Quote`]); a position with
+//! no mark is carried at its cost basis so the total stays meaningful. Short
+//! positions count negatively. Cash is the net cash flow of every trade, so
+//! the account value — cash plus market value — starts from zero and tracks
+//! total trading P/L; adding the cash ledger's balance (see
+//! [`crate::cash::CashSummary::ledger`]) makes it the account's real value.
+
+use crate::db::{Quote, Trade, ValuationSnapshot};
+use crate::positions::{open_positions, Position};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// One position and what it is worth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionValue {
+    pub position: Position,
+    /// Mark per share, or `None` when the position is carried at cost.
+    pub mark: Option<Decimal>,
+    /// Signed market value: negative for a short.
+    pub market_value: Decimal,
+}
+
+/// The portfolio valued on one date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Valuation {
+    pub date: String,
+    pub positions: Vec<PositionValue>,
+    pub market_value: Decimal,
+    pub cash: Decimal,
+}
+
+impl Valuation {
+    pub fn account_value(&self) -> Decimal {
+        self.cash + self.market_value
+    }
+
+    /// Positions carried at cost for lack of a mark.
+    pub fn unpriced(&self) -> usize {
+        self.positions.iter().filter(|p| p.mark.is_none()).count()
+    }
+
+    pub fn snapshot(&self) -> ValuationSnapshot {
+        ValuationSnapshot {
+            date: self.date.clone(),
+            market_value: self.market_value,
+            cash: self.cash,
+            account_value: self.account_value(),
+        }
+    }
+}
+
+/// Values `trades` on `today` (ISO `YYYY-MM-DD`) at the marks in `quotes`.
+pub fn value_portfolio(
+    trades: &[Trade],
+    quotes: &HashMap<String, Quote>,
+    today: &str,
+) -> Valuation {
+    let positions: Vec<PositionValue> = open_positions(trades, today)
+        .into_iter()
+        .map(|position| {
+            let mark = quotes
+                .get(&position.key.instrument())
+                .map(|quote| quote.price);
+            let per_share = mark.unwrap_or(position.average_cost);
+            PositionValue {
+                market_value: per_share * position.quantity * position.multiplier,
+                mark,
+                position,
+            }
+        })
+        .collect();
+    Valuation {
+        date: today.to_string(),
+        market_value: positions.iter().map(|p| p.market_value).sum(),
+        cash: trades.iter().map(Trade::cash_flow).sum(),
+        positions,
+    }
+}
+
+/// Change in account value from the latest snapshot dated before `date`, as
+/// `(amount, percent)`. The percent is `None` when the earlier value was zero.
+pub fn day_change(
+    snapshots: &[ValuationSnapshot],
+    date: &str,
+    account_value: Decimal,
+) -> Option<(Decimal, Option<Decimal>)> {
+    let previous = snapshots
+        .iter()
+        .filter(|s| s.date.as_str() < date)
+        .max_by(|a, b| a.date.cmp(&b.date))?;
+    let change = account_value - previous.account_value;
+    let percent = (previous.account_value != Decimal::ZERO)
+        .then(|| change / previous.account_value.abs() * Decimal::ONE_HUNDRED);
+    Some((change, percent))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Action, OptionStatus, OptionType, TradeType};
+    use rust_decimal_macros::dec;
+
+    fn quote(instrument: &str, price: Decimal) -> (String, Quote) {
+        (
+            instrument.to_string(),
+            Quote {
+                instrument: instrument.to_string(),
+                price,
+                fetched_at: "2024-06-03 15:00:00".to_string(),
+                source: "manual".to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn values_marked_positions_and_carries_the_rest_at_cost() {
+        let trades = vec![
+            Trade {
+                id: Some(1),
+                symbol: "AAPL".to_string(),
+                action: Action::BuyToOpen,
+                price: dec!(180),
+                quantity: dec!(100),
+                date: "2024-05-01".to_string(),
+                ..Default::default()
+            },
+            Trade {
+                id: Some(2),
+                symbol: "AAPL".to_string(),
+                trade_type: TradeType::Option,
+                action: Action::SellToOpen,
+                price: dec!(2),
+                quantity: dec!(1),
+                date: "2024-05-01".to_string(),
+                option_type: Some(OptionType::Call),
+                strike: Some(dec!(190)),
+                expiration: Some("2024-06-21".to_string()),
+                status: Some(OptionStatus::Open),
+                ..Default::default()
+            },
+            Trade {
+                id: Some(3),
+                symbol: "MSFT".to_string(),
+                action: Action::BuyToOpen,
+                price: dec!(400),
+                quantity: dec!(10),
+                date: "2024-05-02".to_string(),
+                ..Default::default()
+            },
+        ];
+        let quotes = HashMap::from([
+            quote("AAPL", dec!(185)),
+            quote("AAPL240621C00190000", dec!(1.5)),
+        ]);
+        let valuation = value_portfolio(&trades, &quotes, "2024-06-03");
+        // 18,500 of stock, -150 for the short call, MSFT at its 4,000 cost.
+        assert_eq!(valuation.market_value, dec!(22350));
+        assert_eq!(valuation.cash, dec!(-21800));
+        assert_eq!(valuation.account_value(), dec!(550));
+        assert_eq!(valuation.unpriced(), 1);
+    }
+
+    #[test]
+    fn day_change_uses_latest_earlier_snapshot() {
+        let snapshot = |date: &str, value: Decimal| ValuationSnapshot {
+            date: date.to_string(),
+            market_value: value,
+            cash: Decimal::ZERO,
+            account_value: value,
+        };
+        let snapshots = vec![
+            snapshot("2024-06-01", dec!(1000)),
+            snapshot("2024-06-02", dec!(800)),
+            snapshot("2024-06-03", dec!(900)),
+        ];
+        assert_eq!(
+            day_change(&snapshots, "2024-06-03", dec!(900)),
+            Some((dec!(100), Some(dec!(12.5))))
+        );
+        assert_eq!(day_change(&snapshots, "2024-06-01", dec!(1000)), None);
+    }
+}
@@ -0,0 +1,95 @@
+//! Checks on a trade before it is saved.
+
+use crate::db::{Action, Database, OptionType, Trade, TradeType};
+use rust_decimal::Decimal;
+
+/// Likely mistakes in `trade`, about to be saved, as (title, message): a
+/// covered call written below the break-even, or a close of more than is
+/// open. They don't stop the save; the trade form asks to confirm and the
+/// `add` command wants `--force`.
+pub fn trade_warnings(db: &Database, trade: &Trade) -> Vec<(&'static str, String)> {
+    let mut warnings = Vec::new();
+
+    // Covered-call warning: writing a call below the underlying's break-even
+    // would lock in a loss if assigned.
+    if trade.action == Action::SellToOpen
+        && trade.trade_type == TradeType::Option
+        && trade.option_type == Some(OptionType::Call)
+    {
+        // Exclude the option being edited so its pre-edit premium doesn't skew
+        // the threshold (no-op for a brand-new trade, whose id is None).
+        let break_even = db
+            .get_break_even_excluding(&trade.symbol, trade.id)
+            .ok()
+            .flatten();
+        if let (Some(be), Some(strike)) = (break_even, trade.strike) {
+            if strike < be {
+                warnings.push((
+                    "Covered call below break-even",
+                    format!(
+                        "Warning: strike ${:.2} is below the {} break-even of ${:.2}. \
+                         If assigned, this covered call locks in a loss.",
+                        strike, trade.symbol, be
+                    ),
+                ));
+            }
+        }
+    }
+
+    // A close pairs with opens of the same stock or contract; closing more than
+    // is open usually means a typo or a missing opening trade.
+    if !trade.action.is_open() {
+        let short = trade.action.is_buy();
+        let open = db
+            .open_quantity(&trade.position_key(), short, trade.id)
+            .unwrap_or(Decimal::ZERO);
+        if trade.quantity > open {
+            warnings.push((
+                "Closing more than is open",
+                format!(
+                    "Warning: this {} closes {} but only {} {} open on the {} side.",
+                    trade.action,
+                    trade.quantity.normalize(),
+                    open.max(Decimal::ZERO).normalize(),
+                    if trade.trade_type == TradeType::Option {
+                        "contract(s) of this option are"
+                    } else {
+                        "share(s) are"
+                    },
+                    if short { "short" } else { "long" },
+                ),
+            ));
+        }
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn warns_of_closing_more_than_is_open() {
+        let db = Database::new(":memory:").unwrap();
+        let buy = Trade {
+            symbol: "KO".to_string(),
+            action: Action::BuyToOpen,
+            quantity: dec!(100),
+            price: dec!(58),
+            date: "2024-03-04".to_string(),
+            ..Default::default()
+        };
+        db.add_trade(&buy).unwrap();
+        let sell = |quantity| Trade {
+            action: Action::SellToClose,
+            quantity,
+            ..buy.clone()
+        };
+        assert!(trade_warnings(&db, &sell(dec!(100))).is_empty());
+        let warnings = trade_warnings(&db, &sell(dec!(150)));
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, "Closing more than is open");
+    }
+}
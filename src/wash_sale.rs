@@ -0,0 +1,192 @@
+//! Wash-sale detection (US tax rules).
+//!
+//! A sale at a loss is a wash sale when substantially identical shares are
+//! bought within 30 days before or after it. The loss is disallowed for as
+//! many shares as were repurchased and added to the cost basis of the
+//! replacement shares instead. "Substantially identical" is taken to mean the
+//! same stock or the same option contract, in any account, and only long lots
+//! are checked.
+//!
+//! The pass runs over the [`LotMatch`]es produced by the cost-basis engine.
+//! Losses are handled in the order they were realized, and each replacement
+//! share absorbs at most one wash.
+
+use crate::cost_basis::{unit_price, LotMatch};
+use crate::date::parse_unix_day;
+use crate::db::{Action, PositionKey, Trade};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Days on either side of a loss sale within which a purchase triggers a wash.
+pub const WASH_WINDOW_DAYS: i64 = 30;
+
+/// One loss sale (or part of one) washed by a replacement purchase.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WashSale {
+    pub key: PositionKey,
+    pub close_id: Option<i64>,
+    pub open_id: Option<i64>,
+    pub close_date: String,
+    /// Shares or contracts of the sale covered by the replacement.
+    pub quantity: Decimal,
+    /// Loss realized on those shares (negative).
+    pub loss: Decimal,
+    /// Portion of the loss that may not be deducted (positive).
+    pub disallowed: Decimal,
+    pub replacement_id: Option<i64>,
+    pub replacement_date: String,
+    /// Per-share basis of the replacement shares after adding the disallowed
+    /// loss.
+    pub adjusted_basis: Decimal,
+}
+
+/// Flags every long lot in `matches` closed at a loss and repurchased within
+/// [`WASH_WINDOW_DAYS`] of the sale.
+pub fn wash_sales(trades: &[Trade], matches: &[LotMatch]) -> Vec<WashSale> {
+    let mut losses: Vec<&LotMatch> = matches
+        .iter()
+        .filter(|m| !m.short && m.close_id.is_some() && m.realized < Decimal::ZERO)
+        .collect();
+    losses.sort_by(|a, b| (&a.close_date, a.close_id).cmp(&(&b.close_date, b.close_id)));
+
+    let mut purchases: Vec<&Trade> = trades
+        .iter()
+        .filter(|t| t.action == Action::BuyToOpen)
+        .collect();
+    purchases.sort_by(|a, b| (&a.date, a.id).cmp(&(&b.date, b.id)));
+    // Replacement shares already used up by an earlier wash.
+    let mut absorbed: HashMap<Option<i64>, Decimal> = HashMap::new();
+
+    let mut washes = Vec::new();
+    for loss in losses {
+        let sale_day = match parse_unix_day(&loss.close_date) {
+            Some(day) => day,
+            None => continue,
+        };
+        // Lots sold by this same trade are being disposed of, not bought back.
+        let sold_lots: Vec<Option<i64>> = matches
+            .iter()
+            .filter(|m| m.close_id == loss.close_id)
+            .map(|m| m.open_id)
+            .collect();
+        let loss_per_unit = loss.realized / loss.quantity;
+        let mut remaining = loss.quantity;
+
+        for purchase in &purchases {
+            if remaining == Decimal::ZERO {
+                break;
+            }
+            if !purchase.position_key().same_instrument(&loss.key)
+                || sold_lots.contains(&purchase.id)
+            {
+                continue;
+            }
+            let in_window = parse_unix_day(&purchase.date)
+                .map(|day| (day - sale_day).abs() <= WASH_WINDOW_DAYS)
+                .unwrap_or(false);
+            if !in_window {
+                continue;
+            }
+            let used = absorbed.entry(purchase.id).or_insert(Decimal::ZERO);
+            let quantity = remaining.min(purchase.quantity - *used);
+            if quantity <= Decimal::ZERO {
+                continue;
+            }
+            *used += quantity;
+            remaining -= quantity;
+
+            let disallowed = -loss_per_unit * quantity;
+            washes.push(WashSale {
+                key: loss.key.clone(),
+                close_id: loss.close_id,
+                open_id: loss.open_id,
+                close_date: loss.close_date.clone(),
+                quantity,
+                loss: loss_per_unit * quantity,
+                disallowed,
+                replacement_id: purchase.id,
+                replacement_date: purchase.date.clone(),
+                adjusted_basis: unit_price(purchase)
+                    + disallowed / (quantity * purchase.multiplier()),
+            });
+        }
+    }
+    washes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cost_basis::{match_lots, CostBasisMethod};
+    use crate::db::TradeType;
+    use rust_decimal_macros::dec;
+
+    fn stock(id: i64, date: &str, action: Action, price: Decimal, quantity: Decimal) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            trade_type: TradeType::Stock,
+            action,
+            price,
+            quantity,
+            date: date.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn washes(trades: &[Trade]) -> Vec<WashSale> {
+        wash_sales(trades, &match_lots(trades, CostBasisMethod::Fifo, &[]))
+    }
+
+    #[test]
+    fn repurchase_within_window_disallows_loss() {
+        let trades = vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-03-01", Action::SellToClose, dec!(90), dec!(10)),
+            stock(3, "2024-03-15", Action::BuyToOpen, dec!(92), dec!(6)),
+        ];
+        let washes = washes(&trades);
+        assert_eq!(washes.len(), 1);
+        let wash = &washes[0];
+        assert_eq!(wash.close_id, Some(2));
+        assert_eq!(wash.replacement_id, Some(3));
+        // Only the 6 repurchased shares are washed: $60 of the $100 loss.
+        assert_eq!(wash.quantity, dec!(6));
+        assert_eq!(wash.disallowed, dec!(60));
+        assert_eq!(wash.adjusted_basis, dec!(102));
+    }
+
+    #[test]
+    fn purchase_before_sale_counts_but_not_outside_window_or_gains() {
+        let trades = vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-02-20", Action::BuyToOpen, dec!(95), dec!(10)),
+            // FIFO sells lot 1 at a loss; lot 2 was bought 10 days earlier.
+            stock(3, "2024-03-01", Action::SellToClose, dec!(90), dec!(10)),
+            stock(4, "2024-06-01", Action::BuyToOpen, dec!(80), dec!(10)),
+            stock(5, "2024-06-05", Action::SellToClose, dec!(99), dec!(10)),
+        ];
+        let washes = washes(&trades);
+        assert_eq!(washes.len(), 1);
+        assert_eq!(washes[0].replacement_id, Some(2));
+        assert_eq!(washes[0].disallowed, dec!(100));
+    }
+
+    #[test]
+    fn each_replacement_share_absorbs_one_wash() {
+        let trades = vec![
+            stock(1, "2024-01-02", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(2, "2024-02-15", Action::BuyToOpen, dec!(100), dec!(10)),
+            stock(3, "2024-03-01", Action::SellToClose, dec!(90), dec!(10)),
+            stock(4, "2024-03-02", Action::SellToClose, dec!(90), dec!(10)),
+            stock(5, "2024-03-10", Action::BuyToOpen, dec!(91), dec!(10)),
+        ];
+        let washes = washes(&trades);
+        // Lot 2 is sold by trade 4 a day after trade 3; it replaces trade 3's
+        // shares first, and the new purchase then covers trade 4.
+        assert_eq!(washes.len(), 2);
+        assert_eq!(washes[0].replacement_id, Some(2));
+        assert_eq!(washes[1].close_id, Some(4));
+        assert_eq!(washes[1].replacement_id, Some(5));
+    }
+}
@@ -0,0 +1,272 @@
+//! Wheel-strategy cycles.
+//!
+//! A wheel cycle on a symbol starts with a cash-secured put (a sold put),
+//! continues through any further puts, the assignment that delivers shares, and
+//! the covered calls written against them, and ends when the shares are called
+//! away (or otherwise sold) and the position is flat again. Trades outside a
+//! cycle — stock bought before the first put, say — are not part of any cycle.
+
+use crate::db::{Action, OptionType, Trade, TradeType};
+use rust_decimal::Decimal;
+
+/// One pass around the wheel on a single symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WheelCycle {
+    pub symbol: String,
+    pub start_date: String,
+    /// Date the shares went flat again; `None` while the cycle is running.
+    pub end_date: Option<String>,
+    pub trade_ids: Vec<i64>,
+    pub puts_sold: i32,
+    pub calls_sold: i32,
+    /// Net option cash flow of the cycle (premium collected minus buybacks
+    /// and fees).
+    pub premium_collected: Decimal,
+    /// Per-share cost of the shares acquired, reduced by the premium collected:
+    /// `(stock bought - premium) / shares bought`. `None` if no shares were
+    /// ever delivered.
+    pub effective_cost_basis: Option<Decimal>,
+    /// Sum of every cash flow in the cycle. Once the cycle has ended this is
+    /// its realized return; while it runs it still carries the cost of the
+    /// shares held.
+    pub total_return: Decimal,
+}
+
+impl WheelCycle {
+    pub fn is_complete(&self) -> bool {
+        self.end_date.is_some()
+    }
+}
+
+/// Splits `trades` into wheel cycles, ordered by symbol then start date.
+pub fn wheel_cycles(trades: &[Trade]) -> Vec<WheelCycle> {
+    let mut sorted: Vec<&Trade> = trades.iter().collect();
+    sorted.sort_by(|a, b| (&a.symbol, &a.date, a.id).cmp(&(&b.symbol, &b.date, b.id)));
+
+    let mut cycles = Vec::new();
+    let mut current: Option<CycleBuilder> = None;
+    for trade in sorted {
+        if let Some(builder) = current.as_ref() {
+            if builder.cycle.symbol != trade.symbol {
+                cycles.push(current.take().expect("cycle in progress").finish());
+            }
+        }
+
+        match current.as_mut() {
+            Some(builder) => {
+                builder.add(trade);
+                if builder.is_flat_after_shares() {
+                    let mut builder = current.take().expect("cycle in progress");
+                    builder.cycle.end_date = Some(trade.date.clone());
+                    cycles.push(builder.finish());
+                }
+            }
+            None if is_cash_secured_put(trade) => {
+                let mut builder = CycleBuilder::new(trade);
+                builder.add(trade);
+                current = Some(builder);
+            }
+            None => {}
+        }
+    }
+    if let Some(builder) = current {
+        cycles.push(builder.finish());
+    }
+    cycles
+}
+
+fn is_cash_secured_put(trade: &Trade) -> bool {
+    trade.trade_type == TradeType::Option
+        && trade.option_type == Some(OptionType::Put)
+        && trade.action == Action::SellToOpen
+}
+
+// Accumulates one cycle's trades until the shares go flat.
+struct CycleBuilder {
+    cycle: WheelCycle,
+    net_shares: Decimal,
+    shares_bought: Decimal,
+    stock_bought: Decimal,
+    held_shares: bool,
+}
+
+impl CycleBuilder {
+    fn new(first: &Trade) -> Self {
+        CycleBuilder {
+            cycle: WheelCycle {
+                symbol: first.symbol.clone(),
+                start_date: first.date.clone(),
+                end_date: None,
+                trade_ids: Vec::new(),
+                puts_sold: 0,
+                calls_sold: 0,
+                premium_collected: Decimal::ZERO,
+                effective_cost_basis: None,
+                total_return: Decimal::ZERO,
+            },
+            net_shares: Decimal::ZERO,
+            shares_bought: Decimal::ZERO,
+            stock_bought: Decimal::ZERO,
+            held_shares: false,
+        }
+    }
+
+    fn add(&mut self, trade: &Trade) {
+        if let Some(id) = trade.id {
+            self.cycle.trade_ids.push(id);
+        }
+        self.cycle.total_return += trade.cash_flow();
+        match trade.trade_type {
+            TradeType::Option => {
+                self.cycle.premium_collected += trade.cash_flow();
+                if trade.action == Action::SellToOpen {
+                    match trade.option_type {
+                        Some(OptionType::Put) => self.cycle.puts_sold += 1,
+                        Some(OptionType::Call) => self.cycle.calls_sold += 1,
+                        None => {}
+                    }
+                }
+            }
+            TradeType::Stock => {
+                self.net_shares += trade.signed_shares();
+                if trade.action.is_buy() {
+                    self.shares_bought += trade.quantity;
+                    self.stock_bought -= trade.cash_flow();
+                }
+                if self.net_shares > Decimal::ZERO {
+                    self.held_shares = true;
+                }
+            }
+        }
+    }
+
+    fn is_flat_after_shares(&self) -> bool {
+        self.held_shares && self.net_shares <= Decimal::ZERO
+    }
+
+    fn finish(mut self) -> WheelCycle {
+        if self.shares_bought > Decimal::ZERO {
+            self.cycle.effective_cost_basis =
+                Some((self.stock_bought - self.cycle.premium_collected) / self.shares_bought);
+        }
+        self.cycle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::OptionStatus;
+    use rust_decimal_macros::dec;
+
+    fn trade(
+        id: i64,
+        date: &str,
+        trade_type: TradeType,
+        action: Action,
+        option_type: Option<OptionType>,
+        price: Decimal,
+        quantity: Decimal,
+    ) -> Trade {
+        Trade {
+            id: Some(id),
+            symbol: "AAPL".to_string(),
+            trade_type,
+            action,
+            price,
+            quantity,
+            date: date.to_string(),
+            option_type,
+            status: option_type.map(|_| OptionStatus::Closed),
+            ..Default::default()
+        }
+    }
+
+    fn put(id: i64, date: &str, premium: Decimal) -> Trade {
+        trade(
+            id,
+            date,
+            TradeType::Option,
+            Action::SellToOpen,
+            Some(OptionType::Put),
+            premium,
+            dec!(1),
+        )
+    }
+
+    fn call(id: i64, date: &str, premium: Decimal) -> Trade {
+        trade(
+            id,
+            date,
+            TradeType::Option,
+            Action::SellToOpen,
+            Some(OptionType::Call),
+            premium,
+            dec!(1),
+        )
+    }
+
+    fn shares(id: i64, date: &str, action: Action, price: Decimal) -> Trade {
+        trade(id, date, TradeType::Stock, action, None, price, dec!(100))
+    }
+
+    #[test]
+    fn full_cycle_from_put_to_called_away() {
+        let trades = vec![
+            put(1, "2024-01-05", dec!(2)),
+            put(2, "2024-01-19", dec!(1.5)),
+            shares(3, "2024-01-19", Action::BuyToOpen, dec!(100)),
+            call(4, "2024-02-02", dec!(1)),
+            shares(5, "2024-02-16", Action::SellToOpen, dec!(105)),
+        ];
+        let cycles = wheel_cycles(&trades);
+        assert_eq!(cycles.len(), 1);
+        let cycle = &cycles[0];
+        assert!(cycle.is_complete());
+        assert_eq!(cycle.start_date, "2024-01-05");
+        assert_eq!(cycle.end_date.as_deref(), Some("2024-02-16"));
+        assert_eq!(cycle.puts_sold, 2);
+        assert_eq!(cycle.calls_sold, 1);
+        assert_eq!(cycle.premium_collected, dec!(450));
+        // 100 shares at $100 less $450 premium → $95.50 per share.
+        assert_eq!(cycle.effective_cost_basis, Some(dec!(95.5)));
+        // Premium + $500 stock gain.
+        assert_eq!(cycle.total_return, dec!(950));
+    }
+
+    #[test]
+    fn running_cycle_and_trades_outside_cycles() {
+        let trades = vec![
+            // Stock bought before any put is not part of a cycle.
+            shares(1, "2023-12-01", Action::BuyToOpen, dec!(90)),
+            shares(2, "2023-12-15", Action::SellToClose, dec!(95)),
+            put(3, "2024-01-05", dec!(2)),
+            shares(4, "2024-01-19", Action::BuyToOpen, dec!(100)),
+        ];
+        let cycles = wheel_cycles(&trades);
+        assert_eq!(cycles.len(), 1);
+        assert!(!cycles[0].is_complete());
+        assert_eq!(cycles[0].trade_ids, vec![3, 4]);
+        assert_eq!(cycles[0].effective_cost_basis, Some(dec!(98)));
+    }
+
+    #[test]
+    fn consecutive_cycles_and_symbols_are_separate() {
+        let mut msft = put(7, "2024-01-01", dec!(3));
+        msft.symbol = "MSFT".to_string();
+        let trades = vec![
+            put(1, "2024-01-05", dec!(2)),
+            shares(2, "2024-01-19", Action::BuyToOpen, dec!(100)),
+            shares(3, "2024-02-16", Action::SellToOpen, dec!(105)),
+            put(4, "2024-03-01", dec!(1)),
+            msft,
+        ];
+        let cycles = wheel_cycles(&trades);
+        assert_eq!(cycles.len(), 3);
+        assert_eq!(cycles[0].symbol, "AAPL");
+        assert!(cycles[0].is_complete());
+        assert_eq!(cycles[1].trade_ids, vec![4]);
+        assert_eq!(cycles[1].effective_cost_basis, None);
+        assert_eq!(cycles[2].symbol, "MSFT");
+    }
+}